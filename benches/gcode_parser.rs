@@ -0,0 +1,52 @@
+// benches/gcode_parser.rs - Line framing throughput, kept vs dropped comments
+//
+// 10,000 numbered, checksummed lines with slicer comments, framed with
+// comments kept and checksums verified as when debugging, and with both
+// dropped. Run with `cargo bench --bench gcode_parser`.
+use std::hint::black_box;
+use criterion::{Criterion, criterion_group, criterion_main};
+use krusty_rs::gcode::parser::{GCodeParser, GCodeParserConfig};
+
+const LINES: usize = 10_000;
+
+fn sliced_file() -> Vec<String> {
+    let mut line_number = 0;
+    (0..LINES)
+        .map(|i| {
+            if i % 5 == 0 {
+                return format!(";LAYER:{}", i / 5);
+            }
+            let (x, y, e) = (i as f64 * 0.1, i as f64 * 0.2, i as f64 * 0.01);
+            let body = match i % 5 {
+                1 => format!("N{} G1 X{:.3} Y{:.3} E{:.5} ; perimeter", line_number, x, y, e),
+                _ => format!("N{} G1 X{:.3} Y{:.3} E{:.5}", line_number, x, y, e),
+            };
+            line_number += 1;
+            let checksum = body.bytes().fold(0u8, |acc, byte| acc ^ byte);
+            format!("{}*{}", body, checksum)
+        })
+        .collect()
+}
+
+fn parse_all(lines: &[String], config: &GCodeParserConfig) -> usize {
+    let mut parser = GCodeParser::new(config.clone());
+    lines.iter().filter(|line| matches!(parser.parse_line(line), Ok(Some(_)))).count()
+}
+
+fn throughput(c: &mut Criterion) {
+    let lines = sliced_file();
+    let debugging = GCodeParserConfig {
+        enable_checksums: true,
+        keep_comments: true,
+        ..GCodeParserConfig::default()
+    };
+    let production = GCodeParserConfig::default();
+
+    let mut group = c.benchmark_group("gcode_parse_10k_lines");
+    group.bench_function("comments_and_checksums", |b| b.iter(|| black_box(parse_all(&lines, &debugging))));
+    group.bench_function("stripped", |b| b.iter(|| black_box(parse_all(&lines, &production))));
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+// benches/segment_pool.rs - Segment allocation latency, global allocator vs pool
+//
+// The planning loop budget is about 1 µs per segment; run with
+// `cargo bench --bench segment_pool`.
+use std::hint::black_box;
+use criterion::{Criterion, criterion_group, criterion_main};
+use krusty_rs::motion::{MotionSegment, MotionType, SegmentPool};
+
+fn fill(segment: &mut MotionSegment, i: f64) {
+    segment.target = [i, i, 0.0, 0.0];
+    segment.feedrate = 100.0;
+    segment.distance = i;
+    segment.motion_type = MotionType::Print;
+}
+
+fn allocation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("segment_allocation");
+
+    group.bench_function("box", |b| {
+        let mut i = 0.0;
+        b.iter(|| {
+            let mut segment = Box::<MotionSegment>::default();
+            fill(&mut segment, i);
+            i += 1.0;
+            black_box(segment)
+        })
+    });
+
+    let pool = SegmentPool::new(32);
+    group.bench_function("pool", |b| {
+        let mut i = 0.0;
+        b.iter(|| {
+            let mut segment = pool.alloc();
+            fill(&mut segment, i);
+            i += 1.0;
+            black_box(segment)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, allocation);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+// benches/sim_accuracy.rs - How closely the simulator follows printer physics
+//
+// Each scenario is checked against an analytical reference and the run
+// fails if any falls outside its tolerance. Run with
+// `cargo bench --bench sim_accuracy`.
+use krusty_rs::simulator::{AccuracyScenarioKind, SimConfig, SimulationAccuracyBenchmark};
+
+/// 200 W heater on a block of 20 J/K losing 0.4 W/K: 10 °C/s, tau = 50 s
+const HEATER_WATTS: f64 = 200.0;
+const HEAT_CAPACITY: f64 = 20.0;
+const LOSS_COEFFICIENT: f64 = 0.4;
+const AMBIENT: f64 = 25.0;
+
+/// Heating from ambient: t = -tau * ln(1 - (T - Ta) / (P / k))
+const HEAT_UP_200_SECS: f64 = 21.539146;
+const HEAT_UP_250_SECS: f64 = 29.891850;
+/// T(30 s) = Ta + P / k * (1 - e^(-30 / tau))
+const TEMP_AFTER_30S_FULL: f64 = 250.594182;
+/// Steady state at half power: Ta + 0.5 * P / k
+const STEADY_STATE_HALF: f64 = 275.0;
+/// Newton's law of cooling: T(t) = Ta + (T0 - Ta) * e^(-t / tau)
+const COOL_200_FOR_60S: f64 = 77.708987;
+const COOL_250_FOR_30S: f64 = 148.482618;
+/// 12345 steps at 80 steps/mm
+const X_AFTER_12345_STEPS: f64 = 154.3125;
+/// 500 moves of 7 steps at 400 steps/mm
+const Z_AFTER_500_MOVES: f64 = 8.75;
+
+const THERMAL_TOLERANCE_PCT: f64 = 5.0;
+/// Positions are exact up to floating point rounding
+const POSITION_TOLERANCE_PCT: f64 = 1e-9;
+
+#[tokio::main]
+async fn main() {
+    let config = SimConfig { ambient_temperature: AMBIENT, ..SimConfig::default() }
+        .with_heater(HEATER_WATTS, HEAT_CAPACITY, LOSS_COEFFICIENT);
+    let mut benchmark = SimulationAccuracyBenchmark::new(config);
+    benchmark.add_scenario(
+        "heat_up_200c",
+        AccuracyScenarioKind::HeatUpTime { target: 200.0, max_secs: 60.0 },
+        HEAT_UP_200_SECS,
+        THERMAL_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "heat_up_250c",
+        AccuracyScenarioKind::HeatUpTime { target: 250.0, max_secs: 60.0 },
+        HEAT_UP_250_SECS,
+        THERMAL_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "full_power_30s",
+        AccuracyScenarioKind::TemperatureAfter { power: 1.0, secs: 30.0 },
+        TEMP_AFTER_30S_FULL,
+        THERMAL_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "steady_state_half_power",
+        AccuracyScenarioKind::TemperatureAfter { power: 0.5, secs: 1000.0 },
+        STEADY_STATE_HALF,
+        THERMAL_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "cool_200c_60s",
+        AccuracyScenarioKind::CoolingAfter { start: 200.0, secs: 60.0 },
+        COOL_200_FOR_60S,
+        THERMAL_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "cool_250c_30s",
+        AccuracyScenarioKind::CoolingAfter { start: 250.0, secs: 30.0 },
+        COOL_250_FOR_30S,
+        THERMAL_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "x_12345_steps",
+        AccuracyScenarioKind::PositionAfterSteps { axis: 0, steps: 12345, moves: 1 },
+        X_AFTER_12345_STEPS,
+        POSITION_TOLERANCE_PCT,
+    );
+    benchmark.add_scenario(
+        "z_500_small_moves",
+        AccuracyScenarioKind::PositionAfterSteps { axis: 2, steps: 7, moves: 500 },
+        Z_AFTER_500_MOVES,
+        POSITION_TOLERANCE_PCT,
+    );
+
+    let results = benchmark.run().await.expect("accuracy scenario failed to run");
+    println!("{:<26} {:>12} {:>12} {:>10}", "scenario", "simulated", "expected", "deviation");
+    for result in &results {
+        println!(
+            "{:<26} {:>12.4} {:>12.4} {:>9.3}% {}",
+            result.name,
+            result.simulated,
+            result.expected,
+            result.deviation_pct,
+            if result.passed { "ok" } else { "FAILED" }
+        );
+    }
+
+    let failed = results.iter().filter(|result| !result.passed).count();
+    if failed > 0 {
+        eprintln!("{} of {} accuracy scenarios outside tolerance", failed, results.len());
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,41 @@
+// benches/sim_threads.rs - Event loop throughput, single-threaded vs worker threads
+//
+// 10,000 events of steps, heater changes and endstop checks. Run with
+// `cargo bench --bench sim_threads`.
+use std::hint::black_box;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use krusty_rs::simulator::{SimConfig, SimEvent, SimEventPayload, SimEventType, Simulator, StepCommand};
+
+const EVENTS: usize = 10_000;
+
+fn loaded_simulator(threads: usize) -> Simulator {
+    let mut sim = Simulator::new(SimConfig { threads, ..SimConfig::default() });
+    sim.schedule(SimEvent::new(0.0, SimEventPayload::PositionUpdate([100.0, 100.0, 10.0, 0.0])));
+    for i in 1..EVENTS {
+        let time = i as f64 * 0.01;
+        let event = match i % 10 {
+            0 => SimEvent::new(time, SimEventPayload::HeaterTarget { target: 150.0 + (i % 7) as f64 * 10.0 }),
+            5 => SimEvent::signal(time, SimEventType::EndstopCheck),
+            n => SimEvent::new(time, SimEventPayload::Step(StepCommand { axis: n % 4, steps: if i % 3 == 0 { -40 } else { 40 } })),
+        };
+        sim.schedule(event);
+    }
+    sim
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sim_10k_events");
+    for threads in [1, 3] {
+        group.bench_function(format!("threads_{}", threads), |b| {
+            b.iter_batched(
+                || loaded_simulator(threads),
+                |mut sim| black_box(sim.run_event_loop()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
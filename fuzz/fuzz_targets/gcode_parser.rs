@@ -0,0 +1,38 @@
+// fuzz/fuzz_targets/gcode_parser.rs - Arbitrary bytes through the streaming G-code parser
+#![no_main]
+
+use std::sync::OnceLock;
+use krusty_rs::gcode::parser::{AsyncGCodeParser, ErrorRecovery, GCodeParserConfig, parse_words};
+use libfuzzer_sys::fuzz_target;
+use tokio::runtime::Runtime;
+
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Builder::new_current_thread().build().unwrap())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data);
+    for enable_checksums in [false, true] {
+        for error_recovery in [ErrorRecovery::Abort, ErrorRecovery::SkipLine, ErrorRecovery::SkipToNextN] {
+            let config = GCodeParserConfig {
+                enable_checksums,
+                error_recovery,
+                max_consecutive_errors: usize::MAX,
+                ..GCodeParserConfig::default()
+            };
+            runtime().block_on(async {
+                let mut parser = AsyncGCodeParser::new(input.as_bytes(), config);
+                while let Some(result) = parser.next_command().await {
+                    match result {
+                        Ok(line) => {
+                            let _ = parse_words(&line.command);
+                        }
+                        Err(_) if error_recovery == ErrorRecovery::Abort => break,
+                        Err(_) => {}
+                    }
+                }
+            });
+        }
+    }
+});
@@ -1,6 +1,16 @@
 // src/config.rs - Single configuration file
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use crate::gcode::meta::GCodeMetaPattern;
+use crate::temperature::SensorFusionMode;
+use crate::web::auth::AuthPermission;
+use std::collections::{HashMap, HashSet};
+
+pub mod legacy;
+pub mod profiles;
+pub mod validation;
+
+pub use profiles::{ConfigError, ConfigManager};
+pub use validation::{ConfigErrorSeverity, ConfigValidationError};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -13,14 +23,137 @@ pub struct Config {
     #[serde(default)]
     pub extruder: ExtruderConfig,
     
+    /// Extruders for T1 onwards; `extruder` is T0
+    #[serde(default)]
+    pub extruders: Vec<ExtruderConfig>,
+    
     #[serde(default)]
     pub heater_bed: HeaterBedConfig,
     
     #[serde(default)]
     pub steppers: HashMap<String, StepperConfig>,
+    
+    #[serde(default)]
+    pub scara: Option<ScaraConfig>,
+    
+    #[serde(default)]
+    pub delta: Option<DeltaConfig>,
+    
+    #[serde(default)]
+    pub hangprinter: Option<HangprinterConfig>,
+    
+    #[serde(default)]
+    pub fan: FanConfig,
+    
+    #[serde(default)]
+    pub chamber: Option<ChamberConfig>,
+    
+    #[serde(default)]
+    pub web: WebConfig,
+    
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    
+    #[serde(default)]
+    pub probe: Option<ProbeConfig>,
+    
+    #[serde(default)]
+    pub bltouch: Option<BLTouchConfig>,
+    
+    #[serde(default)]
+    pub filament_sensor: Option<FilamentSensorConfig>,
+    
+    #[serde(default)]
+    pub post_print: Option<PostPrintConfig>,
+    
+    #[serde(default)]
+    pub stall_detection: Option<StallDetectionConfig>,
+    
+    #[serde(default)]
+    pub clog_detection: Option<ClogDetectionConfig>,
+
+    /// Slicer comments to read layer and progress hints from, tried before the built-in ones
+    #[serde(default)]
+    pub gcode_meta_patterns: Vec<GCodeMetaPattern>,
+
+    #[serde(default)]
+    pub advanced: AdvancedConfig,
+
+    /// Profile from the `profiles/` directory next to this file to start with
+    #[serde(default)]
+    pub default_profile: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+impl Config {
+    /// Extruder for each tool, starting with T0
+    pub fn tools(&self) -> impl Iterator<Item = &ExtruderConfig> {
+        std::iter::once(&self.extruder).chain(&self.extruders)
+    }
+
+    /// Stepper driving X, Y or Z; delta towers A, B and C stand in for them
+    pub fn axis_stepper(&self, axis: usize) -> Option<&StepperConfig> {
+        let names = [["stepper_x", "stepper_a"], ["stepper_y", "stepper_b"], ["stepper_z", "stepper_c"]].get(axis)?;
+        names.iter().find_map(|name| self.steppers.get(*name))
+    }
+
+    /// Sections that differ between `old` and `new`
+    pub fn diff(old: &Config, new: &Config) -> ConfigDiff {
+        let steppers_changed = old
+            .steppers
+            .keys()
+            .chain(new.steppers.keys())
+            .filter(|name| old.steppers.get(*name) != new.steppers.get(*name))
+            .cloned()
+            .collect();
+        let mut fans_changed = HashSet::new();
+        if old.fan != new.fan {
+            fans_changed.insert("fan".to_string());
+        }
+        ConfigDiff {
+            printer_changed: old.printer != new.printer,
+            mcu_changed: old.mcu != new.mcu,
+            motion_changed: crate::motion::MotionConfig::new_from_printer_config(old)
+                != crate::motion::MotionConfig::new_from_printer_config(new),
+            extruder_changed: old.tools().ne(new.tools()),
+            heater_bed_changed: old.heater_bed != new.heater_bed,
+            steppers_changed,
+            fans_changed,
+        }
+    }
+}
+
+/// What changed between two configurations, see `Config::diff`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigDiff {
+    pub printer_changed: bool,
+    pub mcu_changed: bool,
+    /// Anything the motion planner is configured from
+    pub motion_changed: bool,
+    /// Any tool's extruder
+    pub extruder_changed: bool,
+    pub heater_bed_changed: bool,
+    /// Steppers added, removed or changed
+    pub steppers_changed: HashSet<String>,
+    pub fans_changed: HashSet<String>,
+}
+
+impl ConfigDiff {
+    /// Whether the MCU connection has to be set up again
+    pub fn requires_hardware_restart(&self) -> bool {
+        self.mcu_changed
+    }
+
+    /// Whether the motion planner has to be rebuilt
+    pub fn requires_motion_replan(&self) -> bool {
+        self.motion_changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct PrinterConfig {
     #[serde(default = "default_kinematics")]
     pub kinematics: String,
@@ -36,16 +169,110 @@ pub struct PrinterConfig {
     
     #[serde(default = "default_max_z_accel")]
     pub max_z_accel: f64,
+    
+    #[serde(default)]
+    pub z_hop_height: f64,
+    
+    #[serde(default = "default_z_hop_speed")]
+    pub z_hop_speed: f64,
+
+    /// Executed G-code commands kept for `/api/gcode/history`
+    #[serde(default = "default_gcode_history_size")]
+    pub gcode_history_size: usize,
+
+    /// How far M92 may move steps/mm from the configured value, as a factor
+    #[serde(default = "default_steps_per_mm_max_ratio")]
+    pub steps_per_mm_max_ratio: f64,
+
+    /// Where settings changed at runtime (e.g. by M92) are persisted
+    #[serde(default)]
+    pub settings_file: Option<String>,
+
+    /// Corner of the wipe brush or pad where G12 starts (mm)
+    #[serde(default)]
+    pub nozzle_wipe_x_start: f64,
+    #[serde(default)]
+    pub nozzle_wipe_y_start: f64,
+
+    /// Length of the wipe area along X (mm)
+    #[serde(default = "default_nozzle_wipe_length_mm")]
+    pub nozzle_wipe_length_mm: f64,
+
+    /// Depth of the wipe area along Y, used by the spiral and zigzag (mm)
+    #[serde(default = "default_nozzle_wipe_width_mm")]
+    pub nozzle_wipe_width_mm: f64,
+
+    /// G12 refuses to wipe a nozzle colder than this (°C)
+    #[serde(default = "default_nozzle_wipe_min_temp")]
+    pub nozzle_wipe_min_temp: f64,
+
+    /// Slow down layers that would print faster than this (seconds, 0 to disable)
+    #[serde(default)]
+    pub min_layer_time_secs: f64,
+
+    /// Part fan speed while a layer is slowed down for cooling (percent)
+    #[serde(default = "default_max_layer_fan_speed_pct")]
+    pub max_layer_fan_speed_pct: f64,
+
+    /// Longest M400 waits for queued moves to finish (seconds, 0 for no limit)
+    #[serde(default = "default_queue_drain_timeout_secs")]
+    pub queue_drain_timeout_secs: f64,
+
+    /// Lower the current of the less loaded CoreXY motor on XY moves
+    #[serde(default)]
+    pub corexy_current_balance: bool,
+
+    /// How far an idle CoreXY motor's current drops, as a fraction of its
+    /// run current (0.5 halves it)
+    #[serde(default = "default_motor_current_balance")]
+    pub motor_current_balance: f64,
+
+    /// Commands run once the hardware is initialized, e.g. homing
+    #[serde(default)]
+    pub startup_gcode: Vec<String>,
+
+    /// Start without running `startup_gcode`, e.g. on a bench without motors
+    #[serde(default)]
+    pub skip_startup_gcode: bool,
+
+    /// Commands run before the hardware shuts down
+    #[serde(default)]
+    pub shutdown_gcode: Vec<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct McuConfig {
     pub serial: String,
     #[serde(default = "default_baud")]
     pub baud: u32,
+    /// Oldest MCU firmware version accepted, e.g. "v0.12.0"
+    #[serde(default)]
+    pub min_version: Option<String>,
+    #[serde(default)]
+    pub restart_method: RestartMethod,
+    /// `dfu-util` executable used to flash firmware, found on PATH if unset
+    #[serde(default)]
+    pub dfu_util_path: Option<String>,
+    /// Ports opened to the MCU; above 1 for MCUs that accept commands on
+    /// several ports at once
+    #[serde(default = "default_mcu_connections")]
+    pub connections: usize,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+/// How to reset an MCU that stopped responding
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartMethod {
+    /// Send the firmware's `reset` command
+    #[default]
+    Command,
+    /// Power-cycle the USB port of a Raspberry Pi
+    RpiUsb,
+    /// Pulse DTR, like the Arduino bootloader expects
+    Arduino,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct ExtruderConfig {
     pub step_pin: String,
     pub dir_pin: String,
@@ -60,9 +287,73 @@ pub struct ExtruderConfig {
     pub nozzle_diameter: f64,
     #[serde(default = "default_filament_diameter")]
     pub filament_diameter: f64,
+    #[serde(default = "default_sensor_type")]
+    pub sensor_type: String,
+    #[serde(default = "default_pullup_resistor")]
+    pub pullup_resistor: f64,
+    #[serde(default = "default_pid_kp")]
+    pub pid_kp: f64,
+    #[serde(default = "default_pid_ki")]
+    pub pid_ki: f64,
+    #[serde(default = "default_pid_kd")]
+    pub pid_kd: f64,
+    #[serde(default)]
+    pub integral_min: f64,
+    #[serde(default = "default_integral_max")]
+    pub integral_max: f64,
+    #[serde(default = "default_derivative_filter_cutoff")]
+    pub derivative_filter_cutoff: f64,
+    #[serde(default)]
+    pub output_min: f64,
+    #[serde(default = "default_output_max")]
+    pub output_max: f64,
+    /// Prints refuse to start with the hotend below this (°C)
+    #[serde(default = "default_min_extrude_temp")]
+    pub min_extrude_temp: f64,
+    /// Most plastic the hotend can melt (mm³/s); 0 for no limit
+    #[serde(default)]
+    pub max_volumetric_speed_mm3_per_s: f64,
+    /// Temperature to drop to while another tool is printing (°C)
+    #[serde(default)]
+    pub standby_temp: Option<f64>,
+    /// How long after a tool change the idle extruder drops to standby
+    #[serde(default)]
+    pub standby_delay_secs: f64,
+    /// Filament feed encoder, counting in extruder steps
+    #[serde(default)]
+    pub extruder_encoder_pin: Option<String>,
+    /// Filament the encoder may fall behind the commanded steps (mm)
+    #[serde(default = "default_sync_threshold_mm")]
+    pub sync_threshold_mm: f64,
+    /// Command the missing steps before the next move on a de-sync
+    #[serde(default)]
+    pub sync_compensate: bool,
+    /// More thermistors in the hotend, read alongside its own
+    #[serde(default)]
+    pub extra_sensors: Vec<ExtraSensorConfig>,
+    /// How the hotend's thermistor readings combine
+    #[serde(default)]
+    pub sensor_fusion: SensorFusionMode,
+    /// Spread between thermistor readings that counts as a sensor fault (°C)
+    #[serde(default = "default_sensor_divergence_threshold_deg")]
+    pub sensor_divergence_threshold_deg: f64,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+/// A redundant thermistor in a heater zone
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExtraSensorConfig {
+    pub sensor_pin: String,
+}
+
+impl ExtruderConfig {
+    /// Steps per mm of filament, through the gear ratio if there is one
+    pub fn steps_per_mm(&self) -> f64 {
+        let gear_ratio = self.gear_ratio.map_or(1.0, |(driven, driving)| driven / driving);
+        (default_full_steps_per_rotation() * self.microsteps) as f64 * gear_ratio / self.rotation_distance
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct HeaterBedConfig {
     pub heater_pin: String,
     pub sensor_type: String,
@@ -73,7 +364,119 @@ pub struct HeaterBedConfig {
     pub max_temp: f64,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChamberConfig {
+    pub heater_pin: String,
+    pub sensor_type: String,
+    pub sensor_pin: String,
+    #[serde(default = "default_min_temp")]
+    pub min_temp: f64,
+    #[serde(default = "default_chamber_max_temp")]
+    pub max_temp: f64,
+    #[serde(default = "default_output_max")]
+    pub max_power: f64,
+    #[serde(default = "default_chamber_pid_kp")]
+    pub pid_kp: f64,
+    #[serde(default = "default_chamber_pid_ki")]
+    pub pid_ki: f64,
+    #[serde(default)]
+    pub pid_kd: f64,
+    /// Pullup between the sensor pin and the ADC reference (Ω)
+    #[serde(default = "default_pullup_resistor")]
+    pub pullup_resistor: f64,
+    /// How long M191 waits for the chamber to reach its target (s)
+    #[serde(default = "default_chamber_wait_timeout_secs")]
+    pub wait_timeout_secs: f64,
+}
+
+/// Z probe
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ProbeConfig {
+    /// Nozzle height when the probe triggers (mm); unset until calibrated
+    #[serde(default)]
+    pub z_offset: Option<f64>,
+}
+
+/// BLTouch or 3DTouch probe: a servo-driven pin with its own endstop output
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BLTouchConfig {
+    /// PWM pin driving the probe's servo
+    pub servo_pin: String,
+    /// Pin the probe's endstop signal is wired to
+    pub endstop_pin: String,
+    /// Nozzle height when the probe triggers (mm)
+    #[serde(default)]
+    pub z_offset: f64,
+    /// Z lowered between endstop checks while probing (mm)
+    #[serde(default = "default_probe_step")]
+    pub probe_step: f64,
+    /// Probing speed (mm/s)
+    #[serde(default = "default_probe_speed")]
+    pub speed: f64,
+    /// Give up if nothing is touched above this Z (mm)
+    #[serde(default = "default_probe_min_z")]
+    pub min_z: f64,
+    /// Time the pin takes to deploy or stow (seconds)
+    #[serde(default = "default_pin_move_time")]
+    pub pin_move_time: f64,
+    /// Opposite corners of the G29 grid [X, Y] (mm)
+    #[serde(default = "default_mesh_min")]
+    pub mesh_min: [f64; 2],
+    #[serde(default = "default_mesh_max")]
+    pub mesh_max: [f64; 2],
+    /// G29 points along X and Y
+    #[serde(default = "default_mesh_count")]
+    pub mesh_count: [usize; 2],
+}
+
+/// What to do when a stepper driver reports a stall
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StallRecovery {
+    /// Rehome the stalled axis and carry on printing
+    Resume,
+    /// Wait for the print to be resumed, then rehome and carry on
+    #[default]
+    Pause,
+    /// Fail the print
+    Abort,
+}
+
+/// Stall detection through the steppers' `diag_pin`s
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StallDetectionConfig {
+    #[serde(default)]
+    pub recovery: StallRecovery,
+    /// Minimum time between DIAG pin checks while printing (ms)
+    #[serde(default = "default_stall_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+/// Clog detection from the extruder falling behind the planned filament
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClogDetectionConfig {
+    /// Shortfall that counts as a clog (mm)
+    #[serde(default = "default_clog_threshold_mm")]
+    pub clog_threshold_mm: f64,
+    /// Longest time the shortfall is added up over (seconds)
+    #[serde(default = "default_clog_window_secs")]
+    pub clog_window_secs: f64,
+    /// Most step generations kept in the window
+    #[serde(default = "default_clog_window_samples")]
+    pub window_samples: usize,
+    /// Reverse-purge clogged tools unless turned off with M104 T<n> R0
+    #[serde(default = "default_true")]
+    pub recovery: bool,
+}
+
+/// Sensor reporting how much filament is left on the spool
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FilamentSensorConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct StepperConfig {
     pub step_pin: String,
     pub dir_pin: String,
@@ -84,6 +487,319 @@ pub struct StepperConfig {
     pub microsteps: u32,
     #[serde(default = "default_full_steps_per_rotation")]
     pub full_steps_per_rotation: u32,
+    /// TMC driver DIAG output, for stall detection
+    #[serde(default)]
+    pub diag_pin: Option<String>,
+    /// TMC driver RMS current while moving (mA)
+    #[serde(default = "default_run_current_ma")]
+    pub run_current_ma: u32,
+    /// Encoder on the motor shaft, for catching lost steps
+    #[serde(default)]
+    pub encoder: Option<StepEncoder>,
+    /// Encoder drift reported as an error rather than corrected (steps)
+    #[serde(default = "default_max_drift_steps")]
+    pub max_drift_steps: u32,
+}
+
+/// Rotary encoder read back to check a stepper's position
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StepEncoder {
+    pub counts_per_rev: u32,
+    pub pin: String,
+}
+
+impl StepperConfig {
+    pub fn steps_per_mm(&self) -> f64 {
+        (self.full_steps_per_rotation * self.microsteps) as f64 / self.rotation_distance
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScaraConfig {
+    pub arm1_length: f64,
+    pub arm2_length: f64,
+    #[serde(default = "default_scara_steps_per_deg")]
+    pub arm1_steps_per_deg: f64,
+    #[serde(default = "default_scara_steps_per_deg")]
+    pub arm2_steps_per_deg: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeltaConfig {
+    pub diagonal_rod: f64,
+    pub radius: f64,
+    pub print_radius: f64,
+    #[serde(default)]
+    pub endstop_corrections: [f64; 3],
+    #[serde(default)]
+    pub tower_angle_corrections: [f64; 3],
+}
+
+/// Hangprinter anchors and the line encoders CALIBRATE_HANGPRINTER measures with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HangprinterConfig {
+    /// Cable attachment points [A, B, C, D] relative to the homed origin (mm)
+    pub anchors: [[f64; 3]; 4],
+    /// Encoder pin of each line, in anchor order
+    #[serde(default)]
+    pub encoder_pins: Vec<String>,
+    #[serde(default = "default_encoder_counts_per_mm")]
+    pub encoder_counts_per_mm: f64,
+    /// Radius of the ring of calibration points around the origin (mm)
+    #[serde(default = "default_hangprinter_calibration_radius")]
+    pub calibration_radius: f64,
+    /// Height of every other calibration point above the origin (mm)
+    #[serde(default = "default_hangprinter_calibration_height")]
+    pub calibration_height: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct FanCurvePoint {
+    pub temperature: f64,
+    pub speed_pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FanConfig {
+    /// Hotend temperature to speed points; without any, the fan only
+    /// follows M106/M107
+    #[serde(default)]
+    pub curve: Vec<FanCurvePoint>,
+    #[serde(default)]
+    pub hysteresis_deg: f64,
+    /// Whether M106 S sets the PWM duty or a tachometer RPM to hold
+    #[serde(default)]
+    pub fan_control_mode: FanControlMode,
+    /// Fraction of the target RPM the fan may be off by before the duty is
+    /// corrected
+    #[serde(default = "default_fan_rpm_tolerance")]
+    pub fan_rpm_tolerance: f32,
+    /// Rated RPM at full duty, used to scale RPM errors into duty changes
+    #[serde(default = "default_fan_max_rpm")]
+    pub max_rpm: f64,
+    /// Duty change per change in RPM error, as a fraction of `max_rpm`
+    #[serde(default = "default_fan_rpm_kp")]
+    pub rpm_kp: f64,
+    /// Duty change per RPM error as a fraction of `max_rpm`, each tachometer
+    /// reading
+    #[serde(default = "default_fan_rpm_ki")]
+    pub rpm_ki: f64,
+}
+
+impl Default for FanConfig {
+    fn default() -> Self {
+        Self {
+            curve: Vec::new(),
+            hysteresis_deg: 0.0,
+            fan_control_mode: FanControlMode::default(),
+            fan_rpm_tolerance: default_fan_rpm_tolerance(),
+            max_rpm: default_fan_max_rpm(),
+            rpm_kp: default_fan_rpm_kp(),
+            rpm_ki: default_fan_rpm_ki(),
+        }
+    }
+}
+
+/// What the M106 S parameter controls
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FanControlMode {
+    /// PWM duty, 0-255
+    #[default]
+    Pwm,
+    /// Tachometer RPM, held by adjusting the duty
+    Rpm,
+}
+
+/// Settings most printers leave alone
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdvancedConfig {
+    /// JSON file holding the input shaper preset library; presets are
+    /// only kept in memory when unset
+    #[serde(default)]
+    pub shaper_presets_file: Option<String>,
+    /// Where M926 trajectory recordings are saved
+    #[serde(default = "default_trajectory_dir")]
+    pub trajectory_dir: String,
+    /// Time between trajectory samples (ms)
+    #[serde(default = "default_trajectory_sample_interval_ms")]
+    pub trajectory_sample_interval_ms: f64,
+    /// JSONL file printer events are logged to; nothing is logged when unset
+    #[serde(default)]
+    pub event_log_file: Option<String>,
+    /// Size at which the event log is compressed and a new one started
+    #[serde(default = "default_event_log_max_bytes")]
+    pub event_log_max_bytes: u64,
+    /// JSON file the motion error predictor's learned model is saved to
+    /// and restored from on startup
+    #[serde(default)]
+    pub predictor_model_path: Option<String>,
+}
+
+impl Default for AdvancedConfig {
+    fn default() -> Self {
+        Self {
+            shaper_presets_file: None,
+            trajectory_dir: default_trajectory_dir(),
+            trajectory_sample_interval_ms: default_trajectory_sample_interval_ms(),
+            event_log_file: None,
+            event_log_max_bytes: default_event_log_max_bytes(),
+            predictor_model_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    #[serde(default)]
+    pub prometheus_enabled: bool,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Key clients must send as `X-Api-Key`; unset leaves the API open
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Where uploaded G-code files are stored
+    #[serde(default = "default_upload_dir")]
+    pub upload_dir: String,
+    /// Accounts allowed to log in; none leaves the API open
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+    /// Key for signing access tokens; random per start when unset
+    #[serde(default)]
+    pub jwt_secret: Option<String>,
+    /// Access token lifetime
+    #[serde(default = "default_token_lifetime_secs")]
+    pub token_lifetime_secs: u64,
+    #[serde(default = "default_refresh_token_lifetime_secs")]
+    pub refresh_token_lifetime_secs: u64,
+    /// Requests per minute from one IP without a valid token; 0 disables
+    #[serde(default = "default_rate_limit_per_ip_per_min")]
+    pub rate_limit_per_ip_per_min: u32,
+    /// Requests per minute per authenticated user; 0 disables
+    #[serde(default = "default_rate_limit_per_user_per_min")]
+    pub rate_limit_per_user_per_min: u32,
+    /// G-code executions per minute per user; 0 disables
+    #[serde(default = "default_rate_limit_gcode_per_user_per_min")]
+    pub rate_limit_gcode_per_user_per_min: u32,
+    /// Take the client IP from `X-Forwarded-For`; only enable behind a
+    /// reverse proxy that sets it
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+    /// Trusted proxies in front of the server, each appending the address
+    /// it saw to `X-Forwarded-For`; the client is that many entries from
+    /// the right, and anything further left can be forged
+    #[serde(default = "default_trusted_proxy_hops")]
+    pub trusted_proxy_hops: u32,
+    /// Updates per second on `/api/position/stream`, at most 200
+    #[serde(default = "default_position_stream_hz")]
+    pub position_stream_hz: u32,
+    /// Samples per second kept for `/api/temperature/history`
+    #[serde(default = "default_temperature_history_hz")]
+    pub temperature_history_hz: f64,
+    /// How far back `/api/temperature/history` goes (seconds)
+    #[serde(default = "default_history_retention_secs")]
+    pub history_retention_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserConfig {
+    pub username: String,
+    /// Argon2 hash of the password as a PHC string
+    /// (`$argon2id$v=19$...`), printed by `printer-host --hash-password`
+    pub password_hash: String,
+    #[serde(default)]
+    pub role: AuthPermission,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Event types to send (e.g. "print_completed"); empty sends all
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Key for the HMAC-SHA256 `X-Krusty-Signature` header
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_webhook_retry_count")]
+    pub retry_count: u32,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: default_bind_address(),
+            prometheus_enabled: false,
+            webhooks: Vec::new(),
+            api_key: None,
+            upload_dir: default_upload_dir(),
+            users: Vec::new(),
+            jwt_secret: None,
+            token_lifetime_secs: default_token_lifetime_secs(),
+            refresh_token_lifetime_secs: default_refresh_token_lifetime_secs(),
+            rate_limit_per_ip_per_min: default_rate_limit_per_ip_per_min(),
+            rate_limit_per_user_per_min: default_rate_limit_per_user_per_min(),
+            rate_limit_gcode_per_user_per_min: default_rate_limit_gcode_per_user_per_min(),
+            trust_forwarded_for: false,
+            trusted_proxy_hops: default_trusted_proxy_hops(),
+            position_stream_hz: default_position_stream_hz(),
+            temperature_history_hz: default_temperature_history_hz(),
+            history_retention_secs: default_history_retention_secs(),
+        }
+    }
+}
+
+/// What to do once a print's last line has run
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PostPrintConfig {
+    /// Filament pulled back from the nozzle (mm)
+    #[serde(default = "default_post_print_retract_mm")]
+    pub retract_mm: f64,
+    /// Where to park the toolhead; it stays put unless both are set
+    #[serde(default)]
+    pub park_x: Option<f64>,
+    #[serde(default)]
+    pub park_y: Option<f64>,
+    /// Part fan runs until the hotend is below this (°C)
+    #[serde(default = "default_fan_cooldown_temp")]
+    pub fan_cooldown_temp: f64,
+    #[serde(default = "default_true")]
+    pub disable_motors_after_cooldown: bool,
+    #[serde(default)]
+    pub beep: bool,
+}
+
+impl Default for PostPrintConfig {
+    fn default() -> Self {
+        Self {
+            retract_mm: default_post_print_retract_mm(),
+            park_x: None,
+            park_y: None,
+            fan_cooldown_temp: default_fan_cooldown_temp(),
+            disable_motors_after_cooldown: true,
+            beep: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MqttConfig {
+    /// e.g. "mqtt://localhost:1883"
+    pub broker_url: String,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+    #[serde(default = "default_mqtt_publish_interval_ms")]
+    pub publish_interval_ms: u64,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
 // Default value functions
@@ -92,17 +808,123 @@ fn default_max_velocity() -> f64 { 300.0 }
 fn default_max_accel() -> f64 { 3000.0 }
 fn default_max_z_velocity() -> f64 { 25.0 }
 fn default_max_z_accel() -> f64 { 100.0 }
+fn default_z_hop_speed() -> f64 { 10.0 }
 fn default_baud() -> u32 { 250000 }
+fn default_mcu_connections() -> usize { 1 }
 fn default_rotation_distance() -> f64 { 22.67895 }
 fn default_microsteps() -> u32 { 16 }
 fn default_full_steps_per_rotation() -> u32 { 200 }
+fn default_run_current_ma() -> u32 { 800 }
+fn default_max_drift_steps() -> u32 { 16 }
 fn default_nozzle_diameter() -> f64 { 0.4 }
 fn default_filament_diameter() -> f64 { 1.75 }
+fn default_sensor_type() -> String { "EPCOS 100K B57560G104F".to_string() }
+fn default_pullup_resistor() -> f64 { 4700.0 }
+fn default_pid_kp() -> f64 { 0.087 }
+fn default_pid_ki() -> f64 { 0.0042 }
+fn default_pid_kd() -> f64 { 0.447 }
+fn default_integral_max() -> f64 { 250.0 }
+fn default_derivative_filter_cutoff() -> f64 { 0.5 }
+fn default_output_max() -> f64 { 1.0 }
+fn default_min_extrude_temp() -> f64 { 170.0 }
+fn default_true() -> bool { true }
+fn default_clog_threshold_mm() -> f64 { 2.0 }
+fn default_clog_window_secs() -> f64 { 10.0 }
+fn default_clog_window_samples() -> usize { 50 }
+fn default_encoder_counts_per_mm() -> f64 { 100.0 }
+fn default_hangprinter_calibration_radius() -> f64 { 300.0 }
+fn default_hangprinter_calibration_height() -> f64 { 300.0 }
+fn default_fan_rpm_tolerance() -> f32 { 0.1 }
+fn default_fan_max_rpm() -> f64 { 5000.0 }
+fn default_fan_rpm_kp() -> f64 { 0.2 }
+fn default_fan_rpm_ki() -> f64 { 0.5 }
+fn default_chamber_max_temp() -> f64 { 70.0 }
+fn default_chamber_pid_kp() -> f64 { 0.3 }
+fn default_chamber_pid_ki() -> f64 { 0.002 }
+fn default_chamber_wait_timeout_secs() -> f64 { 1800.0 }
 fn default_min_temp() -> f64 { 0.0 }
 fn default_max_temp() -> f64 { 250.0 }
+fn default_scara_steps_per_deg() -> f64 { 200.0 * 16.0 / 360.0 }
+fn default_bind_address() -> String { "127.0.0.1:8080".to_string() }
+fn default_webhook_retry_count() -> u32 { 3 }
+fn default_upload_dir() -> String { "gcodes".to_string() }
+fn default_token_lifetime_secs() -> u64 { 15 * 60 }
+fn default_refresh_token_lifetime_secs() -> u64 { 30 * 24 * 60 * 60 }
+fn default_steps_per_mm_max_ratio() -> f64 { 5.0 }
+fn default_nozzle_wipe_length_mm() -> f64 { 20.0 }
+fn default_nozzle_wipe_width_mm() -> f64 { 5.0 }
+fn default_nozzle_wipe_min_temp() -> f64 { 170.0 }
+fn default_max_layer_fan_speed_pct() -> f64 { 100.0 }
+fn default_queue_drain_timeout_secs() -> f64 { 600.0 }
+fn default_motor_current_balance() -> f64 { 0.5 }
+fn default_sync_threshold_mm() -> f64 { 0.5 }
+fn default_gcode_history_size() -> usize { crate::gcode::history::DEFAULT_HISTORY_CAPACITY }
+fn default_rate_limit_per_ip_per_min() -> u32 { 300 }
+fn default_rate_limit_per_user_per_min() -> u32 { 600 }
+fn default_rate_limit_gcode_per_user_per_min() -> u32 { 120 }
+fn default_trusted_proxy_hops() -> u32 { 1 }
+fn default_position_stream_hz() -> u32 { 50 }
+fn default_temperature_history_hz() -> f64 { 1.0 }
+fn default_history_retention_secs() -> u64 { 3600 }
+fn default_post_print_retract_mm() -> f64 { 2.0 }
+fn default_fan_cooldown_temp() -> f64 { 50.0 }
+fn default_probe_step() -> f64 { 0.01 }
+fn default_probe_speed() -> f64 { 5.0 }
+fn default_probe_min_z() -> f64 { -2.0 }
+fn default_pin_move_time() -> f64 { 0.68 }
+fn default_mesh_min() -> [f64; 2] { [10.0, 10.0] }
+fn default_mesh_max() -> [f64; 2] { [190.0, 190.0] }
+fn default_mesh_count() -> [usize; 2] { [3, 3] }
+fn default_stall_poll_interval_ms() -> u64 { 100 }
+fn default_mqtt_client_id() -> String { "krusty".to_string() }
+fn default_mqtt_topic_prefix() -> String { "krusty".to_string() }
+fn default_mqtt_publish_interval_ms() -> u64 { 1000 }
+fn default_trajectory_dir() -> String { "trajectories".to_string() }
+fn default_trajectory_sample_interval_ms() -> f64 { 1.0 }
+fn default_event_log_max_bytes() -> u64 { 10 * 1024 * 1024 }
+fn default_sensor_divergence_threshold_deg() -> f64 { 5.0 }
 
 pub fn load_config(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
     let contents = std::fs::read_to_string(path)?;
     let config: Config = toml::from_str(&contents)?;
     Ok(config)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> Config {
+        toml::from_str(include_str!("printer.toml")).unwrap()
+    }
+
+    #[test]
+    fn test_config_diff() {
+        let old = test_config();
+        assert!(Config::diff(&old, &old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.printer.max_accel += 500.0;
+        let diff = Config::diff(&old, &new);
+        assert!(diff.printer_changed && diff.motion_changed);
+        assert!(diff.requires_motion_replan() && !diff.requires_hardware_restart());
+
+        // Sections the planner is not built from do not need a replan
+        let mut new = old.clone();
+        new.printer.nozzle_wipe_min_temp += 10.0;
+        new.heater_bed.max_temp += 10.0;
+        new.fan.hysteresis_deg = 2.0;
+        new.extruders.push(old.extruder.clone());
+        let diff = Config::diff(&old, &new);
+        assert!(diff.printer_changed && diff.heater_bed_changed && diff.extruder_changed);
+        assert_eq!(diff.fans_changed, HashSet::from(["fan".to_string()]));
+        assert!(!diff.motion_changed && !diff.mcu_changed);
+
+        let mut new = old.clone();
+        new.mcu.serial = "/dev/ttyACM1".to_string();
+        new.steppers.get_mut("stepper_x").unwrap().microsteps *= 2;
+        new.steppers.insert("stepper_z1".to_string(), StepperConfig::default());
+        let diff = Config::diff(&old, &new);
+        assert!(diff.requires_hardware_restart() && diff.motion_changed && !diff.printer_changed);
+        assert_eq!(diff.steppers_changed, HashSet::from(["stepper_x".to_string(), "stepper_z1".to_string()]));
+    }
+}
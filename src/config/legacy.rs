@@ -0,0 +1,255 @@
+// src/config/legacy.rs - Klipper-style printer.cfg import and export
+use std::fmt::Write;
+use std::path::Path;
+use super::profiles::{io_error, parse_error};
+use super::{Config, ConfigError};
+
+/// Sections written first, in this order; extruders and steppers follow
+const LEGACY_SECTIONS: [&str; 4] = ["printer", "mcu", "extruder", "heater_bed"];
+
+impl Config {
+    /// Write the printer, MCU, extruder, bed and stepper sections as a
+    /// Klipper-style `printer.cfg`
+    ///
+    /// Unset optional settings are left out, as is anything a flat
+    /// `key = value` line cannot hold.
+    pub fn export_legacy(&self, path: &str) -> Result<(), std::io::Error> {
+        std::fs::write(path, self.to_legacy_string())
+    }
+
+    /// The `printer.cfg` text `export_legacy` writes
+    pub fn to_legacy_string(&self) -> String {
+        let table = toml::Table::try_from(self).unwrap_or_default();
+        let section = |name: &str| table.get(name).and_then(toml::Value::as_table);
+
+        let mut out = String::from("# Exported from krusty-rs\n");
+        for name in LEGACY_SECTIONS {
+            if let Some(values) = section(name) {
+                write_section(&mut out, name, values);
+            }
+        }
+        if let Some(toml::Value::Array(extruders)) = table.get("extruders") {
+            for (index, extruder) in extruders.iter().enumerate() {
+                if let Some(values) = extruder.as_table() {
+                    write_section(&mut out, &format!("extruder{}", index + 1), values);
+                }
+            }
+        }
+        if let Some(steppers) = section("steppers") {
+            let mut names: Vec<&String> = steppers.keys().collect();
+            names.sort();
+            for name in names {
+                if let Some(values) = steppers[name].as_table() {
+                    write_section(&mut out, name, values);
+                }
+            }
+        }
+        out
+    }
+
+    /// Read a Klipper-style `printer.cfg`
+    ///
+    /// Options may be written `key: value` or `key = value`. Sections and
+    /// options this host does not use are ignored.
+    pub fn parse_legacy_config(path: &str) -> Result<Config, ConfigError> {
+        let path = Path::new(path);
+        let contents = std::fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+        let table = legacy_to_table(&contents).map_err(|e| parse_error(path, e))?;
+        table.try_into().map_err(|e| parse_error(path, e))
+    }
+}
+
+fn write_section(out: &mut String, name: &str, values: &toml::Table) {
+    let _ = writeln!(out, "\n[{}]", name);
+    for (key, value) in values {
+        if let Some(value) = format_value(value) {
+            let _ = writeln!(out, "{} = {}", key, value);
+        }
+    }
+}
+
+/// A value as written in `printer.cfg`, `None` if it has no flat form
+fn format_value(value: &toml::Value) -> Option<String> {
+    match value {
+        // Quote strings that would otherwise read back as something else
+        toml::Value::String(s) if !matches!(infer_value(s), toml::Value::String(_)) || s.contains([',', '#', ';']) => {
+            Some(format!("\"{}\"", s))
+        }
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(format!("{:?}", f)),
+        toml::Value::Boolean(b) => Some(if *b { "True" } else { "False" }.to_string()),
+        toml::Value::Array(items) if !items.is_empty() => {
+            let items: Option<Vec<String>> = items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::Array(_) | toml::Value::Table(_) => None,
+                    item => format_value(item),
+                })
+                .collect();
+            Some(items?.join(", "))
+        }
+        _ => None,
+    }
+}
+
+/// Sections of a `printer.cfg` as the table a TOML configuration would give
+fn legacy_to_table(contents: &str) -> Result<toml::Table, String> {
+    let mut table = toml::Table::new();
+    let mut extruders = Vec::new();
+    let mut section: Option<(String, toml::Table)> = None;
+
+    let mut finish = |table: &mut toml::Table, section: Option<(String, toml::Table)>| {
+        let Some((name, values)) = section else { return };
+        if name.starts_with("stepper_") {
+            let steppers = table.entry("steppers").or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let Some(steppers) = steppers.as_table_mut() {
+                steppers.insert(name, toml::Value::Table(values));
+            }
+        } else if let Some(index) = name.strip_prefix("extruder").and_then(|n| n.parse::<usize>().ok()) {
+            extruders.push((index, values));
+        } else {
+            table.insert(name, toml::Value::Table(values));
+        }
+    };
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            finish(&mut table, section.take());
+            section = Some((name.trim().to_string(), toml::Table::new()));
+            continue;
+        }
+        let split = line.find([':', '=']).ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+        let (key, value) = (line[..split].trim().to_lowercase(), line[split + 1..].trim());
+        let Some((_, values)) = section.as_mut() else {
+            return Err(format!("line {}: {} is not in a section", number + 1, key));
+        };
+        values.insert(key, infer_value(value));
+    }
+    finish(&mut table, section.take());
+
+    extruders.sort_by_key(|(index, _)| *index);
+    if !extruders.is_empty() {
+        let extruders = extruders.into_iter().map(|(_, values)| toml::Value::Table(values)).collect();
+        table.insert("extruders".to_string(), toml::Value::Array(extruders));
+    }
+    Ok(table)
+}
+
+/// Drop a comment, which starts the line or follows whitespace
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '#' | ';' if !in_quotes && previous.is_whitespace() => return &line[..index],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Read a value as a number, boolean, list or ratio where it looks like one
+fn infer_value(value: &str) -> toml::Value {
+    if let Some(quoted) = value.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        return toml::Value::String(quoted.to_string());
+    }
+    if value.contains(',') {
+        return toml::Value::Array(value.split(',').map(|item| infer_value(item.trim())).collect());
+    }
+    // Klipper writes gear ratios as driven:driving
+    if let Some((driven, driving)) = value.split_once(':')
+        && let (Ok(driven), Ok(driving)) = (driven.trim().parse::<f64>(), driving.trim().parse::<f64>())
+    {
+        return toml::Value::Array(vec![toml::Value::Float(driven), toml::Value::Float(driving)]);
+    }
+    if let Ok(integer) = value.parse::<i64>() {
+        return toml::Value::Integer(integer);
+    }
+    if let Ok(float) = value.parse::<f64>() {
+        return toml::Value::Float(float);
+    }
+    match value.to_lowercase().as_str() {
+        "true" => toml::Value::Boolean(true),
+        "false" => toml::Value::Boolean(false),
+        _ => toml::Value::String(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_round_trip() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.printer.settings_file = Some("/var/lib/krusty/settings.json".to_string());
+        config.extruders.push(crate::config::ExtruderConfig {
+            step_pin: "PD0".to_string(),
+            dir_pin: "!PD1".to_string(),
+            // Would read back as a number without quotes
+            enable_pin: "5".to_string(),
+            standby_temp: Some(150.0),
+            ..config.extruder.clone()
+        });
+        config.steppers.get_mut("stepper_x").unwrap().diag_pin = Some("PG6".to_string());
+
+        let path = std::env::temp_dir().join(format!("krusty-legacy-{}.cfg", std::process::id()));
+        config.export_legacy(path.to_str().unwrap()).unwrap();
+        let exported = std::fs::read_to_string(&path).unwrap();
+        let parsed = Config::parse_legacy_config(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(exported.contains("[stepper_x]") && exported.contains("[extruder1]"));
+        assert!(!exported.contains("min_version"));
+        assert_eq!(parsed.printer, config.printer);
+        assert_eq!(parsed.mcu, config.mcu);
+        assert_eq!(parsed.extruder, config.extruder);
+        assert_eq!(parsed.extruders, config.extruders);
+        assert_eq!(parsed.heater_bed, config.heater_bed);
+        assert_eq!(parsed.steppers, config.steppers);
+    }
+
+    #[test]
+    fn test_parse_klipper_printer_cfg() {
+        let table = legacy_to_table(
+            "\
+# Klipper style
+[printer]
+kinematics: corexy
+max_velocity: 500  ; fast
+max_accel: 7000
+
+[extruder]
+step_pin: PD2
+dir_pin: !PD3
+enable_pin: !PD4
+gear_ratio: 50:17
+pid_Kp: 22.2
+
+[stepper_x]
+step_pin = PB0
+dir_pin = PB1
+enable_pin = PB2
+endstop_pin: ^PG6
+",
+        )
+        .unwrap();
+        let config: Config = table.try_into().unwrap();
+        assert_eq!(config.printer.kinematics, "corexy");
+        assert_eq!(config.printer.max_velocity, 500.0);
+        assert_eq!(config.extruder.dir_pin, "!PD3");
+        assert_eq!(config.extruder.gear_ratio, Some((50.0, 17.0)));
+        assert_eq!(config.extruder.pid_kp, 22.2);
+        assert_eq!(config.steppers["stepper_x"].step_pin, "PB0");
+
+        assert!(legacy_to_table("max_velocity: 500\n").is_err());
+        assert!(legacy_to_table("[printer]\nmax_velocity\n").is_err());
+    }
+}
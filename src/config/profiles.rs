@@ -0,0 +1,228 @@
+// src/config/profiles.rs - Named configuration profiles, e.g. per material
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use super::{Config, ConfigDiff};
+
+/// Directory next to the main configuration holding one TOML file per profile
+pub const PROFILES_DIR: &str = "profiles";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// A file could not be read
+    Io { path: String, message: String },
+
+    /// A file is not a valid configuration
+    Parse { path: String, message: String },
+
+    /// A file could not be written
+    Write { path: String, message: String },
+
+    UnknownProfile(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, message } => write!(f, "Failed to read {}: {}", path, message),
+            ConfigError::Parse { path, message } => write!(f, "Invalid configuration {}: {}", path, message),
+            ConfigError::Write { path, message } => write!(f, "Failed to write {}: {}", path, message),
+            ConfigError::UnknownProfile(name) => write!(f, "Unknown configuration profile: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The main configuration and the profiles that can replace it
+///
+/// A profile file only needs the settings it changes: it is laid over the
+/// main configuration, table by table.
+#[derive(Debug, Clone)]
+pub struct ConfigManager {
+    /// Main configuration as written, for laying profiles over
+    base: toml::Table,
+    /// File the main configuration was loaded from; `None` when built in memory
+    path: Option<PathBuf>,
+    profiles: HashMap<String, Config>,
+    active_profile: Option<String>,
+    active: Config,
+}
+
+impl ConfigManager {
+    /// Manager without profiles, running `config`
+    pub fn new(config: Config) -> Self {
+        Self {
+            base: toml::Table::try_from(&config).unwrap_or_default(),
+            path: None,
+            profiles: HashMap::new(),
+            active_profile: None,
+            active: config,
+        }
+    }
+
+    /// Load the main configuration and every profile next to it, activating
+    /// its `default_profile`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let base = read_table(path)?;
+        let config: Config = base.clone().try_into().map_err(|e| parse_error(path, e))?;
+        let default_profile = config.default_profile.clone();
+
+        let mut manager = Self {
+            base,
+            path: Some(path.to_path_buf()),
+            profiles: HashMap::new(),
+            active_profile: None,
+            active: config,
+        };
+        let dir = path.parent().unwrap_or(Path::new(".")).join(PROFILES_DIR);
+        if dir.is_dir() {
+            manager.load_profiles_dir(&dir)?;
+        }
+        if let Some(name) = default_profile {
+            manager.activate_profile(&name)?;
+        }
+        Ok(manager)
+    }
+
+    /// Add every `*.toml` file in `dir` as a profile named after the file
+    pub fn load_profiles_dir(&mut self, dir: &Path) -> Result<(), ConfigError> {
+        let entries = std::fs::read_dir(dir).map_err(|e| io_error(dir, e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| io_error(dir, e))?.path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let overlay = read_table(&path)?;
+            let mut table = self.base.clone();
+            merge(&mut table, overlay);
+            let config: Config = table.try_into().map_err(|e| parse_error(&path, e))?;
+            tracing::info!("Loaded configuration profile {}", name);
+            self.profiles.insert(name.to_string(), config);
+        }
+        Ok(())
+    }
+
+    pub fn add_profile(&mut self, name: &str, config: Config) {
+        self.profiles.insert(name.to_string(), config);
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Config> {
+        self.profiles.get(name)
+    }
+
+    /// Profile names in alphabetical order
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn active_profile(&self) -> Option<&str> {
+        self.active_profile.as_deref()
+    }
+
+    pub fn active_config(&self) -> &Config {
+        &self.active
+    }
+
+    /// Replace the main configuration, writing it to the file it was loaded
+    /// from
+    ///
+    /// The active profile, if any, is laid over it again on the next start.
+    pub fn save_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        let base = toml::Table::try_from(config).map_err(|e| ConfigError::Parse {
+            path: self.path.as_ref().map_or_else(String::new, |path| path.display().to_string()),
+            message: e.to_string(),
+        })?;
+        if let Some(path) = &self.path {
+            std::fs::write(path, toml::to_string_pretty(&base).map_err(|e| write_error(path, e))?)
+                .map_err(|e| write_error(path, e))?;
+        }
+        self.base = base;
+        if self.active_profile.is_none() {
+            self.active = config.clone();
+        }
+        Ok(())
+    }
+
+    /// Switch to a profile, returning what changed
+    pub fn activate_profile(&mut self, name: &str) -> Result<ConfigDiff, ConfigError> {
+        let config = self.profiles.get(name).ok_or_else(|| ConfigError::UnknownProfile(name.to_string()))?;
+        let diff = Config::diff(&self.active, config);
+        self.active = config.clone();
+        self.active_profile = Some(name.to_string());
+        Ok(diff)
+    }
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+    contents.parse().map_err(|e| parse_error(path, e))
+}
+
+/// Lay `overlay` over `base`, merging tables and replacing everything else
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+pub(super) fn io_error(path: &Path, e: impl fmt::Display) -> ConfigError {
+    ConfigError::Io { path: path.display().to_string(), message: e.to_string() }
+}
+
+fn write_error(path: &Path, e: impl fmt::Display) -> ConfigError {
+    ConfigError::Write { path: path.display().to_string(), message: e.to_string() }
+}
+
+pub(super) fn parse_error(path: &Path, e: impl fmt::Display) -> ConfigError {
+    ConfigError::Parse { path: path.display().to_string(), message: e.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion::MotionConfig;
+
+    #[test]
+    fn test_switch_profiles() {
+        let dir = std::env::temp_dir().join(format!("krusty-profiles-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(PROFILES_DIR)).unwrap();
+        let main = format!("default_profile = \"pla\"\n{}", include_str!("../printer.toml"));
+        std::fs::write(dir.join("printer.toml"), main).unwrap();
+        std::fs::write(dir.join(PROFILES_DIR).join("pla.toml"), "[printer]\nmax_velocity = 150.0\n").unwrap();
+        std::fs::write(dir.join(PROFILES_DIR).join("petg.toml"), "[printer]\nmax_velocity = 90.0\n").unwrap();
+        std::fs::write(dir.join(PROFILES_DIR).join("notes.txt"), "not a profile").unwrap();
+
+        let manager = ConfigManager::load(dir.join("printer.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut manager = manager.unwrap();
+
+        assert_eq!(manager.profile_names(), ["petg", "pla"]);
+        assert_eq!(manager.active_profile(), Some("pla"));
+        assert_eq!(MotionConfig::new_from_printer_config(manager.active_config()).max_velocity[0], 150.0);
+        // Everything a profile leaves out comes from the main file
+        let base: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        assert_eq!(manager.active_config().printer.max_accel, base.printer.max_accel);
+        assert_eq!(manager.active_config().steppers, base.steppers);
+
+        let diff = manager.activate_profile("petg").unwrap();
+        assert!(diff.motion_changed && !diff.requires_hardware_restart());
+        assert_eq!(MotionConfig::new_from_printer_config(manager.active_config()).max_velocity[0], 90.0);
+        assert!(manager.activate_profile("pla").unwrap().motion_changed);
+        assert!(manager.activate_profile("pla").unwrap().is_empty());
+
+        assert_eq!(manager.activate_profile("abs"), Err(ConfigError::UnknownProfile("abs".to_string())));
+        assert_eq!(manager.active_profile(), Some("pla"));
+    }
+}
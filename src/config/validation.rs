@@ -0,0 +1,471 @@
+// src/config/validation.rs - Checking every configuration field, not just the first bad one
+use std::fmt;
+use std::net::SocketAddr;
+use serde::Serialize;
+use crate::motion::kinematics::KinematicsType;
+use super::{Config, ExtruderConfig, HeaterBedConfig, StepperConfig};
+
+/// Microstep settings stepper drivers support
+const MAX_MICROSTEPS: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigErrorSeverity {
+    /// Works, but probably not as intended
+    Warning,
+    /// The printer cannot run with this value
+    Error,
+}
+
+/// A problem with one configuration field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigValidationError {
+    /// Dotted path of the field, e.g. "steppers.stepper_x.rotation_distance"
+    pub field_path: String,
+    pub message: String,
+    pub severity: ConfigErrorSeverity,
+}
+
+impl fmt::Display for ConfigValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field_path, self.message)
+    }
+}
+
+impl Config {
+    /// The first error in the configuration, see `validate_all`
+    pub fn validate(&self) -> Result<(), String> {
+        match self.validate_all().into_iter().find(|e| e.severity == ConfigErrorSeverity::Error) {
+            Some(e) => Err(e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /// Every problem found in the configuration, in file order
+    pub fn validate_all(&self) -> Vec<ConfigValidationError> {
+        let mut v = Validator::default();
+        self.validate_printer(&mut v);
+
+        v.required("mcu.serial", &self.mcu.serial);
+        v.positive("mcu.baud", self.mcu.baud as f64);
+        v.positive("mcu.connections", self.mcu.connections as f64);
+
+        validate_extruder(&mut v, "extruder", &self.extruder);
+        for (i, extruder) in self.extruders.iter().enumerate() {
+            validate_extruder(&mut v, &format!("extruders[{}]", i), extruder);
+        }
+        // A missing [heater_bed] section means there is no heated bed
+        if self.heater_bed != HeaterBedConfig::default() {
+            v.pin("heater_bed.heater_pin", &self.heater_bed.heater_pin);
+            v.required("heater_bed.sensor_type", &self.heater_bed.sensor_type);
+            v.pin("heater_bed.sensor_pin", &self.heater_bed.sensor_pin);
+            v.temperature_range("heater_bed", self.heater_bed.min_temp, self.heater_bed.max_temp);
+        }
+        self.validate_steppers(&mut v);
+        self.validate_kinematics_sections(&mut v);
+        self.validate_fan(&mut v);
+
+        if let Some(chamber) = &self.chamber {
+            v.pin("chamber.heater_pin", &chamber.heater_pin);
+            v.required("chamber.sensor_type", &chamber.sensor_type);
+            v.pin("chamber.sensor_pin", &chamber.sensor_pin);
+            v.temperature_range("chamber", chamber.min_temp, chamber.max_temp);
+            v.range("chamber.max_power", chamber.max_power, 0.0, 1.0);
+            v.non_negative("chamber.pid_kp", chamber.pid_kp);
+            v.non_negative("chamber.pid_ki", chamber.pid_ki);
+            v.non_negative("chamber.pid_kd", chamber.pid_kd);
+            v.positive("chamber.pullup_resistor", chamber.pullup_resistor);
+            v.positive("chamber.wait_timeout_secs", chamber.wait_timeout_secs);
+        }
+        self.validate_web(&mut v);
+        if let Some(mqtt) = &self.mqtt {
+            v.required("mqtt.broker_url", &mqtt.broker_url);
+            v.required("mqtt.client_id", &mqtt.client_id);
+            v.positive("mqtt.publish_interval_ms", mqtt.publish_interval_ms as f64);
+        }
+        if let Some(bltouch) = &self.bltouch {
+            v.pin("bltouch.servo_pin", &bltouch.servo_pin);
+            v.pin("bltouch.endstop_pin", &bltouch.endstop_pin);
+            v.positive("bltouch.probe_step", bltouch.probe_step);
+            v.positive("bltouch.speed", bltouch.speed);
+            v.non_negative("bltouch.pin_move_time", bltouch.pin_move_time);
+            if bltouch.mesh_min.iter().zip(&bltouch.mesh_max).any(|(min, max)| min >= max) {
+                v.error("bltouch.mesh_max", format!("must be above mesh_min {:?} on both axes", bltouch.mesh_min));
+            }
+            if bltouch.mesh_count.iter().any(|&count| count < 2) {
+                v.error("bltouch.mesh_count", "needs at least 2 points along each axis");
+            }
+        }
+        if let Some(post_print) = &self.post_print {
+            v.non_negative("post_print.retract_mm", post_print.retract_mm);
+            v.non_negative("post_print.fan_cooldown_temp", post_print.fan_cooldown_temp);
+        }
+        if let Some(stall) = &self.stall_detection {
+            v.positive("stall_detection.poll_interval_ms", stall.poll_interval_ms as f64);
+        }
+        if let Some(clog) = &self.clog_detection {
+            v.positive("clog_detection.clog_threshold_mm", clog.clog_threshold_mm);
+            v.positive("clog_detection.clog_window_secs", clog.clog_window_secs);
+            v.positive("clog_detection.window_samples", clog.window_samples as f64);
+        }
+        v.required("advanced.trajectory_dir", &self.advanced.trajectory_dir);
+        v.positive("advanced.trajectory_sample_interval_ms", self.advanced.trajectory_sample_interval_ms);
+        v.positive("advanced.event_log_max_bytes", self.advanced.event_log_max_bytes as f64);
+        v.errors
+    }
+
+    fn validate_printer(&self, v: &mut Validator) {
+        let printer = &self.printer;
+        if let Err(e) = printer.kinematics.parse::<KinematicsType>() {
+            v.error("printer.kinematics", e);
+        }
+        v.positive("printer.max_velocity", printer.max_velocity);
+        v.positive("printer.max_accel", printer.max_accel);
+        v.positive("printer.max_z_velocity", printer.max_z_velocity);
+        v.positive("printer.max_z_accel", printer.max_z_accel);
+        v.non_negative("printer.z_hop_height", printer.z_hop_height);
+        v.positive("printer.z_hop_speed", printer.z_hop_speed);
+        if printer.steps_per_mm_max_ratio < 1.0 {
+            v.error("printer.steps_per_mm_max_ratio", "must be at least 1");
+        }
+        v.positive("printer.nozzle_wipe_length_mm", printer.nozzle_wipe_length_mm);
+        v.positive("printer.nozzle_wipe_width_mm", printer.nozzle_wipe_width_mm);
+        v.non_negative("printer.nozzle_wipe_min_temp", printer.nozzle_wipe_min_temp);
+        v.non_negative("printer.min_layer_time_secs", printer.min_layer_time_secs);
+        v.range("printer.max_layer_fan_speed_pct", printer.max_layer_fan_speed_pct, 0.0, 100.0);
+        v.non_negative("printer.queue_drain_timeout_secs", printer.queue_drain_timeout_secs);
+        v.range("printer.motor_current_balance", printer.motor_current_balance, 0.0, 1.0);
+    }
+
+    fn validate_steppers(&self, v: &mut Validator) {
+        let required: &[&str] = match printer_kinematics(self) {
+            Some(KinematicsType::Delta) => &["stepper_a", "stepper_b", "stepper_c"],
+            Some(KinematicsType::Hangprinter) => &[],
+            _ => &["stepper_x", "stepper_y", "stepper_z"],
+        };
+        for name in required {
+            if !self.steppers.contains_key(*name) {
+                v.error(&format!("steppers.{}", name), format!("{} kinematics needs this stepper", self.printer.kinematics));
+            }
+        }
+
+        let mut names: Vec<_> = self.steppers.keys().collect();
+        names.sort();
+        for name in names {
+            validate_stepper(v, &format!("steppers.{}", name), &self.steppers[name]);
+        }
+    }
+
+    /// Sections a kinematics type cannot run without
+    fn validate_kinematics_sections(&self, v: &mut Validator) {
+        match printer_kinematics(self) {
+            Some(KinematicsType::Delta) => match &self.delta {
+                Some(delta) => {
+                    v.positive("delta.radius", delta.radius);
+                    if delta.diagonal_rod <= delta.radius {
+                        v.error("delta.diagonal_rod", format!("must be longer than radius ({})", delta.radius));
+                    }
+                    v.positive("delta.print_radius", delta.print_radius);
+                    if delta.print_radius > delta.radius {
+                        v.warning("delta.print_radius", format!("is beyond the tower radius ({})", delta.radius));
+                    }
+                }
+                None => v.error("delta", "delta kinematics needs a [delta] section"),
+            },
+            Some(KinematicsType::Scara) => match &self.scara {
+                Some(scara) => {
+                    v.positive("scara.arm1_length", scara.arm1_length);
+                    v.positive("scara.arm2_length", scara.arm2_length);
+                    v.positive("scara.arm1_steps_per_deg", scara.arm1_steps_per_deg);
+                    v.positive("scara.arm2_steps_per_deg", scara.arm2_steps_per_deg);
+                }
+                None => v.error("scara", "scara kinematics needs a [scara] section"),
+            },
+            Some(KinematicsType::Hangprinter) => match &self.hangprinter {
+                Some(hangprinter) => {
+                    for (i, pin) in hangprinter.encoder_pins.iter().enumerate() {
+                        v.pin(&format!("hangprinter.encoder_pins[{}]", i), pin);
+                    }
+                    v.positive("hangprinter.encoder_counts_per_mm", hangprinter.encoder_counts_per_mm);
+                    v.positive("hangprinter.calibration_radius", hangprinter.calibration_radius);
+                }
+                None => v.error("hangprinter", "hangprinter kinematics needs a [hangprinter] section"),
+            },
+            _ => {}
+        }
+    }
+
+    fn validate_fan(&self, v: &mut Validator) {
+        for (i, point) in self.fan.curve.iter().enumerate() {
+            v.range(&format!("fan.curve[{}].speed_pct", i), point.speed_pct, 0.0, 100.0);
+        }
+        if self.fan.curve.windows(2).any(|pair| pair[0].temperature >= pair[1].temperature) {
+            v.error("fan.curve", "temperatures must rise from one point to the next");
+        }
+        v.non_negative("fan.hysteresis_deg", self.fan.hysteresis_deg);
+        v.range("fan.fan_rpm_tolerance", self.fan.fan_rpm_tolerance as f64, 0.0, 1.0);
+        v.positive("fan.max_rpm", self.fan.max_rpm);
+        v.non_negative("fan.rpm_kp", self.fan.rpm_kp);
+        v.non_negative("fan.rpm_ki", self.fan.rpm_ki);
+    }
+
+    fn validate_web(&self, v: &mut Validator) {
+        let web = &self.web;
+        if web.bind_address.parse::<SocketAddr>().is_err() {
+            v.error("web.bind_address", format!("\"{}\" is not an address and port", web.bind_address));
+        }
+        v.required("web.upload_dir", &web.upload_dir);
+        v.positive("web.token_lifetime_secs", web.token_lifetime_secs as f64);
+        v.positive("web.refresh_token_lifetime_secs", web.refresh_token_lifetime_secs as f64);
+        if web.trust_forwarded_for {
+            v.positive("web.trusted_proxy_hops", web.trusted_proxy_hops as f64);
+        }
+        v.positive("web.position_stream_hz", web.position_stream_hz as f64);
+        if web.position_stream_hz > 200 {
+            v.warning("web.position_stream_hz", "is capped at 200");
+        }
+        v.positive("web.temperature_history_hz", web.temperature_history_hz);
+        v.positive("web.history_retention_secs", web.history_retention_secs as f64);
+        for (i, user) in web.users.iter().enumerate() {
+            v.required(&format!("web.users[{}].username", i), &user.username);
+            if argon2::PasswordHash::new(&user.password_hash).is_err() {
+                v.error(&format!("web.users[{}].password_hash", i), "must be an Argon2 PHC string ($argon2id$...)");
+            }
+        }
+        for (i, webhook) in web.webhooks.iter().enumerate() {
+            v.required(&format!("web.webhooks[{}].url", i), &webhook.url);
+        }
+    }
+}
+
+fn printer_kinematics(config: &Config) -> Option<KinematicsType> {
+    config.printer.kinematics.parse().ok()
+}
+
+fn validate_extruder(v: &mut Validator, path: &str, extruder: &ExtruderConfig) {
+    v.pin(&format!("{}.step_pin", path), &extruder.step_pin);
+    v.pin(&format!("{}.dir_pin", path), &extruder.dir_pin);
+    v.pin(&format!("{}.enable_pin", path), &extruder.enable_pin);
+    v.positive(&format!("{}.rotation_distance", path), extruder.rotation_distance);
+    if let Some((driven, driving)) = extruder.gear_ratio
+        && (driven <= 0.0 || driving <= 0.0)
+    {
+        v.error(&format!("{}.gear_ratio", path), "both gears must have teeth");
+    }
+    v.microsteps(&format!("{}.microsteps", path), extruder.microsteps);
+    v.positive(&format!("{}.nozzle_diameter", path), extruder.nozzle_diameter);
+    v.positive(&format!("{}.filament_diameter", path), extruder.filament_diameter);
+    v.required(&format!("{}.sensor_type", path), &extruder.sensor_type);
+    v.positive(&format!("{}.pullup_resistor", path), extruder.pullup_resistor);
+    v.non_negative(&format!("{}.pid_kp", path), extruder.pid_kp);
+    v.non_negative(&format!("{}.pid_ki", path), extruder.pid_ki);
+    v.non_negative(&format!("{}.pid_kd", path), extruder.pid_kd);
+    if extruder.integral_min >= extruder.integral_max {
+        v.error(&format!("{}.integral_max", path), format!("must be above integral_min ({})", extruder.integral_min));
+    }
+    v.positive(&format!("{}.derivative_filter_cutoff", path), extruder.derivative_filter_cutoff);
+    v.range(&format!("{}.output_min", path), extruder.output_min, 0.0, 1.0);
+    v.range(&format!("{}.output_max", path), extruder.output_max, 0.0, 1.0);
+    if extruder.output_min >= extruder.output_max {
+        v.error(&format!("{}.output_max", path), format!("must be above output_min ({})", extruder.output_min));
+    }
+    v.non_negative(&format!("{}.min_extrude_temp", path), extruder.min_extrude_temp);
+    v.non_negative(&format!("{}.max_volumetric_speed_mm3_per_s", path), extruder.max_volumetric_speed_mm3_per_s);
+    if let Some(standby_temp) = extruder.standby_temp {
+        v.non_negative(&format!("{}.standby_temp", path), standby_temp);
+    }
+    v.non_negative(&format!("{}.standby_delay_secs", path), extruder.standby_delay_secs);
+    if let Some(pin) = &extruder.extruder_encoder_pin {
+        v.pin(&format!("{}.extruder_encoder_pin", path), pin);
+    }
+    for (i, sensor) in extruder.extra_sensors.iter().enumerate() {
+        v.pin(&format!("{}.extra_sensors[{}].sensor_pin", path, i), &sensor.sensor_pin);
+    }
+    v.positive(&format!("{}.sensor_divergence_threshold_deg", path), extruder.sensor_divergence_threshold_deg);
+    v.positive(&format!("{}.sync_threshold_mm", path), extruder.sync_threshold_mm);
+}
+
+fn validate_stepper(v: &mut Validator, path: &str, stepper: &StepperConfig) {
+    v.pin(&format!("{}.step_pin", path), &stepper.step_pin);
+    v.pin(&format!("{}.dir_pin", path), &stepper.dir_pin);
+    v.pin(&format!("{}.enable_pin", path), &stepper.enable_pin);
+    v.positive(&format!("{}.rotation_distance", path), stepper.rotation_distance);
+    v.microsteps(&format!("{}.microsteps", path), stepper.microsteps);
+    v.positive(&format!("{}.full_steps_per_rotation", path), stepper.full_steps_per_rotation as f64);
+    if let Some(pin) = &stepper.diag_pin {
+        v.pin(&format!("{}.diag_pin", path), pin);
+    }
+    v.positive(&format!("{}.run_current_ma", path), stepper.run_current_ma as f64);
+    if let Some(encoder) = &stepper.encoder {
+        v.pin(&format!("{}.encoder.pin", path), &encoder.pin);
+        v.positive(&format!("{}.encoder.counts_per_rev", path), encoder.counts_per_rev as f64);
+    }
+}
+
+/// Whether `pin` names an MCU pin: a port pin like "PA0" or "PB12", a
+/// Raspberry Pi "gpio17" or a plain Arduino pin number, optionally
+/// prefixed with `!` (inverted), `^` (pull-up) or `~` (pull-down)
+fn is_valid_pin(pin: &str) -> bool {
+    let pin = pin.trim_start_matches(['!', '^', '~']);
+    let number = if let Some(rest) = pin.strip_prefix("gpio") {
+        rest
+    } else if let Some(rest) = pin.strip_prefix('P')
+        && rest.starts_with(|c: char| c.is_ascii_uppercase())
+    {
+        &rest[1..]
+    } else {
+        pin
+    };
+    !number.is_empty() && number.len() <= 3 && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Collects problems as fields are checked
+#[derive(Debug, Default)]
+struct Validator {
+    errors: Vec<ConfigValidationError>,
+}
+
+impl Validator {
+    fn push(&mut self, field_path: &str, message: impl Into<String>, severity: ConfigErrorSeverity) {
+        self.errors.push(ConfigValidationError {
+            field_path: field_path.to_string(),
+            message: message.into(),
+            severity,
+        });
+    }
+
+    fn error(&mut self, field_path: &str, message: impl Into<String>) {
+        self.push(field_path, message, ConfigErrorSeverity::Error);
+    }
+
+    fn warning(&mut self, field_path: &str, message: impl Into<String>) {
+        self.push(field_path, message, ConfigErrorSeverity::Warning);
+    }
+
+    fn positive(&mut self, field_path: &str, value: f64) {
+        if value.is_nan() || value <= 0.0 {
+            self.error(field_path, format!("must be positive, got {}", value));
+        }
+    }
+
+    fn non_negative(&mut self, field_path: &str, value: f64) {
+        if value.is_nan() || value < 0.0 {
+            self.error(field_path, format!("must not be negative, got {}", value));
+        }
+    }
+
+    fn range(&mut self, field_path: &str, value: f64, min: f64, max: f64) {
+        if !(min..=max).contains(&value) {
+            self.error(field_path, format!("must be between {} and {}, got {}", min, max, value));
+        }
+    }
+
+    fn required(&mut self, field_path: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.error(field_path, "is required");
+        }
+    }
+
+    fn pin(&mut self, field_path: &str, pin: &str) {
+        if pin.trim().is_empty() {
+            self.error(field_path, "is required");
+        } else if !is_valid_pin(pin) {
+            self.error(field_path, format!("\"{}\" is not a pin name like PA0", pin));
+        }
+    }
+
+    /// `min_temp` below `max_temp` in the section at `path`
+    fn temperature_range(&mut self, path: &str, min_temp: f64, max_temp: f64) {
+        if min_temp >= max_temp {
+            self.error(&format!("{}.max_temp", path), format!("must be above min_temp ({})", min_temp));
+        }
+    }
+
+    fn microsteps(&mut self, field_path: &str, microsteps: u32) {
+        if microsteps == 0 || microsteps > MAX_MICROSTEPS {
+            self.error(field_path, format!("must be between 1 and {}, got {}", MAX_MICROSTEPS, microsteps));
+        } else if !microsteps.is_power_of_two() {
+            self.warning(field_path, format!("{} is not a power of two, which drivers round down", microsteps));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChamberConfig, UserConfig};
+
+    fn test_config() -> Config {
+        toml::from_str(include_str!("../printer.toml")).unwrap()
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = test_config();
+        assert_eq!(config.validate_all(), []);
+        assert_eq!(config.validate(), Ok(()));
+
+        let mut config = test_config();
+        config.printer.max_velocity = 0.0;
+        config.printer.kinematics = "corexz".to_string();
+        config.mcu.serial = String::new();
+        config.extruder.step_pin = "A0".to_string();
+        config.extruder.nozzle_diameter = -0.4;
+        config.heater_bed.min_temp = 150.0;
+        config.steppers.get_mut("stepper_x").unwrap().rotation_distance = 0.0;
+        config.steppers.get_mut("stepper_y").unwrap().microsteps = 12;
+        config.steppers.get_mut("stepper_y").unwrap().dir_pin = "!PB4".to_string();
+        config.steppers.remove("stepper_z");
+        config.chamber = Some(ChamberConfig {
+            heater_pin: "PD5".to_string(),
+            sensor_type: String::new(),
+            sensor_pin: "PD6".to_string(),
+            min_temp: 0.0,
+            max_temp: 60.0,
+            max_power: 1.5,
+            pid_kp: 0.3,
+            pid_ki: 0.002,
+            pid_kd: 0.0,
+            pullup_resistor: 4700.0,
+            wait_timeout_secs: 1800.0,
+        });
+        config.web.bind_address = "localhost".to_string();
+        config.web.users.push(UserConfig {
+            username: "admin".to_string(),
+            password_hash: "secret".to_string(),
+            role: Default::default(),
+        });
+
+        let errors = config.validate_all();
+        let paths: Vec<&str> = errors.iter().map(|e| e.field_path.as_str()).collect();
+        assert_eq!(paths, [
+            "printer.kinematics",
+            "printer.max_velocity",
+            "mcu.serial",
+            "extruder.step_pin",
+            "extruder.nozzle_diameter",
+            "heater_bed.max_temp",
+            "steppers.stepper_z",
+            "steppers.stepper_x.rotation_distance",
+            "steppers.stepper_y.microsteps",
+            "chamber.sensor_type",
+            "chamber.max_power",
+            "web.bind_address",
+            "web.users[0].password_hash",
+        ]);
+        let microsteps = &errors[8];
+        assert_eq!(microsteps.severity, ConfigErrorSeverity::Warning);
+        assert!(errors.iter().filter(|e| e.field_path != microsteps.field_path).all(|e| e.severity == ConfigErrorSeverity::Error));
+        assert_eq!(config.validate(), Err("printer.kinematics: Unknown kinematics type: corexz".to_string()));
+    }
+
+    #[test]
+    fn test_pin_names() {
+        for pin in ["PA0", "PB12", "!PD1", "^!PC13", "gpio17", "5"] {
+            assert!(is_valid_pin(pin), "{}", pin);
+        }
+        for pin in ["", "A0", "PA", "pa0", "P0", "PA1234", "gpio", "PA0 "] {
+            assert!(!is_valid_pin(pin), "{}", pin);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+// src/eeprom.rs - Settings changed at runtime, kept across restarts
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::motion::kinematics::SkewCorrection;
+
+/// Values that override the configuration file once saved
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PersistentSettings {
+    /// Calibrated steps/mm for [X, Y, Z, E]
+    #[serde(default)]
+    pub steps_per_mm: Option<[f64; 4]>,
+    /// Frame skew from M852 or CALIBRATE_SKEW
+    #[serde(default)]
+    pub skew_correction: Option<SkewCorrection>,
+    /// Delta endstop corrections from G33 (mm)
+    #[serde(default)]
+    pub delta_endstop_corrections: Option<[f64; 3]>,
+    /// Delta tower angle corrections from G33 (degrees)
+    #[serde(default)]
+    pub delta_tower_angle_corrections: Option<[f64; 3]>,
+}
+
+/// Stores `PersistentSettings` as JSON, like printer EEPROM
+#[derive(Debug, Clone)]
+pub struct EepromManager {
+    path: PathBuf,
+}
+
+impl EepromManager {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saved settings, or the defaults if nothing has been saved yet
+    pub fn load(&self) -> io::Result<PersistentSettings> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(PersistentSettings::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write settings, replacing the file only once they are complete
+    pub fn save(&self, settings: &PersistentSettings) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(settings).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temp = self.path.with_extension("tmp");
+        std::fs::write(&temp, json)?;
+        std::fs::rename(temp, &self.path)
+    }
+
+    /// Load, change and save the settings
+    pub fn update(&self, change: impl FnOnce(&mut PersistentSettings)) -> io::Result<()> {
+        let mut settings = self.load()?;
+        change(&mut settings);
+        self.save(&settings)
+    }
+}
@@ -0,0 +1,359 @@
+// src/file/metadata.rs - Slicer metadata embedded in G-code comments
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+
+/// Lines from the top of a file searched for metadata
+pub const METADATA_HEADER_LINES: usize = 200;
+
+/// Print information reported by the slicer
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GCodeMetadata {
+    pub estimated_time_secs: Option<u64>,
+    pub filament_used_mm: Option<f64>,
+    pub layer_count: Option<u32>,
+    pub slicer: Option<String>,
+    pub slicer_version: Option<String>,
+    pub nozzle_diameter: Option<f64>,
+    /// Material of the first extruder, e.g. `PLA`
+    pub filament_type: Option<String>,
+}
+
+/// How the value following a recognized comment key is interpreted
+#[derive(Debug, Clone, Copy)]
+enum MetadataField {
+    /// "<slicer> <version> ..."
+    GeneratedBy,
+    /// Version of a slicer known from the key itself
+    SlicerVersion(&'static str),
+    /// Duration such as "1d 2h 3m 4s"
+    EstimatedTime,
+    /// Duration in whole seconds
+    EstimatedSeconds,
+    FilamentMillimetres,
+    FilamentMetres,
+    LayerCount,
+    NozzleDiameter,
+    FilamentType,
+}
+
+/// Comment keys recognized per slicer, matched against the comment text
+/// with the leading ';' removed. More specific keys come first.
+const METADATA_PATTERNS: &[(&str, MetadataField)] = &[
+    // PrusaSlicer / SuperSlicer
+    ("generated by ", MetadataField::GeneratedBy),
+    ("estimated printing time (normal mode)", MetadataField::EstimatedTime),
+    ("estimated printing time", MetadataField::EstimatedTime),
+    ("filament used [mm]", MetadataField::FilamentMillimetres),
+    ("nozzle_diameter", MetadataField::NozzleDiameter),
+    ("filament_type", MetadataField::FilamentType),
+    // Cura
+    ("Generated with ", MetadataField::GeneratedBy),
+    ("TIME:", MetadataField::EstimatedSeconds),
+    ("Filament used:", MetadataField::FilamentMetres),
+    ("LAYER_COUNT:", MetadataField::LayerCount),
+    ("EXTRUDER_TRAIN.0.NOZZLE.DIAMETER:", MetadataField::NozzleDiameter),
+    // Bambu Studio
+    ("BambuStudio ", MetadataField::SlicerVersion("BambuStudio")),
+    ("model printing time", MetadataField::EstimatedTime),
+    ("total filament length [mm]", MetadataField::FilamentMillimetres),
+    ("total layer number", MetadataField::LayerCount),
+];
+
+impl GCodeMetadata {
+    /// Collect metadata from G-code lines; the first value found for a
+    /// field wins
+    pub fn parse_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut metadata = Self::default();
+        for line in lines {
+            let Some(comment) = line.trim().strip_prefix(';') else {
+                continue;
+            };
+            let comment = comment.trim();
+            for &(key, field) in METADATA_PATTERNS {
+                if let Some(rest) = comment.strip_prefix(key) {
+                    let value = rest.trim_start_matches([' ', ':', '=']).trim();
+                    metadata.apply(field, value);
+                    break;
+                }
+            }
+        }
+        metadata
+    }
+
+    fn apply(&mut self, field: MetadataField, value: &str) {
+        match field {
+            MetadataField::GeneratedBy => {
+                let mut words = value.split_whitespace();
+                if self.slicer.is_none() {
+                    self.slicer = words.next().map(normalize_slicer_name);
+                    self.slicer_version = words.next().map(str::to_string);
+                }
+            }
+            MetadataField::SlicerVersion(name) => {
+                if self.slicer.is_none() {
+                    self.slicer = Some(name.to_string());
+                    self.slicer_version = value.split_whitespace().next().map(str::to_string);
+                }
+            }
+            MetadataField::EstimatedTime => {
+                self.estimated_time_secs = self.estimated_time_secs.or_else(|| parse_duration(value));
+            }
+            MetadataField::EstimatedSeconds => {
+                self.estimated_time_secs = self
+                    .estimated_time_secs
+                    .or_else(|| value.parse::<f64>().ok().map(|secs| secs.max(0.0).round() as u64));
+            }
+            MetadataField::FilamentMillimetres => {
+                self.filament_used_mm = self.filament_used_mm.or_else(|| first_number(value));
+            }
+            MetadataField::FilamentMetres => {
+                self.filament_used_mm = self
+                    .filament_used_mm
+                    .or_else(|| first_number(value.trim_end_matches('m')).map(|metres| metres * 1000.0));
+            }
+            MetadataField::LayerCount => {
+                self.layer_count = self.layer_count.or_else(|| value.parse().ok());
+            }
+            MetadataField::NozzleDiameter => {
+                self.nozzle_diameter = self.nozzle_diameter.or_else(|| first_number(value));
+            }
+            MetadataField::FilamentType => {
+                let first = value.split([';', ',']).next().map(str::trim).filter(|name| !name.is_empty());
+                self.filament_type = self.filament_type.take().or_else(|| first.map(str::to_string));
+            }
+        }
+    }
+}
+
+fn normalize_slicer_name(name: &str) -> String {
+    if name.starts_with("Cura") {
+        "Cura".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// First entry of a comma separated list, as used for per-extruder values
+fn first_number(value: &str) -> Option<f64> {
+    value.split(',').next()?.trim().parse().ok()
+}
+
+/// Parse "1d 2h 3m 4s" style durations into seconds, stopping at the first
+/// word that isn't a duration part
+fn parse_duration(value: &str) -> Option<u64> {
+    let mut total = 0;
+    let mut found = false;
+    for word in value.split_whitespace() {
+        let part = word.trim_end_matches(';');
+        let Some(split) = part.find(|c: char| !c.is_ascii_digit()) else {
+            break;
+        };
+        let (number, unit) = part.split_at(split);
+        let scale = match unit {
+            "d" => 86400,
+            "h" => 3600,
+            "m" => 60,
+            "s" => 1,
+            _ => break,
+        };
+        let Ok(number) = number.parse::<u64>() else {
+            break;
+        };
+        total += number * scale;
+        found = true;
+        if word.ends_with(';') {
+            break;
+        }
+    }
+    found.then_some(total)
+}
+
+/// Comment tags opening and closing thumbnail blocks: PNG, JPEG and QOI
+const THUMBNAIL_TAGS: &[&str] = &["thumbnail", "thumbnail_JPG", "thumbnail_QOI"];
+
+/// Image formats a thumbnail may be in, by leading bytes
+const THUMBNAIL_FORMATS: &[(&[u8], &str)] = &[
+    (b"\x89PNG", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"qoif", "image/qoi"),
+    (b"GIF8", "image/gif"),
+];
+
+/// Media type of an embedded thumbnail, `application/octet-stream` if unknown
+pub fn thumbnail_content_type(data: &[u8]) -> &'static str {
+    THUMBNAIL_FORMATS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map_or("application/octet-stream", |(_, content_type)| content_type)
+}
+
+/// Whether `comment` opens a thumbnail block, with what follows `begin`,
+/// or closes one
+fn thumbnail_marker(comment: &str) -> Option<Option<&str>> {
+    let (tag, rest) = comment.split_once(' ')?;
+    if !THUMBNAIL_TAGS.contains(&tag) {
+        return None;
+    }
+    match rest.trim() {
+        "end" => Some(None),
+        rest => rest.strip_prefix("begin ").map(Some),
+    }
+}
+
+/// Decode the largest thumbnail embedded in PrusaSlicer format:
+///
+/// ```text
+/// ; thumbnail begin 16x16 1234
+/// ; <base64>
+/// ; thumbnail end
+/// ```
+///
+/// JPEG and QOI thumbnails use `thumbnail_JPG` and `thumbnail_QOI` instead.
+pub fn extract_thumbnail<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Vec<u8>> {
+    let mut best: Option<(u64, String)> = None;
+    let mut current: Option<(u64, String)> = None;
+
+    for line in lines {
+        let Some(comment) = line.trim().strip_prefix(';') else {
+            continue;
+        };
+        let comment = comment.trim();
+        let marker = thumbnail_marker(comment);
+        if let Some(Some(header)) = marker {
+            // "<width>x<height> <encoded length>"
+            let area = header
+                .split_whitespace()
+                .next()
+                .and_then(|size| size.split_once('x'))
+                .and_then(|(w, h)| Some(w.parse::<u64>().ok()? * h.parse::<u64>().ok()?))
+                .unwrap_or(0);
+            current = Some((area, String::new()));
+        } else if marker.is_some() {
+            if let Some(done) = current.take()
+                && best.as_ref().is_none_or(|(area, _)| done.0 > *area)
+            {
+                best = Some(done);
+            }
+        } else if let Some((_, data)) = current.as_mut() {
+            data.push_str(comment);
+        }
+    }
+
+    best.and_then(|(_, data)| STANDARD.decode(data).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prusaslicer_metadata() {
+        let gcode = "\
+; generated by PrusaSlicer 2.6.1+linux-x64-GTK3 on 2023-10-01 at 12:00:00 UTC
+;
+; external perimeters extrusion width = 0.45mm
+G28
+; filament used [mm] = 2340.5
+; filament used [g] = 7.0
+; estimated printing time (silent mode) = 1h 30m 2s
+; estimated printing time (normal mode) = 1h 23m
+; nozzle_diameter = 0.4,0.6
+; filament_type = ABS;PETG
+";
+        let metadata = GCodeMetadata::parse_lines(gcode.lines());
+        assert_eq!(metadata.slicer.as_deref(), Some("PrusaSlicer"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("2.6.1+linux-x64-GTK3"));
+        assert_eq!(metadata.estimated_time_secs, Some(3600 + 23 * 60));
+        assert_eq!(metadata.filament_used_mm, Some(2340.5));
+        assert_eq!(metadata.nozzle_diameter, Some(0.4));
+        assert_eq!(metadata.filament_type.as_deref(), Some("ABS"));
+        assert_eq!(metadata.layer_count, None);
+    }
+
+    #[test]
+    fn test_superslicer_metadata() {
+        let gcode = "\
+; generated by SuperSlicer 2.5.59.2 on 2023-11-02 at 08:15:33 UTC
+; estimated printing time = 2d 1h 0m 5s
+; filament used [mm] = 10.0
+";
+        let metadata = GCodeMetadata::parse_lines(gcode.lines());
+        assert_eq!(metadata.slicer.as_deref(), Some("SuperSlicer"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("2.5.59.2"));
+        assert_eq!(metadata.estimated_time_secs, Some(2 * 86400 + 3600 + 5));
+        assert_eq!(metadata.filament_used_mm, Some(10.0));
+    }
+
+    #[test]
+    fn test_cura_metadata() {
+        let gcode = "\
+;FLAVOR:Marlin
+;TIME:5025
+;Filament used: 2.34059m
+;Layer height: 0.2
+;EXTRUDER_TRAIN.0.NOZZLE.DIAMETER:0.4
+;Generated with Cura_SteamEngine 5.4.0
+;LAYER_COUNT:200
+;LAYER:0
+;TIME_ELAPSED:12.5
+";
+        let metadata = GCodeMetadata::parse_lines(gcode.lines());
+        assert_eq!(metadata.slicer.as_deref(), Some("Cura"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("5.4.0"));
+        assert_eq!(metadata.estimated_time_secs, Some(5025));
+        assert!((metadata.filament_used_mm.unwrap() - 2340.59).abs() < 1e-9);
+        assert_eq!(metadata.layer_count, Some(200));
+        assert_eq!(metadata.nozzle_diameter, Some(0.4));
+    }
+
+    #[test]
+    fn test_bambu_studio_metadata() {
+        let gcode = "\
+; HEADER_BLOCK_START
+; BambuStudio 01.07.04.52
+; model printing time: 1h 2m 3s; total estimated time: 1h 10m 3s
+; total layer number: 150
+; total filament length [mm] : 1523.75
+; HEADER_BLOCK_END
+; nozzle_diameter = 0.4
+";
+        let metadata = GCodeMetadata::parse_lines(gcode.lines());
+        assert_eq!(metadata.slicer.as_deref(), Some("BambuStudio"));
+        assert_eq!(metadata.slicer_version.as_deref(), Some("01.07.04.52"));
+        assert_eq!(metadata.estimated_time_secs, Some(3600 + 2 * 60 + 3));
+        assert_eq!(metadata.layer_count, Some(150));
+        assert_eq!(metadata.filament_used_mm, Some(1523.75));
+        assert_eq!(metadata.nozzle_diameter, Some(0.4));
+    }
+
+    #[test]
+    fn test_extract_largest_thumbnail() {
+        let small = STANDARD.encode(b"\x89PNG small");
+        let large = STANDARD.encode(b"\x89PNG large thumbnail data");
+        let (first, second) = large.split_at(8);
+        let gcode = format!(
+            "; thumbnail begin 16x16 {}\n; {}\n; thumbnail end\n;\n\
+             ; thumbnail begin 220x124 {}\n; {}\n; {}\n; thumbnail end\nG28\n",
+            small.len(),
+            small,
+            large.len(),
+            first,
+            second
+        );
+        assert_eq!(extract_thumbnail(gcode.lines()), Some(b"\x89PNG large thumbnail data".to_vec()));
+        assert_eq!(extract_thumbnail("G28\nG1 X10\n".lines()), None);
+    }
+
+    #[test]
+    fn test_extract_qoi_thumbnail() {
+        let qoi = STANDARD.encode(b"qoif thumbnail data");
+        let gcode = format!("; thumbnail_QOI begin 32x32 {}\n; {}\n; thumbnail_QOI end\n", qoi.len(), qoi);
+        let thumbnail = extract_thumbnail(gcode.lines()).unwrap();
+        assert_eq!(thumbnail, b"qoif thumbnail data");
+        assert_eq!(thumbnail_content_type(&thumbnail), "image/qoi");
+        assert_eq!(thumbnail_content_type(b"\x89PNG\r\n"), "image/png");
+        assert_eq!(thumbnail_content_type(b"text"), "application/octet-stream");
+        assert_eq!(extract_thumbnail("; thumbnail_BMP begin 8x8 4\n; AAAA\n; thumbnail_BMP end\n".lines()), None);
+    }
+}
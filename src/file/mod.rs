@@ -1,11 +1,42 @@
 // src/file/mod.rs - File management system
-use std::path::Path;
+pub mod metadata;
+
+pub use metadata::{thumbnail_content_type, GCodeMetadata};
+
+use crate::gcode::parser::{AsyncGCodeParser, GCodeError, GCodeParserConfig, ParsedLine};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Chunk size used when hashing files
+const CHECKSUM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Commands parsed ahead of the consumer when streaming a file
+const DEFAULT_LOOKAHEAD_BUFFER_SIZE: usize = 64;
+
+/// Thumbnail decoded from a file as it was when last modified at `modified`
+#[derive(Debug, Clone)]
+struct CachedThumbnail {
+    modified: SystemTime,
+    thumbnail: Option<Vec<u8>>,
+}
 
 /// File manager for 3D printer operations
 pub struct FileManager {
     watch_paths: Vec<String>,
     file_cache: std::collections::HashMap<String, String>,
+    /// Decoded thumbnails by path, shared between clones
+    thumbnail_cache: Arc<Mutex<HashMap<String, CachedThumbnail>>>,
+    lookahead_buffer_size: usize,
+    keep_comments: bool,
 }
 
 impl FileManager {
@@ -13,6 +44,9 @@ impl FileManager {
         Self {
             watch_paths: vec!["/home/user/printer_files".to_string()],
             file_cache: std::collections::HashMap::new(),
+            thumbnail_cache: Arc::new(Mutex::new(HashMap::new())),
+            lookahead_buffer_size: DEFAULT_LOOKAHEAD_BUFFER_SIZE,
+            keep_comments: false,
         }
     }
 
@@ -22,6 +56,38 @@ impl FileManager {
         Ok(content)
     }
 
+    /// Parse a G-code file line by line without loading it into memory
+    ///
+    /// A background task parses at most `lookahead_buffer_size` commands
+    /// ahead of the consumer. Dropping the stream stops the task.
+    pub async fn stream_gcode(
+        &self,
+        path: &str,
+    ) -> Result<impl Stream<Item = Result<ParsedLine, GCodeError>> + use<>, Box<dyn std::error::Error>> {
+        let file = fs::File::open(path).await?;
+        let (tx, rx) = mpsc::channel(self.lookahead_buffer_size.max(1));
+        let config = GCodeParserConfig { keep_comments: self.keep_comments, ..GCodeParserConfig::default() };
+        tokio::spawn(async move {
+            let mut parser = AsyncGCodeParser::new(BufReader::new(file), config);
+            while let Some(line) = parser.next_command().await {
+                if tx.send(line).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+
+    /// Set how many commands `stream_gcode` may parse ahead
+    pub fn set_lookahead_buffer_size(&mut self, size: usize) {
+        self.lookahead_buffer_size = size.max(1);
+    }
+
+    /// Have `stream_gcode` pass comment-only lines through, e.g. for slicer hints
+    pub fn set_keep_comments(&mut self, keep: bool) {
+        self.keep_comments = keep;
+    }
+
     /// Write a file asynchronously
     pub async fn write_file(&self, path: &str, content: &str) -> Result<(), Box<dyn std::error::Error>> {
         fs::write(path, content).await?;
@@ -35,16 +101,14 @@ impl FileManager {
         
         while let Some(entry) = entries.next_entry().await? {
             let path = entry.path();
-            if let Some(file_name) = path.file_name() {
-                if let Some(name_str) = file_name.to_str() {
-                    let metadata = entry.metadata().await?;
-                    files.push(FileInfo {
-                        name: name_str.to_string(),
-                        size: metadata.len(),
-                        modified: metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH),
-                        is_directory: metadata.is_dir(),
-                    });
-                }
+            if let Some(name_str) = path.file_name().and_then(|name| name.to_str()) {
+                let metadata = entry.metadata().await?;
+                files.push(FileInfo {
+                    name: name_str.to_string(),
+                    size: metadata.len(),
+                    modified: metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH),
+                    is_directory: metadata.is_dir(),
+                });
             }
         }
         
@@ -54,6 +118,7 @@ impl FileManager {
     /// Delete a file
     pub async fn delete_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         fs::remove_file(path).await?;
+        self.thumbnail_cache.lock().unwrap().remove(path);
         Ok(())
     }
 
@@ -82,6 +147,87 @@ impl FileManager {
         })
     }
 
+    /// Read slicer metadata from the header of a G-code file
+    pub async fn parse_metadata(&self, path: &str) -> Result<GCodeMetadata, Box<dyn std::error::Error>> {
+        let mut lines = BufReader::new(fs::File::open(path).await?).lines();
+        let mut header = Vec::new();
+        while header.len() < metadata::METADATA_HEADER_LINES {
+            match lines.next_line().await? {
+                Some(line) => header.push(line),
+                None => break,
+            }
+        }
+        Ok(GCodeMetadata::parse_lines(header.iter().map(String::as_str)))
+    }
+
+    /// Decode the thumbnail embedded in a G-code file, if any
+    ///
+    /// Only the leading comment block is read, where slicers place
+    /// thumbnails. Results are cached until the file is modified or deleted.
+    pub async fn extract_thumbnail(&self, path: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        let modified = fs::metadata(path).await?.modified()?;
+        if let Some(cached) = self.thumbnail_cache.lock().unwrap().get(path)
+            && cached.modified == modified
+        {
+            return Ok(cached.thumbnail.clone());
+        }
+
+        let mut lines = BufReader::new(fs::File::open(path).await?).lines();
+        let mut header = Vec::new();
+        while let Some(line) = lines.next_line().await? {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with(';') {
+                break;
+            }
+            header.push(line);
+        }
+        let thumbnail = metadata::extract_thumbnail(header.iter().map(String::as_str));
+        self.thumbnail_cache
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), CachedThumbnail { modified, thumbnail: thumbnail.clone() });
+        Ok(thumbnail)
+    }
+
+    /// Hex-encoded SHA-256 digest of a file, read in 64 KB chunks
+    pub async fn compute_checksum(&self, path: &str) -> Result<String, io::Error> {
+        let mut file = fs::File::open(path).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; CHECKSUM_CHUNK_SIZE];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Whether a file's SHA-256 digest matches `expected` (hex, any case)
+    pub async fn verify_checksum(&self, path: &str, expected: &str) -> Result<bool, io::Error> {
+        let actual = self.compute_checksum(path).await?;
+        Ok(actual.eq_ignore_ascii_case(expected.trim()))
+    }
+
+    /// Compute a file's checksum and store it in the `<name>.sha256` sidecar
+    ///
+    /// Called once a file has been received so later corruption is detected.
+    pub async fn store_checksum(&self, path: &str) -> Result<String, io::Error> {
+        let checksum = self.compute_checksum(path).await?;
+        fs::write(checksum_sidecar_path(path), format!("{}\n", checksum)).await?;
+        Ok(checksum)
+    }
+
+    /// The digest stored in a file's sidecar, if it has one
+    pub async fn get_stored_checksum(&self, path: &str) -> Result<Option<String>, io::Error> {
+        match fs::read_to_string(checksum_sidecar_path(path)).await {
+            Ok(contents) => Ok(contents.split_whitespace().next().map(str::to_string)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Cache a file in memory
     pub async fn cache_file(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
         let content = self.read_file(path).await?;
@@ -100,15 +246,29 @@ impl FileManager {
     }
 }
 
+impl Default for FileManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Clone for FileManager {
     fn clone(&self) -> Self {
         Self {
             watch_paths: self.watch_paths.clone(),
             file_cache: std::collections::HashMap::new(), // Don't clone cache
+            thumbnail_cache: self.thumbnail_cache.clone(),
+            lookahead_buffer_size: self.lookahead_buffer_size,
+            keep_comments: self.keep_comments,
         }
     }
 }
 
+/// Sidecar file holding the stored checksum of `path`
+pub fn checksum_sidecar_path(path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.sha256", path))
+}
+
 /// File information structure
 #[derive(Debug, Clone)]
 pub struct FileInfo {
@@ -116,4 +276,109 @@ pub struct FileInfo {
     pub size: u64,
     pub modified: std::time::SystemTime,
     pub is_directory: bool,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checksum_detects_modification() {
+        let dir = std::env::temp_dir().join(format!("krusty-checksum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("part.gcode");
+        let path_str = path.to_str().unwrap();
+        std::fs::write(&path, "G28\nG1 X10 Y10 F3000\n").unwrap();
+
+        let manager = FileManager::new();
+        let checksum = manager.store_checksum(path_str).await.unwrap();
+        assert_eq!(checksum.len(), 64);
+        assert_eq!(manager.get_stored_checksum(path_str).await.unwrap(), Some(checksum.clone()));
+        assert!(manager.verify_checksum(path_str, &checksum).await.unwrap());
+
+        // Flip a single byte: X10 -> X11
+        std::fs::write(&path, "G28\nG1 X11 Y10 F3000\n").unwrap();
+        assert!(!manager.verify_checksum(path_str, &checksum).await.unwrap());
+
+        // Known digest of the empty input
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(
+            manager.compute_checksum(path_str).await.unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_thumbnail_cached_until_deleted() {
+        let dir = std::env::temp_dir().join(format!("krusty-thumbnail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("part.gcode");
+        let path_str = path.to_str().unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/thumbnail.gcode"), &path).unwrap();
+
+        let manager = FileManager::new();
+        let thumbnail = manager.extract_thumbnail(path_str).await.unwrap().unwrap();
+        assert!(thumbnail.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(manager.clone().thumbnail_cache.lock().unwrap().contains_key(path_str));
+
+        manager.delete_file(path_str).await.unwrap();
+        assert!(manager.thumbnail_cache.lock().unwrap().is_empty());
+        assert!(manager.extract_thumbnail(path_str).await.is_err());
+
+        std::fs::write(&path, "G28\n").unwrap();
+        assert_eq!(manager.extract_thumbnail(path_str).await.unwrap(), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Resident set size of this process in kB
+    #[cfg(target_os = "linux")]
+    fn resident_kb() -> u64 {
+        std::fs::read_to_string("/proc/self/status")
+            .unwrap()
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|value| value.split_whitespace().next()?.parse().ok())
+            .unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_stream_large_file_in_bounded_memory() {
+        use std::io::Write;
+        use tokio_stream::StreamExt;
+
+        let dir = std::env::temp_dir().join(format!("krusty-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("large.gcode");
+        let mut lines = 0;
+        {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+            let mut written = 0;
+            while written < 10 * 1024 * 1024 {
+                let line = format!("G1 X{:.3} Y{:.3} E{:.5} F3000 ; move {}\n", lines % 200, lines % 150, lines as f64 * 0.01, lines);
+                file.write_all(line.as_bytes()).unwrap();
+                written += line.len();
+                lines += 1;
+            }
+        }
+
+        let before = resident_kb();
+        let mut stream = FileManager::new().stream_gcode(path.to_str().unwrap()).await.unwrap();
+        let mut commands = 0;
+        let mut peak = before;
+        while let Some(line) = stream.next().await {
+            assert!(line.unwrap().command.starts_with("G1"));
+            commands += 1;
+            if commands % 10_000 == 0 {
+                peak = peak.max(resident_kb());
+            }
+        }
+
+        assert_eq!(commands, lines);
+        assert!(peak - before < 20 * 1024, "RSS grew by {} kB", peak - before);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
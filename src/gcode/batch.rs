@@ -0,0 +1,156 @@
+// src/gcode/batch.rs - Command batches run back to back
+use std::collections::BTreeMap;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use super::GCodeProcessor;
+
+/// Longest command a batch may hold (characters)
+pub const MAX_BATCH_COMMAND_LEN: usize = 250;
+/// Most commands in one batch
+pub const MAX_BATCH_COMMANDS: usize = 500;
+/// Finished and pending batches whose status is kept
+const TRACKED_BATCHES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchState {
+    /// Waiting for earlier batches or commands to finish
+    Queued,
+    Running,
+    Completed,
+    /// A command failed; the rest of the batch was skipped
+    Failed,
+}
+
+/// Progress of a submitted batch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GCodeBatchStatus {
+    pub id: u64,
+    pub state: BatchState,
+    /// Commands in the batch
+    pub total: usize,
+    /// Commands run so far
+    pub executed: usize,
+    /// Why the batch failed
+    pub error: Option<String>,
+}
+
+/// Batches waiting to run and the status of recent ones
+#[derive(Debug, Default)]
+pub(super) struct BatchQueue {
+    next_id: u64,
+    statuses: BTreeMap<u64, GCodeBatchStatus>,
+    /// Feeds the task running batches, started by the first submission
+    runner: Option<mpsc::UnboundedSender<(u64, Vec<String>)>>,
+}
+
+/// Check a batch against the size limits
+pub fn validate_batch(commands: &[String]) -> Result<(), String> {
+    if commands.is_empty() {
+        return Err("Batch has no commands".to_string());
+    }
+    if commands.len() > MAX_BATCH_COMMANDS {
+        return Err(format!("Batch has {} commands, at most {} are allowed", commands.len(), MAX_BATCH_COMMANDS));
+    }
+    if let Some(index) = commands.iter().position(|command| command.chars().count() > MAX_BATCH_COMMAND_LEN) {
+        return Err(format!("Command {} is longer than {} characters", index + 1, MAX_BATCH_COMMAND_LEN));
+    }
+    if let Some(index) = commands.iter().position(|command| command.contains(['\n', '\r'])) {
+        return Err(format!("Command {} spans several lines", index + 1));
+    }
+    Ok(())
+}
+
+impl GCodeProcessor {
+    /// Queue `commands` to run one after another, returning the batch ID
+    ///
+    /// Batches run in submission order, each holding the command lock
+    /// throughout so nothing else runs between its commands. A failing
+    /// command ends its batch.
+    pub fn submit_batch(&self, commands: Vec<String>) -> Result<u64, String> {
+        validate_batch(&commands)?;
+        // Not queued behind the batch that may be waiting for it
+        let cancelled = self.run_cancel_wait(&commands);
+        let mut batches = self.batches.lock().unwrap();
+        batches.next_id += 1;
+        let id = batches.next_id;
+        batches.statuses.insert(id, GCodeBatchStatus {
+            id,
+            state: if cancelled { BatchState::Completed } else { BatchState::Queued },
+            total: commands.len(),
+            executed: if cancelled { commands.len() } else { 0 },
+            error: None,
+        });
+        while batches.statuses.len() > TRACKED_BATCHES {
+            batches.statuses.pop_first();
+        }
+        if cancelled {
+            return Ok(id);
+        }
+
+        let runner = match &batches.runner {
+            Some(runner) if !runner.is_closed() => runner.clone(),
+            _ => {
+                let (runner, batches_rx) = mpsc::unbounded_channel();
+                tokio::spawn(self.clone().run_batches(batches_rx));
+                batches.runner = Some(runner.clone());
+                runner
+            }
+        };
+        runner.send((id, commands)).map_err(|_| "Batch runner stopped".to_string())?;
+        Ok(id)
+    }
+
+    /// Progress of a recent batch, `None` if it is unknown or too old
+    pub fn batch_status(&self, id: u64) -> Option<GCodeBatchStatus> {
+        self.batches.lock().unwrap().statuses.get(&id).cloned()
+    }
+
+    async fn run_batches(mut self, mut batches_rx: mpsc::UnboundedReceiver<(u64, Vec<String>)>) {
+        while let Some((id, commands)) = batches_rx.recv().await {
+            let _commands = self.lock_commands().await;
+            self.update_batch(id, |status| status.state = BatchState::Running);
+            for command in &commands {
+                // Errors are stringified so they aren't held across an await
+                let result = self.process_command(command).await.map_err(|e| e.to_string());
+                match result {
+                    Ok(()) => self.update_batch(id, |status| status.executed += 1),
+                    Err(e) => {
+                        tracing::warn!("G-code batch {} stopped at {}: {}", id, command, e);
+                        self.update_batch(id, |status| {
+                            status.state = BatchState::Failed;
+                            status.error = Some(format!("{}: {}", command, e));
+                        });
+                        break;
+                    }
+                }
+            }
+            self.update_batch(id, |status| {
+                if status.state == BatchState::Running {
+                    status.state = BatchState::Completed;
+                }
+            });
+        }
+    }
+
+    fn update_batch(&self, id: u64, update: impl FnOnce(&mut GCodeBatchStatus)) {
+        if let Some(status) = self.batches.lock().unwrap().statuses.get_mut(&id) {
+            update(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_batch() {
+        assert!(validate_batch(&["G28".to_string(), "G29".to_string()]).is_ok());
+        assert!(validate_batch(&[]).is_err());
+        assert!(validate_batch(&vec!["G4 P0".to_string(); MAX_BATCH_COMMANDS + 1]).is_err());
+        let error = validate_batch(&["G28".to_string(), "M117 ".to_string() + &"x".repeat(250)]).unwrap_err();
+        assert_eq!(error, "Command 2 is longer than 250 characters");
+        assert!(validate_batch(&["G28\nG29".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// src/gcode/history.rs - Ring buffer of executed G-code commands
+use std::collections::VecDeque;
+use serde::Serialize;
+
+/// Entries kept when no size is configured
+pub const DEFAULT_HISTORY_CAPACITY: usize = 1000;
+
+/// How a command went
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", content = "message", rename_all = "lowercase")]
+pub enum GCodeHistoryResult {
+    Ok,
+    /// Accepted but not acted on, e.g. an unknown command
+    Warn(String),
+    Err(String),
+}
+
+impl GCodeHistoryResult {
+    /// `ok`, `warn` or `err`
+    pub fn status(&self) -> &'static str {
+        match self {
+            GCodeHistoryResult::Ok => "ok",
+            GCodeHistoryResult::Warn(_) => "warn",
+            GCodeHistoryResult::Err(_) => "err",
+        }
+    }
+}
+
+/// One executed command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GCodeHistoryEntry {
+    /// Unix milliseconds when execution started
+    pub timestamp: u64,
+    pub command: String,
+    pub result: GCodeHistoryResult,
+    pub duration_us: u64,
+}
+
+/// The most recent `capacity` commands, oldest first
+#[derive(Debug, Clone)]
+pub struct GCodeHistory {
+    entries: VecDeque<GCodeHistoryEntry>,
+    capacity: usize,
+}
+
+impl GCodeHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity.min(DEFAULT_HISTORY_CAPACITY)),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, entry: GCodeHistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Up to `limit` entries after skipping `offset`, newest first,
+    /// optionally only those with the given status
+    pub fn query(&self, status: Option<&str>, offset: usize, limit: usize) -> Vec<GCodeHistoryEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| status.is_none_or(|status| entry.result.status() == status))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Every entry, oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &GCodeHistoryEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for GCodeHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, result: GCodeHistoryResult) -> GCodeHistoryEntry {
+        GCodeHistoryEntry {
+            timestamp: 0,
+            command: command.to_string(),
+            result,
+            duration_us: 0,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut history = GCodeHistory::new(3);
+        for i in 0..5 {
+            history.push(entry(&format!("G1 X{}", i), GCodeHistoryResult::Ok));
+        }
+        assert_eq!(history.len(), 3);
+        let commands: Vec<_> = history.query(None, 0, 10).into_iter().map(|entry| entry.command).collect();
+        assert_eq!(commands, ["G1 X4", "G1 X3", "G1 X2"]);
+        assert_eq!(history.query(None, 1, 1)[0].command, "G1 X3");
+
+        let json = serde_json::to_value(entry("G1", GCodeHistoryResult::Warn("odd".to_string()))).unwrap();
+        assert_eq!(json["result"], serde_json::json!({ "status": "warn", "message": "odd" }));
+    }
+}
@@ -0,0 +1,166 @@
+// src/gcode/meta.rs - Layer and progress hints slicers leave in comments
+use serde::{Deserialize, Serialize};
+
+/// What the value after a recognized comment prefix means
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GCodeMetaField {
+    /// Layer about to be printed, numbered from the pattern's `first_layer`
+    Layer,
+    TotalLayers,
+    /// Print time left (seconds)
+    RemainingSecs,
+    /// Print time so far (seconds)
+    ElapsedSecs,
+    /// Estimated time of the whole print (seconds)
+    TotalSecs,
+}
+
+/// A structured comment, matched against the comment text with the leading
+/// `;` and surrounding whitespace removed
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GCodeMetaPattern {
+    pub prefix: String,
+    pub field: GCodeMetaField,
+    /// Number the slicer gives the first layer, for `Layer` patterns
+    #[serde(default = "default_first_layer")]
+    pub first_layer: u32,
+}
+
+impl GCodeMetaPattern {
+    fn new(prefix: &str, field: GCodeMetaField, first_layer: u32) -> Self {
+        Self { prefix: prefix.to_string(), field, first_layer }
+    }
+}
+
+fn default_first_layer() -> u32 { 1 }
+
+/// Patterns recognized without any configuration
+pub fn default_meta_patterns() -> Vec<GCodeMetaPattern> {
+    use GCodeMetaField::*;
+    vec![
+        // PrusaSlicer
+        GCodeMetaPattern::new("layer_num", Layer, 0),
+        GCodeMetaPattern::new("remaining_time", RemainingSecs, 1),
+        GCodeMetaPattern::new("print_time_remaining", RemainingSecs, 1),
+        // Cura
+        GCodeMetaPattern::new("LAYER_COUNT:", TotalLayers, 1),
+        GCodeMetaPattern::new("LAYER:", Layer, 0),
+        GCodeMetaPattern::new("TIME_ELAPSED:", ElapsedSecs, 1),
+        GCodeMetaPattern::new("TIME:", TotalSecs, 1),
+        // Bambu Studio
+        GCodeMetaPattern::new("total layers count", TotalLayers, 1),
+        GCodeMetaPattern::new("current layer", Layer, 1),
+    ]
+}
+
+/// A hint extracted from a structured comment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GCodeMetaCommand {
+    /// Layer about to be printed, counting the first as 1
+    Layer(u32),
+    TotalLayers(u32),
+    RemainingSecs(f64),
+    ElapsedSecs(f64),
+    TotalSecs(f64),
+}
+
+impl GCodeMetaCommand {
+    /// Recognize a comment line using the first pattern whose prefix matches
+    pub fn parse(line: &str, patterns: &[GCodeMetaPattern]) -> Option<Self> {
+        let comment = line.trim().strip_prefix(';')?.trim();
+        let (pattern, rest) = patterns
+            .iter()
+            .find_map(|pattern| Some((pattern, comment.strip_prefix(pattern.prefix.as_str())?)))?;
+        let value: f64 = rest.trim_start_matches([' ', ':', '=']).split_whitespace().next()?.parse().ok()?;
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        Some(match pattern.field {
+            GCodeMetaField::Layer => {
+                GCodeMetaCommand::Layer((value as u32 + 1).saturating_sub(pattern.first_layer).max(1))
+            }
+            GCodeMetaField::TotalLayers => GCodeMetaCommand::TotalLayers(value as u32),
+            GCodeMetaField::RemainingSecs => GCodeMetaCommand::RemainingSecs(value),
+            GCodeMetaField::ElapsedSecs => GCodeMetaCommand::ElapsedSecs(value),
+            GCodeMetaField::TotalSecs => GCodeMetaCommand::TotalSecs(value),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(gcode: &str) -> Vec<GCodeMetaCommand> {
+        let patterns = default_meta_patterns();
+        gcode.lines().filter_map(|line| GCodeMetaCommand::parse(line, &patterns)).collect()
+    }
+
+    #[test]
+    fn test_prusaslicer_hints() {
+        let gcode = "\
+;LAYER_CHANGE
+;Z:0.2
+; layer_num 0
+G1 X10 Y10 E1
+; remaining_time 3600
+; layer_num 1
+; print_time_remaining 3540
+";
+        assert_eq!(parse_all(gcode), [
+            GCodeMetaCommand::Layer(1),
+            GCodeMetaCommand::RemainingSecs(3600.0),
+            GCodeMetaCommand::Layer(2),
+            GCodeMetaCommand::RemainingSecs(3540.0),
+        ]);
+    }
+
+    #[test]
+    fn test_cura_hints() {
+        let gcode = "\
+;FLAVOR:Marlin
+;TIME:5025
+;LAYER_COUNT:200
+;LAYER:0
+G1 X10 Y10 E1
+;TIME_ELAPSED:12.5
+;LAYER:1
+";
+        assert_eq!(parse_all(gcode), [
+            GCodeMetaCommand::TotalSecs(5025.0),
+            GCodeMetaCommand::TotalLayers(200),
+            GCodeMetaCommand::Layer(1),
+            GCodeMetaCommand::ElapsedSecs(12.5),
+            GCodeMetaCommand::Layer(2),
+        ]);
+    }
+
+    #[test]
+    fn test_bambu_studio_hints() {
+        let gcode = "\
+; total layers count = 150
+; CHANGE_LAYER
+; current layer = 1
+; Z_HEIGHT: 0.2
+; current layer = 2
+";
+        assert_eq!(parse_all(gcode), [
+            GCodeMetaCommand::TotalLayers(150),
+            GCodeMetaCommand::Layer(1),
+            GCodeMetaCommand::Layer(2),
+        ]);
+    }
+
+    #[test]
+    fn test_custom_pattern() {
+        let config: crate::config::Config = toml::from_str(
+            "[[gcode_meta_patterns]]\nprefix = \"LAYER #\"\nfield = \"layer\"\nfirst_layer = 0\n",
+        )
+        .unwrap();
+        let patterns = config.gcode_meta_patterns;
+        assert_eq!(GCodeMetaCommand::parse(";LAYER #4", &patterns), Some(GCodeMetaCommand::Layer(5)));
+        assert_eq!(GCodeMetaCommand::parse(";LAYER #x", &patterns), None);
+        assert_eq!(GCodeMetaCommand::parse("G1 X1", &patterns), None);
+    }
+}
@@ -1,13 +1,137 @@
 // src/gcode/mod.rs - Use the state field
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use crate::printer::PrinterState;
-use crate::motion::MotionController;
+pub mod batch;
+pub mod history;
+pub mod meta;
+pub mod parser;
+pub mod pause;
+pub mod variables;
+pub mod wipe;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Notify, OwnedMutexGuard, RwLock, broadcast};
+use tokio::task::AbortHandle;
+use crate::eeprom::EepromManager;
+use crate::post_print::PostPrintRoutine;
+use crate::printer::{ExtruderMode, PositioningMode, PrinterEvent, PrinterState};
+use crate::motion::{HangprinterCalibrator, MotionController, MotionError, MotionMode, MotionRecorder, MotionSegment, MotionType, ShaperPreset, ShaperPresetLibrary, StepPositionDrift};
+use crate::motion::delta_calibration::DeltaCalibrator;
+use crate::motion::kinematics::{DeltaKinematics, KinematicsType, SkewCorrection};
+use crate::motion::shaper_presets::suggest_from_frequency;
+use crate::config::{DeltaConfig, FanControlMode, FanCurvePoint, HangprinterConfig};
+use crate::file::FileManager;
+use crate::config::StallRecovery;
+use crate::hardware::{BLTouchProbe, StepperStallDetector};
+use crate::print_job::{PrintJob, PrintJobEvent, PrintJobState};
+use crate::temperature::ToolHeater;
+use tokio_stream::StreamExt;
+use parser::{AsyncGCodeParser, GCodeParser, GCodeParserConfig};
+use batch::BatchQueue;
+use history::{GCodeHistory, GCodeHistoryEntry, GCodeHistoryResult};
+use meta::{GCodeMetaCommand, GCodeMetaPattern};
+use pause::PauseAtCondition;
+use variables::VariableScope;
+use wipe::{NozzleWipe, WipePattern};
+
+/// Minimum XY travel distance (mm) before a Z-hop is inserted
+const Z_HOP_MIN_TRAVEL: f64 = 1.0;
+
+/// How often hotends are checked while waiting for them to heat
+const TEMPERATURE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a paused print checks whether it was resumed
+const RESUME_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How far M600 raises the nozzle before parking, unless given Z (mm)
+const FILAMENT_CHANGE_Z_LIFT: f64 = 5.0;
+
+/// How far the nozzle is raised off the print before rehoming a stalled
+/// axis (mm)
+const STALL_RECOVERY_Z_LIFT: f64 = 2.0;
+
+/// Reverse purge after a clog: retract, then push through a little more (mm, mm/s)
+const CLOG_PURGE_RETRACT: (f64, f64) = (30.0, 10.0);
+const CLOG_PURGE_ADVANCE: (f64, f64) = (35.0, 2.0);
+
+/// Deepest M98 subroutine calls may nest
+const MAX_SUBROUTINE_DEPTH: usize = 16;
+
+/// Slowest the minimum layer time may make moves, as a feedrate factor
+const MIN_LAYER_FEEDRATE_FACTOR: f64 = 0.1;
+
+/// Speed between Hangprinter calibration points (mm/s)
+const HANGPRINTER_CALIBRATION_SPEED: f64 = 50.0;
+
+/// Calibration points visited when CALIBRATE_HANGPRINTER is not given N
+const DEFAULT_HANGPRINTER_CALIBRATION_POINTS: usize = 9;
+
+/// Points G33 probes when not given P
+const DEFAULT_DELTA_PROBE_POINTS: usize = 13;
+
+/// Radius G33 probes within, as a fraction of the delta's print radius
+const DELTA_PROBE_RADIUS_FRACTION: f64 = 0.9;
+
+/// Outcome of simulating a G-code file without moving hardware
+#[derive(Debug, Clone)]
+pub struct DryRunReport {
+    /// Commands processed
+    pub commands: usize,
+
+    /// Toolhead travel in XYZ (mm)
+    pub total_distance: f64,
+
+    /// Estimated print time at the requested feedrates (seconds)
+    pub estimated_time: f64,
+
+    /// Position at the end of the file [X, Y, Z, E]
+    pub final_position: [f64; 4],
+
+    /// Printer state at the end of the file
+    pub final_state: PrinterState,
+}
 
 #[derive(Debug, Clone)]
 pub struct GCodeProcessor {
     state: Arc<RwLock<PrinterState>>,
     motion_controller: MotionController,
+    /// Shared by all clones
+    history: Arc<Mutex<GCodeHistory>>,
+    /// Woken by M108 to end whatever the printer is waiting for
+    cancel_wait: Arc<Notify>,
+    /// Run after the last line of a printed file
+    post_print: Option<PostPrintRoutine>,
+    /// Where calibration changes are saved
+    settings: Option<EepromManager>,
+    /// Pending standby drops by tool, shared by all clones
+    standby_tasks: Arc<Mutex<HashMap<usize, AbortHandle>>>,
+    /// Checked between lines of a printed file
+    stall_detector: Option<Arc<StepperStallDetector>>,
+    /// Where G12 wipes the nozzle
+    nozzle_wipe: Option<NozzleWipe>,
+    /// Slicer comments read for layer and progress hints
+    meta_patterns: Arc<Vec<GCodeMetaPattern>>,
+    /// Input shaper presets for M593, shared by all clones
+    shaper_presets: Arc<Mutex<ShaperPresetLibrary>>,
+    /// Where added presets are saved
+    shaper_presets_file: Option<Arc<Path>>,
+    /// Anchors and line encoders for CALIBRATE_HANGPRINTER
+    hangprinter: Option<HangprinterConfig>,
+    /// Delta geometry G33 calibrates, shared by all clones
+    delta: Option<Arc<Mutex<DeltaConfig>>>,
+    /// SET_VAR variables, scoped to M98 calls; shared by all clones
+    variables: Arc<Mutex<VariableScope>>,
+    /// Held by whoever is running commands that must not be interleaved
+    /// with others; shared by all clones
+    command_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Submitted batches, shared by all clones
+    batches: Arc<Mutex<BatchQueue>>,
+    /// Frames lines streamed by a host and tracks their line numbers,
+    /// shared by all clones
+    host_parser: Arc<Mutex<GCodeParser>>,
+    /// Where refused host lines are reported
+    events: Option<broadcast::Sender<PrinterEvent>>,
 }
 
 impl GCodeProcessor {
@@ -18,37 +142,346 @@ impl GCodeProcessor {
         Self {
             state,
             motion_controller,
+            history: Arc::new(Mutex::new(GCodeHistory::default())),
+            cancel_wait: Arc::new(Notify::new()),
+            post_print: None,
+            settings: None,
+            standby_tasks: Arc::new(Mutex::new(HashMap::new())),
+            stall_detector: None,
+            nozzle_wipe: None,
+            meta_patterns: Arc::new(meta::default_meta_patterns()),
+            shaper_presets: Arc::new(Mutex::new(ShaperPresetLibrary::default())),
+            shaper_presets_file: None,
+            hangprinter: None,
+            delta: None,
+            variables: Arc::new(Mutex::new(VariableScope::new())),
+            command_lock: Arc::new(tokio::sync::Mutex::new(())),
+            batches: Arc::new(Mutex::new(BatchQueue::default())),
+            host_parser: Arc::new(Mutex::new(GCodeParser::new(GCodeParserConfig::default()))),
+            events: None,
+        }
+    }
+
+    /// Frame lines from `process_host_line` by `config`
+    pub fn with_host_framing(mut self, config: GCodeParserConfig) -> Self {
+        self.host_parser = Arc::new(Mutex::new(GCodeParser::new(config)));
+        self
+    }
+
+    /// Send a `GCodeError` event on `events` for each refused host line
+    pub fn with_event_sender(mut self, events: broadcast::Sender<PrinterEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Save calibration changes such as M92 to `settings`
+    pub fn with_settings(mut self, settings: EepromManager) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// Watch for stepper stalls while printing files
+    pub fn with_stall_detector(mut self, detector: StepperStallDetector) -> Self {
+        self.stall_detector = Some(Arc::new(detector));
+        self
+    }
+
+    /// Let G12 wipe the nozzle on `wipe`
+    pub fn with_nozzle_wipe(mut self, wipe: NozzleWipe) -> Self {
+        self.nozzle_wipe = Some(wipe);
+        self
+    }
+
+    /// Let CALIBRATE_HANGPRINTER measure with the line encoders in `config`
+    pub fn with_hangprinter(mut self, config: HangprinterConfig) -> Self {
+        self.hangprinter = Some(config);
+        self
+    }
+
+    /// Let G33 calibrate the delta geometry in `config`
+    pub fn with_delta(mut self, config: DeltaConfig) -> Self {
+        self.delta = Some(Arc::new(Mutex::new(config)));
+        self
+    }
+
+    /// Also read hints from `patterns`, which take precedence over the built-in ones
+    pub fn with_meta_patterns(mut self, patterns: Vec<GCodeMetaPattern>) -> Self {
+        self.meta_patterns = Arc::new(patterns.into_iter().chain(meta::default_meta_patterns()).collect());
+        self
+    }
+
+    /// Start from `library` for M593 presets, saving additions to `file`
+    pub fn with_shaper_presets(mut self, library: ShaperPresetLibrary, file: Option<&Path>) -> Self {
+        self.shaper_presets = Arc::new(Mutex::new(library));
+        self.shaper_presets_file = file.map(Arc::from);
+        self
+    }
+
+    pub fn shaper_presets(&self) -> Vec<ShaperPreset> {
+        self.shaper_presets.lock().unwrap().list_presets().to_vec()
+    }
+
+    /// Add or replace a preset and save the library
+    pub fn add_shaper_preset(&self, preset: ShaperPreset) -> Result<(), Box<dyn std::error::Error>> {
+        let mut library = self.shaper_presets.lock().unwrap();
+        library.add_preset(preset)?;
+        if let Some(file) = &self.shaper_presets_file {
+            library.save_to_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// Motor steps per mm for [X, Y, Z, E]
+    pub fn steps_per_mm(&self) -> [f64; 4] {
+        self.motion_controller.steps_per_mm()
+    }
+
+    /// Run `routine` whenever a print finishes
+    pub fn with_post_print(mut self, routine: PostPrintRoutine) -> Self {
+        self.post_print = Some(routine);
+        self
+    }
+
+    /// Run the post-print routine, if there is one
+    pub async fn finish_print(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.post_print.clone() {
+            Some(routine) => routine.run(self).await,
+            None => Ok(()),
         }
     }
 
+    /// Notified when M108 asks to stop waiting
+    pub fn cancel_wait_signal(&self) -> Arc<Notify> {
+        self.cancel_wait.clone()
+    }
+
+    /// Keep the last `capacity` commands in the history instead of the default
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history = Arc::new(Mutex::new(GCodeHistory::new(capacity)));
+        self
+    }
+
+    /// Execute one command and record it in the history
     pub async fn process_command(&mut self, command: &str) -> Result<(), Box<dyn std::error::Error>> {
         let command = command.trim();
-        if command.is_empty() || command.starts_with(';') {
+        if command.starts_with(';') {
+            self.apply_meta_comment(command).await;
             return Ok(());
         }
-        
+        if command.is_empty() {
+            return Ok(());
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_millis() as u64).unwrap_or(0);
+        let started = Instant::now();
+        let result = self.execute_command(command).await;
+        let entry = GCodeHistoryEntry {
+            timestamp,
+            command: command.to_string(),
+            result: match &result {
+                Ok(None) => GCodeHistoryResult::Ok,
+                Ok(Some(warning)) => GCodeHistoryResult::Warn(warning.clone()),
+                Err(e) => GCodeHistoryResult::Err(e.to_string()),
+            },
+            duration_us: started.elapsed().as_micros() as u64,
+        };
+        self.history.lock().unwrap().push(entry);
+        result.map(|_| ())
+    }
+
+    pub async fn motion_mode(&self) -> MotionMode {
+        self.motion_controller.motion_mode().await
+    }
+
+    /// Switch motion mode between commands, see
+    /// `MotionController::set_motion_mode`
+    pub async fn set_motion_mode(&mut self, mode: MotionMode) -> Result<(), MotionError> {
+        let _commands = self.lock_commands().await;
+        self.motion_controller.set_motion_mode(mode).await
+    }
+
+    /// Run `commands` now if they are all M108, without the command lock
+    ///
+    /// M108 ends the wait of an M600, M191 or the like, which holds the
+    /// lock until it is over, so it can't queue for the lock behind it.
+    /// Returns whether the commands were run.
+    pub fn run_cancel_wait<S: AsRef<str>>(&self, commands: &[S]) -> bool {
+        let is_cancel = |command: &S| {
+            let code = command.as_ref().split(';').next().unwrap_or("").trim();
+            code.eq_ignore_ascii_case("M108")
+        };
+        if commands.is_empty() || !commands.iter().all(is_cancel) {
+            return false;
+        }
+        for command in commands {
+            self.cancel_waits();
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_millis() as u64).unwrap_or(0);
+            self.history.lock().unwrap().push(GCodeHistoryEntry {
+                timestamp,
+                command: command.as_ref().trim().to_string(),
+                result: GCodeHistoryResult::Ok,
+                duration_us: 0,
+            });
+        }
+        true
+    }
+
+    /// Run `commands` in order as homing moves and wait for the moves to
+    /// finish, stopping at the first that fails
+    ///
+    /// For the startup and shutdown sequences: moves aren't refused
+    /// before homing and other sources' commands wait until the end.
+    pub async fn run_gcode_sequence(&mut self, commands: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let _commands = self.lock_commands().await;
+        self.motion_controller.set_motion_type_override(Some(MotionType::Home));
+        let mut result = Ok(());
+        for command in commands {
+            // Errors are stringified so they aren't held across an await
+            result = self.process_command(command).await.map_err(|e| format!("{}: {}", command, e));
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            result = self.motion_controller.wait_for_queue_empty().await.map_err(|e| e.to_string());
+        }
+        self.motion_controller.set_motion_type_override(None);
+        Ok(result?)
+    }
+
+    /// Frame and execute one line streamed by a host, such as
+    /// `N12 G1 X10*98`
+    ///
+    /// With checksums and strict line numbers enabled, a line out of
+    /// sequence is refused with a `LineNumberMismatch` whose
+    /// `resend_response` asks the host for the expected line. M110
+    /// restarts the sequence. Refused lines are also sent as `GCodeError`
+    /// events.
+    pub async fn process_host_line(&mut self, raw: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let parsed = self.host_parser.lock().unwrap().parse_line(raw);
+        match parsed {
+            Ok(Some(line)) => self.process_command(&line.command).await,
+            Ok(None) => Ok(()),
+            Err(e) => {
+                tracing::warn!("Refusing streamed line {}: {}", raw.trim(), e);
+                if let Some(events) = &self.events {
+                    let _ = events.send(PrinterEvent::GCodeError(e.clone()));
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Wait for the command lock, keeping other sources' commands out
+    /// until the guard is dropped
+    ///
+    /// Waiters get the lock in the order they asked for it.
+    pub async fn lock_commands(&self) -> OwnedMutexGuard<()> {
+        self.command_lock.clone().lock_owned().await
+    }
+
+    /// Type of the kinematics moves are converted with
+    pub fn kinematics_type(&self) -> KinematicsType {
+        self.motion_controller.get_planner().kinematics_type()
+    }
+
+    /// Switch kinematics once queued moves finish, between commands
+    pub async fn set_kinematics(&mut self, kinematics_type: KinematicsType) -> Result<(), Box<dyn std::error::Error>> {
+        let _commands = self.lock_commands().await;
+        self.motion_controller.set_kinematics(kinematics_type).await
+    }
+
+    /// Value of a SET_VAR variable as seen from the current scope
+    pub fn get_variable(&self, name: &str) -> Option<f64> {
+        self.variables.lock().unwrap().get(name)
+    }
+
+    /// Executed commands newest first, see `GCodeHistory::query`
+    pub fn query_history(&self, status: Option<&str>, offset: usize, limit: usize) -> Vec<GCodeHistoryEntry> {
+        self.history.lock().unwrap().query(status, offset, limit)
+    }
+
+    /// Every command in the history, oldest first
+    pub fn history_entries(&self) -> Vec<GCodeHistoryEntry> {
+        self.history.lock().unwrap().entries().cloned().collect()
+    }
+
+    /// Dispatch a command, returning a warning if it was not acted on
+    async fn execute_command(&mut self, command: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
         let parts: Vec<&str> = command.split_whitespace().collect();
         
         if parts.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
         
+        self.state.write().await.gcode_commands += 1;
+        
         match parts[0].to_uppercase().as_str() {
-            "G0" | "G1" => self.handle_linear_move(&parts).await?,
+            "G0" | "G1" => {
+                // Segments point back at the command for debugging
+                self.motion_controller.set_segment_label(Some(command.to_string()));
+                let result = self.handle_linear_move(&parts).await;
+                self.motion_controller.set_segment_label(None);
+                result?
+            }
+            "G4" => self.handle_dwell(&parts).await?,
             "G28" => self.handle_home(&parts).await?,
+            "G90" => self.set_positioning_mode(PositioningMode::Absolute).await,
+            "G91" => self.set_positioning_mode(PositioningMode::Relative).await,
+            "G29" => self.handle_probe_mesh().await?,
+            "G30" => self.handle_probe(&parts).await?,
+            "G33" => self.handle_delta_calibration(&parts).await?,
+            "G12" => self.handle_nozzle_wipe(&parts).await?,
             "G92" => self.handle_set_position(&parts).await?,
+            "M205" => self.handle_set_advanced(&parts).await?,
+            "M208" => self.handle_set_z_hop(&parts).await?,
+            "M852" => self.handle_set_skew(&parts).await?,
+            "CALIBRATE_SKEW" => self.handle_calibrate_skew(&parts).await?,
+            "CALIBRATE_HANGPRINTER" => self.handle_calibrate_hangprinter(&parts).await?,
+            "M92" => self.handle_set_steps_per_mm(&parts)?,
+            "M200" => self.handle_set_filament_diameter(&parts)?,
+            "M203" => self.handle_set_max_flow(&parts)?,
+            "M900" => self.handle_linear_advance(&parts)?,
+            "M572" => self.handle_motion_mode(&parts).await?,
+            "M593" => self.handle_input_shaper(&parts)?,
+            "M926" => self.handle_motion_recording(&parts)?,
+            "M204" => self.handle_set_curve_accel(&parts)?,
+            "M226" => self.handle_pause_at(&parts).await?,
+            "SET_PAUSE_AT_HEIGHT" | "SET_PAUSE_AT_LAYER" => self.handle_set_pause_at(&parts).await?,
+            "M600" => self.handle_filament_change(&parts).await?,
             "M104" => self.handle_set_hotend_temp(&parts).await?,
             "M109" => self.handle_set_hotend_temp_wait(&parts).await?,
             "M140" => self.handle_set_bed_temp(&parts).await?,
             "M190" => self.handle_set_bed_temp_wait(&parts).await?,
-            "M82" => println!("Extruder set to absolute mode"),
-            "M84" => println!("Motors disabled"),
+            "M141" => self.handle_set_chamber_temp(&parts).await?,
+            "M191" => self.handle_set_chamber_temp_wait(&parts).await?,
+            "M82" => self.set_extruder_mode(ExtruderMode::Absolute).await,
+            "M83" => self.set_extruder_mode(ExtruderMode::Relative).await,
+            "M84" => self.motion_controller.disable_motors().await,
+            "M108" => self.cancel_waits(),
+            "M110" => {} // Line numbering is tracked by the parser
+            "M116" => self.handle_wait_for_tools(&parts).await?,
             "M106" => self.handle_fan_on(&parts).await?,
-            "M107" => println!("Fan turned off"),
-            _ => println!("Unhandled G-code: {}", command),
+            "M107" => self.handle_fan_off().await,
+            "M145" => self.handle_set_fan_curve(&parts).await?,
+            "M73" => self.handle_set_progress(&parts).await?,
+            "M300" => println!("Beep"),
+            "SET_VAR" => self.handle_set_variable(&parts)?,
+            "M98" => self.handle_call_subroutine(&parts).await?,
+            "M99" => {
+                if !self.variables.lock().unwrap().pop_scope() {
+                    return Ok(Some("M99 outside a subroutine".to_string()));
+                }
+            }
+            "M400" => self.motion_controller.wait_for_queue_empty().await?,
+            tool if tool.len() > 1 && tool.starts_with('T') => self.handle_tool_change(&tool[1..]).await?,
+            _ => {
+                println!("Unhandled G-code: {}", command);
+                return Ok(Some(format!("Unhandled G-code: {}", parts[0])));
+            }
         }
         
-        Ok(())
+        Ok(None)
     }
 
     async fn handle_linear_move(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
@@ -74,24 +507,287 @@ impl GCodeProcessor {
             }
         }
         
-        // Get current position for relative moves (simplified - assuming absolute)
+        // Resolve targets against the current position according to the active modes.
+        // Unspecified axes stay where they are in either mode.
+        let (positioning_mode, extruder_mode) = {
+            let state = self.state.read().await;
+            (state.positioning_mode, state.extruder_mode)
+        };
         let current_pos = self.get_current_position().await;
-        let target_x = x.unwrap_or(current_pos[0]);
-        let target_y = y.unwrap_or(current_pos[1]);
-        let target_z = z.unwrap_or(current_pos[2]);
+        let resolve = |value: Option<f64>, current: f64| match (value, positioning_mode) {
+            (Some(v), PositioningMode::Absolute) => v,
+            (Some(v), PositioningMode::Relative) => current + v,
+            (None, _) => current,
+        };
+        let target_x = resolve(x, current_pos[0]);
+        let target_y = resolve(y, current_pos[1]);
+        let target_z = resolve(z, current_pos[2]);
         
-        self.motion_controller
-            .queue_linear_move([target_x, target_y, target_z], f, e)
-            .await?;
+        // The motion controller takes E as the amount to extrude for this move
+        let e = match extruder_mode {
+            ExtruderMode::Absolute => e.map(|e| e - current_pos[3]),
+            ExtruderMode::Relative => e,
+        };
+        
+        // Pause before the first extrusion of a layer that a condition asks
+        // for, and slow the layer down if the last one was too quick
+        if e.is_some_and(|e| e > 0.0) {
+            let (pause, layer_time) = {
+                let mut state = self.state.write().await;
+                let layer = state.layer_tracker.on_extrusion(target_z);
+                let pause = layer.is_some_and(|layer| state.pause_conditions.take_layer(layer, target_z));
+                (pause, layer.and(state.layer_tracker.previous_layer_time()))
+            };
+            if let Some(layer_time) = layer_time {
+                self.apply_min_layer_time(layer_time).await;
+            }
+            if pause {
+                println!("Pausing at Z{:.3}", target_z);
+                self.filament_change([None; 3]).await?;
+            }
+        }
+        
+        // Lift the nozzle over travel moves so it doesn't knock over printed walls.
+        // Moves that already climb by the hop height (e.g. parking) are left alone.
+        let motion_config = self.motion_controller.get_motion_config();
+        let hop_height = motion_config.z_hop_height;
+        let hop_speed = Some(motion_config.z_hop_speed);
+        let xy_distance = ((target_x - current_pos[0]).powi(2) + (target_y - current_pos[1]).powi(2)).sqrt();
+        let is_travel = e.is_none_or(|e| e <= 0.0);
+        let hop_z = current_pos[2] + hop_height;
+        
+        if hop_height > 0.0 && is_travel && xy_distance > Z_HOP_MIN_TRAVEL && target_z < hop_z {
+            self.motion_controller
+                .queue_linear_move([current_pos[0], current_pos[1], hop_z], hop_speed, None)
+                .await?;
+            self.motion_controller
+                .queue_linear_move([target_x, target_y, hop_z], f, e)
+                .await?;
+            self.motion_controller
+                .queue_linear_move([target_x, target_y, target_z], hop_speed, None)
+                .await?;
+        } else {
+            self.motion_controller
+                .queue_linear_move([target_x, target_y, target_z], f, e)
+                .await?;
+        }
+        
+        if let Some(e) = e
+            && let Some(job) = self.state.write().await.job.as_mut()
+        {
+            job.record_extrusion(e);
+        }
         
         Ok(())
     }
 
+    /// Scale feedrates so a layer that took `layer_time` lasts at least
+    /// `min_layer_time_secs`, running the fan harder while slowed
+    async fn apply_min_layer_time(&self, layer_time: Duration) {
+        let config = self.motion_controller.get_motion_config();
+        if config.min_layer_time_secs <= 0.0 {
+            return;
+        }
+        let mut state = self.state.write().await;
+        // What the layer would have taken without the current slowdown
+        let full_speed_time = layer_time.as_secs_f64() * state.feedrate_factor;
+        let factor = (full_speed_time / config.min_layer_time_secs).clamp(MIN_LAYER_FEEDRATE_FACTOR, 1.0);
+        if factor < 1.0 {
+            if state.fan_before_slowdown.is_none() {
+                state.fan_before_slowdown = Some(state.fan.get_speed());
+            }
+            state.fan.set_speed(config.max_layer_fan_speed_pct / 100.0);
+            tracing::info!("Layer took {:.1}s, slowing to {:.0}%", full_speed_time, factor * 100.0);
+        } else if let Some(speed) = state.fan_before_slowdown.take() {
+            state.fan.set_speed(speed);
+        }
+        state.feedrate_factor = factor;
+    }
+
+    async fn set_positioning_mode(&mut self, mode: PositioningMode) {
+        println!("Positioning set to {:?} mode", mode);
+        self.state.write().await.positioning_mode = mode;
+    }
+
+    async fn set_extruder_mode(&mut self, mode: ExtruderMode) {
+        println!("Extruder set to {:?} mode", mode);
+        self.state.write().await.extruder_mode = mode;
+    }
+
     async fn handle_home(&mut self, _parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
         self.motion_controller.queue_home().await?;
         Ok(())
     }
 
+    /// BLTouch positioned at the toolhead, once homed
+    async fn bltouch(&self) -> Result<BLTouchProbe, Box<dyn std::error::Error>> {
+        if !self.state.read().await.homed {
+            return Err("Probing requires homing first (G28)".into());
+        }
+        let mut probe = self
+            .motion_controller
+            .get_hardware_manager()
+            .bltouch()
+            .ok_or("No BLTouch configured")?;
+        let [x, y, z, _] = self.motion_controller.get_current_position();
+        probe.set_position([x, y, z]);
+        Ok(probe)
+    }
+
+    /// Take over wherever the probe left the toolhead
+    async fn finish_probing(&mut self, probe: &BLTouchProbe) {
+        let [x, y, z] = probe.position();
+        let e = self.motion_controller.get_current_position()[3];
+        self.motion_controller.set_position([x, y, z, e]);
+        self.state.write().await.position = [x, y, z];
+    }
+
+    async fn handle_probe(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut probe = self.bltouch().await?;
+        let [mut x, mut y, _] = probe.position();
+        for part in parts.iter().skip(1) {
+            match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('X') => x = part[1..].parse()?,
+                Some('Y') => y = part[1..].parse()?,
+                _ => {}
+            }
+        }
+        
+        let z = probe.position()[2];
+        self.motion_controller.queue_probe_move([x, y, z], probe.config().speed).await?;
+        probe.set_position([x, y, z]);
+        let result = probe.probe_single_point().await;
+        self.finish_probing(&probe).await;
+        println!("Bed height at X{:.3} Y{:.3}: {:.4}", x, y, result?);
+        Ok(())
+    }
+
+    async fn handle_probe_mesh(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut probe = self.bltouch().await?;
+        let result = probe.probe_mesh().await;
+        self.finish_probing(&probe).await;
+        let mesh = result?;
+        for [x, y, z] in &mesh {
+            println!("Bed height at X{:.3} Y{:.3}: {:.4}", x, y, z);
+        }
+        self.state.write().await.bed_mesh = Some(mesh);
+        Ok(())
+    }
+
+    /// G33 [P<points>] [V<verbosity>]: probe the bed and fit the delta
+    /// endstop and tower angle corrections, saving them
+    ///
+    /// V0 reports nothing, V1 (the default) the result and V2 also each
+    /// probed height.
+    async fn handle_delta_calibration(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut probe_count = DEFAULT_DELTA_PROBE_POINTS;
+        let mut verbosity = 1;
+        for part in parts.iter().skip(1) {
+            match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('P') => probe_count = part[1..].parse()?,
+                Some('V') => verbosity = part[1..].parse()?,
+                _ => {}
+            }
+        }
+        let delta = self.delta.clone().ok_or("No delta configured")?;
+        let mut config = delta.lock().unwrap().clone();
+        let calibrator = DeltaCalibrator::new(probe_count, config.print_radius * DELTA_PROBE_RADIUS_FRACTION)?;
+        let mut probe = self.bltouch().await?;
+
+        let mut heights = Vec::with_capacity(probe_count);
+        let mut result = Ok(());
+        for &[x, y] in calibrator.probe_points() {
+            match probe.move_to_xy(x, y).await {
+                Ok(()) => match probe.probe_single_point().await {
+                    Ok(height) => heights.push(height),
+                    Err(e) => result = Err(e),
+                },
+                Err(e) => result = Err(e),
+            }
+            if result.is_err() {
+                break;
+            }
+            if verbosity >= 2 {
+                println!("Bed height at X{:.3} Y{:.3}: {:.4}", x, y, heights[heights.len() - 1]);
+            }
+        }
+        self.finish_probing(&probe).await;
+        result?;
+
+        let mut kinematics = DeltaKinematics::from_config(&config);
+        let calibration = calibrator.calibrate(&mut kinematics, &heights)?;
+        config.endstop_corrections = calibration.endstop_corrections;
+        config.tower_angle_corrections = calibration.tower_angle_corrections;
+        self.motion_controller.set_kinematics_handler(KinematicsType::Delta, Box::new(kinematics));
+        *delta.lock().unwrap() = config;
+        if let Some(settings) = &self.settings
+            && let Err(e) = settings.update(|settings| {
+                settings.delta_endstop_corrections = Some(calibration.endstop_corrections);
+                settings.delta_tower_angle_corrections = Some(calibration.tower_angle_corrections);
+            })
+        {
+            tracing::warn!("Could not save delta calibration to {}: {}", settings.path().display(), e);
+        }
+
+        if verbosity >= 1 {
+            println!(
+                "Delta calibration: deviation {:.4}mm -> {:.4}mm, endstops {:?}, tower angles {:?}",
+                calibration.deviation_before,
+                calibration.deviation_after,
+                calibration.endstop_corrections,
+                calibration.tower_angle_corrections
+            );
+        }
+        Ok(())
+    }
+
+    /// G12 [P<pattern>] [S<strokes>] [T<count>]: wipe the nozzle at travel speed
+    async fn handle_nozzle_wipe(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let wipe = self.nozzle_wipe.ok_or("No nozzle wipe area configured")?;
+        let (mut pattern, mut strokes, mut count) = (WipePattern::Straight, 1, 1);
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix(['P', 'p']) {
+                pattern = WipePattern::from_code(value.parse()?)
+                    .ok_or_else(|| format!("Unknown wipe pattern {}", part))?;
+            } else if let Some(value) = part.strip_prefix(['S', 's']) {
+                strokes = value.parse()?;
+            } else if let Some(value) = part.strip_prefix(['T', 't']) {
+                count = value.parse()?;
+            }
+        }
+        {
+            let state = self.state.read().await;
+            if !state.homed {
+                return Err("Home before wiping the nozzle".into());
+            }
+            if state.temperature < wipe.min_temp {
+                return Err(format!(
+                    "Nozzle at {:.1}°C is below the {:.1}°C wipe minimum",
+                    state.temperature, wipe.min_temp
+                )
+                .into());
+            }
+        }
+        
+        let position = self.get_current_position().await;
+        let waypoints = wipe.waypoints(pattern, strokes, count, position[2], position[3]);
+        let motion_config = self.motion_controller.get_motion_config();
+        let limits = motion_config.axis_limits;
+        if let Some(point) = waypoints
+            .iter()
+            .find(|point| (0..2).any(|axis| point[axis] < limits[axis][0] || point[axis] > limits[axis][1]))
+        {
+            return Err(format!("Wipe point X{:.1} Y{:.1} is outside the axis limits", point[0], point[1]).into());
+        }
+        
+        let speed = Some(motion_config.max_velocity[0]);
+        for point in waypoints {
+            self.motion_controller.queue_linear_move([point[0], point[1], point[2]], speed, None).await?;
+        }
+        println!("Nozzle wiped with {:?} pattern", pattern);
+        Ok(())
+    }
+
     async fn handle_set_position(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
         let mut x = None;
         let mut y = None;
@@ -114,68 +810,2108 @@ impl GCodeProcessor {
         }
         
         println!("Setting position - X:{:?} Y:{:?} Z:{:?} E:{:?}", x, y, z, e);
+        // Queued moves would put the planner back at their own targets
+        self.motion_controller.wait_for_queue_empty().await?;
+        let current = self.get_current_position().await;
+        let position = [x.unwrap_or(current[0]), y.unwrap_or(current[1]), z.unwrap_or(current[2]), e.unwrap_or(current[3])];
+        self.motion_controller.set_position(position);
+        self.state.write().await.position = [position[0], position[1], position[2]];
         Ok(())
     }
 
-    async fn handle_set_hotend_temp(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    async fn handle_set_advanced(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
         for part in parts.iter().skip(1) {
-            if part.starts_with('S') {
-                let temp: f64 = part[1..].parse().unwrap_or(0.0);
-                println!("Setting hotend temperature to {:.1}°C", temp);
-                
-                // Update state
-                {
-                    let mut state = self.state.write().await;
-                    state.temperature = temp;
-                }
+            if let Some(value) = part.strip_prefix(['L', 'l']) {
+                let size: usize = value.parse()?;
+                self.motion_controller.set_lookahead_buffer_size(size)?;
+                println!("Lookahead buffer size set to {}", size);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_set_z_hop(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix(['Z', 'z']) {
+                let height: f64 = value.parse().map_err(|e| format!("Invalid Z-hop height '{}': {}", value, e))?;
+                println!("Setting Z-hop height to {:.3}mm", height);
+                self.motion_controller.set_z_hop(height, None);
                 break;
             }
         }
         Ok(())
     }
 
-    async fn handle_set_hotend_temp_wait(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
-        self.handle_set_hotend_temp(parts).await?;
-        println!("Waiting for hotend temperature...");
+    /// M852 I<xy> J<xz> K<yz>: set the skew correction (radians)
+    async fn handle_set_skew(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut skew = self.motion_controller.get_motion_config().skew_correction;
+        let mut changed = false;
+        
+        for part in parts.iter().skip(1) {
+            let angle = match part.chars().next() {
+                Some('I') | Some('i') => &mut skew.xy,
+                Some('J') | Some('j') => &mut skew.xz,
+                Some('K') | Some('k') => &mut skew.yz,
+                _ => continue,
+            };
+            *angle = part[1..].parse()?;
+            changed = true;
+        }
+        
+        if changed {
+            self.set_skew(skew)?;
+        }
+        println!("Skew correction: I{:.6} J{:.6} K{:.6}", skew.xy, skew.xz, skew.yz);
         Ok(())
     }
 
-    async fn handle_set_bed_temp(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    /// CALIBRATE_SKEW AC=<mm> BD=<mm> SIDE=<mm>: correct XY skew from the
+    /// measured diagonals of a printed square
+    ///
+    /// The measured skew is what is left over with the current correction,
+    /// so it is added to it.
+    async fn handle_calibrate_skew(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut ac, mut bd, mut side) = (None, None, None);
         for part in parts.iter().skip(1) {
-            if part.starts_with('S') {
-                let temp: f64 = part[1..].parse().unwrap_or(0.0);
-                println!("Setting bed temperature to {:.1}°C", temp);
-                break;
+            let (name, value) = part.split_once('=').ok_or_else(|| format!("Unknown CALIBRATE_SKEW parameter {}", part))?;
+            let value: f64 = value.parse()?;
+            match name.to_ascii_uppercase().as_str() {
+                "AC" => ac = Some(value),
+                "BD" => bd = Some(value),
+                "SIDE" => side = Some(value),
+                _ => return Err(format!("Unknown CALIBRATE_SKEW parameter {}", part).into()),
             }
         }
+        let (Some(ac), Some(bd), Some(side)) = (ac, bd, side) else {
+            return Err("CALIBRATE_SKEW needs AC, BD and SIDE".into());
+        };
+        if [ac, bd, side].iter().any(|&length| !length.is_finite() || length <= 0.0) {
+            return Err("CALIBRATE_SKEW lengths must be positive".into());
+        }
+        
+        let measured = SkewCorrection::from_diagonals(ac, bd, side);
+        let mut skew = self.motion_controller.get_motion_config().skew_correction;
+        skew.xy += measured.xy;
+        self.set_skew(skew)?;
+        println!("Skew correction: I{:.6} J{:.6} K{:.6}", skew.xy, skew.xz, skew.yz);
         Ok(())
     }
 
-    async fn handle_set_bed_temp_wait(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
-        self.handle_set_bed_temp(parts).await?;
-        println!("Waiting for bed temperature...");
+    /// CALIBRATE_HANGPRINTER [N=<points>]: fit the anchor positions to the
+    /// line lengths measured at points around the homed origin
+    ///
+    /// Prints the refined anchors as a snippet for the config file.
+    async fn handle_calibrate_hangprinter(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut point_count = DEFAULT_HANGPRINTER_CALIBRATION_POINTS;
+        for part in parts.iter().skip(1) {
+            match part.split_once('=') {
+                Some((name, value)) if name.eq_ignore_ascii_case("N") => point_count = value.parse()?,
+                _ => return Err(format!("Unknown CALIBRATE_HANGPRINTER parameter {}", part).into()),
+            }
+        }
+        let config = self.hangprinter.clone().ok_or("No Hangprinter configured")?;
+        if config.encoder_pins.len() != 4 {
+            return Err("Hangprinter calibration needs an encoder pin for each of the 4 lines".into());
+        }
+        if config.encoder_counts_per_mm <= 0.0 {
+            return Err("Hangprinter encoder_counts_per_mm must be positive".into());
+        }
+        if !self.state.read().await.homed {
+            return Err("Hangprinter calibration requires homing first (G28)".into());
+        }
+        let calibrator = HangprinterCalibrator::new(point_count, config.calibration_radius, config.calibration_height)?;
+
+        let mut origin_counts = Vec::new();
+        let mut length_changes = Vec::with_capacity(point_count);
+        for point in calibrator.points() {
+            self.motion_controller.queue_linear_move(*point, Some(HANGPRINTER_CALIBRATION_SPEED), None).await?;
+            self.motion_controller.wait_for_queue_empty().await?;
+            let counts = self.motion_controller.get_hardware_manager().read_encoders(&config.encoder_pins).await?;
+            if origin_counts.is_empty() {
+                origin_counts = counts.clone();
+            }
+            let changes: [f64; 4] = std::array::from_fn(|line| {
+                (counts[line] - origin_counts[line]) as f64 / config.encoder_counts_per_mm
+            });
+            length_changes.push(changes);
+        }
+        self.motion_controller.queue_linear_move([0.0; 3], Some(HANGPRINTER_CALIBRATION_SPEED), None).await?;
+
+        let result = calibrator.calibrate(config.anchors, &length_changes)?;
+        println!(
+            "Hangprinter calibration: deviation {:.4}mm -> {:.4}mm\n{}",
+            result.deviation_before, result.deviation_after, result.to_toml()
+        );
         Ok(())
     }
 
-    async fn handle_fan_on(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut speed = 255; // Full speed default
+    /// G4: wait for queued moves to finish, then pause for P milliseconds
+    /// or S seconds
+    async fn handle_dwell(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut duration = Duration::ZERO;
         for part in parts.iter().skip(1) {
-            if part.starts_with('S') {
-                speed = part[1..].parse().unwrap_or(255);
-                break;
+            if let Some(ms) = part.strip_prefix('P') {
+                duration = Duration::from_millis(ms.parse()?);
+            } else if let Some(secs) = part.strip_prefix('S') {
+                duration = Duration::try_from_secs_f64(secs.parse()?)?;
             }
         }
-        println!("Setting fan speed to {}", speed);
+        if self.state.read().await.dry_run {
+            self.motion_controller.simulate_dwell(duration);
+            return Ok(());
+        }
+        self.motion_controller.wait_for_queue_empty().await?;
+        tokio::time::sleep(duration).await;
+        Ok(())
+    }
+
+    /// Apply a skew correction and save it
+    fn set_skew(&mut self, skew: SkewCorrection) -> Result<(), Box<dyn std::error::Error>> {
+        self.motion_controller.set_skew_correction(skew)?;
+        if let Some(settings) = &self.settings
+            && let Err(e) = settings.update(|settings| settings.skew_correction = Some(skew))
+        {
+            tracing::warn!("Could not save skew correction to {}: {}", settings.path().display(), e);
+        }
         Ok(())
     }
 
-    async fn get_current_position(&self) -> [f64; 3] {
-        let pos = self.motion_controller.get_current_position();
-        [pos[0], pos[1], pos[2]]
+    fn handle_set_steps_per_mm(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut values = [None; 4];
+        for part in parts.iter().skip(1) {
+            let axis = match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('X') => 0,
+                Some('Y') => 1,
+                Some('Z') => 2,
+                Some('E') => 3,
+                _ => continue,
+            };
+            values[axis] = Some(part[1..].parse::<f64>()?);
+        }
+        
+        if values.iter().all(Option::is_none) {
+            let [x, y, z, e] = self.motion_controller.steps_per_mm();
+            println!("Steps/mm: X{:.3} Y{:.3} Z{:.3} E{:.3}", x, y, z, e);
+            return Ok(());
+        }
+        
+        let steps_per_mm = self.motion_controller.set_steps_per_mm(values)?;
+        if let Some(settings) = &self.settings
+            && let Err(e) = settings.update(|settings| settings.steps_per_mm = Some(steps_per_mm))
+        {
+            tracing::warn!("Could not save steps/mm to {}: {}", settings.path().display(), e);
+        }
+        Ok(())
     }
-    
-    // Add method to access state
-    pub async fn get_state(&self) -> PrinterState {
-        self.state.read().await.clone()
+
+    /// M572 R<mode>: shape moves as basic (0), adaptive (1) or snap-crackle
+    /// (2) once the queue has drained
+    async fn handle_motion_mode(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix(['R', 'r']) {
+                let mode = MotionMode::from_index(value.parse()?)
+                    .ok_or_else(|| format!("Unknown motion mode R{}", value))?;
+                self.motion_controller.set_motion_mode(mode).await?;
+            }
+        }
+        println!("Motion mode: {}", self.motion_controller.motion_mode().await.name());
+        Ok(())
+    }
+
+    fn handle_set_filament_diameter(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('D').or_else(|| part.strip_prefix('d')) {
+                self.motion_controller.set_filament_diameter(value.parse()?)?;
+            }
+        }
+        println!("Filament diameter: {:.3}mm", self.motion_controller.get_motion_config().filament_diameter);
+        Ok(())
+    }
+
+    /// M203 E<mm³/s>: volumetric speed limit; M203 T<type> V<mm/s>: speed
+    /// limit for print, travel, home, probe or extruder moves
+    fn handle_set_max_flow(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut motion_type, mut velocity) = (None, None);
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix(['E', 'e']) {
+                self.motion_controller.set_max_volumetric_speed(value.parse()?)?;
+            } else if let Some(value) = part.strip_prefix(['T', 't']) {
+                motion_type = Some(value.parse::<MotionType>()?);
+            } else if let Some(value) = part.strip_prefix(['V', 'v']) {
+                velocity = Some(value.parse::<f64>()?);
+            }
+        }
+        match (motion_type, velocity) {
+            (Some(motion_type), Some(velocity)) => {
+                self.motion_controller.set_motion_type_max_velocity(motion_type, velocity)?;
+            }
+            (None, None) => {}
+            _ => return Err("M203 needs both T and V to set a motion type's speed".into()),
+        }
+        let config = self.motion_controller.get_motion_config();
+        println!("Max volumetric speed: {:.1}mm³/s", config.max_volumetric_speed);
+        if let Some(motion_type) = motion_type
+            && let Some(limits) = config.motion_types.get(&motion_type)
+        {
+            println!("Max {} speed: {:.1}mm/s", motion_type.as_str(), limits.max_velocity);
+        }
+        Ok(())
+    }
+
+    /// M204 C<factor>: acceleration scale for curves; other M204 parameters are ignored
+    fn handle_set_curve_accel(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('C').or_else(|| part.strip_prefix('c')) {
+                self.motion_controller.set_curve_accel_factor(value.parse()?)?;
+            }
+        }
+        println!("Curve acceleration factor: {:.2}", self.motion_controller.get_motion_config().curve_accel_factor);
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Segments waiting in the motion queue
+    pub fn queued_segments(&self) -> Vec<MotionSegment> {
+        self.motion_controller.get_planner().published_segments()
+    }
+
+    /// Latest encoder drift of each motor with an encoder
+    pub fn position_drift(&self) -> Vec<StepPositionDrift> {
+        self.motion_controller.get_planner().position_drift()
+    }
+
+    /// Recorder started and stopped by M926
+    pub fn motion_recorder(&self) -> MotionRecorder {
+        self.motion_controller.get_planner().motion_recorder().clone()
+    }
+
+    fn handle_linear_advance(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('K').or_else(|| part.strip_prefix('k')) {
+                self.motion_controller.set_linear_advance(value.parse()?)?;
+            }
+        }
+        match self.motion_controller.get_motion_config().linear_advance_k {
+            Some(k) => println!("Linear advance K: {:.3}", k),
+            None => println!("Linear advance disabled"),
+        }
+        Ok(())
+    }
+
+    /// M593 F<hz> D<damping> or M593 PRESET=<name>: set the input shaper
+    ///
+    /// F and D change the active shaper, starting from the one suggested
+    /// for the frequency if there is none; F0 turns shaping off.
+    fn handle_input_shaper(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut shaper = self.motion_controller.get_motion_config().input_shaper.clone();
+        let (mut frequency, mut damping) = (None, None);
+        for part in parts.iter().skip(1) {
+            if let Some((name, value)) = part.split_once('=') {
+                if !name.eq_ignore_ascii_case("PRESET") {
+                    return Err(format!("Unknown M593 parameter {}", part).into());
+                }
+                let preset = self.shaper_presets.lock().unwrap().get_preset(value).cloned();
+                shaper = Some(preset.ok_or_else(|| format!("No shaper preset {}", value))?);
+            } else if let Some(value) = part.strip_prefix(['F', 'f']) {
+                frequency = Some(value.parse::<f64>()?);
+            } else if let Some(value) = part.strip_prefix(['D', 'd']) {
+                damping = Some(value.parse::<f64>()?);
+            }
+        }
+
+        if frequency == Some(0.0) {
+            shaper = None;
+        } else if frequency.is_some() || damping.is_some() {
+            let mut custom = match (shaper, frequency) {
+                (Some(shaper), _) => shaper,
+                (None, Some(frequency)) => suggest_from_frequency(frequency),
+                (None, None) => return Err("M593 needs F to start shaping".into()),
+            };
+            custom.frequency = frequency.unwrap_or(custom.frequency);
+            custom.damping = damping.unwrap_or(custom.damping);
+            shaper = Some(custom);
+        }
+        if parts.len() > 1 {
+            self.motion_controller.set_input_shaper(shaper)?;
+        }
+
+        match &self.motion_controller.get_motion_config().input_shaper {
+            Some(shaper) => println!(
+                "Input shaper {} ({:?}): F{:.1} D{:.3}",
+                shaper.name, shaper.shaper_type, shaper.frequency, shaper.damping
+            ),
+            None => println!("Input shaper off"),
+        }
+        Ok(())
+    }
+
+    /// M926 S1 starts recording the toolhead trajectory, M926 S0 stops and saves it
+    fn handle_motion_recording(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let recorder = self.motion_recorder();
+        for part in parts.iter().skip(1) {
+            match part.strip_prefix(['S', 's']) {
+                Some("1") => recorder.start(),
+                Some("0") => {
+                    if let Some(path) = recorder.stop()? {
+                        println!("Trajectory saved to {}", path.display());
+                    }
+                }
+                _ => return Err(format!("Unknown M926 parameter {}", part).into()),
+            }
+        }
+        println!("Trajectory recording {}", if recorder.is_recording() { "on" } else { "off" });
+        Ok(())
+    }
+
+    async fn handle_set_hotend_temp(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut temp = None;
+        let mut tool = None;
+        let mut clog_recovery = None;
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('S') {
+                temp = Some(value.parse().unwrap_or(0.0));
+            } else if let Some(value) = part.strip_prefix('T') {
+                tool = Some(value.parse::<usize>()?);
+            } else if let Some(value) = part.strip_prefix('R') {
+                clog_recovery = Some(value.parse::<u8>()? != 0);
+            }
+        }
+        
+        let mut state = self.state.write().await;
+        let tool = tool.unwrap_or(state.active_tool);
+        if let Some(enabled) = clog_recovery {
+            let detector = self.motion_controller.clog_detector().ok_or("Clog detection is not configured")?;
+            detector.set_recovery(tool, enabled);
+            println!("T{} clog recovery {}", tool, if enabled { "on" } else { "off" });
+        }
+        let Some(temp) = temp else {
+            return Ok(());
+        };
+        
+        println!("Setting T{} temperature to {:.1}°C", tool, temp);
+        state.tools.get_mut(tool).ok_or_else(|| format!("No extruder for T{}", tool))?.set_active_temp(temp);
+        if tool == state.active_tool {
+            state.temperature = temp;
+        }
+        Ok(())
+    }
+
+    async fn handle_set_hotend_temp_wait(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        self.handle_set_hotend_temp(parts).await?;
+        println!("Waiting for hotend temperature...");
+        Ok(())
+    }
+
+    async fn handle_set_bed_temp(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('S') {
+                let temp: f64 = value.parse().unwrap_or(0.0);
+                println!("Setting bed temperature to {:.1}°C", temp);
+                self.state.write().await.bed_target = temp;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_set_bed_temp_wait(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        self.handle_set_bed_temp(parts).await?;
+        println!("Waiting for bed temperature...");
+        Ok(())
+    }
+
+    async fn handle_set_chamber_temp(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('S') {
+                let temp: f64 = value.parse().unwrap_or(0.0);
+                println!("Setting chamber temperature to {:.1}°C", temp);
+                
+                let mut state = self.state.write().await;
+                let chamber = state.chamber.as_mut().ok_or("No chamber heater configured")?;
+                chamber.set_target(temp)?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_set_chamber_temp_wait(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        self.handle_set_chamber_temp(parts).await?;
+        if self.state.read().await.dry_run {
+            return Ok(());
+        }
+        println!("Waiting for chamber temperature...");
+        self.wait_for_chamber().await
+    }
+
+    /// Wait until the chamber is at its target, or M108
+    ///
+    /// Fails if the heater faults or the chamber's wait timeout passes first.
+    async fn wait_for_chamber(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let cancelled = self.cancel_wait.notified();
+        tokio::pin!(cancelled);
+        cancelled.as_mut().enable();
+
+        let (target, timeout) = {
+            let state = self.state.read().await;
+            let chamber = state.chamber.as_ref().ok_or("No chamber heater configured")?;
+            (chamber.get_target(), chamber.get_wait_timeout())
+        };
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            {
+                let state = self.state.read().await;
+                let chamber = state.chamber.as_ref().ok_or("No chamber heater configured")?;
+                if let Some(fault) = chamber.get_fault() {
+                    return Err(format!("Chamber heater fault: {}", fault).into());
+                }
+                if chamber.is_heated() {
+                    return Ok(());
+                }
+            }
+            tokio::select! {
+                _ = &mut cancelled => {
+                    println!("Wait cancelled");
+                    return Ok(());
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    return Err(format!(
+                        "Chamber did not reach {:.1}°C within {:.0}s",
+                        target,
+                        timeout.as_secs_f64()
+                    ).into());
+                }
+                _ = tokio::time::sleep(TEMPERATURE_POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// M106 S sets the PWM duty (0-255), or the RPM to hold for fans in RPM
+    /// control mode; without S the fan runs at full duty
+    async fn handle_fan_on(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let value = parts.iter().skip(1).find_map(|part| part.strip_prefix('S'));
+        let mut state = self.state.write().await;
+        match (state.fan.control_mode(), value) {
+            (FanControlMode::Rpm, Some(rpm)) => {
+                let rpm: f64 = rpm.parse()?;
+                println!("Setting fan target to {} RPM", rpm);
+                state.fan.set_target_rpm(rpm.max(0.0).round() as u32);
+            }
+            (_, value) => {
+                let speed = value.and_then(|value| value.parse().ok()).unwrap_or(255.0);
+                println!("Setting fan speed to {}", speed);
+                state.fan.override_speed(speed / 255.0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Switch tools, heating the new one before returning
+    ///
+    /// The previous tool drops to its standby temperature after its
+    /// standby delay, unless it is selected again first.
+    async fn handle_tool_change(&mut self, tool: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let tool: usize = tool.parse()?;
+        if let Some(standby) = self.standby_tasks.lock().unwrap().remove(&tool) {
+            standby.abort();
+        }
+        
+        let previous = {
+            let mut state = self.state.write().await;
+            let heater = state.tools.get_mut(tool).ok_or_else(|| format!("No extruder for T{}", tool))?;
+            if heater.is_on_standby() {
+                heater.restore();
+            }
+            let temperature = heater.temperature;
+            state.temperature = temperature;
+            std::mem::replace(&mut state.active_tool, tool)
+        };
+        if previous != tool {
+            self.schedule_standby(previous).await;
+        }
+        
+        println!("Tool T{} selected", tool);
+        self.wait_for_tools(Some(tool)).await;
+        if self.state.write().await.pause_conditions.take_filament_change() {
+            self.filament_change([None; 3]).await?;
+        }
+        Ok(())
+    }
+
+    /// M226 Z<mm> | L<layer> | N<line> | C: pause for a filament change at a
+    /// height, layer, file command or after the next tool change
+    ///
+    /// Unlike Marlin's M226 this does not wait on a pin.
+    async fn handle_pause_at(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conditions = Vec::new();
+        for part in parts.iter().skip(1) {
+            conditions.push(if let Some(value) = part.strip_prefix(['Z', 'z']) {
+                PauseAtCondition::AtHeight(value.parse()?)
+            } else if let Some(value) = part.strip_prefix(['L', 'l']) {
+                PauseAtCondition::AtLayer(value.parse()?)
+            } else if let Some(value) = part.strip_prefix(['N', 'n']) {
+                PauseAtCondition::AtGCodeLine(value.parse()?)
+            } else if part.starts_with(['C', 'c']) {
+                PauseAtCondition::AfterFilamentChange
+            } else {
+                return Err(format!("Unknown M226 parameter {}", part).into());
+            });
+        }
+        if conditions.is_empty() {
+            return Err("M226 needs Z, L, N or C".into());
+        }
+        self.add_pause_conditions(conditions).await;
+        Ok(())
+    }
+
+    /// Klipper style SET_PAUSE_AT_HEIGHT HEIGHT=<mm> and SET_PAUSE_AT_LAYER LAYER=<n>
+    async fn handle_set_pause_at(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut conditions = Vec::new();
+        for part in parts.iter().skip(1) {
+            match part.split_once('=') {
+                Some((name, value)) if name.eq_ignore_ascii_case("HEIGHT") => {
+                    conditions.push(PauseAtCondition::AtHeight(value.parse()?));
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("LAYER") => {
+                    conditions.push(PauseAtCondition::AtLayer(value.parse()?));
+                }
+                _ => return Err(format!("Unknown {} parameter {}", parts[0], part).into()),
+            }
+        }
+        if conditions.is_empty() {
+            return Err(format!("{} needs HEIGHT or LAYER", parts[0]).into());
+        }
+        self.add_pause_conditions(conditions).await;
+        Ok(())
+    }
+
+    /// SET_VAR NAME=<name> VALUE=<value>, in the innermost scope unless the
+    /// name starts with `global.`
+    fn handle_set_variable(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut name, mut value) = (None, None);
+        for part in parts.iter().skip(1) {
+            match part.split_once('=') {
+                Some((key, v)) if key.eq_ignore_ascii_case("NAME") => name = Some(v.to_lowercase()),
+                Some((key, v)) if key.eq_ignore_ascii_case("VALUE") => value = Some(v.parse::<f64>()?),
+                _ => return Err(format!("Unknown SET_VAR parameter {}", part).into()),
+            }
+        }
+        let (Some(name), Some(value)) = (name, value) else {
+            return Err("SET_VAR needs NAME and VALUE".into());
+        };
+        self.variables.lock().unwrap().set(&name, value);
+        Ok(())
+    }
+
+    /// M98 P<file>: run a G-code file in its own variable scope, until M99
+    /// or its last line
+    async fn handle_call_subroutine(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let path = parts
+            .iter()
+            .skip(1)
+            .find_map(|part| part.strip_prefix('P'))
+            .map(|path| path.trim_matches('"'))
+            .ok_or("M98 needs P<file>")?;
+        let contents = tokio::fs::read_to_string(path).await.map_err(|e| format!("Cannot read subroutine {}: {}", path, e))?;
+
+        let depth = {
+            let mut variables = self.variables.lock().unwrap();
+            if variables.depth() >= MAX_SUBROUTINE_DEPTH {
+                return Err(format!("Subroutines nested deeper than {}", MAX_SUBROUTINE_DEPTH).into());
+            }
+            variables.push_scope();
+            variables.depth()
+        };
+        let leave_scope = |variables: &Mutex<VariableScope>| {
+            let mut variables = variables.lock().unwrap();
+            while variables.depth() >= depth {
+                variables.pop_scope();
+            }
+        };
+        for line in contents.lines() {
+            let command = line.split(';').next().unwrap_or_default();
+            if let Err(e) = Box::pin(self.process_command(command)).await {
+                leave_scope(&self.variables);
+                return Err(e);
+            }
+            // M99 already popped the scope
+            if self.variables.lock().unwrap().depth() < depth {
+                return Ok(());
+            }
+        }
+        leave_scope(&self.variables);
+        Ok(())
+    }
+
+    async fn add_pause_conditions(&self, conditions: Vec<PauseAtCondition>) {
+        let mut state = self.state.write().await;
+        for condition in conditions {
+            let id = state.pause_conditions.add(condition);
+            println!("Pause {} added: {:?}", id, condition);
+        }
+    }
+
+    /// M600 [X Y Z]: park and wait for the filament to be changed
+    async fn handle_filament_change(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut park = [None; 3];
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix(['X', 'x']) {
+                park[0] = Some(value.parse()?);
+            } else if let Some(value) = part.strip_prefix(['Y', 'y']) {
+                park[1] = Some(value.parse()?);
+            } else if let Some(value) = part.strip_prefix(['Z', 'z']) {
+                park[2] = Some(value.parse()?);
+            }
+        }
+        self.filament_change(park).await
+    }
+
+    /// Lift by `park[2]` and move to `park[0]`, `park[1]` (default X0 Y0),
+    /// release the extruder and go back once the user continues
+    async fn filament_change(&mut self, park: [Option<f64>; 3]) -> Result<(), Box<dyn std::error::Error>> {
+        let resume_at = self.park(park).await?;
+        self.motion_controller.disable_extruder().await;
+        self.wait_for_user().await?;
+        self.unpark(resume_at, park[2]).await?;
+        println!("Filament change done");
+        Ok(())
+    }
+
+    /// Lift by `park[2]` (default `FILAMENT_CHANGE_Z_LIFT`) and move to
+    /// `park[0]`, `park[1]` (default X0 Y0), returning where to go back to
+    async fn park(&mut self, park: [Option<f64>; 3]) -> Result<[f64; 4], Box<dyn std::error::Error>> {
+        let resume_at = self.get_current_position().await;
+        let park_z = resume_at[2] + park[2].unwrap_or(FILAMENT_CHANGE_Z_LIFT);
+        let (park_x, park_y) = (park[0].unwrap_or(0.0), park[1].unwrap_or(0.0));
+        println!("Parking at X{:.1} Y{:.1} Z{:.1}", park_x, park_y, park_z);
+        self.motion_controller
+            .queue_linear_move([resume_at[0], resume_at[1], park_z], None, None)
+            .await?;
+        self.motion_controller.queue_linear_move([park_x, park_y, park_z], None, None).await?;
+        Ok(resume_at)
+    }
+
+    /// Return from `park` to `resume_at`, over the same lift
+    async fn unpark(&mut self, resume_at: [f64; 4], lift: Option<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        let park_z = resume_at[2] + lift.unwrap_or(FILAMENT_CHANGE_Z_LIFT);
+        self.motion_controller
+            .queue_linear_move([resume_at[0], resume_at[1], park_z], None, None)
+            .await?;
+        self.motion_controller
+            .queue_linear_move([resume_at[0], resume_at[1], resume_at[2]], None, None)
+            .await
+    }
+
+    /// Pause the running print until it is resumed, or wait for M108
+    async fn wait_for_user(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (dry_run, printing) = {
+            let state = self.state.read().await;
+            (state.dry_run, matches!(state.job.as_ref().map(PrintJob::state), Some(PrintJobState::Printing)))
+        };
+        if dry_run {
+            return Ok(());
+        }
+        if printing {
+            return self.wait_for_resume().await;
+        }
+        println!("Send M108 to continue");
+        self.cancel_wait.notified().await;
+        Ok(())
+    }
+
+    /// Put `tool` on standby once its standby delay has passed
+    async fn schedule_standby(&self, tool: usize) {
+        let delay = match &self.state.read().await.tools[tool] {
+            ToolHeater { standby_temp: Some(_), standby_delay, .. } => *standby_delay,
+            _ => return,
+        };
+        let state = self.state.clone();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let mut state = state.write().await;
+            if state.active_tool != tool && state.tools[tool].enter_standby() {
+                tracing::info!("T{} on standby at {:.1}°C", tool, state.tools[tool].target);
+            }
+        });
+        if let Some(previous) = self.standby_tasks.lock().unwrap().insert(tool, task.abort_handle()) {
+            previous.abort();
+        }
+    }
+
+    async fn handle_wait_for_tools(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut tool = None;
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('P') {
+                tool = Some(value.parse::<usize>()?);
+            }
+        }
+        if let Some(tool) = tool
+            && tool >= self.state.read().await.tools.len()
+        {
+            return Err(format!("No extruder for T{}", tool).into());
+        }
+        self.wait_for_tools(tool).await;
+        Ok(())
+    }
+
+    /// Wait until `tool`, or every tool, is at its target, or M108
+    async fn wait_for_tools(&self, tool: Option<usize>) {
+        if self.state.read().await.dry_run {
+            return;
+        }
+        let cancelled = self.cancel_wait.notified();
+        tokio::pin!(cancelled);
+        cancelled.as_mut().enable();
+        
+        loop {
+            let heated = {
+                let state = self.state.read().await;
+                match tool {
+                    Some(tool) => state.tools[tool].is_heated(),
+                    None => state.tools.iter().all(ToolHeater::is_heated),
+                }
+            };
+            if heated {
+                return;
+            }
+            tokio::select! {
+                _ = &mut cancelled => {
+                    println!("Wait cancelled");
+                    return;
+                }
+                _ = tokio::time::sleep(TEMPERATURE_POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    fn cancel_waits(&self) {
+        println!("Wait cancelled");
+        self.cancel_wait.notify_waiters();
+    }
+
+    /// M73 P<pct> R<mins>: progress reported by the slicer, only displayed
+    async fn handle_set_progress(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.state.write().await;
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix(['P', 'p']) {
+                state.print_progress = (value.parse::<f64>()? / 100.0).clamp(0.0, 1.0);
+            } else if let Some(value) = part.strip_prefix(['R', 'r']) {
+                state.slicer_remaining_mins = Some(value.parse::<f64>()?.max(0.0));
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the layer and progress from a slicer's structured comment
+    async fn apply_meta_comment(&self, comment: &str) {
+        let Some(meta) = GCodeMetaCommand::parse(comment, &self.meta_patterns) else {
+            return;
+        };
+        let mut state = self.state.write().await;
+        match meta {
+            GCodeMetaCommand::Layer(layer) => {
+                state.current_layer = Some(layer);
+                // Layers are a coarse measure of progress; times are better when there are any
+                if state.slicer_total_secs.is_none()
+                    && let Some(total) = state.total_layers.filter(|&total| total > 0)
+                {
+                    state.print_progress = ((layer - 1) as f64 / total as f64).clamp(0.0, 1.0);
+                }
+            }
+            GCodeMetaCommand::TotalLayers(total) => state.total_layers = Some(total),
+            GCodeMetaCommand::TotalSecs(secs) => state.slicer_total_secs = Some(secs),
+            GCodeMetaCommand::RemainingSecs(secs) => {
+                // Without a total, the first remaining time is the whole print
+                let total = *state.slicer_total_secs.get_or_insert(secs);
+                state.slicer_remaining_mins = Some(secs / 60.0);
+                if total > 0.0 {
+                    state.print_progress = (1.0 - secs / total).clamp(0.0, 1.0);
+                }
+            }
+            GCodeMetaCommand::ElapsedSecs(secs) => {
+                if let Some(total) = state.slicer_total_secs.filter(|&total| total > 0.0) {
+                    state.print_progress = (secs / total).clamp(0.0, 1.0);
+                    state.slicer_remaining_mins = Some((total - secs).max(0.0) / 60.0);
+                }
+            }
+        }
+    }
+
+    async fn handle_fan_off(&mut self) {
+        println!("Fan turned off");
+        self.state.write().await.fan.override_speed(0.0);
+    }
+
+    async fn handle_set_fan_curve(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut fan = 0;
+        let mut start = None;
+        let mut full = None;
+        
+        for part in parts.iter().skip(1) {
+            if let Some(value) = part.strip_prefix('S') {
+                fan = value.parse()?;
+            } else if let Some(value) = part.strip_prefix('H') {
+                start = Some(value.parse::<f64>()?);
+            } else if let Some(value) = part.strip_prefix('C') {
+                full = Some(value.parse::<f64>()?);
+            }
+        }
+        
+        if fan != 0 {
+            return Err(format!("Unknown fan index: {}", fan).into());
+        }
+        let (start, full) = match (start, full) {
+            (Some(start), Some(full)) if start < full => (start, full),
+            _ => return Err("M145 requires H<start temp> below C<full speed temp>".into()),
+        };
+        
+        println!("Fan curve: 0% at {:.1}°C to 100% at {:.1}°C", start, full);
+        self.state.write().await.fan.set_curve(vec![
+            FanCurvePoint { temperature: start, speed_pct: 0.0 },
+            FanCurvePoint { temperature: full, speed_pct: 100.0 },
+        ]);
+        Ok(())
+    }
+
+    /// Enable or disable dry-run mode
+    ///
+    /// In dry-run mode commands update position and state as usual but no
+    /// motion is queued and nothing is sent to the hardware.
+    pub async fn set_dry_run(&mut self, enabled: bool) {
+        self.state.write().await.dry_run = enabled;
+        if enabled {
+            self.motion_controller.reset_dry_run_stats();
+        }
+    }
+
+    /// Simulate a whole G-code file and report what it would do
+    ///
+    /// Commands from other sources wait until the run ends, and printer
+    /// state and position are restored afterwards.
+    pub async fn dry_run_file(&mut self, path: &str) -> Result<DryRunReport, Box<dyn std::error::Error>> {
+        let _commands = self.lock_commands().await;
+        if self.motion_controller.get_queue_stats().length > 0 {
+            return Err("Cannot dry-run while motion is queued".into());
+        }
+        
+        let file = tokio::fs::File::open(path).await?;
+        let mut parser = AsyncGCodeParser::new(tokio::io::BufReader::new(file), GCodeParserConfig::default());
+        
+        let saved_state = self.get_state().await;
+        let saved_position = self.get_current_position().await;
+        self.set_dry_run(true).await;
+        
+        let mut commands = 0;
+        let mut result = Ok(());
+        while let Some(line) = parser.next_command().await {
+            let processed = match line {
+                Ok(line) => self.process_command(&line.command).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = processed {
+                result = Err(format!("Dry run failed after {} commands: {}", commands, e));
+                break;
+            }
+            commands += 1;
+        }
+        
+        let stats = self.motion_controller.get_dry_run_stats().clone();
+        let report = DryRunReport {
+            commands,
+            total_distance: stats.total_distance,
+            estimated_time: stats.estimated_time,
+            final_position: self.get_current_position().await,
+            final_state: self.get_state().await,
+        };
+        
+        *self.state.write().await = saved_state;
+        self.motion_controller.set_position(saved_position);
+        
+        result?;
+        Ok(report)
+    }
+
+    /// Run every command in a G-code file, reading it incrementally so
+    /// files larger than memory can be printed
+    ///
+    /// Returns the number of commands processed.
+    pub async fn process_file_streaming(&mut self, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut files = FileManager::default();
+        files.set_keep_comments(true);
+        let mut stream = files.stream_gcode(path).await?;
+        let mut commands = 0;
+        {
+            let mut state = self.state.write().await;
+            state.layer_tracker.reset();
+            state.feedrate_factor = 1.0;
+            state.slicer_remaining_mins = None;
+            state.current_layer = None;
+            state.total_layers = None;
+            state.slicer_total_secs = None;
+        }
+        while let Some(line) = stream.next().await {
+            let line = line?;
+            if line.command.starts_with(';') {
+                self.apply_meta_comment(&line.command).await;
+                continue;
+            }
+            if self.state.write().await.pause_conditions.take_line(commands + 1) {
+                println!("Pausing before line {}", commands + 1);
+                self.filament_change([None; 3]).await?;
+            }
+            {
+                // Batches submitted while printing run between lines
+                let _commands = self.lock_commands().await;
+                self.process_command(&line.command).await?;
+            }
+            commands += 1;
+            self.check_for_stall().await?;
+            self.check_for_clog().await?;
+        }
+        Ok(commands)
+    }
+
+    /// Pause the print after a clog, reverse-purging the nozzle first if
+    /// the active tool has clog recovery enabled
+    async fn check_for_clog(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(detector) = self.motion_controller.clog_detector().cloned() else {
+            return Ok(());
+        };
+        let Some(shortfall) = detector.take_clog() else {
+            return Ok(());
+        };
+        println!("Clog detected ({:.1}mm behind), pausing", shortfall);
+        self.pause_job().await?;
+        let resume_at = self.park([None; 3]).await?;
+        if detector.recovery_enabled(self.state.read().await.active_tool) {
+            let (retract, retract_speed) = CLOG_PURGE_RETRACT;
+            let (advance, advance_speed) = CLOG_PURGE_ADVANCE;
+            self.motion_controller.queue_extruder_move(-retract, Some(retract_speed)).await?;
+            self.motion_controller.queue_extruder_move(advance, Some(advance_speed)).await?;
+        }
+        self.wait_for_resume().await?;
+        self.unpark(resume_at, None).await?;
+        tracing::info!("Resuming after clog");
+        Ok(())
+    }
+
+    /// Recover from a stepper stall as `stall_detection.recovery` says
+    ///
+    /// The stalled axis is rehomed and the toolhead sent back to where the
+    /// file had taken it, unless the print is aborted.
+    async fn check_for_stall(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(detector) = &self.stall_detector else {
+            return Ok(());
+        };
+        let Some(axis) = detector.poll().await? else {
+            return Ok(());
+        };
+        let recovery = detector.recovery();
+        let resume_at = self.motion_controller.get_current_position();
+        self.motion_controller.cancel_moves();
+        
+        // Homing Z would drive the nozzle into the print
+        if recovery == StallRecovery::Abort || axis == 2 {
+            return Err(format!("Stepper stalled on axis {}", axis).into());
+        }
+        if recovery == StallRecovery::Pause {
+            self.wait_for_resume().await?;
+        }
+        
+        // Clear the print before the stalled axis moves to its endstop,
+        // then travel back over it and drop down to where the stall hit
+        let lift_speed = Some(self.motion_controller.get_motion_config().z_hop_speed);
+        let lift_z = resume_at[2] + STALL_RECOVERY_Z_LIFT;
+        self.motion_controller
+            .queue_linear_move([resume_at[0], resume_at[1], lift_z], lift_speed, None)
+            .await?;
+        self.motion_controller.wait_for_queue_empty().await?;
+        self.motion_controller.home_axis(axis).await?;
+        self.motion_controller
+            .queue_linear_move([resume_at[0], resume_at[1], lift_z], None, None)
+            .await?;
+        self.motion_controller
+            .queue_linear_move([resume_at[0], resume_at[1], resume_at[2]], lift_speed, None)
+            .await?;
+        tracing::info!("Resuming after stall on axis {}", axis);
+        Ok(())
+    }
+
+    /// Pause the print job if it is printing
+    async fn pause_job(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(job) = self.state.write().await.job.as_mut()
+            && job.state() == &PrintJobState::Printing
+        {
+            job.transition(PrintJobEvent::Pause)?;
+        }
+        Ok(())
+    }
+
+    /// Pause the print job until it is resumed, failing if it is cancelled
+    async fn wait_for_resume(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.pause_job().await?;
+        loop {
+            match self.state.read().await.job.as_ref().map(PrintJob::state) {
+                Some(PrintJobState::Paused) => {}
+                Some(PrintJobState::Printing) => return Ok(()),
+                _ => return Err("Print stopped while paused".into()),
+            }
+            tokio::time::sleep(RESUME_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn get_current_position(&self) -> [f64; 4] {
+        self.motion_controller.get_current_position()
+    }
+    
+    // Add method to access state
+    pub async fn get_state(&self) -> PrinterState {
+        self.state.read().await.clone()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChamberConfig, Config};
+    use crate::hardware::HardwareManager;
+    use crate::motion::{MotionConfig, MotionType};
+    use crate::printer::PrinterEvent;
+    use crate::motion::kinematics::{Kinematics, KinematicsType, ScaraKinematics};
+    use crate::temperature::Heater;
+
+    async fn create_test_processor() -> GCodeProcessor {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut hardware_manager = HardwareManager::new(config.clone());
+        hardware_manager.connect().await.unwrap();
+        let motion_config = MotionConfig::new_from_printer_config(&config);
+        let motion_controller = MotionController::new(state.clone(), hardware_manager, motion_config);
+        GCodeProcessor::new(state, motion_controller)
+    }
+
+    #[tokio::test]
+    async fn test_m572_motion_mode() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("G1 X50 F6000").await.unwrap();
+        let basic_feedrate = processor.motion_controller.get_planner().get_queue()[0].feedrate;
+        let position = processor.motion_controller.get_current_position();
+
+        processor.process_command("M572 R1").await.unwrap();
+        assert_eq!(processor.motion_mode().await, MotionMode::Adaptive);
+        assert_eq!(processor.motion_controller.get_current_position(), position);
+        assert!(processor.motion_controller.get_planner().get_queue().is_empty());
+
+        // The adaptive planner slows moves down from the requested feedrate
+        processor.process_command("G1 X100 F6000").await.unwrap();
+        let feedrate = processor.motion_controller.get_planner().get_queue()[0].feedrate;
+        assert!(feedrate < basic_feedrate, "{} >= {}", feedrate, basic_feedrate);
+
+        processor.process_command("M572 R0").await.unwrap();
+        assert_eq!(processor.motion_mode().await, MotionMode::Basic);
+        assert_eq!(processor.motion_controller.get_current_position()[0], 100.0);
+        processor.process_command("G1 X150 F6000").await.unwrap();
+        assert_eq!(processor.motion_controller.get_planner().get_queue()[0].feedrate, basic_feedrate);
+
+        assert!(processor.process_command("M572 R9").await.is_err());
+        assert_eq!(processor.motion_mode().await, MotionMode::Basic);
+    }
+
+    #[tokio::test]
+    async fn test_m203_motion_type_speed() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("M203 Ttravel V250").await.unwrap();
+        processor.process_command("M203 TPRINT V60").await.unwrap();
+        assert!(processor.process_command("M203 Tprint").await.is_err());
+        assert!(processor.process_command("M203 Tcruise V100").await.is_err());
+        
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("G1 X100 F30000").await.unwrap();
+        processor.process_command("G1 X200 E5 F30000").await.unwrap();
+        let queue = processor.motion_controller.get_planner().get_queue();
+        assert_eq!(queue[0].motion_type, MotionType::Travel);
+        assert_eq!(queue[0].feedrate, 250.0);
+        assert_eq!(queue[1].motion_type, MotionType::Print);
+        assert_eq!(queue[1].feedrate, 60.0);
+    }
+
+    #[tokio::test]
+    async fn test_host_line_numbers() {
+        let framed = |line: &str| format!("{}*{}", line, line.bytes().fold(0u8, |acc, byte| acc ^ byte));
+        let (event_tx, mut events) = tokio::sync::broadcast::channel(16);
+        let mut processor = create_test_processor().await
+            .with_host_framing(GCodeParserConfig {
+                enable_checksums: true,
+                strict_line_numbers: true,
+                ..GCodeParserConfig::default()
+            })
+            .with_event_sender(event_tx);
+        processor.process_host_line(&framed("N1 G28")).await.unwrap();
+        processor.process_host_line(&framed("N2 G1 X10 F3000 ; travel")).await.unwrap();
+
+        // N3 was lost; N4 isn't run and the host is asked for N3
+        let error = processor.process_host_line(&framed("N4 G1 X30")).await.unwrap_err();
+        let error = error.downcast_ref::<parser::GCodeError>().unwrap();
+        assert_eq!(error.resend_response().as_deref(), Some("Resend: 3"));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(PrinterEvent::GCodeError(parser::GCodeError::LineNumberMismatch { expected: 3, received: 4 }))
+        ));
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 1);
+        processor.process_host_line(&framed("N3 G1 X20")).await.unwrap();
+        processor.process_host_line(&framed("N4 G1 X30")).await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 3);
+
+        processor.process_host_line(&framed("N5 M110 N100")).await.unwrap();
+        processor.process_host_line(&framed("N101 G1 X40")).await.unwrap();
+
+        // Numbering wraps after N65535
+        processor.process_host_line(&framed("N102 M110 N65534")).await.unwrap();
+        processor.process_host_line(&framed("N65535 G1 X50")).await.unwrap();
+        processor.process_host_line(&framed("N0 G1 X60")).await.unwrap();
+        assert!(events.try_recv().is_err());
+
+        // Lenient framing runs lines whatever their number
+        let mut processor = create_test_processor().await;
+        processor.process_host_line("N7 G28").await.unwrap();
+        processor.process_host_line("N3 G1 X10 F3000*0").await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 1);
+    }
+
+    #[tokio::test]
+    async fn test_startup_gcode_sequence() {
+        let toml = include_str!("../printer.toml").replace("[printer]", "[printer]\nstartup_gcode = [\"G28\", \"M104 S0\"]");
+        let config: Config = toml::from_str(&toml).unwrap();
+        let mut processor = create_test_processor().await;
+        processor.process_command("M104 S200").await.unwrap();
+
+        processor.run_gcode_sequence(&config.printer.startup_gcode).await.unwrap();
+        let state = processor.get_state().await;
+        assert!(state.homed);
+        assert_eq!(processor.get_current_position().await, [0.0; 4]);
+        assert_eq!(state.tools[0].target, 0.0);
+
+        // Moves are planned as homing moves, and the queue has drained
+        processor.run_gcode_sequence(&["G1 X10 F3000".to_string(), "G1 X20 E1".to_string()]).await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 0);
+        assert_eq!(processor.get_current_position().await, [20.0, 0.0, 0.0, 1.0]);
+        assert!(processor.run_gcode_sequence(&["G1 X5".to_string(), "M104 T9 S0".to_string()]).await.is_err());
+        // The override ends with a failed sequence too
+        processor.process_command("G1 X30 E2").await.unwrap();
+        let queue = processor.motion_controller.get_planner().get_queue();
+        assert_eq!(queue.last().unwrap().motion_type, MotionType::Print);
+    }
+
+    #[tokio::test]
+    async fn test_travel_move_with_z_hop() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("M208 Z0.4").await.unwrap();
+        processor.process_command("G1 X50 Y50 F100").await.unwrap();
+
+        let planner = processor.motion_controller.get_planner();
+        assert_eq!(planner.queue_length(), 3);
+
+        let targets: Vec<[f64; 4]> = planner.get_queue().iter().map(|s| s.target).collect();
+        assert_eq!(targets[0], [0.0, 0.0, 0.4, 0.0]);
+        assert_eq!(targets[1], [50.0, 50.0, 0.4, 0.0]);
+        assert_eq!(targets[2], [50.0, 50.0, 0.0, 0.0]);
+        assert!(planner.get_queue().iter().all(|s| s.motion_type == MotionType::Travel));
+    }
+
+    #[tokio::test]
+    async fn test_z_hop_skipped_for_print_and_short_moves() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("M208 Z0.4").await.unwrap();
+
+        // Extruding moves never hop
+        processor.process_command("G1 X50 Y50 E5 F100").await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 1);
+
+        // Travel shorter than the minimum distance doesn't hop
+        processor.process_command("G1 X50.5 F100").await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 2);
+
+        // Travel that already climbs past the hop height (parking) doesn't hop
+        processor.process_command("G1 X0 Y0 Z10 F100").await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 3);
+    }
+
+    #[tokio::test]
+    async fn test_relative_positioning_accumulates() {
+        let mut processor = create_test_processor().await;
+        for command in ["G91", "G1 X10 F100", "G1 X10 F100", "G90"] {
+            processor.process_command(command).await.unwrap();
+        }
+
+        let position = processor.motion_controller.get_current_position();
+        assert_eq!(position[0], 20.0);
+
+        // Mode persists: back in absolute mode X10 is a real coordinate
+        processor.process_command("G1 X10 F100").await.unwrap();
+        assert_eq!(processor.motion_controller.get_current_position()[0], 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_relative_positioning_unspecified_axes() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G1 X5 Y7 Z1 F100").await.unwrap();
+        processor.process_command("G91").await.unwrap();
+        processor.process_command("G1 Y3 F100").await.unwrap();
+
+        let position = processor.motion_controller.get_current_position();
+        assert_eq!(&position[..3], &[5.0, 10.0, 1.0]);
+        assert_eq!(processor.get_state().await.positioning_mode, PositioningMode::Relative);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_position() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("G1 X10 Y10 E5 F100").await.unwrap();
+        processor.process_command("G92 E0").await.unwrap();
+        assert_eq!(processor.motion_controller.get_current_position(), [10.0, 10.0, 0.0, 0.0]);
+
+        // Absolute E counts from the reset, so this extrudes 1mm, not -4mm
+        processor.process_command("G1 X20 E1 F100").await.unwrap();
+        let queue = processor.motion_controller.get_planner().get_queue();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].target, [20.0, 10.0, 0.0, 1.0]);
+        assert_eq!(queue[0].motion_type, MotionType::Print);
+        processor.process_command("M400").await.unwrap();
+
+        processor.process_command("G92 X0 Y0").await.unwrap();
+        assert_eq!(processor.motion_controller.get_current_position(), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(processor.get_state().await.position, [0.0, 0.0, 0.0]);
+    }
+
+    #[tokio::test]
+    async fn test_extruder_modes() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G28").await.unwrap();
+
+        // Absolute extrusion (default): E is a coordinate
+        processor.process_command("G1 X10 E2 F100").await.unwrap();
+        processor.process_command("G1 X20 E3 F100").await.unwrap();
+        assert_eq!(processor.motion_controller.get_current_position()[3], 3.0);
+
+        // Relative extrusion: E is an amount, independent of G90/G91
+        processor.process_command("M83").await.unwrap();
+        processor.process_command("G1 X30 E3 F100").await.unwrap();
+        let position = processor.motion_controller.get_current_position();
+        assert_eq!(position[0], 30.0);
+        assert_eq!(position[3], 6.0);
+    }
+
+    #[tokio::test]
+    async fn test_z_hop_disabled_by_default() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G1 X50 Y50 F100").await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 1);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_z_hop_rejected() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("M208 Z0.4").await.unwrap();
+        assert!(processor.process_command("M208 Zabc").await.is_err());
+        assert_eq!(processor.motion_controller.get_motion_config().z_hop_height, 0.4);
+    }
+
+    #[tokio::test]
+    async fn test_home_enables_print_moves() {
+        let mut processor = create_test_processor().await;
+        assert!(processor.process_command("G1 X10 E1 F100").await.is_err());
+        processor.process_command("G1 X10 F100").await.unwrap();
+        
+        // Homing through a copy, as the web API does, counts for every copy
+        processor.clone().process_command("G28").await.unwrap();
+        assert!(processor.motion_controller.get_planner().is_homed().await);
+        processor.process_command("G1 X10 E1 F100").await.unwrap();
+        
+        processor.motion_controller.reset().await;
+        assert!(!processor.get_state().await.homed);
+        assert!(processor.process_command("G1 X20 E2 F100").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_home_stays_unhomed() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let motion = MotionController::new(state.clone(), HardwareManager::new(config.clone()), MotionConfig::new_from_printer_config(&config));
+        let mut processor = GCodeProcessor::new(state.clone(), motion);
+        
+        // The MCU never connected, so homing can't have happened
+        assert!(processor.process_command("G28").await.is_err());
+        assert!(!state.read().await.homed);
+        assert!(processor.process_command("G1 X10 E1 F100").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe_requires_home_and_bltouch() {
+        let mut processor = create_test_processor().await;
+        let error = processor.process_command("G30").await.unwrap_err();
+        assert!(error.to_string().contains("homing"), "{}", error);
+
+        processor.process_command("G28").await.unwrap();
+        let error = processor.process_command("G29").await.unwrap_err();
+        assert_eq!(error.to_string(), "No BLTouch configured");
+        assert!(processor.get_state().await.bed_mesh.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tool_change_standby() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.extruder.standby_temp = Some(150.0);
+        config.extruder.standby_delay_secs = 10.0;
+        config.extruders = vec![config.extruder.clone()];
+        let state = Arc::new(RwLock::new(PrinterState {
+            tools: config.tools().map(ToolHeater::from_config).collect(),
+            ..PrinterState::new()
+        }));
+        let motion = MotionController::new(state.clone(), HardwareManager::new(config.clone()), MotionConfig::new_from_printer_config(&config));
+        let mut processor = GCodeProcessor::new(state, motion);
+        let targets = |state: PrinterState| [state.tools[0].target, state.tools[1].target];
+
+        processor.process_command("M104 T0 S210").await.unwrap();
+        processor.process_command("M104 T1 S220").await.unwrap();
+        processor.process_command("M116").await.unwrap();
+        processor.process_command("T1").await.unwrap();
+        assert_eq!(processor.get_state().await.temperature, 220.0);
+
+        processor.process_command("T0").await.unwrap();
+        assert_eq!(targets(processor.get_state().await), [210.0, 220.0]);
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        assert_eq!(targets(processor.get_state().await), [210.0, 150.0]);
+
+        // Back to T1 before T0's delay is up: T0 stays hot, T1 is restored
+        processor.process_command("T1").await.unwrap();
+        let state = processor.get_state().await;
+        assert_eq!((state.active_tool, state.temperature), (1, 220.0));
+        assert_eq!(targets(state), [210.0, 220.0]);
+        processor.process_command("T0").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        processor.process_command("T1").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        assert_eq!(targets(processor.get_state().await), [150.0, 220.0]);
+
+        assert!(processor.process_command("T2").await.is_err());
+        assert!(processor.process_command("M116 P2").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_lookahead_buffer_size() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("M205 L32").await.unwrap();
+        assert_eq!(processor.motion_controller.get_motion_config().lookahead_buffer_size, 32);
+        
+        assert!(processor.process_command("M205 L2").await.is_err());
+        assert_eq!(processor.motion_controller.get_motion_config().lookahead_buffer_size, 32);
+    }
+
+    #[tokio::test]
+    async fn test_set_skew() {
+        let settings_path = std::env::temp_dir().join(format!("krusty-skew-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&settings_path);
+        let mut processor = create_test_processor().await.with_settings(EepromManager::new(&settings_path));
+        
+        processor.process_command("M852 I0.01").await.unwrap();
+        processor.process_command("M852 K-0.002").await.unwrap();
+        assert_eq!(
+            processor.motion_controller.get_motion_config().skew_correction,
+            SkewCorrection { xy: 0.01, xz: 0.0, yz: -0.002 }
+        );
+        
+        // Query leaves the values unchanged
+        processor.process_command("M852").await.unwrap();
+        let motors = processor.motion_controller.get_planner().get_kinematics()
+            .cartesian_to_motors(&[0.0, 100.0, 0.0]).unwrap();
+        assert!((motors[0] + 100.0 * 0.01f64.tan()).abs() < 1e-9);
+        
+        // A square printed with that correction still came out 0.5° skewed
+        let (side, residual) = (100.0f64, 0.5f64.to_radians());
+        let ac = (2.0 * side * side * (1.0 + residual.tan())).sqrt();
+        let bd = (2.0 * side * side * (1.0 - residual.tan())).sqrt();
+        processor.process_command(&format!("CALIBRATE_SKEW AC={} BD={} SIDE={}", ac, bd, side)).await.unwrap();
+        let skew = processor.motion_controller.get_motion_config().skew_correction;
+        assert!((skew.xy - (0.01 + residual)).abs() < 1e-9);
+        assert_eq!(EepromManager::new(&settings_path).load().unwrap().skew_correction, Some(skew));
+        assert!(processor.process_command("CALIBRATE_SKEW AC=141").await.is_err());
+        std::fs::remove_file(&settings_path).unwrap();
+        
+        // Only gantry kinematics support skew correction
+        processor.motion_controller.set_kinematics_handler(
+            KinematicsType::Scara,
+            Box::new(ScaraKinematics::new(150.0, 120.0, 10.0, 10.0)),
+        );
+        assert!(processor.process_command("M852 I0.01").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fan_commands() {
+        let mut processor = create_test_processor().await;
+        
+        processor.process_command("M106 S127").await.unwrap();
+        assert!((processor.get_state().await.fan.get_speed() - 127.0 / 255.0).abs() < 1e-9);
+        processor.process_command("M107").await.unwrap();
+        assert_eq!(processor.get_state().await.fan.get_speed(), 0.0);
+        
+        processor.process_command("M145 S0 H50 C60").await.unwrap();
+        let mut state = processor.get_state().await;
+        state.fan.update_temperature(55.0);
+        assert_eq!(state.fan.get_speed(), 0.5);
+        
+        assert!(processor.process_command("M145 S1 H50 C60").await.is_err());
+        assert!(processor.process_command("M145 H60 C50").await.is_err());
+
+        // In RPM mode S is the speed to hold
+        let rpm_mode = crate::config::FanConfig { fan_control_mode: FanControlMode::Rpm, ..Default::default() };
+        processor.state.write().await.fan = crate::temperature::FanController::new(&rpm_mode);
+        processor.process_command("M106 S3000").await.unwrap();
+        assert_eq!(processor.get_state().await.fan.get_target_rpm(), Some(3000.0));
+        processor.process_command("M107").await.unwrap();
+        assert_eq!(processor.get_state().await.fan.get_target_rpm(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_chamber_commands() {
+        let mut processor = create_test_processor().await;
+        assert!(processor.process_command("M141 S45").await.is_err());
+        
+        let chamber: ChamberConfig = toml::from_str(r#"
+            heater_pin = "PB10"
+            sensor_type = "EPCOS 100K B57560G104F"
+            sensor_pin = "PA5"
+            max_temp = 60.0
+            wait_timeout_secs = 600.0
+        "#).unwrap();
+        processor.state.write().await.chamber = Some(Heater::from_chamber_config(&chamber));
+        
+        // No readings: M191 gives up after the wait timeout
+        let started = tokio::time::Instant::now();
+        let error = processor.process_command("M191 S45").await.unwrap_err();
+        assert!(error.to_string().contains("did not reach 45.0°C"), "{}", error);
+        assert!(started.elapsed() >= Duration::from_secs(600));
+        let state = processor.get_state().await;
+        assert_eq!(state.chamber.as_ref().unwrap().get_target(), 45.0);
+        
+        // Returns once the chamber warms up
+        let state = processor.state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            state.write().await.chamber.as_mut().unwrap().update(44.0, 1.0).unwrap();
+        });
+        let started = tokio::time::Instant::now();
+        processor.process_command("M191 S45").await.unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(30));
+        assert!(started.elapsed() < Duration::from_secs(60));
+        
+        
+        // Above max_temp
+        assert!(processor.process_command("M141 S90").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_file() {
+        let mut processor = create_test_processor().await;
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dry_run.gcode");
+        
+        // The dwell is counted, not waited for, and M600 parks without
+        // waiting for M108
+        let report = tokio::time::timeout(Duration::from_secs(5), processor.dry_run_file(path)).await.unwrap().unwrap();
+        assert_eq!(report.commands, 16);
+        assert_eq!(report.final_position, [0.0, 0.0, 10.0, 3.0]);
+        let park = 2.0 * FILAMENT_CHANGE_Z_LIFT;
+        assert!((report.total_distance - (130.0 + park)).abs() < 1e-9);
+        let expected_time =
+            0.5 + 1.5 + 1.0 + (40.0f64 * 40.0 + 4.0 * 4.0).sqrt() / 20.0 + 1.0 + 0.1 + 0.5 + park / 300.0;
+        assert!((report.estimated_time - expected_time).abs() < 1e-9);
+        assert_eq!(report.final_state.temperature, 200.0);
+        assert!(report.final_state.dry_run);
+        
+        // Nothing was queued and the printer is back where it started
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 0);
+        assert_eq!(processor.get_current_position().await, [0.0; 4]);
+        let state = processor.get_state().await;
+        assert!(!state.dry_run);
+        assert_eq!(state.temperature, 0.0);
+        assert_eq!(state.positioning_mode, PositioningMode::Absolute);
+    }
+
+    #[tokio::test]
+    async fn test_process_file_streaming() {
+        let mut processor = create_test_processor().await;
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dry_run.gcode");
+        
+        processor.set_dry_run(true).await;
+        let commands = processor.process_file_streaming(path).await.unwrap();
+        assert_eq!(commands, 16);
+        assert_eq!(processor.get_current_position().await, [0.0, 0.0, 10.0, 3.0]);
+        assert_eq!(processor.get_state().await.temperature, 200.0);
+        
+        assert!(processor.process_file_streaming("/nonexistent/file.gcode").await.is_err());
+    }
+
+    /// MCU that accepts everything and remembers what it was sent
+    #[derive(Debug, Default)]
+    struct RecordingPort {
+        commands: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl crate::hardware::McuPort for RecordingPort {
+        fn transact<'a>(&'a self, command: &'a str) -> crate::hardware::PortFuture<'a> {
+            self.commands.lock().unwrap().push(command.to_string());
+            Box::pin(async { Ok("ok".to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_m92_extruder_steps() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        // 200 * 16 / 8 = 400 steps/mm
+        config.extruder.rotation_distance = 8.0;
+        config.extruder.gear_ratio = None;
+        let settings_path = std::env::temp_dir().join(format!("krusty-m92-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&settings_path);
+
+        let port = Arc::new(RecordingPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        let mut processor = GCodeProcessor::new(state, motion).with_settings(EepromManager::new(&settings_path));
+        assert_eq!(processor.steps_per_mm()[3], 400.0);
+
+        // Outside 5x of the configured value, or not positive
+        assert!(processor.process_command("M92 E2001").await.is_err());
+        assert!(processor.process_command("M92 E-400").await.is_err());
+        assert_eq!(processor.steps_per_mm(), [80.0, 80.0, 400.0, 400.0]);
+
+        processor.process_command("M92 E415").await.unwrap();
+        assert_eq!(processor.steps_per_mm(), [80.0, 80.0, 400.0, 415.0]);
+        let saved = EepromManager::new(&settings_path).load().unwrap();
+        assert_eq!(saved.steps_per_mm, Some([80.0, 80.0, 400.0, 415.0]));
+
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("G1 E10 F300").await.unwrap();
+        processor.motion_controller.update().await.unwrap();
+        let commands = port.commands.lock().unwrap().clone();
+        assert!(commands.contains(&"step E 4150 1".to_string()), "{:?}", commands);
+
+        let _ = std::fs::remove_file(&settings_path);
+    }
+
+    #[tokio::test]
+    async fn test_subroutine_variable_scope() {
+        let mut processor = create_test_processor().await;
+        let path = std::env::temp_dir().join(format!("krusty-subroutine-{}.gcode", std::process::id()));
+        std::fs::write(
+            &path,
+            "SET_VAR NAME=speed VALUE=20 ; shadows the caller's\n\
+             SET_VAR NAME=global.calls VALUE=1\n\
+             M99\n\
+             SET_VAR NAME=global.after_return VALUE=1\n",
+        )
+        .unwrap();
+
+        processor.process_command("SET_VAR NAME=speed VALUE=100").await.unwrap();
+        processor.process_command(&format!("M98 P{}", path.display())).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(processor.get_variable("speed"), Some(100.0));
+        assert_eq!(processor.get_variable("calls"), Some(1.0));
+        assert_eq!(processor.get_variable("after_return"), None);
+        assert!(processor.process_command("SET_VAR NAME=speed").await.is_err());
+        assert!(processor.process_command(&format!("M98 P{}", path.display())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_m400_and_dwell() {
+        let mut processor = create_test_processor().await;
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("M400").await.unwrap();
+
+        for x in 1..=5 {
+            processor.process_command(&format!("G1 X{} F6000", x)).await.unwrap();
+        }
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 5);
+        processor.process_command("M400").await.unwrap();
+        assert_eq!(processor.motion_controller.get_queue_stats().length, 0);
+        assert!(!processor.motion_controller.get_planner().is_active());
+        assert_eq!(processor.get_state().await.position, [5.0, 0.0, 0.0]);
+
+        // The dwell starts once the queue has drained
+        processor.process_command("G1 X10 F6000").await.unwrap();
+        let start = std::time::Instant::now();
+        processor.process_command("G4 P500").await.unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(processor.get_state().await.position, [10.0, 0.0, 0.0]);
+        assert!(elapsed >= Duration::from_millis(500) && elapsed < Duration::from_millis(900), "{:?}", elapsed);
+
+        assert!(processor.process_command("G4 Pabc").await.is_err());
+    }
+
+    /// MCU whose stall-detecting driver reports a stall on the third DIAG
+    /// check
+    #[derive(Debug, Default)]
+    struct StallPort {
+        diag_checks: std::sync::Mutex<u32>,
+        commands: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl crate::hardware::McuPort for StallPort {
+        fn transact<'a>(&'a self, command: &'a str) -> crate::hardware::PortFuture<'a> {
+            self.commands.lock().unwrap().push(command.to_string());
+            let mut response = "ok";
+            if command.starts_with("query_diag") {
+                let mut checks = self.diag_checks.lock().unwrap();
+                *checks += 1;
+                response = if *checks == 3 { "triggered" } else { "open" };
+            }
+            Box::pin(async move { Ok(response.to_string()) })
+        }
+    }
+
+    /// Print a short file with a stall injected after its third line
+    async fn print_with_stall(recovery: StallRecovery, stepper: &str) -> (Result<usize, String>, Arc<StallPort>, HardwareManager, Vec<PrinterEvent>) {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.steppers.get_mut(stepper).unwrap().diag_pin = Some("PG6".to_string());
+        config.stall_detection = Some(crate::config::StallDetectionConfig { recovery, poll_interval_ms: 0 });
+        let path = std::env::temp_dir().join(format!("krusty-stall-{}-{:?}-{}.gcode", stepper, recovery, std::process::id()));
+        std::fs::write(&path, "G28\nG1 X10 Y10 F3000\nG1 X20 Y10 F3000\nG1 X30 Y20 F3000\nG1 X40 Y20 F3000\n").unwrap();
+        
+        let port = Arc::new(StallPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let (event_tx, mut events) = tokio::sync::broadcast::channel(16);
+        let mut job = PrintJob::new(path.to_str().unwrap(), 0);
+        job.transition(PrintJobEvent::Start).unwrap();
+        job.transition(PrintJobEvent::PreheatComplete).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState { job: Some(job), ..PrinterState::new() }));
+        let motion = MotionController::new(state.clone(), hardware.clone(), MotionConfig::new_from_printer_config(&config));
+        let detector = StepperStallDetector::new(hardware.clone(), &config, event_tx);
+        let mut processor = GCodeProcessor::new(state.clone(), motion).with_stall_detector(detector);
+        
+        let file = path.to_str().unwrap().to_string();
+        let mut task = tokio::spawn(async move {
+            let result = processor.process_file_streaming(&file).await.map_err(|e| e.to_string());
+            (result, processor.get_current_position().await)
+        });
+        if recovery == StallRecovery::Pause {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            assert!(!task.is_finished());
+            let mut state = state.write().await;
+            let job = state.job.as_mut().unwrap();
+            assert_eq!(job.state(), &PrintJobState::Paused);
+            job.transition(PrintJobEvent::Resume).unwrap();
+        }
+        let (result, position) = (&mut task).await.unwrap();
+        if result.is_ok() {
+            assert_eq!(position, [40.0, 20.0, 0.0, 0.0]);
+        }
+        let _ = std::fs::remove_file(&path);
+        
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        (result, port, hardware, received)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stall_recovery() {
+        for recovery in [StallRecovery::Resume, StallRecovery::Pause, StallRecovery::Abort] {
+            let (result, port, hardware, events) = print_with_stall(recovery, "stepper_x").await;
+            assert_eq!(hardware.get_command_stats().stepper_stall_count, 1);
+            assert!(matches!(events[..], [PrinterEvent::StepperStalled { axis: 0 }]), "{:?}", events);
+            
+            let commands = port.commands.lock().unwrap().clone();
+            let rehomed = commands.contains(&"home_axis X".to_string());
+            if recovery == StallRecovery::Abort {
+                assert_eq!(result.unwrap_err(), "Stepper stalled on axis 0");
+                assert!(!rehomed);
+            } else {
+                // Every line still runs
+                assert_eq!(result, Ok(5), "{:?}", recovery);
+                assert!(rehomed);
+                // Lifted off the print before homing
+                let home = commands.iter().position(|command| command == "home_axis X").unwrap();
+                assert_eq!(commands[home - 1], "step Z 800 1");
+            }
+        }
+
+        // Z can't be rehomed over the print
+        let (result, port, _hardware, events) = print_with_stall(StallRecovery::Resume, "stepper_z").await;
+        assert!(matches!(events[..], [PrinterEvent::StepperStalled { axis: 2 }]), "{:?}", events);
+        assert_eq!(result.unwrap_err(), "Stepper stalled on axis 2");
+        assert!(!port.commands.lock().unwrap().contains(&"home_axis Z".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_pause_parameters() {
+        let mut processor = create_test_processor().await;
+        assert!(processor.process_command("M226").await.is_err());
+        assert!(processor.process_command("M226 é5").await.is_err());
+
+        // M600 ignores what it doesn't know; a dry run doesn't wait for M108
+        processor.set_dry_run(true).await;
+        processor.process_command("M600 é5 Z2").await.unwrap();
+        assert_eq!(processor.motion_controller.get_dry_run_stats().total_distance, 4.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pause_at_layer() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let port = Arc::new(RecordingPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let mut job = PrintJob::new("layers.gcode", 0);
+        job.transition(PrintJobEvent::Start).unwrap();
+        job.transition(PrintJobEvent::PreheatComplete).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState { job: Some(job), ..PrinterState::new() }));
+        let motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        let mut processor = GCodeProcessor::new(state.clone(), motion);
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("M226 L5").await.unwrap();
+
+        // Stand in for the user: resume whenever the print pauses
+        let pauses = Arc::new(Mutex::new(Vec::new()));
+        let resumer = tokio::spawn({
+            let (state, pauses) = (state.clone(), pauses.clone());
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    let mut state = state.write().await;
+                    let layer = state.layer_tracker.layer();
+                    let job = state.job.as_mut().unwrap();
+                    if job.state() == &PrintJobState::Paused {
+                        job.transition(PrintJobEvent::Resume).unwrap();
+                        pauses.lock().unwrap().push(layer);
+                    }
+                }
+            }
+        });
+
+        processor.process_command("M83").await.unwrap();
+        for layer in 1..=6 {
+            processor.process_command(&format!("G1 Z{:.1} F600", layer as f64 * 0.2)).await.unwrap();
+            processor.process_command("G1 X20 Y20 E1 F1200").await.unwrap();
+            processor.process_command("G1 X0 Y0 E1 F1200").await.unwrap();
+        }
+        resumer.abort();
+
+        // Paused once, as layer 5 started
+        assert_eq!(*pauses.lock().unwrap(), vec![5]);
+        let commands = port.commands.lock().unwrap().clone();
+        assert_eq!(commands.iter().filter(|c| *c == "disable_extruder").count(), 1);
+        assert_eq!(state.read().await.layer_tracker.layer(), 6);
+        assert!(state.read().await.pause_conditions.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nozzle_wipe() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let mut processor = create_test_processor().await.with_nozzle_wipe(NozzleWipe::from_config(&config.printer));
+        processor.process_command("G28").await.unwrap();
+        
+        // No cold wipes; unknown parameters, even multi-byte ones, are ignored
+        assert!(processor.process_command("G12 P0").await.is_err());
+        assert!(processor.process_command("G12 é1").await.is_err());
+        processor.process_command("M104 S200").await.unwrap();
+        assert!(processor.process_command("G12 P3").await.is_err());
+        
+        processor.process_command("G12 P2 S2 T3").await.unwrap();
+        assert!(processor.motion_controller.get_queue_stats().length > 0);
+        assert_eq!(processor.get_current_position().await, [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_layer_time() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.printer.min_layer_time_secs = 10.0;
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut hardware = HardwareManager::new(config.clone());
+        hardware.connect().await.unwrap();
+        let motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        let mut processor = GCodeProcessor::new(state.clone(), motion);
+        processor.process_command("G28").await.unwrap();
+        processor.process_command("M83").await.unwrap();
+        processor.process_command("M106 S51").await.unwrap();
+        
+        processor.process_command("G1 Z0.2 X10 E1 F50").await.unwrap();
+        tokio::time::advance(Duration::from_secs(3)).await;
+        processor.process_command("G1 Z0.4 X20 E1 F50").await.unwrap();
+        {
+            let state = state.read().await;
+            assert!((state.feedrate_factor - 0.3).abs() < 1e-9);
+            assert_eq!(state.fan.get_speed(), 1.0);
+        }
+        
+        // At 30% the next layer takes the full 10s, so the slowdown holds
+        tokio::time::advance(Duration::from_secs(10)).await;
+        processor.process_command("G1 Z0.6 X10 E1 F50").await.unwrap();
+        assert!((state.read().await.feedrate_factor - 0.3).abs() < 1e-9);
+        
+        // A slow layer ends it and gives the fan back
+        tokio::time::advance(Duration::from_secs(60)).await;
+        processor.process_command("G1 Z0.8 X20 E1 F50").await.unwrap();
+        let state = state.read().await;
+        assert_eq!(state.feedrate_factor, 1.0);
+        assert_eq!(state.fan.get_speed(), 0.2);
+        
+        drop(state);
+        processor.process_command("M73 P42 R7 é1").await.unwrap();
+        let state = processor.get_state().await;
+        assert_eq!((state.print_progress, state.slicer_remaining_mins), (0.42, Some(7.0)));
+    }
+
+    #[tokio::test]
+    async fn test_input_shaper() {
+        let mut processor = create_test_processor().await;
+        processor.add_shaper_preset(ShaperPreset { name: "bench".to_string(), ..suggest_from_frequency(48.5) }).unwrap();
+        let shaper = |processor: &GCodeProcessor| processor.motion_controller.get_motion_config().input_shaper.clone();
+        
+        processor.handle_input_shaper(&["M593", "PRESET=bench"]).unwrap();
+        processor.handle_input_shaper(&["M593", "D0.05"]).unwrap();
+        let active = shaper(&processor).unwrap();
+        assert_eq!((active.name.as_str(), active.frequency, active.damping), ("bench", 48.5, 0.05));
+        assert!(processor.handle_input_shaper(&["M593", "PRESET=missing"]).is_err());
+        assert!(processor.handle_input_shaper(&["M593", "D1.5"]).is_err());
+        
+        processor.handle_input_shaper(&["M593", "F0"]).unwrap();
+        assert_eq!(shaper(&processor), None);
+        processor.handle_input_shaper(&["M593", "F20"]).unwrap();
+        assert_eq!(shaper(&processor).unwrap().shaper_type, crate::motion::shaper_presets::InputShaperType::TwoHumpEi);
+    }
+
+    #[tokio::test]
+    async fn test_slicer_progress_comments() {
+        let mut processor = create_test_processor().await;
+        let path = std::env::temp_dir().join(format!("krusty-meta-{}.gcode", std::process::id()));
+        std::fs::write(&path, ";TIME:1000\n;LAYER_COUNT:4\n;LAYER:0\nG90\n;TIME_ELAPSED:250\n;LAYER:1\nM83\n").unwrap();
+        let commands = processor.process_file_streaming(path.to_str().unwrap()).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(commands, 2);
+        let state = processor.get_state().await;
+        assert_eq!((state.current_layer, state.total_layers), (Some(2), Some(4)));
+        assert_eq!((state.print_progress, state.slicer_remaining_mins), (0.25, Some(12.5)));
+
+        // Without times, layers give the progress
+        let mut processor = create_test_processor().await;
+        processor.process_command("; total layers count = 10").await.unwrap();
+        processor.process_command("; current layer = 3").await.unwrap();
+        assert_eq!(processor.get_state().await.print_progress, 0.2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clog_recovery() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let clog_config = crate::config::ClogDetectionConfig {
+            clog_threshold_mm: 2.0,
+            clog_window_secs: 10.0,
+            window_samples: 50,
+            recovery: true,
+        };
+        let path = std::env::temp_dir().join(format!("krusty-clog-{}.gcode", std::process::id()));
+        std::fs::write(&path, "G28\nG1 X10 Y10 F3000\nG1 X20 Y10 F3000\n").unwrap();
+        
+        let (event_tx, mut events) = tokio::sync::broadcast::channel(16);
+        let detector = crate::motion::ClogDetector::new(&clog_config, event_tx);
+        let mut job = PrintJob::new(path.to_str().unwrap(), 0);
+        job.transition(PrintJobEvent::Start).unwrap();
+        job.transition(PrintJobEvent::PreheatComplete).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState { job: Some(job), ..PrinterState::new() }));
+        let mut hardware = HardwareManager::new(config.clone());
+        hardware.connect().await.unwrap();
+        let mut motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        motion.set_clog_detector(detector.clone());
+        let mut processor = GCodeProcessor::new(state.clone(), motion);
+        
+        // The steps fell 5mm behind the plan
+        detector.record(0.0, 0.0);
+        assert_eq!(detector.record(5.0, 0.0), Some(5.0));
+        
+        let file = path.to_str().unwrap().to_string();
+        let task = tokio::spawn(async move {
+            let result = processor.process_file_streaming(&file).await.map_err(|e| e.to_string());
+            (result, processor.get_current_position().await)
+        });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!task.is_finished());
+        {
+            let mut state = state.write().await;
+            let job = state.job.as_mut().unwrap();
+            assert_eq!(job.state(), &PrintJobState::Paused);
+            job.transition(PrintJobEvent::Resume).unwrap();
+        }
+        let (result, position) = task.await.unwrap();
+        assert_eq!(result, Ok(3));
+        // Retracted 30mm and pushed 35mm back through
+        assert_eq!(position, [20.0, 10.0, 0.0, 5.0]);
+        assert!(matches!(events.try_recv(), Ok(PrinterEvent::ClogDetected { shortfall_mm }) if shortfall_mm == 5.0));
+        let _ = std::fs::remove_file(&path);
+        
+        let mut processor = create_test_processor().await;
+        assert!(processor.process_command("M104 T0 R0").await.is_err());
+        processor.motion_controller.set_clog_detector(detector.clone());
+        processor.process_command("M104 T0 R0").await.unwrap();
+        assert!(!detector.recovery_enabled(0));
+    }
+
+    /// Hangprinter whose line encoders follow the steps the MCU is sent
+    #[derive(Debug)]
+    struct HangprinterPort {
+        anchors: [[f64; 3]; 4],
+        steps_per_mm: [f64; 3],
+        steps: std::sync::Mutex<[i64; 3]>,
+        encoder_queries: std::sync::Mutex<usize>,
+    }
+
+    impl crate::hardware::McuPort for HangprinterPort {
+        fn transact<'a>(&'a self, command: &'a str) -> crate::hardware::PortFuture<'a> {
+            let words: Vec<&str> = command.split_whitespace().collect();
+            let mut response = "ok".to_string();
+            if let ["step", axis, count, dir] = words[..]
+                && let Some(axis) = "XYZ".find(axis)
+            {
+                let count: i64 = count.parse().unwrap();
+                self.steps.lock().unwrap()[axis] += if dir == "1" { count } else { -count };
+            } else if let Some(pin) = command.strip_prefix("query_encoder pin=L") {
+                *self.encoder_queries.lock().unwrap() += 1;
+                let steps = *self.steps.lock().unwrap();
+                let position: [f64; 3] = std::array::from_fn(|axis| steps[axis] as f64 / self.steps_per_mm[axis]);
+                let anchor = self.anchors[pin.parse::<usize>().unwrap()];
+                let length = |point: [f64; 3]| (0..3).map(|i| (anchor[i] - point[i]).powi(2)).sum::<f64>().sqrt();
+                response = (((length(position) - length([0.0; 3])) * 100.0).round() as i64).to_string();
+            }
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calibrate_hangprinter() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let anchors = [[0.0, -150.0, -20.0], [130.0, 75.0, -20.0], [-130.0, 75.0, -20.0], [0.0, 0.0, 200.0]];
+        let port = Arc::new(HangprinterPort {
+            anchors,
+            steps_per_mm: [80.0, 80.0, 400.0],
+            steps: std::sync::Mutex::new([0; 3]),
+            encoder_queries: std::sync::Mutex::new(0),
+        });
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        let hangprinter = HangprinterConfig {
+            anchors: [[2.0, -148.0, -20.0], [131.0, 74.0, -21.0], [-129.0, 76.0, -19.0], [1.0, 1.0, 203.0]],
+            encoder_pins: (0..4).map(|line| format!("L{}", line)).collect(),
+            encoder_counts_per_mm: 100.0,
+            calibration_radius: 10.0,
+            calibration_height: 10.0,
+        };
+
+        let mut processor = GCodeProcessor::new(state.clone(), motion.clone());
+        assert!(processor.process_command("CALIBRATE_HANGPRINTER").await.is_err());
+
+        let mut processor = GCodeProcessor::new(state, motion).with_hangprinter(hangprinter);
+        assert!(processor.process_command("CALIBRATE_HANGPRINTER").await.is_err());
+        processor.process_command("G28").await.unwrap();
+        assert!(processor.process_command("CALIBRATE_HANGPRINTER N=4").await.is_err());
+        processor.process_command("CALIBRATE_HANGPRINTER N=5").await.unwrap();
+        assert_eq!(*port.encoder_queries.lock().unwrap(), 20);
+        assert_eq!(processor.motion_controller.get_current_position()[..3], [0.0; 3]);
+    }
+
+    /// Delta over a flat bed at Z0 whose real geometry is `actual` while the
+    /// probe moves by `believed`, with a BLTouch triggering at the nozzle
+    #[derive(Debug)]
+    struct DeltaBedPort {
+        actual: DeltaKinematics,
+        believed: DeltaKinematics,
+        /// Toolhead [X, Y, Z] and whether the pin is out
+        toolhead: std::sync::Mutex<([f64; 3], bool)>,
+    }
+
+    impl DeltaBedPort {
+        /// Real nozzle height when the firmware puts it at `position`
+        fn bed_height(&self, position: [f64; 3]) -> f64 {
+            let motors = self.believed.cartesian_to_motors(&position).unwrap();
+            self.actual.motors_to_cartesian(&motors).unwrap()[2]
+        }
+    }
+
+    impl crate::hardware::McuPort for DeltaBedPort {
+        fn transact<'a>(&'a self, command: &'a str) -> crate::hardware::PortFuture<'a> {
+            let field = |key: &str| -> f64 {
+                command.split_whitespace().find_map(|part| part.strip_prefix(key)?.strip_prefix('=')?.parse().ok()).unwrap()
+            };
+            let mut toolhead = self.toolhead.lock().unwrap();
+            let response = match command.split_whitespace().next() {
+                Some("move_to") => {
+                    toolhead.0 = [field("x"), field("y"), field("z")];
+                    "ok"
+                }
+                // 700us deploys the pin
+                Some("set_servo") => {
+                    toolhead.1 = field("pulse_us") == 700.0;
+                    "ok"
+                }
+                Some("query_endstop") if toolhead.1 && self.bed_height(toolhead.0) <= 0.0 => "triggered",
+                Some("query_endstop") => "open",
+                _ => "ok",
+            };
+            Box::pin(async move { Ok(response.to_string()) })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delta_calibration() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.bltouch = Some(toml::from_str("servo_pin = \"PA1\"\nendstop_pin = \"PB1\"\nz_offset = 0.0\nprobe_step = 0.001").unwrap());
+        let delta = DeltaConfig {
+            diagonal_rod: 250.0,
+            radius: 120.0,
+            print_radius: 100.0,
+            endstop_corrections: [0.0; 3],
+            tower_angle_corrections: [0.0; 3],
+        };
+        let mut actual = DeltaKinematics::from_config(&delta);
+        actual.endstop_corrections = [0.8, -0.5, 0.3];
+        actual.tower_angle_corrections = [0.4, -0.3, 0.25];
+        let port = Arc::new(DeltaBedPort {
+            actual,
+            believed: DeltaKinematics::from_config(&delta),
+            toolhead: std::sync::Mutex::new(([0.0, 0.0, 5.0], false)),
+        });
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        let settings_path = std::env::temp_dir().join(format!("krusty-delta-settings-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&settings_path);
+
+        let mut processor = GCodeProcessor::new(state.clone(), motion.clone());
+        processor.process_command("G28").await.unwrap();
+        assert_eq!(processor.process_command("G33").await.unwrap_err().to_string(), "No delta configured");
+
+        let mut processor = GCodeProcessor::new(state, motion)
+            .with_delta(delta)
+            .with_settings(EepromManager::new(&settings_path));
+        processor.motion_controller.set_position([0.0, 0.0, 5.0, 0.0]);
+        assert!(processor.process_command("G33 P6").await.is_err());
+        processor.process_command("G33 P13 V2").await.unwrap();
+
+        // The fitted geometry puts the nozzle on the bed everywhere
+        let calibrated = DeltaKinematics::from_config(&processor.delta.as_ref().unwrap().lock().unwrap());
+        let check = DeltaCalibrator::new(37, 95.0).unwrap();
+        for &[x, y] in check.probe_points() {
+            let motors = calibrated.cartesian_to_motors(&[x, y, 0.0]).unwrap();
+            let height = port.actual.motors_to_cartesian(&motors).unwrap()[2];
+            assert!(height.abs() < 0.01, "{:.4}mm off at X{} Y{}", height, x, y);
+        }
+        assert_eq!(processor.motion_controller.get_planner().kinematics_type(), KinematicsType::Delta);
+        let saved = EepromManager::new(&settings_path).load().unwrap();
+        assert_eq!(saved.delta_endstop_corrections, Some(calibrated.endstop_corrections));
+        assert_eq!(saved.delta_tower_angle_corrections, Some(calibrated.tower_angle_corrections));
+        std::fs::remove_file(&settings_path).unwrap();
+    }
+}
@@ -0,0 +1,646 @@
+// src/gcode/parser.rs - Line framing for streamed G-code (line numbers, checksums)
+use std::fmt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+use tokio::sync::broadcast;
+use crate::printer::PrinterEvent;
+
+/// Errors raised while framing incoming G-code lines
+#[derive(Debug, Clone, PartialEq)]
+pub enum GCodeError {
+    /// The `*<checksum>` suffix doesn't match the line contents
+    ChecksumMismatch { line: String, expected: u8, computed: u8 },
+
+    /// A checksum was required but the line has none
+    MissingChecksum(String),
+
+    /// `N<num>` prefix or `*<checksum>` suffix could not be parsed
+    Malformed(String),
+
+    /// The line number is not the one expected next
+    LineNumberMismatch { expected: u32, received: u32 },
+
+    /// Reading the input failed
+    Io(String),
+
+    /// Recovery gave up after this many bad lines in a row
+    TooManyErrors(usize),
+}
+
+impl fmt::Display for GCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GCodeError::ChecksumMismatch { line, expected, computed } => {
+                write!(f, "Checksum mismatch (expected {}, computed {}): {}", expected, computed, line)
+            }
+            GCodeError::MissingChecksum(line) => write!(f, "Missing checksum: {}", line),
+            GCodeError::Malformed(line) => write!(f, "Malformed line: {}", line),
+            GCodeError::LineNumberMismatch { expected, received } => {
+                write!(f, "Line number mismatch: expected {}, received {}", expected, received)
+            }
+            GCodeError::Io(message) => write!(f, "Read error: {}", message),
+            GCodeError::TooManyErrors(count) => write!(f, "Aborting after {} consecutive bad lines", count),
+        }
+    }
+}
+
+impl std::error::Error for GCodeError {}
+
+impl GCodeError {
+    /// `Resend: <n>` asking a streaming host to resend from the expected
+    /// line, for errors that leave a gap in the line numbers
+    pub fn resend_response(&self) -> Option<String> {
+        match self {
+            GCodeError::LineNumberMismatch { expected, .. } => Some(format!("Resend: {}", expected)),
+            _ => None,
+        }
+    }
+}
+
+/// What the parser does when a line fails to parse
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorRecovery {
+    /// Return the error and stop
+    #[default]
+    Abort,
+
+    /// Discard the bad line and continue with the next one
+    SkipLine,
+
+    /// Discard input until the next line carrying an `N<num>` marker
+    SkipToNextN,
+}
+
+/// Parser behavior settings
+#[derive(Debug, Clone)]
+pub struct GCodeParserConfig {
+    /// Require and verify `N<num> ... *<checksum>` framing on every line
+    pub enable_checksums: bool,
+
+    /// How to continue after a bad line
+    pub error_recovery: ErrorRecovery,
+
+    /// Give up once more than this many bad lines are skipped in a row
+    pub max_consecutive_errors: usize,
+
+    /// Return comment-only lines as commands starting with `;` instead of skipping them
+    pub keep_comments: bool,
+
+    /// With `enable_checksums`, refuse numbered lines that are out of
+    /// sequence so the host resends (Marlin-style flow control)
+    pub strict_line_numbers: bool,
+}
+
+impl Default for GCodeParserConfig {
+    fn default() -> Self {
+        Self {
+            enable_checksums: false,
+            error_recovery: ErrorRecovery::Abort,
+            max_consecutive_errors: 3,
+            keep_comments: false,
+            strict_line_numbers: false,
+        }
+    }
+}
+
+/// A framed command ready for the G-code processor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedLine {
+    /// Line number from the `N` prefix, if any
+    pub line_number: Option<u32>,
+
+    /// Command text with line number, checksum and comments removed
+    pub command: String,
+}
+
+/// Verify the `*<checksum>` suffix of a line
+///
+/// The checksum is the XOR of every byte before the `*`, including the
+/// `N<num>` prefix. Returns the command with both prefix and suffix removed.
+pub fn verify_checksum(line: &str) -> Result<&str, GCodeError> {
+    let line = line.trim();
+    let (body, checksum) = line
+        .rsplit_once('*')
+        .ok_or_else(|| GCodeError::MissingChecksum(line.to_string()))?;
+
+    let expected: u8 = checksum
+        .trim()
+        .parse()
+        .map_err(|_| GCodeError::Malformed(line.to_string()))?;
+    let computed = body.bytes().fold(0u8, |acc, byte| acc ^ byte);
+
+    if computed != expected {
+        return Err(GCodeError::ChecksumMismatch {
+            line: line.to_string(),
+            expected,
+            computed,
+        });
+    }
+
+    Ok(split_line_number(body)?.1)
+}
+
+/// Split an optional `N<num>` prefix from a line
+pub(super) fn split_line_number(line: &str) -> Result<(Option<u32>, &str), GCodeError> {
+    let line = line.trim();
+    let Some(rest) = line.strip_prefix(['N', 'n']) else {
+        return Ok((None, line));
+    };
+
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let number = rest[..digits]
+        .parse()
+        .map_err(|_| GCodeError::Malformed(line.to_string()))?;
+    Ok((Some(number), rest[digits..].trim()))
+}
+
+/// One `<letter><number>` word of a command, e.g. `G1` or `X10.5`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GCodeWord {
+    /// Upper-case address letter
+    pub letter: char,
+    pub value: f64,
+}
+
+impl GCodeWord {
+    /// Parse a single word; the value must be a finite number
+    pub fn parse(word: &str) -> Result<Self, GCodeError> {
+        let mut chars = word.chars();
+        let letter = chars
+            .next()
+            .filter(char::is_ascii_alphabetic)
+            .ok_or_else(|| GCodeError::Malformed(word.to_string()))?;
+        let value: f64 = chars
+            .as_str()
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .ok_or_else(|| GCodeError::Malformed(word.to_string()))?;
+        Ok(Self {
+            letter: letter.to_ascii_uppercase(),
+            value,
+        })
+    }
+}
+
+impl fmt::Display for GCodeWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.letter, self.value)
+    }
+}
+
+/// Split a framed command (as in `ParsedLine::command`) into its words
+pub fn parse_words(command: &str) -> Result<Vec<GCodeWord>, GCodeError> {
+    command.split_whitespace().map(GCodeWord::parse).collect()
+}
+
+/// Highest line number a streaming host sends before wrapping to 0
+pub const MAX_LINE_NUMBER: u32 = 65535;
+
+/// Line numbers of commands streamed by a host, which start at 1 and
+/// wrap after `MAX_LINE_NUMBER`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineSequenceTracker {
+    expected: u32,
+}
+
+impl Default for LineSequenceTracker {
+    fn default() -> Self {
+        Self { expected: 1 }
+    }
+}
+
+impl LineSequenceTracker {
+    /// Line number the next line must carry
+    pub fn expected(&self) -> u32 {
+        self.expected
+    }
+
+    /// Accept line `number` if it is the next one; a higher number means
+    /// lines were lost and a lower one that a line was sent again
+    pub fn check(&mut self, number: u32) -> Result<(), GCodeError> {
+        if number != self.expected {
+            return Err(GCodeError::LineNumberMismatch { expected: self.expected, received: number });
+        }
+        self.reset(number);
+        Ok(())
+    }
+
+    /// Continue the sequence after line `number` (M110)
+    pub fn reset(&mut self, number: u32) {
+        self.expected = if number >= MAX_LINE_NUMBER { 0 } else { number + 1 };
+    }
+}
+
+/// Frames raw lines into commands, tracking the line number sequence
+#[derive(Debug, Clone)]
+pub struct GCodeParser {
+    config: GCodeParserConfig,
+
+    /// Line number the next numbered line must carry
+    sequence: LineSequenceTracker,
+
+    /// Accept the next numbered line as the new sequence start
+    resynchronizing: bool,
+}
+
+impl GCodeParser {
+    pub fn new(config: GCodeParserConfig) -> Self {
+        Self {
+            config,
+            sequence: LineSequenceTracker::default(),
+            resynchronizing: false,
+        }
+    }
+
+    /// Take the next numbered line's number as correct, whatever it is
+    pub fn resynchronize(&mut self) {
+        self.resynchronizing = true;
+    }
+
+    pub fn get_config(&self) -> &GCodeParserConfig {
+        &self.config
+    }
+
+    /// Line number expected on the next numbered line
+    pub fn expected_line_number(&self) -> u32 {
+        self.sequence.expected()
+    }
+
+    /// Parse one raw line; returns None for blank and comment-only lines
+    pub fn parse_line(&mut self, raw: &str) -> Result<Option<ParsedLine>, GCodeError> {
+        let raw = raw.trim();
+        if raw.starts_with(';') && self.config.keep_comments {
+            return Ok(Some(ParsedLine { line_number: None, command: raw.to_string() }));
+        }
+        if raw.is_empty() || raw.starts_with(';') {
+            return Ok(None);
+        }
+
+        let framed = if self.config.enable_checksums {
+            verify_checksum(raw)?;
+            raw.rsplit_once('*').map(|(body, _)| body).unwrap_or(raw)
+        } else {
+            // Hosts may still send framing; drop it unchecked
+            raw.split_once('*').map(|(body, _)| body).unwrap_or(raw)
+        };
+
+        let (line_number, command) = split_line_number(framed)?;
+        let command = command.split(';').next().unwrap_or("").trim().to_string();
+
+        if Self::is_line_number_reset(&command) {
+            // M110 N<n>, or N<n> M110: the next line carries n + 1
+            let reset = Self::line_number_reset(&command).or(line_number).unwrap_or(0);
+            self.sequence.reset(reset);
+            self.resynchronizing = false;
+        } else if let Some(number) = line_number {
+            let strict = self.config.enable_checksums && self.config.strict_line_numbers;
+            if strict && !self.resynchronizing {
+                self.sequence.check(number)?;
+            } else {
+                self.sequence.reset(number);
+            }
+            self.resynchronizing = false;
+        }
+
+        if command.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(ParsedLine { line_number, command }))
+    }
+
+    fn is_line_number_reset(command: &str) -> bool {
+        command.split_whitespace().next().is_some_and(|word| word.eq_ignore_ascii_case("M110"))
+    }
+
+    /// The N argument of an `M110 N<n>` command
+    fn line_number_reset(command: &str) -> Option<u32> {
+        if !Self::is_line_number_reset(command) {
+            return None;
+        }
+        command.split_whitespace().skip(1).find_map(|part| part.strip_prefix(['N', 'n'])?.parse().ok())
+    }
+}
+
+/// Reads and frames G-code lines from an async source (serial port, file, socket)
+pub struct AsyncGCodeParser<R> {
+    lines: Lines<R>,
+    parser: GCodeParser,
+    consecutive_errors: usize,
+    event_tx: broadcast::Sender<PrinterEvent>,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncGCodeParser<R> {
+    pub fn new(reader: R, config: GCodeParserConfig) -> Self {
+        let (event_tx, _) = broadcast::channel(16);
+        Self {
+            lines: reader.lines(),
+            parser: GCodeParser::new(config),
+            consecutive_errors: 0,
+            event_tx,
+        }
+    }
+
+    pub fn get_parser(&self) -> &GCodeParser {
+        &self.parser
+    }
+
+    /// Subscribe to parse error events for skipped lines
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PrinterEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Read the next command, skipping blank and comment lines
+    ///
+    /// Bad lines are handled according to the configured `ErrorRecovery`.
+    /// Returns None at end of input.
+    pub async fn next_command(&mut self) -> Option<Result<ParsedLine, GCodeError>> {
+        let mut skipping_to_n = false;
+
+        loop {
+            let raw = match self.lines.next_line().await {
+                Ok(Some(raw)) => raw,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(GCodeError::Io(e.to_string()))),
+            };
+
+            if skipping_to_n {
+                if !raw.trim_start().starts_with(['N', 'n']) {
+                    tracing::debug!("Skipping unnumbered line while resynchronizing: {}", raw);
+                    continue;
+                }
+                self.parser.resynchronize();
+                skipping_to_n = false;
+            }
+
+            let error = match self.parser.parse_line(&raw) {
+                Ok(Some(line)) => {
+                    self.consecutive_errors = 0;
+                    return Some(Ok(line));
+                }
+                Ok(None) => continue,
+                Err(e) => e,
+            };
+
+            let recovery = self.parser.get_config().error_recovery;
+            if recovery == ErrorRecovery::Abort {
+                return Some(Err(error));
+            }
+
+            self.consecutive_errors += 1;
+            if self.consecutive_errors > self.parser.get_config().max_consecutive_errors {
+                tracing::error!("G-code error: {} - giving up", error);
+                return Some(Err(GCodeError::TooManyErrors(self.consecutive_errors)));
+            }
+
+            tracing::warn!("Skipping bad G-code line: {}", error);
+            let _ = self.event_tx.send(PrinterEvent::GCodeError(error));
+            skipping_to_n = recovery == ErrorRecovery::SkipToNextN;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Append the checksum a host would send
+    fn with_checksum(line: &str) -> String {
+        let checksum = line.bytes().fold(0u8, |acc, byte| acc ^ byte);
+        format!("{}*{}", line, checksum)
+    }
+
+    fn checked_config(error_recovery: ErrorRecovery) -> GCodeParserConfig {
+        GCodeParserConfig {
+            enable_checksums: true,
+            error_recovery,
+            ..GCodeParserConfig::default()
+        }
+    }
+
+    fn checked_parser() -> GCodeParser {
+        GCodeParser::new(checked_config(ErrorRecovery::Abort))
+    }
+
+    /// Read every command until end of input or the first returned error
+    async fn read_all(input: &str, config: GCodeParserConfig) -> (Vec<String>, Option<GCodeError>, usize) {
+        let mut parser = AsyncGCodeParser::new(input.as_bytes(), config);
+        let mut events = parser.subscribe_events();
+        let mut commands = Vec::new();
+        let mut error = None;
+        while let Some(result) = parser.next_command().await {
+            match result {
+                Ok(line) => commands.push(line.command),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        let mut event_count = 0;
+        while events.try_recv().is_ok() {
+            event_count += 1;
+        }
+        (commands, error, event_count)
+    }
+
+    #[test]
+    fn test_correct_checksum() {
+        assert_eq!(verify_checksum("N12 G1 X10*98"), Ok("G1 X10"));
+
+        let mut parser = checked_parser();
+        let line = parser.parse_line(&with_checksum("N0 G28 ; home")).unwrap().unwrap();
+        assert_eq!(line, ParsedLine { line_number: Some(0), command: "G28".to_string() });
+        assert_eq!(parser.expected_line_number(), 1);
+    }
+
+    #[test]
+    fn test_parse_words() {
+        let words = parse_words("g1 X10.5 y-2 E.25").unwrap();
+        assert_eq!(words[0], GCodeWord { letter: 'G', value: 1.0 });
+        assert_eq!(words[1], GCodeWord { letter: 'X', value: 10.5 });
+        assert_eq!(words[2], GCodeWord { letter: 'Y', value: -2.0 });
+        assert_eq!(words[3], GCodeWord { letter: 'E', value: 0.25 });
+        assert!(parse_words("G1 X1.2.3").is_err());
+        assert!(parse_words("G1 X1e999").is_err());
+        assert!(parse_words("G1 XNaN").is_err());
+    }
+
+    #[test]
+    fn test_wrong_checksum() {
+        assert!(matches!(
+            verify_checksum("N12 G1 X10*71"),
+            Err(GCodeError::ChecksumMismatch { expected: 71, computed: 98, .. })
+        ));
+        assert!(matches!(verify_checksum("N12 G1 X10"), Err(GCodeError::MissingChecksum(_))));
+        assert!(matches!(verify_checksum("N12 G1 X10*abc"), Err(GCodeError::Malformed(_))));
+
+        // Corrupted in transit: X10 became X19
+        let mut parser = checked_parser();
+        let corrupted = with_checksum("N1 G1 X10").replace("X10", "X19");
+        assert!(parser.parse_line(&corrupted).is_err());
+        assert_eq!(parser.expected_line_number(), 1);
+
+        // Without checksums enabled the same line is accepted as-is
+        let mut parser = GCodeParser::new(GCodeParserConfig::default());
+        assert_eq!(parser.parse_line(&corrupted).unwrap().unwrap().command, "G1 X19");
+    }
+
+    #[test]
+    fn test_line_number_sequence_and_rollover() {
+        let mut parser = GCodeParser::new(GCodeParserConfig {
+            strict_line_numbers: true,
+            ..checked_config(ErrorRecovery::Abort)
+        });
+        parser.parse_line(&with_checksum("N1 G28")).unwrap();
+        assert!(matches!(
+            parser.parse_line(&with_checksum("N3 G1 X1")),
+            Err(GCodeError::LineNumberMismatch { expected: 2, received: 3 })
+        ));
+
+        // Host resets numbering
+        parser.parse_line(&with_checksum("N7 M110 N99")).unwrap();
+        assert_eq!(parser.expected_line_number(), 100);
+        parser.parse_line(&with_checksum("N100 G1 X1")).unwrap();
+
+        // Numbering wraps around at the top of the range
+        parser.parse_line(&with_checksum(&format!("N101 M110 N{}", MAX_LINE_NUMBER - 1))).unwrap();
+        parser.parse_line(&with_checksum(&format!("N{} G1 X2", MAX_LINE_NUMBER))).unwrap();
+        assert_eq!(parser.expected_line_number(), 0);
+        parser.parse_line(&with_checksum("N0 G1 X3")).unwrap();
+
+        // Without strict line numbers any number is taken as the next
+        let mut parser = checked_parser();
+        parser.parse_line(&with_checksum("N5 G28")).unwrap();
+        assert_eq!(parser.expected_line_number(), 6);
+    }
+
+    #[test]
+    fn test_line_sequence_in_order() {
+        let mut tracker = LineSequenceTracker::default();
+        for number in 1..=5 {
+            tracker.check(number).unwrap();
+        }
+        assert_eq!(tracker.expected(), 6);
+    }
+
+    #[test]
+    fn test_line_sequence_skip_by_one() {
+        let mut tracker = LineSequenceTracker::default();
+        tracker.check(1).unwrap();
+        // N2 was lost
+        let error = tracker.check(3).unwrap_err();
+        assert_eq!(error, GCodeError::LineNumberMismatch { expected: 2, received: 3 });
+        assert_eq!(error.resend_response().as_deref(), Some("Resend: 2"));
+        // A resend of a line already run is refused too
+        assert_eq!(tracker.check(1).unwrap_err().resend_response().as_deref(), Some("Resend: 2"));
+        tracker.check(2).unwrap();
+        tracker.check(3).unwrap();
+    }
+
+    #[test]
+    fn test_line_sequence_rollover() {
+        let mut tracker = LineSequenceTracker::default();
+        tracker.reset(MAX_LINE_NUMBER - 1);
+        tracker.check(MAX_LINE_NUMBER).unwrap();
+        assert_eq!(tracker.expected(), 0);
+        assert!(tracker.check(MAX_LINE_NUMBER + 1).is_err());
+        tracker.check(0).unwrap();
+        tracker.check(1).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stripped_comments_leave_commands_unchanged() {
+        let input = [
+            ";FLAVOR:Marlin".to_string(),
+            with_checksum("N0 G28 ; home"),
+            ";LAYER:0".to_string(),
+            with_checksum("N1 G1 X5 Y5 E0.2"),
+            "; perimeter".to_string(),
+            with_checksum("N2 G1 X10 Y5 E0.4 ;inline"),
+        ].join("\n");
+        let kept = GCodeParserConfig { keep_comments: true, ..checked_config(ErrorRecovery::Abort) };
+        let (with_comments, error, _) = read_all(&input, kept).await;
+        assert!(error.is_none());
+        let (stripped, error, _) = read_all(&input, checked_config(ErrorRecovery::Abort)).await;
+        assert!(error.is_none());
+
+        assert_eq!(with_comments.len(), 6);
+        let commands: Vec<String> = with_comments.into_iter().filter(|command| !command.starts_with(';')).collect();
+        assert_eq!(commands, stripped);
+        assert_eq!(stripped, ["G28", "G1 X5 Y5 E0.2", "G1 X10 Y5 E0.4"]);
+    }
+
+    #[tokio::test]
+    async fn test_async_parser_reads_stream() {
+        let input = format!("; header\n\n{}\n{}\n", with_checksum("N0 G28"), with_checksum("N1 G1 X5"));
+        let mut parser = AsyncGCodeParser::new(input.as_bytes(), checked_config(ErrorRecovery::Abort));
+
+        assert_eq!(parser.next_command().await.unwrap().unwrap().command, "G28");
+        assert_eq!(parser.next_command().await.unwrap().unwrap().command, "G1 X5");
+        assert!(parser.next_command().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recovery_abort() {
+        let input = [with_checksum("N0 G28"), "N1 G1 X1*0".to_string(), with_checksum("N2 G1 X2")].join("\n");
+        let (commands, error, events) = read_all(&input, checked_config(ErrorRecovery::Abort)).await;
+        assert_eq!(commands, vec!["G28"]);
+        assert!(matches!(error, Some(GCodeError::ChecksumMismatch { .. })));
+        assert_eq!(events, 0);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_skip_line() {
+        // The corrupted line is dropped; the host resends N1
+        let input = [
+            with_checksum("N0 G28"),
+            "N1 G1 X1*0".to_string(),
+            with_checksum("N1 G1 X2"),
+            with_checksum("N2 G1 X3"),
+        ].join("\n");
+        let (commands, error, events) = read_all(&input, checked_config(ErrorRecovery::SkipLine)).await;
+        assert_eq!(commands, vec!["G28", "G1 X2", "G1 X3"]);
+        assert!(error.is_none());
+        assert_eq!(events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_skip_to_next_n() {
+        // Garbage after the bad line is dropped, numbering resumes at N5
+        let input = [
+            with_checksum("N0 G28"),
+            "N1 G1 X1*0".to_string(),
+            "G1 X2".to_string(),
+            "X3 Y4".to_string(),
+            with_checksum("N5 G1 X5"),
+            with_checksum("N6 G1 X6"),
+        ].join("\n");
+        let (commands, error, events) = read_all(&input, checked_config(ErrorRecovery::SkipToNextN)).await;
+        assert_eq!(commands, vec!["G28", "G1 X5", "G1 X6"]);
+        assert!(error.is_none());
+        assert_eq!(events, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_gives_up_after_consecutive_errors() {
+        let mut lines = vec![with_checksum("N0 G28")];
+        lines.extend((1..=4).map(|n| format!("N{} G1 X{}*0", n, n)));
+        lines.push(with_checksum("N5 G1 X5"));
+        let (commands, error, events) = read_all(&lines.join("\n"), checked_config(ErrorRecovery::SkipLine)).await;
+        assert_eq!(commands, vec!["G28"]);
+        assert_eq!(error, Some(GCodeError::TooManyErrors(4)));
+        assert_eq!(events, 3);
+
+        // Errors separated by good lines don't add up
+        let input = [
+            "N1 G1*0".to_string(), with_checksum("N0 G28"),
+            "N1 G1*0".to_string(), with_checksum("N1 G1 X1"),
+            "N2 G1*0".to_string(), with_checksum("N2 G1 X2"),
+            "N3 G1*0".to_string(), with_checksum("N3 G1 X3"),
+        ].join("\n");
+        let (commands, error, _) = read_all(&input, checked_config(ErrorRecovery::SkipLine)).await;
+        assert_eq!(commands.len(), 4);
+        assert!(error.is_none());
+    }
+}
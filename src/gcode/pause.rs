@@ -0,0 +1,153 @@
+// src/gcode/pause.rs - Pausing prints at a height, layer or line
+use std::time::Duration;
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// Z steps smaller than this are not a new layer (mm)
+const LAYER_Z_TOLERANCE: f64 = 1e-4;
+
+/// When to pause a print for a filament change
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum PauseAtCondition {
+    /// Before the first layer at or above this Z (mm)
+    AtHeight(f64),
+    /// Before this layer, counting the first as 1
+    AtLayer(u32),
+    /// Before this command of the printed file, counting from 1
+    AtGCodeLine(usize),
+    /// After the next tool change
+    AfterFilamentChange,
+}
+
+/// A pending condition and the id it can be removed by
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PauseAt {
+    pub id: u64,
+    pub condition: PauseAtCondition,
+}
+
+/// Conditions that have not triggered yet; each triggers once
+#[derive(Debug, Clone, Default)]
+pub struct PauseConditions {
+    conditions: Vec<PauseAt>,
+    next_id: u64,
+}
+
+impl PauseConditions {
+    /// Add a condition, returning its id
+    pub fn add(&mut self, condition: PauseAtCondition) -> u64 {
+        self.next_id += 1;
+        self.conditions.push(PauseAt { id: self.next_id, condition });
+        self.next_id
+    }
+
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.conditions.len();
+        self.conditions.retain(|pause| pause.id != id);
+        self.conditions.len() != before
+    }
+
+    pub fn list(&self) -> &[PauseAt] {
+        &self.conditions
+    }
+
+    /// Drop the conditions met when `layer` starts at `z`, returning whether there were any
+    pub fn take_layer(&mut self, layer: u32, z: f64) -> bool {
+        self.take(|condition| match condition {
+            PauseAtCondition::AtHeight(height) => z >= height - LAYER_Z_TOLERANCE,
+            PauseAtCondition::AtLayer(at) => layer >= at,
+            _ => false,
+        })
+    }
+
+    /// Drop the conditions met before running command `line` of a file
+    pub fn take_line(&mut self, line: usize) -> bool {
+        self.take(|condition| matches!(condition, PauseAtCondition::AtGCodeLine(at) if line >= at))
+    }
+
+    /// Drop the conditions met by a tool change
+    pub fn take_filament_change(&mut self) -> bool {
+        self.take(|condition| condition == PauseAtCondition::AfterFilamentChange)
+    }
+
+    fn take(&mut self, met: impl Fn(PauseAtCondition) -> bool) -> bool {
+        let before = self.conditions.len();
+        self.conditions.retain(|pause| !met(pause.condition));
+        self.conditions.len() != before
+    }
+}
+
+/// Counts layers from the heights extrusion happens at
+///
+/// Travel moves are ignored so Z-hops do not count as layers.
+#[derive(Debug, Clone, Default)]
+pub struct LayerTracker {
+    layer: u32,
+    z: Option<f64>,
+    /// When the current layer started
+    started: Option<Instant>,
+    /// How long the last finished layer took
+    previous_layer_time: Option<Duration>,
+}
+
+impl LayerTracker {
+    /// Current layer, 0 before anything is extruded
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Note an extrusion at `z`, returning the new layer if it starts one
+    pub fn on_extrusion(&mut self, z: f64) -> Option<u32> {
+        if self.z.is_some_and(|last| z <= last + LAYER_Z_TOLERANCE) {
+            return None;
+        }
+        let now = Instant::now();
+        self.previous_layer_time = self.started.map(|started| now - started);
+        self.started = Some(now);
+        self.z = Some(z);
+        self.layer += 1;
+        Some(self.layer)
+    }
+
+    /// How long the layer before the current one took
+    pub fn previous_layer_time(&self) -> Option<Duration> {
+        self.previous_layer_time
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions_trigger_once() {
+        let mut conditions = PauseConditions::default();
+        let height = conditions.add(PauseAtCondition::AtHeight(1.0));
+        conditions.add(PauseAtCondition::AtGCodeLine(10));
+        conditions.add(PauseAtCondition::AfterFilamentChange);
+
+        assert!(!conditions.take_layer(4, 0.8));
+        assert!(conditions.take_layer(5, 1.0));
+        assert!(!conditions.take_layer(6, 1.2));
+        assert!(!conditions.take_line(9));
+        assert!(conditions.take_line(10));
+        assert!(conditions.take_filament_change());
+        assert!(conditions.list().is_empty());
+        assert!(!conditions.remove(height));
+    }
+
+    #[test]
+    fn test_layer_tracker_ignores_lower_z() {
+        let mut tracker = LayerTracker::default();
+        assert_eq!(tracker.on_extrusion(0.2), Some(1));
+        assert_eq!(tracker.on_extrusion(0.2), None);
+        assert_eq!(tracker.on_extrusion(0.4), Some(2));
+        assert_eq!(tracker.on_extrusion(0.3), None);
+        assert_eq!(tracker.layer(), 2);
+    }
+}
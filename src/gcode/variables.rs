@@ -0,0 +1,116 @@
+// src/gcode/variables.rs - G-code variables scoped to subroutine calls
+use std::collections::HashMap;
+
+/// Prefix naming a variable in the global frame from any scope
+const GLOBAL_PREFIX: &str = "global.";
+
+/// Variables set with SET_VAR, one frame per subroutine call
+///
+/// Writes go to the innermost frame and reads look outward from it, so a
+/// subroutine's variables shadow its callers' and vanish when it returns.
+/// Names starting with `global.` always refer to the outermost frame.
+#[derive(Debug, Clone)]
+pub struct VariableScope {
+    /// Outermost first; the first frame is the global one and is never popped
+    frames: Vec<HashMap<String, f64>>,
+}
+
+impl VariableScope {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![HashMap::new()],
+        }
+    }
+
+    /// Enter a subroutine
+    pub fn push_scope(&mut self) {
+        self.frames.push(HashMap::new());
+    }
+
+    /// Leave a subroutine, dropping its variables; false at the global level
+    pub fn pop_scope(&mut self) -> bool {
+        if self.frames.len() == 1 {
+            return false;
+        }
+        self.frames.pop();
+        true
+    }
+
+    /// Subroutine calls currently entered
+    pub fn depth(&self) -> usize {
+        self.frames.len() - 1
+    }
+
+    pub fn set(&mut self, name: &str, value: f64) {
+        let (frame, name) = match name.strip_prefix(GLOBAL_PREFIX) {
+            Some(name) => (&mut self.frames[0], name),
+            None => (self.frames.last_mut().expect("global frame is never popped"), name),
+        };
+        frame.insert(name.to_string(), value);
+    }
+
+    /// The nearest binding of `name`, looking outward from the innermost scope
+    pub fn get(&self, name: &str) -> Option<f64> {
+        match name.strip_prefix(GLOBAL_PREFIX) {
+            Some(name) => self.frames[0].get(name).copied(),
+            None => self.frames.iter().rev().find_map(|frame| frame.get(name).copied()),
+        }
+    }
+}
+
+impl Default for VariableScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_shadows_global() {
+        let mut variables = VariableScope::new();
+        variables.set("global.speed", 100.0);
+        assert_eq!(variables.get("speed"), Some(100.0));
+
+        variables.push_scope();
+        variables.set("speed", 20.0);
+        assert_eq!(variables.get("speed"), Some(20.0));
+        assert_eq!(variables.get("global.speed"), Some(100.0));
+
+        // Nested calls see the nearest binding
+        variables.push_scope();
+        assert_eq!(variables.get("speed"), Some(20.0));
+        assert!(variables.pop_scope());
+
+        assert!(variables.pop_scope());
+        assert_eq!(variables.get("speed"), Some(100.0));
+        assert!(!variables.pop_scope());
+    }
+
+    #[test]
+    fn test_global_survives_return() {
+        let mut variables = VariableScope::new();
+        variables.push_scope();
+        variables.set("global.layer_count", 3.0);
+        variables.set("offset", 0.2);
+        assert_eq!(variables.depth(), 1);
+        variables.pop_scope();
+
+        assert_eq!(variables.depth(), 0);
+        assert_eq!(variables.get("layer_count"), Some(3.0));
+        assert_eq!(variables.get("offset"), None);
+    }
+
+    #[test]
+    fn test_undefined_in_nested_scope() {
+        let mut variables = VariableScope::new();
+        variables.set("defined", 1.0);
+        variables.push_scope();
+        variables.push_scope();
+        assert_eq!(variables.get("undefined"), None);
+        assert_eq!(variables.get("global.undefined"), None);
+        assert_eq!(variables.get("defined"), Some(1.0));
+    }
+}
@@ -0,0 +1,151 @@
+// src/gcode/wipe.rs - Nozzle wipe patterns for G12
+use crate::config::PrinterConfig;
+
+/// How G12 moves the nozzle over the wipe area
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WipePattern {
+    /// P0: back and forth along X
+    Straight,
+    /// P1: rectangles shrinking towards the middle
+    Spiral,
+    /// P2: diagonal zigzag across the area
+    Zigzag,
+}
+
+impl WipePattern {
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(WipePattern::Straight),
+            1 => Some(WipePattern::Spiral),
+            2 => Some(WipePattern::Zigzag),
+            _ => None,
+        }
+    }
+}
+
+/// The brush or pad the nozzle is wiped on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NozzleWipe {
+    /// Corner the wipe starts from [X, Y] (mm)
+    pub start: [f64; 2],
+    /// Extent along X (mm)
+    pub length: f64,
+    /// Extent along Y (mm)
+    pub width: f64,
+    /// Coldest nozzle temperature a wipe is allowed at (°C)
+    pub min_temp: f64,
+}
+
+impl NozzleWipe {
+    pub fn from_config(config: &PrinterConfig) -> Self {
+        Self {
+            start: [config.nozzle_wipe_x_start, config.nozzle_wipe_y_start],
+            length: config.nozzle_wipe_length_mm,
+            width: config.nozzle_wipe_width_mm,
+            min_temp: config.nozzle_wipe_min_temp,
+        }
+    }
+
+    /// Waypoints [X, Y, Z, E] for `strokes` passes of `pattern` at `z`
+    ///
+    /// `count` is the number of spiral loops or zigzag triangles.
+    pub fn waypoints(&self, pattern: WipePattern, strokes: u32, count: u32, z: f64, e: f64) -> Vec<[f64; 4]> {
+        match pattern {
+            WipePattern::Straight => straight_wipe(self, strokes, z, e),
+            WipePattern::Spiral => spiral_wipe(self, strokes, count, z, e),
+            WipePattern::Zigzag => zigzag_wipe(self, strokes, count, z, e),
+        }
+    }
+}
+
+/// Along X from the start to the far end and back, `strokes` times
+pub fn straight_wipe(wipe: &NozzleWipe, strokes: u32, z: f64, e: f64) -> Vec<[f64; 4]> {
+    let [x, y] = wipe.start;
+    let mut waypoints = vec![[x, y, z, e]];
+    for _ in 0..strokes.max(1) {
+        waypoints.push([x + wipe.length, y, z, e]);
+        waypoints.push([x, y, z, e]);
+    }
+    waypoints
+}
+
+/// Around the edge of the area, then `loops - 1` smaller rectangles
+/// inside it, `strokes` times
+pub fn spiral_wipe(wipe: &NozzleWipe, strokes: u32, loops: u32, z: f64, e: f64) -> Vec<[f64; 4]> {
+    let loops = loops.max(1);
+    let [x, y] = wipe.start;
+    let inset_step = wipe.length.min(wipe.width) / (2 * loops) as f64;
+    let mut waypoints = Vec::new();
+    for _ in 0..strokes.max(1) {
+        for i in 0..loops {
+            let inset = inset_step * i as f64;
+            let (x0, x1) = (x + inset, x + wipe.length - inset);
+            let (y0, y1) = (y + inset, y + wipe.width - inset);
+            waypoints.extend([[x0, y0, z, e], [x1, y0, z, e], [x1, y1, z, e], [x0, y1, z, e], [x0, y0, z, e]]);
+        }
+    }
+    waypoints
+}
+
+/// `triangles` diagonal zigs between the near and far Y edges along the
+/// length of the area and back, `strokes` times
+pub fn zigzag_wipe(wipe: &NozzleWipe, strokes: u32, triangles: u32, z: f64, e: f64) -> Vec<[f64; 4]> {
+    let points = 2 * triangles.max(1);
+    let [x, y] = wipe.start;
+    let forward: Vec<[f64; 4]> = (0..=points)
+        .map(|k| {
+            let edge = if k % 2 == 0 { y } else { y + wipe.width };
+            [x + wipe.length * k as f64 / points as f64, edge, z, e]
+        })
+        .collect();
+    let mut waypoints = vec![forward[0]];
+    for _ in 0..strokes.max(1) {
+        waypoints.extend(forward.iter().skip(1));
+        waypoints.extend(forward.iter().rev().skip(1));
+    }
+    waypoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIPE: NozzleWipe = NozzleWipe { start: [10.0, 200.0], length: 30.0, width: 6.0, min_temp: 170.0 };
+
+    /// Check the waypoints span the whole length and stay on the pad
+    fn assert_covers_area(waypoints: &[[f64; 4]]) {
+        let min_x = waypoints.iter().map(|p| p[0]).fold(f64::INFINITY, f64::min);
+        let max_x = waypoints.iter().map(|p| p[0]).fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!((min_x, max_x), (10.0, 40.0));
+        for point in waypoints {
+            assert!((10.0..=40.0).contains(&point[0]) && (200.0..=206.0).contains(&point[1]), "{:?}", point);
+            assert_eq!((point[2], point[3]), (0.5, 3.0));
+        }
+    }
+
+    #[test]
+    fn test_straight_wipe() {
+        let waypoints = WIPE.waypoints(WipePattern::Straight, 3, 1, 0.5, 3.0);
+        assert_covers_area(&waypoints);
+        assert_eq!(waypoints.len(), 7);
+        assert_eq!(waypoints.last(), Some(&[10.0, 200.0, 0.5, 3.0]));
+    }
+
+    #[test]
+    fn test_spiral_wipe() {
+        let waypoints = WIPE.waypoints(WipePattern::Spiral, 1, 3, 0.5, 3.0);
+        assert_covers_area(&waypoints);
+        assert_eq!(waypoints.len(), 15);
+        // The innermost loop is inset by 2/3 of half the width
+        assert_eq!(waypoints[10], [12.0, 202.0, 0.5, 3.0]);
+    }
+
+    #[test]
+    fn test_zigzag_wipe() {
+        let waypoints = WIPE.waypoints(WipePattern::Zigzag, 2, 3, 0.5, 3.0);
+        assert_covers_area(&waypoints);
+        assert_eq!(waypoints.len(), 1 + 2 * 2 * 6);
+        assert_eq!(waypoints[1], [15.0, 206.0, 0.5, 3.0]);
+        assert!(waypoints.iter().any(|p| p[1] == 200.0) && waypoints.iter().any(|p| p[1] == 206.0));
+    }
+}
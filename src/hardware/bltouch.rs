@@ -0,0 +1,342 @@
+// src/hardware/bltouch.rs - BLTouch / 3DTouch bed probe
+use std::fmt;
+use std::time::Duration;
+use super::HardwareManager;
+use crate::config::BLTouchConfig;
+
+/// Servo pulse width that pushes the pin out (μs)
+const DEPLOY_PULSE_US: u32 = 700;
+
+/// Servo pulse width that pulls the pin in (μs)
+const STOW_PULSE_US: u32 = 1500;
+
+/// How long each servo pulse is repeated (ms)
+const PULSE_DURATION_MS: u32 = 10;
+
+/// Speed for moves between mesh points (mm/s)
+const TRAVEL_SPEED: f64 = 50.0;
+
+/// Why a probe sequence was abandoned
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeError {
+    /// The endstop reported contact right after the pin was deployed
+    DeployFailed,
+    /// The endstop still reported contact after the pin was stowed
+    StowFailed,
+    /// Reached `min_z` without touching the bed
+    NoContact,
+    /// The endstop was triggered before probing or after lifting off the bed
+    ContactNotCleared,
+    /// The MCU rejected a command or answered something unexpected
+    Hardware(String),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::DeployFailed => write!(f, "BLTouch failed to deploy"),
+            ProbeError::StowFailed => write!(f, "BLTouch failed to stow"),
+            ProbeError::NoContact => write!(f, "BLTouch did not touch the bed"),
+            ProbeError::ContactNotCleared => write!(f, "BLTouch contact did not clear"),
+            ProbeError::Hardware(e) => write!(f, "BLTouch command failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+/// BLTouch probe driven through the MCU
+///
+/// Z is lowered one `probe_step` at a time with the endstop checked after
+/// each step, so `position` must be set to the toolhead's before probing.
+#[derive(Debug, Clone)]
+pub struct BLTouchProbe {
+    hardware: HardwareManager,
+    config: BLTouchConfig,
+    position: [f64; 3],
+}
+
+impl BLTouchProbe {
+    pub fn new(hardware: HardwareManager, config: BLTouchConfig) -> Self {
+        Self {
+            hardware,
+            config,
+            position: [0.0; 3],
+        }
+    }
+
+    /// Where the toolhead is [X, Y, Z]
+    pub fn position(&self) -> [f64; 3] {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: [f64; 3]) {
+        self.position = position;
+    }
+
+    pub fn config(&self) -> &BLTouchConfig {
+        &self.config
+    }
+
+    /// Push the pin out and check it isn't already reporting contact
+    pub async fn probe_deploy(&self) -> Result<(), ProbeError> {
+        self.servo_pulse(DEPLOY_PULSE_US).await?;
+        if self.probe_touch_check().await? {
+            return Err(ProbeError::DeployFailed);
+        }
+        Ok(())
+    }
+
+    /// Pull the pin in and check the endstop has cleared
+    pub async fn probe_stow(&self) -> Result<(), ProbeError> {
+        self.servo_pulse(STOW_PULSE_US).await?;
+        if self.probe_touch_check().await? {
+            return Err(ProbeError::StowFailed);
+        }
+        Ok(())
+    }
+
+    /// Whether the endstop currently reports contact
+    pub async fn probe_touch_check(&self) -> Result<bool, ProbeError> {
+        let response = self.command(&format!("query_endstop pin={}", self.config.endstop_pin)).await?;
+        match response.trim() {
+            "triggered" => Ok(true),
+            "open" => Ok(false),
+            other => Err(ProbeError::Hardware(format!("unexpected endstop state '{}'", other))),
+        }
+    }
+
+    /// Probe the bed under the toolhead
+    ///
+    /// Deploys, lowers until the endstop triggers, lifts back to the starting
+    /// Z and stows. Returns the bed height with `z_offset` applied.
+    pub async fn probe_single_point(&mut self) -> Result<f64, ProbeError> {
+        if self.probe_touch_check().await? {
+            return Err(ProbeError::ContactNotCleared);
+        }
+        self.probe_deploy().await?;
+
+        let start_z = self.position[2];
+        let contact_z = match self.lower_until_contact().await {
+            Ok(z) => z,
+            Err(e) => {
+                let _ = self.move_to(self.position[0], self.position[1], start_z, self.config.speed).await;
+                let _ = self.probe_stow().await;
+                return Err(e);
+            }
+        };
+        self.move_to(self.position[0], self.position[1], start_z, self.config.speed).await?;
+        if self.probe_touch_check().await? {
+            let _ = self.probe_stow().await;
+            return Err(ProbeError::ContactNotCleared);
+        }
+        self.probe_stow().await?;
+
+        tracing::info!("Probe at X{:.3} Y{:.3} triggered at Z{:.4}", self.position[0], self.position[1], contact_z);
+        Ok(contact_z - self.config.z_offset)
+    }
+
+    /// Probe the `mesh_min`..`mesh_max` grid row by row
+    ///
+    /// Returns [X, Y, bed height] for each point.
+    pub async fn probe_mesh(&mut self) -> Result<Vec<[f64; 3]>, ProbeError> {
+        let [count_x, count_y] = self.config.mesh_count;
+        let (mesh_min, mesh_max) = (self.config.mesh_min, self.config.mesh_max);
+        let grid = |index: usize, count: usize, axis: usize| {
+            let (min, max) = (mesh_min[axis], mesh_max[axis]);
+            if count > 1 {
+                min + (max - min) * index as f64 / (count - 1) as f64
+            } else {
+                min
+            }
+        };
+
+        let mut points = Vec::with_capacity(count_x * count_y);
+        for j in 0..count_y {
+            for i in 0..count_x {
+                let (x, y) = (grid(i, count_x, 0), grid(j, count_y, 1));
+                self.move_to(x, y, self.position[2], TRAVEL_SPEED).await?;
+                points.push([x, y, self.probe_single_point().await?]);
+            }
+        }
+        Ok(points)
+    }
+
+    /// Move to `x`, `y` at the current height
+    pub async fn move_to_xy(&mut self, x: f64, y: f64) -> Result<(), ProbeError> {
+        self.move_to(x, y, self.position[2], TRAVEL_SPEED).await
+    }
+
+    /// Step down until the endstop triggers, returning the Z it triggered at
+    async fn lower_until_contact(&mut self) -> Result<f64, ProbeError> {
+        let start_z = self.position[2];
+        let step = self.config.probe_step;
+        let mut steps = 0;
+        loop {
+            if self.probe_touch_check().await? {
+                return Ok(self.position[2]);
+            }
+            steps += 1;
+            let z = start_z - step * steps as f64;
+            if z < self.config.min_z {
+                return Err(ProbeError::NoContact);
+            }
+            self.move_to(self.position[0], self.position[1], z, self.config.speed).await?;
+        }
+    }
+
+    async fn move_to(&mut self, x: f64, y: f64, z: f64, speed: f64) -> Result<(), ProbeError> {
+        self.command(&format!("move_to x={:.3} y={:.3} z={:.4} speed={}", x, y, z, speed)).await?;
+        self.position = [x, y, z];
+        Ok(())
+    }
+
+    /// Send a servo pulse and give the pin time to move
+    async fn servo_pulse(&self, pulse_us: u32) -> Result<(), ProbeError> {
+        self.command(&format!(
+            "set_servo pin={} pulse_us={} duration_ms={}",
+            self.config.servo_pin, pulse_us, PULSE_DURATION_MS
+        ))
+        .await?;
+        tokio::time::sleep(Duration::from_secs_f64(self.config.pin_move_time)).await;
+        Ok(())
+    }
+
+    async fn command(&self, command: &str) -> Result<String, ProbeError> {
+        self.hardware
+            .send_command(command)
+            .await
+            .map_err(|e| ProbeError::Hardware(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::config::Config;
+    use crate::hardware::{McuPort, PortFuture};
+
+    /// Nozzle Z at which the pin reaches the bed in the tests below
+    const Z_OFFSET: f64 = 1.5;
+
+    /// MCU with a BLTouch over a bed whose height is `bed(x, y)`
+    #[derive(Debug)]
+    struct BedPort {
+        bed: fn(f64, f64) -> f64,
+        /// Endstop reports contact no matter where the pin is
+        stuck: bool,
+        /// Toolhead [X, Y, Z] and whether the pin is out
+        toolhead: Mutex<([f64; 3], bool)>,
+        pulses: Mutex<Vec<u32>>,
+    }
+
+    impl BedPort {
+        fn new(bed: fn(f64, f64) -> f64, start: [f64; 3]) -> Self {
+            Self {
+                bed,
+                stuck: false,
+                toolhead: Mutex::new((start, false)),
+                pulses: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    fn field(command: &str, key: &str) -> f64 {
+        command
+            .split_whitespace()
+            .find_map(|part| part.strip_prefix(key)?.strip_prefix('='))
+            .and_then(|value| value.parse().ok())
+            .unwrap()
+    }
+
+    impl McuPort for BedPort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            let mut toolhead = self.toolhead.lock().unwrap();
+            let response = match command.split_whitespace().next() {
+                Some("move_to") => {
+                    toolhead.0 = [field(command, "x"), field(command, "y"), field(command, "z")];
+                    "ok"
+                }
+                Some("set_servo") => {
+                    let pulse = field(command, "pulse_us") as u32;
+                    self.pulses.lock().unwrap().push(pulse);
+                    toolhead.1 = pulse == DEPLOY_PULSE_US;
+                    "ok"
+                }
+                Some("query_endstop") => {
+                    let ([x, y, z], deployed) = *toolhead;
+                    if self.stuck || (deployed && z <= (self.bed)(x, y) + Z_OFFSET) {
+                        "triggered"
+                    } else {
+                        "open"
+                    }
+                }
+                _ => "ok",
+            };
+            Box::pin(async move { Ok(response.to_string()) })
+        }
+    }
+
+    async fn probe(port: Arc<BedPort>) -> BLTouchProbe {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.bltouch = Some(toml::from_str(&format!(
+            "servo_pin = \"PA1\"\nendstop_pin = \"PB1\"\nz_offset = {}\nmesh_count = [2, 2]",
+            Z_OFFSET
+        )).unwrap());
+        let start = port.toolhead.lock().unwrap().0;
+        let mut hardware = HardwareManager::with_port(config, port);
+        hardware.connect().await.unwrap();
+        let mut probe = hardware.bltouch().unwrap();
+        probe.set_position(start);
+        probe
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_single_point_applies_z_offset() {
+        let port = Arc::new(BedPort::new(|_, _| 0.2, [100.0, 100.0, 5.0]));
+        let mut probe = probe(port.clone()).await;
+
+        let height = probe.probe_single_point().await.unwrap();
+        assert!((height - 0.2).abs() <= probe.config().probe_step + 1e-9, "{}", height);
+        assert_eq!(probe.position(), [100.0, 100.0, 5.0]);
+        assert_eq!(*port.pulses.lock().unwrap(), [DEPLOY_PULSE_US, STOW_PULSE_US]);
+        assert!(!port.toolhead.lock().unwrap().1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_contact_stows() {
+        let port = Arc::new(BedPort::new(|_, _| -10.0, [100.0, 100.0, 5.0]));
+        let mut probe = probe(port.clone()).await;
+
+        assert_eq!(probe.probe_single_point().await, Err(ProbeError::NoContact));
+        assert_eq!(probe.position()[2], 5.0);
+        assert_eq!(port.pulses.lock().unwrap().last(), Some(&STOW_PULSE_US));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stuck_endstop() {
+        let port = Arc::new(BedPort {
+            stuck: true,
+            ..BedPort::new(|_, _| 0.0, [100.0, 100.0, 5.0])
+        });
+        let mut probe = probe(port.clone()).await;
+
+        assert_eq!(probe.probe_single_point().await, Err(ProbeError::ContactNotCleared));
+        assert_eq!(probe.probe_deploy().await, Err(ProbeError::DeployFailed));
+        assert_eq!(probe.probe_stow().await, Err(ProbeError::StowFailed));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mesh_follows_tilted_bed() {
+        let port = Arc::new(BedPort::new(|x, y| 0.002 * x - 0.001 * y, [0.0, 0.0, 5.0]));
+        let mut probe = probe(port).await;
+
+        let mesh = probe.probe_mesh().await.unwrap();
+        let corners: Vec<_> = mesh.iter().map(|point| [point[0], point[1]]).collect();
+        assert_eq!(corners, [[10.0, 10.0], [190.0, 10.0], [10.0, 190.0], [190.0, 190.0]]);
+        for [x, y, height] in mesh {
+            assert!((height - (0.002 * x - 0.001 * y)).abs() <= 0.01 + 1e-9, "{} {} {}", x, y, height);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// src/hardware/bus.rs - Command dispatch across MCUs and their connections
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio::task::JoinHandle;
+use super::{HardwareError, McuPort};
+
+/// Name of the MCU configured in the `[mcu]` section
+pub const PRIMARY_MCU: &str = "mcu";
+
+/// A connection held for one exchange at a time
+pub type McuConnection = OwnedMutexGuard<Arc<dyn McuPort>>;
+
+/// Connections to one MCU
+#[derive(Debug)]
+struct ConnectionPool {
+    connections: Vec<Arc<Mutex<Arc<dyn McuPort>>>>,
+    /// Connection to queue on when all are busy
+    next: AtomicUsize,
+}
+
+/// Routes commands to MCUs by name
+///
+/// Each MCU has a pool of connections, each carrying one exchange at a
+/// time. Commands for different MCUs, or for an MCU with several
+/// connections, run in parallel. Clones share the pools.
+#[derive(Debug, Clone)]
+pub struct CommandBus {
+    pools: Arc<HashMap<String, ConnectionPool>>,
+    timeout: Duration,
+}
+
+impl CommandBus {
+    /// Bus with no MCUs, waiting at most `timeout` for each response
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            pools: Arc::new(HashMap::new()),
+            timeout,
+        }
+    }
+
+    /// Add `mcu`, reached over each of `connections`
+    ///
+    /// Panics if `connections` is empty or the bus has been cloned.
+    pub fn with_mcu(mut self, mcu: impl Into<String>, connections: Vec<Arc<dyn McuPort>>) -> Self {
+        assert!(!connections.is_empty(), "an MCU needs at least one connection");
+        let pool = ConnectionPool {
+            connections: connections.into_iter().map(|port| Arc::new(Mutex::new(port))).collect(),
+            next: AtomicUsize::new(0),
+        };
+        Arc::get_mut(&mut self.pools)
+            .expect("MCUs are added before the bus is shared")
+            .insert(mcu.into(), pool);
+        self
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Names of the MCUs on the bus
+    pub fn mcus(&self) -> impl Iterator<Item = &str> {
+        self.pools.keys().map(String::as_str)
+    }
+
+    /// Connections to `mcu`
+    pub fn pool_size(&self, mcu: &str) -> usize {
+        self.pools.get(mcu).map_or(0, |pool| pool.connections.len())
+    }
+
+    /// Hold a connection to `mcu`, taking a free one if there is one and
+    /// otherwise queueing on the connections in turn
+    pub async fn acquire(&self, mcu: &str) -> Result<McuConnection, HardwareError> {
+        let pool = self
+            .pools
+            .get(mcu)
+            .ok_or_else(|| HardwareError::Command(format!("unknown MCU {}", mcu)))?;
+        for connection in &pool.connections {
+            if let Ok(guard) = connection.clone().try_lock_owned() {
+                return Ok(guard);
+            }
+        }
+        let index = pool.next.fetch_add(1, Ordering::Relaxed) % pool.connections.len();
+        Ok(pool.connections[index].clone().lock_owned().await)
+    }
+
+    /// Exchange `command` with `mcu` and wait for the response
+    pub async fn exchange(&self, mcu: &str, command: &str) -> Result<String, HardwareError> {
+        let exchange = async {
+            let port = self.acquire(mcu).await?;
+            port.transact(command)
+                .await
+                .map_err(|e| HardwareError::Command(format!("{} '{}' failed: {}", mcu, command, e)))
+        };
+        tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| HardwareError::Command(format!("{} '{}' timed out after {:?}", mcu, command, self.timeout)))?
+    }
+
+    /// Dispatch `command` to `mcu` on its own task
+    pub fn send(&self, mcu: &str, command: &str) -> JoinHandle<Result<String, HardwareError>> {
+        let bus = self.clone();
+        let (mcu, command) = (mcu.to_string(), command.to_string());
+        tokio::spawn(async move { bus.exchange(&mcu, &command).await })
+    }
+
+    /// Send every `(mcu, command)` at once and wait for all the responses,
+    /// returned in the same order
+    pub async fn send_all_barrier(&self, commands: Vec<(&str, &str)>) -> Vec<Result<String, HardwareError>> {
+        let handles: Vec<_> = commands.into_iter().map(|(mcu, command)| self.send(mcu, command)).collect();
+        let mut responses = Vec::with_capacity(handles.len());
+        for handle in handles {
+            responses.push(handle.await.unwrap_or_else(|e| Err(HardwareError::Command(e.to_string()))));
+        }
+        responses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::PortFuture;
+
+    /// MCU answering every command with its name after 100ms
+    #[derive(Debug)]
+    struct SlowPort(&'static str);
+
+    impl McuPort for SlowPort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(format!("{} {}", self.0, command))
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_commands_to_two_mcus() {
+        let port = |name| Arc::new(SlowPort(name)) as Arc<dyn McuPort>;
+        let bus = CommandBus::new(Duration::from_secs(1))
+            .with_mcu("mcu", vec![port("mcu"), port("mcu")])
+            .with_mcu("toolhead", vec![port("toolhead")]);
+        assert_eq!(bus.pool_size("mcu"), 2);
+
+        let started = tokio::time::Instant::now();
+        let responses = bus
+            .send_all_barrier(vec![("mcu", "a"), ("toolhead", "b"), ("mcu", "c"), ("toolhead", "d")])
+            .await;
+        let responses: Vec<String> = responses.into_iter().map(Result::unwrap).collect();
+        assert_eq!(responses, ["mcu a", "toolhead b", "mcu c", "toolhead d"]);
+        // Both MCUs at once; the toolhead's single connection takes its two in turn
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+
+        assert!(bus.send("extruder", "e").await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,188 @@
+// src/hardware/extruder_sync.rs - Extruder de-sync detection from a filament encoder
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use super::{FilamentEncoder, HardwareError, HardwareManager};
+use crate::config::ExtruderConfig;
+use crate::printer::PrinterEvent;
+
+/// Time between encoder reads
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Default)]
+struct SyncState {
+    /// Extruder steps commanded since the monitor started, without compensation
+    commanded: i64,
+    /// Encoder count at the first read
+    encoder_origin: Option<i64>,
+    /// Steps already reported missing and not yet made up
+    missing: i64,
+    /// Missing steps to send before the next move
+    compensation: i64,
+}
+
+/// Watches for the extruder stepper skipping steps, by comparing the steps
+/// commanded with the filament a `FilamentEncoder` saw go past
+///
+/// Clones share their state, so the planner records the steps it sends and
+/// picks up compensation while the polling task reads the encoder.
+#[derive(Debug, Clone)]
+pub struct ExtruderSyncMonitor {
+    encoder: FilamentEncoder,
+    state: Arc<Mutex<SyncState>>,
+    steps_per_mm: f64,
+    threshold_mm: f64,
+    compensate: bool,
+    events: broadcast::Sender<PrinterEvent>,
+}
+
+impl ExtruderSyncMonitor {
+    /// Monitor for `extruder`, `None` if it has no `extruder_encoder_pin`
+    pub fn new(
+        hardware: HardwareManager,
+        extruder: &ExtruderConfig,
+        events: broadcast::Sender<PrinterEvent>,
+    ) -> Option<Self> {
+        let pin = extruder.extruder_encoder_pin.clone()?;
+        Some(Self {
+            encoder: FilamentEncoder::new(hardware, pin),
+            state: Arc::new(Mutex::new(SyncState::default())),
+            steps_per_mm: extruder.steps_per_mm(),
+            threshold_mm: extruder.sync_threshold_mm,
+            compensate: extruder.sync_compensate,
+            events,
+        })
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(self) {
+        let mut interval = tokio::time::interval(SYNC_POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.poll().await {
+                tracing::warn!("Could not read filament encoder {}: {}", self.encoder.pin(), e);
+            }
+        }
+    }
+
+    /// Count steps sent to the extruder stepper, negative for retraction
+    pub fn record_commanded(&self, steps: i64) {
+        self.state.lock().unwrap().commanded += steps;
+    }
+
+    /// Read the encoder, returning the steps found missing if they exceed
+    /// `sync_threshold_mm`
+    ///
+    /// Each shortfall is reported once as `PrinterEvent::ExtruderDeSync`;
+    /// with `sync_compensate` the missing steps are also queued for
+    /// `take_compensation`. Feeding more than commanded is not a de-sync.
+    pub async fn poll(&self) -> Result<Option<i64>, HardwareError> {
+        let count = self.encoder.read_steps().await?;
+        let mut state = self.state.lock().unwrap();
+        let origin = *state.encoder_origin.get_or_insert(count);
+        let missing = state.commanded - (count - origin) - state.missing;
+        let missing_mm = missing as f64 / self.steps_per_mm;
+        if missing_mm <= self.threshold_mm {
+            return Ok(None);
+        }
+
+        tracing::error!("Extruder {} steps ({:.3}mm) behind the filament encoder", missing, missing_mm);
+        state.missing += missing;
+        if self.compensate {
+            state.compensation += missing;
+        }
+        let _ = self.events.send(PrinterEvent::ExtruderDeSync { missing_steps: missing, missing_mm });
+        Ok(Some(missing))
+    }
+
+    /// Missing steps to command before the next move, clearing them
+    pub fn take_compensation(&self) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        let steps = std::mem::take(&mut state.compensation);
+        state.missing -= steps;
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use tokio::sync::RwLock;
+    use crate::config::Config;
+    use crate::motion::{MotionConfig, MotionController};
+    use crate::printer::PrinterState;
+
+    /// MCU with a filament encoder at a settable count
+    #[derive(Debug, Default)]
+    struct EncoderPort {
+        count: AtomicI64,
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl super::super::McuPort for EncoderPort {
+        fn transact<'a>(&'a self, command: &'a str) -> super::super::PortFuture<'a> {
+            self.commands.lock().unwrap().push(command.to_string());
+            let response = if command.starts_with("query_encoder pin=PA5") {
+                self.count.load(Ordering::SeqCst).to_string()
+            } else {
+                "ok".to_string()
+            };
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_desync_detected_and_compensated() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        // 200 * 16 / 8 = 400 steps/mm
+        config.extruder.rotation_distance = 8.0;
+        config.extruder.gear_ratio = None;
+        config.extruder.extruder_encoder_pin = Some("PA5".to_string());
+        config.extruder.sync_threshold_mm = 0.02;
+        config.extruder.sync_compensate = true;
+
+        let port = Arc::new(EncoderPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let (events, mut received) = broadcast::channel(4);
+        let monitor = ExtruderSyncMonitor::new(hardware.clone(), &config.extruder, events).unwrap();
+        assert_eq!(monitor.poll().await.unwrap(), None);
+
+        // 100 steps commanded, the encoder saw 90
+        monitor.record_commanded(100);
+        port.count.store(90, Ordering::SeqCst);
+        assert_eq!(monitor.poll().await.unwrap(), Some(10));
+        assert!(matches!(
+            received.try_recv(),
+            Ok(PrinterEvent::ExtruderDeSync { missing_steps: 10, missing_mm }) if missing_mm == 0.025
+        ));
+        // Reported once
+        assert_eq!(monitor.poll().await.unwrap(), None);
+        assert!(received.try_recv().is_err());
+
+        // The next move makes up the missing steps first
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut motion = MotionController::new(state, hardware, MotionConfig::new_from_printer_config(&config));
+        motion.set_extruder_sync_monitor(monitor.clone());
+        port.commands.lock().unwrap().clear();
+        motion.queue_extruder_move(1.0, None).await.unwrap();
+        motion.update().await.unwrap();
+        let steps: Vec<String> = port.commands.lock().unwrap().iter().filter(|c| c.starts_with("step E")).cloned().collect();
+        assert_eq!(steps, ["step E 10 1", "step E 400 1"]);
+        assert_eq!(monitor.take_compensation(), 0);
+
+        // Once the encoder catches up it is in sync again
+        port.count.store(500, Ordering::SeqCst);
+        assert_eq!(monitor.poll().await.unwrap(), None);
+        port.count.store(495, Ordering::SeqCst);
+        assert_eq!(monitor.poll().await.unwrap(), None);
+        port.count.store(470, Ordering::SeqCst);
+        assert_eq!(monitor.poll().await.unwrap(), Some(30));
+    }
+}
@@ -0,0 +1,34 @@
+// src/hardware/filament_encoder.rs - Rotary encoder on the filament feed
+use super::{HardwareError, HardwareManager};
+
+/// Encoder counting the filament actually fed into the extruder
+///
+/// Counts are read from the MCU with `query_encoder` and are in extruder
+/// steps, so they compare directly with the steps commanded.
+#[derive(Debug, Clone)]
+pub struct FilamentEncoder {
+    hardware: HardwareManager,
+    pin: String,
+}
+
+impl FilamentEncoder {
+    pub fn new(hardware: HardwareManager, pin: impl Into<String>) -> Self {
+        Self {
+            hardware,
+            pin: pin.into(),
+        }
+    }
+
+    pub fn pin(&self) -> &str {
+        &self.pin
+    }
+
+    /// Steps of filament fed since the MCU started counting
+    pub async fn read_steps(&self) -> Result<i64, HardwareError> {
+        let counts = self.hardware.read_encoders(std::slice::from_ref(&self.pin)).await?;
+        counts
+            .first()
+            .copied()
+            .ok_or_else(|| HardwareError::InvalidResponse(format!("no count for encoder {}", self.pin)))
+    }
+}
@@ -0,0 +1,385 @@
+// src/hardware/flash.rs - Writing new firmware to the MCU through its bootloader
+use std::process::Stdio;
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio::sync::broadcast;
+use super::{HardwareError, HardwareManager};
+use crate::config::RestartMethod;
+use crate::printer::PrinterEvent;
+
+/// STK500v1 commands and replies spoken by ATmega bootloaders (optiboot)
+const STK_GET_SYNC: u8 = 0x30;
+const STK_ENTER_PROGMODE: u8 = 0x50;
+const STK_LEAVE_PROGMODE: u8 = 0x51;
+const STK_LOAD_ADDRESS: u8 = 0x55;
+const STK_PROG_PAGE: u8 = 0x64;
+const STK_CRC_EOP: u8 = 0x20;
+const STK_INSYNC: u8 = 0x14;
+const STK_OK: u8 = 0x10;
+/// Memory type of `STK_PROG_PAGE` writes to flash
+const STK_FLASH_MEMORY: u8 = b'F';
+
+/// Bytes written per `STK_PROG_PAGE`
+const STK_PAGE_SIZE: usize = 128;
+
+/// `STK_GET_SYNC` attempts while the bootloader starts up
+const STK_SYNC_ATTEMPTS: usize = 5;
+
+/// Largest image STK500v1's 16-bit word addresses reach (bytes)
+const STK_MAX_FIRMWARE_BYTES: usize = 128 * 1024;
+
+/// USB vendor:product of the STM32 DFU bootloader
+const DFU_DEVICE: &str = "0483:df11";
+
+/// Where `dfu-util` writes the image, leaving DFU mode afterwards
+const DFU_FLASH_ADDRESS: &str = "0x08000000:leave";
+
+/// How to get new firmware onto the MCU
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlashMethod {
+    /// Send the MCU to its DFU bootloader, power-cycle its Raspberry Pi USB
+    /// port so it re-enumerates, then run `dfu-util`
+    RpiUsb,
+    /// Reset into an ATmega bootloader with DTR and write over STK500
+    SerialBootloader,
+    /// Send the MCU to its DFU bootloader and run `dfu-util` (STM32)
+    DfuUtil,
+}
+
+impl HardwareManager {
+    /// Write the firmware image at `firmware_path` to the MCU
+    ///
+    /// Progress is reported on `events` as `PrinterEvent::FirmwareFlash`.
+    /// Serial bootloaders take a raw binary or Intel HEX image, `dfu-util`
+    /// a raw binary. The MCU has to be initialized again afterwards.
+    pub async fn flash_firmware(
+        &self,
+        firmware_path: &str,
+        method: FlashMethod,
+        events: &broadcast::Sender<PrinterEvent>,
+    ) -> Result<(), HardwareError> {
+        tracing::warn!("Flashing {} to the MCU ({:?})", firmware_path, method);
+        let report = |stage: &str, percent: f64| {
+            let _ = events.send(PrinterEvent::FirmwareFlash { stage: stage.to_string(), percent });
+        };
+
+        report("entering_bootloader", 0.0);
+        match method {
+            FlashMethod::SerialBootloader => {
+                let image = read_firmware_image(firmware_path).await?;
+                self.restart_into(RestartMethod::Arduino).await?;
+                self.write_stk500(&image, |percent| report("writing", percent)).await?;
+            }
+            FlashMethod::RpiUsb | FlashMethod::DfuUtil => {
+                // An MCU without working firmware may already sit in DFU mode
+                if let Err(e) = self.send_command("enter_bootloader").await {
+                    tracing::warn!("MCU did not acknowledge enter_bootloader: {}", e);
+                }
+                if method == FlashMethod::RpiUsb {
+                    self.restart_into(RestartMethod::RpiUsb).await?;
+                }
+                self.run_dfu_util(firmware_path, |percent| report("writing", percent)).await?;
+            }
+        }
+        report("done", 100.0);
+        tracing::info!("MCU firmware flashed");
+        Ok(())
+    }
+
+    async fn restart_into(&self, method: RestartMethod) -> Result<(), HardwareError> {
+        let restart = async { self.connection().await?.restart(method).await };
+        tokio::time::timeout(self.command_timeout, restart)
+            .await
+            .map_err(|_| HardwareError::Flash(format!("{:?} restart timed out", method)))?
+            .map_err(|e| HardwareError::Flash(e.to_string()))
+    }
+
+    /// Program `image` from address 0 over STK500v1, page by page
+    async fn write_stk500(&self, image: &[u8], progress: impl Fn(f64)) -> Result<(), HardwareError> {
+        if image.len() > STK_MAX_FIRMWARE_BYTES {
+            return Err(HardwareError::Flash(format!(
+                "{} byte image exceeds the {} bytes STK500 can address",
+                image.len(),
+                STK_MAX_FIRMWARE_BYTES
+            )));
+        }
+
+        let mut synced = Err(HardwareError::Flash("no bootloader answered".to_string()));
+        for _ in 0..STK_SYNC_ATTEMPTS {
+            synced = self.stk500_command(&[STK_GET_SYNC, STK_CRC_EOP]).await;
+            if synced.is_ok() {
+                break;
+            }
+        }
+        synced?;
+        self.stk500_command(&[STK_ENTER_PROGMODE, STK_CRC_EOP]).await?;
+
+        let pages = image.len().div_ceil(STK_PAGE_SIZE);
+        for (index, page) in image.chunks(STK_PAGE_SIZE).enumerate() {
+            // Addresses count 16-bit words
+            let [address_high, address_low] = ((index * STK_PAGE_SIZE / 2) as u16).to_be_bytes();
+            self.stk500_command(&[STK_LOAD_ADDRESS, address_low, address_high, STK_CRC_EOP]).await?;
+
+            let [size_high, size_low] = (page.len() as u16).to_be_bytes();
+            let mut command = vec![STK_PROG_PAGE, size_high, size_low, STK_FLASH_MEMORY];
+            command.extend_from_slice(page);
+            command.push(STK_CRC_EOP);
+            self.stk500_command(&command).await?;
+            progress((index + 1) as f64 / pages as f64 * 100.0);
+        }
+
+        self.stk500_command(&[STK_LEAVE_PROGMODE, STK_CRC_EOP]).await
+    }
+
+    async fn stk500_command(&self, command: &[u8]) -> Result<(), HardwareError> {
+        let exchange = async { self.connection().await?.transact_raw(command, 2).await };
+        let response = tokio::time::timeout(self.command_timeout, exchange)
+            .await
+            .map_err(|_| HardwareError::Flash(format!("bootloader did not answer 0x{:02x}", command[0])))?
+            .map_err(|e| HardwareError::Flash(e.to_string()))?;
+        if response != [STK_INSYNC, STK_OK] {
+            return Err(HardwareError::InvalidResponse(format!("{:02x?}", response)));
+        }
+        Ok(())
+    }
+
+    /// Run `dfu-util` on `firmware_path`, passing on the percentages it prints
+    async fn run_dfu_util(&self, firmware_path: &str, progress: impl Fn(f64)) -> Result<(), HardwareError> {
+        let program = self.config.mcu.dfu_util_path.as_deref().unwrap_or("dfu-util");
+        let mut child = tokio::process::Command::new(program)
+            // -w: wait for the MCU to re-enumerate in DFU mode
+            .args(["-w", "-d", DFU_DEVICE, "-a", "0", "-s", DFU_FLASH_ADDRESS, "-D", firmware_path])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| HardwareError::Flash(format!("could not run {}: {}", program, e)))?;
+
+        // Progress bars are redrawn with carriage returns
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 256];
+        loop {
+            let read = stdout.read(&mut buffer).await.map_err(|e| HardwareError::Flash(e.to_string()))?;
+            if read == 0 {
+                break;
+            }
+            output.extend_from_slice(&buffer[..read]);
+            while let Some(end) = output.iter().position(|&byte| byte == b'\r' || byte == b'\n') {
+                let line: Vec<u8> = output.drain(..=end).collect();
+                if let Some(percent) = dfu_progress(&String::from_utf8_lossy(&line)) {
+                    progress(percent);
+                }
+            }
+        }
+
+        let result = child.wait_with_output().await.map_err(|e| HardwareError::Flash(e.to_string()))?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            let reason = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("no output");
+            return Err(HardwareError::Flash(format!("{} {}: {}", program, result.status, reason.trim())));
+        }
+        Ok(())
+    }
+}
+
+/// Percentage from a `dfu-util` progress line such as
+/// `Download [=====        ]  38%   98304 bytes`
+fn dfu_progress(line: &str) -> Option<f64> {
+    line.split_whitespace().find_map(|word| word.strip_suffix('%')?.parse().ok())
+}
+
+/// Firmware bytes from a raw binary or Intel HEX file
+async fn read_firmware_image(path: &str) -> Result<Vec<u8>, HardwareError> {
+    let contents = tokio::fs::read(path)
+        .await
+        .map_err(|e| HardwareError::Flash(format!("could not read {}: {}", path, e)))?;
+    if contents.starts_with(b":") {
+        let text = std::str::from_utf8(&contents).map_err(|_| HardwareError::Flash("Intel HEX file is not text".to_string()))?;
+        parse_intel_hex(text)
+    } else {
+        Ok(contents)
+    }
+}
+
+/// Flash image described by Intel HEX records, gaps filled with erased bytes
+fn parse_intel_hex(text: &str) -> Result<Vec<u8>, HardwareError> {
+    let mut image = Vec::new();
+    let mut base = 0usize;
+    for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let invalid = || HardwareError::Flash(format!("invalid Intel HEX record on line {}", number + 1));
+        let record = line.trim().strip_prefix(':').filter(|record| record.len() % 2 == 0 && record.is_ascii()).ok_or_else(invalid)?;
+        let bytes = (0..record.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&record[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let count = *bytes.first().ok_or_else(invalid)? as usize;
+        if bytes.len() != count + 5 || bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid());
+        }
+
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        let data = &bytes[4..4 + count];
+        match bytes[3] {
+            0x00 => {
+                let start = base + address;
+                // Checked before the image grows to reach the record
+                if start + count > STK_MAX_FIRMWARE_BYTES {
+                    return Err(HardwareError::Flash(format!(
+                        "Intel HEX record on line {} is past the {} bytes STK500 can address",
+                        number + 1,
+                        STK_MAX_FIRMWARE_BYTES
+                    )));
+                }
+                if image.len() < start + count {
+                    image.resize(start + count, 0xff);
+                }
+                image[start..start + count].copy_from_slice(data);
+            }
+            0x01 => break,
+            // Extended segment and linear addresses
+            0x02 if count == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 4,
+            0x04 if count == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as usize) << 16,
+            0x02 | 0x04 => return Err(invalid()),
+            _ => {}
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use crate::config::Config;
+    use super::super::{FrameFuture, McuPort, PortFuture, RestartFuture};
+
+    /// ATmega bootloader acknowledging every STK500 command
+    #[derive(Debug, Default)]
+    struct BootloaderPort {
+        restarts: Mutex<Vec<RestartMethod>>,
+        raw: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl McuPort for BootloaderPort {
+        fn transact<'a>(&'a self, _command: &'a str) -> PortFuture<'a> {
+            Box::pin(async { Err(io::Error::new(io::ErrorKind::TimedOut, "in bootloader")) })
+        }
+
+        fn transact_raw<'a>(&'a self, request: &'a [u8], response_len: usize) -> FrameFuture<'a> {
+            self.raw.lock().unwrap().push(request.to_vec());
+            assert_eq!(response_len, 2);
+            Box::pin(async { Ok(vec![STK_INSYNC, STK_OK]) })
+        }
+
+        fn restart(&self, method: RestartMethod) -> RestartFuture<'_> {
+            self.restarts.lock().unwrap().push(method);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn test_config() -> Config {
+        toml::from_str(include_str!("../printer.toml")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serial_bootloader_sequence() {
+        let path = std::env::temp_dir().join(format!("krusty-firmware-{}.bin", std::process::id()));
+        let image: Vec<u8> = (0..300u16).map(|i| i as u8).collect();
+        std::fs::write(&path, &image).unwrap();
+
+        let port = Arc::new(BootloaderPort::default());
+        let hardware = HardwareManager::with_port(test_config(), port.clone());
+        let (events, mut received) = broadcast::channel(16);
+        hardware.flash_firmware(path.to_str().unwrap(), FlashMethod::SerialBootloader, &events).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(*port.restarts.lock().unwrap(), [RestartMethod::Arduino]);
+        let raw = port.raw.lock().unwrap();
+        assert_eq!(raw.len(), 9);
+        assert_eq!(raw[0], [STK_GET_SYNC, STK_CRC_EOP]);
+        assert_eq!(raw[1], [STK_ENTER_PROGMODE, STK_CRC_EOP]);
+        // Pages at words 0, 64 and 128, the last one short
+        for (page, words) in [0u8, 64, 128].into_iter().enumerate() {
+            let load = &raw[2 + page * 2];
+            assert_eq!(load, &[STK_LOAD_ADDRESS, words, 0, STK_CRC_EOP]);
+            let program = &raw[3 + page * 2];
+            let data = &image[page * STK_PAGE_SIZE..image.len().min((page + 1) * STK_PAGE_SIZE)];
+            assert_eq!(program[..4], [STK_PROG_PAGE, 0, data.len() as u8, STK_FLASH_MEMORY]);
+            assert_eq!(&program[4..program.len() - 1], data);
+            assert_eq!(program.last(), Some(&STK_CRC_EOP));
+        }
+        assert_eq!(raw[8], [STK_LEAVE_PROGMODE, STK_CRC_EOP]);
+
+        let mut stages = Vec::new();
+        while let Ok(PrinterEvent::FirmwareFlash { stage, percent }) = received.try_recv() {
+            stages.push((stage, percent.round()));
+        }
+        assert_eq!(stages.first(), Some(&("entering_bootloader".to_string(), 0.0)));
+        assert_eq!(stages[1..4].iter().map(|(_, percent)| *percent).collect::<Vec<_>>(), [33.0, 67.0, 100.0]);
+        assert_eq!(stages.last(), Some(&("done".to_string(), 100.0)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dfu_util_invocation() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("krusty-dfu-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("dfu-util");
+        let args = dir.join("args");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$@\" > {}\nprintf 'Download\\t[====    ]  50%%\\r'\nprintf 'Download\\t[========] 100%%\\n'\n",
+                args.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = test_config();
+        config.mcu.dfu_util_path = Some(script.to_string_lossy().into_owned());
+        let port = Arc::new(BootloaderPort::default());
+        let hardware = HardwareManager::with_port(config, port.clone());
+        let (events, mut received) = broadcast::channel(16);
+        hardware.flash_firmware("klipper.bin", FlashMethod::RpiUsb, &events).await.unwrap();
+
+        assert_eq!(*port.restarts.lock().unwrap(), [RestartMethod::RpiUsb]);
+        assert_eq!(
+            std::fs::read_to_string(&args).unwrap().trim(),
+            "-w -d 0483:df11 -a 0 -s 0x08000000:leave -D klipper.bin"
+        );
+        let mut percentages = Vec::new();
+        while let Ok(PrinterEvent::FirmwareFlash { percent, .. }) = received.try_recv() {
+            percentages.push(percent);
+        }
+        assert_eq!(percentages, [0.0, 50.0, 100.0, 100.0]);
+
+        // A failing dfu-util fails the flash
+        std::fs::write(&script, "#!/bin/sh\necho 'No DFU capable USB device available' >&2\nexit 74\n").unwrap();
+        let error = hardware.flash_firmware("klipper.bin", FlashMethod::DfuUtil, &events).await.unwrap_err();
+        assert!(error.to_string().contains("No DFU capable USB device available"), "{}", error);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_intel_hex() {
+        let hex = ":0400000001020304F2\n:020000040001F9\n:020010000506E3\n:00000001FF\n";
+        let image = parse_intel_hex(hex).unwrap();
+        assert_eq!(image.len(), 0x01_0012);
+        assert_eq!(image[..4], [1, 2, 3, 4]);
+        assert_eq!(image[4], 0xff);
+        assert_eq!(image[0x01_0010..], [5, 6]);
+
+        assert!(parse_intel_hex(":0400000001020304F3\n").is_err());
+        assert!(parse_intel_hex("0400000001020304F2\n").is_err());
+        // An STM32 flash address would need a 128MB image
+        assert!(parse_intel_hex(":020000040800F2\n:0400000001020304F2\n").is_err());
+    }
+}
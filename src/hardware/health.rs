@@ -0,0 +1,164 @@
+// src/hardware/health.rs - MCU liveness pings and automatic recovery
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{RwLock, broadcast};
+use tokio::task::JoinHandle;
+use super::HardwareManager;
+use crate::printer::{PrinterEvent, PrinterState};
+
+/// Time between pings
+const PING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a ping may wait for its `ok`
+const PING_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Consecutive missed pings before the MCU is restarted
+const MAX_MISSED_PINGS: u32 = 3;
+
+/// Background task that pings the MCU and restarts it when it hangs
+///
+/// While the MCU is being restarted and re-initialized the printer is
+/// marked not ready.
+pub struct McuHealthMonitor {
+    hardware: HardwareManager,
+    state: Arc<RwLock<PrinterState>>,
+    events: broadcast::Sender<PrinterEvent>,
+}
+
+impl McuHealthMonitor {
+    pub fn new(
+        hardware: HardwareManager,
+        state: Arc<RwLock<PrinterState>>,
+        events: broadcast::Sender<PrinterEvent>,
+    ) -> Self {
+        Self { hardware, state, events }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(mut self) {
+        let mut interval = tokio::time::interval(PING_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut missed_pings = 0;
+        loop {
+            interval.tick().await;
+            if self.ping().await {
+                missed_pings = 0;
+                continue;
+            }
+
+            missed_pings += 1;
+            tracing::warn!("MCU missed ping {}/{}", missed_pings, MAX_MISSED_PINGS);
+            if missed_pings >= MAX_MISSED_PINGS {
+                self.recover(missed_pings).await;
+                missed_pings = 0;
+                interval.reset();
+            }
+        }
+    }
+
+    async fn ping(&self) -> bool {
+        match self.hardware.send_command_with_timeout("ping", PING_TIMEOUT).await {
+            Ok(response) => response.trim() == "ok",
+            Err(_) => false,
+        }
+    }
+
+    async fn recover(&mut self, missed_pings: u32) {
+        tracing::error!("MCU unresponsive after {} pings, attempting recovery", missed_pings);
+        self.state.write().await.ready = false;
+        let _ = self.events.send(PrinterEvent::McuUnresponsive { missed_pings });
+
+        // Errors are stringified so they aren't held across an await
+        let restarted = self.hardware.restart_mcu().await.map_err(|e| e.to_string());
+        let result = match restarted {
+            Ok(()) => self.hardware.initialize().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                self.state.write().await.ready = true;
+                tracing::info!("MCU recovered");
+            }
+            Err(e) => tracing::error!("MCU recovery failed, printer stays not ready: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::config::Config;
+    use crate::hardware::{McuPort, PortFuture};
+
+    /// MCU that ignores pings 2-4, then answers everything again
+    #[derive(Debug, Default)]
+    struct HangingPort {
+        pings: Mutex<u32>,
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl McuPort for HangingPort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            Box::pin(async move {
+                self.commands.lock().unwrap().push(command.to_string());
+                match command {
+                    "ping" => {
+                        let ping = {
+                            let mut pings = self.pings.lock().unwrap();
+                            *pings += 1;
+                            *pings
+                        };
+                        if (2..=4).contains(&ping) {
+                            std::future::pending::<()>().await;
+                        }
+                        Ok("ok".to_string())
+                    }
+                    "HELLO" => {
+                        // Rebooting takes a moment
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        Ok("// Klipper firmware version v0.12.0 build 2024-02-01".to_string())
+                    }
+                    "GET_CONFIG" => Ok("step_pins=PF0 dir_pins=PF1".to_string()),
+                    _ => Ok("ok".to_string()),
+                }
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recovers_from_unresponsive_mcu() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let port = Arc::new(HangingPort::default());
+        let mut hardware = HardwareManager::with_port(config, port.clone());
+        hardware.connect().await.unwrap();
+
+        let state = Arc::new(RwLock::new(PrinterState { ready: true, ..PrinterState::new() }));
+        let (event_tx, mut events) = broadcast::channel(16);
+        let monitor = McuHealthMonitor::new(hardware.clone(), state.clone(), event_tx).spawn();
+
+        let event = events.recv().await.unwrap();
+        assert!(matches!(event, PrinterEvent::McuUnresponsive { missed_pings: 3 }));
+        assert!(!state.read().await.ready);
+
+        // Restart, then the full initialization sequence
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(state.read().await.ready);
+        let commands = port.commands.lock().unwrap().clone();
+        let reset_at = commands.iter().position(|command| command == "reset").unwrap();
+        assert_eq!(commands[reset_at + 1..reset_at + 4], ["HELLO", "GET_CONFIG", "reset"]);
+
+        let stats = hardware.get_command_stats();
+        assert_eq!(stats.mcu_reset_count, 1);
+        assert_eq!(stats.timeout_commands, 3);
+
+        // Pings keep succeeding afterwards
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(state.read().await.ready);
+        assert_eq!(hardware.get_command_stats().mcu_reset_count, 1);
+        monitor.abort();
+    }
+}
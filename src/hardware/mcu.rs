@@ -0,0 +1,142 @@
+// src/hardware/mcu.rs - MCU identification: firmware version and pin map
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use serde::Serialize;
+use super::HardwareError;
+
+/// Prefix of the MCU's answer to `HELLO`
+const HELLO_PREFIX: &str = "// Klipper firmware version ";
+
+/// Firmware version reported by the MCU, e.g. `v0.12.0-85-gd785b396`
+///
+/// Only the numeric release is compared; the git suffix is kept for display.
+#[derive(Debug, Clone, Serialize)]
+pub struct McuVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub raw: String,
+    pub build: Option<String>,
+}
+
+impl McuVersion {
+    /// Parse the `// Klipper firmware version <version> build <date>` line
+    pub fn from_hello(response: &str) -> Result<Self, HardwareError> {
+        let rest = response
+            .trim()
+            .strip_prefix(HELLO_PREFIX)
+            .ok_or_else(|| HardwareError::InvalidResponse(response.to_string()))?;
+        let (version, build) = match rest.split_once(" build ") {
+            Some((version, build)) => (version, Some(build.trim().to_string())),
+            None => (rest, None),
+        };
+        let mut version: McuVersion = version.trim().parse()?;
+        version.build = build;
+        Ok(version)
+    }
+
+    fn release(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for McuVersion {
+    type Err = HardwareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || HardwareError::InvalidResponse(format!("invalid firmware version '{}'", s));
+        let release = s.strip_prefix('v').unwrap_or(s).split('-').next().unwrap_or_default();
+        let mut parts = release.split('.').map(|part| part.parse::<u32>());
+        let major = parts.next().and_then(Result::ok).ok_or_else(invalid)?;
+        let minor = parts.next().unwrap_or(Ok(0)).map_err(|_| invalid())?;
+        let patch = parts.next().unwrap_or(Ok(0)).map_err(|_| invalid())?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            raw: s.to_string(),
+            build: None,
+        })
+    }
+}
+
+impl fmt::Display for McuVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl PartialEq for McuVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.release() == other.release()
+    }
+}
+
+impl PartialOrd for McuVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.release().cmp(&other.release()))
+    }
+}
+
+/// Step and dir pins compiled into the MCU firmware
+///
+/// Parsed from the `GET_CONFIG` answer: `step_pins=PF0,PA0 dir_pins=PF1,PA1`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct McuPinMap {
+    pub step_pins: Vec<String>,
+    pub dir_pins: Vec<String>,
+}
+
+impl McuPinMap {
+    pub fn from_config_response(response: &str) -> Result<Self, HardwareError> {
+        let mut map = Self::default();
+        for field in response.split_whitespace() {
+            let Some((key, pins)) = field.split_once('=') else {
+                return Err(HardwareError::InvalidResponse(response.to_string()));
+            };
+            let pins = pins.split(',').filter(|pin| !pin.is_empty()).map(str::to_string).collect();
+            match key {
+                "step_pins" => map.step_pins = pins,
+                "dir_pins" => map.dir_pins = pins,
+                _ => {} // fields newer firmware may add
+            }
+        }
+        Ok(map)
+    }
+
+    /// The `GET_CONFIG` answer describing this map
+    pub fn to_config_response(&self) -> String {
+        format!("step_pins={} dir_pins={}", self.step_pins.join(","), self.dir_pins.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_parsing() {
+        let version = McuVersion::from_hello("// Klipper firmware version v0.12.0-85-gd785b396 build 2024-02-01").unwrap();
+        assert_eq!(version.release(), (0, 12, 0));
+        assert_eq!(version.raw, "v0.12.0-85-gd785b396");
+        assert_eq!(version.build.as_deref(), Some("2024-02-01"));
+
+        assert!(version >= "0.11".parse().unwrap());
+        assert!(version < "v0.12.1".parse().unwrap());
+        assert!(McuVersion::from_hello("ok").is_err());
+        assert!("v1.x".parse::<McuVersion>().is_err());
+    }
+
+    #[test]
+    fn test_pin_map_parsing() {
+        let map = McuPinMap::from_config_response("step_pins=PF0,PA0 dir_pins=PF1,PA1 extra=1").unwrap();
+        assert_eq!(map.step_pins, ["PF0", "PA0"]);
+        assert_eq!(map.dir_pins, ["PF1", "PA1"]);
+        assert_eq!(McuPinMap::from_config_response(&map.to_config_response()), Ok(map));
+        assert!(McuPinMap::from_config_response("garbage").is_err());
+    }
+}
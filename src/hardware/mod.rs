@@ -1,20 +1,132 @@
 // src/hardware.rs - Fixed hardware manager
+pub mod bltouch;
+pub mod bus;
+pub mod extruder_sync;
+pub mod filament_encoder;
+pub mod flash;
+pub mod health;
+pub mod mcu;
+pub mod port;
+pub mod protocol;
+pub mod stall;
+pub mod stats;
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use crate::config::Config;
 
+pub use bltouch::{BLTouchProbe, ProbeError};
+pub use bus::{CommandBus, McuConnection, PRIMARY_MCU};
+pub use extruder_sync::ExtruderSyncMonitor;
+pub use filament_encoder::FilamentEncoder;
+pub use flash::FlashMethod;
+pub use health::McuHealthMonitor;
+pub use mcu::{McuPinMap, McuVersion};
+pub use port::{FrameFuture, McuPort, PortFuture, RestartFuture, SimulatedPort};
+pub use protocol::{BinaryProtocolFrame, ProtocolError};
+pub use stall::StepperStallDetector;
+pub use stats::{CommandCounters, CommandStats};
+
+/// How long a command may wait for its response
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Errors raised while bringing up the MCU
+#[derive(Debug, Clone, PartialEq)]
+pub enum HardwareError {
+    /// A command failed or timed out
+    Command(String),
+    /// The MCU answered something we couldn't parse
+    InvalidResponse(String),
+    /// The firmware is older than `mcu.min_version`
+    IncompatibleVersion { found: String, minimum: String },
+    /// Writing new firmware failed; the MCU may need flashing again
+    Flash(String),
+}
+
+impl fmt::Display for HardwareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HardwareError::Command(e) => write!(f, "MCU command failed: {}", e),
+            HardwareError::InvalidResponse(response) => write!(f, "Unexpected MCU response: {}", response),
+            HardwareError::IncompatibleVersion { found, minimum } => {
+                write!(f, "MCU firmware {} is older than the minimum supported {}", found, minimum)
+            }
+            HardwareError::Flash(e) => write!(f, "Firmware flash failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HardwareError {}
+
+/// What the MCU told us about itself during `initialize`
+#[derive(Debug, Clone, Default)]
+pub struct HardwareState {
+    pub mcu_version: Option<McuVersion>,
+    pub pin_map: Option<McuPinMap>,
+}
+
 #[derive(Debug, Clone)]
 pub struct HardwareManager {
-    config: Config,
+    config: Arc<Config>,
     connected: bool,
+    bus: CommandBus,
+    command_timeout: Duration,
+    stats: Arc<CommandCounters>,
+    state: Arc<RwLock<HardwareState>>,
 }
 
 impl HardwareManager {
     pub fn new(config: Config) -> Self {
+        let connections = (0..config.mcu.connections.max(1))
+            .map(|_| Arc::new(SimulatedPort::for_config(&config)) as Arc<dyn McuPort>)
+            .collect();
+        let bus = CommandBus::new(DEFAULT_COMMAND_TIMEOUT).with_mcu(PRIMARY_MCU, connections);
+        Self::with_bus(config, bus)
+    }
+
+    /// Manager talking to the MCU over `port`
+    pub fn with_port(config: Config, port: Arc<dyn McuPort>) -> Self {
+        Self::with_bus(config, CommandBus::new(DEFAULT_COMMAND_TIMEOUT).with_mcu(PRIMARY_MCU, vec![port]))
+    }
+
+    /// Manager sending commands over `bus`, which must have `PRIMARY_MCU`
+    pub fn with_bus(config: Config, bus: CommandBus) -> Self {
         Self {
-            config,
+            config: Arc::new(config),
             connected: false,
+            bus,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            stats: Arc::new(CommandCounters::default()),
+            state: Arc::new(RwLock::new(HardwareState::default())),
         }
     }
 
+    /// Printer configuration the manager was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn set_command_timeout(&mut self, timeout: Duration) {
+        self.command_timeout = timeout;
+        self.bus.set_timeout(timeout);
+    }
+
+    /// Bus reaching every MCU, for dispatching commands in parallel
+    pub fn command_bus(&self) -> &CommandBus {
+        &self.bus
+    }
+
+    /// Hold a connection to the primary MCU
+    async fn connection(&self) -> std::io::Result<McuConnection> {
+        self.bus.acquire(PRIMARY_MCU).await.map_err(std::io::Error::other)
+    }
+
+    #[cfg(not(feature = "klipper-protocol"))]
+    async fn transact(&self, command: &str) -> std::io::Result<String> {
+        self.connection().await?.transact(command).await
+    }
+
     pub async fn connect(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Connecting to MCU: {}", self.config.mcu.serial);
         // In real implementation, this would open the serial port
@@ -24,22 +136,165 @@ impl HardwareManager {
     }
 
     pub async fn send_command(&self, command: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.send_command_with_timeout(command, self.command_timeout).await
+    }
+
+    /// Like `send_command`, waiting at most `timeout` for the response
+    pub async fn send_command_with_timeout(
+        &self,
+        command: &str,
+        timeout: Duration,
+    ) -> Result<String, Box<dyn std::error::Error>> {
         if !self.connected {
             return Err("Not connected to hardware".into());
         }
         
         tracing::debug!("MCU <- {}", command);
         
-        // Simulate typical responses
-        let response = match command {
-            "reset" => "ok",
-            cmd if cmd.starts_with("config_stepper") => "ok",
-            cmd if cmd.starts_with("step") => "ok",
-            _ => "ok",
+        #[cfg(feature = "klipper-protocol")]
+        let exchange = self.transact_binary(command);
+        #[cfg(not(feature = "klipper-protocol"))]
+        let exchange = self.transact(command);
+
+        let started = tokio::time::Instant::now();
+        let response = match tokio::time::timeout(timeout, exchange).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                self.stats.record_failure();
+                return Err(format!("MCU command '{}' failed: {}", command, e).into());
+            }
+            Err(_) => {
+                self.stats.record_timeout();
+                return Err(format!("MCU command '{}' timed out after {:?}", command, timeout).into());
+            }
         };
+        self.stats.record_response(started.elapsed());
         
         tracing::debug!("MCU -> {}", response);
-        Ok(response.to_string())
+        Ok(response)
+    }
+
+    /// Exchange `command` as a binary frame and decode the response
+    #[cfg(feature = "klipper-protocol")]
+    async fn transact_binary(&self, command: &str) -> std::io::Result<String> {
+        let request = BinaryProtocolFrame::from_command(command).encode();
+        let response = self.connection().await?.transact_frame(&request).await?;
+        let response = BinaryProtocolFrame::decode(&response)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(response.to_command())
+    }
+
+    /// Command counts and round-trip latency since startup
+    pub fn get_command_stats(&self) -> CommandStats {
+        self.stats.snapshot()
+    }
+
+    /// The configured BLTouch probe, if there is one
+    pub fn bltouch(&self) -> Option<BLTouchProbe> {
+        let config = self.config.bltouch.clone()?;
+        Some(BLTouchProbe::new(self.clone(), config))
+    }
+
+    /// Reset the MCU using the configured `mcu.restart_method`
+    pub async fn restart_mcu(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let method = self.config.mcu.restart_method;
+        tracing::warn!("Restarting MCU ({:?})", method);
+        self.stats.record_reset();
+        let restart = async { self.connection().await?.restart(method).await };
+        tokio::time::timeout(self.command_timeout, restart)
+            .await
+            .map_err(|_| format!("MCU restart timed out after {:?}", self.command_timeout))??;
+        Ok(())
+    }
+
+    /// Version and pin map reported by the MCU
+    pub fn get_state(&self) -> HardwareState {
+        self.state.read().unwrap().clone()
+    }
+
+    /// Current count of each encoder in `pins`
+    pub async fn read_encoders(&self, pins: &[String]) -> Result<Vec<i64>, HardwareError> {
+        let mut counts = Vec::with_capacity(pins.len());
+        for pin in pins {
+            let response = self
+                .send_command(&format!("query_encoder pin={}", pin))
+                .await
+                .map_err(|e| HardwareError::Command(e.to_string()))?;
+            let count = response
+                .trim()
+                .parse()
+                .map_err(|_| HardwareError::InvalidResponse(response.trim().to_string()))?;
+            counts.push(count);
+        }
+        Ok(counts)
+    }
+
+    /// Raw reading of the 12-bit ADC on `pin`
+    pub async fn read_adc(&self, pin: &str) -> Result<u16, HardwareError> {
+        let response = self
+            .send_command(&format!("query_adc pin={}", pin))
+            .await
+            .map_err(|e| HardwareError::Command(e.to_string()))?;
+        response
+            .trim()
+            .parse()
+            .map_err(|_| HardwareError::InvalidResponse(response.trim().to_string()))
+    }
+
+    /// Drive `pin` at `duty` (0.0 - 1.0)
+    pub async fn set_pwm(&self, pin: &str, duty: f64) -> Result<(), HardwareError> {
+        self.send_command(&format!("set_pwm pin={} value={:.3}", pin, duty.clamp(0.0, 1.0)))
+            .await
+            .map_err(|e| HardwareError::Command(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Set the TMC run current of the stepper for `axis` (0-2 for X-Z) over UART
+    pub async fn set_stepper_current(&self, axis: usize, current_ma: u32) -> Result<(), HardwareError> {
+        let name = ["stepper_x", "stepper_y", "stepper_z"]
+            .get(axis)
+            .ok_or_else(|| HardwareError::Command(format!("no stepper for axis {}", axis)))?;
+        self.send_command(&format!("set_tmc_current stepper={} current={}", name, current_ma))
+            .await
+            .map_err(|e| HardwareError::Command(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Identify the MCU firmware and check it against our configuration
+    ///
+    /// Fails if the firmware is older than `mcu.min_version`. Step pins the
+    /// firmware doesn't know about are only logged, since the MCU may still
+    /// accept them at runtime.
+    pub async fn negotiate_version(&self) -> Result<McuVersion, HardwareError> {
+        let query = |command| async move {
+            self.send_command(command)
+                .await
+                .map_err(|e| HardwareError::Command(e.to_string()))
+        };
+
+        let version = McuVersion::from_hello(&query("HELLO").await?)?;
+        tracing::info!("MCU firmware version {}", version);
+        if let Some(minimum) = &self.config.mcu.min_version {
+            let minimum: McuVersion = minimum.parse()?;
+            if version < minimum {
+                return Err(HardwareError::IncompatibleVersion {
+                    found: version.to_string(),
+                    minimum: minimum.to_string(),
+                });
+            }
+        }
+
+        let pin_map = McuPinMap::from_config_response(&query("GET_CONFIG").await?)?;
+        for (name, stepper) in &self.config.steppers {
+            if !pin_map.step_pins.contains(&stepper.step_pin) {
+                tracing::warn!("Stepper {} step_pin {} is not in the MCU pin map", name, stepper.step_pin);
+            }
+        }
+
+        let mut state = self.state.write().unwrap();
+        state.mcu_version = Some(version.clone());
+        state.pin_map = Some(pin_map);
+        Ok(version)
     }
 
     pub async fn initialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
@@ -48,6 +303,7 @@ impl HardwareManager {
         }
         
         tracing::info!("Initializing printer hardware...");
+        self.negotiate_version().await?;
         self.send_command("reset").await?;
         
         // Configure all steppers
@@ -71,4 +327,96 @@ impl HardwareManager {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::time::Duration;
+
+    /// MCU that answers after the delay given in the command ("delay <ms>")
+    #[derive(Debug)]
+    struct DelayedPort;
+
+    impl McuPort for DelayedPort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            Box::pin(async move {
+                let ms: u64 = command
+                    .strip_prefix("delay ")
+                    .and_then(|ms| ms.parse().ok())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "bad command"))?;
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok("ok".to_string())
+            })
+        }
+    }
+
+    /// MCU answering the handshake like real firmware
+    #[derive(Debug)]
+    struct HandshakePort;
+
+    impl McuPort for HandshakePort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            Box::pin(async move {
+                Ok(match command {
+                    "HELLO" => "// Klipper firmware version v0.12.0-85-gd785b396 build 2024-02-01".to_string(),
+                    "GET_CONFIG" => "step_pins=PF0 dir_pins=PF1".to_string(),
+                    _ => "ok".to_string(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_version() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.mcu.min_version = Some("v0.11.0".to_string());
+        let mut hardware = HardwareManager::with_port(config.clone(), Arc::new(HandshakePort));
+        hardware.initialize().await.unwrap();
+
+        let state = hardware.get_state();
+        let version = state.mcu_version.unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (0, 12, 0));
+        assert_eq!(version.raw, "v0.12.0-85-gd785b396");
+        assert_eq!(version.build.as_deref(), Some("2024-02-01"));
+        assert_eq!(state.pin_map.unwrap().step_pins, ["PF0"]);
+
+        config.mcu.min_version = Some("v0.13".to_string());
+        let mut hardware = HardwareManager::with_port(config, Arc::new(HandshakePort));
+        hardware.connect().await.unwrap();
+        assert!(matches!(
+            hardware.negotiate_version().await,
+            Err(HardwareError::IncompatibleVersion { .. })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency_histogram() {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let mut hardware = HardwareManager::with_port(config, Arc::new(DelayedPort));
+        hardware.connect().await.unwrap();
+
+        for ms in [0, 3, 3, 3, 50, 300] {
+            hardware.send_command(&format!("delay {}", ms)).await.unwrap();
+        }
+        assert!(hardware.send_command("garbage").await.is_err());
+        assert!(hardware.send_command("delay 5000").await.is_err());
+
+        let stats = hardware.get_command_stats();
+        assert_eq!(stats.total_commands, 8);
+        assert_eq!(stats.failed_commands, 1);
+        assert_eq!(stats.timeout_commands, 1);
+        let mut expected = [0; stats::LATENCY_BUCKETS];
+        expected[0] = 1; // 0ms
+        expected[8] = 3; // 3ms -> (2, 4]
+        expected[12] = 1; // 50ms -> (32, 64]
+        expected[15] = 1; // 300ms -> (256, 512]
+        assert_eq!(stats.latency_histogram, expected);
+        assert_eq!(stats.p50_latency_ms(), 4.0);
+        assert_eq!(stats.p99_latency_ms(), 512.0);
+
+        // Clones share the counters
+        assert_eq!(hardware.clone().get_command_stats(), stats);
+    }
+}
@@ -0,0 +1,99 @@
+// src/hardware/port.rs - Transport between the host and the MCU
+use std::fmt::Debug;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use super::mcu::McuPinMap;
+use super::protocol::BinaryProtocolFrame;
+use crate::config::{Config, RestartMethod};
+
+/// Response to one command, resolved once the MCU answers
+pub type PortFuture<'a> = Pin<Box<dyn Future<Output = io::Result<String>> + Send + 'a>>;
+
+/// Resolved once a restart has been triggered
+pub type RestartFuture<'a> = Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>>;
+
+/// Encoded response frame, resolved once the MCU answers
+pub type FrameFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<u8>>> + Send + 'a>>;
+
+/// A link to the MCU that answers each command with one response line
+pub trait McuPort: Debug + Send + Sync {
+    /// Send a command and wait for its response
+    fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a>;
+
+    /// Send an encoded `BinaryProtocolFrame` and wait for the response frame
+    ///
+    /// Ports that only speak ASCII answer through `transact`.
+    fn transact_frame<'a>(&'a self, frame: &'a [u8]) -> FrameFuture<'a> {
+        Box::pin(async move {
+            let request = BinaryProtocolFrame::decode(frame).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let response = self.transact(&request.to_command()).await?;
+            Ok(BinaryProtocolFrame::from_command(&response).encode())
+        })
+    }
+
+    /// Write raw bytes and read exactly `response_len` back, for talking to
+    /// a bootloader rather than the firmware
+    fn transact_raw<'a>(&'a self, _request: &'a [u8], _response_len: usize) -> FrameFuture<'a> {
+        Box::pin(async {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "raw transfers are not supported by this port"))
+        })
+    }
+
+    /// Reset the MCU
+    ///
+    /// Only `RestartMethod::Command` works over a plain command link; the
+    /// other methods need control of the underlying serial or USB device.
+    fn restart(&self, method: RestartMethod) -> RestartFuture<'_> {
+        Box::pin(async move {
+            match method {
+                RestartMethod::Command => self.transact("reset").await.map(|_| ()),
+                other => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{:?} restart is not supported by this port", other),
+                )),
+            }
+        })
+    }
+}
+
+/// Firmware version the simulated MCU reports
+const SIMULATED_VERSION: &str = "v0.12.0-simulated";
+
+/// Stand-in MCU that acknowledges every command
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedPort {
+    pin_map: McuPinMap,
+}
+
+impl SimulatedPort {
+    /// Simulated MCU built with the pins `config` expects
+    pub fn for_config(config: &Config) -> Self {
+        let (step_pins, dir_pins) = config
+            .steppers
+            .values()
+            .map(|stepper| (stepper.step_pin.clone(), stepper.dir_pin.clone()))
+            .unzip();
+        Self {
+            pin_map: McuPinMap { step_pins, dir_pins },
+        }
+    }
+}
+
+impl McuPort for SimulatedPort {
+    fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+        Box::pin(async move {
+            // Simulate typical responses
+            let response = match command {
+                "HELLO" => return Ok(format!("// Klipper firmware version {} build simulator", SIMULATED_VERSION)),
+                "GET_CONFIG" => return Ok(self.pin_map.to_config_response()),
+                "reset" => "ok",
+                cmd if cmd.starts_with("config_stepper") => "ok",
+                cmd if cmd.starts_with("step") => "ok",
+                cmd if cmd.starts_with("query_encoder") => "0",
+                _ => "ok",
+            };
+            Ok(response.to_string())
+        })
+    }
+}
@@ -0,0 +1,226 @@
+// src/hardware/protocol.rs - Binary MCU framing with CRC-16 CCITT
+use std::fmt;
+
+/// Marks the start and end of every frame
+pub const FRAME_FLAG: u8 = 0x7e;
+
+/// Precedes a stuffed byte inside a frame
+pub const FRAME_ESCAPE: u8 = 0x7d;
+
+/// Stuffed bytes are sent XORed with this
+const ESCAPE_XOR: u8 = 0x20;
+
+/// Command id for frames carrying a free-form ASCII line
+pub const RAW_COMMAND_ID: u8 = 0;
+
+/// Command ids for commands the host sends by name
+const COMMAND_IDS: &[(&str, u8)] = &[
+    ("reset", 1),
+    ("config_stepper", 2),
+    ("step", 3),
+    ("home_all", 4),
+    ("disable_all_motors", 5),
+    ("disable_heaters", 6),
+];
+
+/// CRC-16 CCITT (polynomial 0x1021, initial value 0xffff)
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    data.iter().fold(0xffff, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Errors raised while decoding a frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    /// The frame doesn't start and end with `FRAME_FLAG`
+    MissingFlag,
+    /// An escape byte at the end of the frame or before a byte that
+    /// needn't be escaped
+    InvalidEscape,
+    /// Fewer bytes than a command id and CRC
+    TooShort(usize),
+    CrcMismatch { expected: u16, actual: u16 },
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::MissingFlag => write!(f, "Frame is missing its 0x7e delimiters"),
+            ProtocolError::InvalidEscape => write!(f, "Invalid escape sequence in frame"),
+            ProtocolError::TooShort(len) => write!(f, "Frame too short ({} bytes)", len),
+            ProtocolError::CrcMismatch { expected, actual } => {
+                write!(f, "CRC mismatch: expected {:#06x}, got {:#06x}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// One command or response on the wire
+///
+/// Encoded as `0x7e <id> <parameters..> <crc hi> <crc lo> 0x7e`, with
+/// 0x7e and 0x7d between the flags sent as 0x7d followed by the byte
+/// XOR 0x20. The CRC covers the id and parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryProtocolFrame {
+    pub command_id: u8,
+    pub parameters: Vec<u8>,
+    pub crc: u16,
+}
+
+impl BinaryProtocolFrame {
+    pub fn new(command_id: u8, parameters: Vec<u8>) -> Self {
+        let crc = Self::checksum(command_id, &parameters);
+        Self {
+            command_id,
+            parameters,
+            crc,
+        }
+    }
+
+    /// Frame for an ASCII command line; known command names are sent as
+    /// their id followed by the arguments, anything else as a raw line
+    pub fn from_command(command: &str) -> Self {
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        match COMMAND_IDS.iter().find(|(known, _)| *known == name) {
+            Some(&(_, id)) => Self::new(id, args.as_bytes().to_vec()),
+            None => Self::new(RAW_COMMAND_ID, command.as_bytes().to_vec()),
+        }
+    }
+
+    /// The ASCII command line this frame was built from
+    pub fn to_command(&self) -> String {
+        let args = String::from_utf8_lossy(&self.parameters);
+        match COMMAND_IDS.iter().find(|(_, id)| *id == self.command_id) {
+            Some((name, _)) if args.is_empty() => name.to_string(),
+            Some((name, _)) => format!("{} {}", name, args),
+            None => args.into_owned(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(self.parameters.len() + 5);
+        frame.push(FRAME_FLAG);
+        let body = std::iter::once(self.command_id)
+            .chain(self.parameters.iter().copied())
+            .chain(self.crc.to_be_bytes());
+        for byte in body {
+            if byte == FRAME_FLAG || byte == FRAME_ESCAPE {
+                frame.push(FRAME_ESCAPE);
+                frame.push(byte ^ ESCAPE_XOR);
+            } else {
+                frame.push(byte);
+            }
+        }
+        frame.push(FRAME_FLAG);
+        frame
+    }
+
+    /// Unstuff a complete frame and verify its CRC
+    pub fn decode(frame: &[u8]) -> Result<Self, ProtocolError> {
+        let inner = frame
+            .strip_prefix(&[FRAME_FLAG])
+            .and_then(|rest| rest.strip_suffix(&[FRAME_FLAG]))
+            .ok_or(ProtocolError::MissingFlag)?;
+
+        let mut body = Vec::with_capacity(inner.len());
+        let mut bytes = inner.iter();
+        while let Some(&byte) = bytes.next() {
+            match byte {
+                FRAME_FLAG => return Err(ProtocolError::MissingFlag),
+                FRAME_ESCAPE => {
+                    let unstuffed = bytes.next().ok_or(ProtocolError::InvalidEscape)? ^ ESCAPE_XOR;
+                    if unstuffed != FRAME_FLAG && unstuffed != FRAME_ESCAPE {
+                        return Err(ProtocolError::InvalidEscape);
+                    }
+                    body.push(unstuffed);
+                }
+                _ => body.push(byte),
+            }
+        }
+
+        if body.len() < 3 {
+            return Err(ProtocolError::TooShort(body.len()));
+        }
+        let crc_at = body.len() - 2;
+        let actual = u16::from_be_bytes([body[crc_at], body[crc_at + 1]]);
+        let command_id = body[0];
+        let parameters = body[1..crc_at].to_vec();
+        let expected = Self::checksum(command_id, &parameters);
+        if actual != expected {
+            return Err(ProtocolError::CrcMismatch { expected, actual });
+        }
+        Ok(Self {
+            command_id,
+            parameters,
+            crc: actual,
+        })
+    }
+
+    fn checksum(command_id: u8, parameters: &[u8]) -> u16 {
+        let mut data = Vec::with_capacity(parameters.len() + 1);
+        data.push(command_id);
+        data.extend_from_slice(parameters);
+        crc16_ccitt(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16_ccitt() {
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29b1);
+        assert_eq!(crc16_ccitt(b""), 0xffff);
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let frame = BinaryProtocolFrame::from_command("config_stepper name=x step_pin=PA0");
+        let mut encoded = frame.encode();
+        assert_eq!(BinaryProtocolFrame::decode(&encoded), Ok(frame.clone()));
+        assert_eq!(frame.to_command(), "config_stepper name=x step_pin=PA0");
+
+        // Any single flipped bit in the id or parameters is caught
+        for bit in 0..8 {
+            encoded[3] ^= 1 << bit;
+            assert!(matches!(
+                BinaryProtocolFrame::decode(&encoded),
+                Err(ProtocolError::CrcMismatch { .. })
+            ));
+            encoded[3] ^= 1 << bit;
+        }
+
+        // Payloads of every length, including ones needing stuffing
+        for len in [0, 1, 2, 7, 64, 255, 1024] {
+            let parameters: Vec<u8> = (0..len).map(|i| [FRAME_FLAG, FRAME_ESCAPE, i as u8][i % 3]).collect();
+            let frame = BinaryProtocolFrame::new(RAW_COMMAND_ID, parameters);
+            let encoded = frame.encode();
+            assert_eq!(encoded.iter().filter(|&&byte| byte == FRAME_FLAG).count(), 2);
+            assert_eq!(BinaryProtocolFrame::decode(&encoded), Ok(frame));
+        }
+    }
+
+    #[test]
+    fn test_malformed_frames() {
+        assert_eq!(BinaryProtocolFrame::decode(&[0x01, 0x02, 0x03]), Err(ProtocolError::MissingFlag));
+        assert_eq!(BinaryProtocolFrame::decode(&[FRAME_FLAG, 0x01, FRAME_FLAG]), Err(ProtocolError::TooShort(1)));
+        assert_eq!(
+            BinaryProtocolFrame::decode(&[FRAME_FLAG, 0x01, FRAME_ESCAPE, FRAME_FLAG]),
+            Err(ProtocolError::InvalidEscape)
+        );
+        assert_eq!(
+            BinaryProtocolFrame::decode(&[FRAME_FLAG, 0x01, FRAME_ESCAPE, 0x00, 0x00, FRAME_FLAG]),
+            Err(ProtocolError::InvalidEscape)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// src/hardware/stall.rs - Stepper stall detection through TMC DIAG pins
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use super::{HardwareError, HardwareManager};
+use crate::config::{Config, StallRecovery};
+use crate::printer::PrinterEvent;
+
+/// Checks the DIAG pins of the X, Y and Z drivers for stalls
+///
+/// Each stall is counted in the `CommandStats` and reported as
+/// `PrinterEvent::StepperStalled`; recovering is left to the caller.
+#[derive(Debug, Clone)]
+pub struct StepperStallDetector {
+    hardware: HardwareManager,
+    /// (axis, DIAG pin) for each stepper that has one
+    diag_pins: Vec<(usize, String)>,
+    recovery: StallRecovery,
+    poll_interval: Duration,
+    last_poll: Arc<Mutex<Option<Instant>>>,
+    events: broadcast::Sender<PrinterEvent>,
+}
+
+impl StepperStallDetector {
+    /// Detector for the steppers with a `diag_pin`, using `config.stall_detection`
+    pub fn new(hardware: HardwareManager, config: &Config, events: broadcast::Sender<PrinterEvent>) -> Self {
+        let diag_pins = (0..3)
+            .filter_map(|axis| Some((axis, config.axis_stepper(axis)?.diag_pin.clone()?)))
+            .collect();
+        let (recovery, poll_interval_ms) = config
+            .stall_detection
+            .as_ref()
+            .map_or((StallRecovery::default(), 0), |stall| (stall.recovery, stall.poll_interval_ms));
+        Self {
+            hardware,
+            diag_pins,
+            recovery,
+            poll_interval: Duration::from_millis(poll_interval_ms),
+            last_poll: Arc::new(Mutex::new(None)),
+            events,
+        }
+    }
+
+    pub fn recovery(&self) -> StallRecovery {
+        self.recovery
+    }
+
+    /// The first axis whose driver reports a stall
+    ///
+    /// Returns `None` without asking the MCU if the pins were checked less
+    /// than `poll_interval_ms` ago.
+    pub async fn poll(&self) -> Result<Option<usize>, HardwareError> {
+        {
+            let mut last_poll = self.last_poll.lock().unwrap();
+            let now = Instant::now();
+            if last_poll.is_some_and(|last| now.duration_since(last) < self.poll_interval) {
+                return Ok(None);
+            }
+            *last_poll = Some(now);
+        }
+
+        for (axis, pin) in &self.diag_pins {
+            let response = self
+                .hardware
+                .send_command(&format!("query_diag pin={}", pin))
+                .await
+                .map_err(|e| HardwareError::Command(e.to_string()))?;
+            match response.trim() {
+                "open" => {}
+                "triggered" => {
+                    tracing::error!("Stepper stall detected on axis {}", axis);
+                    self.hardware.stats.record_stall();
+                    let _ = self.events.send(PrinterEvent::StepperStalled { axis: *axis });
+                    return Ok(Some(*axis));
+                }
+                other => return Err(HardwareError::InvalidResponse(other.to_string())),
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,166 @@
+// src/hardware/stats.rs - MCU command counters and round-trip latency histogram
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use serde::Serialize;
+
+/// Number of latency buckets
+pub const LATENCY_BUCKETS: usize = 16;
+
+/// Upper bound of the last latency bucket (ms); each bucket below covers
+/// half the range of the one above it
+pub const MAX_LATENCY_MS: f64 = 512.0;
+
+/// p99 latency above which a warning is logged (ms)
+const HIGH_LATENCY_WARNING_MS: f64 = 200.0;
+
+/// Upper bound of a latency bucket (ms)
+pub fn bucket_upper_bound_ms(bucket: usize) -> f64 {
+    MAX_LATENCY_MS / (1u64 << (LATENCY_BUCKETS - 1 - bucket.min(LATENCY_BUCKETS - 1))) as f64
+}
+
+/// Bucket a round-trip time falls into; anything above the range lands in
+/// the last bucket
+fn bucket_for(latency: Duration) -> usize {
+    let ms = latency.as_secs_f64() * 1000.0;
+    (0..LATENCY_BUCKETS)
+        .find(|&bucket| ms <= bucket_upper_bound_ms(bucket))
+        .unwrap_or(LATENCY_BUCKETS - 1)
+}
+
+/// Live counters, shared by every clone of a `HardwareManager`
+#[derive(Debug, Default)]
+pub struct CommandCounters {
+    total_commands: AtomicU64,
+    failed_commands: AtomicU64,
+    timeout_commands: AtomicU64,
+    mcu_resets: AtomicU64,
+    stepper_stalls: AtomicU64,
+    latency_histogram: [AtomicU64; LATENCY_BUCKETS],
+    high_latency: AtomicBool,
+}
+
+impl CommandCounters {
+    /// Count a command that got a response after `latency`
+    pub fn record_response(&self, latency: Duration) {
+        self.total_commands.fetch_add(1, Ordering::Relaxed);
+        self.latency_histogram[bucket_for(latency)].fetch_add(1, Ordering::Relaxed);
+
+        let p99 = self.snapshot().p99_latency_ms();
+        let high = p99 > HIGH_LATENCY_WARNING_MS;
+        if self.high_latency.swap(high, Ordering::Relaxed) != high && high {
+            tracing::warn!("MCU command p99 latency is {:.0}ms (over {:.0}ms)", p99, HIGH_LATENCY_WARNING_MS);
+        }
+    }
+
+    /// Count a command that failed before a response arrived
+    pub fn record_failure(&self) {
+        self.total_commands.fetch_add(1, Ordering::Relaxed);
+        self.failed_commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a command that got no response in time
+    pub fn record_timeout(&self) {
+        self.total_commands.fetch_add(1, Ordering::Relaxed);
+        self.timeout_commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an MCU restart
+    pub fn record_reset(&self) {
+        self.mcu_resets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a stall reported by a stepper driver
+    pub fn record_stall(&self) {
+        self.stepper_stalls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> CommandStats {
+        CommandStats {
+            total_commands: self.total_commands.load(Ordering::Relaxed),
+            failed_commands: self.failed_commands.load(Ordering::Relaxed),
+            timeout_commands: self.timeout_commands.load(Ordering::Relaxed),
+            mcu_reset_count: self.mcu_resets.load(Ordering::Relaxed),
+            stepper_stall_count: self.stepper_stalls.load(Ordering::Relaxed),
+            latency_histogram: std::array::from_fn(|bucket| self.latency_histogram[bucket].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Command statistics at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CommandStats {
+    pub total_commands: u64,
+    pub failed_commands: u64,
+    pub timeout_commands: u64,
+    pub mcu_reset_count: u64,
+    pub stepper_stall_count: u64,
+
+    /// Responses per latency bucket; see `bucket_upper_bound_ms`
+    pub latency_histogram: [u64; LATENCY_BUCKETS],
+}
+
+impl CommandStats {
+    pub fn p50_latency_ms(&self) -> f64 {
+        self.latency_percentile_ms(0.50)
+    }
+
+    pub fn p95_latency_ms(&self) -> f64 {
+        self.latency_percentile_ms(0.95)
+    }
+
+    pub fn p99_latency_ms(&self) -> f64 {
+        self.latency_percentile_ms(0.99)
+    }
+
+    /// Upper bound of the bucket holding the given quantile (0 without data)
+    pub fn latency_percentile_ms(&self, quantile: f64) -> f64 {
+        let responses: u64 = self.latency_histogram.iter().sum();
+        if responses == 0 {
+            return 0.0;
+        }
+        let rank = ((responses as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.latency_histogram.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_upper_bound_ms(bucket);
+            }
+        }
+        MAX_LATENCY_MS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds() {
+        assert_eq!(bucket_upper_bound_ms(LATENCY_BUCKETS - 1), 512.0);
+        assert_eq!(bucket_upper_bound_ms(6), 1.0);
+        assert_eq!(bucket_for(Duration::from_micros(10)), 0);
+        assert_eq!(bucket_for(Duration::from_millis(1)), 6);
+        assert_eq!(bucket_for(Duration::from_millis(3)), 8);
+        assert_eq!(bucket_for(Duration::from_secs(5)), LATENCY_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let counters = CommandCounters::default();
+        for _ in 0..90 {
+            counters.record_response(Duration::from_millis(3));
+        }
+        for _ in 0..9 {
+            counters.record_response(Duration::from_millis(50));
+        }
+        counters.record_response(Duration::from_millis(300));
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.total_commands, 100);
+        assert_eq!(stats.p50_latency_ms(), 4.0);
+        assert_eq!(stats.p95_latency_ms(), 64.0);
+        assert_eq!(stats.p99_latency_ms(), 64.0);
+        assert_eq!(stats.latency_percentile_ms(1.0), 512.0);
+        assert_eq!(CommandStats::default().p99_latency_ms(), 0.0);
+    }
+}
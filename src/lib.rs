@@ -0,0 +1,15 @@
+// src/lib.rs - Library root shared by the printer host binary and tests
+pub mod config;
+pub mod eeprom;
+pub mod file;
+pub mod gcode;
+pub mod hardware;
+pub mod motion;
+pub mod mqtt;
+pub mod post_print;
+pub mod print_job;
+pub mod printer;
+pub mod simulator;
+pub mod system_info;
+pub mod temperature;
+pub mod web;
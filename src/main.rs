@@ -1,16 +1,21 @@
 // src/main.rs - Fixed main function
-mod printer;
-mod gcode;
-mod motion;
-mod hardware;
-mod config;
-
-use printer::Printer;
+use krusty_rs::config::ConfigManager;
+use krusty_rs::printer::Printer;
 use tokio::signal;
 use std::env;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    // Print a hash for `password_hash` under [[web.users]]
+    if args.get(1).map(String::as_str) == Some("--hash-password") {
+        let Some(password) = args.get(2) else {
+            return Err("Usage: printer-host --hash-password <password>".into());
+        };
+        println!("{}", krusty_rs::web::auth::hash_password(password)?);
+        return Ok(());
+    }
+
     // Initialize logging
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
@@ -21,7 +26,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Version: 0.1.0");
     
     // Get configuration file path
-    let args: Vec<String> = env::args().collect();
     let config_path = if args.len() > 1 {
         &args[1]
     } else {
@@ -30,18 +34,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     tracing::info!("Loading configuration from: {}", config_path);
     
-    // Load configuration
-    let config = match config::load_config(config_path) {
-        Ok(cfg) => {
+    // Load configuration and any profiles next to it
+    let config_manager = match ConfigManager::load(config_path) {
+        Ok(manager) => {
             tracing::info!("Configuration loaded successfully");
-            cfg
+            if let Some(profile) = manager.active_profile() {
+                tracing::info!("Using configuration profile: {}", profile);
+            }
+            manager
         },
         Err(e) => {
             tracing::error!("Failed to load config from '{}': {}", config_path, e);
             tracing::error!("Please ensure the configuration file exists and is properly formatted");
-            return Err(e);
+            return Err(e.into());
         }
     };
+    let config = config_manager.active_config();
     
     // Display basic config info
     tracing::info!("Printer configuration:");
@@ -51,7 +59,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("  Steppers configured: {}", config.steppers.len());
     
     // Create and start printer
-    let mut printer = match Printer::new(config).await {
+    let mut printer = match Printer::new_with_profiles(config_manager).await {
         Ok(p) => p,
         Err(e) => {
             tracing::error!("Failed to initialize printer: {}", e);
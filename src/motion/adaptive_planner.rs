@@ -1,16 +1,18 @@
 // src/motion/adaptive_planner.rs - Complete adaptive motion planner
 use std::collections::VecDeque;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use crate::printer::PrinterState;
-use crate::hardware::HardwareManager;
-use crate::motion::{MotionConfig, MotionType, MotionBlock};
+use std::io;
+use serde::{Deserialize, Serialize};
+use crate::config::AdvancedConfig;
+use crate::motion::{MotionConfig, MotionType};
+
+/// Inputs of the error predictor network
+const PREDICTOR_INPUTS: usize = 12;
+
+/// Hidden units of the error predictor network
+const PREDICTOR_HIDDEN: usize = 64;
 
 /// Complete adaptive motion planner with real-time optimization
 pub struct AdaptiveMotionPlanner {
-    state: Arc<RwLock<PrinterState>>,
-    hardware_manager: HardwareManager,
-    
     /// Core motion planner
     core_planner: MotionPlanner,
     
@@ -26,6 +28,9 @@ pub struct AdaptiveMotionPlanner {
     /// Vibration analysis system
     vibration_analyzer: VibrationAnalyzer,
     
+    /// Where the last planned move ends [X, Y, Z, E]
+    current_position: [f64; 4],
+    
     /// Configuration
     config: AdaptiveConfig,
 }
@@ -38,6 +43,9 @@ pub struct AdaptiveConfig {
     pub performance_window: usize,
     pub vibration_threshold: f64,
     pub quality_threshold: f64,
+    /// Where the error predictor's learned model is kept; loaded on startup
+    /// if it exists
+    pub model_path: Option<String>,
 }
 
 impl Default for AdaptiveConfig {
@@ -48,6 +56,17 @@ impl Default for AdaptiveConfig {
             performance_window: 100,
             vibration_threshold: 0.02, // 20 microns RMS
             quality_threshold: 0.95,   // 95% quality target
+            model_path: None,
+        }
+    }
+}
+
+impl AdaptiveConfig {
+    /// Defaults, with the model kept at `advanced.predictor_model_path`
+    pub fn from_config(advanced: &AdvancedConfig) -> Self {
+        Self {
+            model_path: advanced.predictor_model_path.clone(),
+            ..Self::default()
         }
     }
 }
@@ -65,6 +84,121 @@ pub struct AdaptiveOptimizer {
     
     /// Convergence tracking
     convergence_tracker: ConvergenceTracker,
+    
+    /// Best trade-offs between quality, speed and vibration seen so far
+    pareto_front: ParetoFront<OptimizationParams>,
+}
+
+/// Parameters a `ParetoFront` can hold
+pub trait Parameters: Clone + std::fmt::Debug {}
+
+impl Parameters for OptimizationParams {}
+
+/// Parameters tagged with the performance measured while using them
+#[derive(Debug, Clone)]
+pub struct ParetoSolution<P: Parameters = OptimizationParams> {
+    pub params: P,
+    /// Print quality, higher is better (0.0 - 1.0)
+    pub quality: f64,
+    /// Actual over requested speed, higher is better
+    pub speed_ratio: f64,
+    /// RMS vibration, lower is better (mm)
+    pub vibration: f64,
+}
+
+impl<P: Parameters> ParetoSolution<P> {
+    /// Whether this is no worse than `other` in every objective and better
+    /// in at least one
+    pub fn dominates(&self, other: &Self) -> bool {
+        let no_worse = self.quality >= other.quality
+            && self.speed_ratio >= other.speed_ratio
+            && self.vibration <= other.vibration;
+        let better = self.quality > other.quality
+            || self.speed_ratio > other.speed_ratio
+            || self.vibration < other.vibration;
+        no_worse && better
+    }
+
+    fn same_objectives(&self, other: &Self) -> bool {
+        self.quality == other.quality && self.speed_ratio == other.speed_ratio && self.vibration == other.vibration
+    }
+}
+
+/// Most solutions a `ParetoFront` keeps; the oldest go first
+const MAX_PARETO_SOLUTIONS: usize = 64;
+
+/// Solutions none of which dominates another
+#[derive(Debug, Clone)]
+pub struct ParetoFront<P: Parameters> {
+    solutions: Vec<ParetoSolution<P>>,
+}
+
+impl<P: Parameters> Default for ParetoFront<P> {
+    fn default() -> Self {
+        Self { solutions: Vec::new() }
+    }
+}
+
+impl<P: Parameters> ParetoFront<P> {
+    /// Add `solution` unless a member dominates or equals it, dropping the
+    /// members it dominates; returns whether it was added
+    pub fn insert(&mut self, solution: ParetoSolution<P>) -> bool {
+        if self
+            .solutions
+            .iter()
+            .any(|member| member.dominates(&solution) || member.same_objectives(&solution))
+        {
+            return false;
+        }
+        self.solutions.retain(|member| !solution.dominates(member));
+        self.solutions.push(solution);
+        if self.solutions.len() > MAX_PARETO_SOLUTIONS {
+            self.solutions.remove(0);
+        }
+        true
+    }
+
+    pub fn solutions(&self) -> &[ParetoSolution<P>] {
+        &self.solutions
+    }
+
+    /// The member best suited to `priority`, `None` while empty
+    ///
+    /// `Balanced` scales each objective to 0-1 across the front and takes
+    /// the member with the highest total.
+    pub fn pick(&self, priority: Priority) -> Option<&ParetoSolution<P>> {
+        let by = |score: &dyn Fn(&ParetoSolution<P>) -> f64| {
+            self.solutions.iter().max_by(|a, b| score(a).total_cmp(&score(b)))
+        };
+        match priority {
+            Priority::MaxQuality => by(&|solution| solution.quality),
+            Priority::MaxSpeed => by(&|solution| solution.speed_ratio),
+            Priority::Balanced => {
+                let range = |objective: fn(&ParetoSolution<P>) -> f64| {
+                    let values = self.solutions.iter().map(objective);
+                    let min = values.clone().fold(f64::INFINITY, f64::min);
+                    let max = values.fold(f64::NEG_INFINITY, f64::max);
+                    (min, (max - min).max(f64::EPSILON))
+                };
+                let (quality_min, quality_span) = range(|solution| solution.quality);
+                let (speed_min, speed_span) = range(|solution| solution.speed_ratio);
+                let (vibration_min, vibration_span) = range(|solution| solution.vibration);
+                by(&|solution| {
+                    (solution.quality - quality_min) / quality_span
+                        + (solution.speed_ratio - speed_min) / speed_span
+                        + 1.0 - (solution.vibration - vibration_min) / vibration_span
+                })
+            }
+        }
+    }
+}
+
+/// Which objective `AdaptiveOptimizer::pick_from_pareto` favours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    MaxQuality,
+    MaxSpeed,
+    Balanced,
 }
 
 /// Performance data for adaptive optimization
@@ -122,9 +256,6 @@ pub struct PerformanceMonitor {
     /// Position tracking accuracy
     position_errors: VecDeque<f64>,
     
-    /// Processing performance metrics
-    processing_times: VecDeque<f64>,
-    
     /// Current metrics
     current_metrics: PerformanceMetrics,
     
@@ -151,18 +282,28 @@ pub struct ErrorPredictor {
     model_biases: Vec<f64>,
     
     /// Training data
-    training_ Vec<TrainingSample>,
+    training_data: Vec<TrainingSample>,
     
     /// Prediction confidence
     confidence: f64,
     
-    /// Correction factors
-    corrections: Vec<CorrectionFactor>,
-    
     /// Model configuration
     input_size: usize,
     hidden_size: usize,
-    output_size: usize,
+    
+    /// Bumped on every `update_model`, so a saved model can be told apart
+    /// from an older one
+    pub model_version: u64,
+}
+
+/// On-disk form of an `ErrorPredictor`'s learned parameters
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedModel {
+    model_version: u64,
+    input_size: usize,
+    hidden_size: usize,
+    model_weights: Vec<Vec<f64>>,
+    model_biases: Vec<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -174,15 +315,6 @@ pub struct TrainingSample {
     pub timestamp: std::time::Instant,
 }
 
-#[derive(Debug, Clone)]
-pub struct CorrectionFactor {
-    pub position: [f64; 3],          // X, Y, Z coordinates
-    pub correction: [f64; 3],        // X, Y, Z correction values
-    pub confidence: f64,             // How confident in this correction
-    pub timestamp: std::time::Instant,
-    pub effectiveness: f64,          // How effective this correction was
-}
-
 /// Advanced vibration analyzer
 pub struct VibrationAnalyzer {
     /// Frequency analysis parameters
@@ -220,8 +352,9 @@ pub struct ShaperParams {
     pub effectiveness: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum ShaperType {
+    #[default]
     None,
     ZVD,
     ZVDD,
@@ -248,38 +381,42 @@ pub struct VibrationStats {
 }
 
 impl AdaptiveMotionPlanner {
-    pub fn new(
-        state: Arc<RwLock<PrinterState>>,
-        hardware_manager: HardwareManager,
-        motion_config: MotionConfig,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let config = AdaptiveConfig::default();
-        
+    pub fn new(motion_config: MotionConfig, config: AdaptiveConfig) -> Self {
         let core_planner = MotionPlanner::new(motion_config);
         let optimizer = AdaptiveOptimizer::new(config.clone());
         let performance_monitor = PerformanceMonitor::new(100);
-        let error_predictor = ErrorPredictor::new(12, 64, 1);
+        let error_predictor = ErrorPredictor::load_or_new(config.model_path.as_deref());
         let vibration_analyzer = VibrationAnalyzer::new(1000.0, 1024);
         
-        Ok(Self {
-            state,
-            hardware_manager,
+        Self {
             core_planner,
             optimizer,
             performance_monitor,
             error_predictor,
             vibration_analyzer,
+            current_position: [0.0; 4],
             config,
-        })
+        }
     }
 
-    /// Plan move with adaptive optimization
+    /// Where the last planned move ends [X, Y, Z, E]
+    pub fn get_current_position(&self) -> [f64; 4] {
+        self.current_position
+    }
+
+    /// Plan the next move from `position`, e.g. after homing or when
+    /// taking over from another planner
+    pub fn set_position(&mut self, position: [f64; 4]) {
+        self.current_position = position;
+    }
+
+    /// Plan a move from the current position with adaptive optimization
     pub async fn plan_adaptive_move(
         &mut self,
         target: [f64; 4],
         feedrate: f64,
         motion_type: MotionType,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<MotionBlock, Box<dyn std::error::Error>> {
         // Get current performance metrics
         let metrics = self.performance_monitor.get_current_metrics();
         
@@ -303,8 +440,8 @@ impl AdaptiveMotionPlanner {
         let optimized_params = self.optimizer.get_optimized_params();
         
         // Plan the move with optimized parameters
-        let mut block = self.core_planner.plan_move(
-            [0.0, 0.0, 0.0, 0.0], // Would get current position
+        let block = self.core_planner.plan_move(
+            self.current_position,
             shaped_target,
             adjusted_feedrate,
             motion_type,
@@ -312,19 +449,18 @@ impl AdaptiveMotionPlanner {
                 max_velocity: optimized_params.max_acceleration, // Simplified
                 max_acceleration: optimized_params.max_acceleration,
                 max_jerk: optimized_params.max_jerk,
-                junction_deviation: optimized_params.junction_deviation,
-                ..Default::default() // Fill in other defaults
+                ..self.core_planner.config.clone()
             },
         )?;
         
         // Apply final optimizations
-        block = self.apply_final_optimizations(block, &metrics).await?;
+        let block = self.apply_final_optimizations(block, &metrics).await?;
+        self.current_position = target;
         
-        // In real implementation, this would queue the block
-        tracing::info!("Planned adaptive move to [{:.3}, {:.3}, {:.3}, {:.3}] at {:.1}mm/s",
-                      target[0], target[1], target[2], target[3], adjusted_feedrate);
+        tracing::debug!("Planned adaptive move to [{:.3}, {:.3}, {:.3}, {:.3}] at {:.1}mm/s",
+                      target[0], target[1], target[2], target[3], block.limited_feedrate);
         
-        Ok(())
+        Ok(block)
     }
 
     /// Adjust feedrate based on error prediction
@@ -371,7 +507,8 @@ impl AdaptiveMotionPlanner {
         self.performance_monitor.update(&metrics).await?;
         
         // Analyze vibrations
-        let vibration_analysis = self.vibration_analyzer.analyze_vibrations(&metrics)?;
+        let samples = self.performance_monitor.vibration_samples();
+        let vibration_analysis = self.vibration_analyzer.analyze_vibrations(&samples, &metrics)?;
         
         // Update optimizer with new data
         self.optimizer.update_with_data(&metrics, &vibration_analysis).await?;
@@ -437,8 +574,8 @@ impl AdaptiveMotionPlanner {
 
     /// Set adaptive configuration
     pub fn set_config(&mut self, config: AdaptiveConfig) {
-        self.config = config;
         self.optimizer.set_config(config.clone());
+        self.config = config;
     }
 }
 
@@ -454,6 +591,7 @@ impl AdaptiveOptimizer {
                 stable_iterations: 0,
                 last_improvement: std::time::Instant::now(),
             },
+            pareto_front: ParetoFront::default(),
         }
     }
 
@@ -475,6 +613,14 @@ impl AdaptiveOptimizer {
             temperature_stability: metrics.thermal_stability,
         };
         
+        // The metrics were measured with the parameters before this adaptation
+        self.pareto_front.insert(ParetoSolution {
+            params: self.optimization_params.clone(),
+            quality: performance_data.print_quality,
+            speed_ratio: performance_data.print_speed_ratio,
+            vibration: performance_data.vibration_level,
+        });
+        
         // Store performance data
         self.performance_history.push_back(performance_data);
         
@@ -536,11 +682,9 @@ impl AdaptiveOptimizer {
             1.0 // Maintain current
         };
         
-        for i in 0..4 {
-            self.optimization_params.max_acceleration[i] *= acceleration_factor;
+        for acceleration in &mut self.optimization_params.max_acceleration {
             // Apply reasonable limits
-            self.optimization_params.max_acceleration[i] = 
-                self.optimization_params.max_acceleration[i].max(100.0).min(10000.0);
+            *acceleration = (*acceleration * acceleration_factor).clamp(100.0, 10000.0);
         }
         
         // Adjust jerk based on resonance analysis
@@ -549,9 +693,9 @@ impl AdaptiveOptimizer {
                 / vibration_analysis.resonance_peaks.len() as f64;
             
             // Reduce jerk for axes with resonance issues
-            for i in 0..4 {
-                if avg_resonance > 50.0 {  // High frequency resonance
-                    self.optimization_params.max_jerk[i] *= 1.0 - (adaptation_rate * 0.3);
+            if avg_resonance > 50.0 {  // High frequency resonance
+                for jerk in &mut self.optimization_params.max_jerk {
+                    *jerk *= 1.0 - (adaptation_rate * 0.3);
                 }
             }
         }
@@ -583,7 +727,7 @@ impl AdaptiveOptimizer {
                 let older_avg: f64 = older.iter().sum::<f64>() / older.len() as f64;
                 
                 let improvement = recent_avg - older_avg;
-                self.convergence_tracker.convergence_score = improvement.max(-1.0).min(1.0);
+                self.convergence_tracker.convergence_score = improvement.clamp(-1.0, 1.0);
                 
                 if improvement > 0.01 {
                     self.convergence_tracker.stable_iterations = 0;
@@ -602,6 +746,19 @@ impl AdaptiveOptimizer {
         self.optimization_params.clone()
     }
 
+    /// Non-dominated parameter sets seen so far
+    pub fn get_pareto_front(&self) -> &[ParetoSolution] {
+        self.pareto_front.solutions()
+    }
+
+    /// Parameters from the Pareto front best suited to `priority`, or the
+    /// current ones before any have been measured
+    pub fn pick_from_pareto(&self, priority: Priority) -> OptimizationParams {
+        self.pareto_front
+            .pick(priority)
+            .map_or_else(|| self.optimization_params.clone(), |solution| solution.params.clone())
+    }
+
     /// Set configuration
     pub fn set_config(&mut self, config: AdaptiveConfig) {
         self.config = config;
@@ -618,7 +775,6 @@ impl PerformanceMonitor {
         Self {
             vibration_buffer: VecDeque::with_capacity(buffer_size),
             position_errors: VecDeque::with_capacity(buffer_size),
-            processing_times: VecDeque::with_capacity(buffer_size),
             current_metrics: PerformanceMetrics::default(),
             buffer_size,
         }
@@ -643,6 +799,11 @@ impl PerformanceMonitor {
         }
     }
 
+    /// Buffered vibration measurements, oldest first
+    pub fn vibration_samples(&self) -> Vec<f64> {
+        self.vibration_buffer.iter().copied().collect()
+    }
+
     /// Add position error measurement
     pub fn add_position_error(&mut self, error: f64) {
         self.position_errors.push_back(error);
@@ -658,7 +819,7 @@ impl PerformanceMonitor {
         if !self.vibration_buffer.is_empty() {
             let sum: f64 = self.vibration_buffer.iter().sum();
             metrics.avg_vibration = sum / self.vibration_buffer.len() as f64;
-            metrics.max_vibration = *self.vibration_buffer.iter().fold(&0.0, |a, b| a.max(b));
+            metrics.max_vibration = self.vibration_buffer.iter().copied().fold(0.0, f64::max);
         }
         
         if !self.position_errors.is_empty() {
@@ -671,32 +832,90 @@ impl PerformanceMonitor {
 }
 
 impl ErrorPredictor {
-    pub fn new(input_size: usize, hidden_size: usize, output_size: usize) -> Self {
-        // Initialize weights with small random values
-        let mut model_weights = vec![vec![0.0; input_size]; hidden_size];
-        let mut model_biases = vec![0.0; hidden_size];
-        
-        // Simple Xavier initialization
-        let weight_scale = (6.0 / (input_size + hidden_size) as f64).sqrt();
-        for i in 0..hidden_size {
-            for j in 0..input_size {
-                model_weights[i][j] = (rand::random::<f64>() - 0.5) * 2.0 * weight_scale;
-            }
-            model_biases[i] = (rand::random::<f64>() - 0.5) * 0.1;
-        }
-        
+    pub fn new(input_size: usize, hidden_size: usize) -> Self {
+        let (model_weights, model_biases) = Self::random_model(input_size, hidden_size);
         Self {
             model_weights,
             model_biases,
             training_data: Vec::new(),
             confidence: 0.5,
-            corrections: Vec::new(),
             input_size,
             hidden_size,
-            output_size,
+            model_version: 0,
         }
     }
 
+    /// Small random weights and biases
+    fn random_model(input_size: usize, hidden_size: usize) -> (Vec<Vec<f64>>, Vec<f64>) {
+        // Simple Xavier initialization
+        let weight_scale = (6.0 / (input_size + hidden_size) as f64).sqrt();
+        let model_weights = (0..hidden_size)
+            .map(|_| (0..input_size).map(|_| (rand::random::<f64>() - 0.5) * 2.0 * weight_scale).collect())
+            .collect();
+        let model_biases = (0..hidden_size).map(|_| (rand::random::<f64>() - 0.5) * 0.1).collect();
+        (model_weights, model_biases)
+    }
+
+    /// Predictor restored from `path` if a model is saved there, otherwise
+    /// a freshly initialized one
+    pub fn load_or_new(path: Option<&str>) -> Self {
+        let mut predictor = Self::new(PREDICTOR_INPUTS, PREDICTOR_HIDDEN);
+        if let Some(path) = path
+            && std::path::Path::new(path).exists()
+        {
+            match predictor.load_model(path) {
+                Ok(()) => tracing::info!("Loaded error predictor model v{} from {}", predictor.model_version, path),
+                Err(e) => tracing::warn!("Ignoring error predictor model {}: {}", path, e),
+            }
+        }
+        predictor
+    }
+
+    /// Forget everything learned and start again from random weights
+    pub fn reset_model(&mut self) {
+        (self.model_weights, self.model_biases) = Self::random_model(self.input_size, self.hidden_size);
+        self.model_version = 0;
+        self.confidence = 0.5;
+    }
+
+    /// Write the weights and biases to `path` as JSON
+    pub fn save_model(&self, path: &str) -> Result<(), io::Error> {
+        let model = SavedModel {
+            model_version: self.model_version,
+            input_size: self.input_size,
+            hidden_size: self.hidden_size,
+            model_weights: self.model_weights.clone(),
+            model_biases: self.model_biases.clone(),
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(&model)?)
+    }
+
+    /// Replace the weights and biases with those saved at `path`
+    ///
+    /// Fails without changing anything if the file is for a network of a
+    /// different shape.
+    pub fn load_model(&mut self, path: &str) -> Result<(), io::Error> {
+        let model: SavedModel = serde_json::from_slice(&std::fs::read(path)?)?;
+        let shape_matches = model.input_size == self.input_size
+            && model.hidden_size == self.hidden_size
+            && model.model_biases.len() == self.hidden_size
+            && model.model_weights.len() == self.hidden_size
+            && model.model_weights.iter().all(|row| row.len() == self.input_size);
+        if !shape_matches {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Model is {}x{}, expected {}x{}",
+                    model.hidden_size, model.input_size, self.hidden_size, self.input_size
+                ),
+            ));
+        }
+        self.model_weights = model.model_weights;
+        self.model_biases = model.model_biases;
+        self.model_version = model.model_version;
+        Ok(())
+    }
+
     /// Predict error for a given motion
     pub async fn predict_error(
         &self,
@@ -705,7 +924,7 @@ impl ErrorPredictor {
         metrics: &PerformanceMetrics,
     ) -> Result<f64, Box<dyn std::error::Error>> {
         // Create feature vector
-        let features = vec![
+        let features = [
             target[0], target[1], target[2], target[3], // Position
             feedrate,                                   // Feedrate
             metrics.avg_vibration,                      // Current vibration
@@ -715,23 +934,15 @@ impl ErrorPredictor {
             metrics.speed_efficiency,                   // Speed efficiency
         ];
         
-        // Simple neural network forward pass (simplified)
-        let mut hidden = vec![0.0; self.hidden_size];
-        
-        // Linear transformation + ReLU activation
-        for i in 0..self.hidden_size {
-            for (j, &feature) in features.iter().enumerate() {
-                hidden[i] += self.model_weights[i][j] * feature;
-            }
-            hidden[i] += self.model_biases[i];
-            hidden[i] = hidden[i].max(0.0); // ReLU
-        }
+        // Simple neural network forward pass (simplified):
+        // linear transformation + ReLU activation
+        let hidden = self.model_weights.iter().zip(&self.model_biases).map(|(weights, bias)| {
+            let sum: f64 = weights.iter().zip(&features).map(|(w, x)| w * x).sum();
+            (sum + bias).max(0.0)
+        });
         
         // Output layer (single output for error prediction)
-        let mut output = 0.0;
-        for (i, &hidden_val) in hidden.iter().enumerate() {
-            output += hidden_val * 0.1; // Simplified output weights
-        }
+        let output: f64 = hidden.map(|hidden_val| hidden_val * 0.1).sum(); // Simplified output weights
         
         // Sigmoid activation to bound output between 0 and 1
         let predicted_error = 1.0 / (1.0 + (-output).exp());
@@ -751,6 +962,7 @@ impl ErrorPredictor {
             self.confidence = (self.confidence - 0.01).max(0.0);
         }
         
+        self.model_version += 1;
         Ok(())
     }
 
@@ -782,37 +994,76 @@ impl VibrationAnalyzer {
     }
 
     /// Analyze vibrations and detect resonances
-    pub fn analyze_vibrations(&mut self, metrics: &PerformanceMetrics) -> Result<VibrationAnalysis, Box<dyn std::error::Error>> {
+    ///
+    /// `samples` are accelerometer readings at `sample_rate`; the most
+    /// recent `analysis_window` of them are transformed, zero-padded to a
+    /// power of two.
+    pub fn analyze_vibrations(
+        &mut self,
+        samples: &[f64],
+        metrics: &PerformanceMetrics,
+    ) -> Result<VibrationAnalysis, Box<dyn std::error::Error>> {
         // Increment analysis counter
         self.stats.total_analyses += 1;
         self.stats.last_analysis = Some(std::time::Instant::now());
         
-        // Simulate resonance detection based on metrics
-        let mut detected_peaks = Vec::new();
+        let window = &samples[samples.len().saturating_sub(self.analysis_window)..];
+        if window.len() < 4 {
+            self.resonance_peaks.clear();
+            return Ok(VibrationAnalysis {
+                frequency_spectrum: Vec::new(),
+                resonance_peaks: Vec::new(),
+                dominant_frequencies: Vec::new(),
+                overall_level: metrics.avg_vibration,
+            });
+        }
+        
+        let spectrum = magnitude_spectrum(window);
+        let bin_width = self.sample_rate / ((spectrum.len() - 1) * 2) as f64;
+        let db: Vec<f64> = spectrum.iter().map(|magnitude| 20.0 * magnitude.max(1e-12).log10()).collect();
         
-        // Detect resonances based on vibration characteristics
-        if metrics.avg_vibration > 0.015 {
-            // High vibration - likely resonance
+        // Local maxima standing clear of both the noise and the window's sidelobes
+        let mut sorted = db.clone();
+        sorted.sort_by(f64::total_cmp);
+        let noise_floor = sorted[sorted.len() / 2] + NOISE_FLOOR_MARGIN_DB;
+        let strongest = sorted[sorted.len() - 1];
+        let threshold = noise_floor.max(strongest - PEAK_DYNAMIC_RANGE_DB);
+        
+        let mut detected_peaks = Vec::new();
+        for k in 1..db.len() - 1 {
+            if db[k] <= threshold || db[k] <= db[k - 1] || db[k] < db[k + 1] {
+                continue;
+            }
+            
+            // Vertex of the parabola through the peak bin and its neighbours
+            let (left, center, right) = (db[k - 1], db[k], db[k + 1]);
+            let curvature = left - 2.0 * center + right;
+            let offset = if curvature < 0.0 { 0.5 * (left - right) / curvature } else { 0.0 };
+            let frequency = (k as f64 + offset) * bin_width;
+            let peak_db = center - 0.25 * (left - right) * offset;
+            
+            let bandwidth = bandwidth_at(&db, k, peak_db - 3.0) * bin_width;
             detected_peaks.push(ResonancePeak {
-                frequency: 45.0 + rand::random::<f64>() * 10.0, // Random frequency around 50Hz
-                amplitude: metrics.avg_vibration,
-                bandwidth: 5.0 + rand::random::<f64>() * 10.0,
-                quality_factor: 3.0 + rand::random::<f64>() * 2.0,
-                axis: rand::random::<usize>() % 3, // Random axis
+                frequency,
+                amplitude: 10f64.powf(peak_db / 20.0),
+                bandwidth,
+                quality_factor: if bandwidth > 0.0 { frequency / bandwidth } else { f64::INFINITY },
+                axis: 0, // Samples come from a single accelerometer axis
                 timestamp: std::time::Instant::now(),
             });
-            
-            self.stats.resonances_detected += 1;
         }
+        detected_peaks.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+        self.stats.resonances_detected += detected_peaks.len() as u64;
         
         // Update stored resonance peaks
         self.resonance_peaks = detected_peaks.clone();
         
+        let rms = (window.iter().map(|sample| sample * sample).sum::<f64>() / window.len() as f64).sqrt();
         let analysis = VibrationAnalysis {
-            frequency_spectrum: vec![(50.0, metrics.avg_vibration)], // Simplified
+            frequency_spectrum: spectrum.iter().enumerate().map(|(k, &magnitude)| (k as f64 * bin_width, magnitude)).collect(),
+            dominant_frequencies: detected_peaks.iter().map(|peak| peak.frequency).collect(),
             resonance_peaks: detected_peaks,
-            dominant_frequencies: vec![50.0],
-            overall_level: metrics.avg_vibration,
+            overall_level: rms,
         };
         
         Ok(analysis)
@@ -874,6 +1125,79 @@ impl VibrationAnalyzer {
 }
 
 /// Vibration analysis results
+/// Spectral peaks must rise this far above the median bin
+const NOISE_FLOOR_MARGIN_DB: f64 = 20.0;
+
+/// Peaks further than this below the strongest are ignored; the Hann
+/// window's sidelobes are at least 31 dB down
+const PEAK_DYNAMIC_RANGE_DB: f64 = 30.0;
+
+/// Single-sided amplitude spectrum of Hann-windowed `samples`, scaled so a
+/// sinusoid's bin reads its amplitude
+fn magnitude_spectrum(samples: &[f64]) -> Vec<f64> {
+    let n = samples.len().next_power_of_two();
+    let hann = |i: usize| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / samples.len() as f64).cos();
+    let window_sum: f64 = (0..samples.len()).map(hann).sum();
+    
+    let mut buffer = vec![(0.0, 0.0); n];
+    for (i, sample) in samples.iter().enumerate() {
+        buffer[i].0 = sample * hann(i);
+    }
+    fft(&mut buffer);
+    
+    buffer[..=n / 2]
+        .iter()
+        .map(|(re, im)| 2.0 * (re * re + im * im).sqrt() / window_sum)
+        .collect()
+}
+
+/// In-place iterative radix-2 Cooley-Tukey FFT; `buffer.len()` must be a
+/// power of two
+fn fft(buffer: &mut [(f64, f64)]) {
+    let n = buffer.len();
+    
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+    
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f64::consts::PI / len as f64;
+        for chunk in buffer.chunks_mut(len) {
+            let (even, odd) = chunk.split_at_mut(len / 2);
+            for (k, (a, b)) in even.iter_mut().zip(odd.iter_mut()).enumerate() {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let twiddled = (b.0 * cos - b.1 * sin, b.0 * sin + b.1 * cos);
+                *b = (a.0 - twiddled.0, a.1 - twiddled.1);
+                *a = (a.0 + twiddled.0, a.1 + twiddled.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Width in bins of the peak at `k` where it crosses `level` dB, linearly
+/// interpolated between bins
+fn bandwidth_at(db: &[f64], k: usize, level: f64) -> f64 {
+    let crossing = |inner: usize, outer: usize| {
+        let fraction = (db[inner] - level) / (db[inner] - db[outer]);
+        inner as f64 + (outer as f64 - inner as f64) * fraction
+    };
+    let low = (1..=k).rev().find(|&i| db[i - 1] < level).map_or(0.0, |i| crossing(i, i - 1));
+    let high = (k..db.len() - 1).find(|&i| db[i + 1] < level).map_or((db.len() - 1) as f64, |i| crossing(i, i + 1));
+    high - low
+}
+
 #[derive(Debug, Clone)]
 pub struct VibrationAnalysis {
     pub frequency_spectrum: Vec<(f64, f64)>,  // (frequency, amplitude)
@@ -883,10 +1207,21 @@ pub struct VibrationAnalysis {
 }
 
 // Helper implementations
-impl Default for ShaperType {
-    fn default() -> Self {
-        ShaperType::None
-    }
+/// A move planned by the simplified core planner
+#[derive(Debug, Clone)]
+pub struct MotionBlock {
+    pub target: [f64; 4],
+    pub motor_target: [f64; 4],
+    pub requested_feedrate: f64,
+    pub limited_feedrate: f64,
+    pub distance: f64,
+    pub duration: f64,
+    pub acceleration: f64,
+    pub entry_speed: f64,
+    pub exit_speed: f64,
+    pub motion_type: MotionType,
+    pub optimized: bool,
+    pub timestamp: std::time::Instant,
 }
 
 // MotionPlanner implementation (simplified)
@@ -901,14 +1236,14 @@ impl MotionPlanner {
 
     pub fn plan_move(
         &self,
-        _start: [f64; 4],
+        start: [f64; 4],
         target: [f64; 4],
         feedrate: f64,
         motion_type: MotionType,
         _config: &MotionConfig,
     ) -> Result<MotionBlock, Box<dyn std::error::Error>> {
         // Simplified motion planning
-        let distance = ((target[0].powi(2) + target[1].powi(2) + target[2].powi(2) + target[3].powi(2))).sqrt();
+        let distance = target.iter().zip(start).map(|(to, from)| (to - from).powi(2)).sum::<f64>().sqrt();
         
         Ok(MotionBlock {
             target,
@@ -925,4 +1260,107 @@ impl MotionPlanner {
             timestamp: std::time::Instant::now(),
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solution(quality: f64, speed_ratio: f64, vibration: f64) -> ParetoSolution {
+        ParetoSolution { params: OptimizationParams::default(), quality, speed_ratio, vibration }
+    }
+
+    #[test]
+    fn test_pareto_dominance() {
+        let base = solution(0.9, 0.8, 0.01);
+        assert!(solution(0.95, 0.8, 0.01).dominates(&base));
+        assert!(solution(0.9, 0.8, 0.005).dominates(&base));
+        // Equal is not better
+        assert!(!base.dominates(&base));
+        // A trade-off dominates neither way
+        let faster = solution(0.85, 1.0, 0.01);
+        assert!(!faster.dominates(&base));
+        assert!(!base.dominates(&faster));
+    }
+
+    #[test]
+    fn test_pareto_front_pruning() {
+        let mut front = ParetoFront::default();
+        assert!(front.insert(solution(0.9, 0.8, 0.01)));
+        assert!(front.insert(solution(0.8, 1.0, 0.02)));
+        assert!(front.insert(solution(0.7, 0.7, 0.001)));
+        // Dominated by the first member, or a repeat of it
+        assert!(!front.insert(solution(0.85, 0.8, 0.01)));
+        assert!(!front.insert(solution(0.9, 0.8, 0.01)));
+        assert_eq!(front.solutions().len(), 3);
+
+        // Beats the first two, not the low-vibration one
+        assert!(front.insert(solution(0.95, 1.0, 0.01)));
+        let objectives: Vec<_> = front.solutions().iter().map(|s| (s.quality, s.speed_ratio, s.vibration)).collect();
+        assert_eq!(objectives, [(0.7, 0.7, 0.001), (0.95, 1.0, 0.01)]);
+
+        assert!(front.insert(solution(0.6, 1.2, 0.05)));
+        assert_eq!(front.pick(Priority::MaxQuality).unwrap().quality, 0.95);
+        assert_eq!(front.pick(Priority::MaxSpeed).unwrap().speed_ratio, 1.2);
+        assert_eq!(front.pick(Priority::Balanced).unwrap().quality, 0.95);
+        assert!(ParetoFront::<OptimizationParams>::default().pick(Priority::Balanced).is_none());
+    }
+
+    #[test]
+    fn test_resonance_peaks() {
+        let sample_rate = 1000.0;
+        let samples: Vec<f64> = (0..1024)
+            .map(|i| {
+                let t = i as f64 / sample_rate;
+                (2.0 * std::f64::consts::PI * 42.3 * t).sin() + 0.5 * (2.0 * std::f64::consts::PI * 117.8 * t + 1.0).sin()
+            })
+            .collect();
+        let mut analyzer = VibrationAnalyzer::new(sample_rate, 1024);
+        let analysis = analyzer.analyze_vibrations(&samples, &PerformanceMetrics::default()).unwrap();
+
+        assert_eq!(analysis.resonance_peaks.len(), 2, "{:?}", analysis.dominant_frequencies);
+        for (peak, (frequency, amplitude)) in analysis.resonance_peaks.iter().zip([(42.3, 1.0), (117.8, 0.5)]) {
+            assert!((peak.frequency - frequency).abs() < 0.5, "found {} Hz, expected {}", peak.frequency, frequency);
+            assert!((peak.amplitude - amplitude).abs() < 0.1 * amplitude);
+            assert!(peak.quality_factor > 10.0);
+        }
+        assert_eq!(analyzer.resonance_peaks.len(), 2);
+
+        // Nothing but noise-free silence: no peaks
+        let analysis = analyzer.analyze_vibrations(&[0.0; 1024], &PerformanceMetrics::default()).unwrap();
+        assert!(analysis.resonance_peaks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_model_save_restore() {
+        let mut predictor = ErrorPredictor::new(12, 64);
+        for step in 0..100 {
+            let metrics = PerformanceMetrics {
+                quality_score: if step % 2 == 0 { 0.95 } else { 0.6 },
+                ..PerformanceMetrics::default()
+            };
+            predictor.update_model(&metrics).await.unwrap();
+        }
+        assert_eq!(predictor.model_version, 100);
+
+        let path = std::env::temp_dir().join(format!("krusty-predictor-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        predictor.save_model(path).unwrap();
+
+        let mut restored = ErrorPredictor::new(12, 64);
+        assert_ne!(restored.model_weights, predictor.model_weights);
+        restored.load_model(path).unwrap();
+        assert_eq!(restored.model_weights, predictor.model_weights);
+        assert_eq!(restored.model_biases, predictor.model_biases);
+        assert_eq!(restored.model_version, 100);
+
+        // A model for another network shape is refused
+        let mut other = ErrorPredictor::new(8, 64);
+        assert_eq!(other.load_model(path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+
+        restored.reset_model();
+        assert_eq!(restored.model_version, 0);
+        assert_ne!(restored.model_weights, predictor.model_weights);
+    }
+}
@@ -156,7 +156,7 @@ impl AdvancedMotionPlanner {
         let kinematics = create_kinematics(
             config.kinematics_type,
             config.axis_limits,
-        );
+        )?;
         
         let junction_deviation = JunctionDeviation::new(config.junction_deviation);
         
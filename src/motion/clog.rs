@@ -0,0 +1,129 @@
+// src/motion/clog.rs - Clog detection from planned against stepped extrusion
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use crate::config::ClogDetectionConfig;
+use crate::printer::PrinterEvent;
+
+/// Extruder position after one step generation (mm)
+#[derive(Debug, Clone, Copy)]
+struct ClogSample {
+    at: Instant,
+    planned: f64,
+    stepped: f64,
+}
+
+#[derive(Debug, Default)]
+struct ClogState {
+    samples: VecDeque<ClogSample>,
+    /// Shortfall of a clog not yet handled (mm)
+    clog: Option<f64>,
+    /// Tools whose recovery differs from the configured default
+    recovery: HashMap<usize, bool>,
+}
+
+/// Watches for the extruder falling behind the filament the planner asked for
+///
+/// Clones share their state, so the planner executing moves feeds the
+/// detector and the G-code processor recovers from what it finds.
+#[derive(Debug, Clone)]
+pub struct ClogDetector {
+    state: Arc<Mutex<ClogState>>,
+    threshold_mm: f64,
+    window: Duration,
+    max_samples: usize,
+    default_recovery: bool,
+    events: broadcast::Sender<PrinterEvent>,
+}
+
+impl ClogDetector {
+    pub fn new(config: &ClogDetectionConfig, events: broadcast::Sender<PrinterEvent>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ClogState::default())),
+            threshold_mm: config.clog_threshold_mm,
+            window: Duration::from_secs_f64(config.clog_window_secs.max(0.0)),
+            max_samples: config.window_samples.max(2),
+            default_recovery: config.recovery,
+            events,
+        }
+    }
+
+    /// Record the extruder position after a step generation
+    ///
+    /// Returns the shortfall over the window if it exceeds
+    /// `clog_threshold_mm`, which also reports `PrinterEvent::ClogDetected`.
+    pub fn record(&self, planned_e: f64, stepped_e: f64) -> Option<f64> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.samples.push_back(ClogSample { at: now, planned: planned_e, stepped: stepped_e });
+        while state.samples.len() > self.max_samples
+            || state.samples.front().is_some_and(|sample| now.duration_since(sample.at) > self.window)
+        {
+            state.samples.pop_front();
+        }
+
+        let (first, last) = (*state.samples.front()?, *state.samples.back()?);
+        let shortfall = (last.planned - first.planned) - (last.stepped - first.stepped);
+        if shortfall <= self.threshold_mm {
+            return None;
+        }
+        tracing::error!("Extruder {:.2}mm behind, nozzle clogged", shortfall);
+        state.samples.clear();
+        state.clog = Some(shortfall);
+        let _ = self.events.send(PrinterEvent::ClogDetected { shortfall_mm: shortfall });
+        Some(shortfall)
+    }
+
+    /// Shortfall of the clog detected since the last call, if any
+    pub fn take_clog(&self) -> Option<f64> {
+        self.state.lock().unwrap().clog.take()
+    }
+
+    /// Whether to reverse-purge `tool` when it clogs
+    pub fn recovery_enabled(&self, tool: usize) -> bool {
+        self.state.lock().unwrap().recovery.get(&tool).copied().unwrap_or(self.default_recovery)
+    }
+
+    pub fn set_recovery(&self, tool: usize, enabled: bool) {
+        self.state.lock().unwrap().recovery.insert(tool, enabled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_extruder_falling_behind() {
+        let config = ClogDetectionConfig {
+            clog_threshold_mm: 2.0,
+            clog_window_secs: 10.0,
+            window_samples: 20,
+            recovery: true,
+        };
+        let (events, mut received) = broadcast::channel(4);
+        let detector = ClogDetector::new(&config, events);
+
+        // Following the plan, then the steps fall 5mm behind
+        for i in 0..=10 {
+            assert_eq!(detector.record(i as f64, i as f64), None);
+            tokio::time::advance(Duration::from_millis(100)).await;
+        }
+        assert_eq!(detector.record(15.0, 10.0), Some(5.0));
+        assert!(matches!(received.try_recv(), Ok(PrinterEvent::ClogDetected { shortfall_mm }) if shortfall_mm == 5.0));
+        assert_eq!(detector.take_clog(), Some(5.0));
+        assert_eq!(detector.take_clog(), None);
+
+        // Shortfalls spread over more than the window do not add up
+        detector.record(20.0, 20.0);
+        detector.record(21.5, 20.0);
+        tokio::time::advance(Duration::from_secs(11)).await;
+        assert_eq!(detector.record(23.0, 20.0), None);
+
+        assert!(detector.recovery_enabled(1));
+        detector.set_recovery(1, false);
+        assert!(!detector.recovery_enabled(1) && detector.recovery_enabled(0));
+    }
+}
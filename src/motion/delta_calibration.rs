@@ -0,0 +1,321 @@
+// src/motion/delta_calibration.rs - Delta auto-calibration (G33)
+use super::kinematics::{DeltaKinematics, Kinematics};
+
+/// Number of calibrated factors: endstop corrections × 3, tower angle corrections × 3
+const CALIBRATION_FACTORS: usize = 6;
+
+/// Step used for the numerical Jacobian (mm or degrees)
+const JACOBIAN_STEP: f64 = 1e-4;
+
+/// Damping keeps the step finite along directions the probe cannot observe
+/// (rotating all three towers together does not change bed heights)
+const DAMPING: f64 = 1e-6;
+
+/// Outcome of a calibration pass
+#[derive(Debug, Clone)]
+pub struct DeltaCalibrationResult {
+    /// RMS of the probed height errors before calibration (mm)
+    pub deviation_before: f64,
+
+    /// Expected RMS height error with the new parameters (mm)
+    pub deviation_after: f64,
+
+    /// New endstop corrections (mm)
+    pub endstop_corrections: [f64; 3],
+
+    /// New tower angle corrections (degrees)
+    pub tower_angle_corrections: [f64; 3],
+}
+
+/// Fits delta geometry errors to bed probe measurements
+///
+/// Probe heights are the Z positions (in the printer's current coordinate
+/// frame) at which the probe triggered on a flat bed. A perfectly calibrated
+/// delta reads 0 everywhere.
+#[derive(Debug, Clone)]
+pub struct DeltaCalibrator {
+    probe_points: Vec<[f64; 2]>,
+    max_iterations: usize,
+}
+
+impl DeltaCalibrator {
+    /// Create a calibrator probing the center plus two rings within `probe_radius`
+    pub fn new(probe_count: usize, probe_radius: f64) -> Result<Self, Box<dyn std::error::Error>> {
+        if probe_count <= CALIBRATION_FACTORS {
+            return Err(format!(
+                "Delta calibration needs at least {} probe points, got {}",
+                CALIBRATION_FACTORS + 1, probe_count
+            ).into());
+        }
+
+        // Center point, then an outer ring and an inner ring at half radius
+        let ring_points = probe_count - 1;
+        let outer_count = ring_points.div_ceil(2);
+        let inner_count = ring_points - outer_count;
+
+        let mut probe_points = vec![[0.0, 0.0]];
+        for (count, radius) in [(outer_count, probe_radius), (inner_count, probe_radius / 2.0)] {
+            for i in 0..count {
+                let angle = std::f64::consts::TAU * i as f64 / count as f64;
+                probe_points.push([radius * angle.cos(), radius * angle.sin()]);
+            }
+        }
+
+        Ok(Self {
+            probe_points,
+            max_iterations: 10,
+        })
+    }
+
+    /// XY positions to probe, in order
+    pub fn probe_points(&self) -> &[[f64; 2]] {
+        &self.probe_points
+    }
+
+    /// Fit new endstop and tower angle corrections to the probed heights
+    /// and apply them to `kinematics`
+    pub fn calibrate(
+        &self,
+        kinematics: &mut DeltaKinematics,
+        heights: &[f64],
+    ) -> Result<DeltaCalibrationResult, Box<dyn std::error::Error>> {
+        if heights.len() != self.probe_points.len() {
+            return Err(format!(
+                "Expected {} probe heights, got {}",
+                self.probe_points.len(), heights.len()
+            ).into());
+        }
+
+        // Carriage positions at the moment each probe triggered; these are
+        // what the machine physically measured and stay fixed while fitting
+        let mut carriages = Vec::with_capacity(heights.len());
+        for (point, &height) in self.probe_points.iter().zip(heights) {
+            carriages.push(kinematics.cartesian_to_motors(&[point[0], point[1], height])?);
+        }
+
+        let mut model = kinematics.clone();
+        let deviation_before = rms(&bed_heights(&model, &carriages)?);
+
+        for _ in 0..self.max_iterations {
+            let residuals = bed_heights(&model, &carriages)?;
+            let jacobian = self.jacobian(&model, &carriages)?;
+
+            // Solve the damped system [J; √λ·I]·Δ = [-r; 0]
+            let mut a = jacobian;
+            let mut b: Vec<f64> = residuals.iter().map(|r| -r).collect();
+            for factor in 0..CALIBRATION_FACTORS {
+                let mut row = vec![0.0; CALIBRATION_FACTORS];
+                row[factor] = DAMPING.sqrt();
+                a.push(row);
+                b.push(0.0);
+            }
+            let step = solve_least_squares(a, b)
+                .ok_or("Delta calibration system is singular")?;
+
+            let mut factors = get_factors(&model);
+            for (factor, delta) in factors.iter_mut().zip(&step) {
+                *factor += delta;
+            }
+            set_factors(&mut model, &factors);
+
+            if step.iter().all(|delta| delta.abs() < 1e-9) {
+                break;
+            }
+        }
+
+        let deviation_after = rms(&bed_heights(&model, &carriages)?);
+        kinematics.endstop_corrections = model.endstop_corrections;
+        kinematics.tower_angle_corrections = model.tower_angle_corrections;
+
+        tracing::info!(
+            "Delta calibration: deviation {:.4}mm -> {:.4}mm, endstops {:?}, tower angles {:?}",
+            deviation_before, deviation_after,
+            model.endstop_corrections, model.tower_angle_corrections
+        );
+
+        Ok(DeltaCalibrationResult {
+            deviation_before,
+            deviation_after,
+            endstop_corrections: model.endstop_corrections,
+            tower_angle_corrections: model.tower_angle_corrections,
+        })
+    }
+
+    /// Central-difference derivative of each bed height with respect to each factor
+    fn jacobian(
+        &self,
+        model: &DeltaKinematics,
+        carriages: &[[f64; 4]],
+    ) -> Result<Vec<Vec<f64>>, Box<dyn std::error::Error>> {
+        let mut jacobian = vec![vec![0.0; CALIBRATION_FACTORS]; carriages.len()];
+        let factors = get_factors(model);
+        let mut perturbed = model.clone();
+
+        for factor in 0..CALIBRATION_FACTORS {
+            let mut shifted = factors;
+            shifted[factor] += JACOBIAN_STEP;
+            set_factors(&mut perturbed, &shifted);
+            let plus = bed_heights(&perturbed, carriages)?;
+
+            shifted[factor] -= 2.0 * JACOBIAN_STEP;
+            set_factors(&mut perturbed, &shifted);
+            let minus = bed_heights(&perturbed, carriages)?;
+
+            for (row, (p, m)) in jacobian.iter_mut().zip(plus.iter().zip(&minus)) {
+                row[factor] = (p - m) / (2.0 * JACOBIAN_STEP);
+            }
+        }
+
+        Ok(jacobian)
+    }
+}
+
+/// Bed height under each probed carriage position according to `model`
+fn bed_heights(
+    model: &DeltaKinematics,
+    carriages: &[[f64; 4]],
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    carriages
+        .iter()
+        .map(|motors| model.motors_to_cartesian(motors).map(|position| position[2]))
+        .collect()
+}
+
+fn get_factors(model: &DeltaKinematics) -> [f64; CALIBRATION_FACTORS] {
+    let e = model.endstop_corrections;
+    let a = model.tower_angle_corrections;
+    [e[0], e[1], e[2], a[0], a[1], a[2]]
+}
+
+fn set_factors(model: &mut DeltaKinematics, factors: &[f64; CALIBRATION_FACTORS]) {
+    model.endstop_corrections = [factors[0], factors[1], factors[2]];
+    model.tower_angle_corrections = [factors[3], factors[4], factors[5]];
+}
+
+fn rms(values: &[f64]) -> f64 {
+    (values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64).sqrt()
+}
+
+/// Solve min |A·x - b| for an overdetermined system using Householder QR
+///
+/// Returns None if A is rank deficient.
+pub(crate) fn solve_least_squares(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let rows = a.len();
+    let cols = a.first()?.len();
+    if rows < cols || b.len() != rows {
+        return None;
+    }
+
+    for k in 0..cols {
+        // Reflect column k onto the k-th axis
+        let column_norm = (k..rows).map(|i| a[i][k] * a[i][k]).sum::<f64>().sqrt();
+        if column_norm < 1e-15 {
+            return None;
+        }
+        let alpha = if a[k][k] > 0.0 { -column_norm } else { column_norm };
+
+        let mut v: Vec<f64> = (k..rows).map(|i| a[i][k]).collect();
+        v[0] -= alpha;
+        let v_norm_squared: f64 = v.iter().map(|x| x * x).sum();
+        if v_norm_squared < 1e-30 {
+            continue;
+        }
+
+        for j in k..cols {
+            let projection: f64 = a[k..].iter().zip(&v).map(|(row, vi)| vi * row[j]).sum::<f64>() * 2.0 / v_norm_squared;
+            for (row, vi) in a[k..].iter_mut().zip(&v) {
+                row[j] -= projection * vi;
+            }
+        }
+        let projection: f64 = b[k..].iter().zip(&v).map(|(bi, vi)| vi * bi).sum::<f64>() * 2.0 / v_norm_squared;
+        for (bi, vi) in b[k..].iter_mut().zip(&v) {
+            *bi -= projection * vi;
+        }
+    }
+
+    // Back substitution on the upper triangular R
+    let mut x = vec![0.0; cols];
+    for k in (0..cols).rev() {
+        if a[k][k].abs() < 1e-12 {
+            return None;
+        }
+        let sum: f64 = ((k + 1)..cols).map(|j| a[k][j] * x[j]).sum();
+        x[k] = (b[k] - sum) / a[k][k];
+    }
+
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Probe a flat bed at Z=0 on a delta whose real geometry is `actual`
+    /// while the firmware believes `believed`
+    fn simulate_probe(actual: &DeltaKinematics, believed: &DeltaKinematics, points: &[[f64; 2]]) -> Vec<f64> {
+        points
+            .iter()
+            .map(|point| {
+                // Lower the nozzle until it touches the bed
+                let mut z = 0.0;
+                for _ in 0..20 {
+                    let motors = believed.cartesian_to_motors(&[point[0], point[1], z]).unwrap();
+                    z -= actual.motors_to_cartesian(&motors).unwrap()[2];
+                }
+                z
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_least_squares_solver() {
+        // Fit y = 2x + 1 through exact points
+        let a = vec![vec![0.0, 1.0], vec![1.0, 1.0], vec![2.0, 1.0], vec![3.0, 1.0]];
+        let b = vec![1.0, 3.0, 5.0, 7.0];
+        let x = solve_least_squares(a, b).unwrap();
+        assert!((x[0] - 2.0).abs() < 1e-12 && (x[1] - 1.0).abs() < 1e-12);
+
+        // Rank deficient
+        let a = vec![vec![1.0, 2.0], vec![2.0, 4.0], vec![3.0, 6.0]];
+        assert!(solve_least_squares(a, vec![1.0, 2.0, 3.0]).is_none());
+    }
+
+    #[test]
+    fn test_delta_calibration_converges() {
+        let mut actual = DeltaKinematics::new(250.0, 120.0, 100.0);
+        actual.endstop_corrections = [0.8, -0.5, 0.3];
+        actual.tower_angle_corrections = [0.4, -0.3, 0.25];
+
+        let mut believed = DeltaKinematics::new(250.0, 120.0, 100.0);
+        let calibrator = DeltaCalibrator::new(13, 90.0).unwrap();
+        assert_eq!(calibrator.probe_points().len(), 13);
+
+        let initial = simulate_probe(&actual, &believed, calibrator.probe_points());
+        assert!(initial.iter().any(|h| h.abs() > 0.1));
+
+        for _ in 0..5 {
+            let heights = simulate_probe(&actual, &believed, calibrator.probe_points());
+            calibrator.calibrate(&mut believed, &heights).unwrap();
+        }
+
+        // Check on a denser grid than was probed
+        let check = DeltaCalibrator::new(37, 95.0).unwrap();
+        let remaining = simulate_probe(&actual, &believed, check.probe_points());
+        let max_error = remaining.iter().fold(0.0f64, |max, h| max.max(h.abs()));
+        assert!(max_error < 0.01, "max error after calibration: {:.4}mm", max_error);
+    }
+
+    #[test]
+    fn test_delta_calibration_input_validation() {
+        assert!(DeltaCalibrator::new(6, 90.0).is_err());
+
+        let calibrator = DeltaCalibrator::new(7, 90.0).unwrap();
+        let mut kinematics = DeltaKinematics::new(250.0, 120.0, 100.0);
+        assert!(calibrator.calibrate(&mut kinematics, &[0.0; 3]).is_err());
+
+        // An already calibrated printer stays put
+        let result = calibrator.calibrate(&mut kinematics, &[0.0; 7]).unwrap();
+        assert!(result.deviation_before < 1e-12 && result.deviation_after < 1e-12);
+        assert!(result.endstop_corrections.iter().all(|e| e.abs() < 1e-9));
+    }
+}
@@ -0,0 +1,177 @@
+// src/motion/drift.rs - Stepper position checks against motor encoders
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use crate::config::Config;
+use crate::hardware::{HardwareError, HardwareManager};
+use super::stepper::StepGenerator;
+
+/// Time between encoder reads
+const DRIFT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// An encoder on one motor
+#[derive(Debug, Clone)]
+struct MotorEncoder {
+    axis: usize,
+    pin: String,
+    /// Motor steps per encoder count
+    steps_per_count: f64,
+}
+
+/// Reads the encoders on the X-Z motors and hands the positions to the
+/// step generator, which reconciles or reports any drift
+#[derive(Debug, Clone)]
+pub struct PositionDriftMonitor {
+    hardware: HardwareManager,
+    step_generator: Arc<Mutex<StepGenerator>>,
+    encoders: Vec<MotorEncoder>,
+}
+
+impl PositionDriftMonitor {
+    /// Monitor for the steppers in `config`, `None` if none has an encoder
+    pub fn new(
+        hardware: HardwareManager,
+        config: &Config,
+        step_generator: Arc<Mutex<StepGenerator>>,
+    ) -> Option<Self> {
+        let encoders: Vec<MotorEncoder> = (0..3)
+            .filter_map(|axis| {
+                let stepper = config.axis_stepper(axis)?;
+                let encoder = stepper.encoder.as_ref()?;
+                let steps_per_rev = (stepper.full_steps_per_rotation * stepper.microsteps) as f64;
+                Some(MotorEncoder {
+                    axis,
+                    pin: encoder.pin.clone(),
+                    steps_per_count: steps_per_rev / encoder.counts_per_rev.max(1) as f64,
+                })
+            })
+            .collect();
+        (!encoders.is_empty()).then_some(Self { hardware, step_generator, encoders })
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(self) {
+        let mut interval = tokio::time::interval(DRIFT_POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.poll().await {
+                tracing::warn!("Could not read stepper encoders: {}", e);
+            }
+        }
+    }
+
+    /// Read every encoder and report the positions to the step generator
+    pub async fn poll(&self) -> Result<(), HardwareError> {
+        let pins: Vec<String> = self.encoders.iter().map(|encoder| encoder.pin.clone()).collect();
+        let counts = self.hardware.read_encoders(&pins).await?;
+        let mut step_generator = self.step_generator.lock().unwrap();
+        for (encoder, count) in self.encoders.iter().zip(counts) {
+            let steps = (count as f64 * encoder.steps_per_count).round() as i64;
+            step_generator.report_encoder_steps(encoder.axis, steps);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use tokio::sync::RwLock;
+    use crate::config::StepEncoder;
+    use crate::hardware::{McuPort, PortFuture};
+    use crate::motion::{MotionConfig, MotionEvent, MotionPlanner, MotionType, StepPositionDrift};
+    use crate::printer::PrinterState;
+
+    /// MCU with an X motor encoder at a settable count
+    #[derive(Debug, Default)]
+    struct EncoderPort {
+        count: AtomicI64,
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl McuPort for EncoderPort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            self.commands.lock().unwrap().push(command.to_string());
+            let response = if command.starts_with("query_encoder pin=PC0") {
+                self.count.load(Ordering::SeqCst).to_string()
+            } else {
+                "ok".to_string()
+            };
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    /// Planner and drift monitor for an X encoder at one count
+    /// per step (80 steps/mm) allowing `max_drift_steps`
+    async fn setup(max_drift_steps: u32) -> (Arc<EncoderPort>, MotionPlanner, PositionDriftMonitor) {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let stepper = config.steppers.get_mut("stepper_x").unwrap();
+        stepper.encoder = Some(StepEncoder { counts_per_rev: 3200, pin: "PC0".to_string() });
+        stepper.max_drift_steps = max_drift_steps;
+
+        let port = Arc::new(EncoderPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut planner = MotionPlanner::new(state, hardware.clone(), MotionConfig::new_from_printer_config(&config));
+        planner.set_homed([0.0; 4]).await;
+        let monitor = PositionDriftMonitor::new(hardware, &config, planner.step_generator()).unwrap();
+        // Latch the encoder origin before moving
+        monitor.poll().await.unwrap();
+        (port, planner, monitor)
+    }
+
+    /// Move X to `x` and return the X step commands sent
+    async fn move_x(port: &EncoderPort, planner: &mut MotionPlanner, x: f64) -> Vec<String> {
+        port.commands.lock().unwrap().clear();
+        planner.plan_linear_move([x, 0.0, 0.0, 0.0], 200.0, MotionType::Travel).await.unwrap();
+        while planner.queue_length() > 0 || planner.is_active() {
+            planner.update().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        port.commands.lock().unwrap().iter().filter(|c| c.starts_with("step X")).cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn test_drift_over_threshold_reported() {
+        let (port, mut planner, monitor) = setup(5).await;
+        let mut events = planner.subscribe_events();
+        // 12.5mm at 80 steps/mm
+        assert_eq!(move_x(&port, &mut planner, 12.5).await, ["step X 1000 1"]);
+
+        port.count.store(990, Ordering::SeqCst);
+        monitor.poll().await.unwrap();
+        let drift = StepPositionDrift { axis: 0, commanded: 1000, actual: 990, delta: -10 };
+        assert_eq!(planner.position_drift(), [drift]);
+
+        // Over the limit the error is reported once and nothing is corrected
+        assert_eq!(move_x(&port, &mut planner, 25.0).await, ["step X 1000 1"]);
+        assert_eq!(events.try_recv().unwrap(), MotionEvent::PositionDrift(drift));
+        monitor.poll().await.unwrap();
+        assert_eq!(move_x(&port, &mut planner, 37.5).await, ["step X 1000 1"]);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drift_within_threshold_reconciled() {
+        let (port, mut planner, monitor) = setup(10).await;
+        let mut events = planner.subscribe_events();
+        assert_eq!(move_x(&port, &mut planner, 12.5).await, ["step X 1000 1"]);
+
+        port.count.store(990, Ordering::SeqCst);
+        monitor.poll().await.unwrap();
+        // The next move makes up the 10 lost steps
+        assert_eq!(move_x(&port, &mut planner, 25.0).await, ["step X 1010 1"]);
+        assert!(events.try_recv().is_err());
+
+        port.count.store(2000, Ordering::SeqCst);
+        monitor.poll().await.unwrap();
+        let drift = StepPositionDrift { axis: 0, commanded: 2000, actual: 2000, delta: 0 };
+        assert_eq!(planner.position_drift(), [drift]);
+    }
+}
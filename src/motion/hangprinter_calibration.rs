@@ -0,0 +1,249 @@
+// src/motion/hangprinter_calibration.rs - Hangprinter anchor calibration (CALIBRATE_HANGPRINTER)
+use super::delta_calibration::solve_least_squares;
+
+/// Coordinates fitted per anchor
+const ANCHOR_COORDINATES: usize = 3;
+
+/// Keeps the step finite if the points barely constrain an anchor's distance
+const DAMPING: f64 = 1e-9;
+
+/// Anchor names in config order
+pub const ANCHOR_NAMES: [char; 4] = ['A', 'B', 'C', 'D'];
+
+/// Outcome of a calibration pass
+#[derive(Debug, Clone)]
+pub struct HangprinterCalibrationResult {
+    /// Refined anchor positions [A, B, C, D] (mm)
+    pub anchors: [[f64; 3]; 4],
+
+    /// RMS of the line length errors with the initial anchors (mm)
+    pub deviation_before: f64,
+
+    /// RMS of the line length errors with the refined anchors (mm)
+    pub deviation_after: f64,
+}
+
+impl HangprinterCalibrationResult {
+    /// `[hangprinter]` snippet to paste into the config file
+    pub fn to_toml(&self) -> String {
+        let anchors: Vec<String> = self
+            .anchors
+            .iter()
+            .zip(ANCHOR_NAMES)
+            .map(|(anchor, name)| format!("    [{:.3}, {:.3}, {:.3}], # {}", anchor[0], anchor[1], anchor[2], name))
+            .collect();
+        format!("[hangprinter]\nanchors = [\n{}\n]\n", anchors.join("\n"))
+    }
+}
+
+/// Fits anchor positions to line length changes measured between known positions
+///
+/// The first calibration point is the origin the printer was homed at; line
+/// encoders only count relative motion, so every measurement is the change
+/// in each line's length since that point.
+#[derive(Debug, Clone)]
+pub struct HangprinterCalibrator {
+    points: Vec<[f64; 3]>,
+    max_iterations: usize,
+}
+
+impl HangprinterCalibrator {
+    /// Create a calibrator visiting the origin, then a ring of `radius`
+    /// alternating between Z=0 and Z=`height`
+    pub fn new(point_count: usize, radius: f64, height: f64) -> Result<Self, Box<dyn std::error::Error>> {
+        if point_count <= ANCHOR_COORDINATES + 1 {
+            return Err(format!(
+                "Hangprinter calibration needs at least {} points, got {}",
+                ANCHOR_COORDINATES + 2, point_count
+            ).into());
+        }
+        if radius <= 0.0 || height <= 0.0 {
+            return Err("Hangprinter calibration radius and height must be positive".into());
+        }
+
+        let ring_points = point_count - 1;
+        let mut points = vec![[0.0; 3]];
+        for i in 0..ring_points {
+            let angle = std::f64::consts::TAU * i as f64 / ring_points as f64;
+            let z = if i % 2 == 0 { 0.0 } else { height };
+            points.push([radius * angle.cos(), radius * angle.sin(), z]);
+        }
+
+        Ok(Self {
+            points,
+            max_iterations: 50,
+        })
+    }
+
+    /// Positions to measure at, in order, starting with the origin
+    pub fn points(&self) -> &[[f64; 3]] {
+        &self.points
+    }
+
+    /// Fit the anchors to the line length changes at each point, starting
+    /// from `anchors`
+    ///
+    /// `length_changes[k][i]` is how much line `i` lengthened moving from
+    /// the origin to point `k`; the first row is therefore all zeros.
+    pub fn calibrate(
+        &self,
+        anchors: [[f64; 3]; 4],
+        length_changes: &[[f64; 4]],
+    ) -> Result<HangprinterCalibrationResult, Box<dyn std::error::Error>> {
+        if length_changes.len() != self.points.len() {
+            return Err(format!(
+                "Expected {} line length measurements, got {}",
+                self.points.len(), length_changes.len()
+            ).into());
+        }
+
+        let mut refined = anchors;
+        let mut residuals_before = Vec::new();
+        let mut residuals_after = Vec::new();
+        for (line, anchor) in refined.iter_mut().enumerate() {
+            let measured: Vec<f64> = length_changes.iter().map(|changes| changes[line]).collect();
+            residuals_before.extend(self.residuals(anchor, &measured));
+
+            for _ in 0..self.max_iterations {
+                let residuals = self.residuals(anchor, &measured);
+
+                // Solve the damped system [J; √λ·I]·Δ = [-r; 0]
+                let mut a = self.jacobian(anchor);
+                let mut b: Vec<f64> = residuals.iter().map(|r| -r).collect();
+                for coordinate in 0..ANCHOR_COORDINATES {
+                    let mut row = vec![0.0; ANCHOR_COORDINATES];
+                    row[coordinate] = DAMPING.sqrt();
+                    a.push(row);
+                    b.push(0.0);
+                }
+                let step = solve_least_squares(a, b)
+                    .ok_or_else(|| format!("Anchor {} is not constrained by the calibration points", ANCHOR_NAMES[line]))?;
+
+                for (coordinate, delta) in anchor.iter_mut().zip(&step) {
+                    *coordinate += delta;
+                }
+                if step.iter().all(|delta| delta.abs() < 1e-9) {
+                    break;
+                }
+            }
+            residuals_after.extend(self.residuals(anchor, &measured));
+        }
+
+        let deviation_before = rms(&residuals_before);
+        let deviation_after = rms(&residuals_after);
+        tracing::info!(
+            "Hangprinter calibration: deviation {:.4}mm -> {:.4}mm, anchors {:?}",
+            deviation_before, deviation_after, refined
+        );
+
+        Ok(HangprinterCalibrationResult {
+            anchors: refined,
+            deviation_before,
+            deviation_after,
+        })
+    }
+
+    /// Predicted minus measured length change at each point
+    fn residuals(&self, anchor: &[f64; 3], measured: &[f64]) -> Vec<f64> {
+        let origin_length = distance(anchor, &self.points[0]);
+        self.points
+            .iter()
+            .zip(measured)
+            .map(|(point, change)| distance(anchor, point) - origin_length - change)
+            .collect()
+    }
+
+    /// Derivative of each residual with respect to the anchor coordinates
+    fn jacobian(&self, anchor: &[f64; 3]) -> Vec<Vec<f64>> {
+        let origin = &self.points[0];
+        let origin_length = distance(anchor, origin);
+        self.points
+            .iter()
+            .map(|point| {
+                let length = distance(anchor, point);
+                (0..ANCHOR_COORDINATES)
+                    .map(|i| (anchor[i] - point[i]) / length - (anchor[i] - origin[i]) / origin_length)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f64>().sqrt()
+}
+
+fn rms(values: &[f64]) -> f64 {
+    (values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANCHORS: [[f64; 3]; 4] = [
+        [0.0, -1600.0, -120.0],
+        [1400.0, 800.0, -110.0],
+        [-1400.0, 800.0, -130.0],
+        [0.0, 0.0, 2300.0],
+    ];
+
+    /// Line length changes an encoder would report on a printer with
+    /// `anchors`, with up to `noise` mm of deterministic jitter
+    fn simulate_measurements(anchors: &[[f64; 3]; 4], points: &[[f64; 3]], noise: f64) -> Vec<[f64; 4]> {
+        points
+            .iter()
+            .enumerate()
+            .map(|(k, point)| {
+                let mut changes = [0.0; 4];
+                for (line, anchor) in anchors.iter().enumerate() {
+                    let jitter = if k == 0 { 0.0 } else { noise * ((k * 7 + line * 13) as f64).sin() };
+                    changes[line] = distance(anchor, point) - distance(anchor, &points[0]) + jitter;
+                }
+                changes
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hangprinter_calibration_converges() {
+        let calibrator = HangprinterCalibrator::new(21, 400.0, 500.0).unwrap();
+        assert_eq!(calibrator.points().len(), 21);
+        assert_eq!(calibrator.points()[0], [0.0; 3]);
+
+        let measurements = simulate_measurements(&ANCHORS, calibrator.points(), 0.01);
+
+        // Anchors as measured with a tape
+        let mut initial = ANCHORS;
+        for (anchor, offset) in initial.iter_mut().zip([[15.0, -20.0, 10.0], [-25.0, 10.0, 5.0], [20.0, 15.0, -10.0], [10.0, -10.0, 30.0]]) {
+            for (coordinate, delta) in anchor.iter_mut().zip(offset) {
+                *coordinate += delta;
+            }
+        }
+
+        let result = calibrator.calibrate(initial, &measurements).unwrap();
+        assert!(result.deviation_after < result.deviation_before);
+        for (fitted, actual) in result.anchors.iter().zip(&ANCHORS) {
+            let error = distance(fitted, actual);
+            assert!(error < 1.0, "anchor off by {:.3}mm: {:?} vs {:?}", error, fitted, actual);
+        }
+
+        let snippet = result.to_toml();
+        let parsed: toml::Table = toml::from_str(&snippet).unwrap();
+        assert_eq!(parsed["hangprinter"]["anchors"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_hangprinter_calibration_input_validation() {
+        assert!(HangprinterCalibrator::new(4, 300.0, 200.0).is_err());
+        assert!(HangprinterCalibrator::new(9, 0.0, 200.0).is_err());
+
+        let calibrator = HangprinterCalibrator::new(5, 300.0, 200.0).unwrap();
+        assert!(calibrator.calibrate(ANCHORS, &[[0.0; 4]; 3]).is_err());
+
+        // Exact anchors stay put
+        let measurements = simulate_measurements(&ANCHORS, calibrator.points(), 0.0);
+        let result = calibrator.calibrate(ANCHORS, &measurements).unwrap();
+        assert!(result.deviation_before < 1e-9 && result.deviation_after < 1e-9);
+    }
+}
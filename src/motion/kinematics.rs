@@ -1,4 +1,11 @@
 // src/motion/kinematics.rs
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::config::{Config, DeltaConfig, ScaraConfig};
+
+/// Skew angles below this are treated as square (radians)
+const NEGLIGIBLE_SKEW: f64 = 0.0001;
+
 /// Different types of printer kinematics
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum KinematicsType {
@@ -6,10 +13,70 @@ pub enum KinematicsType {
     CoreXY,
     Delta,
     Hangprinter,
+    Scara,
+}
+
+impl FromStr for KinematicsType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cartesian" => Ok(KinematicsType::Cartesian),
+            "corexy" => Ok(KinematicsType::CoreXY),
+            "delta" => Ok(KinematicsType::Delta),
+            "hangprinter" => Ok(KinematicsType::Hangprinter),
+            "scara" => Ok(KinematicsType::Scara),
+            other => Err(format!("Unknown kinematics type: {}", other)),
+        }
+    }
+}
+
+/// Correction for a frame whose axes are not square (radians)
+///
+/// Positive `xy` means the Y axis leans toward +X; `xz` and `yz` mean Z
+/// leans toward +X and +Y.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub struct SkewCorrection {
+    pub xy: f64,
+    pub xz: f64,
+    pub yz: f64,
+}
+
+impl SkewCorrection {
+    /// XY skew from a printed calibration square
+    ///
+    /// `ac` runs from the (-X, -Y) corner to the (+X, +Y) corner, `bd` from
+    /// (+X, -Y) to (-X, +Y). A sheared square satisfies
+    /// AC² - BD² = 4 · side² · tan(skew).
+    pub fn from_diagonals(ac: f64, bd: f64, side: f64) -> Self {
+        let difference = ac * ac - bd * bd;
+        Self {
+            xy: (difference / (4.0 * side * side)).atan(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether every angle is too small to matter
+    pub fn is_negligible(&self) -> bool {
+        [self.xy, self.xz, self.yz].iter().all(|angle| angle.abs() < NEGLIGIBLE_SKEW)
+    }
+
+    /// Position to command so the skewed frame reaches `cartesian`
+    pub fn correct(&self, cartesian: &[f64; 3]) -> [f64; 3] {
+        let [x, y, z] = *cartesian;
+        [x - y * self.xy.tan() - z * self.xz.tan(), y - z * self.yz.tan(), z]
+    }
+
+    /// Where the skewed frame ends up when `commanded` is sent; undoes `correct`
+    pub fn uncorrect(&self, commanded: &[f64; 3]) -> [f64; 3] {
+        let z = commanded[2];
+        let y = commanded[1] + z * self.yz.tan();
+        [commanded[0] + y * self.xy.tan() + z * self.xz.tan(), y, z]
+    }
 }
 
 /// Kinematics handler for different printer types
-pub trait Kinematics {
+pub trait Kinematics: std::fmt::Debug + Send + Sync {
     /// Convert Cartesian coordinates to motor positions
     fn cartesian_to_motors(&self, cartesian: &[f64; 3]) -> Result<[f64; 4], Box<dyn std::error::Error>>;
     
@@ -18,60 +85,99 @@ pub trait Kinematics {
     
     /// Check if position is valid for this kinematics
     fn is_valid_position(&self, cartesian: &[f64; 3]) -> bool;
+    
+    /// Fraction of run current for the first two motors while moving
+    /// between motor positions `start` and `end`, `None` to leave it alone
+    fn motor_current_scale(&self, _start: &[f64; 4], _end: &[f64; 4]) -> Option<[f64; 2]> {
+        None
+    }
 }
 
 /// Cartesian kinematics (most common 3D printer type)
+#[derive(Debug, Clone)]
 pub struct CartesianKinematics {
     /// Limits for each axis
     limits: [[f64; 2]; 3], // [min, max] for X, Y, Z
+    
+    skew: SkewCorrection,
 }
 
 impl CartesianKinematics {
     pub fn new(limits: [[f64; 2]; 3]) -> Self {
-        Self { limits }
+        Self { limits, skew: SkewCorrection::default() }
+    }
+
+    pub fn set_skew(&mut self, skew: SkewCorrection) {
+        self.skew = skew;
     }
 }
 
 impl Kinematics for CartesianKinematics {
     fn cartesian_to_motors(&self, cartesian: &[f64; 3]) -> Result<[f64; 4], Box<dyn std::error::Error>> {
-        // For Cartesian, motors directly correspond to axes
+        // For Cartesian, motors directly correspond to the deskewed axes
         // [X, Y, Z, E]
-        Ok([cartesian[0], cartesian[1], cartesian[2], 0.0])
+        let [x, y, z] = self.skew.correct(cartesian);
+        Ok([x, y, z, 0.0])
     }
     
     fn motors_to_cartesian(&self, motors: &[f64; 4]) -> Result<[f64; 3], Box<dyn std::error::Error>> {
-        Ok([motors[0], motors[1], motors[2]])
+        Ok(self.skew.uncorrect(&[motors[0], motors[1], motors[2]]))
     }
     
     fn is_valid_position(&self, cartesian: &[f64; 3]) -> bool {
-        for i in 0..3 {
-            if cartesian[i] < self.limits[i][0] || cartesian[i] > self.limits[i][1] {
-                return false;
-            }
-        }
-        true
+        within_limits(&self.limits, cartesian)
     }
 }
 
+/// Check a Cartesian position against [min, max] limits for each axis
+fn within_limits(limits: &[[f64; 2]; 3], cartesian: &[f64; 3]) -> bool {
+    limits
+        .iter()
+        .zip(cartesian.iter())
+        .all(|(limit, &value)| value >= limit[0] && value <= limit[1])
+}
+
 /// CoreXY kinematics
+#[derive(Debug, Clone)]
 pub struct CoreXYKinematics {
     limits: [[f64; 2]; 3],
+    
+    skew: SkewCorrection,
+    
+    /// How far the current of a motor that doesn't move drops (0 disables)
+    motor_current_balance: f64,
 }
 
 impl CoreXYKinematics {
     pub fn new(limits: [[f64; 2]; 3]) -> Self {
-        Self { limits }
+        Self {
+            limits,
+            skew: SkewCorrection::default(),
+            motor_current_balance: 0.0,
+        }
+    }
+
+    pub fn set_skew(&mut self, skew: SkewCorrection) {
+        self.skew = skew;
+    }
+
+    /// Balance motor currents by `balance` (0.0 - 1.0) on XY moves
+    pub fn set_motor_current_balance(&mut self, balance: f64) {
+        self.motor_current_balance = balance.clamp(0.0, 1.0);
     }
 }
 
 impl Kinematics for CoreXYKinematics {
     fn cartesian_to_motors(&self, cartesian: &[f64; 3]) -> Result<[f64; 4], Box<dyn std::error::Error>> {
+        // Remove the frame skew before mapping to belts
+        let [x, y, _] = self.skew.correct(cartesian);
+        
         // CoreXY kinematics:
         // Motor A = X + Y
         // Motor B = X - Y
         // Motor C = Z
-        let motor_a = cartesian[0] + cartesian[1];
-        let motor_b = cartesian[0] - cartesian[1];
+        let motor_a = x + y;
+        let motor_b = x - y;
         let motor_c = cartesian[2];
         
         Ok([motor_a, motor_b, motor_c, 0.0])
@@ -84,30 +190,512 @@ impl Kinematics for CoreXYKinematics {
         // Z = C
         let x = (motors[0] + motors[1]) / 2.0;
         let y = (motors[0] - motors[1]) / 2.0;
-        let z = motors[2];
         
-        Ok([x, y, z])
+        Ok(self.skew.uncorrect(&[x, y, motors[2]]))
+    }
+    
+    fn is_valid_position(&self, cartesian: &[f64; 3]) -> bool {
+        within_limits(&self.limits, cartesian)
+    }
+    
+    fn motor_current_scale(&self, start: &[f64; 4], end: &[f64; 4]) -> Option<[f64; 2]> {
+        // Pure X or Y moves turn both belts equally; a 45° diagonal turns
+        // only one, so the other holds at reduced current
+        let travel = [(end[0] - start[0]).abs(), (end[1] - start[1]).abs()];
+        let busiest = travel[0].max(travel[1]);
+        if self.motor_current_balance == 0.0 || busiest == 0.0 {
+            return None;
+        }
+        Some(travel.map(|motor| 1.0 - self.motor_current_balance * (1.0 - motor / busiest)))
+    }
+}
+
+/// SCARA kinematics (two-link planar arm with a linear Z axis)
+///
+/// Motor positions are the shoulder and elbow joint angles in degrees,
+/// followed by Z in mm: [theta1, theta2, Z, E]
+#[derive(Debug, Clone)]
+pub struct ScaraKinematics {
+    /// Shoulder to elbow length (mm)
+    pub arm1_length: f64,
+    
+    /// Elbow to nozzle length (mm)
+    pub arm2_length: f64,
+    
+    /// Steps per degree of shoulder rotation
+    pub arm1_steps_per_deg: f64,
+    
+    /// Steps per degree of elbow rotation
+    pub arm2_steps_per_deg: f64,
+    
+    /// [min, max] for Z; the arm reach bounds X and Y
+    z_limits: [f64; 2],
+}
+
+impl ScaraKinematics {
+    pub fn new(arm1_length: f64, arm2_length: f64, arm1_steps_per_deg: f64, arm2_steps_per_deg: f64) -> Self {
+        Self {
+            arm1_length,
+            arm2_length,
+            arm1_steps_per_deg,
+            arm2_steps_per_deg,
+            z_limits: [f64::NEG_INFINITY, f64::INFINITY],
+        }
+    }
+
+    pub fn set_z_limits(&mut self, z_limits: [f64; 2]) {
+        self.z_limits = z_limits;
+    }
+
+    /// Arm geometry and joint step rates from `config`; the step rates are
+    /// the X and Y motors' in `MotionConfig::steps_per_mm`
+    pub fn from_config(config: &ScaraConfig) -> Self {
+        Self::new(
+            config.arm1_length,
+            config.arm2_length,
+            config.arm1_steps_per_deg,
+            config.arm2_steps_per_deg,
+        )
+    }
+
+    /// Inner and outer radius of the reachable annulus (mm)
+    pub fn reach(&self) -> (f64, f64) {
+        (
+            (self.arm1_length - self.arm2_length).abs(),
+            self.arm1_length + self.arm2_length,
+        )
+    }
+}
+
+impl Kinematics for ScaraKinematics {
+    fn cartesian_to_motors(&self, cartesian: &[f64; 3]) -> Result<[f64; 4], Box<dyn std::error::Error>> {
+        // Two-link inverse kinematics:
+        // cos(theta2) = (r² - L1² - L2²) / (2 * L1 * L2)
+        // theta1 = atan2(y, x) - atan2(L2 * sin(theta2), L1 + L2 * cos(theta2))
+        let (x, y) = (cartesian[0], cartesian[1]);
+        let (l1, l2) = (self.arm1_length, self.arm2_length);
+        let r_squared = x * x + y * y;
+        
+        let cos_theta2 = (r_squared - l1 * l1 - l2 * l2) / (2.0 * l1 * l2);
+        if !(-1.0 - 1e-9..=1.0 + 1e-9).contains(&cos_theta2) {
+            return Err(format!("Position ({:.3}, {:.3}) is outside SCARA reach", x, y).into());
+        }
+        
+        // With equal arm lengths the origin can be reached at any shoulder angle
+        if r_squared.sqrt() < 1e-9 {
+            return Err("SCARA singularity: shoulder angle undefined at the origin".into());
+        }
+        
+        let theta2 = cos_theta2.clamp(-1.0, 1.0).acos();
+        let theta1 = y.atan2(x) - (l2 * theta2.sin()).atan2(l1 + l2 * theta2.cos());
+        
+        Ok([theta1.to_degrees(), theta2.to_degrees(), cartesian[2], 0.0])
+    }
+    
+    fn motors_to_cartesian(&self, motors: &[f64; 4]) -> Result<[f64; 3], Box<dyn std::error::Error>> {
+        // Forward kinematics:
+        // X = L1 * cos(theta1) + L2 * cos(theta1 + theta2)
+        // Y = L1 * sin(theta1) + L2 * sin(theta1 + theta2)
+        let theta1 = motors[0].to_radians();
+        let theta2 = motors[1].to_radians();
+        
+        let x = self.arm1_length * theta1.cos() + self.arm2_length * (theta1 + theta2).cos();
+        let y = self.arm1_length * theta1.sin() + self.arm2_length * (theta1 + theta2).sin();
+        
+        Ok([x, y, motors[2]])
     }
     
     fn is_valid_position(&self, cartesian: &[f64; 3]) -> bool {
-        for i in 0..3 {
-            if cartesian[i] < self.limits[i][0] || cartesian[i] > self.limits[i][1] {
-                return false;
+        let (inner, outer) = self.reach();
+        let r = (cartesian[0] * cartesian[0] + cartesian[1] * cartesian[1]).sqrt();
+        r >= inner && r <= outer && r > 0.0
+            && cartesian[2] >= self.z_limits[0] && cartesian[2] <= self.z_limits[1]
+    }
+}
+
+/// Nominal tower angles for a delta printer (degrees): X, Y, Z towers
+pub const DELTA_TOWER_ANGLES: [f64; 3] = [210.0, 330.0, 90.0];
+
+/// Linear delta kinematics (three vertical towers with diagonal rods)
+///
+/// Motor positions are the carriage heights of the X, Y and Z towers in mm,
+/// measured in the same frame as Z: [A, B, C, E]
+#[derive(Debug, Clone)]
+pub struct DeltaKinematics {
+    /// Diagonal rod length (mm)
+    pub diagonal_rod: f64,
+    
+    /// Horizontal distance from the center to each tower (mm)
+    pub radius: f64,
+    
+    /// Radius of the printable area (mm)
+    pub print_radius: f64,
+    
+    /// Per-tower endstop height corrections (mm)
+    pub endstop_corrections: [f64; 3],
+    
+    /// Per-tower angle corrections relative to the nominal angles (degrees)
+    pub tower_angle_corrections: [f64; 3],
+}
+
+impl DeltaKinematics {
+    pub fn new(diagonal_rod: f64, radius: f64, print_radius: f64) -> Self {
+        Self {
+            diagonal_rod,
+            radius,
+            print_radius,
+            endstop_corrections: [0.0; 3],
+            tower_angle_corrections: [0.0; 3],
+        }
+    }
+
+    pub fn from_config(config: &DeltaConfig) -> Self {
+        Self {
+            diagonal_rod: config.diagonal_rod,
+            radius: config.radius,
+            print_radius: config.print_radius,
+            endstop_corrections: config.endstop_corrections,
+            tower_angle_corrections: config.tower_angle_corrections,
+        }
+    }
+
+    /// XY position of each tower, including angle corrections
+    pub fn tower_positions(&self) -> [[f64; 2]; 3] {
+        let mut towers = [[0.0; 2]; 3];
+        for (tower, (nominal, correction)) in towers
+            .iter_mut()
+            .zip(DELTA_TOWER_ANGLES.iter().zip(self.tower_angle_corrections.iter()))
+        {
+            let angle = (nominal + correction).to_radians();
+            *tower = [self.radius * angle.cos(), self.radius * angle.sin()];
+        }
+        towers
+    }
+}
+
+impl Kinematics for DeltaKinematics {
+    fn cartesian_to_motors(&self, cartesian: &[f64; 3]) -> Result<[f64; 4], Box<dyn std::error::Error>> {
+        // Each carriage sits one rod length away from the effector:
+        // A = Z + sqrt(L² - (X - Xa)² - (Y - Ya)²) + endstop correction
+        let rod_squared = self.diagonal_rod * self.diagonal_rod;
+        let mut motors = [0.0; 4];
+        
+        for (i, tower) in self.tower_positions().iter().enumerate() {
+            let dx = cartesian[0] - tower[0];
+            let dy = cartesian[1] - tower[1];
+            let height_squared = rod_squared - dx * dx - dy * dy;
+            if height_squared <= 0.0 {
+                return Err(format!(
+                    "Position ({:.3}, {:.3}) is out of reach of delta tower {}",
+                    cartesian[0], cartesian[1], i
+                ).into());
             }
+            motors[i] = cartesian[2] + height_squared.sqrt() + self.endstop_corrections[i];
+        }
+        
+        Ok(motors)
+    }
+    
+    fn motors_to_cartesian(&self, motors: &[f64; 4]) -> Result<[f64; 3], Box<dyn std::error::Error>> {
+        // Trilateration: the effector is the lower intersection of three spheres
+        // of rod length centered on the carriages
+        let towers = self.tower_positions();
+        let carriage = |i: usize| -> [f64; 3] {
+            [towers[i][0], towers[i][1], motors[i] - self.endstop_corrections[i]]
+        };
+        let (p1, p2, p3) = (carriage(0), carriage(1), carriage(2));
+        
+        let p12 = sub(&p2, &p1);
+        let d = norm(&p12);
+        let ex = scale(&p12, 1.0 / d);
+        let p13 = sub(&p3, &p1);
+        let i = dot(&ex, &p13);
+        let ey_raw = sub(&p13, &scale(&ex, i));
+        let ey = scale(&ey_raw, 1.0 / norm(&ey_raw));
+        let ez = cross(&ex, &ey);
+        let j = dot(&ey, &p13);
+        
+        // Equal sphere radii simplify the general trilateration formulas
+        let x = d / 2.0;
+        let y = (i * i + j * j - 2.0 * i * x) / (2.0 * j);
+        let z_squared = self.diagonal_rod * self.diagonal_rod - x * x - y * y;
+        if z_squared < 0.0 {
+            return Err("Delta carriage positions do not intersect".into());
         }
-        true
+        
+        let base = [
+            p1[0] + x * ex[0] + y * ey[0],
+            p1[1] + x * ex[1] + y * ey[1],
+            p1[2] + x * ex[2] + y * ey[2],
+        ];
+        let offset = scale(&ez, z_squared.sqrt());
+        let above = [base[0] + offset[0], base[1] + offset[1], base[2] + offset[2]];
+        let below = sub(&base, &offset);
+        
+        Ok(if below[2] < above[2] { below } else { above })
+    }
+    
+    fn is_valid_position(&self, cartesian: &[f64; 3]) -> bool {
+        let r = (cartesian[0] * cartesian[0] + cartesian[1] * cartesian[1]).sqrt();
+        r <= self.print_radius && cartesian[2] >= 0.0
     }
 }
 
-/// Factory for creating kinematics handlers
+fn sub(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: &[f64; 3], factor: f64) -> [f64; 3] {
+    [a[0] * factor, a[1] * factor, a[2] * factor]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn norm(a: &[f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Create a kinematics handler that needs nothing beyond axis limits
+///
+/// SCARA and delta geometry comes from their config sections, see
+/// `create_kinematics_of_type`.
 pub fn create_kinematics(
     kinematics_type: KinematicsType,
     limits: [[f64; 2]; 3],
-) -> Box<dyn Kinematics> {
+) -> Result<Box<dyn Kinematics>, Box<dyn std::error::Error>> {
     match kinematics_type {
-        KinematicsType::Cartesian => Box::new(CartesianKinematics::new(limits)),
-        KinematicsType::CoreXY => Box::new(CoreXYKinematics::new(limits)),
-        // Add other kinematics types as needed
-        _ => Box::new(CartesianKinematics::new(limits)), // fallback
+        KinematicsType::Cartesian => Ok(Box::new(CartesianKinematics::new(limits))),
+        KinematicsType::CoreXY => Ok(Box::new(CoreXYKinematics::new(limits))),
+        KinematicsType::Hangprinter => Err("Hangprinter kinematics are not supported".into()),
+        other => Err(format!("{:?} kinematics need their config section", other).into()),
+    }
+}
+
+/// Create the kinematics handler described by the printer configuration
+pub fn create_kinematics_from_config(
+    config: &Config,
+    limits: [[f64; 2]; 3],
+) -> Result<Box<dyn Kinematics>, Box<dyn std::error::Error>> {
+    create_kinematics_of_type(config.printer.kinematics.parse()?, config, limits)
+}
+
+/// Create a `kinematics_type` handler, reading geometry beyond axis limits
+/// (SCARA, delta) from its section of `config`
+pub fn create_kinematics_of_type(
+    kinematics_type: KinematicsType,
+    config: &Config,
+    limits: [[f64; 2]; 3],
+) -> Result<Box<dyn Kinematics>, Box<dyn std::error::Error>> {
+    match kinematics_type {
+        KinematicsType::Scara => {
+            let scara = config.scara.as_ref()
+                .ok_or("SCARA kinematics requires a [scara] config section")?;
+            let mut kinematics = ScaraKinematics::from_config(scara);
+            kinematics.set_z_limits(limits[2]);
+            Ok(Box::new(kinematics))
+        }
+        KinematicsType::Delta => {
+            let delta = config.delta.as_ref()
+                .ok_or("Delta kinematics requires a [delta] config section")?;
+            Ok(Box::new(DeltaKinematics::from_config(delta)))
+        }
+        other => create_kinematics(other, limits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scara_round_trip_grid() {
+        let scara = ScaraKinematics::new(150.0, 120.0, 10.0, 10.0);
+        let (inner, outer) = scara.reach();
+        
+        let mut checked = 0;
+        for xi in -27..=27 {
+            for yi in -27..=27 {
+                let target = [xi as f64 * 10.0, yi as f64 * 10.0, 5.0];
+                let r = (target[0] * target[0] + target[1] * target[1]).sqrt();
+                if r <= inner + 1.0 || r >= outer - 1.0 {
+                    continue;
+                }
+                
+                assert!(scara.is_valid_position(&target));
+                let motors = scara.cartesian_to_motors(&target).unwrap();
+                let result = scara.motors_to_cartesian(&motors).unwrap();
+                for axis in 0..3 {
+                    assert!((result[axis] - target[axis]).abs() < 1e-9, "{:?} -> {:?}", target, result);
+                }
+                checked += 1;
+            }
+        }
+        assert!(checked > 100);
+    }
+
+    #[test]
+    fn test_scara_unreachable_positions() {
+        let scara = ScaraKinematics::new(150.0, 120.0, 10.0, 10.0);
+        
+        // Beyond full extension
+        assert!(!scara.is_valid_position(&[300.0, 0.0, 0.0]));
+        assert!(scara.cartesian_to_motors(&[300.0, 0.0, 0.0]).is_err());
+        
+        // Inside the dead zone around the shoulder
+        assert!(!scara.is_valid_position(&[10.0, 10.0, 0.0]));
+        assert!(scara.cartesian_to_motors(&[10.0, 10.0, 0.0]).is_err());
+        
+        // Equal arms reach the origin, but the shoulder angle is undefined
+        let equal = ScaraKinematics::new(100.0, 100.0, 10.0, 10.0);
+        assert!(!equal.is_valid_position(&[0.0, 0.0, 0.0]));
+        assert!(equal.cartesian_to_motors(&[0.0, 0.0, 0.0]).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_scara_from_config() {
+        let config: Config = toml::from_str(r#"
+            [printer]
+            kinematics = "scara"
+
+            [scara]
+            arm1_length = 150.0
+            arm2_length = 120.0
+        "#).unwrap();
+        
+        let limits = [[-270.0, 270.0], [-270.0, 270.0], [0.0, 200.0]];
+        let kinematics = create_kinematics_from_config(&config, limits).unwrap();
+        let motors = kinematics.cartesian_to_motors(&[270.0, 0.0, 0.0]).unwrap();
+        assert!(motors[0].abs() < 1e-6 && motors[1].abs() < 1e-6);
+        
+        // Z is held to the configured limits like on Cartesian printers
+        assert!(kinematics.is_valid_position(&[200.0, 0.0, 200.0]));
+        assert!(!kinematics.is_valid_position(&[200.0, 0.0, 200.5]));
+        assert!(!kinematics.is_valid_position(&[200.0, 0.0, -1.0]));
+        
+        // The arm geometry is required
+        let config: Config = toml::from_str("[printer]\nkinematics = \"scara\"").unwrap();
+        assert!(create_kinematics_from_config(&config, limits).is_err());
+        
+        assert_eq!("SCARA".parse::<KinematicsType>(), Ok(KinematicsType::Scara));
+        assert!("polar".parse::<KinematicsType>().is_err());
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let mut delta = DeltaKinematics::new(250.0, 120.0, 100.0);
+        delta.endstop_corrections = [0.3, -0.2, 0.1];
+        delta.tower_angle_corrections = [0.5, -0.4, 0.2];
+        
+        for target in [[0.0, 0.0, 0.0], [50.0, -30.0, 10.0], [-70.0, 60.0, 100.0], [0.0, 95.0, 2.5]] {
+            assert!(delta.is_valid_position(&target));
+            let motors = delta.cartesian_to_motors(&target).unwrap();
+            let result = delta.motors_to_cartesian(&motors).unwrap();
+            for axis in 0..3 {
+                assert!((result[axis] - target[axis]).abs() < 1e-9, "{:?} -> {:?}", target, result);
+            }
+        }
+        
+        assert!(!delta.is_valid_position(&[90.0, 90.0, 0.0]));
+        assert!(delta.cartesian_to_motors(&[400.0, 0.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn test_corexy_skew_from_test_print() {
+        let limits = [[0.0, 300.0]; 3];
+        let skew = 0.01;
+        let side = 100.0;
+        
+        // A frame that leans by `skew` moves the nozzle by tan(skew) in X per mm of Y
+        let mut frame = CoreXYKinematics::new(limits);
+        frame.set_skew(SkewCorrection { xy: skew, ..SkewCorrection::default() });
+        let print_square = |commanded: &CoreXYKinematics| -> Vec<[f64; 3]> {
+            [[0.0, 0.0], [side, 0.0], [side, side], [0.0, side]]
+                .iter()
+                .map(|corner| {
+                    let motors = commanded.cartesian_to_motors(&[corner[0], corner[1], 0.0]).unwrap();
+                    frame.motors_to_cartesian(&motors).unwrap()
+                })
+                .collect()
+        };
+        let distance = |a: &[f64; 3], b: &[f64; 3]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+        
+        // Uncorrected: the square prints as a parallelogram
+        let corners = print_square(&CoreXYKinematics::new(limits));
+        let ac = distance(&corners[0], &corners[2]);
+        let bd = distance(&corners[1], &corners[3]);
+        let expected_difference = 4.0 * side * side * skew.tan();
+        assert!((ac * ac - bd * bd - expected_difference).abs() < 1e-6);
+        
+        let computed = SkewCorrection::from_diagonals(ac, bd, side);
+        assert!((computed.xy - skew).abs() < 1e-9);
+        
+        // Corrected: the diagonals match again
+        let mut corrected = CoreXYKinematics::new(limits);
+        corrected.set_skew(computed);
+        let corners = print_square(&corrected);
+        assert!((distance(&corners[0], &corners[2]) - distance(&corners[1], &corners[3])).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_corexy_motor_current_scale() {
+        let mut corexy = CoreXYKinematics::new([[0.0, 300.0]; 3]);
+        let motors = |x: f64, y: f64| CoreXYKinematics::new([[0.0, 300.0]; 3]).cartesian_to_motors(&[x, y, 0.0]).unwrap();
+        let (origin, diagonal, along_x) = (motors(0.0, 0.0), motors(10.0, 10.0), motors(10.0, 0.0));
+        assert_eq!(corexy.motor_current_scale(&origin, &diagonal), None);
+        
+        corexy.set_motor_current_balance(0.5);
+        assert_eq!(corexy.motor_current_scale(&origin, &diagonal), Some([1.0, 0.5]));
+        assert_eq!(corexy.motor_current_scale(&diagonal, &origin), Some([1.0, 0.5]));
+        assert_eq!(corexy.motor_current_scale(&origin, &along_x), Some([1.0, 1.0]));
+        assert_eq!(corexy.motor_current_scale(&origin, &[0.0, 0.0, 5.0, 0.0]), None);
+    }
+
+    #[test]
+    fn test_corexy_skew_round_trip() {
+        let mut corexy = CoreXYKinematics::new([[0.0, 300.0]; 3]);
+        corexy.set_skew(SkewCorrection { xy: 0.02, xz: -0.01, yz: 0.015 });
+        
+        let target = [120.0, 80.0, 40.0];
+        let motors = corexy.cartesian_to_motors(&target).unwrap();
+        let result = corexy.motors_to_cartesian(&motors).unwrap();
+        for axis in 0..3 {
+            assert!((result[axis] - target[axis]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_cartesian_skew_diagonal_length() {
+        let limits = [[0.0, 300.0]; 3];
+        let skew = SkewCorrection { xy: 1.0f64.to_radians(), ..SkewCorrection::default() };
+        assert!(!skew.is_negligible());
+        assert!(SkewCorrection { xz: 0.00005, ..SkewCorrection::default() }.is_negligible());
+        
+        // Where a frame leaning by 1° in XY puts the nozzle for the given motor positions
+        let frame = |motors: [f64; 4]| skew.uncorrect(&[motors[0], motors[1], motors[2]]);
+        let diagonal = |kinematics: &CartesianKinematics| {
+            let start = frame(kinematics.cartesian_to_motors(&[50.0, 50.0, 0.0]).unwrap());
+            let end = frame(kinematics.cartesian_to_motors(&[150.0, 150.0, 0.0]).unwrap());
+            ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2)).sqrt()
+        };
+        let expected = 100.0 * 2.0f64.sqrt();
+        
+        assert!((diagonal(&CartesianKinematics::new(limits)) - expected).abs() > 1.0);
+        let mut corrected = CartesianKinematics::new(limits);
+        corrected.set_skew(skew);
+        assert!((diagonal(&corrected) - expected).abs() < 1e-9);
+        
+        let motors = corrected.cartesian_to_motors(&[120.0, 80.0, 40.0]).unwrap();
+        let result = corrected.motors_to_cartesian(&motors).unwrap();
+        assert!((0..3).all(|axis| (result[axis] - [120.0, 80.0, 40.0][axis]).abs() < 1e-9));
+    }
+}
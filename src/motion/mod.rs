@@ -1,25 +1,154 @@
 // src/motion/mod.rs - Use the hardware_manager field
+pub mod adaptive_planner;
+pub mod clog;
+pub mod delta_calibration;
+pub mod drift;
+pub mod hangprinter_calibration;
+pub mod kinematics;
+pub mod planner;
+pub mod pool;
+pub mod queue;
+pub mod recorder;
+pub mod shaper_presets;
+pub mod snap_crackle;
+pub mod stepper;
+
+use std::fmt;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
 use crate::printer::PrinterState;
 use crate::hardware::HardwareManager;
 
+pub use planner::{MotionConfig, MotionEvent, MotionPlanner, MotionPlannerStats, MotionQueueState, MotionSegment, MotionType, MotionTypeConfig};
+pub use clog::ClogDetector;
+pub use drift::PositionDriftMonitor;
+pub use hangprinter_calibration::{HangprinterCalibrationResult, HangprinterCalibrator};
+pub use shaper_presets::{ShaperPreset, ShaperPresetLibrary};
+pub use pool::{PooledSegment, SegmentPool};
+pub use queue::{segment_queue, ExecutorHandle, MotionError, PlannerHandle, SegmentQueue};
+pub use recorder::{MotionRecorder, RecordingInfo, TrajectorySample};
+pub use stepper::StepPositionDrift;
+
+use adaptive_planner::{AdaptiveConfig, AdaptiveMotionPlanner};
+use kinematics::{Kinematics, KinematicsType, SkewCorrection};
+use snap_crackle::{SnapCrackleConfig, SnapCrackleMotion};
+
 #[derive(Debug, Clone)]
 pub struct MotionController {
     state: Arc<RwLock<PrinterState>>,
     hardware_manager: HardwareManager,
-    current_position: [f64; 4], // X, Y, Z, E
+    planner: MotionPlanner,
+    dry_run_stats: DryRunStats,
+    /// Plan linear moves as this type instead of by what they do
+    motion_type_override: Option<MotionType>,
+    /// Shapes moves in the current `MotionMode`; clones share it
+    mode_planner: Arc<Mutex<MotionPlannerEnum>>,
+}
+
+/// How linear moves are shaped before `MotionPlanner` queues them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MotionMode {
+    /// At the requested feedrate
+    #[default]
+    Basic,
+    /// Slowed by `AdaptiveMotionPlanner` where it predicts errors
+    Adaptive,
+    /// Timed by `SnapCrackleMotion`'s seventh-order profiles
+    SnapCrackle,
+}
+
+impl MotionMode {
+    /// Mode selected by `M572 R<index>`
+    pub fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(MotionMode::Basic),
+            1 => Some(MotionMode::Adaptive),
+            2 => Some(MotionMode::SnapCrackle),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MotionMode::Basic => "basic",
+            MotionMode::Adaptive => "adaptive",
+            MotionMode::SnapCrackle => "snap_crackle",
+        }
+    }
+}
+
+impl std::str::FromStr for MotionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "basic" => Ok(MotionMode::Basic),
+            "adaptive" => Ok(MotionMode::Adaptive),
+            "snap_crackle" | "snapcrackle" => Ok(MotionMode::SnapCrackle),
+            _ => Err(format!("Unknown motion mode: {}", s)),
+        }
+    }
+}
+
+/// The planner of each `MotionMode`
+pub enum MotionPlannerEnum {
+    Basic,
+    Adaptive(Box<AdaptiveMotionPlanner>),
+    SnapCrackle(Box<SnapCrackleMotion>),
+}
+
+impl MotionPlannerEnum {
+    pub fn mode(&self) -> MotionMode {
+        match self {
+            MotionPlannerEnum::Basic => MotionMode::Basic,
+            MotionPlannerEnum::Adaptive(_) => MotionMode::Adaptive,
+            MotionPlannerEnum::SnapCrackle(_) => MotionMode::SnapCrackle,
+        }
+    }
+}
+
+impl fmt::Debug for MotionPlannerEnum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MotionPlannerEnum").field(&self.mode()).finish()
+    }
+}
+
+/// Totals for moves simulated while the printer is in dry-run mode
+#[derive(Debug, Clone, Default)]
+pub struct DryRunStats {
+    /// Number of moves simulated
+    pub moves: usize,
+
+    /// Toolhead travel in XYZ (mm)
+    pub total_distance: f64,
+
+    /// Sum of move durations at the requested feedrates (seconds)
+    pub estimated_time: f64,
+}
+
+/// Motion queue statistics
+#[derive(Debug, Clone, Default)]
+pub struct QueueStats {
+    /// Number of segments waiting to be executed
+    pub length: usize,
 }
 
 impl MotionController {
     pub fn new(
         state: Arc<RwLock<PrinterState>>,
         hardware_manager: HardwareManager,
+        motion_config: MotionConfig,
     ) -> Self {
+        let planner = MotionPlanner::new(state.clone(), hardware_manager.clone(), motion_config);
+
         Self {
             state,
             hardware_manager,
-            current_position: [0.0, 0.0, 0.0, 0.0],
+            planner,
+            dry_run_stats: DryRunStats::default(),
+            motion_type_override: None,
+            mode_planner: Arc::new(Mutex::new(MotionPlannerEnum::Basic)),
         }
     }
 
@@ -29,120 +158,412 @@ impl MotionController {
         feedrate: Option<f64>,
         extrude: Option<f64>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let current_e = self.current_position[3];
+        let current = self.planner.get_planned_position();
         let target_e = if let Some(e) = extrude {
-            current_e + e
+            current[3] + e
         } else {
-            current_e
+            current[3]
         };
-        
-        let feedrate = feedrate.unwrap_or(300.0);
+
+        let feedrate = feedrate.unwrap_or(300.0) * self.state.read().await.feedrate_factor;
         let target_4d = [target[0], target[1], target[2], target_e];
-        
+
+        // Moves without positive extrusion are travel moves; pure E moves are retract/prime
+        let moves_xyz = (0..3).any(|i| target_4d[i] != current[i]);
+        let motion_type = self.motion_type_override.unwrap_or(match extrude {
+            Some(e) if e != 0.0 && !moves_xyz => MotionType::Extruder,
+            Some(e) if e > 0.0 => MotionType::Print,
+            _ => MotionType::Travel,
+        });
+
+        if self.state.read().await.dry_run {
+            self.simulate_move(target_4d, feedrate);
+            return Ok(());
+        }
+
+        let feedrate = self.shape_feedrate(target_4d, feedrate, motion_type).await?;
         tracing::info!("Queuing linear move to [{:.3}, {:.3}, {:.3}, {:.3}] at {:.1}mm/s",
                       target_4d[0], target_4d[1], target_4d[2], target_4d[3], feedrate);
-        
-        // Send step commands to hardware
-        self.send_steps_to_hardware(&target_4d).await?;
-        
-        // Update current position
-        self.current_position = target_4d;
-        
-        // Update printer state
-        {
-            let mut state = self.state.write().await;
-            state.position = [target_4d[0], target_4d[1], target_4d[2]];
+
+        self.wait_for_queue_space().await?;
+        self.planner.plan_linear_move(target_4d, feedrate, motion_type).await?;
+
+        Ok(())
+    }
+
+    /// Feedrate the current motion mode gives a move to `target`
+    async fn shape_feedrate(
+        &self,
+        target: [f64; 4],
+        feedrate: f64,
+        motion_type: MotionType,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        if motion_type == MotionType::Home {
+            return Ok(feedrate);
         }
-        
+        let start = self.planner.get_planned_position();
+        match &mut *self.mode_planner.lock().await {
+            MotionPlannerEnum::Basic => Ok(feedrate),
+            MotionPlannerEnum::Adaptive(adaptive) => {
+                // Homing and G92 move the position without planning a move
+                adaptive.set_position(start);
+                Ok(adaptive.plan_adaptive_move(target, feedrate, motion_type).await?.limited_feedrate)
+            }
+            MotionPlannerEnum::SnapCrackle(snap_crackle) => {
+                let xyz = (0..3).map(|i| (target[i] - start[i]).powi(2)).sum::<f64>().sqrt();
+                let distance = if xyz > 0.0 { xyz } else { (target[3] - start[3]).abs() };
+                if distance == 0.0 {
+                    return Ok(feedrate);
+                }
+                let duration = snap_crackle.plan_move_duration(distance, feedrate).await?;
+                Ok(if duration > 0.0 { (distance / duration).min(feedrate) } else { feedrate })
+            }
+        }
+    }
+
+    /// Mode linear moves are shaped in
+    pub async fn motion_mode(&self) -> MotionMode {
+        self.mode_planner.lock().await.mode()
+    }
+
+    /// Switch to `mode` once the queued moves have finished
+    ///
+    /// The new mode's planner takes over at the position the queue ended
+    /// at.
+    pub async fn set_motion_mode(&mut self, mode: MotionMode) -> Result<(), MotionError> {
+        if let Err(e) = self.wait_for_queue_empty().await.map_err(|e| e.to_string()) {
+            tracing::warn!("Not switching motion mode: {}", e);
+            return Err(MotionError::QueueNotDrained);
+        }
+        let position = self.planner.get_planned_position();
+        let mut planner = self.mode_planner.lock().await;
+        if planner.mode() == mode {
+            return Ok(());
+        }
+        tracing::info!("Switching motion mode from {} to {}", planner.mode().name(), mode.name());
+        *planner = match mode {
+            MotionMode::Basic => MotionPlannerEnum::Basic,
+            MotionMode::Adaptive => {
+                let mut adaptive = AdaptiveMotionPlanner::new(self.planner.get_config().clone(), AdaptiveConfig::default());
+                adaptive.set_position(position);
+                MotionPlannerEnum::Adaptive(Box::new(adaptive))
+            }
+            MotionMode::SnapCrackle => {
+                let config = SnapCrackleConfig::default();
+                MotionPlannerEnum::SnapCrackle(Box::new(SnapCrackleMotion::new(config.max_snap, config.max_crackle)))
+            }
+        };
         Ok(())
     }
 
+    /// Plan linear moves as `motion_type`, or by what they do with `None`
+    ///
+    /// `MotionType::Home` moves may run before homing and aren't shaped.
+    pub fn set_motion_type_override(&mut self, motion_type: Option<MotionType>) {
+        self.motion_type_override = motion_type;
+    }
+
     pub async fn queue_home(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Queuing home command");
-        self.current_position = [0.0, 0.0, 0.0, self.current_position[3]];
-        
-        // Send home command to hardware
-        let _ = self.hardware_manager.send_command("home_all").await;
-        
-        // Update printer state
-        {
-            let mut state = self.state.write().await;
-            state.position = [0.0, 0.0, 0.0];
+
+        // Run the homing moves, which bypass the input shaper so the
+        // endstops trigger where planned, then send home command to
+        // hardware; a failed home leaves the position unknown
+        if !self.state.read().await.dry_run {
+            self.wait_for_queue_empty().await?;
+            self.planner.plan_home(None).await?;
+            self.wait_for_queue_empty().await?;
+            self.hardware_manager.send_command("home_all").await?;
+        }
+
+        // Homing complete: the position is known again
+        let current = self.planner.get_planned_position();
+        self.planner.set_homed([0.0, 0.0, 0.0, current[3]]).await;
+        self.state.write().await.position = [0.0, 0.0, 0.0];
+
+        Ok(())
+    }
+
+    /// Move to `target` [X, Y, Z] as a probing move and wait until the
+    /// toolhead is there
+    ///
+    /// Probing moves bypass the input shaper, so the toolhead ends exactly
+    /// at `target` before the probe triggers.
+    pub async fn queue_probe_move(&mut self, target: [f64; 3], feedrate: f64) -> Result<(), Box<dyn std::error::Error>> {
+        let current = self.planner.get_planned_position();
+        self.wait_for_queue_space().await?;
+        self.planner
+            .plan_linear_move([target[0], target[1], target[2], current[3]], feedrate, MotionType::Probe)
+            .await?;
+        self.wait_for_queue_empty().await
+    }
+
+    /// Home one axis (0-2 for X-Z), leaving the others where they are
+    pub async fn home_axis(&mut self, axis: usize) -> Result<(), Box<dyn std::error::Error>> {
+        let name = ["X", "Y", "Z"].get(axis).ok_or_else(|| format!("Invalid axis {}", axis))?;
+        tracing::info!("Homing {}", name);
+        if !self.state.read().await.dry_run {
+            let _ = self.hardware_manager.send_command(&format!("home_axis {}", name)).await;
         }
         
+        let mut position = self.planner.get_planned_position();
+        position[axis] = 0.0;
+        self.planner.set_position(position);
+        self.state.write().await.position[axis] = 0.0;
         Ok(())
     }
 
+    /// Drop queued moves, keeping the position reached so far
+    pub fn cancel_moves(&mut self) {
+        self.planner.clear_queue();
+    }
+
+    /// Motor steps per mm for [X, Y, Z, E]
+    pub fn steps_per_mm(&self) -> [f64; 4] {
+        self.planner.steps_per_mm()
+    }
+
+    /// Change steps/mm for the given axes, leaving the others alone
+    ///
+    /// Each value must be positive and within `steps_per_mm_max_ratio` of
+    /// the configured one; nothing changes if any is rejected.
+    pub fn set_steps_per_mm(&mut self, values: [Option<f64>; 4]) -> Result<[f64; 4], Box<dyn std::error::Error>> {
+        let config = self.planner.get_config();
+        let ratio = config.steps_per_mm_max_ratio;
+        let mut steps_per_mm = self.planner.steps_per_mm();
+        for (axis, value) in values.iter().enumerate() {
+            let Some(value) = *value else {
+                continue;
+            };
+            let default = config.steps_per_mm[axis];
+            let (min, max) = (default / ratio, default * ratio);
+            if !value.is_finite() || value <= 0.0 || value < min || value > max {
+                return Err(format!(
+                    "{} steps/mm {} outside {:.3}..{:.3}",
+                    ["X", "Y", "Z", "E"][axis], value, min, max
+                )
+                .into());
+            }
+            steps_per_mm[axis] = value;
+        }
+        self.planner.set_steps_per_mm(steps_per_mm);
+        tracing::info!("Steps/mm set to {:?}", steps_per_mm);
+        Ok(steps_per_mm)
+    }
+
+    /// Release the steppers; the position is unknown until the next home
+    pub async fn disable_motors(&mut self) {
+        tracing::info!("Disabling motors");
+        if !self.state.read().await.dry_run {
+            let _ = self.hardware_manager.send_command("disable_motors").await;
+        }
+        self.planner.clear_homed().await;
+    }
+
+    /// Release the extruder motor so filament can be changed by hand
+    pub async fn disable_extruder(&mut self) {
+        if !self.state.read().await.dry_run {
+            let _ = self.hardware_manager.send_command("disable_extruder").await;
+        }
+    }
+
+    /// Drop all motion and mark the position unknown until the next home
+    pub async fn reset(&mut self) {
+        self.planner.reset().await;
+    }
+
     pub async fn queue_extruder_move(
         &mut self,
         amount: f64,
         feedrate: Option<f64>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let target_e = self.current_position[3] + amount;
+        let current = self.planner.get_planned_position();
+        let target_e = current[3] + amount;
         let feedrate = feedrate.unwrap_or(20.0);
-        
+
+        if self.state.read().await.dry_run {
+            self.simulate_move([current[0], current[1], current[2], target_e], feedrate);
+            return Ok(());
+        }
+
         tracing::info!("Queuing extruder move: {:.3}mm at {:.1}mm/s", amount, feedrate);
-        
-        // Send extruder command to hardware
-        let cmd = format!("extrude {} {}", amount, feedrate);
-        let _ = self.hardware_manager.send_command(&cmd).await;
-        
-        self.current_position[3] = target_e;
-        
+
+        self.wait_for_queue_space().await?;
+        self.planner.plan_extruder_move(target_e, feedrate).await?;
+
         Ok(())
     }
 
+    /// Account for a move without queuing it (dry-run mode)
+    fn simulate_move(&mut self, target: [f64; 4], feedrate: f64) {
+        let current = self.planner.get_planned_position();
+        let delta: Vec<f64> = (0..4).map(|i| target[i] - current[i]).collect();
+        let xyz_distance = (delta[0] * delta[0] + delta[1] * delta[1] + delta[2] * delta[2]).sqrt();
+        let move_length = (xyz_distance * xyz_distance + delta[3] * delta[3]).sqrt();
+
+        self.dry_run_stats.moves += 1;
+        self.dry_run_stats.total_distance += xyz_distance;
+        if feedrate > 0.0 {
+            self.dry_run_stats.estimated_time += move_length / feedrate;
+        }
+        self.planner.set_position(target);
+    }
+
+    /// Account for a dwell without waiting (dry-run mode)
+    pub fn simulate_dwell(&mut self, duration: Duration) {
+        self.dry_run_stats.estimated_time += duration.as_secs_f64();
+    }
+
+    /// Get the totals for moves simulated in dry-run mode
+    pub fn get_dry_run_stats(&self) -> &DryRunStats {
+        &self.dry_run_stats
+    }
+
+    /// Clear the dry-run totals
+    pub fn reset_dry_run_stats(&mut self) {
+        self.dry_run_stats = DryRunStats::default();
+    }
+
+    /// Set the planner position without moving (e.g. to restore after a dry run)
+    pub fn set_position(&mut self, position: [f64; 4]) {
+        self.planner.set_position(position);
+    }
+
     pub async fn update(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Process motion updates
-        // In a real implementation, this would process queued moves
-        // For now, this is a placeholder that checks hardware status
-        let _ = self.hardware_manager.send_command("get_status").await;
-        Ok(())
+        // Advance the planner, executing queued segments as their time elapses
+        self.planner.update().await
+    }
+
+    /// Run the planner until every queued move has finished (M400)
+    ///
+    /// Gives up after the configured `queue_drain_timeout_secs`, leaving the
+    /// remaining moves queued.
+    pub async fn wait_for_queue_empty(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.run_until(|planner| planner.queue_length() == 0 && !planner.is_active()).await
+    }
+
+    /// Run the planner until the queue has room for another move
+    ///
+    /// The planner refuses moves with `MotionError::QueueFull` rather than
+    /// blocking; this holds the caller back instead, for as long as
+    /// `wait_for_queue_empty` would.
+    async fn wait_for_queue_space(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.planner.is_queue_full() {
+            return Ok(());
+        }
+        self.planner.run_until(|planner| !planner.is_queue_full()).await
     }
 
     pub fn emergency_stop(&mut self) {
         tracing::warn!("Emergency stop activated - clearing motion state");
-        self.current_position = [0.0, 0.0, 0.0, self.current_position[3]];
-        // In real implementation, this would clear the motion queue and stop motors
+        let current = self.planner.get_planned_position();
+        self.planner.clear_queue();
+        self.planner.set_position([0.0, 0.0, 0.0, current[3]]);
     }
 
+    /// Get the position at the end of all queued moves [X, Y, Z, E]
     pub fn get_current_position(&self) -> [f64; 4] {
-        self.current_position
-    }
-    
-    // Helper method to send steps to hardware
-    async fn send_steps_to_hardware(&self, target: &[f64; 4]) -> Result<(), Box<dyn std::error::Error>> {
-        // Calculate steps needed (simplified)
-        let dx = target[0] - self.current_position[0];
-        let dy = target[1] - self.current_position[1];
-        let dz = target[2] - self.current_position[2];
-        let de = target[3] - self.current_position[3];
-        
-        if dx != 0.0 {
-            let cmd = format!("step X {} {}", dx.abs() as i32, if dx > 0.0 { 1 } else { 0 });
-            let _ = self.hardware_manager.send_command(&cmd).await;
-        }
-        
-        if dy != 0.0 {
-            let cmd = format!("step Y {} {}", dy.abs() as i32, if dy > 0.0 { 1 } else { 0 });
-            let _ = self.hardware_manager.send_command(&cmd).await;
-        }
-        
-        if dz != 0.0 {
-            let cmd = format!("step Z {} {}", dz.abs() as i32, if dz > 0.0 { 1 } else { 0 });
-            let _ = self.hardware_manager.send_command(&cmd).await;
-        }
-        
-        if de != 0.0 {
-            let cmd = format!("step E {} {}", de.abs() as i32, if de > 0.0 { 1 } else { 0 });
-            let _ = self.hardware_manager.send_command(&cmd).await;
+        self.planner.get_planned_position()
+    }
+
+    /// Get motion queue statistics
+    pub fn get_queue_stats(&self) -> QueueStats {
+        QueueStats {
+            length: self.planner.queue_length(),
         }
-        
-        Ok(())
     }
-    
+
+    /// Get the active motion configuration
+    pub fn get_motion_config(&self) -> &MotionConfig {
+        self.planner.get_config()
+    }
+
+    /// Configure Z-hop for travel moves (height 0 disables it)
+    pub fn set_z_hop(&mut self, height: f64, speed: Option<f64>) {
+        self.planner.set_z_hop(height, speed);
+    }
+
+    /// Set how many segments the planner looks ahead over
+    pub fn set_lookahead_buffer_size(&mut self, size: usize) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_lookahead_buffer_size(size)
+    }
+
+    /// Replace the active kinematics handler
+    pub fn set_kinematics_handler(&mut self, kinematics_type: KinematicsType, kinematics: Box<dyn Kinematics>) {
+        self.planner.set_kinematics_handler(kinematics_type, kinematics);
+    }
+
+    /// Switch kinematics at runtime once the motion queue has drained
+    pub async fn set_kinematics(&mut self, kinematics_type: KinematicsType) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_kinematics(kinematics_type).await
+    }
+
+    /// Set the filament diameter used for volumetric limits (mm)
+    pub fn set_filament_diameter(&mut self, diameter: f64) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_filament_diameter(diameter)
+    }
+
+    /// Limit extrusion to `speed` mm³/s, 0 for no limit
+    pub fn set_max_volumetric_speed(&mut self, speed: f64) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_max_volumetric_speed(speed)
+    }
+
+    /// Label the moves queued from now on, `None` to stop labeling
+    pub fn set_segment_label(&mut self, label: Option<String>) {
+        self.planner.set_segment_label(label);
+    }
+
+    /// Limit `motion_type` moves to `velocity` mm/s
+    pub fn set_motion_type_max_velocity(&mut self, motion_type: MotionType, velocity: f64) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_motion_type_max_velocity(motion_type, velocity)
+    }
+
+    /// Set the linear advance K-factor, 0 to disable it
+    pub fn set_linear_advance(&mut self, k_factor: f64) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_linear_advance(k_factor)
+    }
+
+    /// Check the extruder keeps up with planned moves; clones made afterwards share the detector
+    pub fn set_clog_detector(&mut self, detector: ClogDetector) {
+        self.planner.set_clog_detector(detector);
+    }
+
+    /// Record trajectories with `recorder`; clones made afterwards share it
+    pub fn set_motion_recorder(&mut self, recorder: MotionRecorder) {
+        self.planner.set_motion_recorder(recorder);
+    }
+
+    pub fn clog_detector(&self) -> Option<&ClogDetector> {
+        self.planner.clog_detector()
+    }
+
+    /// Report extruder steps to `monitor` and make up the ones it finds missing
+    pub fn set_extruder_sync_monitor(&mut self, monitor: crate::hardware::ExtruderSyncMonitor) {
+        self.planner.set_extruder_sync_monitor(monitor);
+    }
+
+    /// Use `shaper` for input shaping, `None` to turn it off
+    pub fn set_input_shaper(&mut self, shaper: Option<ShaperPreset>) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_input_shaper(shaper)
+    }
+
+    /// Set the acceleration factor for curve segments
+    pub fn set_curve_accel_factor(&mut self, factor: f64) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_curve_accel_factor(factor)
+    }
+
+    /// Set the frame skew correction
+    pub fn set_skew_correction(&mut self, skew: SkewCorrection) -> Result<(), Box<dyn std::error::Error>> {
+        self.planner.set_skew_correction(skew)
+    }
+
+    /// Get the underlying motion planner
+    pub fn get_planner(&self) -> &MotionPlanner {
+        &self.planner
+    }
+
     // Add method to access hardware manager
     pub fn get_hardware_manager(&self) -> &HardwareManager {
         &self.hardware_manager
     }
-}
\ No newline at end of file
+}
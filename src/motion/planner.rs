@@ -1,12 +1,47 @@
 // src/motion/planner.rs
-use std::collections::VecDeque;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{RwLock, broadcast, watch};
 use crate::printer::PrinterState;
-use crate::hardware::HardwareManager;
+use crate::hardware::{ExtruderSyncMonitor, HardwareManager};
+use super::kinematics::{create_kinematics_of_type, CartesianKinematics, CoreXYKinematics, Kinematics, KinematicsType, ScaraKinematics, SkewCorrection};
+use super::pool::{PooledSegment, SegmentPool};
+use super::queue::{segment_queue, ExecutorHandle, MotionError, PlannerHandle, SegmentQueue};
+use super::clog::ClogDetector;
+use super::recorder::MotionRecorder;
+use super::shaper_presets::ShaperPreset;
+use super::stepper::{LinearAdvance, StepGenerator, StepPositionDrift};
+
+/// Smallest lookahead buffer the planner accepts
+const MIN_LOOKAHEAD_BUFFER_SIZE: usize = 4;
+
+/// Relative junction speed change that invalidates the current plan
+const REPLAN_SPEED_TOLERANCE: f64 = 0.01;
+
+/// How often `run_until` advances the planner
+const QUEUE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Acceleration factor for curve segments unless changed with M204 C
+pub const DEFAULT_CURVE_ACCEL_FACTOR: f64 = 0.7;
+
+/// Speed and acceleration limits for homing and probing moves (mm/s, mm/s²)
+const HOME_MAX_VELOCITY: f64 = 50.0;
+const PROBE_MAX_VELOCITY: f64 = 5.0;
+const PROBE_MAX_ACCELERATION: f64 = 100.0;
+
+/// Sequence number of the next planned segment, across all planners
+static NEXT_SEGMENT_SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+/// Segments longer than this are never part of a curve (mm)
+const CURVE_MAX_SEGMENT_LENGTH: f64 = 5.0;
+
+/// Junctions bending less than this are straight, more than the maximum are corners
+const CURVE_MIN_ANGLE_DEG: f64 = 0.5;
+const CURVE_MAX_ANGLE_DEG: f64 = 15.0;
 
 /// A single motion segment in the planned path
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct MotionSegment {
     /// Target position [X, Y, Z, E] in mm
     pub target: [f64; 4],
@@ -23,17 +58,38 @@ pub struct MotionSegment {
     /// Time to complete this segment in seconds
     pub duration: f64,
     
+    /// Speed at the start of this segment (mm/s)
+    pub entry_speed: f64,
+    
+    /// Speed at the end of this segment (mm/s)
+    pub exit_speed: f64,
+    
     /// Type of motion (printing, travel, homing, etc.)
     pub motion_type: MotionType,
+    
+    /// Part of a run of short segments bending gently, i.e. an arc;
+    /// `acceleration` is already scaled by `curve_accel_factor`
+    pub is_curve: bool,
+    
+    /// Never input shaped, so the toolhead is exactly at `target` when the
+    /// segment ends; for homing, probing and calibration
+    pub bypass_shaper: bool,
+    
+    /// What the segment was planned for, e.g. the G-code command
+    pub label: Option<String>,
+    
+    /// Order the segment was planned in, unique across planners
+    pub sequence_number: u64,
 }
 
 /// Types of motion segments
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum MotionType {
     /// Printing move (extruder moving)
     Print,
     
     /// Travel move (no extrusion)
+    #[default]
     Travel,
     
     /// Homing move
@@ -41,10 +97,79 @@ pub enum MotionType {
     
     /// Retract/Prime move
     Extruder,
+    
+    /// Probing move: slow, never shaped and not slowed at corners by jerk
+    Probe,
+}
+
+impl MotionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MotionType::Print => "print",
+            MotionType::Travel => "travel",
+            MotionType::Home => "home",
+            MotionType::Extruder => "extruder",
+            MotionType::Probe => "probe",
+        }
+    }
+}
+
+impl std::str::FromStr for MotionType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "print" => Ok(MotionType::Print),
+            "travel" => Ok(MotionType::Travel),
+            "home" => Ok(MotionType::Home),
+            "extruder" => Ok(MotionType::Extruder),
+            "probe" => Ok(MotionType::Probe),
+            other => Err(format!("Unknown motion type: {}", other)),
+        }
+    }
+}
+
+/// Limits for one type of motion, applied on top of the per-axis ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionTypeConfig {
+    /// Toolhead speed (mm/s)
+    pub max_velocity: f64,
+    
+    /// Toolhead acceleration (mm/s²)
+    pub max_acceleration: f64,
+    
+    /// Junction deviation bounding corner speeds (mm, 0 leaves corners to the jerk limits)
+    pub junction_deviation: f64,
+}
+
+/// Events emitted by the motion planner
+#[derive(Debug, Clone, PartialEq)]
+pub enum MotionEvent {
+    /// The active kinematics were replaced
+    KinematicsChanged(KinematicsType),
+    /// A motor's encoder drifted further from its commanded position than
+    /// `max_drift_steps`
+    PositionDrift(StepPositionDrift),
+}
+
+/// Whether the planner is executing its queue
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum MotionQueueState {
+    /// Nothing to execute
+    #[default]
+    Idle,
+    Running,
+    /// Execution is held until `resume`
+    Paused,
+    /// Queued moves were dropped before they finished
+    Cancelled,
+    /// A queued move could not be executed; nothing more runs until
+    /// `clear_error`
+    Error(String),
 }
 
 /// Motion planning parameters
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MotionConfig {
     /// Maximum velocity for each axis (mm/s)
     pub max_velocity: [f64; 4], // [X, Y, Z, E]
@@ -60,10 +185,80 @@ pub struct MotionConfig {
     
     /// Lookahead buffer size for motion planning
     pub lookahead_buffer_size: usize,
+    
+    /// Height to lift the nozzle during travel moves (mm, 0 disables Z-hop)
+    pub z_hop_height: f64,
+    
+    /// Feedrate used for the Z-hop lift and lower moves (mm/s)
+    pub z_hop_speed: f64,
+    
+    /// Configured printer kinematics; see `MotionPlanner::kinematics_type`
+    /// for the ones in use
+    pub kinematics_type: KinematicsType,
+    
+    /// Travel limits [min, max] for X, Y, Z (mm)
+    pub axis_limits: [[f64; 2]; 3],
+    
+    /// Frame skew correction (Cartesian and CoreXY only)
+    pub skew_correction: SkewCorrection,
+    
+    /// Motor steps per mm for [X, Y, Z, E] from the configuration
+    pub steps_per_mm: [f64; 4],
+    
+    /// Factor steps/mm may be calibrated away from `steps_per_mm`
+    pub steps_per_mm_max_ratio: f64,
+    
+    /// Diameter of the filament going into the extruder (mm)
+    pub filament_diameter: f64,
+    
+    /// Most filament volume extruded per second (mm³/s, 0 for no limit)
+    pub max_volumetric_speed: f64,
+    
+    /// Linear advance K-factor (mm per mm/s of E speed), `None` when disabled
+    pub linear_advance_k: Option<f64>,
+    
+    /// Acceleration scale for segments found to form a curve
+    pub curve_accel_factor: f64,
+    
+    /// Shortest time a layer may take before moves are slowed (seconds, 0 to disable)
+    pub min_layer_time_secs: f64,
+    
+    /// Part fan speed while layers are slowed for cooling (percent)
+    pub max_layer_fan_speed_pct: f64,
+    
+    /// Input shaper set with M593, applied to X and Y as moves execute;
+    /// `None` when off
+    pub input_shaper: Option<ShaperPreset>,
+    
+    /// Limits by motion type; types without an entry only have the per-axis limits
+    pub motion_types: HashMap<MotionType, MotionTypeConfig>,
+    
+    /// Longest to wait for the queue to drain (seconds, 0 for no limit)
+    pub queue_drain_timeout_secs: f64,
+    
+    /// CoreXY motor current balance, `None` when disabled
+    pub motor_current_balance: Option<f64>,
+    
+    /// Configured run current of the X and Y (A and B) motors (mA)
+    pub run_current_ma: [u32; 2],
+    
+    /// Encoder drift allowed on each motor with an encoder (steps)
+    pub max_drift_steps: [Option<u32>; 3],
 }
 
+/// Run current for motors without a stepper section (mA)
+const DEFAULT_RUN_CURRENT_MA: u32 = 800;
+
+/// Steps/mm used for axes without a usable stepper section
+const FALLBACK_STEPS_PER_MM: [f64; 4] = [80.0, 80.0, 400.0, 500.0];
+
 impl MotionConfig {
     pub fn new_from_printer_config(config: &crate::config::Config) -> Self {
+        let kinematics_type = config.printer.kinematics.parse().unwrap_or_else(|e| {
+            tracing::warn!("{}, falling back to cartesian", e);
+            KinematicsType::Cartesian
+        });
+        
         Self {
             max_velocity: [
                 config.printer.max_velocity,
@@ -80,11 +275,91 @@ impl MotionConfig {
             max_jerk: [10.0, 10.0, 0.4, 2.0], // Typical jerk values
             minimum_step_distance: 0.001, // 1 micron minimum
             lookahead_buffer_size: 16, // Look ahead at 16 moves
+            z_hop_height: config.printer.z_hop_height,
+            z_hop_speed: config.printer.z_hop_speed,
+            kinematics_type,
+            // Soft limits are not configurable yet
+            axis_limits: [[f64::NEG_INFINITY, f64::INFINITY]; 3],
+            skew_correction: SkewCorrection::default(),
+            steps_per_mm: configured_steps_per_mm(config, kinematics_type),
+            steps_per_mm_max_ratio: config.printer.steps_per_mm_max_ratio,
+            filament_diameter: config.extruder.filament_diameter,
+            max_volumetric_speed: config.extruder.max_volumetric_speed_mm3_per_s,
+            linear_advance_k: None,
+            curve_accel_factor: DEFAULT_CURVE_ACCEL_FACTOR,
+            min_layer_time_secs: config.printer.min_layer_time_secs,
+            max_layer_fan_speed_pct: config.printer.max_layer_fan_speed_pct,
+            input_shaper: None,
+            motion_types: default_motion_types(config),
+            queue_drain_timeout_secs: config.printer.queue_drain_timeout_secs,
+            motor_current_balance: config.printer.corexy_current_balance.then_some(config.printer.motor_current_balance),
+            run_current_ma: [0, 1].map(|axis| {
+                config.axis_stepper(axis).map_or(DEFAULT_RUN_CURRENT_MA, |stepper| stepper.run_current_ma)
+            }),
+            max_drift_steps: [0, 1, 2].map(|axis| {
+                config
+                    .axis_stepper(axis)
+                    .filter(|stepper| stepper.encoder.is_some())
+                    .map(|stepper| stepper.max_drift_steps)
+            }),
         }
     }
 }
 
+/// Print and travel moves go up to the configured speed, homing and probing slower
+fn default_motion_types(config: &crate::config::Config) -> HashMap<MotionType, MotionTypeConfig> {
+    let printer = &config.printer;
+    HashMap::from([
+        (MotionType::Print, MotionTypeConfig {
+            max_velocity: printer.max_velocity,
+            max_acceleration: printer.max_accel,
+            junction_deviation: 0.0,
+        }),
+        (MotionType::Travel, MotionTypeConfig {
+            max_velocity: printer.max_velocity,
+            max_acceleration: printer.max_accel,
+            junction_deviation: 0.0,
+        }),
+        (MotionType::Home, MotionTypeConfig {
+            max_velocity: HOME_MAX_VELOCITY,
+            max_acceleration: printer.max_accel,
+            junction_deviation: 0.0,
+        }),
+        (MotionType::Probe, MotionTypeConfig {
+            max_velocity: PROBE_MAX_VELOCITY,
+            max_acceleration: PROBE_MAX_ACCELERATION,
+            junction_deviation: 0.0,
+        }),
+    ])
+}
+
+/// Steps/mm of each motor; delta towers A, B and C stand in for X, Y and Z,
+/// and SCARA shoulder and elbow motors step per degree instead
+fn configured_steps_per_mm(config: &crate::config::Config, kinematics_type: KinematicsType) -> [f64; 4] {
+    let mut steps_per_mm = FALLBACK_STEPS_PER_MM;
+    for (axis, value) in steps_per_mm.iter_mut().take(3).enumerate() {
+        if let Some(stepper) = config.axis_stepper(axis) {
+            *value = stepper.steps_per_mm();
+        }
+    }
+    if kinematics_type == KinematicsType::Scara
+        && let Some(scara) = &config.scara
+    {
+        let scara = ScaraKinematics::from_config(scara);
+        steps_per_mm[0] = scara.arm1_steps_per_deg;
+        steps_per_mm[1] = scara.arm2_steps_per_deg;
+    }
+    steps_per_mm[3] = config.extruder.steps_per_mm();
+    for (value, fallback) in steps_per_mm.iter_mut().zip(FALLBACK_STEPS_PER_MM) {
+        if !value.is_finite() || *value <= 0.0 {
+            *value = fallback;
+        }
+    }
+    steps_per_mm
+}
+
 /// Motion planner that generates smooth, coordinated movements
+#[derive(Debug, Clone)]
 pub struct MotionPlanner {
     /// Shared printer state
     state: Arc<RwLock<PrinterState>>,
@@ -95,11 +370,50 @@ pub struct MotionPlanner {
     /// Motion configuration parameters
     config: MotionConfig,
     
+    /// Converts Cartesian positions to motor positions; shared so a switch
+    /// reaches the executing planner
+    kinematics: Arc<Mutex<ActiveKinematics>>,
+    
+    /// Planner event channel
+    event_tx: broadcast::Sender<MotionEvent>,
+    
+    /// Latest queue statistics, shared with monitoring
+    stats_tx: Arc<watch::Sender<MotionPlannerStats>>,
+    
+    /// Latest interpolated toolhead position, shared with monitoring
+    position_tx: Arc<watch::Sender<[f64; 4]>>,
+    
+    /// Queued segments as last planned, shared with debugging
+    segments_tx: Arc<watch::Sender<Vec<MotionSegment>>>,
+    
+    /// Compares planned and stepped extrusion after each step generation
+    clog_detector: Option<ClogDetector>,
+    
+    /// Told the extruder steps sent, and makes up ones the encoder missed
+    extruder_sync: Option<ExtruderSyncMonitor>,
+    
+    /// Samples the interpolated position while M926 recording is on
+    recorder: MotionRecorder,
+    
+    /// Turns motor moves into step commands; shared so calibration
+    /// reaches the executing planner
+    step_generator: Arc<Mutex<StepGenerator>>,
+    
+    /// Motor currents last set by current balancing (mA)
+    motor_currents: Option<[u32; 2]>,
+    
     /// Current position [X, Y, Z, E]
     current_position: [f64; 4],
     
-    /// Planned motion segments waiting execution
-    motion_queue: VecDeque<MotionSegment>,
+    /// Planned motion segments waiting execution, as many as the lookahead
+    /// buffer holds, each allocated from `segment_pool`
+    motion_queue: SegmentQueue<PooledSegment>,
+    
+    /// Label given to the segments planned next
+    segment_label: Option<String>,
+    
+    /// Reusable boxed segments, sized to twice the lookahead buffer
+    segment_pool: Arc<SegmentPool>,
     
     /// Current velocity for each axis
     current_velocity: [f64; 4],
@@ -108,6 +422,23 @@ pub struct MotionPlanner {
     planner_state: PlannerState,
 }
 
+/// Kinematics a planner converts moves with
+#[derive(Debug, Clone)]
+struct ActiveKinematics {
+    kinematics_type: KinematicsType,
+    handler: Arc<dyn Kinematics>,
+}
+
+/// Planner statistics published for monitoring
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MotionPlannerStats {
+    /// Segments waiting to be executed
+    pub queue_depth: usize,
+
+    /// Times the queue ran dry in the middle of printing
+    pub underruns: usize,
+}
+
 /// Internal state of the motion planner
 #[derive(Debug, Clone)]
 struct PlannerState {
@@ -115,13 +446,32 @@ struct PlannerState {
     active: bool,
     
     /// Current segment being executed
-    current_segment: Option<MotionSegment>,
+    current_segment: Option<PooledSegment>,
     
     /// Time into current segment (seconds)
     segment_time: f64,
     
+    /// Time spent executing since the planner started (seconds)
+    elapsed: f64,
+    
+    /// Recent commanded positions by `elapsed`, as far back as the input
+    /// shaper's last impulse
+    position_history: VecDeque<(f64, [f64; 4])>,
+    
+    /// Where the kinematics last put the motors
+    motor_positions: [f64; 4],
+    
     /// Last update timestamp
     last_update: std::time::Instant,
+    
+    /// Type of the last segment that finished executing
+    last_motion_type: Option<MotionType>,
+    
+    /// Times the queue ran dry in the middle of printing
+    underruns: usize,
+    
+    /// Running, paused or stopped by an error
+    queue_state: MotionQueueState,
 }
 
 impl MotionPlanner {
@@ -131,18 +481,55 @@ impl MotionPlanner {
         hardware_manager: HardwareManager,
         config: MotionConfig,
     ) -> Self {
+        let mut config = config;
+        let kinematics = build_kinematics(&config, hardware_manager.config()).unwrap_or_else(|e| {
+            tracing::warn!("{}, falling back to cartesian", e);
+            config.kinematics_type = KinematicsType::Cartesian;
+            Box::new(CartesianKinematics::new(config.axis_limits))
+        });
+        let (event_tx, _) = broadcast::channel(16);
+        let (stats_tx, _) = watch::channel(MotionPlannerStats::default());
+        let (position_tx, _) = watch::channel([0.0; 4]);
+        let motion_queue = SegmentQueue::new(config.lookahead_buffer_size);
+        let segment_pool = SegmentPool::new(config.lookahead_buffer_size * 2);
+        let mut step_generator = StepGenerator::new(config.steps_per_mm, [false; 4]);
+        step_generator.set_linear_advance(config.linear_advance_k.map(|k_factor| LinearAdvance { k_factor }));
+        let [x, y, z] = config.max_drift_steps;
+        step_generator.set_max_drift_steps([x, y, z, None]);
+        
         Self {
             state,
             hardware_manager,
+            kinematics: Arc::new(Mutex::new(ActiveKinematics {
+                kinematics_type: config.kinematics_type,
+                handler: Arc::from(kinematics),
+            })),
+            event_tx,
+            stats_tx: Arc::new(stats_tx),
+            position_tx: Arc::new(position_tx),
+            segments_tx: Arc::new(watch::Sender::new(Vec::new())),
+            clog_detector: None,
+            extruder_sync: None,
+            recorder: MotionRecorder::default(),
+            step_generator: Arc::new(Mutex::new(step_generator)),
             config,
+            motor_currents: None,
             current_position: [0.0, 0.0, 0.0, 0.0],
-            motion_queue: VecDeque::new(),
+            motion_queue,
+            segment_label: None,
+            segment_pool,
             current_velocity: [0.0; 4],
             planner_state: PlannerState {
                 active: false,
                 current_segment: None,
                 segment_time: 0.0,
+                elapsed: 0.0,
+                position_history: VecDeque::new(),
+                motor_positions: [0.0; 4],
                 last_update: std::time::Instant::now(),
+                last_motion_type: None,
+                underruns: 0,
+                queue_state: MotionQueueState::Idle,
             },
         }
     }
@@ -159,8 +546,50 @@ impl MotionPlanner {
         feedrate: f64,
         motion_type: MotionType,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let bypass_shaper = matches!(motion_type, MotionType::Home | MotionType::Probe);
+        self.plan_segment(target, feedrate, motion_type, bypass_shaper).await
+    }
+
+    /// Queue a move that is not input shaped, e.g. towards a probe or endstop
+    pub async fn plan_move_no_shaper(
+        &mut self,
+        target: [f64; 4],
+        feedrate: f64,
+        motion_type: MotionType,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.plan_segment(target, feedrate, motion_type, true).await
+    }
+
+    async fn plan_segment(
+        &mut self,
+        target: [f64; 4],
+        feedrate: f64,
+        motion_type: MotionType,
+        bypass_shaper: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let MotionQueueState::Error(reason) = &self.planner_state.queue_state {
+            return Err(format!("Motion queue stopped: {}", reason).into());
+        }
+        
+        // The position is meaningless until homed: refuse to print there
+        if !self.state.read().await.homed {
+            match motion_type {
+                MotionType::Print => return Err(MotionError::Other("Not homed".into()).into()),
+                MotionType::Travel => tracing::warn!("Travel move before homing, position is unknown"),
+                MotionType::Home | MotionType::Extruder | MotionType::Probe => {}
+            }
+        }
+        
+        // The executor frees a slot as soon as it starts the oldest segment
+        if self.motion_queue.is_full() {
+            return Err(MotionError::QueueFull.into());
+        }
+        
+        // Moves are planned from the end of the last queued segment
+        let start = self.get_planned_position();
+        
         // Calculate move distance
-        let distance = self.calculate_distance(&self.current_position, &target);
+        let distance = self.calculate_distance(&start, &target);
         
         // Skip very small moves
         if distance < self.config.minimum_step_distance {
@@ -168,36 +597,63 @@ impl MotionPlanner {
             return Ok(());
         }
         
-        // Calculate acceleration-limited feedrate
-        let limited_feedrate = self.limit_feedrate_by_acceleration(&target, feedrate);
+        // Limits for this type of move, then the acceleration-limited feedrate
+        let limits = self.config.motion_types.get(&motion_type).copied();
+        let max_acceleration = limits.map_or(f64::INFINITY, |limits| limits.max_acceleration);
+        let feedrate = limits.map_or(feedrate, |limits| feedrate.min(limits.max_velocity));
+        let feedrate = self.limit_feedrate_by_flow(&start, &target, feedrate);
+        let limited_feedrate = self.limit_feedrate_by_acceleration(&start, &target, feedrate, max_acceleration);
+        
+        // Enter at the speed the corner with the previous segment allows;
+        // leave at full speed until a following segment says otherwise
+        let queued = self.motion_queue.len();
+        let previous_start = queued
+            .checked_sub(2)
+            .and_then(|index| self.motion_queue.with(index, |segment| segment.target))
+            .or(self.planner_state.current_segment.as_ref().map(|segment| segment.target))
+            .unwrap_or(self.current_position);
+        let (entry_speed, corner_changed) = queued
+            .checked_sub(1)
+            .and_then(|index| {
+                self.motion_queue.with(index, |previous| {
+                    let entry_speed = self.junction_speed(&previous_start, previous, &target, limited_feedrate, motion_type);
+                    let tolerance = previous.exit_speed.max(f64::EPSILON) * REPLAN_SPEED_TOLERANCE;
+                    (entry_speed, (entry_speed - previous.exit_speed).abs() > tolerance)
+                })
+            })
+            .unwrap_or((0.0, false));
         
         // Create motion segment
-        let segment = MotionSegment {
+        let mut segment = self.segment_pool.alloc();
+        *segment = MotionSegment {
             target,
             feedrate: limited_feedrate,
-            acceleration: self.calculate_acceleration(&target),
+            acceleration: self.calculate_acceleration(&start, &target).min(max_acceleration),
             distance,
             duration: distance / limited_feedrate,
+            entry_speed,
+            exit_speed: limited_feedrate,
             motion_type,
+            is_curve: false,
+            bypass_shaper,
+            label: self.segment_label.clone(),
+            sequence_number: NEXT_SEGMENT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
         };
         
         tracing::debug!(
             "Planned {} move: {:.3}mm @ {:.1}mm/s",
-            match motion_type {
-                MotionType::Print => "print",
-                MotionType::Travel => "travel",
-                MotionType::Home => "home",
-                MotionType::Extruder => "extruder",
-            },
+            motion_type.as_str(),
             distance,
             limited_feedrate
         );
         
         // Add to queue
-        self.motion_queue.push_back(segment);
+        self.motion_queue.push(segment)?;
+        self.publish_stats();
         
-        // Trigger replanning if queue has enough moves
-        if self.motion_queue.len() >= self.config.lookahead_buffer_size / 2 {
+        // Trigger replanning if queue has enough moves, or a corner
+        // invalidated the speed the previous segment planned to leave at
+        if corner_changed || self.motion_queue.len() >= self.config.lookahead_buffer_size / 2 {
             self.replan_queue().await?;
         }
         
@@ -214,21 +670,28 @@ impl MotionPlanner {
         (dx * dx + dy * dy + dz * dz + de * de).sqrt()
     }
 
-    /// Limit feedrate based on acceleration capabilities
-    fn limit_feedrate_by_acceleration(&self, target: &[f64; 4], requested_feedrate: f64) -> f64 {
+    /// Limit feedrate based on acceleration capabilities, with the toolhead
+    /// accelerating at most `max_acceleration`
+    fn limit_feedrate_by_acceleration(
+        &self,
+        start: &[f64; 4],
+        target: &[f64; 4],
+        requested_feedrate: f64,
+        max_acceleration: f64,
+    ) -> f64 {
         // Calculate unit vector for this move
-        let distance = self.calculate_distance(&self.current_position, target);
+        let distance = self.calculate_distance(start, target);
         if distance == 0.0 {
             return requested_feedrate;
         }
         
-        let dx = (target[0] - self.current_position[0]) / distance;
-        let dy = (target[1] - self.current_position[1]) / distance;
-        let dz = (target[2] - self.current_position[2]) / distance;
-        let de = (target[3] - self.current_position[3]) / distance;
+        let dx = (target[0] - start[0]) / distance;
+        let dy = (target[1] - start[1]) / distance;
+        let dz = (target[2] - start[2]) / distance;
+        let de = (target[3] - start[3]) / distance;
         
         // Find limiting acceleration for each axis
-        let mut max_acceleration = f64::INFINITY;
+        let mut max_acceleration = max_acceleration;
         for i in 0..4 {
             let axis_component = match i {
                 0 => dx.abs(),
@@ -252,35 +715,153 @@ impl MotionPlanner {
         requested_feedrate.min(acceleration_limited_feedrate)
     }
 
+    /// Limit feedrate so extrusion stays within the hotend's volumetric speed
+    fn limit_feedrate_by_flow(&self, start: &[f64; 4], target: &[f64; 4], requested_feedrate: f64) -> f64 {
+        let distance = self.calculate_distance(start, target);
+        let extruded = target[3] - start[3];
+        if self.config.max_volumetric_speed <= 0.0 || distance == 0.0 || extruded <= 0.0 {
+            return requested_feedrate;
+        }
+        
+        let filament_area = std::f64::consts::PI / 4.0 * self.config.filament_diameter.powi(2);
+        let flow = requested_feedrate * filament_area * extruded / distance;
+        if flow <= self.config.max_volumetric_speed {
+            return requested_feedrate;
+        }
+        tracing::debug!("Flow {:.1}mm³/s over the {:.1}mm³/s limit", flow, self.config.max_volumetric_speed);
+        requested_feedrate * self.config.max_volumetric_speed / flow
+    }
+
+    /// Set the filament diameter used for volumetric limits (M200)
+    pub fn set_filament_diameter(&mut self, diameter: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if !diameter.is_finite() || diameter <= 0.0 {
+            return Err(format!("Invalid filament diameter {}", diameter).into());
+        }
+        self.config.filament_diameter = diameter;
+        Ok(())
+    }
+
+    /// Set the volumetric speed limit, 0 to remove it (M203 E)
+    pub fn set_max_volumetric_speed(&mut self, speed: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if !speed.is_finite() || speed < 0.0 {
+            return Err(format!("Invalid volumetric speed {}", speed).into());
+        }
+        self.config.max_volumetric_speed = speed;
+        Ok(())
+    }
+
+    /// Set the linear advance K-factor, 0 to disable it (M900 K)
+    pub fn set_linear_advance(&mut self, k_factor: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if !k_factor.is_finite() || k_factor < 0.0 {
+            return Err(format!("Invalid linear advance factor {}", k_factor).into());
+        }
+        self.config.linear_advance_k = (k_factor > 0.0).then_some(k_factor);
+        self.step_generator
+            .lock()
+            .unwrap()
+            .set_linear_advance(self.config.linear_advance_k.map(|k_factor| LinearAdvance { k_factor }));
+        Ok(())
+    }
+
+    /// Use `shaper` for input shaping, `None` to turn it off (M593)
+    pub fn set_input_shaper(&mut self, shaper: Option<ShaperPreset>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(shaper) = &shaper {
+            shaper.validate()?;
+            tracing::info!("Input shaper {:?} at {:.1}Hz, damping {:.3}", shaper.shaper_type, shaper.frequency, shaper.damping);
+        }
+        self.config.input_shaper = shaper;
+        Ok(())
+    }
+
     /// Calculate appropriate acceleration for a move
-    fn calculate_acceleration(&self, target: &[f64; 4]) -> f64 {
+    fn calculate_acceleration(&self, start: &[f64; 4], target: &[f64; 4]) -> f64 {
         // Weighted average based on axis movement
-        let distance = self.calculate_distance(&self.current_position, target);
+        let distance = self.calculate_distance(start, target);
         if distance == 0.0 {
             return self.config.max_acceleration[0];
         }
         
-        let dx = (target[0] - self.current_position[0]).abs() / distance;
-        let dy = (target[1] - self.current_position[1]).abs() / distance;
-        let dz = (target[2] - self.current_position[2]).abs() / distance;
-        let de = (target[3] - self.current_position[3]).abs() / distance;
+        let dx = (target[0] - start[0]).abs() / distance;
+        let dy = (target[1] - start[1]).abs() / distance;
+        let dz = (target[2] - start[2]).abs() / distance;
+        let de = (target[3] - start[3]).abs() / distance;
         
-        let weighted_accel = 
-            dx * self.config.max_acceleration[0] +
+        dx * self.config.max_acceleration[0] +
             dy * self.config.max_acceleration[1] +
             dz * self.config.max_acceleration[2] +
-            de * self.config.max_acceleration[3];
+            de * self.config.max_acceleration[3]
+    }
+
+    /// Highest speed at which the toolhead can go from `previous` into a
+    /// `motion_type` move to `target` without exceeding the per-axis jerk
+    /// limits or the junction deviation of that type
+    ///
+    /// Probing moves are slow enough to ignore jerk.
+    fn junction_speed(
+        &self,
+        previous_start: &[f64; 4],
+        previous: &MotionSegment,
+        target: &[f64; 4],
+        feedrate: f64,
+        motion_type: MotionType,
+    ) -> f64 {
+        let start = previous.target;
+        let distance = self.calculate_distance(&start, target);
+        let mut speed = previous.feedrate.min(feedrate);
+        if previous.distance <= 0.0 || distance <= 0.0 || motion_type == MotionType::Probe {
+            return speed;
+        }
+        
+        let mut cos_angle = 0.0;
+        for i in 0..4 {
+            let previous_direction = (previous.target[i] - previous_start[i]) / previous.distance;
+            let direction = (target[i] - start[i]) / distance;
+            cos_angle += previous_direction * direction;
+            let change = (direction - previous_direction).abs();
+            if change > f64::EPSILON {
+                speed = speed.min(self.config.max_jerk[i] / change);
+            }
+        }
         
-        weighted_accel
+        // Speed at which the corner is taken on an arc `junction_deviation` from it
+        if let Some(limits) = self.config.motion_types.get(&motion_type)
+            && limits.junction_deviation > 0.0
+        {
+            let sin_half = ((1.0 + cos_angle.clamp(-1.0, 1.0)) / 2.0).sqrt();
+            if sin_half < 1.0 - f64::EPSILON {
+                let acceleration = limits.max_acceleration.min(previous.acceleration);
+                speed = speed.min((acceleration * limits.junction_deviation * sin_half / (1.0 - sin_half)).sqrt());
+            }
+        }
+        speed
+    }
+
+    /// Label the segments planned from now on, `None` to stop labeling
+    pub fn set_segment_label(&mut self, label: Option<String>) {
+        self.segment_label = label;
+    }
+
+    /// Set the speed limit for `motion_type` (M203 T V)
+    pub fn set_motion_type_max_velocity(&mut self, motion_type: MotionType, velocity: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if !velocity.is_finite() || velocity <= 0.0 {
+            return Err(format!("Invalid {} velocity {}", motion_type.as_str(), velocity).into());
+        }
+        let limits = self.config.motion_types.entry(motion_type).or_insert(MotionTypeConfig {
+            max_velocity: velocity,
+            max_acceleration: f64::INFINITY,
+            junction_deviation: 0.0,
+        });
+        limits.max_velocity = velocity;
+        Ok(())
     }
 
     /// Replan the motion queue for optimal jerk and acceleration
     /// 
-    /// This implements lookahead planning to smooth motion between segments
+    /// This implements lookahead planning to smooth motion between segments:
+    /// a backward pass limits each segment's entry speed to what it can
+    /// decelerate from, then a forward pass limits each exit speed to what
+    /// it can accelerate to. Junctions end up with matching speeds.
     async fn replan_queue(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Simple implementation - in production, this would implement
-        // junction deviation, S-curve acceleration, and advanced lookahead
-        
         let queue_len = self.motion_queue.len();
         if queue_len < 2 {
             return Ok(());
@@ -288,10 +869,123 @@ impl MotionPlanner {
         
         tracing::debug!("Replanning {} motion segments", queue_len);
         
-        // For now, we'll just ensure smooth velocity transitions
-        // A full implementation would calculate optimal junction speeds
-        // based on centripetal acceleration and configured jerk limits
+        self.plan_speeds();
+        // Curves accelerate more gently, which can only lower the speeds
+        if self.mark_curves() {
+            self.plan_speeds();
+        }
+        self.publish_segments();
+        
+        Ok(())
+    }
+
+    fn plan_speeds(&mut self) {
+        let queue_len = self.motion_queue.len();
+        let reachable = |speed: f64, segment: &MotionSegment| {
+            (speed * speed + 2.0 * segment.acceleration * segment.distance).sqrt()
+        };
+        
+        // Backward pass: the last segment keeps its planned exit speed
+        let mut next_entry = f64::INFINITY;
+        for i in (0..queue_len).rev() {
+            self.motion_queue.with_mut(i, |segment| {
+                segment.exit_speed = segment.exit_speed.min(next_entry);
+                segment.entry_speed = segment.entry_speed.min(reachable(segment.exit_speed, segment));
+                next_entry = segment.entry_speed;
+            });
+        }
+        
+        // Forward pass: each segment leaves at the speed the next one enters at
+        let mut previous_exit = f64::INFINITY;
+        for i in 0..queue_len {
+            let mut entry_speed = 0.0;
+            self.motion_queue.with_mut(i, |segment| {
+                segment.entry_speed = segment.entry_speed.min(previous_exit);
+                segment.exit_speed = segment.exit_speed.min(reachable(segment.entry_speed, segment));
+                entry_speed = segment.entry_speed;
+                previous_exit = segment.exit_speed;
+            });
+            if i > 0 {
+                self.motion_queue.with_mut(i - 1, |previous| previous.exit_speed = entry_speed);
+            }
+        }
+    }
+
+    /// Flag queued segments on either side of a gentle bend between short
+    /// segments as curve segments, scaling their acceleration
+    ///
+    /// Returns whether any segment was newly flagged.
+    fn mark_curves(&mut self) -> bool {
+        let mut start = self
+            .planner_state
+            .current_segment
+            .as_ref()
+            .map_or(self.current_position, |segment| segment.target);
+        let mut directions = Vec::with_capacity(self.motion_queue.len());
+        self.motion_queue.for_each(|segment| {
+            directions.push([0, 1, 2].map(|axis| segment.target[axis] - start[axis]));
+            start = segment.target;
+        });
+        
+        let is_short = |v: &[f64; 3]| {
+            let length = v.iter().map(|c| c * c).sum::<f64>().sqrt();
+            length > 0.0 && length <= CURVE_MAX_SEGMENT_LENGTH
+        };
+        let mut curve = vec![false; directions.len()];
+        for i in 1..directions.len() {
+            let (a, b) = (&directions[i - 1], &directions[i]);
+            if !is_short(a) || !is_short(b) {
+                continue;
+            }
+            let dot: f64 = (0..3).map(|axis| a[axis] * b[axis]).sum();
+            let lengths = a.iter().map(|c| c * c).sum::<f64>().sqrt() * b.iter().map(|c| c * c).sum::<f64>().sqrt();
+            let angle = (dot / lengths).clamp(-1.0, 1.0).acos().to_degrees();
+            if angle > CURVE_MIN_ANGLE_DEG && angle < CURVE_MAX_ANGLE_DEG {
+                curve[i - 1] = true;
+                curve[i] = true;
+            }
+        }
         
+        let factor = self.config.curve_accel_factor;
+        let mut changed = false;
+        let mut curve = curve.into_iter();
+        self.motion_queue.for_each_mut(|segment| {
+            if curve.next() == Some(true) && !segment.is_curve {
+                segment.is_curve = true;
+                segment.acceleration *= factor;
+                changed = true;
+            }
+        });
+        changed
+    }
+
+    pub fn set_clog_detector(&mut self, detector: ClogDetector) {
+        self.clog_detector = Some(detector);
+    }
+
+    pub fn clog_detector(&self) -> Option<&ClogDetector> {
+        self.clog_detector.as_ref()
+    }
+
+    /// Record trajectories with `recorder`; clones made afterwards share it
+    pub fn set_motion_recorder(&mut self, recorder: MotionRecorder) {
+        self.recorder = recorder;
+    }
+
+    pub fn motion_recorder(&self) -> &MotionRecorder {
+        &self.recorder
+    }
+
+    pub fn set_extruder_sync_monitor(&mut self, monitor: ExtruderSyncMonitor) {
+        self.extruder_sync = Some(monitor);
+    }
+
+    /// Set the acceleration factor for curve segments planned from now on (M204 C)
+    pub fn set_curve_accel_factor(&mut self, factor: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if !factor.is_finite() || factor <= 0.0 || factor > 1.0 {
+            return Err(format!("Curve acceleration factor {} outside 0..1", factor).into());
+        }
+        self.config.curve_accel_factor = factor;
         Ok(())
     }
 
@@ -303,27 +997,72 @@ impl MotionPlanner {
         let now = std::time::Instant::now();
         let dt = (now - self.planner_state.last_update).as_secs_f64();
         self.planner_state.last_update = now;
+        match &self.planner_state.queue_state {
+            MotionQueueState::Error(reason) => return Err(format!("Motion queue stopped: {}", reason).into()),
+            MotionQueueState::Paused => return Ok(()),
+            _ => {}
+        }
+        self.planner_state.elapsed += dt;
         
         // If no active segment, check if we have queued moves
         if self.planner_state.current_segment.is_none() {
-            if let Some(segment) = self.motion_queue.pop_front() {
+            if let Some(segment) = self.motion_queue.pop() {
+                self.publish_stats();
+                // Errors are stringified so they aren't held across an await
+                let mut started = self.balance_motor_currents(&segment).await.map_err(|e| e.to_string());
+                if started.is_ok() {
+                    // Dispatch the step deltas for the whole segment to the MCU
+                    started = self.send_steps_to_hardware(&segment).await.map_err(|e| e.to_string());
+                }
+                if let Err(e) = started {
+                    let reason = format!("Move #{} to {:?} failed: {}", segment.sequence_number, segment.target, e);
+                    self.fail(reason.clone()).await;
+                    return Err(reason.into());
+                }
+                
+                let distance = self.calculate_distance(&self.current_position, &segment.target);
+                for i in 0..4 {
+                    self.current_velocity[i] = if distance > 0.0 {
+                        (segment.target[i] - self.current_position[i]) / distance * segment.feedrate
+                    } else {
+                        0.0
+                    };
+                }
+                
+                self.record_position(self.current_position);
                 self.planner_state.current_segment = Some(segment);
                 self.planner_state.segment_time = 0.0;
                 self.planner_state.active = true;
+                self.planner_state.queue_state = MotionQueueState::Running;
             } else {
+                if self.planner_state.active && self.planner_state.last_motion_type == Some(MotionType::Print) {
+                    self.planner_state.underruns += 1;
+                    tracing::warn!(
+                        "Motion queue underrun while printing (lookahead buffer {})",
+                        self.config.lookahead_buffer_size
+                    );
+                    self.publish_stats();
+                }
                 self.planner_state.active = false;
+                if self.planner_state.queue_state == MotionQueueState::Running {
+                    self.planner_state.queue_state = MotionQueueState::Idle;
+                }
+                self.current_velocity = [0.0; 4];
+                self.publish_position();
                 return Ok(());
             }
         }
         
         // Process current segment
-        if let Some(ref mut segment) = self.planner_state.current_segment {
+        if let Some(segment) = self.planner_state.current_segment.take() {
             self.planner_state.segment_time += dt;
             
             // Check if segment is complete
             if self.planner_state.segment_time >= segment.duration {
                 // Move complete - update current position
                 self.current_position = segment.target;
+                self.record_position(self.current_position);
+                self.planner_state.last_motion_type = Some(segment.motion_type);
                 
                 // Update printer state
                 {
@@ -335,9 +1074,7 @@ impl MotionPlanner {
                     ];
                 }
                 
-                // Clear current segment and prepare for next
-                self.planner_state.current_segment = None;
-                
+                // Current segment stays cleared to prepare for next
                 tracing::debug!(
                     "Completed move to [{:.3}, {:.3}, {:.3}, {:.3}]",
                     self.current_position[0],
@@ -359,45 +1096,208 @@ impl MotionPlanner {
                 ];
                 
                 // Generate steps for this position
-                self.generate_steps(&current_pos, segment).await?;
+                let result = self.generate_steps(&current_pos, &segment).await;
+                self.planner_state.current_segment = Some(segment);
+                result?;
             }
         }
         
+        self.publish_position();
         Ok(())
     }
 
-    /// Generate step commands for current interpolated position
+    /// Move the motors to the interpolated position, input shaped if a
+    /// shaper is set and the segment doesn't bypass it
+    ///
+    /// Shaping only applies to X and Y, where the frame rings.
     async fn generate_steps(
-        &self,
+        &mut self,
         position: &[f64; 4],
         segment: &MotionSegment,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // In a real implementation, this would:
-        // 1. Convert position to step counts for each motor
-        // 2. Calculate timing for step pulses
-        // 3. Send step commands to MCU
+        self.record_position(*position);
+        let shaped = match &self.config.input_shaper {
+            Some(shaper) if !segment.bypass_shaper => self.shaped_position(position, &shaper.impulses()),
+            _ => *position,
+        };
+        self.planner_state.motor_positions = self.get_kinematics().cartesian_to_motors(&[shaped[0], shaped[1], shaped[2]])?;
         
-        // For now, we'll just log the position
         tracing::trace!(
-            "Position: [{:.3}, {:.3}, {:.3}, {:.3}]",
-            position[0], position[1], position[2], position[3]
+            "Position: [{:.3}, {:.3}, {:.3}, {:.3}] shaped to [{:.3}, {:.3}] ({:?} #{} {})",
+            position[0], position[1], position[2], position[3],
+            shaped[0], shaped[1],
+            segment.motion_type,
+            segment.sequence_number,
+            segment.label.as_deref().unwrap_or("unlabeled")
         );
         
-        // In real implementation:
-        // self.hardware_manager.send_step_commands(position).await?;
+        Ok(())
+    }
+
+    /// Remember where the toolhead was commanded to be now, for shaping
+    fn record_position(&mut self, position: [f64; 4]) {
+        let now = self.planner_state.elapsed;
+        let horizon = self.config.input_shaper.as_ref()
+            .and_then(|shaper| shaper.impulses().last().map(|&(_, delay)| delay))
+            .unwrap_or(0.0);
+        let history = &mut self.planner_state.position_history;
+        history.push_back((now, position));
+        // Keep one sample from before the horizon to interpolate from
+        while history.len() > 2 && history[1].0 <= now - horizon {
+            history.pop_front();
+        }
+    }
+
+    /// `position` with X and Y replaced by the sum of the impulses applied
+    /// to where the toolhead was commanded to be at each delay
+    fn shaped_position(&self, position: &[f64; 4], impulses: &[(f64, f64)]) -> [f64; 4] {
+        let now = self.planner_state.elapsed;
+        let mut shaped = *position;
+        shaped[0] = 0.0;
+        shaped[1] = 0.0;
+        for &(amplitude, delay) in impulses {
+            let past = self.commanded_position_at(now - delay).unwrap_or(*position);
+            shaped[0] += amplitude * past[0];
+            shaped[1] += amplitude * past[1];
+        }
+        shaped
+    }
+
+    /// Commanded position at `time`, interpolated between recorded samples
+    fn commanded_position_at(&self, time: f64) -> Option<[f64; 4]> {
+        let history = &self.planner_state.position_history;
+        let after = history.iter().position(|&(sample_time, _)| sample_time >= time);
+        match after {
+            None => history.back().map(|&(_, position)| position),
+            Some(0) => history.front().map(|&(_, position)| position),
+            Some(index) => {
+                let (t0, p0) = history[index - 1];
+                let (t1, p1) = history[index];
+                let fraction = if t1 > t0 { (time - t0) / (t1 - t0) } else { 1.0 };
+                Some(std::array::from_fn(|axis| p0[axis] + (p1[axis] - p0[axis]) * fraction))
+            }
+        }
+    }
+
+    /// Motor positions for the last executed step, after input shaping
+    pub fn get_motor_positions(&self) -> [f64; 4] {
+        self.planner_state.motor_positions
+    }
+
+    /// Set the A and B motor currents the kinematics ask for on this move
+    ///
+    /// Only changed currents are sent; moves that don't turn either motor
+    /// keep the last ones.
+    async fn balance_motor_currents(&mut self, segment: &MotionSegment) -> Result<(), Box<dyn std::error::Error>> {
+        if self.config.motor_current_balance.is_none() {
+            return Ok(());
+        }
+        let kinematics = self.get_kinematics();
+        let start = kinematics.cartesian_to_motors(&[
+            self.current_position[0], self.current_position[1], self.current_position[2],
+        ])?;
+        let end = kinematics.cartesian_to_motors(&[segment.target[0], segment.target[1], segment.target[2]])?;
+        let Some(scale) = kinematics.motor_current_scale(&start, &end) else {
+            return Ok(());
+        };
+        
+        let currents = [0, 1].map(|motor| (self.config.run_current_ma[motor] as f64 * scale[motor]).round() as u32);
+        for motor in 0..2 {
+            if self.motor_currents.map(|last| last[motor]) == Some(currents[motor]) {
+                continue;
+            }
+            if let Err(e) = self.hardware_manager.set_stepper_current(motor, currents[motor]).await {
+                tracing::warn!("Could not set motor {} current: {}", motor, e);
+                return Ok(());
+            }
+        }
+        self.motor_currents = Some(currents);
+        Ok(())
+    }
+
+    /// Send step commands for a move from the current position to the segment target
+    async fn send_steps_to_hardware(&self, segment: &MotionSegment) -> Result<(), Box<dyn std::error::Error>> {
+        let target = &segment.target;
+        // Motor positions come from the kinematics; E is driven directly
+        let kinematics = self.get_kinematics();
+        let start = kinematics.cartesian_to_motors(&[
+            self.current_position[0], self.current_position[1], self.current_position[2],
+        ])?;
+        let end = kinematics.cartesian_to_motors(&[target[0], target[1], target[2]])?;
+        // E speed at either end of the move, for linear advance
+        let distance = self.calculate_distance(&self.current_position, target);
+        let extruded = target[3] - self.current_position[3];
+        let e_velocity = if extruded > 0.0 && distance > 0.0 {
+            (segment.entry_speed * extruded / distance, segment.exit_speed * extruded / distance)
+        } else {
+            (0.0, 0.0)
+        };
+        let (commands, stepped_e, e_steps, drift_events) = {
+            let mut step_generator = self.step_generator.lock().unwrap();
+            let previous_steps = step_generator.extruder_steps();
+            let commands = step_generator.generate_extruding_move(
+                &[start[0], start[1], start[2], self.current_position[3]],
+                &[end[0], end[1], end[2], target[3]],
+                e_velocity,
+            );
+            let drift_events = step_generator.take_drift_events();
+            (commands, step_generator.extruder_position(), step_generator.extruder_steps() - previous_steps, drift_events)
+        };
+        for drift in drift_events {
+            tracing::error!(
+                "Motor {} is {} steps from its commanded position ({} commanded, encoder at {})",
+                drift.axis, drift.delta, drift.commanded, drift.actual
+            );
+            let _ = self.event_tx.send(MotionEvent::PositionDrift(drift));
+        }
+        if let Some(detector) = &self.clog_detector {
+            detector.record(target[3], stepped_e);
+        }
+        if let Some(monitor) = &self.extruder_sync {
+            let compensation = monitor.take_compensation();
+            if compensation != 0 {
+                tracing::info!("Commanding {} extruder steps lost to a de-sync", compensation);
+                let direction = if compensation > 0 { 1 } else { 0 };
+                let command = format!("step E {} {}", compensation.unsigned_abs(), direction);
+                let _ = self.hardware_manager.send_command(&command).await;
+            }
+            monitor.record_commanded(e_steps);
+        }
+        for command in commands {
+            let _ = self.hardware_manager.send_command(&command.to_mcu_command()).await;
+        }
         
         Ok(())
     }
 
+    /// Motor steps per mm for [X, Y, Z, E]
+    pub fn steps_per_mm(&self) -> [f64; 4] {
+        self.step_generator.lock().unwrap().current_steps_per_mm()
+    }
+
+    /// Step generator shared by every clone, for feeding it encoder readings
+    pub fn step_generator(&self) -> Arc<Mutex<StepGenerator>> {
+        self.step_generator.clone()
+    }
+
+    /// Latest encoder drift of each motor with an encoder
+    pub fn position_drift(&self) -> Vec<StepPositionDrift> {
+        self.step_generator.lock().unwrap().position_drift()
+    }
+
+    /// Use new steps/mm for all following moves, in every clone
+    pub fn set_steps_per_mm(&mut self, steps_per_mm: [f64; 4]) {
+        self.step_generator.lock().unwrap().set_steps_per_mm(steps_per_mm);
+    }
+
     /// Queue a homing operation
     pub async fn plan_home(&mut self, axes: Option<[bool; 3]>) -> Result<(), Box<dyn std::error::Error>> {
-        let target = [0.0, 0.0, 0.0, self.current_position[3]]; // Keep E position
         let axes = axes.unwrap_or([true, true, true]); // Home all by default
         
-        // Create home move for each axis
+        // Create home move for each axis, keeping E position
         for (i, &home_axis) in axes.iter().enumerate() {
             if home_axis {
-                let mut home_target = self.current_position;
+                let mut home_target = self.get_planned_position();
                 home_target[i] = 0.0; // Move to home position
                 
                 self.plan_linear_move(
@@ -417,7 +1317,7 @@ impl MotionPlanner {
         target_e: f64,
         feedrate: f64,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut target = self.current_position;
+        let mut target = self.get_planned_position();
         target[3] = target_e;
         
         self.plan_linear_move(
@@ -429,20 +1329,898 @@ impl MotionPlanner {
         Ok(())
     }
 
+    /// Whether a segment is executing
+    pub fn is_active(&self) -> bool {
+        self.planner_state.current_segment.is_some()
+    }
+
     /// Get current motion queue length
     pub fn queue_length(&self) -> usize {
         self.motion_queue.len()
     }
 
+    /// Whether the queue is full, so the next move would be refused with
+    /// `MotionError::QueueFull`
+    pub fn is_queue_full(&self) -> bool {
+        self.motion_queue.is_full()
+    }
+
+    /// Copies of the segments waiting for execution, oldest first
+    pub fn get_queue(&self) -> Vec<MotionSegment> {
+        let mut segments = Vec::with_capacity(self.motion_queue.len());
+        self.motion_queue.for_each(|segment| segments.push(MotionSegment::clone(segment)));
+        segments
+    }
+
+    pub fn queue_state(&self) -> &MotionQueueState {
+        &self.planner_state.queue_state
+    }
+
+    /// Hold execution where it is until `resume`; refused while stopped by
+    /// an error
+    pub fn pause(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let MotionQueueState::Error(reason) = &self.planner_state.queue_state {
+            return Err(format!("Motion queue stopped: {}", reason).into());
+        }
+        self.planner_state.queue_state = MotionQueueState::Paused;
+        self.current_velocity = [0.0; 4];
+        Ok(())
+    }
+
+    /// Continue after `pause`; an error must be cleared with `clear_error`
+    /// instead
+    pub fn resume(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.planner_state.queue_state {
+            MotionQueueState::Error(reason) => Err(format!("Motion queue stopped: {}", reason).into()),
+            MotionQueueState::Paused => {
+                self.planner_state.last_update = std::time::Instant::now();
+                self.planner_state.queue_state = if self.planner_state.current_segment.is_some() {
+                    MotionQueueState::Running
+                } else {
+                    MotionQueueState::Idle
+                };
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop the queue and go back to `Idle` after an error
+    pub async fn clear_error(&mut self) {
+        if !matches!(self.planner_state.queue_state, MotionQueueState::Error(_)) {
+            return;
+        }
+        self.clear_queue();
+        self.planner_state.queue_state = MotionQueueState::Idle;
+        self.state.write().await.motion_error = None;
+        tracing::info!("Motion queue error cleared");
+    }
+
+    /// Stop executing after a move failed, keeping the rest of the queue
+    /// until `clear_error`
+    async fn fail(&mut self, reason: String) {
+        tracing::error!("Motion queue stopped: {}", reason);
+        self.planner_state.active = false;
+        self.planner_state.current_segment = None;
+        self.current_velocity = [0.0; 4];
+        self.state.write().await.motion_error = Some(reason.clone());
+        self.planner_state.queue_state = MotionQueueState::Error(reason);
+        self.publish_position();
+    }
+
     /// Clear all queued motions (emergency stop)
     pub fn clear_queue(&mut self) {
+        let had_motion = !self.motion_queue.is_empty() || self.planner_state.current_segment.is_some();
+        if had_motion && matches!(self.planner_state.queue_state, MotionQueueState::Running | MotionQueueState::Paused) {
+            self.planner_state.queue_state = MotionQueueState::Cancelled;
+        }
         self.motion_queue.clear();
+        self.publish_stats();
         self.planner_state.current_segment = None;
         self.planner_state.segment_time = 0.0;
+        self.planner_state.position_history.clear();
+        self.current_velocity = [0.0; 4];
     }
 
     /// Set current position (used after homing)
     pub fn set_position(&mut self, position: [f64; 4]) {
         self.current_position = position;
+        // No motion led here, so there is nothing to shape
+        self.planner_state.position_history.clear();
+        self.publish_position();
+    }
+
+    /// Forget all motion and the position, e.g. after a print ends or is
+    /// cancelled; the printer must be homed again before printing
+    pub async fn reset(&mut self) {
+        self.clear_queue();
+        self.current_position = [0.0; 4];
+        self.state.write().await.homed = false;
+        self.publish_position();
+    }
+
+    /// Mark the position as known after homing
+    ///
+    /// Homing is tracked in `PrinterState::homed`, so every clone of the
+    /// planner sees it.
+    pub async fn set_homed(&mut self, position: [f64; 4]) {
+        self.current_position = position;
+        self.planner_state.position_history.clear();
+        self.state.write().await.homed = true;
+        self.publish_position();
+    }
+
+    /// Mark the position as unknown, e.g. once the motors are released,
+    /// keeping queued motion
+    pub async fn clear_homed(&mut self) {
+        self.state.write().await.homed = false;
+    }
+
+    pub async fn is_homed(&self) -> bool {
+        self.state.read().await.homed
+    }
+
+    /// Get the position at the end of the last queued segment
+    ///
+    /// New moves are planned from here so that consecutive queued moves
+    /// chain together instead of all starting at the executed position
+    pub fn get_planned_position(&self) -> [f64; 4] {
+        self.motion_queue
+            .len()
+            .checked_sub(1)
+            .and_then(|last| self.motion_queue.with(last, |segment| segment.target))
+            .or(self.planner_state.current_segment.as_ref().map(|segment| segment.target))
+            .unwrap_or(self.current_position)
+    }
+
+    /// Get where the toolhead is now, part-way along the executing segment
+    pub fn get_interpolated_position(&self) -> [f64; 4] {
+        match &self.planner_state.current_segment {
+            Some(segment) if segment.duration > 0.0 => {
+                let progress = (self.planner_state.segment_time / segment.duration).min(1.0);
+                std::array::from_fn(|i| {
+                    self.current_position[i] + (segment.target[i] - self.current_position[i]) * progress
+                })
+            }
+            _ => self.current_position,
+        }
+    }
+
+    /// Get the velocity of each axis for the segment being executed (mm/s)
+    pub fn get_current_velocity(&self) -> [f64; 4] {
+        self.current_velocity
+    }
+
+    /// Get motion configuration
+    pub fn get_config(&self) -> &MotionConfig {
+        &self.config
     }
-}
\ No newline at end of file
+
+    /// Set how many segments the planner looks ahead over (at least 4)
+    pub fn set_lookahead_buffer_size(&mut self, size: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if size < MIN_LOOKAHEAD_BUFFER_SIZE {
+            return Err(format!(
+                "Lookahead buffer size {} is below the minimum of {}",
+                size, MIN_LOOKAHEAD_BUFFER_SIZE
+            ).into());
+        }
+        
+        if size < self.motion_queue.len() {
+            return Err(format!(
+                "{} moves are queued, more than a lookahead buffer of {} holds",
+                self.motion_queue.len(), size
+            ).into());
+        }
+        
+        self.config.lookahead_buffer_size = size;
+        if self.motion_queue.capacity() != size {
+            let mut queue = SegmentQueue::new(size);
+            while let Some(segment) = self.motion_queue.pop() {
+                queue.push(segment)?;
+            }
+            self.motion_queue = queue;
+        }
+        if self.segment_pool.capacity() != size * 2 {
+            self.segment_pool = SegmentPool::new(size * 2);
+        }
+        Ok(())
+    }
+
+    /// Number of times the queue ran dry in the middle of printing
+    pub fn get_underrun_count(&self) -> usize {
+        self.planner_state.underruns
+    }
+
+    /// Time taken to plan `n_moves` straight-line moves, for tracking
+    /// planner performance
+    ///
+    /// Runs on a copy of the planner, leaving this one untouched. The copy
+    /// drops its oldest segment whenever the queue fills, as if an
+    /// executor kept up.
+    pub async fn bench_throughput(&self, n_moves: usize) -> std::time::Duration {
+        let mut planner = self.clone();
+        planner.stats_tx = Arc::new(watch::channel(MotionPlannerStats::default()).0);
+        planner.clear_queue();
+        planner.set_position([0.0; 4]);
+        
+        let started = std::time::Instant::now();
+        for i in 1..=n_moves {
+            let target = [i as f64 * 0.1, 0.0, 0.0, i as f64 * 0.005];
+            if planner.motion_queue.is_full() {
+                planner.motion_queue.pop();
+            }
+            if let Err(e) = planner.plan_linear_move(target, 100.0, MotionType::Print).await {
+                tracing::warn!("Benchmark move {} failed: {}", i, e);
+                break;
+            }
+        }
+        started.elapsed()
+    }
+
+    /// Configure Z-hop for travel moves (height 0 disables it)
+    pub fn set_z_hop(&mut self, height: f64, speed: Option<f64>) {
+        self.config.z_hop_height = height.max(0.0);
+        if let Some(speed) = speed {
+            self.config.z_hop_speed = speed;
+        }
+    }
+
+    /// Get the active kinematics
+    pub fn get_kinematics(&self) -> Arc<dyn Kinematics> {
+        self.kinematics.lock().unwrap().handler.clone()
+    }
+
+    /// Type of the active kinematics
+    pub fn kinematics_type(&self) -> KinematicsType {
+        self.kinematics.lock().unwrap().kinematics_type
+    }
+
+    /// Replace the active kinematics handler, for every clone of the planner
+    pub fn set_kinematics_handler(&mut self, kinematics_type: KinematicsType, kinematics: Box<dyn Kinematics>) {
+        *self.kinematics.lock().unwrap() = ActiveKinematics {
+            kinematics_type,
+            handler: Arc::from(kinematics),
+        };
+        let _ = self.event_tx.send(MotionEvent::KinematicsChanged(kinematics_type));
+    }
+
+    /// Switch to a different kinematics type at runtime, built from the
+    /// printer configuration
+    ///
+    /// Waits for all queued motion to finish first. The printer must be
+    /// homed so the Cartesian position is known under the new kinematics.
+    /// Switching to or from SCARA resets the X and Y steps/mm to the
+    /// configured ones.
+    pub async fn set_kinematics(&mut self, kinematics_type: KinematicsType) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.is_homed().await {
+            return Err("Cannot change kinematics before the printer is homed".into());
+        }
+        let mut config = self.config.clone();
+        config.kinematics_type = kinematics_type;
+        let kinematics = build_kinematics(&config, self.hardware_manager.config())?;
+        
+        self.run_until(|planner| planner.queue_length() == 0 && !planner.is_active()).await?;
+        
+        tracing::info!("Switching kinematics from {:?} to {:?}", self.kinematics_type(), kinematics_type);
+        let steps_per_mm = configured_steps_per_mm(self.hardware_manager.config(), kinematics_type);
+        if steps_per_mm != self.config.steps_per_mm {
+            self.config.steps_per_mm = steps_per_mm;
+            self.set_steps_per_mm(steps_per_mm);
+        }
+        self.set_kinematics_handler(kinematics_type, kinematics);
+        
+        Ok(())
+    }
+
+    /// Advance the planner until `done`, giving up after
+    /// `queue_drain_timeout_secs`
+    pub async fn run_until(&mut self, done: impl Fn(&Self) -> bool) -> Result<(), Box<dyn std::error::Error>> {
+        let timeout_secs = self.config.queue_drain_timeout_secs;
+        let deadline = (timeout_secs > 0.0)
+            .then(|| std::time::Instant::now() + std::time::Duration::from_secs_f64(timeout_secs));
+        loop {
+            self.update().await?;
+            if done(self) {
+                return Ok(());
+            }
+            if deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline) {
+                return Err(format!(
+                    "Timed out after {}s waiting for {} queued moves",
+                    timeout_secs,
+                    self.queue_length()
+                ).into());
+            }
+            tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Set the frame skew correction
+    ///
+    /// Applies to moves executed from now on, including ones already queued.
+    pub fn set_skew_correction(&mut self, skew: SkewCorrection) -> Result<(), Box<dyn std::error::Error>> {
+        let kinematics_type = self.kinematics_type();
+        if !matches!(kinematics_type, KinematicsType::Cartesian | KinematicsType::CoreXY) {
+            return Err(format!("Skew correction is not supported for {:?} kinematics", kinematics_type).into());
+        }
+        
+        if !skew.is_negligible() {
+            tracing::info!("Skew correction XY {:.6} XZ {:.6} YZ {:.6} rad", skew.xy, skew.xz, skew.yz);
+        }
+        self.config.skew_correction = skew;
+        let mut config = self.config.clone();
+        config.kinematics_type = kinematics_type;
+        self.kinematics.lock().unwrap().handler = Arc::from(build_kinematics(&config, self.hardware_manager.config())?);
+        Ok(())
+    }
+
+    /// Subscribe to planner events
+    pub fn subscribe_events(&self) -> broadcast::Receiver<MotionEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Pool of reusable segment allocations
+    pub fn get_segment_pool(&self) -> &Arc<SegmentPool> {
+        &self.segment_pool
+    }
+
+    /// Create a queue for handing finished segments to an executor running
+    /// on another task, sized to the lookahead buffer
+    pub fn create_execution_queue(&self) -> (PlannerHandle, ExecutorHandle) {
+        segment_queue(self.config.lookahead_buffer_size)
+    }
+
+    /// Move queued segments, oldest first, into an execution queue until it
+    /// is full; returns how many were moved
+    ///
+    /// Handed-off segments are final and no longer replanned.
+    pub fn hand_off(&mut self, queue: &mut PlannerHandle) -> usize {
+        let mut moved = 0;
+        while !queue.is_full() {
+            let Some(segment) = self.motion_queue.pop() else {
+                break;
+            };
+            if queue.push(segment.into_inner()).is_err() {
+                break;
+            }
+            moved += 1;
+        }
+        if moved > 0 {
+            self.publish_stats();
+        }
+        moved
+    }
+
+    /// Current queue statistics
+    pub fn get_stats(&self) -> MotionPlannerStats {
+        MotionPlannerStats {
+            queue_depth: self.motion_queue.len(),
+            underruns: self.planner_state.underruns,
+        }
+    }
+
+    /// Watch the queue statistics as the planner runs
+    pub fn subscribe_stats(&self) -> watch::Receiver<MotionPlannerStats> {
+        self.stats_tx.subscribe()
+    }
+
+    /// Publish the queue statistics and segments after the queue changed
+    fn publish_stats(&self) {
+        self.stats_tx.send_replace(self.get_stats());
+        self.publish_segments();
+    }
+
+    /// Queued segments as of the last time they were planned or executed,
+    /// in whichever clone runs the queue
+    pub fn published_segments(&self) -> Vec<MotionSegment> {
+        self.segments_tx.borrow().clone()
+    }
+
+    fn publish_segments(&self) {
+        self.segments_tx.send_replace(self.get_queue());
+    }
+
+    /// Watch the interpolated toolhead position as segments execute
+    pub fn subscribe_position(&self) -> watch::Receiver<[f64; 4]> {
+        self.position_tx.subscribe()
+    }
+
+    fn publish_position(&self) {
+        let position = self.get_interpolated_position();
+        let label = self.planner_state.current_segment.as_ref().and_then(|segment| segment.label.as_deref());
+        self.recorder.sample(position, self.current_velocity, label);
+        self.position_tx.send_replace(position);
+    }
+}
+
+/// Create the kinematics described by a motion config, including skew
+/// correction, with SCARA and delta geometry from `printer`
+fn build_kinematics(
+    config: &MotionConfig,
+    printer: &crate::config::Config,
+) -> Result<Box<dyn Kinematics>, Box<dyn std::error::Error>> {
+    match config.kinematics_type {
+        KinematicsType::Cartesian => {
+            let mut cartesian = CartesianKinematics::new(config.axis_limits);
+            cartesian.set_skew(config.skew_correction);
+            Ok(Box::new(cartesian))
+        }
+        KinematicsType::CoreXY => {
+            let mut corexy = CoreXYKinematics::new(config.axis_limits);
+            corexy.set_skew(config.skew_correction);
+            if let Some(balance) = config.motor_current_balance {
+                corexy.set_motor_current_balance(balance);
+            }
+            Ok(Box::new(corexy))
+        }
+        other => create_kinematics_of_type(other, printer, config.axis_limits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::motion::shaper_presets::{suggest_from_frequency, InputShaperType};
+
+    fn create_test_planner() -> (MotionPlanner, Arc<RwLock<PrinterState>>) {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState { homed: true, ..PrinterState::new() }));
+        let planner = MotionPlanner::new(
+            state.clone(),
+            HardwareManager::new(config.clone()),
+            MotionConfig::new_from_printer_config(&config),
+        );
+        (planner, state)
+    }
+
+    #[tokio::test]
+    async fn test_set_kinematics_requires_homing() {
+        let (mut planner, _state) = create_test_planner();
+        planner.clear_homed().await;
+        assert!(planner.set_kinematics(KinematicsType::CoreXY).await.is_err());
+        assert_eq!(planner.kinematics_type(), KinematicsType::Cartesian);
+    }
+
+    #[tokio::test]
+    async fn test_set_kinematics_switches_implementation() {
+        let (mut planner, _state) = create_test_planner();
+        let mut events = planner.subscribe_events();
+        
+        let motors = planner.get_kinematics().cartesian_to_motors(&[10.0, 5.0, 1.0]).unwrap();
+        assert_eq!(motors, [10.0, 5.0, 1.0, 0.0]);
+        
+        planner.plan_linear_move([1.0, 0.0, 0.0, 0.0], 1000.0, MotionType::Travel).await.unwrap();
+        planner.set_kinematics(KinematicsType::CoreXY).await.unwrap();
+        
+        // The queued move finished before the switch
+        assert_eq!(planner.queue_length(), 0);
+        assert_eq!(planner.get_planned_position(), [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(events.try_recv().unwrap(), MotionEvent::KinematicsChanged(KinematicsType::CoreXY));
+        
+        planner.plan_linear_move([10.0, 5.0, 1.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        assert_eq!(planner.queue_length(), 1);
+        assert_eq!(planner.kinematics_type(), KinematicsType::CoreXY);
+        let motors = planner.get_kinematics().cartesian_to_motors(&[10.0, 5.0, 1.0]).unwrap();
+        assert_eq!(motors, [15.0, 5.0, 1.0, 0.0]);
+    }
+
+    #[tokio::test]
+    async fn test_set_kinematics_gives_up_on_stuck_queue() {
+        let (mut planner, _state) = create_test_planner();
+        planner.config.queue_drain_timeout_secs = 0.05;
+        
+        // A 100 s move can't drain in time
+        planner.plan_linear_move([100.0, 0.0, 0.0, 0.0], 1.0, MotionType::Travel).await.unwrap();
+        let error = planner.set_kinematics(KinematicsType::CoreXY).await.unwrap_err();
+        assert!(error.to_string().contains("Timed out"), "{}", error);
+        assert_eq!(planner.kinematics_type(), KinematicsType::Cartesian);
+    }
+
+    #[tokio::test]
+    async fn test_set_kinematics_from_config() {
+        let (mut planner, state) = create_test_planner();
+
+        // printer.toml has no [delta] or [scara] section
+        for kinematics_type in [KinematicsType::Delta, KinematicsType::Scara, KinematicsType::Hangprinter] {
+            assert!(planner.set_kinematics(kinematics_type).await.is_err());
+        }
+        assert_eq!(planner.kinematics_type(), KinematicsType::Cartesian);
+
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.delta = Some(crate::config::DeltaConfig {
+            diagonal_rod: 250.0,
+            radius: 120.0,
+            print_radius: 100.0,
+            endstop_corrections: [0.0; 3],
+            tower_angle_corrections: [0.0; 3],
+        });
+        let mut planner = MotionPlanner::new(state, HardwareManager::new(config.clone()), MotionConfig::new_from_printer_config(&config));
+        let observer = planner.clone();
+        planner.set_kinematics(KinematicsType::Delta).await.unwrap();
+
+        // Clones convert with the delta's carriage heights too
+        let expected = crate::motion::kinematics::DeltaKinematics::from_config(config.delta.as_ref().unwrap())
+            .cartesian_to_motors(&[10.0, 5.0, 1.0])
+            .unwrap();
+        assert_eq!(observer.kinematics_type(), KinematicsType::Delta);
+        assert_eq!(observer.get_kinematics().cartesian_to_motors(&[10.0, 5.0, 1.0]).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_corner_triggers_replan() {
+        let (mut planner, _state) = create_test_planner();
+        
+        // Straight line: the first segment keeps its optimistic exit speed
+        planner.plan_linear_move([10.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        planner.plan_linear_move([20.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        let queue = planner.get_queue();
+        assert_eq!(queue[0].entry_speed, 0.0);
+        assert_eq!(queue[0].exit_speed, 100.0);
+        assert_eq!(queue[1].entry_speed, 100.0);
+        
+        // A 90° corner limits the junction speed and the plan is updated
+        planner.plan_linear_move([20.0, 10.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        let queue = planner.get_queue();
+        assert_eq!(queue.len(), 3);
+        let corner = queue[2].entry_speed;
+        assert!(corner > 0.0 && corner < 100.0);
+        assert_eq!(queue[1].exit_speed, corner);
+    }
+    
+    #[tokio::test]
+    async fn test_queue_error_state() {
+        let (mut planner, state) = create_test_planner();
+        // Arms reaching 200mm at most
+        planner.set_kinematics_handler(KinematicsType::Scara, Box::new(ScaraKinematics::new(100.0, 100.0, 100.0, 100.0)));
+        planner.set_homed([100.0, 50.0, 0.0, 0.0]).await;
+        planner.plan_linear_move([120.0, 50.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        planner.plan_linear_move([300.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        planner.plan_linear_move([100.0, 50.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+
+        planner.update().await.unwrap();
+        assert_eq!(planner.queue_state(), &MotionQueueState::Running);
+        let error = loop {
+            if let Err(e) = planner.update().await {
+                break e.to_string();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        };
+        assert!(error.contains("outside SCARA reach"), "{}", error);
+        assert!(matches!(planner.queue_state(), MotionQueueState::Error(reason) if *reason == error));
+        assert_eq!(state.read().await.motion_error.as_deref(), Some(error.as_str()));
+
+        // Stopped: the rest of the queue waits and nothing resumes it
+        assert_eq!(planner.queue_length(), 1);
+        assert!(!planner.is_active());
+        assert!(planner.update().await.is_err());
+        assert!(planner.resume().is_err());
+        assert!(planner.pause().is_err());
+        assert!(planner.plan_linear_move([10.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.is_err());
+
+        planner.clear_error().await;
+        assert_eq!(planner.queue_state(), &MotionQueueState::Idle);
+        assert_eq!(planner.queue_length(), 0);
+        assert_eq!(state.read().await.motion_error, None);
+        planner.update().await.unwrap();
+        planner.plan_linear_move([100.0, 50.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume() {
+        let (mut planner, _state) = create_test_planner();
+        planner.plan_linear_move([10.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        planner.pause().unwrap();
+        planner.update().await.unwrap();
+        assert_eq!(planner.queue_state(), &MotionQueueState::Paused);
+        assert_eq!(planner.queue_length(), 1);
+
+        planner.resume().unwrap();
+        assert_eq!(planner.queue_state(), &MotionQueueState::Idle);
+        planner.update().await.unwrap();
+        assert_eq!(planner.queue_state(), &MotionQueueState::Running);
+        planner.clear_queue();
+        assert_eq!(planner.queue_state(), &MotionQueueState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_set_lookahead_buffer_size() {
+        let (mut planner, _state) = create_test_planner();
+        assert!(planner.set_lookahead_buffer_size(3).is_err());
+        assert_eq!(planner.get_config().lookahead_buffer_size, 16);
+        
+        planner.set_lookahead_buffer_size(32).unwrap();
+        assert_eq!(planner.get_config().lookahead_buffer_size, 32);
+        assert_eq!(planner.motion_queue.capacity(), 32);
+        
+        assert!(planner.bench_throughput(100).await > std::time::Duration::ZERO);
+        assert_eq!(planner.queue_length(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_plan_move_refuses_when_queue_full() {
+        let (mut planner, _state) = create_test_planner();
+        planner.set_lookahead_buffer_size(4).unwrap();
+        for i in 1..=4 {
+            planner.plan_linear_move([i as f64 * 10.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        }
+        assert!(planner.is_queue_full());
+
+        let error = planner.plan_linear_move([50.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<MotionError>(), Some(&MotionError::QueueFull));
+        assert_eq!(planner.queue_length(), 4);
+        assert_eq!(planner.get_planned_position()[0], 40.0);
+    }
+
+    #[tokio::test]
+    async fn test_queued_segments_come_from_pool() {
+        let (mut planner, _state) = create_test_planner();
+        let pool = planner.get_segment_pool().clone();
+        for i in 1..=4 {
+            planner.plan_linear_move([i as f64 * 10.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        }
+        assert_eq!(pool.available(), pool.capacity() - 4);
+
+        // Executing a segment keeps its allocation until the move is done
+        planner.update().await.unwrap();
+        assert_eq!(pool.available(), pool.capacity() - 4);
+
+        planner.clear_queue();
+        assert_eq!(pool.available(), pool.capacity());
+        assert_eq!(pool.get_misses(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_volumetric_speed_limit() {
+        let (mut planner, _state) = create_test_planner();
+        assert_eq!(planner.get_config().filament_diameter, 1.75);
+        planner.set_max_volumetric_speed(15.0).unwrap();
+        
+        // 10mm/s of 1.75mm filament is ~24mm³/s
+        planner.plan_linear_move([0.0, 0.0, 0.0, 10.0], 10.0, MotionType::Extruder).await.unwrap();
+        let segment = planner.get_queue()[0].clone();
+        let area = std::f64::consts::PI / 4.0 * 1.75 * 1.75;
+        assert!((segment.feedrate * area - 15.0).abs() < 1e-9, "{}", segment.feedrate);
+        assert!(segment.duration > 1.0);
+        
+        // Only the extruder's share of a print move counts
+        planner.plan_linear_move([100.0, 0.0, 0.0, 13.0], 300.0, MotionType::Print).await.unwrap();
+        let segment = &planner.get_queue()[1];
+        let flow = segment.feedrate * area * 3.0 / (100.0f64.powi(2) + 9.0).sqrt();
+        assert!((flow - 15.0).abs() < 1e-9, "{}", flow);
+        
+        // Retracts and unlimited hotends are left alone
+        planner.plan_linear_move([100.0, 0.0, 0.0, 3.0], 40.0, MotionType::Extruder).await.unwrap();
+        assert_eq!(planner.get_queue()[2].feedrate, 40.0);
+        planner.set_max_volumetric_speed(0.0).unwrap();
+        planner.plan_linear_move([100.0, 0.0, 0.0, 13.0], 10.0, MotionType::Extruder).await.unwrap();
+        assert_eq!(planner.get_queue()[3].feedrate, 10.0);
+        assert!(planner.set_filament_diameter(0.0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_print_moves_require_homing() {
+        let (mut planner, _state) = create_test_planner();
+        planner.plan_linear_move([5.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        planner.reset().await;
+        assert!(!planner.is_homed().await);
+        assert_eq!(planner.queue_length(), 0);
+        assert_eq!(planner.get_planned_position(), [0.0; 4]);
+        
+        let error = planner.plan_linear_move([10.0, 0.0, 0.0, 1.0], 100.0, MotionType::Print).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<MotionError>(), Some(&MotionError::Other("Not homed".into())));
+        assert_eq!(error.to_string(), "Not homed");
+        assert_eq!(planner.queue_length(), 0);
+        
+        // Travel is still allowed
+        planner.plan_linear_move([10.0, 0.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        assert_eq!(planner.queue_length(), 1);
+        
+        planner.clear_queue();
+        planner.set_homed([0.0, 0.0, 5.0, 0.0]).await;
+        planner.plan_linear_move([10.0, 0.0, 5.0, 1.0], 100.0, MotionType::Print).await.unwrap();
+        assert_eq!(planner.queue_length(), 1);
+    }
+
+    /// Execute for about `millis` of wall time, as the host loop would
+    async fn run_for(planner: &mut MotionPlanner, millis: u64) {
+        for _ in 0..millis / 5 {
+            planner.update().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_input_shaper_delays_motors() {
+        let (mut planner, _state) = create_test_planner();
+        planner.plan_linear_move([100.0, 0.0, 0.0, 0.0], 50.0, MotionType::Travel).await.unwrap();
+        run_for(&mut planner, 100).await;
+        assert_eq!(planner.get_motor_positions()[0], planner.get_interpolated_position()[0]);
+        
+        // A 20 Hz ZV shaper puts half the move 25 ms behind: 0.6 mm at 50 mm/s
+        let zv = ShaperPreset { shaper_type: InputShaperType::Zv, damping: 0.0, ..suggest_from_frequency(20.0) };
+        planner.set_input_shaper(Some(zv)).unwrap();
+        run_for(&mut planner, 100).await;
+        let lag = planner.get_interpolated_position()[0] - planner.get_motor_positions()[0];
+        assert!((lag - 0.625).abs() < 0.1, "lag {}", lag);
+    }
+
+    #[tokio::test]
+    async fn test_bypass_shaper_moves() {
+        let (mut planner, _state) = create_test_planner();
+        let zv = ShaperPreset { shaper_type: InputShaperType::Zv, damping: 0.0, ..suggest_from_frequency(20.0) };
+        planner.set_input_shaper(Some(zv)).unwrap();
+        
+        // 400 ms each, the second one unshaped
+        planner.plan_linear_move([20.0, 0.0, 0.0, 0.0], 50.0, MotionType::Travel).await.unwrap();
+        planner.plan_move_no_shaper([20.0, 20.0, 0.0, 0.0], 50.0, MotionType::Travel).await.unwrap();
+        let kinematics = planner.get_kinematics();
+        let motors_at = |position: [f64; 4]| kinematics.cartesian_to_motors(&[position[0], position[1], position[2]]).unwrap();
+        
+        run_for(&mut planner, 200).await;
+        let raw = motors_at(planner.get_interpolated_position());
+        assert!(raw[0] - planner.get_motor_positions()[0] > 0.3, "{:?} not delayed from {:?}", planner.get_motor_positions(), raw);
+        
+        run_for(&mut planner, 400).await;
+        let position = planner.get_interpolated_position();
+        assert!(position[1] > 0.0 && position[1] < 20.0, "{:?} not on the second move", position);
+        assert_eq!(planner.get_motor_positions(), motors_at(position));
+        
+        // Homing moves bypass the shaper too
+        planner.plan_home(Some([false, true, false])).await.unwrap();
+        assert!(planner.get_queue().iter().all(|segment| segment.bypass_shaper && segment.motion_type == MotionType::Home));
+    }
+
+    #[tokio::test]
+    async fn test_motion_type_limits() {
+        let (mut planner, _state) = create_test_planner();
+        planner.set_motion_type_max_velocity(MotionType::Travel, 250.0).unwrap();
+        planner.set_motion_type_max_velocity(MotionType::Print, 80.0).unwrap();
+        assert!(planner.set_motion_type_max_velocity(MotionType::Print, 0.0).is_err());
+        
+        planner.plan_linear_move([200.0, 0.0, 0.0, 0.0], 400.0, MotionType::Travel).await.unwrap();
+        planner.plan_linear_move([200.0, 200.0, 0.0, 10.0], 400.0, MotionType::Print).await.unwrap();
+        planner.plan_linear_move([200.0, 0.0, 0.0, 10.0], 400.0, MotionType::Home).await.unwrap();
+        planner.plan_linear_move([200.0, 0.0, -5.0, 10.0], 400.0, MotionType::Probe).await.unwrap();
+        
+        let queue = planner.get_queue();
+        let feedrates: Vec<f64> = queue.iter().map(|segment| segment.feedrate).collect();
+        assert_eq!(feedrates, [250.0, 80.0, HOME_MAX_VELOCITY, PROBE_MAX_VELOCITY]);
+        assert!(queue[3].bypass_shaper && queue[3].acceleration <= PROBE_MAX_ACCELERATION);
+        
+        // Probing turns the corner at full (probe) speed despite the jerk limits
+        assert_eq!(queue[3].entry_speed, PROBE_MAX_VELOCITY);
+    }
+
+    #[tokio::test]
+    async fn test_junction_deviation() {
+        let (mut planner, _state) = create_test_planner();
+        planner.config.motion_types.get_mut(&MotionType::Travel).unwrap().junction_deviation = 0.01;
+        planner.config.max_jerk = [1000.0; 4];
+        
+        planner.plan_linear_move([50.0, 0.0, 0.0, 0.0], 200.0, MotionType::Travel).await.unwrap();
+        planner.plan_linear_move([50.0, 50.0, 0.0, 0.0], 200.0, MotionType::Travel).await.unwrap();
+        
+        // 90° corner: sin(45°) = 0.707, so v² = a·0.01·0.707 / 0.293
+        let acceleration = planner.get_queue()[0].acceleration;
+        let expected = (acceleration * 0.01 * std::f64::consts::FRAC_1_SQRT_2 / (1.0 - std::f64::consts::FRAC_1_SQRT_2)).sqrt();
+        assert!((planner.get_queue()[1].entry_speed - expected).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_arc_segments_are_curves() {
+        let (mut planner, _state) = create_test_planner();
+        planner.set_lookahead_buffer_size(64).unwrap();
+        
+        // Half circle of radius 20 in 5° steps
+        let travel_acceleration = planner.config.motion_types[&MotionType::Travel].max_acceleration;
+        let mut base_accelerations = Vec::new();
+        let mut start = [0.0; 4];
+        for step in 1..=36 {
+            let angle = (step as f64 * 5.0).to_radians();
+            let target = [20.0 - 20.0 * angle.cos(), 20.0 * angle.sin(), 0.0, 0.0];
+            base_accelerations.push(planner.calculate_acceleration(&start, &target).min(travel_acceleration));
+            planner.plan_linear_move(target, 100.0, MotionType::Travel).await.unwrap();
+            start = target;
+        }
+        let segments = planner.published_segments();
+        assert_eq!(segments.len(), 36);
+        for (segment, base) in segments.iter().zip(base_accelerations) {
+            assert!(segment.is_curve);
+            assert!((segment.acceleration - base * DEFAULT_CURVE_ACCEL_FACTOR).abs() < 1e-6);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lines_and_corners_are_not_curves() {
+        let (mut planner, _state) = create_test_planner();
+        for target in [[1.0, 0.0], [2.0, 0.0], [3.0, 0.0], [3.0, 1.0], [3.0, 2.0], [3.0, 3.0], [2.0, 3.0], [1.0, 3.0]] {
+            planner.plan_linear_move([target[0], target[1], 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        }
+        assert_eq!(planner.queue_length(), 8);
+        assert!(planner.get_queue().iter().all(|segment| !segment.is_curve));
+        
+        assert!(planner.set_curve_accel_factor(1.5).is_err());
+        planner.set_curve_accel_factor(0.5).unwrap();
+        assert_eq!(planner.get_config().curve_accel_factor, 0.5);
+    }
+
+    /// MCU recording the commands it is sent
+    #[derive(Debug, Default)]
+    struct RecordingPort {
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl crate::hardware::McuPort for RecordingPort {
+        fn transact<'a>(&'a self, command: &'a str) -> crate::hardware::PortFuture<'a> {
+            self.commands.lock().unwrap().push(command.to_string());
+            Box::pin(async { Ok("ok".to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corexy_current_balance() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.printer.kinematics = "corexy".to_string();
+        config.printer.corexy_current_balance = true;
+        let port = Arc::new(RecordingPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut planner = MotionPlanner::new(state, hardware, MotionConfig::new_from_printer_config(&config));
+        planner.set_homed([0.0; 4]).await;
+
+        let mut currents_for = async |target: [f64; 4]| {
+            port.commands.lock().unwrap().clear();
+            planner.plan_linear_move(target, 200.0, MotionType::Travel).await.unwrap();
+            while planner.queue_length() > 0 || planner.is_active() {
+                planner.update().await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+            let commands = port.commands.lock().unwrap();
+            commands.iter().filter(|c| c.starts_with("set_tmc_current")).cloned().collect::<Vec<_>>()
+        };
+
+        // 45° turns only motor A, so B drops to half current
+        assert_eq!(
+            currents_for([10.0, 10.0, 0.0, 0.0]).await,
+            ["set_tmc_current stepper=stepper_x current=800", "set_tmc_current stepper=stepper_y current=400"]
+        );
+        // Pure X turns both motors equally; A is already at full current
+        assert_eq!(currents_for([20.0, 10.0, 0.0, 0.0]).await, ["set_tmc_current stepper=stepper_y current=800"]);
+        assert!(currents_for([20.0, 10.0, 1.0, 0.0]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scara_steps_per_degree() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.printer.kinematics = "scara".to_string();
+        config.scara = Some(crate::config::ScaraConfig {
+            arm1_length: 100.0,
+            arm2_length: 100.0,
+            arm1_steps_per_deg: 10.0,
+            arm2_steps_per_deg: 20.0,
+        });
+        let motion_config = MotionConfig::new_from_printer_config(&config);
+        assert_eq!(&motion_config.steps_per_mm[..2], &[10.0, 20.0]);
+
+        let port = Arc::new(RecordingPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut planner = MotionPlanner::new(state, hardware, motion_config);
+        planner.set_kinematics_handler(KinematicsType::Scara, Box::new(ScaraKinematics::from_config(config.scara.as_ref().unwrap())));
+        // Shoulder at 0° and elbow at 90°
+        planner.set_homed([100.0, 100.0, 0.0, 0.0]).await;
+        port.commands.lock().unwrap().clear();
+
+        // Shoulder turns +90° and elbow -90°
+        planner.plan_linear_move([0.0, 200.0, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+        while planner.queue_length() > 0 || planner.is_active() {
+            planner.update().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        let commands = port.commands.lock().unwrap();
+        let steps: Vec<&String> = commands.iter().filter(|c| c.starts_with("step")).collect();
+        assert_eq!(steps, ["step X 900 1", "step Y 1800 0"]);
+    }
+}
@@ -0,0 +1,160 @@
+// src/motion/pool.rs - Reusable segment allocations
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_queue::ArrayQueue;
+use super::planner::MotionSegment;
+
+/// Pre-allocated boxed segments handed out and returned without touching
+/// the global allocator
+///
+/// When the pool runs dry `alloc` falls back to a fresh allocation, so
+/// callers never fail; `get_misses` reports how often that happened.
+#[derive(Debug)]
+pub struct SegmentPool {
+    free: ArrayQueue<Box<MotionSegment>>,
+    misses: AtomicUsize,
+}
+
+impl SegmentPool {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        let free = ArrayQueue::new(capacity.max(1));
+        while free.push(Box::default()).is_ok() {}
+        Arc::new(Self {
+            free,
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    /// Take a segment from the pool; it is returned when the handle drops
+    ///
+    /// The segment keeps whatever values its previous user left in it.
+    pub fn alloc(self: &Arc<Self>) -> PooledSegment {
+        let segment = self.free.pop().unwrap_or_else(|| {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            Box::default()
+        });
+        PooledSegment {
+            segment: Some(segment),
+            pool: Arc::clone(self),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.free.capacity()
+    }
+
+    /// Segments currently waiting in the pool
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Allocations the pool couldn't serve from its free list
+    pub fn get_misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// A segment borrowed from a `SegmentPool`
+pub struct PooledSegment {
+    segment: Option<Box<MotionSegment>>,
+    pool: Arc<SegmentPool>,
+}
+
+impl PooledSegment {
+    /// Move the segment's values out, returning the allocation to the pool
+    pub fn into_inner(mut self) -> MotionSegment {
+        std::mem::take(&mut *self)
+    }
+}
+
+impl Deref for PooledSegment {
+    type Target = MotionSegment;
+
+    fn deref(&self) -> &MotionSegment {
+        self.segment.as_ref().expect("segment present until drop")
+    }
+}
+
+impl DerefMut for PooledSegment {
+    fn deref_mut(&mut self) -> &mut MotionSegment {
+        self.segment.as_mut().expect("segment present until drop")
+    }
+}
+
+/// Copies into another allocation from the same pool
+impl Clone for PooledSegment {
+    fn clone(&self) -> Self {
+        let mut segment = self.pool.alloc();
+        MotionSegment::clone_from(&mut segment, self);
+        segment
+    }
+}
+
+impl fmt::Debug for PooledSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl Drop for PooledSegment {
+    fn drop(&mut self) {
+        if let Some(segment) = self.segment.take() {
+            // A full pool means this one was a fallback allocation
+            let _ = self.pool.free.push(segment);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_simultaneous_allocations_are_distinct() {
+        let lookahead_buffer_size = 16;
+        let pool = SegmentPool::new(lookahead_buffer_size * 2);
+
+        let mut segments: Vec<PooledSegment> = (0..lookahead_buffer_size).map(|_| pool.alloc()).collect();
+        for (i, segment) in segments.iter_mut().enumerate() {
+            segment.distance = i as f64;
+        }
+
+        // Every handle owns its own allocation
+        let addresses: HashSet<*const MotionSegment> = segments.iter().map(|segment| &**segment as *const _).collect();
+        assert_eq!(addresses.len(), lookahead_buffer_size);
+        assert!(segments.iter().enumerate().all(|(i, segment)| segment.distance == i as f64));
+        assert_eq!(pool.available(), lookahead_buffer_size);
+
+        // Each returns exactly once
+        drop(segments);
+        assert_eq!(pool.available(), pool.capacity());
+        assert_eq!(pool.get_misses(), 0);
+    }
+
+    #[test]
+    fn test_exhausted_pool_falls_back_to_allocating() {
+        let pool = SegmentPool::new(2);
+        let segments: Vec<PooledSegment> = (0..3).map(|_| pool.alloc()).collect();
+        assert_eq!(pool.available(), 0);
+        assert_eq!(pool.get_misses(), 1);
+
+        drop(segments);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_into_inner_returns_the_allocation() {
+        let pool = SegmentPool::new(2);
+        let mut segment = pool.alloc();
+        segment.distance = 5.0;
+        let copy = segment.clone();
+        assert_eq!(pool.available(), 0);
+
+        assert_eq!(segment.into_inner().distance, 5.0);
+        assert_eq!(copy.distance, 5.0);
+        assert_eq!(pool.available(), 1);
+    }
+}
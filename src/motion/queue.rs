@@ -0,0 +1,483 @@
+// src/motion/queue.rs - Lock-free single-producer/single-consumer segment queue
+use std::fmt;
+use std::mem::MaybeUninit;
+use super::planner::MotionSegment;
+
+#[cfg(not(krusty_loom))]
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+#[cfg(krusty_loom)]
+use loom::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+
+/// Errors raised by the motion system
+#[derive(Debug, Clone, PartialEq)]
+pub enum MotionError {
+    /// The execution queue has no free slot; retry once the executor has
+    /// caught up
+    QueueFull,
+    /// Queued moves didn't finish in time for a change that needs an idle
+    /// queue
+    QueueNotDrained,
+    /// A move the planner refused, such as printing before homing
+    Other(String),
+}
+
+impl fmt::Display for MotionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MotionError::QueueFull => write!(f, "Motion queue full"),
+            MotionError::QueueNotDrained => write!(f, "Motion queue did not drain"),
+            MotionError::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MotionError {}
+
+/// Create a queue holding up to `capacity` segments, split into its push
+/// and pop sides
+///
+/// Each side can be moved to its own task or thread; neither ever blocks
+/// or takes a lock.
+pub fn segment_queue<T>(capacity: usize) -> (PlannerHandle<T>, ExecutorHandle<T>) {
+    let ring = Arc::new(Ring::new(capacity.max(1)));
+    (PlannerHandle { ring: ring.clone() }, ExecutorHandle { ring })
+}
+
+/// Push side of a segment queue, owned by the planner
+pub struct PlannerHandle<T = MotionSegment> {
+    ring: Arc<Ring<T>>,
+}
+
+/// Pop side of a segment queue, owned by the executor
+pub struct ExecutorHandle<T = MotionSegment> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> PlannerHandle<T> {
+    /// Append a segment, or return `QueueFull` without blocking
+    ///
+    /// A full queue drops `value`; check `is_full` first to keep it. Only
+    /// the executor can free slots, so a queue that isn't full stays that
+    /// way until the next push.
+    pub fn push(&mut self, value: T) -> Result<(), MotionError> {
+        self.ring.push(value)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.ring.len() >= self.ring.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+}
+
+impl<T> ExecutorHandle<T> {
+    /// Take the oldest segment, if any
+    pub fn pop(&mut self) -> Option<T> {
+        self.ring.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity()
+    }
+}
+
+/// Both sides of a segment queue kept by one owner, such as a planner
+/// that executes its own segments
+///
+/// With both handles held nothing else can push or pop, so queued values
+/// can also be read and rewritten in place, as lookahead replanning does.
+/// `split` hands the two sides to separate tasks instead.
+pub struct SegmentQueue<T = MotionSegment> {
+    planner: PlannerHandle<T>,
+    executor: ExecutorHandle<T>,
+}
+
+impl<T> SegmentQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        let (planner, executor) = segment_queue(capacity);
+        Self { planner, executor }
+    }
+
+    /// Append a value, or return `QueueFull` without blocking
+    pub fn push(&mut self, value: T) -> Result<(), MotionError> {
+        self.planner.push(value)
+    }
+
+    /// Take the oldest value, if any
+    pub fn pop(&mut self) -> Option<T> {
+        self.executor.pop()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.planner.is_full()
+    }
+
+    pub fn len(&self) -> usize {
+        self.planner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.planner.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.planner.capacity()
+    }
+
+    /// Drop every queued value
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+
+    /// Run `f` on the value `index` places after the oldest
+    pub fn with<R>(&self, index: usize, f: impl FnOnce(&T) -> R) -> Option<R> {
+        // Holding both handles, nothing can pop or overwrite the slot meanwhile
+        (index < self.len()).then(|| unsafe { self.planner.ring.with_queued(index, f) })
+    }
+
+    /// Run `f` on the value `index` places after the oldest, which it may
+    /// change
+    pub fn with_mut<R>(&mut self, index: usize, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        (index < self.len()).then(|| unsafe { self.planner.ring.with_queued_mut(index, f) })
+    }
+
+    /// Run `f` on each value, oldest first
+    pub fn for_each(&self, mut f: impl FnMut(&T)) {
+        for index in 0..self.len() {
+            self.with(index, &mut f);
+        }
+    }
+
+    /// Run `f` on each value, oldest first, which it may change
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut T)) {
+        for index in 0..self.len() {
+            self.with_mut(index, &mut f);
+        }
+    }
+
+    /// Hand the push and pop sides to separate owners
+    pub fn split(self) -> (PlannerHandle<T>, ExecutorHandle<T>) {
+        (self.planner, self.executor)
+    }
+}
+
+impl<T: Clone> SegmentQueue<T> {
+    /// Copies of the queued values, oldest first
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut values = Vec::with_capacity(self.len());
+        self.for_each(|value| values.push(value.clone()));
+        values
+    }
+}
+
+impl<T: Clone> Clone for SegmentQueue<T> {
+    fn clone(&self) -> Self {
+        let mut queue = Self::new(self.capacity());
+        self.for_each(|value| {
+            // Same capacity, so every value fits
+            let _ = queue.push(value.clone());
+        });
+        queue
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SegmentQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        self.for_each(|value| {
+            list.entry(value);
+        });
+        list.finish()
+    }
+}
+
+/// Fixed ring of slots indexed by ever-increasing head (next pop) and
+/// tail (next push) counters
+///
+/// The producer alone writes `tail` and the consumer alone writes `head`;
+/// publishing either with Release after touching a slot hands that slot
+/// over to the other side.
+struct Ring<T> {
+    slots: Box<[Slot<T>]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// Slots are only accessed by the side that currently owns them
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| Slot::new()).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    fn push(&self, value: T) -> Result<(), MotionError> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) >= self.capacity() {
+            return Err(MotionError::QueueFull);
+        }
+        // The consumer has released this slot (head moved past it)
+        unsafe { self.slots[tail % self.capacity()].write(value) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // The producer has published this slot (tail moved past it)
+        let value = unsafe { self.slots[head % self.capacity()].read() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Run `f` on the value `offset` places after the head
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold both sides of the ring, and `offset` must be
+    /// below `len`.
+    unsafe fn with_queued<R>(&self, offset: usize, f: impl FnOnce(&T) -> R) -> R {
+        let head = self.head.load(Ordering::Relaxed);
+        unsafe { self.slots[head.wrapping_add(offset) % self.capacity()].with(f) }
+    }
+
+    /// Run `f` on the value `offset` places after the head, which it may
+    /// change
+    ///
+    /// # Safety
+    ///
+    /// As `with_queued`, with no other access to the ring until `f` returns.
+    unsafe fn with_queued_mut<R>(&self, offset: usize, f: impl FnOnce(&mut T) -> R) -> R {
+        let head = self.head.load(Ordering::Relaxed);
+        unsafe { self.slots[head.wrapping_add(offset) % self.capacity()].with_mut(f) }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(not(krusty_loom))]
+struct Slot<T>(std::cell::UnsafeCell<MaybeUninit<T>>);
+
+#[cfg(not(krusty_loom))]
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self(std::cell::UnsafeCell::new(MaybeUninit::uninit()))
+    }
+
+    unsafe fn write(&self, value: T) {
+        unsafe { (*self.0.get()).write(value) };
+    }
+
+    unsafe fn read(&self) -> T {
+        unsafe { (*self.0.get()).assume_init_read() }
+    }
+
+    unsafe fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(unsafe { (*self.0.get()).assume_init_ref() })
+    }
+
+    unsafe fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(unsafe { (*self.0.get()).assume_init_mut() })
+    }
+}
+
+#[cfg(krusty_loom)]
+struct Slot<T>(loom::cell::UnsafeCell<MaybeUninit<T>>);
+
+#[cfg(krusty_loom)]
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self(loom::cell::UnsafeCell::new(MaybeUninit::uninit()))
+    }
+
+    unsafe fn write(&self, value: T) {
+        self.0.with_mut(|slot| unsafe { (*slot).write(value) });
+    }
+
+    unsafe fn read(&self) -> T {
+        self.0.with(|slot| unsafe { (*slot).assume_init_read() })
+    }
+
+    unsafe fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.0.with(|slot| f(unsafe { (*slot).assume_init_ref() }))
+    }
+
+    unsafe fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.0.with_mut(|slot| f(unsafe { (*slot).assume_init_mut() }))
+    }
+}
+
+#[cfg(all(test, not(krusty_loom)))]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::hardware::HardwareManager;
+    use crate::motion::{MotionConfig, MotionPlanner, MotionType};
+    use crate::printer::PrinterState;
+    use tokio::sync::RwLock;
+
+    #[test]
+    fn test_fifo_order_and_backpressure() {
+        let (mut planner, mut executor) = segment_queue::<u32>(3);
+        for value in 0..3 {
+            planner.push(value).unwrap();
+        }
+        assert!(planner.is_full());
+        assert_eq!(planner.push(3), Err(MotionError::QueueFull));
+
+        // Wrap around the ring a few times
+        for value in 3..10 {
+            assert_eq!(executor.pop(), Some(value - 3));
+            planner.push(value).unwrap();
+        }
+        assert_eq!(executor.len(), 3);
+        assert_eq!((executor.pop(), executor.pop(), executor.pop(), executor.pop()), (Some(7), Some(8), Some(9), None));
+    }
+
+    #[test]
+    fn test_segment_queue_rewrites_in_place() {
+        let mut queue = SegmentQueue::<u32>::new(3);
+        for value in 0..3 {
+            queue.push(value).unwrap();
+        }
+        assert_eq!(queue.push(3), Err(MotionError::QueueFull));
+
+        // Index from the oldest value after the head has wrapped
+        assert_eq!(queue.pop(), Some(0));
+        queue.push(3).unwrap();
+        queue.with_mut(2, |value| *value *= 10);
+        assert_eq!(queue.with(0, |value| *value), Some(1));
+        assert_eq!(queue.with(3, |value| *value), None);
+        assert_eq!(queue.to_vec(), vec![1, 2, 30]);
+        assert_eq!(queue.clone().to_vec(), vec![1, 2, 30]);
+
+        queue.clear();
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_unpopped_values_are_dropped() {
+        let value = Arc::new(());
+        let (mut planner, executor) = segment_queue(4);
+        planner.push(value.clone()).unwrap();
+        planner.push(value.clone()).unwrap();
+        drop((planner, executor));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_plan_and_execute() {
+        const MOVES: usize = 2000;
+
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut motion_planner = MotionPlanner::new(
+            state,
+            HardwareManager::new(config.clone()),
+            MotionConfig::new_from_printer_config(&config),
+        );
+        motion_planner.set_homed([0.0; 4]).await;
+        let (mut producer, mut consumer) = motion_planner.create_execution_queue();
+        assert_eq!(producer.capacity(), motion_planner.get_config().lookahead_buffer_size);
+
+        let planning = tokio::spawn(async move {
+            for i in 1..=MOVES {
+                let target = [i as f64, 0.0, 0.0, 0.0];
+                motion_planner.plan_linear_move(target, 100.0, MotionType::Travel).await.unwrap();
+                while motion_planner.queue_length() > 0 {
+                    if motion_planner.hand_off(&mut producer) == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+            }
+        });
+
+        let executing = tokio::spawn(async move {
+            let mut executed = 0;
+            while executed < MOVES {
+                match consumer.pop() {
+                    Some(segment) => {
+                        executed += 1;
+                        assert_eq!(segment.target[0], executed as f64);
+                    }
+                    None => tokio::task::yield_now().await,
+                }
+            }
+        });
+
+        planning.await.unwrap();
+        executing.await.unwrap();
+    }
+}
+
+#[cfg(all(test, krusty_loom))]
+mod loom_tests {
+    use super::*;
+
+    /// Run with `RUSTFLAGS="--cfg krusty_loom" cargo test --lib motion::queue`
+    #[test]
+    fn test_spsc_no_data_race() {
+        loom::model(|| {
+            let (mut planner, mut executor) = segment_queue::<usize>(2);
+            let producer = loom::thread::spawn(move || {
+                for value in 0..3 {
+                    while planner.push(value).is_err() {
+                        loom::thread::yield_now();
+                    }
+                }
+            });
+
+            let mut received = Vec::new();
+            while received.len() < 3 {
+                match executor.pop() {
+                    Some(value) => received.push(value),
+                    None => loom::thread::yield_now(),
+                }
+            }
+            producer.join().unwrap();
+            assert_eq!(received, vec![0, 1, 2]);
+        });
+    }
+}
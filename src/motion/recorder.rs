@@ -0,0 +1,251 @@
+// src/motion/recorder.rs - Toolhead trajectory recording for debugging print defects
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+/// File names are `trajectory_<unix ms>.jsonl`
+const RECORDING_PREFIX: &str = "trajectory_";
+const RECORDING_EXTENSION: &str = "jsonl";
+
+/// Where the toolhead was at one moment of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectorySample {
+    /// Seconds since the recording started
+    pub timestamp: f64,
+    /// [X, Y, Z, E] (mm)
+    pub position: [f64; 4],
+    /// [X, Y, Z, E] (mm/s)
+    pub velocity: [f64; 4],
+    /// Command that planned the executing segment, empty if unlabeled
+    pub segment_label: String,
+}
+
+/// A saved recording
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordingInfo {
+    /// File name without the extension
+    pub id: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Recording {
+    started: Instant,
+    started_unix_ms: u64,
+    samples: Vec<TrajectorySample>,
+}
+
+#[derive(Debug)]
+struct RecorderState {
+    output_dir: PathBuf,
+    interval: Duration,
+    recording: Option<Recording>,
+}
+
+/// Captures the planner's interpolated position while recording (M926)
+///
+/// Clones share the recording, so the planner samples into the same one
+/// G-code starts and stops.
+#[derive(Debug, Clone)]
+pub struct MotionRecorder {
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl Default for MotionRecorder {
+    fn default() -> Self {
+        Self::new("trajectories", Duration::from_millis(1))
+    }
+}
+
+impl MotionRecorder {
+    /// Recorder saving to `output_dir`, sampling at most once per `interval`
+    pub fn new(output_dir: impl Into<PathBuf>, interval: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RecorderState {
+                output_dir: output_dir.into(),
+                interval,
+                recording: None,
+            })),
+        }
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        self.state.lock().unwrap().output_dir.clone()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.state.lock().unwrap().recording.is_some()
+    }
+
+    /// Start a new recording, discarding one in progress
+    pub fn start(&self) {
+        let started_unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64);
+        self.state.lock().unwrap().recording = Some(Recording {
+            started: Instant::now(),
+            started_unix_ms,
+            samples: Vec::new(),
+        });
+    }
+
+    /// Record a sample if recording and `interval` has passed since the last
+    pub fn sample(&self, position: [f64; 4], velocity: [f64; 4], segment_label: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        let interval = state.interval.as_secs_f64();
+        let Some(recording) = &mut state.recording else {
+            return;
+        };
+        let timestamp = recording.started.elapsed().as_secs_f64();
+        if recording.samples.last().is_some_and(|last| timestamp - last.timestamp < interval) {
+            return;
+        }
+        recording.samples.push(TrajectorySample {
+            timestamp,
+            position,
+            velocity,
+            segment_label: segment_label.unwrap_or_default().to_string(),
+        });
+    }
+
+    /// End the recording and save it, returning the file written, or
+    /// `None` if nothing was being recorded
+    pub fn stop(&self) -> io::Result<Option<PathBuf>> {
+        let (output_dir, recording) = {
+            let mut state = self.state.lock().unwrap();
+            let Some(recording) = state.recording.take() else {
+                return Ok(None);
+            };
+            (state.output_dir.clone(), recording)
+        };
+        std::fs::create_dir_all(&output_dir)?;
+        let path = output_dir.join(format!(
+            "{}{}.{}",
+            RECORDING_PREFIX, recording.started_unix_ms, RECORDING_EXTENSION
+        ));
+        write_trajectory(&path, &recording.samples)?;
+        tracing::info!("Saved {} trajectory samples to {}", recording.samples.len(), path.display());
+        Ok(Some(path))
+    }
+
+    /// Saved recordings, oldest first
+    pub fn list_recordings(&self) -> io::Result<Vec<RecordingInfo>> {
+        let output_dir = self.output_dir();
+        let entries = match std::fs::read_dir(&output_dir) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            entries => entries?,
+        };
+        let mut recordings = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if is_recording_id(id) && path.extension().is_some_and(|extension| extension == RECORDING_EXTENSION) {
+                recordings.push(RecordingInfo { id: id.to_string(), size_bytes: entry.metadata()?.len() });
+            }
+        }
+        recordings.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(recordings)
+    }
+
+    /// Samples of the saved recording `id`
+    pub fn read_recording(&self, id: &str) -> io::Result<Vec<TrajectorySample>> {
+        if !is_recording_id(id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid recording ID {}", id)));
+        }
+        read_trajectory(self.output_dir().join(format!("{}.{}", id, RECORDING_EXTENSION)))
+    }
+}
+
+/// Whether `id` names a recording, so it can't reach outside the output directory
+fn is_recording_id(id: &str) -> bool {
+    id.strip_prefix(RECORDING_PREFIX)
+        .is_some_and(|stamp| !stamp.is_empty() && stamp.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Write `samples` to `path`, one JSON object per line
+pub fn write_trajectory(path: impl AsRef<Path>, samples: &[TrajectorySample]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+    for sample in samples {
+        serde_json::to_writer(&mut file, sample)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()
+}
+
+/// Read a trajectory written by `write_trajectory`
+pub fn read_trajectory(path: impl AsRef<Path>) -> io::Result<Vec<TrajectorySample>> {
+    let file = io::BufReader::new(std::fs::File::open(path)?);
+    let mut samples = Vec::new();
+    for line in file.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            samples.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::RwLock;
+    use crate::config::Config;
+    use crate::hardware::HardwareManager;
+    use crate::motion::{MotionConfig, MotionPlanner, MotionType};
+    use crate::printer::PrinterState;
+    use crate::simulator::Simulator;
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = std::env::temp_dir().join(format!("krusty-trajectory-{}", std::process::id()));
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let hardware = HardwareManager::new(config.clone());
+        let mut planner = MotionPlanner::new(state, hardware, MotionConfig::new_from_printer_config(&config));
+        planner.set_homed([0.0; 4]).await;
+        let recorder = MotionRecorder::new(&dir, Duration::ZERO);
+        planner.set_motion_recorder(recorder.clone());
+
+        recorder.start();
+        let targets = [[10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0], [5.5, 2.25]];
+        for (i, [x, y]) in targets.into_iter().enumerate() {
+            planner.set_segment_label(Some(format!("move {}", i)));
+            planner.plan_linear_move([x, y, 0.0, 0.0], 100.0, MotionType::Travel).await.unwrap();
+            while planner.queue_length() > 0 || planner.is_active() {
+                planner.update().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+        let path = recorder.stop().unwrap().unwrap();
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.stop().unwrap(), None);
+
+        let recordings = recorder.list_recordings().unwrap();
+        assert_eq!(recordings.len(), 1);
+        assert_eq!(path.file_stem().unwrap().to_str(), Some(recordings[0].id.as_str()));
+        let samples = recorder.read_recording(&recordings[0].id).unwrap();
+        for [x, y] in targets {
+            assert!(
+                samples.iter().any(|s| (s.position[0] - x).abs() < 1e-3 && (s.position[1] - y).abs() < 1e-3),
+                "no sample at ({}, {})",
+                x,
+                y
+            );
+        }
+        assert!(samples.iter().any(|s| s.segment_label == "move 4"));
+        assert!(samples.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert!(recorder.read_recording("../printer").is_err());
+
+        let mut sim = Simulator::default();
+        assert_eq!(sim.load_trajectory(&path).unwrap(), samples.len());
+        sim.run_until(samples.last().unwrap().timestamp + 1.0);
+        let position = sim.get_position();
+        // Within half a step at 80 steps/mm
+        assert!((position[0] - 5.5).abs() <= 1.0 / 160.0 && (position[1] - 2.25).abs() <= 1.0 / 160.0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
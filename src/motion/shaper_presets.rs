@@ -0,0 +1,249 @@
+// src/motion/shaper_presets.rs - Named input shaper settings that can be shared
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Damping ratio used when a preset does not come from a measurement
+pub const DEFAULT_SHAPER_DAMPING: f64 = 0.1;
+
+/// Vibration the EI shapers tolerate at their frequency, as in Klipper
+const SHAPER_VIBRATION_TOLERANCE: f64 = 0.05;
+
+/// Input shaper filters, named as in Klipper
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum InputShaperType {
+    #[serde(rename = "zv")]
+    Zv,
+    #[serde(rename = "mzv")]
+    Mzv,
+    #[serde(rename = "zvd")]
+    Zvd,
+    #[serde(rename = "ei")]
+    Ei,
+    #[serde(rename = "2hump_ei")]
+    TwoHumpEi,
+    #[serde(rename = "3hump_ei")]
+    ThreeHumpEi,
+}
+
+impl std::str::FromStr for InputShaperType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "zv" => Ok(InputShaperType::Zv),
+            "mzv" => Ok(InputShaperType::Mzv),
+            "zvd" => Ok(InputShaperType::Zvd),
+            "ei" => Ok(InputShaperType::Ei),
+            "2hump_ei" => Ok(InputShaperType::TwoHumpEi),
+            "3hump_ei" => Ok(InputShaperType::ThreeHumpEi),
+            other => Err(format!("Unknown input shaper type: {}", other)),
+        }
+    }
+}
+
+/// A tuned shaper, with what it was tuned for
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ShaperPreset {
+    pub name: String,
+    pub shaper_type: InputShaperType,
+    /// Resonant frequency the shaper cancels (Hz)
+    pub frequency: f64,
+    /// Damping ratio, from 0 up to but excluding 1
+    pub damping: f64,
+    #[serde(default)]
+    pub target_printer_model: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+impl ShaperPreset {
+    /// Check the preset could be applied
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Shaper preset needs a name".to_string());
+        }
+        if !self.frequency.is_finite() || self.frequency <= 0.0 {
+            return Err(format!("Shaper frequency {} must be positive", self.frequency));
+        }
+        if !(0.0..1.0).contains(&self.damping) {
+            return Err(format!("Shaper damping {} outside 0..1", self.damping));
+        }
+        Ok(())
+    }
+
+    /// The shaper's impulses as (amplitude, delay in seconds), earliest
+    /// first; the amplitudes add up to 1
+    ///
+    /// The shaped position is the sum of each amplitude times where the
+    /// toolhead was commanded to be that long ago. Coefficients follow
+    /// Klipper's shaper definitions.
+    pub fn impulses(&self) -> Vec<(f64, f64)> {
+        let damped = (1.0 - self.damping * self.damping).sqrt();
+        let k = (-self.damping * std::f64::consts::PI / damped).exp();
+        let period = 1.0 / (self.frequency * damped);
+        let v_tol = SHAPER_VIBRATION_TOLERANCE;
+
+        let (amplitudes, delays): (Vec<f64>, Vec<f64>) = match self.shaper_type {
+            InputShaperType::Zv => (vec![1.0, k], vec![0.0, 0.5]),
+            InputShaperType::Mzv => {
+                let k = (-0.75 * self.damping * std::f64::consts::PI / damped).exp();
+                let a1 = 1.0 - std::f64::consts::FRAC_1_SQRT_2;
+                (vec![a1, (std::f64::consts::SQRT_2 - 1.0) * k, a1 * k * k], vec![0.0, 0.375, 0.75])
+            }
+            InputShaperType::Zvd => (vec![1.0, 2.0 * k, k * k], vec![0.0, 0.5, 1.0]),
+            InputShaperType::Ei => {
+                let a1 = 0.25 * (1.0 + v_tol);
+                (vec![a1, 0.5 * (1.0 - v_tol) * k, a1 * k * k], vec![0.0, 0.5, 1.0])
+            }
+            InputShaperType::TwoHumpEi => {
+                let v2 = v_tol * v_tol;
+                let x = (v2 * ((1.0 - v2).sqrt() + 1.0)).cbrt();
+                let a1 = (3.0 * x * x + 2.0 * x + 3.0 * v2) / (16.0 * x);
+                let a2 = (0.5 - a1) * k;
+                (vec![a1, a2, a2 * k, a1 * k * k * k], vec![0.0, 0.5, 1.0, 1.5])
+            }
+            InputShaperType::ThreeHumpEi => {
+                let k2 = k * k;
+                let a1 = 0.0625 * (1.0 + 3.0 * v_tol + 2.0 * (2.0 * (v_tol + 1.0) * v_tol).sqrt());
+                let a2 = 0.25 * (1.0 - v_tol) * k;
+                let a3 = (0.5 * (1.0 + v_tol) - 2.0 * a1) * k2;
+                (vec![a1, a2, a3, a2 * k2, a1 * k2 * k2], vec![0.0, 0.5, 1.0, 1.5, 2.0])
+            }
+        };
+
+        let total: f64 = amplitudes.iter().sum();
+        amplitudes
+            .into_iter()
+            .zip(delays)
+            .map(|(amplitude, delay)| (amplitude / total, delay * period))
+            .collect()
+    }
+}
+
+/// The shaper that usually works best for a measured resonance
+///
+/// Low frequencies get the more robust multi-hump shapers, which smooth
+/// more; high ones the short ZV, which barely smooths at all.
+pub fn suggest_from_frequency(frequency: f64) -> ShaperPreset {
+    let shaper_type = match frequency {
+        f if f < 25.0 => InputShaperType::TwoHumpEi,
+        f if f < 40.0 => InputShaperType::Ei,
+        f if f < 70.0 => InputShaperType::Mzv,
+        _ => InputShaperType::Zv,
+    };
+    ShaperPreset {
+        name: format!("suggested_{:.0}hz", frequency),
+        shaper_type,
+        frequency,
+        damping: DEFAULT_SHAPER_DAMPING,
+        target_printer_model: None,
+        notes: None,
+    }
+}
+
+/// Presets by name, stored as JSON
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ShaperPresetLibrary {
+    presets: Vec<ShaperPreset>,
+}
+
+impl ShaperPresetLibrary {
+    /// Add a preset, replacing any with the same name
+    pub fn add_preset(&mut self, preset: ShaperPreset) -> Result<(), String> {
+        preset.validate()?;
+        match self.presets.iter_mut().find(|existing| existing.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+        Ok(())
+    }
+
+    pub fn get_preset(&self, name: &str) -> Option<&ShaperPreset> {
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+
+    pub fn list_presets(&self) -> &[ShaperPreset] {
+        &self.presets
+    }
+
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, json)
+    }
+
+    pub fn load_from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let library: Self = serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        for preset in &library.presets {
+            preset.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        Ok(library)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_round_trip() {
+        let mut library = ShaperPresetLibrary::default();
+        library
+            .add_preset(ShaperPreset {
+                name: "voron_x".to_string(),
+                shaper_type: InputShaperType::Mzv,
+                frequency: 54.2,
+                damping: 0.08,
+                target_printer_model: Some("Voron 2.4".to_string()),
+                notes: Some("Measured with an ADXL345".to_string()),
+            })
+            .unwrap();
+        library
+            .add_preset(ShaperPreset {
+                name: "ender_y".to_string(),
+                shaper_type: InputShaperType::TwoHumpEi,
+                frequency: 21.0,
+                damping: 0.1,
+                target_printer_model: Some("Ender 3".to_string()),
+                notes: None,
+            })
+            .unwrap();
+        library.add_preset(suggest_from_frequency(88.0)).unwrap();
+        assert!(library.add_preset(ShaperPreset { damping: 1.0, ..suggest_from_frequency(30.0) }).is_err());
+
+        let path = std::env::temp_dir().join(format!("krusty-shapers-{}.json", std::process::id()));
+        library.save_to_file(&path).unwrap();
+        let loaded = ShaperPresetLibrary::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, library);
+        assert_eq!(loaded.list_presets().len(), 3);
+        assert_eq!(loaded.get_preset("voron_x"), library.get_preset("voron_x"));
+        assert_eq!(loaded.get_preset("suggested_88hz").unwrap().shaper_type, InputShaperType::Zv);
+    }
+
+    #[test]
+    fn test_impulses() {
+        for shaper_type in ["zv", "mzv", "zvd", "ei", "2hump_ei", "3hump_ei"] {
+            let preset = ShaperPreset { shaper_type: shaper_type.parse().unwrap(), ..suggest_from_frequency(50.0) };
+            let impulses = preset.impulses();
+            let total: f64 = impulses.iter().map(|(amplitude, _)| amplitude).sum();
+            assert!((total - 1.0).abs() < 1e-12, "{}", shaper_type);
+            assert_eq!(impulses[0].1, 0.0);
+            assert!(impulses.windows(2).all(|pair| pair[0].1 < pair[1].1), "{}", shaper_type);
+        }
+
+        // Undamped ZV: two equal halves, half a period apart
+        let zv = ShaperPreset { shaper_type: InputShaperType::Zv, damping: 0.0, ..suggest_from_frequency(50.0) };
+        assert_eq!(zv.impulses(), vec![(0.5, 0.0), (0.5, 0.01)]);
+    }
+
+    #[test]
+    fn test_suggest_from_frequency() {
+        assert_eq!(suggest_from_frequency(18.0).shaper_type, InputShaperType::TwoHumpEi);
+        assert_eq!(suggest_from_frequency(35.0).shaper_type, InputShaperType::Ei);
+        assert_eq!(suggest_from_frequency(50.0).shaper_type, InputShaperType::Mzv);
+        assert_eq!(suggest_from_frequency(100.0).shaper_type, InputShaperType::Zv);
+    }
+}
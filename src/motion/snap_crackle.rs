@@ -74,27 +74,6 @@ pub struct HigherOrderController {
     
     /// 6th derivative (Lock) limit
     max_lock: f64,
-    
-    /// Mathematical model for higher-order motion
-    motion_model: MotionModel,
-    
-    /// Boundary conditions solver
-    boundary_solver: BoundarySolver,
-}
-
-/// Mathematical model for snap/crackle motion
-pub struct MotionModel {
-    /// Polynomial degree (7th order for full Snap/Crackle)
-    degree: usize,
-    
-    /// Boundary conditions
-    boundary_conditions: BoundaryConditions,
-    
-    /// Optimization constraints
-    constraints: MotionConstraints,
-    
-    /// Coefficient matrix for solving boundary conditions
-    coefficient_matrix: Vec<Vec<f64>>,
 }
 
 /// Boundary conditions for motion planning
@@ -118,7 +97,7 @@ pub struct BoundaryConditions {
 }
 
 /// Motion constraints for optimization
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct MotionConstraints {
     pub max_velocity: f64,
     pub max_acceleration: f64,
@@ -130,22 +109,7 @@ pub struct MotionConstraints {
 }
 
 /// Solver for boundary conditions
-pub struct BoundarySolver {
-    /// Matrix solver for linear systems
-    matrix_solver: MatrixSolver,
-    
-    /// Cache for frequently used matrices
-    matrix_cache: std::collections::HashMap<String, Vec<Vec<f64>>>,
-}
-
-/// Matrix solver for linear algebra operations
-pub struct MatrixSolver {
-    /// Tolerance for numerical computations
-    tolerance: f64,
-    
-    /// Maximum iterations for iterative solvers
-    max_iterations: usize,
-}
+pub struct BoundarySolver;
 
 /// Vibration canceller using advanced signal processing
 pub struct VibrationCanceller {
@@ -154,23 +118,10 @@ pub struct VibrationCanceller {
     
     /// Predictive cancellation using simplified machine learning
     predictor: VibrationPredictor,
-    
-    /// Active damping system
-    active_damper: ActiveDamper,
-    
-    /// Configuration
-    config: VibrationCancellationConfig,
 }
 
-/// Configuration for vibration cancellation
-#[derive(Debug, Clone, Default)]
-pub struct VibrationCancellationConfig {
-    pub adaptive_filtering: bool,
-    pub prediction_enabled: bool,
-    pub active_damping: bool,
-    pub filter_learning_rate: f64,
-    pub prediction_horizon: f64,
-}
+/// Errors kept for `AdaptiveFilter::mean_squared_error`
+const ERROR_HISTORY_LEN: usize = 256;
 
 /// Adaptive filter for vibration cancellation
 pub struct AdaptiveFilter {
@@ -185,61 +136,15 @@ pub struct AdaptiveFilter {
     
     /// Filter order
     order: usize,
+    
+    /// Most recent `order` input samples, newest first
+    inputs: VecDeque<f64>,
 }
 
 /// Simplified vibration predictor
 pub struct VibrationPredictor {
-    /// Historical vibration patterns
-    vibration_history: VecDeque<VibrationPattern>,
-    
-    /// Prediction model weights
-    model_weights: Vec<f64>,
-    
     /// Prediction horizon (seconds ahead)
     prediction_horizon: f64,
-    
-    /// Confidence in predictions
-    prediction_confidence: f64,
-}
-
-/// Vibration pattern for learning
-#[derive(Debug, Clone)]
-pub struct VibrationPattern {
-    pub frequency: f64,
-    pub amplitude: f64,
-    pub phase: f64,
-    pub timestamp: std::time::Instant,
-    pub axis: usize,
-    pub effectiveness: f64,
-}
-
-/// Active damping system
-pub struct ActiveDamper {
-    /// PID controller for damping
-    damping_controller: PIDController,
-    
-    /// Damping effectiveness tracking
-    effectiveness_tracker: EffectivenessTracker,
-    
-    /// Configuration
-    config: ActiveDampingConfig,
-}
-
-/// Configuration for active damping
-#[derive(Debug, Clone, Default)]
-pub struct ActiveDampingConfig {
-    pub kp: f64,
-    pub ki: f64,
-    pub kd: f64,
-    pub max_damping_force: f64,
-    pub response_time: f64,
-}
-
-/// Effectiveness tracker for damping
-pub struct EffectivenessTracker {
-    pub recent_effectiveness: VecDeque<f64>,
-    pub average_effectiveness: f64,
-    pub improvement_rate: f64,
 }
 
 /// PID controller implementation
@@ -347,51 +252,32 @@ impl SnapCrackleMotion {
         // Calculate optimal duration
         let duration = self.calculate_optimal_duration(start, end, constraints)?;
         
+        // Coefficients a₀..a₇ matching position through jerk at both ends
+        let coefficients = BoundarySolver::seventh_order_coefficients(start, end, duration)?;
+        
         // Generate motion points at high resolution
         let time_step = 0.0001; // 100μs resolution
-        let mut points = Vec::new();
-        
-        let mut t = 0.0;
-        while t <= duration {
-            let point = self.evaluate_seventh_order_polynomial(start, end, t, duration);
-            points.push(point);
-            t += time_step;
-        }
+        let steps = (duration / time_step).floor() as usize;
+        let points = (0..=steps)
+            .map(|i| self.evaluate_seventh_order_polynomial(&coefficients, i as f64 * time_step))
+            .collect();
         
         Ok(points)
     }
 
     /// Evaluate 7th-order polynomial and all derivatives
-    fn evaluate_seventh_order_polynomial(
-        &self,
-        start: &MotionState7D,
-        end: &MotionState7D,
-        t: f64,
-        duration: f64,
-    ) -> MotionPoint7D {
-        // Normalize time to [0, 1]
-        let normalized_t = if duration > 0.0 { t / duration } else { 0.0 };
-        
-        // For simplicity, use linear interpolation with higher-order derivatives set to zero
-        // In a real implementation, this would solve the full 7th-order polynomial
-        
-        let position = start.position + (end.position - start.position) * normalized_t;
-        let velocity = if duration > 0.0 {
-            (end.position - start.position) / duration
-        } else {
-            0.0
-        };
-        
+    fn evaluate_seventh_order_polynomial(&self, coefficients: &[f64; 8], t: f64) -> MotionPoint7D {
+        let derivative = |order| polynomial_derivative(coefficients, order, t);
         MotionPoint7D {
             time: t,
-            position,
-            velocity,
-            acceleration: 0.0,
-            jerk: 0.0,
-            snap: 0.0,
-            crackle: 0.0,
-            pop: 0.0,
-            lock: 0.0,
+            position: derivative(0),
+            velocity: derivative(1),
+            acceleration: derivative(2),
+            jerk: derivative(3),
+            snap: derivative(4),
+            crackle: derivative(5),
+            pop: derivative(6),
+            lock: derivative(7),
         }
     }
 
@@ -428,10 +314,33 @@ impl SnapCrackleMotion {
 
     /// Set configuration
     pub fn set_config(&mut self, config: SnapCrackleConfig) {
-        self.config = config;
         self.max_snap = config.max_snap;
         self.max_crackle = config.max_crackle;
         self.higher_order_controller.set_limits(config.max_pop, config.max_lock);
+        self.config = config;
+    }
+
+    /// Duration of a move over `distance` at up to `max_velocity`, with
+    /// the limits tuned by the optimizer when it's enabled
+    ///
+    /// Plans the same move as `plan_snap_crackle_move` without sampling its
+    /// profile.
+    pub async fn plan_move_duration(
+        &mut self,
+        distance: f64,
+        max_velocity: f64,
+    ) -> Result<f64, Box<dyn std::error::Error>> {
+        let start = MotionState7D::default();
+        let end = MotionState7D { position: distance.abs(), ..MotionState7D::default() };
+        let constraints = MotionConstraints { max_velocity, ..MotionConstraints::default() };
+        self.stats.total_moves += 1;
+        let constraints = if self.config.optimization_enabled {
+            self.stats.optimized_moves += 1;
+            self.optimizer.optimize_constraints(&start, &end, &constraints).await?
+        } else {
+            constraints
+        };
+        self.calculate_optimal_duration(&start, &end, &constraints)
     }
 
     /// Get performance statistics
@@ -476,8 +385,6 @@ impl HigherOrderController {
         Self {
             max_pop,
             max_lock,
-            motion_model: MotionModel::new(),
-            boundary_solver: BoundarySolver::new(),
         }
     }
 
@@ -487,45 +394,153 @@ impl HigherOrderController {
     }
 }
 
-impl MotionModel {
-    pub fn new() -> Self {
-        Self {
-            degree: 7,
-            boundary_conditions: BoundaryConditions::default(),
-            constraints: MotionConstraints::default(),
-            coefficient_matrix: vec![vec![0.0; 14]; 14], // 7 start + 7 end conditions
+impl BoundarySolver {
+    /// Coefficients a₀..a₇ of the 7th-order polynomial taking position,
+    /// velocity, acceleration and jerk from `start` at t=0 to `end` at
+    /// t=`duration`
+    ///
+    /// The system is solved in normalized time τ = t/T, which keeps it well
+    /// conditioned for the millisecond-to-second durations of real moves.
+    pub fn seventh_order_coefficients(
+        start: &MotionState7D,
+        end: &MotionState7D,
+        duration: f64,
+    ) -> Result<[f64; 8], SolveError> {
+        let start_conditions = [start.position, start.velocity, start.acceleration, start.jerk];
+        if duration <= 0.0 {
+            // Nothing to move through: hold the start state
+            let mut coefficients = [0.0; 8];
+            for (order, value) in start_conditions.into_iter().enumerate() {
+                coefficients[order] = value / factorial(order);
+            }
+            return Ok(coefficients);
+        }
+        
+        let end_conditions = [end.position, end.velocity, end.acceleration, end.jerk];
+        let mut matrix = [[0.0; 8]; 8];
+        let mut rhs = [0.0; 8];
+        for order in 0..4 {
+            // d^k/dτ^k = T^k d^k/dt^k
+            let scale = duration.powi(order as i32);
+            // At τ=0 only the n == k term survives; at τ=1 all do
+            matrix[order][order] = factorial(order);
+            for (n, entry) in matrix[order + 4].iter_mut().enumerate().skip(order) {
+                *entry = factorial(n) / factorial(n - order);
+            }
+            rhs[order] = start_conditions[order] * scale;
+            rhs[order + 4] = end_conditions[order] * scale;
         }
+        
+        let normalized = Self::solve_lu(&mut matrix, &mut rhs)?;
+        let mut coefficients = [0.0; 8];
+        for (n, b) in normalized.into_iter().enumerate() {
+            coefficients[n] = b / duration.powi(n as i32);
+        }
+        Ok(coefficients)
     }
-}
 
-impl BoundarySolver {
-    pub fn new() -> Self {
-        Self {
-            matrix_solver: MatrixSolver::new(),
-            matrix_cache: std::collections::HashMap::new(),
+    /// Solve `matrix · x = rhs` by LU decomposition with partial pivoting
+    ///
+    /// Both arguments are overwritten: `matrix` with its L and U factors,
+    /// `rhs` with the permuted right-hand side.
+    pub fn solve_lu(matrix: &mut [[f64; 8]; 8], rhs: &mut [f64; 8]) -> Result<[f64; 8], SolveError> {
+        if matrix.iter().flatten().chain(rhs.iter()).any(|value| !value.is_finite()) {
+            return Err(SolveError::NonFinite);
         }
+        let scale = matrix.iter().flatten().fold(0.0f64, |max, value| max.max(value.abs()));
+        let tolerance = scale * 8.0 * f64::EPSILON;
+        
+        for column in 0..8 {
+            let pivot = (column..8)
+                .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))
+                .unwrap_or(column);
+            if matrix[pivot][column].abs() <= tolerance {
+                return Err(SolveError::Singular { column });
+            }
+            matrix.swap(column, pivot);
+            rhs.swap(column, pivot);
+            
+            let (upper, lower) = matrix.split_at_mut(column + 1);
+            let pivot_row = &upper[column];
+            for row in lower {
+                let factor = row[column] / pivot_row[column];
+                row[column] = factor;
+                for (entry, pivot) in row[column + 1..].iter_mut().zip(&pivot_row[column + 1..]) {
+                    *entry -= factor * pivot;
+                }
+            }
+        }
+        
+        // Forward substitution through the unit lower triangle
+        let mut y = [0.0; 8];
+        for row in 0..8 {
+            y[row] = rhs[row] - (0..row).map(|k| matrix[row][k] * y[k]).sum::<f64>();
+        }
+        
+        // Back substitution through the upper triangle
+        let mut x = [0.0; 8];
+        for row in (0..8).rev() {
+            let known: f64 = (row + 1..8).map(|k| matrix[row][k] * x[k]).sum();
+            x[row] = (y[row] - known) / matrix[row][row];
+        }
+        Ok(x)
     }
 }
 
-impl MatrixSolver {
-    pub fn new() -> Self {
-        Self {
-            tolerance: 1e-10,
-            max_iterations: 1000,
+/// Why a boundary value system could not be solved
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolveError {
+    /// No usable pivot in this column
+    Singular { column: usize },
+    /// The system contains NaN or infinity
+    NonFinite,
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::Singular { column } => write!(f, "Singular matrix (column {})", column),
+            SolveError::NonFinite => write!(f, "Matrix contains non-finite values"),
         }
     }
 }
 
+impl std::error::Error for SolveError {}
+
+/// `order`-th derivative of Σ aₙ tⁿ at `t`, by Horner's rule
+fn polynomial_derivative(coefficients: &[f64; 8], order: usize, t: f64) -> f64 {
+    (order..8)
+        .rev()
+        .fold(0.0, |acc, n| acc * t + coefficients[n] * factorial(n) / factorial(n - order))
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|k| k as f64).product()
+}
+
+impl Default for VibrationCanceller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl VibrationCanceller {
     pub fn new() -> Self {
         Self {
             adaptive_filter: AdaptiveFilter::new(32, 0.001),
             predictor: VibrationPredictor::new(0.1),
-            active_damper: ActiveDamper::new(),
-            config: VibrationCancellationConfig::default(),
         }
     }
 
+    /// Run one sample through the adaptive filter
+    ///
+    /// `input` is the reference signal driving the vibration and `desired`
+    /// the vibration measured by the accelerometer (or simulated). Returns
+    /// the filter's estimate of it.
+    pub fn process_sample(&mut self, input: f64, desired: f64) -> f64 {
+        self.adaptive_filter.update(input, desired)
+    }
+
     pub async fn cancel_vibrations(
         &mut self,
         motion_profile: Vec<MotionPoint7D>,
@@ -568,7 +583,7 @@ impl VibrationCanceller {
         cancellation: &[CancellationPoint],
     ) -> Result<Vec<MotionPoint7D>, Box<dyn std::error::Error>> {
         // Apply cancellation signal to motion profile
-        let mut cancelled = profile.to_vec();
+        let cancelled = profile.to_vec();
         
         // This would involve sophisticated signal processing
         // to blend the cancellation signal with the original motion
@@ -608,18 +623,59 @@ impl AdaptiveFilter {
             learning_rate,
             error_history: VecDeque::new(),
             order,
+            inputs: VecDeque::from(vec![0.0; order]),
+        }
+    }
+
+    /// Start from previously learned coefficients instead of zeros
+    pub fn with_coefficients(coefficients: Vec<f64>, learning_rate: f64) -> Self {
+        let mut filter = Self::new(coefficients.len(), learning_rate);
+        filter.coefficients = coefficients;
+        filter
+    }
+
+    /// One LMS step: filter `input` and adapt towards `desired`
+    ///
+    /// Returns the filter output, computed before the coefficients are
+    /// updated.
+    pub fn update(&mut self, input: f64, desired: f64) -> f64 {
+        if self.order == 0 {
+            return 0.0;
+        }
+        self.inputs.pop_back();
+        self.inputs.push_front(input);
+        
+        let output: f64 = self.coefficients.iter().zip(&self.inputs).map(|(w, x)| w * x).sum();
+        let error = desired - output;
+        
+        // w[i] += 2μ e x[n - i]
+        for (w, x) in self.coefficients.iter_mut().zip(&self.inputs) {
+            *w += 2.0 * self.learning_rate * error * x;
+        }
+        
+        if self.error_history.len() == ERROR_HISTORY_LEN {
+            self.error_history.pop_front();
         }
+        self.error_history.push_back(error);
+        output
+    }
+
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    /// Mean squared error over the recent samples
+    pub fn mean_squared_error(&self) -> f64 {
+        if self.error_history.is_empty() {
+            return 0.0;
+        }
+        self.error_history.iter().map(|e| e * e).sum::<f64>() / self.error_history.len() as f64
     }
 }
 
 impl VibrationPredictor {
     pub fn new(prediction_horizon: f64) -> Self {
-        Self {
-            vibration_history: VecDeque::new(),
-            model_weights: vec![0.0; 10], // Simplified model
-            prediction_horizon,
-            prediction_confidence: 0.5,
-        }
+        Self { prediction_horizon }
     }
 
     pub async fn predict_vibrations(
@@ -650,7 +706,7 @@ impl VibrationPredictor {
                 amplitude,
                 frequency: freq,
                 phase: rand::random::<f64>() * 2.0 * std::f64::consts::PI,
-                axis: rand::random::<usize>() % 3,
+                axis: rand::random_range(0..3),
                 confidence: 0.7, // Moderate confidence
             });
         }
@@ -659,26 +715,6 @@ impl VibrationPredictor {
     }
 }
 
-impl ActiveDamper {
-    pub fn new() -> Self {
-        Self {
-            damping_controller: PIDController::new(1.0, 0.1, 0.05),
-            effectiveness_tracker: EffectivenessTracker::new(),
-            config: ActiveDampingConfig::default(),
-        }
-    }
-}
-
-impl EffectivenessTracker {
-    pub fn new() -> Self {
-        Self {
-            recent_effectiveness: VecDeque::new(),
-            average_effectiveness: 0.5,
-            improvement_rate: 0.0,
-        }
-    }
-}
-
 impl PIDController {
     pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
         Self {
@@ -716,7 +752,7 @@ impl PIDController {
 
 /// Snap/Crackle optimizer
 pub struct SnapCrackleOptimizer {
-    /// Simplified optimization using gradient descent
+    /// Gradient descent step size, applied to the normalized cost
     learning_rate: f64,
     
     /// Performance database
@@ -724,6 +760,18 @@ pub struct SnapCrackleOptimizer {
     
     /// Current optimization state
     optimization_state: OptimizationState,
+    
+    /// Weights of the cost function
+    weights: CostWeights,
+    
+    /// Gradient descent steps per call at most
+    max_iterations: usize,
+    
+    /// Gradient norm below which the optimization has converged
+    convergence_tol: f64,
+    
+    /// Non-dominated solutions found across all calls
+    pareto_front: Vec<ParetoPoint>,
 }
 
 #[derive(Debug, Clone)]
@@ -743,107 +791,257 @@ pub struct OptimizationState {
     pub improvement_rate: f64,
     pub convergence: f64, // 0.0 to 1.0
     pub iterations: usize,
+    /// Whether the last call's gradient norm dropped below the tolerance
+    pub converged: bool,
+    /// Gradient descent steps the last call needed to converge
+    pub iterations_to_convergence: Option<usize>,
+}
+
+/// Weights of the cost
+/// `J = w_vib·vibration + w_quality·(1 − quality) + w_time·execution_time`
+#[derive(Debug, Clone, Copy)]
+pub struct CostWeights {
+    pub vibration: f64,
+    pub quality: f64,
+    pub time: f64,
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        Self {
+            vibration: 1.0,
+            quality: 1.0,
+            time: 1.0,
+        }
+    }
+}
+
+/// A solution no other known solution beats on every objective
+#[derive(Debug, Clone)]
+pub struct ParetoPoint {
+    pub parameters: MotionConstraints,
+    pub vibration: f64,
+    pub quality: f64,
+    pub execution_time: f64,
+}
+
+impl ParetoPoint {
+    fn cost(&self, weights: &CostWeights) -> f64 {
+        weights.vibration * self.vibration + weights.quality * (1.0 - self.quality) + weights.time * self.execution_time
+    }
+
+    /// At least as good on every objective and better on one
+    fn dominates(&self, other: &ParetoPoint) -> bool {
+        let no_worse = self.vibration <= other.vibration
+            && self.quality >= other.quality
+            && self.execution_time <= other.execution_time;
+        let better = self.vibration < other.vibration
+            || self.quality > other.quality
+            || self.execution_time < other.execution_time;
+        no_worse && better
+    }
+}
+
+/// Optimized limits are kept within this factor of the requested ones
+const MIN_SCALE: f64 = 0.1;
+const MAX_SCALE: f64 = 10.0;
+
+/// Step for finite-difference gradients
+const GRADIENT_EPSILON: f64 = 1e-4;
+
+/// Predicts a move's outcome for scaled acceleration, jerk and snap limits
+///
+/// Vibration and quality loss grow with the square of the mean scale,
+/// calibrated against the performance history; execution time follows the
+/// velocity profile's acceleration and ramp phases.
+struct SurrogateModel {
+    vibration_gain: f64,
+    quality_loss_gain: f64,
+    cruise_time: f64,
+    ramp_time: f64,
+}
+
+impl SurrogateModel {
+    fn calibrate(history: &[PerformanceRecord], constraints: &MotionConstraints, distance: f64) -> Self {
+        let aggressiveness = |record: &PerformanceRecord| {
+            let scale = scales_between(constraints, &record.motion_parameters);
+            mean_scale(&scale).powi(2).max(f64::EPSILON)
+        };
+        let average = |value: &dyn Fn(&PerformanceRecord) -> f64, default: f64| {
+            if history.is_empty() {
+                default
+            } else {
+                history.iter().map(value).sum::<f64>() / history.len() as f64
+            }
+        };
+        Self {
+            vibration_gain: average(&|record| record.vibration_level / aggressiveness(record), 0.02),
+            quality_loss_gain: average(&|record| (1.0 - record.print_quality) / aggressiveness(record), 0.2),
+            cruise_time: distance / constraints.max_velocity,
+            ramp_time: constraints.max_velocity / constraints.max_acceleration,
+        }
+    }
+
+    /// (vibration, quality, execution time) for limits scaled by `scale`
+    fn predict(&self, scale: &[f64; 3]) -> (f64, f64, f64) {
+        let [acceleration, jerk, snap] = *scale;
+        let aggressiveness = mean_scale(scale).powi(2);
+        let execution_time = self.cruise_time + self.ramp_time * (1.0 / acceleration + acceleration / jerk + jerk / snap) / 3.0;
+        (self.vibration_gain * aggressiveness, 1.0 - self.quality_loss_gain * aggressiveness, execution_time)
+    }
+
+    fn cost(&self, scale: &[f64; 3], weights: &CostWeights) -> f64 {
+        let (vibration, quality, execution_time) = self.predict(scale);
+        weights.vibration * vibration + weights.quality * (1.0 - quality) + weights.time * execution_time
+    }
+}
+
+/// Acceleration, jerk and snap of `limits` relative to `reference`
+fn scales_between(reference: &MotionConstraints, limits: &MotionConstraints) -> [f64; 3] {
+    [
+        limits.max_acceleration / reference.max_acceleration,
+        limits.max_jerk / reference.max_jerk,
+        limits.max_snap / reference.max_snap,
+    ]
+}
+
+fn mean_scale(scale: &[f64; 3]) -> f64 {
+    scale.iter().sum::<f64>() / 3.0
+}
+
+impl Default for SnapCrackleOptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SnapCrackleOptimizer {
     pub fn new() -> Self {
         Self {
-            learning_rate: 0.01,
+            learning_rate: 0.5,
             performance_db: Vec::new(),
             optimization_state: OptimizationState::default(),
+            weights: CostWeights::default(),
+            max_iterations: 50,
+            convergence_tol: 1e-3,
+            pareto_front: Vec::new(),
         }
     }
 
+    /// Record how a move printed, to calibrate later optimizations
+    pub fn record_performance(&mut self, record: PerformanceRecord) {
+        self.performance_db.push(record);
+        
+        // Keep only recent records
+        while self.performance_db.len() > 100 {
+            self.performance_db.remove(0);
+        }
+    }
+
+    /// Gradient descent on the acceleration, jerk and snap limits
+    ///
+    /// Stops after `max_iterations` steps or once the gradient norm is below
+    /// `convergence_tol`. If the descent ends up worse than where it started
+    /// the best solution known from earlier calls is returned instead.
     pub async fn optimize_constraints(
         &mut self,
         start: &MotionState7D,
         end: &MotionState7D,
         constraints: &MotionConstraints,
     ) -> Result<MotionConstraints, Box<dyn std::error::Error>> {
-        // Extract features for optimization
-        let features = self.extract_optimization_features(start, end, constraints);
-        
-        // Simple gradient-based optimization
-        let mut optimized = constraints.clone();
-        
-        // Adjust constraints based on features and learning rate
         let distance = (end.position - start.position).abs();
-        let should_accelerate = distance > 10.0 && features[0] > 0.8; // Long distance, good quality
+        let model = SurrogateModel::calibrate(&self.performance_db, constraints, distance);
+        let weights = self.weights;
         
-        if should_accelerate {
-            // Increase limits for better performance
-            for i in 0..4 {
-                optimized.max_acceleration = constraints.max_acceleration * (1.0 + self.learning_rate);
+        // Normalized so the step size doesn't depend on the move's length
+        let initial = [1.0; 3];
+        let initial_cost = model.cost(&initial, &weights);
+        let cost = |scale: &[f64; 3]| model.cost(scale, &weights) / initial_cost.max(f64::EPSILON);
+        
+        let mut scale = initial;
+        let mut converged = false;
+        let mut iterations_to_convergence = None;
+        let mut initial_norm = None;
+        let mut norm = f64::INFINITY;
+        for iteration in 0..self.max_iterations {
+            let gradient: [f64; 3] = std::array::from_fn(|i| {
+                let (mut up, mut down) = (scale, scale);
+                up[i] += GRADIENT_EPSILON;
+                down[i] -= GRADIENT_EPSILON;
+                (cost(&up) - cost(&down)) / (2.0 * GRADIENT_EPSILON)
+            });
+            norm = gradient.iter().map(|g| g * g).sum::<f64>().sqrt();
+            if !norm.is_finite() {
+                break;
             }
-            optimized.max_jerk = constraints.max_jerk * (1.0 + self.learning_rate * 0.5);
-            optimized.max_snap = constraints.max_snap * (1.0 + self.learning_rate * 0.3);
-        } else if features[1] > 0.05 { // High vibration
-            // Decrease limits for stability
-            for i in 0..4 {
-                optimized.max_acceleration = constraints.max_acceleration * (1.0 - self.learning_rate);
+            let initial_norm = *initial_norm.get_or_insert(norm);
+            self.optimization_state.convergence = (1.0 - norm / initial_norm.max(f64::EPSILON)).clamp(0.0, 1.0);
+            if norm < self.convergence_tol {
+                converged = true;
+                iterations_to_convergence = Some(iteration);
+                self.optimization_state.convergence = 1.0;
+                break;
+            }
+            for (p, g) in scale.iter_mut().zip(gradient) {
+                *p = (*p - self.learning_rate * g).clamp(MIN_SCALE, MAX_SCALE);
             }
-            optimized.max_jerk = constraints.max_jerk * (1.0 - self.learning_rate * 0.5);
         }
         
-        // Store performance record
-        self.performance_db.push(PerformanceRecord {
-            motion_parameters: constraints.clone(),
-            vibration_level: features[1],
-            print_quality: features[0],
-            execution_time: features[2],
-            energy_consumption: features[3],
-            timestamp: std::time::Instant::now(),
-        });
+        let final_cost = model.cost(&scale, &weights);
+        let diverged = !norm.is_finite() || !final_cost.is_finite() || final_cost > initial_cost;
         
-        // Keep only recent records
-        while self.performance_db.len() > 100 {
-            self.performance_db.remove(0);
+        self.optimization_state.iterations += 1;
+        self.optimization_state.converged = converged && !diverged;
+        self.optimization_state.iterations_to_convergence = iterations_to_convergence.filter(|_| !diverged);
+        
+        if diverged {
+            tracing::warn!("Snap/Crackle optimization diverged, using best known limits");
+            self.optimization_state.improvement_rate = 0.0;
+            return Ok(match self.best_known() {
+                Some(best) => best.parameters.clone(),
+                None => constraints.clone(),
+            });
         }
         
-        // Update optimization state
-        self.optimization_state.iterations += 1;
-        self.optimization_state.current_score = features[0]; // Quality score
+        let mut optimized = constraints.clone();
+        optimized.max_acceleration = constraints.max_acceleration * scale[0];
+        optimized.max_jerk = constraints.max_jerk * scale[1];
+        optimized.max_snap = constraints.max_snap * scale[2];
+        
+        let (vibration, quality, execution_time) = model.predict(&scale);
+        self.add_to_pareto_front(ParetoPoint {
+            parameters: optimized.clone(),
+            vibration,
+            quality,
+            execution_time,
+        });
+        if let Some(best) = self.best_known() {
+            self.optimization_state.best_parameters = best.parameters.clone();
+        }
+        self.optimization_state.current_score = final_cost;
+        self.optimization_state.improvement_rate = (initial_cost - final_cost) / initial_cost.max(f64::EPSILON);
         
         Ok(optimized)
     }
 
-    fn extract_optimization_features(
-        &self,
-        start: &MotionState7D,
-        end: &MotionState7D,
-        constraints: &MotionConstraints,
-    ) -> Vec<f64> {
-        let mut features = Vec::new();
-        
-        // Motion characteristics
-        features.push((end.position - start.position).abs()); // Distance
-        features.push((end.velocity - start.velocity).abs()); // Velocity change
-        features.push((end.acceleration - start.acceleration).abs()); // Acceleration change
-        
-        // Current constraints (normalized)
-        features.push(constraints.max_velocity / 1000.0);
-        features.push(constraints.max_acceleration / 10000.0);
-        features.push(constraints.max_jerk / 100.0);
-        features.push(constraints.max_snap / 10000.0);
-        
-        // Historical performance (would come from database)
-        let avg_quality: f64 = if !self.performance_db.is_empty() {
-            self.performance_db.iter().map(|r| r.print_quality).sum::<f64>() / self.performance_db.len() as f64
-        } else {
-            0.8
-        };
-        
-        let avg_vibration: f64 = if !self.performance_db.is_empty() {
-            self.performance_db.iter().map(|r| r.vibration_level).sum::<f64>() / self.performance_db.len() as f64
-        } else {
-            0.02
-        };
-        
-        features.push(avg_quality);
-        features.push(avg_vibration);
-        features.push(self.optimization_state.convergence);
-        
-        features
+    fn add_to_pareto_front(&mut self, point: ParetoPoint) {
+        if self.pareto_front.iter().any(|known| known.dominates(&point)) {
+            return;
+        }
+        self.pareto_front.retain(|known| !point.dominates(known));
+        self.pareto_front.push(point);
+    }
+
+    /// The Pareto-optimal solution with the lowest cost under the current weights
+    fn best_known(&self) -> Option<&ParetoPoint> {
+        self.pareto_front
+            .iter()
+            .min_by(|a, b| a.cost(&self.weights).total_cmp(&b.cost(&self.weights)))
+    }
+
+    /// Non-dominated solutions found so far
+    pub fn pareto_front(&self) -> &[ParetoPoint] {
+        &self.pareto_front
     }
 
     /// Get current optimization state
@@ -855,6 +1053,7 @@ impl SnapCrackleOptimizer {
     pub fn reset(&mut self) {
         self.optimization_state = OptimizationState::default();
         self.performance_db.clear();
+        self.pareto_front.clear();
     }
 }
 
@@ -871,4 +1070,161 @@ impl Default for MotionConstraints {
             max_lock: 125000.0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(position: f64, velocity: f64, acceleration: f64, jerk: f64) -> MotionState7D {
+        MotionState7D {
+            position,
+            velocity,
+            acceleration,
+            jerk,
+            ..MotionState7D::default()
+        }
+    }
+
+    /// Sample `n` of a 50 Hz sine sampled at 1 kHz
+    fn sine_50hz(n: usize, phase: f64) -> f64 {
+        (2.0 * std::f64::consts::PI * 50.0 * n as f64 / 1000.0 + phase).sin()
+    }
+
+    fn peak(values: &[f64]) -> f64 {
+        values.iter().fold(0.0f64, |max, value| max.max(value.abs()))
+    }
+
+    #[test]
+    fn test_lms_converges() {
+        // Coefficients learned on some other move: the output starts out
+        // as large as the input and must be driven to zero
+        let mut coefficients = vec![0.0; 32];
+        coefficients[0] = 1.0;
+        let mut filter = AdaptiveFilter::with_coefficients(coefficients, 0.001);
+        let outputs: Vec<f64> = (0..2000).map(|n| filter.update(sine_50hz(n, 0.0), 0.0)).collect();
+        assert!(peak(&outputs[..20]) > 0.9);
+        assert!(peak(&outputs[1900..]) < 0.1, "output amplitude {}", peak(&outputs[1900..]));
+
+        // Track a vibration that lags the input and is a different size
+        let mut canceller = VibrationCanceller::new();
+        let residuals: Vec<f64> = (0..2000)
+            .map(|n| {
+                let vibration = 0.6 * sine_50hz(n, -0.8);
+                vibration - canceller.process_sample(sine_50hz(n, 0.0), vibration)
+            })
+            .collect();
+        assert!(peak(&residuals[1900..]) < 0.1, "residual amplitude {}", peak(&residuals[1900..]));
+        assert!(canceller.adaptive_filter.mean_squared_error() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_optimizer_converges() {
+        let mut optimizer = SnapCrackleOptimizer::new();
+        let constraints = MotionConstraints::default();
+        let (start, end) = (state(0.0, 0.0, 0.0, 0.0), state(50.0, 0.0, 0.0, 0.0));
+
+        let mut limits = constraints.clone();
+        for call in 0..50 {
+            optimizer.record_performance(PerformanceRecord {
+                motion_parameters: limits.clone(),
+                vibration_level: 0.02,
+                print_quality: 0.7 + 0.005 * call as f64,
+                execution_time: 0.3,
+                energy_consumption: 0.0,
+                timestamp: std::time::Instant::now(),
+            });
+            limits = optimizer.optimize_constraints(&start, &end, &constraints).await.unwrap();
+            if optimizer.get_state().converged {
+                break;
+            }
+        }
+        let state = optimizer.get_state().clone();
+        assert!(state.converged);
+        assert!(state.iterations_to_convergence.unwrap() <= 50);
+        assert!(state.improvement_rate > 0.0);
+        assert!(limits.max_acceleration.is_finite() && limits.max_acceleration > 0.0);
+        assert_eq!(optimizer.pareto_front().len(), 1);
+
+        // A step size that overshoots falls back to the best known limits
+        optimizer.learning_rate = 1e3;
+        let fallback = optimizer.optimize_constraints(&start, &end, &constraints).await.unwrap();
+        assert!(!optimizer.get_state().converged);
+        assert_eq!(fallback.max_acceleration, state.best_parameters.max_acceleration);
+    }
+
+    #[test]
+    fn test_solve_lu() {
+        // Needs a row swap: the first pivot is zero
+        let mut matrix = [[0.0; 8]; 8];
+        for i in 0..8 {
+            matrix[i][(i + 1) % 8] = 2.0;
+            matrix[i][i] += 1.0 - (i == 0) as u8 as f64;
+        }
+        let expected = [1.0, -2.0, 3.0, -4.0, 5.0, -6.0, 7.0, -8.0];
+        let mut rhs = [0.0; 8];
+        for i in 0..8 {
+            rhs[i] = (0..8).map(|k| matrix[i][k] * expected[k]).sum();
+        }
+        let x = BoundarySolver::solve_lu(&mut matrix.clone(), &mut rhs).unwrap();
+        for i in 0..8 {
+            assert!((x[i] - expected[i]).abs() < 1e-12, "{:?}", x);
+        }
+
+        matrix[3] = matrix[2];
+        assert!(matches!(BoundarySolver::solve_lu(&mut matrix, &mut [1.0; 8]), Err(SolveError::Singular { .. })));
+    }
+
+    #[test]
+    fn test_seventh_order_boundary_conditions() {
+        let motion = SnapCrackleMotion::new(1000.0, 5000.0);
+        for (start, end, duration) in [
+            (state(0.0, 0.0, 0.0, 0.0), state(10.0, 0.0, 0.0, 0.0), 0.05),
+            (state(-3.0, 40.0, 500.0, 2e4), state(25.0, 120.0, -800.0, 0.0), 0.4),
+            (state(100.0, -5.0, 0.0, 0.0), state(0.0, 0.0, 10.0, -3e3), 2.0),
+        ] {
+            let coefficients = BoundarySolver::seventh_order_coefficients(&start, &end, duration).unwrap();
+            for (expected, t) in [(&start, 0.0), (&end, duration)] {
+                let point = motion.evaluate_seventh_order_polynomial(&coefficients, t);
+                let actual = [point.position, point.velocity, point.acceleration, point.jerk];
+                let expected = [expected.position, expected.velocity, expected.acceleration, expected.jerk];
+                for (order, (actual, expected)) in actual.into_iter().zip(expected).enumerate() {
+                    // Compared in normalized time, where all derivatives are
+                    // on the scale of the distance moved
+                    let scale = duration.powi(order as i32);
+                    let error = (actual - expected).abs() * scale / (end.position - start.position).abs();
+                    assert!(error < 1e-9, "t={} order {} got {}, expected {}", t, order, actual, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_derivatives_continuous() {
+        let motion = SnapCrackleMotion::new(1000.0, 5000.0);
+        let coefficients =
+            BoundarySolver::seventh_order_coefficients(&state(0.0, 10.0, 0.0, 0.0), &state(50.0, 0.0, 0.0, 0.0), 0.5).unwrap();
+        let derivatives = |t| {
+            let point = motion.evaluate_seventh_order_polynomial(&coefficients, t);
+            [point.position, point.velocity, point.acceleration, point.jerk, point.snap, point.crackle, point.pop, point.lock]
+        };
+
+        // Each derivative is the slope of the one before it, so none of them jump
+        let h = 1e-6;
+        let samples: Vec<f64> = (1..100).map(|i| 0.5 * i as f64 / 100.0).collect();
+        let mut scale = [0.0f64; 8];
+        for &t in &samples {
+            for (order, value) in derivatives(t).into_iter().enumerate() {
+                scale[order] = scale[order].max(value.abs());
+            }
+        }
+        for &t in &samples {
+            let (before, at, after) = (derivatives(t - h), derivatives(t), derivatives(t + h));
+            for order in 0..7 {
+                let slope = (after[order] - before[order]) / (2.0 * h);
+                assert!((slope - at[order + 1]).abs() < 1e-6 * scale[order + 1], "order {} at t={}", order + 1, t);
+            }
+        }
+        assert_eq!(derivatives(0.1)[7], derivatives(0.4)[7]);
+    }
+}
@@ -1,7 +1,10 @@
 // src/motion/stepper.rs - Complete step generator implementation
-use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use serde::Serialize;
 
 /// Complete step generator that converts motion positions to motor step commands
+#[derive(Debug)]
 pub struct StepGenerator {
     /// Steps per mm for each axis
     steps_per_mm: [f64; 4], // [X, Y, Z, E]
@@ -20,6 +23,63 @@ pub struct StepGenerator {
     
     /// Step buffer for batch processing
     step_buffer: StepBuffer,
+    
+    /// Extra extrusion while the filament flow changes
+    linear_advance: Option<LinearAdvance>,
+    
+    /// Advance extrusion applied at the end of the last move (mm)
+    e_advance: f64,
+    
+    /// Net steps sent to each motor, unaffected by position resets
+    stepped: [i64; 4],
+    
+    /// Drift beyond which an encoder reading is an error, `None` for
+    /// motors without an encoder (steps)
+    max_drift_steps: [Option<u32>; 4],
+    
+    /// Encoder reading minus `stepped` when each encoder was first read
+    encoder_origin: [Option<i64>; 4],
+    
+    /// Latest encoder comparison per motor
+    drift: [Option<StepPositionDrift>; 4],
+    
+    /// Drift from the latest readings, applied by the next `generate_steps`
+    pending_drift: [Option<i64>; 4],
+    
+    /// Whether each motor's drift is over `max_drift_steps`
+    drift_exceeded: [bool; 4],
+    
+    /// Drift errors not yet collected by `take_drift_events`
+    drift_events: Vec<StepPositionDrift>,
+}
+
+/// A motor's encoder position compared with the steps sent to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StepPositionDrift {
+    /// Motor index, 0-3 for [X, Y, Z, E]
+    pub axis: usize,
+    /// Steps sent to the motor
+    pub commanded: i64,
+    /// Position the encoder reports (steps)
+    pub actual: i64,
+    /// `actual - commanded`
+    pub delta: i64,
+}
+
+/// Linear advance: extrude `k_factor` mm more for each mm/s of filament speed
+///
+/// Unlike adjusting the planned position, this only adds E steps while the
+/// flow rises and takes them back while it falls; the path is unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearAdvance {
+    pub k_factor: f64,
+}
+
+impl LinearAdvance {
+    /// Advance extrusion added while E speed goes from `entry_velocity` to `exit_velocity` (mm)
+    pub fn advance_mm(&self, entry_velocity: f64, exit_velocity: f64) -> f64 {
+        self.k_factor * (exit_velocity - entry_velocity)
+    }
 }
 
 /// Step timing configuration
@@ -47,6 +107,7 @@ pub struct EnableTiming {
 }
 
 /// Step buffer for efficient batch processing
+#[derive(Debug)]
 pub struct StepBuffer {
     /// Buffered step commands
     commands: Vec<StepCommand>,
@@ -59,7 +120,7 @@ pub struct StepBuffer {
 }
 
 /// A single step command for precise motor control
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct StepCommand {
     /// Axis identifier
     pub axis: Axis,
@@ -74,7 +135,19 @@ pub struct StepCommand {
     pub timing: Option<StepTiming>,
     
     /// Step completion callback
-    pub callback: Option<Box<dyn Fn() + Send>>,
+    pub callback: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl fmt::Debug for StepCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StepCommand")
+            .field("axis", &self.axis)
+            .field("steps", &self.steps)
+            .field("direction", &self.direction)
+            .field("timing", &self.timing)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
 }
 
 /// Axis identifiers
@@ -135,6 +208,102 @@ impl StepGenerator {
                 max_size: 1000,
                 position: 0,
             },
+            linear_advance: None,
+            e_advance: 0.0,
+            stepped: [0; 4],
+            max_drift_steps: [None; 4],
+            encoder_origin: [None; 4],
+            drift: [None; 4],
+            pending_drift: [None; 4],
+            drift_exceeded: [false; 4],
+            drift_events: Vec::new(),
+        }
+    }
+
+    pub fn linear_advance(&self) -> Option<LinearAdvance> {
+        self.linear_advance
+    }
+
+    /// Extruder position the generated steps reached, without linear advance (mm)
+    pub fn extruder_position(&self) -> f64 {
+        self.current_steps[3] as f64 / self.steps_per_mm[3] - self.e_advance
+    }
+
+    /// Extruder steps generated so far, including linear advance
+    pub fn extruder_steps(&self) -> i64 {
+        self.current_steps[3]
+    }
+
+    /// Enable or disable linear advance for the following moves
+    pub fn set_linear_advance(&mut self, linear_advance: Option<LinearAdvance>) {
+        self.linear_advance = linear_advance;
+    }
+
+    /// Steps per mm for [X, Y, Z, E]
+    pub fn current_steps_per_mm(&self) -> [f64; 4] {
+        self.steps_per_mm
+    }
+
+    /// Change steps per mm, e.g. after E-steps calibration
+    pub fn set_steps_per_mm(&mut self, steps_per_mm: [f64; 4]) {
+        self.steps_per_mm = steps_per_mm;
+    }
+
+    /// Track motors with an encoder, each allowed to drift up to the given
+    /// number of steps before it counts as an error
+    pub fn set_max_drift_steps(&mut self, max_drift_steps: [Option<u32>; 4]) {
+        self.max_drift_steps = max_drift_steps;
+    }
+
+    /// Record an encoder reading of `steps` for the motor `axis`
+    ///
+    /// The first reading only sets the encoder's origin. Later ones are
+    /// compared with the steps sent so far; the next `generate_steps`
+    /// reconciles the motor to the encoder or, if the drift is over
+    /// `max_drift_steps`, reports it instead.
+    pub fn report_encoder_steps(&mut self, axis: usize, steps: i64) {
+        let Some(max_drift) = self.max_drift_steps.get(axis).copied().flatten() else {
+            return;
+        };
+        let origin = *self.encoder_origin[axis].get_or_insert(steps - self.stepped[axis]);
+        let drift = StepPositionDrift {
+            axis,
+            commanded: self.stepped[axis],
+            actual: steps - origin,
+            delta: steps - origin - self.stepped[axis],
+        };
+        self.drift[axis] = Some(drift);
+        self.pending_drift[axis] = Some(drift.delta);
+        if drift.delta.unsigned_abs() <= max_drift as u64 {
+            self.drift_exceeded[axis] = false;
+        } else if !self.drift_exceeded[axis] {
+            self.drift_exceeded[axis] = true;
+            self.drift_events.push(drift);
+        }
+    }
+
+    /// Latest encoder comparison for each motor with an encoder
+    pub fn position_drift(&self) -> Vec<StepPositionDrift> {
+        self.drift.iter().flatten().copied().collect()
+    }
+
+    /// Drift errors found since the last call, each reported once
+    pub fn take_drift_events(&mut self) -> Vec<StepPositionDrift> {
+        std::mem::take(&mut self.drift_events)
+    }
+
+    /// Move each motor's step count to its encoder reading when the drift
+    /// is within limits, so the next move makes up lost steps
+    fn reconcile_encoders(&mut self) {
+        for axis in 0..4 {
+            let Some(delta) = self.pending_drift[axis].take() else {
+                continue;
+            };
+            if delta == 0 || self.drift_exceeded[axis] {
+                continue;
+            }
+            self.current_steps[axis] += delta;
+            self.stepped[axis] += delta;
         }
     }
 
@@ -153,6 +322,8 @@ impl StepGenerator {
 
     /// Generate step commands for movement to new position
     pub fn generate_steps(&mut self, new_position: &[f64; 4]) -> Vec<StepCommand> {
+        self.reconcile_encoders();
+        
         // Convert new position to steps
         let target_steps = self.position_to_steps(new_position);
         
@@ -160,6 +331,7 @@ impl StepGenerator {
         let mut step_deltas = [0i64; 4];
         for i in 0..4 {
             step_deltas[i] = target_steps[i] - self.current_steps[i];
+            self.stepped[i] += step_deltas[i];
         }
         
         // Store current steps for next calculation
@@ -170,7 +342,7 @@ impl StepGenerator {
         
         for (i, &delta) in step_deltas.iter().enumerate() {
             if delta != 0 {
-                let steps = delta.abs() as u32;
+                let steps = u32::try_from(delta.unsigned_abs()).unwrap_or(u32::MAX);
                 let direction = if delta > 0 {
                     !self.direction_invert[i]
                 } else {
@@ -198,6 +370,33 @@ impl StepGenerator {
         commands
     }
 
+    /// Generate step commands for a move from `start` to `end`, regardless
+    /// of where the last move ended
+    pub fn generate_move(&mut self, start: &[f64; 4], end: &[f64; 4]) -> Vec<StepCommand> {
+        self.current_steps = self.position_to_steps(start);
+        self.generate_steps(end)
+    }
+
+    /// Like `generate_move`, adding linear advance for the E speed going
+    /// from `e_velocity.0` to `e_velocity.1` (mm/s)
+    ///
+    /// Moves that do not extrude should pass zero speeds so the advance is
+    /// taken back before travelling.
+    pub fn generate_extruding_move(
+        &mut self,
+        start: &[f64; 4],
+        end: &[f64; 4],
+        e_velocity: (f64, f64),
+    ) -> Vec<StepCommand> {
+        let end_advance = self
+            .linear_advance
+            .map_or(0.0, |advance| advance.advance_mm(0.0, e_velocity.1));
+        let start = [start[0], start[1], start[2], start[3] + self.e_advance];
+        let end = [end[0], end[1], end[2], end[3] + end_advance];
+        self.e_advance = end_advance;
+        self.generate_move(&start, &end)
+    }
+
     /// Generate interpolated steps for smooth motion
     pub fn generate_interpolated_steps(
         &mut self,
@@ -311,9 +510,9 @@ impl StepCommand {
     /// Create step command with callback
     pub fn with_callback<F>(mut self, callback: F) -> Self 
     where 
-        F: Fn() + Send + 'static,
+        F: Fn() + Send + Sync + 'static,
     {
-        self.callback = Some(Box::new(callback));
+        self.callback = Some(Arc::new(callback));
         self
     }
     
@@ -346,7 +545,7 @@ impl StepBuffer {
     }
     
     /// Get next command from buffer
-    pub fn next(&mut self) -> Option<StepCommand> {
+    pub fn next_command(&mut self) -> Option<StepCommand> {
         if self.position < self.commands.len() {
             let command = self.commands[self.position].clone();
             self.position += 1;
@@ -398,4 +597,32 @@ impl Default for StepBuffer {
     fn default() -> Self {
         Self::new(1000)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e_steps(commands: &[StepCommand]) -> u32 {
+        commands.iter().filter(|c| c.axis == Axis::E).map(|c| c.steps).sum()
+    }
+
+    #[test]
+    fn test_linear_advance_extrusion() {
+        let advance = LinearAdvance { k_factor: 0.2 };
+        assert!((advance.advance_mm(0.0, 100.0) - 20.0).abs() < 1e-9);
+
+        let mut generator = StepGenerator::new([80.0, 80.0, 400.0, 100.0], [false; 4]);
+        let start = [0.0; 4];
+        let end = [10.0, 0.0, 0.0, 1.0];
+        assert_eq!(e_steps(&generator.generate_extruding_move(&start, &end, (0.0, 100.0))), 100);
+
+        generator.set_linear_advance(Some(advance));
+        // 1mm planned plus 20mm advance at 100 steps/mm
+        assert_eq!(e_steps(&generator.generate_extruding_move(&start, &end, (0.0, 100.0))), 2100);
+
+        // Decelerating back to a stop retracts the advance again
+        let commands = generator.generate_extruding_move(&end, &[20.0, 0.0, 0.0, 2.0], (100.0, 0.0));
+        let e = commands.iter().find(|c| c.axis == Axis::E).unwrap();
+        assert_eq!((e.steps, e.direction), (1900, false));
+    }
+}
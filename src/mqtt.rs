@@ -0,0 +1,282 @@
+// src/mqtt.rs - MQTT telemetry for home automation (Home Assistant, Node-RED)
+use std::sync::Arc;
+use std::time::Duration;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+use tokio::sync::{RwLock, broadcast};
+use tokio::task::JoinHandle;
+use crate::config::MqttConfig;
+use crate::printer::{PrinterEvent, PrinterState};
+
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// Requests buffered between the publisher and the MQTT event loop
+const CLIENT_CAPACITY: usize = 64;
+
+/// Periodically publishes printer state and forwards printer events to an
+/// MQTT broker
+pub struct MqttTelemetryPublisher {
+    config: MqttConfig,
+    state: Arc<RwLock<PrinterState>>,
+    events: broadcast::Receiver<PrinterEvent>,
+}
+
+impl MqttTelemetryPublisher {
+    pub fn new(
+        config: MqttConfig,
+        state: Arc<RwLock<PrinterState>>,
+        events: broadcast::Receiver<PrinterEvent>,
+    ) -> Self {
+        Self { config, state, events }
+    }
+
+    /// Connect and publish until the printer event channel closes
+    ///
+    /// Publishing never waits on the broker; while disconnected, messages
+    /// that don't fit in the client buffer are dropped.
+    pub fn spawn(self) -> Result<JoinHandle<()>, Box<dyn std::error::Error>> {
+        let (host, port) = parse_broker_url(&self.config.broker_url)?;
+        let mut options = MqttOptions::new(&self.config.client_id, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &self.config.username {
+            options.set_credentials(username, self.config.password.clone().unwrap_or_default());
+        }
+        let (client, eventloop) = AsyncClient::new(options, CLIENT_CAPACITY);
+
+        let config = self.config.clone();
+        Ok(tokio::spawn(async move {
+            tokio::select! {
+                _ = drive_connection(eventloop, client.clone(), config) => {}
+                _ = self.run(client) => {}
+            }
+        }))
+    }
+
+    async fn run(mut self, client: AsyncClient) {
+        let prefix = self.config.topic_prefix.trim_end_matches('/').to_string();
+        let mut interval = tokio::time::interval(Duration::from_millis(self.config.publish_interval_ms.max(1)));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let state = self.state.read().await.clone();
+                    for (topic, payload) in telemetry(&prefix, &state) {
+                        publish(&client, topic, payload, false);
+                    }
+                }
+                event = self.events.recv() => match event {
+                    Ok(event) => publish(&client, format!("{}/events", prefix), event.to_json().to_string(), false),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("MQTT publisher lagging, skipped {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+}
+
+/// Poll the MQTT connection, announcing the sensors to Home Assistant on
+/// every (re)connect
+async fn drive_connection(mut eventloop: EventLoop, client: AsyncClient, config: MqttConfig) {
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("Connected to MQTT broker {}", config.broker_url);
+                for (topic, payload) in discovery(&config) {
+                    publish(&client, topic, payload, true);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("MQTT connection error: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+fn publish(client: &AsyncClient, topic: String, payload: String, retain: bool) {
+    if let Err(e) = client.try_publish(&topic, QoS::AtMostOnce, retain, payload) {
+        tracing::debug!("Dropped MQTT message for {}: {}", topic, e);
+    }
+}
+
+/// Periodic state topics and their payloads
+fn telemetry(prefix: &str, state: &PrinterState) -> Vec<(String, String)> {
+    let status = if !state.ready {
+        "offline"
+    } else if state.print_progress > 0.0 && state.print_progress < 1.0 {
+        "printing"
+    } else {
+        "idle"
+    };
+    let position = serde_json::json!({
+        "x": state.position[0],
+        "y": state.position[1],
+        "z": state.position[2],
+    });
+    vec![
+        (format!("{}/hotend/temperature", prefix), format!("{:.2}", state.temperature)),
+        (format!("{}/bed/temperature", prefix), format!("{:.2}", state.bed_temperature)),
+        (format!("{}/status", prefix), status.to_string()),
+        (format!("{}/position", prefix), position.to_string()),
+    ]
+}
+
+/// Home Assistant MQTT discovery messages for the published sensors
+fn discovery(config: &MqttConfig) -> Vec<(String, String)> {
+    let prefix = config.topic_prefix.trim_end_matches('/');
+    let id = &config.client_id;
+    let device = serde_json::json!({ "identifiers": [id], "name": id, "manufacturer": "Krusty" });
+    let sensors = [
+        ("hotend_temperature", "Hotend temperature", "hotend/temperature", Some("°C")),
+        ("bed_temperature", "Bed temperature", "bed/temperature", Some("°C")),
+        ("status", "Status", "status", None),
+    ];
+
+    sensors
+        .into_iter()
+        .map(|(object_id, name, topic, unit)| {
+            let mut payload = serde_json::json!({
+                "name": name,
+                "unique_id": format!("{}_{}", id, object_id),
+                "state_topic": format!("{}/{}", prefix, topic),
+                "device": device,
+            });
+            if let Some(unit) = unit {
+                payload["unit_of_measurement"] = unit.into();
+                payload["device_class"] = "temperature".into();
+                payload["state_class"] = "measurement".into();
+            }
+            (format!("homeassistant/sensor/{}/{}/config", id, object_id), payload.to_string())
+        })
+        .collect()
+}
+
+/// Split `mqtt://host:port` (scheme and port optional) into host and port
+fn parse_broker_url(url: &str) -> Result<(String, u16), Box<dyn std::error::Error>> {
+    let address = url
+        .strip_prefix("mqtt://")
+        .or_else(|| url.strip_prefix("tcp://"))
+        .unwrap_or(url)
+        .trim_end_matches('/');
+    match address.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_string(), port.parse()?)),
+        None if !address.is_empty() => Ok((address.to_string(), DEFAULT_MQTT_PORT)),
+        None => Err(format!("Invalid MQTT broker URL: {}", url).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    /// Minimal MQTT 3.1.1 broker: accepts one client and forwards the
+    /// topic and payload of every PUBLISH it receives
+    async fn spawn_broker() -> (u16, mpsc::UnboundedReceiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            while let Some((packet_type, body)) = read_packet(&mut socket).await {
+                match packet_type {
+                    1 => socket.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap(), // CONNECT -> CONNACK
+                    3 => {
+                        let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+                        let topic = String::from_utf8_lossy(&body[2..2 + topic_len]).into_owned();
+                        let payload = String::from_utf8_lossy(&body[2 + topic_len..]).into_owned();
+                        let _ = tx.send((topic, payload));
+                    }
+                    12 => socket.write_all(&[0xD0, 0x00]).await.unwrap(), // PINGREQ -> PINGRESP
+                    _ => {}
+                }
+            }
+        });
+        (port, rx)
+    }
+
+    async fn read_packet(socket: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let header = socket.read_u8().await.ok()?;
+        let mut length = 0usize;
+        for shift in (0..28).step_by(7) {
+            let byte = socket.read_u8().await.ok()?;
+            length |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        socket.read_exact(&mut body).await.ok()?;
+        Some((header >> 4, body))
+    }
+
+    #[test]
+    fn test_parse_broker_url() {
+        assert_eq!(parse_broker_url("mqtt://broker.local:1884").unwrap(), ("broker.local".to_string(), 1884));
+        assert_eq!(parse_broker_url("10.0.0.2").unwrap(), ("10.0.0.2".to_string(), 1883));
+        assert!(parse_broker_url("mqtt://host:port").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_temperature_topics_follow_state() {
+        let (port, mut received) = spawn_broker().await;
+        let config = MqttConfig {
+            broker_url: format!("mqtt://127.0.0.1:{}", port),
+            client_id: "krusty-test".to_string(),
+            topic_prefix: "printer".to_string(),
+            publish_interval_ms: 100,
+            username: None,
+            password: None,
+        };
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let (event_tx, event_rx) = broadcast::channel(16);
+        let publisher = MqttTelemetryPublisher::new(config, state.clone(), event_rx).spawn().unwrap();
+
+        // Wait for the first telemetry round before changing the state
+        let mut discovery_topics = Vec::new();
+        loop {
+            let (topic, _) = received.recv().await.unwrap();
+            if topic.starts_with("homeassistant/") {
+                discovery_topics.push(topic);
+            } else if topic == "printer/hotend/temperature" {
+                break;
+            }
+        }
+        assert!(discovery_topics.contains(&"homeassistant/sensor/krusty-test/hotend_temperature/config".to_string()));
+
+        {
+            let mut state = state.write().await;
+            state.temperature = 215.5;
+            state.bed_temperature = 60.0;
+        }
+        let mut latest = std::collections::HashMap::new();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while latest.get("printer/hotend/temperature") != Some(&215.5)
+                || latest.get("printer/bed/temperature") != Some(&60.0)
+            {
+                let (topic, payload) = received.recv().await.unwrap();
+                if topic.ends_with("/temperature") {
+                    let value: f64 = payload.parse().expect("temperature payload is a float");
+                    latest.insert(topic, value);
+                }
+            }
+        })
+        .await
+        .expect("temperatures published within 1s of the update");
+
+        event_tx.send(PrinterEvent::PrintStarted { path: "part.gcode".to_string() }).unwrap();
+        let payload = loop {
+            let (topic, payload) = received.recv().await.unwrap();
+            if topic == "printer/events" {
+                break payload;
+            }
+        };
+        assert!(payload.contains("print_started"));
+
+        drop(event_tx);
+        publisher.await.unwrap();
+    }
+}
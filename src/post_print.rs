@@ -0,0 +1,177 @@
+// src/post_print.rs - Cooldown and parking after a print
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::config::PostPrintConfig;
+use crate::gcode::GCodeProcessor;
+use crate::printer::PrinterState;
+
+/// How often the hotend is checked while cooling down
+const COOLDOWN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Feedrate for the retraction (mm/min)
+const RETRACT_FEEDRATE: f64 = 2400.0;
+
+/// Feedrate for parking (mm/min)
+const PARK_FEEDRATE: f64 = 6000.0;
+
+type HotendReading = Arc<dyn Fn(&PrinterState) -> f64 + Send + Sync>;
+
+/// Retracts, parks, turns the heaters off, then keeps the part fan running
+/// until the hotend has cooled
+///
+/// M108 ends the cooldown wait early.
+#[derive(Clone)]
+pub struct PostPrintRoutine {
+    config: PostPrintConfig,
+    hotend_reading: HotendReading,
+}
+
+impl fmt::Debug for PostPrintRoutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostPrintRoutine").field("config", &self.config).finish_non_exhaustive()
+    }
+}
+
+impl PostPrintRoutine {
+    pub fn new(config: PostPrintConfig) -> Self {
+        Self {
+            config,
+            hotend_reading: Arc::new(|state: &PrinterState| state.temperature),
+        }
+    }
+
+    /// Read the hotend temperature with `reading` instead of from
+    /// `PrinterState::temperature`
+    pub fn with_hotend_reading(mut self, reading: impl Fn(&PrinterState) -> f64 + Send + Sync + 'static) -> Self {
+        self.hotend_reading = Arc::new(reading);
+        self
+    }
+
+    pub async fn run(&self, gcode: &mut GCodeProcessor) -> Result<(), Box<dyn std::error::Error>> {
+        let config = &self.config;
+        tracing::info!("Running post-print routine");
+
+        if config.retract_mm > 0.0 {
+            gcode.process_command("M83").await?;
+            gcode.process_command(&format!("G1 E{:.3} F{}", -config.retract_mm, RETRACT_FEEDRATE)).await?;
+        }
+        if let (Some(x), Some(y)) = (config.park_x, config.park_y) {
+            gcode.process_command("G90").await?;
+            gcode.process_command(&format!("G0 X{} Y{} F{}", x, y, PARK_FEEDRATE)).await?;
+        }
+        gcode.process_command("M140 S0").await?;
+        gcode.process_command("M104 S0").await?;
+        if config.beep {
+            gcode.process_command("M300").await?;
+        }
+
+        gcode.process_command("M106 S255").await?;
+        self.wait_for_cooldown(gcode).await;
+        gcode.process_command("M107").await?;
+
+        if config.disable_motors_after_cooldown {
+            gcode.process_command("M84").await?;
+        }
+        tracing::info!("Post-print routine finished");
+        Ok(())
+    }
+
+    /// Wait until the hotend is below `fan_cooldown_temp`, or M108
+    async fn wait_for_cooldown(&self, gcode: &GCodeProcessor) {
+        let signal = gcode.cancel_wait_signal();
+        let cancelled = signal.notified();
+        tokio::pin!(cancelled);
+        cancelled.as_mut().enable();
+
+        loop {
+            let temperature = (self.hotend_reading)(&gcode.get_state().await);
+            if temperature < self.config.fan_cooldown_temp {
+                return;
+            }
+            tokio::select! {
+                _ = &mut cancelled => {
+                    tracing::info!("Cooldown skipped at {:.1}°C", temperature);
+                    return;
+                }
+                _ = tokio::time::sleep(COOLDOWN_POLL_INTERVAL) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::sync::RwLock;
+    use crate::config::Config;
+    use crate::hardware::HardwareManager;
+    use crate::motion::{MotionConfig, MotionController};
+
+    async fn homed_processor() -> GCodeProcessor {
+        let config: Config = toml::from_str(include_str!("printer.toml")).unwrap();
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut hardware = HardwareManager::new(config.clone());
+        hardware.connect().await.unwrap();
+        let motion = MotionController::new(state.clone(), hardware, MotionConfig::new_from_printer_config(&config));
+        let mut gcode = GCodeProcessor::new(state, motion);
+        gcode.process_command("G28").await.unwrap();
+        gcode.process_command("M104 S210").await.unwrap();
+        gcode
+    }
+
+    /// Routine reading the hotend from `temperature` instead of the state
+    fn routine(temperature: &Arc<Mutex<f64>>) -> PostPrintRoutine {
+        let temperature = temperature.clone();
+        PostPrintRoutine::new(PostPrintConfig {
+            park_x: Some(0.0),
+            park_y: Some(200.0),
+            ..PostPrintConfig::default()
+        })
+        .with_hotend_reading(move |_| *temperature.lock().unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_motors_disabled_after_cooldown() {
+        let gcode = homed_processor().await;
+        let temperature = Arc::new(Mutex::new(210.0));
+        let routine = routine(&temperature);
+        let mut runner = gcode.clone();
+        let task = tokio::spawn(async move { routine.run(&mut runner).await.map_err(|e| e.to_string()) });
+
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        let state = gcode.get_state().await;
+        assert!(state.homed, "motors disabled before the hotend cooled");
+        assert_eq!(state.fan.get_speed(), 1.0);
+        assert_eq!(state.bed_target, 0.0);
+        assert!(!task.is_finished());
+
+        *temperature.lock().unwrap() = 49.0;
+        tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap().unwrap();
+        let state = gcode.get_state().await;
+        assert!(!state.homed);
+        assert_eq!(state.fan.get_speed(), 0.0);
+
+        let commands: Vec<_> = gcode.history_entries().into_iter().map(|entry| entry.command).collect();
+        let retract = commands.iter().position(|command| command.starts_with("G1 E-2.000")).unwrap();
+        let park = commands.iter().position(|command| command.starts_with("G0 X0 Y200")).unwrap();
+        assert!(retract < park);
+        assert_eq!(commands.last().map(String::as_str), Some("M84"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_m108_skips_cooldown() {
+        let mut gcode = homed_processor().await;
+        let temperature = Arc::new(Mutex::new(210.0));
+        let routine = routine(&temperature);
+        let mut runner = gcode.clone();
+        let task = tokio::spawn(async move { routine.run(&mut runner).await.map_err(|e| e.to_string()) });
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert!(!task.is_finished());
+        gcode.process_command("M108").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), task).await.unwrap().unwrap().unwrap();
+        assert!(!gcode.get_state().await.homed);
+    }
+}
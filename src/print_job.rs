@@ -0,0 +1,707 @@
+// src/print_job.rs - Print job lifecycle
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime};
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{RwLock, broadcast};
+use crate::config::Config;
+use crate::file::FileManager;
+use crate::file::metadata::GCodeMetadata;
+use crate::gcode::GCodeProcessor;
+use crate::printer::{PrinterEvent, PrinterState};
+
+/// Lowest chamber temperature (°C) each material prints reliably at;
+/// materials not listed don't need a warm chamber
+const MATERIAL_CHAMBER_TEMPS: &[(&str, f64)] = &[
+    ("ABS", 40.0),
+    ("ASA", 40.0),
+    ("PC", 45.0),
+    ("PA", 40.0),
+    ("PA6", 40.0),
+    ("PA12", 40.0),
+    ("NYLON", 40.0),
+];
+
+/// Where a print job is in its lifecycle
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintJobState {
+    Queued,
+    Preparing,
+    Preheating,
+    Printing,
+    Paused,
+    Cancelling,
+    Completed,
+    Failed(String),
+}
+
+/// Something that happened to a print job
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintJobEvent {
+    /// Begin setting the job up: `Queued` to `Preparing`, then `Preheating`
+    Start,
+    /// Heaters are at temperature, or there was nothing to heat
+    PreheatComplete,
+    Pause,
+    Resume,
+    /// The last line ran, or a cancellation finished winding down
+    Complete,
+    Cancel,
+    Fail(String),
+}
+
+/// An event that does not apply to the job's current state
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidTransition {
+    pub from: PrintJobState,
+    pub event: PrintJobEvent,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid print job transition: {:?} on {:?}", self.event, self.from)
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
+impl PrintJobState {
+    /// The state `event` leads to from this one
+    pub fn transition(&self, event: PrintJobEvent) -> Result<PrintJobState, InvalidTransition> {
+        use PrintJobEvent as E;
+        use PrintJobState as S;
+        let next = match (self, &event) {
+            (S::Queued, E::Start) => S::Preparing,
+            (S::Preparing, E::Start) => S::Preheating,
+            (S::Preparing | S::Preheating, E::PreheatComplete) => S::Printing,
+            (S::Printing, E::Pause) => S::Paused,
+            (S::Paused, E::Resume) => S::Printing,
+            (S::Printing, E::Complete) => S::Completed,
+            (S::Cancelling, E::Complete) => S::Failed("Cancelled".to_string()),
+            (state, E::Cancel) if state.is_active() && *state != S::Cancelling => S::Cancelling,
+            (state, E::Fail(reason)) if state.is_active() => S::Failed(reason.clone()),
+            (state, _) => {
+                return Err(InvalidTransition {
+                    from: state.clone(),
+                    event,
+                });
+            }
+        };
+        Ok(next)
+    }
+
+    /// Not yet finished, one way or the other
+    pub fn is_active(&self) -> bool {
+        !matches!(self, PrintJobState::Completed | PrintJobState::Failed(_))
+    }
+}
+
+/// The file being printed, or the last one printed
+#[derive(Debug, Clone)]
+pub struct PrintJob {
+    pub path: String,
+    /// File size in bytes
+    pub size: u64,
+    /// When the job was queued
+    pub created_at: SystemTime,
+    state: PrintJobState,
+    started: Option<Instant>,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+    finished: Option<Instant>,
+    filament_used_mm: f64,
+}
+
+impl PrintJob {
+    pub fn new(path: &str, size: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            size,
+            created_at: SystemTime::now(),
+            state: PrintJobState::Queued,
+            started: None,
+            paused_at: None,
+            paused_total: Duration::ZERO,
+            finished: None,
+            filament_used_mm: 0.0,
+        }
+    }
+
+    pub fn state(&self) -> &PrintJobState {
+        &self.state
+    }
+
+    /// Apply `event`, leaving the job untouched if it does not apply
+    pub fn transition(&mut self, event: PrintJobEvent) -> Result<PrintJobState, InvalidTransition> {
+        let next = self.state.transition(event)?;
+        tracing::info!("Print job {}: {:?} -> {:?}", self.path, self.state, next);
+
+        let now = Instant::now();
+        match next {
+            PrintJobState::Preparing => self.started = Some(now),
+            PrintJobState::Paused => self.paused_at = Some(now),
+            PrintJobState::Printing => {
+                if let Some(paused_at) = self.paused_at.take() {
+                    self.paused_total += now.duration_since(paused_at);
+                }
+            }
+            PrintJobState::Completed | PrintJobState::Failed(_) => self.finished = Some(now),
+            _ => {}
+        }
+        self.state = next.clone();
+        Ok(next)
+    }
+
+    /// Seconds spent on the job since it started, not counting pauses
+    pub fn elapsed_secs(&self) -> f64 {
+        let Some(started) = self.started else {
+            return 0.0;
+        };
+        let end = self.finished.or(self.paused_at).unwrap_or_else(Instant::now);
+        let paused = match (self.paused_at, self.finished) {
+            // Failed or cancelled while paused: that pause never ended
+            (Some(paused_at), Some(finished)) => self.paused_total + finished.duration_since(paused_at),
+            _ => self.paused_total,
+        };
+        end.duration_since(started).saturating_sub(paused).as_secs_f64()
+    }
+
+    /// Count an extruder move towards the filament used; retractions
+    /// subtract until they are primed back
+    pub fn record_extrusion(&mut self, e: f64) {
+        if self.state == PrintJobState::Printing {
+            self.filament_used_mm += e;
+        }
+    }
+
+    /// Net filament pushed through the nozzle while printing
+    pub fn filament_used_mm(&self) -> f64 {
+        self.filament_used_mm
+    }
+}
+
+/// Run a started job's file to the end, then the post-print routine,
+/// tracking it in `state.job`
+///
+/// Errors are returned as strings so the future can be spawned.
+pub async fn run(mut job: PrintJob, state: &RwLock<PrinterState>, gcode: &mut GCodeProcessor) -> Result<usize, String> {
+    let path = job.path.clone();
+    job.transition(PrintJobEvent::Start).map_err(|e| e.to_string())?;
+    // Heating is left to the file's own M109/M190
+    job.transition(PrintJobEvent::PreheatComplete).map_err(|e| e.to_string())?;
+    state.write().await.job = Some(job);
+
+    let mut result = gcode.process_file_streaming(&path).await.map_err(|e| e.to_string());
+    if let Ok(lines) = result {
+        result = gcode.finish_print().await.map(|()| lines).map_err(|e| e.to_string());
+    }
+    if let Some(job) = state.write().await.job.as_mut() {
+        let event = match &result {
+            Ok(_) => PrintJobEvent::Complete,
+            Err(e) => PrintJobEvent::Fail(e.clone()),
+        };
+        if let Err(e) = job.transition(event) {
+            tracing::warn!("{}", e);
+        }
+    }
+    result
+}
+
+/// Check `path` against the checksum stored when it was uploaded
+///
+/// Files without a stored checksum are accepted. On a mismatch a
+/// `FileIntegrityError` event is sent and the print must not start.
+pub async fn verify_file(
+    files: &FileManager,
+    events: &broadcast::Sender<PrinterEvent>,
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(expected) = files.get_stored_checksum(path).await? else {
+        return Ok(());
+    };
+    let actual = files.compute_checksum(path).await?;
+    if actual.eq_ignore_ascii_case(&expected) {
+        return Ok(());
+    }
+    let _ = events.send(PrinterEvent::FileIntegrityError {
+        path: path.to_string(),
+        expected: expected.clone(),
+        actual: actual.clone(),
+    });
+    Err(format!("Checksum mismatch for {}: expected {}, got {}", path, expected, actual).into())
+}
+
+/// `run` a verified job, sending `PrintStarted` and then `PrintCompleted`
+/// or `PrintFailed` on `events`
+pub async fn run_with_events(
+    job: PrintJob,
+    state: &RwLock<PrinterState>,
+    gcode: &mut GCodeProcessor,
+    events: &broadcast::Sender<PrinterEvent>,
+) -> Result<usize, String> {
+    let path = job.path.clone();
+    let _ = events.send(PrinterEvent::PrintStarted { path: path.clone() });
+    let result = run(job, state, gcode).await;
+    let _ = events.send(match &result {
+        Ok(lines) => PrinterEvent::PrintCompleted { path, lines: *lines },
+        Err(e) => PrinterEvent::PrintFailed { path, reason: e.clone() },
+    });
+    result
+}
+
+/// How serious a pre-print finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, the print may still start
+    Warning,
+    /// The print must not start
+    Error,
+}
+
+/// A problem found before starting a print
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationWarning {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl ValidationWarning {
+    fn warning(message: String) -> Self {
+        Self { severity: Severity::Warning, message }
+    }
+
+    fn error(message: String) -> Self {
+        Self { severity: Severity::Error, message }
+    }
+}
+
+/// What a G-code file does before it first extrudes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrintFilePreamble {
+    /// Highest hotend temperature set with M104/M109
+    pub hotend_temp: Option<f64>,
+    /// Runs G28
+    pub homes: bool,
+}
+
+impl PrintFilePreamble {
+    /// Scan G-code lines up to the first move with positive E
+    pub fn parse_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut preamble = Self::default();
+        for line in lines {
+            if extrudes(line) {
+                break;
+            }
+            match command(line).as_deref() {
+                Some("G28") => preamble.homes = true,
+                Some("M104" | "M109") => {
+                    if let Some(temp) = param(line, 'S') {
+                        preamble.hotend_temp = Some(preamble.hotend_temp.map_or(temp, |current| current.max(temp)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        preamble
+    }
+}
+
+/// Upper-case command word of a G-code line, comments stripped
+fn command(line: &str) -> Option<String> {
+    line.split(';').next()?.split_whitespace().next().map(str::to_ascii_uppercase)
+}
+
+/// Value of a parameter such as `S200`, either case
+fn param(line: &str, letter: char) -> Option<f64> {
+    line.split(';')
+        .next()?
+        .split_whitespace()
+        .skip(1)
+        .find(|word| word.chars().next().is_some_and(|first| first.eq_ignore_ascii_case(&letter)))
+        .and_then(|word| word[1..].parse().ok())
+}
+
+/// A move that pushes filament
+fn extrudes(line: &str) -> bool {
+    matches!(command(line).as_deref(), Some("G0" | "G1")) && param(line, 'E').is_some_and(|e| e > 0.0)
+}
+
+/// Checks a print's preconditions before it starts
+#[derive(Debug, Clone, Default)]
+pub struct PrintJobValidator {
+    /// Why the file can't be printed: missing, unreadable or corrupted
+    pub file_problem: Option<String>,
+    pub metadata: GCodeMetadata,
+    pub preamble: PrintFilePreamble,
+}
+
+impl PrintJobValidator {
+    /// Read what the checks need from the file at `path`
+    pub async fn inspect(files: &FileManager, path: &str) -> Self {
+        let mut validator = Self::default();
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) => {
+                validator.file_problem = Some(format!("G-code file {} not found: {}", path, e));
+                return validator;
+            }
+        };
+        match files.get_stored_checksum(path).await {
+            Ok(Some(expected)) => match files.compute_checksum(path).await {
+                Ok(actual) if actual.eq_ignore_ascii_case(&expected) => {}
+                Ok(actual) => {
+                    validator.file_problem = Some(format!(
+                        "Checksum mismatch for {}: expected {}, got {}",
+                        path, expected, actual
+                    ));
+                }
+                Err(e) => validator.file_problem = Some(format!("Could not read {}: {}", path, e)),
+            },
+            Ok(None) => {}
+            Err(e) => validator.file_problem = Some(format!("Could not read checksum of {}: {}", path, e)),
+        }
+        validator.metadata = files.parse_metadata(path).await.unwrap_or_default();
+
+        let mut lines = BufReader::new(file).lines();
+        let mut preamble = Vec::new();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let done = extrudes(&line);
+            preamble.push(line);
+            if done {
+                break;
+            }
+        }
+        validator.preamble = PrintFilePreamble::parse_lines(preamble.iter().map(String::as_str));
+        validator
+    }
+
+    /// Everything wrong with starting `job` now
+    pub fn validate(&self, job: &PrintJob, state: &PrinterState, config: &Config) -> Vec<ValidationWarning> {
+        let mut findings = Vec::new();
+
+        if let Some(problem) = &self.file_problem {
+            findings.push(ValidationWarning::error(problem.clone()));
+        }
+
+        let min_extrude_temp = config.extruder.min_extrude_temp;
+        let preheated = self.preamble.hotend_temp.is_some_and(|temp| temp >= min_extrude_temp);
+        if state.temperature < min_extrude_temp && !preheated {
+            findings.push(ValidationWarning::error(format!(
+                "Hotend is at {:.0}°C, below the minimum extrusion temperature of {:.0}°C, and {} does not heat it",
+                state.temperature, min_extrude_temp, job.path
+            )));
+        }
+
+        if !state.homed && !self.preamble.homes {
+            findings.push(ValidationWarning::error(format!(
+                "Axes are not homed and {} does not home them",
+                job.path
+            )));
+        }
+
+        if config.probe.as_ref().is_some_and(|probe| probe.z_offset.is_none()) {
+            findings.push(ValidationWarning::warning("Z probe offset has not been calibrated".to_string()));
+        }
+
+        let sensor_enabled = config.filament_sensor.as_ref().is_some_and(|sensor| sensor.enabled);
+        if sensor_enabled
+            && let (Some(needed), Some(remaining)) = (self.metadata.filament_used_mm, state.remaining_filament_mm)
+            && needed > remaining
+        {
+            findings.push(ValidationWarning::warning(format!(
+                "Print needs {:.0} mm of filament but only {:.0} mm is left",
+                needed, remaining
+            )));
+        }
+
+        if let (Some(material), Some(chamber)) = (&self.metadata.filament_type, &state.chamber)
+            && let Some(min_temp) = min_chamber_temp(material)
+            && chamber.get_temperature() < min_temp
+        {
+            findings.push(ValidationWarning::warning(format!(
+                "Chamber is at {:.0}°C, below the {:.0}°C recommended for {}",
+                chamber.get_temperature(),
+                min_temp,
+                material
+            )));
+        }
+
+        findings
+    }
+}
+
+/// Minimum chamber temperature for a slicer material name like `PA-CF`
+fn min_chamber_temp(material: &str) -> Option<f64> {
+    let base = material.split(['-', '+', ' ', ';']).next().unwrap_or("").trim().to_ascii_uppercase();
+    MATERIAL_CHAMBER_TEMPS.iter().find(|(name, _)| *name == base).map(|&(_, temp)| temp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChamberConfig, FilamentSensorConfig, ProbeConfig};
+    use crate::temperature::Heater;
+
+    fn states() -> Vec<PrintJobState> {
+        vec![
+            PrintJobState::Queued,
+            PrintJobState::Preparing,
+            PrintJobState::Preheating,
+            PrintJobState::Printing,
+            PrintJobState::Paused,
+            PrintJobState::Cancelling,
+            PrintJobState::Completed,
+            PrintJobState::Failed("jam".to_string()),
+        ]
+    }
+
+    fn events() -> Vec<PrintJobEvent> {
+        vec![
+            PrintJobEvent::Start,
+            PrintJobEvent::PreheatComplete,
+            PrintJobEvent::Pause,
+            PrintJobEvent::Resume,
+            PrintJobEvent::Complete,
+            PrintJobEvent::Cancel,
+            PrintJobEvent::Fail("thermal runaway".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_transition_table() {
+        use PrintJobEvent as E;
+        use PrintJobState as S;
+        let failed = || S::Failed("thermal runaway".to_string());
+        let expected = |state: &S, event: &E| -> Option<S> {
+            Some(match (state, event) {
+                (S::Queued, E::Start) => S::Preparing,
+                (S::Queued, E::Cancel) => S::Cancelling,
+                (S::Queued, E::Fail(_)) => failed(),
+                (S::Preparing, E::Start) => S::Preheating,
+                (S::Preparing, E::PreheatComplete) => S::Printing,
+                (S::Preparing, E::Cancel) => S::Cancelling,
+                (S::Preparing, E::Fail(_)) => failed(),
+                (S::Preheating, E::PreheatComplete) => S::Printing,
+                (S::Preheating, E::Cancel) => S::Cancelling,
+                (S::Preheating, E::Fail(_)) => failed(),
+                (S::Printing, E::Pause) => S::Paused,
+                (S::Printing, E::Complete) => S::Completed,
+                (S::Printing, E::Cancel) => S::Cancelling,
+                (S::Printing, E::Fail(_)) => failed(),
+                (S::Paused, E::Resume) => S::Printing,
+                (S::Paused, E::Cancel) => S::Cancelling,
+                (S::Paused, E::Fail(_)) => failed(),
+                (S::Cancelling, E::Complete) => S::Failed("Cancelled".to_string()),
+                (S::Cancelling, E::Fail(_)) => failed(),
+                _ => return None,
+            })
+        };
+
+        let mut legal = 0;
+        for state in states() {
+            for event in events() {
+                let result = state.transition(event.clone());
+                match expected(&state, &event) {
+                    Some(next) => {
+                        assert_eq!(result, Ok(next), "{:?} on {:?}", event, state);
+                        legal += 1;
+                    }
+                    None => assert_eq!(
+                        result,
+                        Err(InvalidTransition {
+                            from: state.clone(),
+                            event: event.clone()
+                        }),
+                        "{:?} on {:?}",
+                        event,
+                        state
+                    ),
+                }
+            }
+        }
+        assert_eq!(legal, 19);
+    }
+
+    #[test]
+    fn test_job_timing_and_filament() {
+        let mut job = PrintJob::new("part.gcode", 1024);
+        assert_eq!(job.elapsed_secs(), 0.0);
+        job.transition(PrintJobEvent::Start).unwrap();
+        job.record_extrusion(5.0); // purge before printing doesn't count
+        job.transition(PrintJobEvent::PreheatComplete).unwrap();
+        job.record_extrusion(2.0);
+        job.record_extrusion(-0.8);
+        job.record_extrusion(0.8);
+        assert!((job.filament_used_mm() - 2.0).abs() < 1e-12);
+
+        job.transition(PrintJobEvent::Pause).unwrap();
+        let paused = job.elapsed_secs();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(job.elapsed_secs(), paused);
+        job.transition(PrintJobEvent::Resume).unwrap();
+        job.transition(PrintJobEvent::Complete).unwrap();
+        let done = job.elapsed_secs();
+        assert!(done >= paused && done < paused + 0.04);
+
+        // Finished jobs stay as they ended
+        assert!(job.transition(PrintJobEvent::Start).is_err());
+        assert_eq!(job.state(), &PrintJobState::Completed);
+    }
+
+    fn ready_printer() -> (PrinterState, Config) {
+        let mut config: Config = toml::from_str(include_str!("printer.toml")).unwrap();
+        config.extruder.min_extrude_temp = 170.0;
+        let state = PrinterState {
+            homed: true,
+            temperature: 210.0,
+            ..PrinterState::new()
+        };
+        (state, config)
+    }
+
+    fn findings(validator: &PrintJobValidator, state: &PrinterState, config: &Config) -> Vec<(Severity, String)> {
+        let job = PrintJob::new("part.gcode", 0);
+        validator
+            .validate(&job, state, config)
+            .into_iter()
+            .map(|finding| (finding.severity, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_ready_printer() {
+        let (state, config) = ready_printer();
+        assert!(findings(&PrintJobValidator::default(), &state, &config).is_empty());
+    }
+
+    #[test]
+    fn test_validate_cold_hotend() {
+        let (mut state, config) = ready_printer();
+        state.temperature = 25.0;
+        let found = findings(&PrintJobValidator::default(), &state, &config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Error);
+        assert!(found[0].1.contains("minimum extrusion temperature"));
+
+        // Fine if the file heats up before extruding, but not if it heats too little
+        let mut validator = PrintJobValidator {
+            preamble: PrintFilePreamble::parse_lines(["M104 S215", "M109 S215", "G1 X10 E5"]),
+            ..PrintJobValidator::default()
+        };
+        assert!(findings(&validator, &state, &config).is_empty());
+        validator.preamble = PrintFilePreamble::parse_lines(["M109 S150", "G1 X10 E5", "M109 S215"]);
+        assert_eq!(findings(&validator, &state, &config).len(), 1);
+    }
+
+    #[test]
+    fn test_validate_not_homed() {
+        let (mut state, config) = ready_printer();
+        state.homed = false;
+        let found = findings(&PrintJobValidator::default(), &state, &config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Error);
+        assert!(found[0].1.contains("not homed"));
+
+        let validator = PrintJobValidator {
+            preamble: PrintFilePreamble::parse_lines(["G28 ; home all", "G1 Z5"]),
+            ..PrintJobValidator::default()
+        };
+        assert!(findings(&validator, &state, &config).is_empty());
+    }
+
+    #[test]
+    fn test_validate_probe_offset() {
+        let (state, mut config) = ready_printer();
+        config.probe = Some(ProbeConfig { z_offset: None });
+        let found = findings(&PrintJobValidator::default(), &state, &config);
+        assert_eq!(found, [(Severity::Warning, "Z probe offset has not been calibrated".to_string())]);
+
+        config.probe = Some(ProbeConfig { z_offset: Some(1.2) });
+        assert!(findings(&PrintJobValidator::default(), &state, &config).is_empty());
+    }
+
+    #[test]
+    fn test_validate_remaining_filament() {
+        let (mut state, mut config) = ready_printer();
+        let mut validator = PrintJobValidator::default();
+        validator.metadata.filament_used_mm = Some(5000.0);
+        state.remaining_filament_mm = Some(3000.0);
+        // Ignored without an enabled sensor
+        assert!(findings(&validator, &state, &config).is_empty());
+        config.filament_sensor = Some(FilamentSensorConfig { enabled: false });
+        assert!(findings(&validator, &state, &config).is_empty());
+
+        config.filament_sensor = Some(FilamentSensorConfig { enabled: true });
+        let found = findings(&validator, &state, &config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Warning);
+        state.remaining_filament_mm = Some(6000.0);
+        assert!(findings(&validator, &state, &config).is_empty());
+    }
+
+    #[test]
+    fn test_validate_chamber_temperature() {
+        let (mut state, config) = ready_printer();
+        let mut chamber = Heater::from_chamber_config(&ChamberConfig {
+            heater_pin: "PA1".to_string(),
+            sensor_type: "NTC 100K".to_string(),
+            sensor_pin: "PA2".to_string(),
+            min_temp: 0.0,
+            max_temp: 70.0,
+            max_power: 1.0,
+            pid_kp: 0.3,
+            pid_ki: 0.002,
+            pid_kd: 0.0,
+            pullup_resistor: 4700.0,
+            wait_timeout_secs: 1800.0,
+        });
+        chamber.update(25.0, 0.1).unwrap();
+        state.chamber = Some(chamber);
+        let mut validator = PrintJobValidator::default();
+        validator.metadata.filament_type = Some("PLA".to_string());
+        assert!(findings(&validator, &state, &config).is_empty());
+
+        validator.metadata.filament_type = Some("ABS-GF".to_string());
+        let found = findings(&validator, &state, &config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Warning);
+        assert!(found[0].1.contains("ABS-GF"));
+    }
+
+    #[tokio::test]
+    async fn test_inspect_file() {
+        let dir = std::env::temp_dir().join(format!("krusty-validate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("part.gcode");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "; filament used [mm] = 1200\nG28\nM109 S220\nG1 X10 E2\nM104 S250\n").unwrap();
+        let files = FileManager::new();
+        files.store_checksum(path).await.unwrap();
+
+        let validator = PrintJobValidator::inspect(&files, path).await;
+        assert_eq!(validator.file_problem, None);
+        assert_eq!(validator.metadata.filament_used_mm, Some(1200.0));
+        assert_eq!(
+            validator.preamble,
+            PrintFilePreamble {
+                hotend_temp: Some(220.0),
+                homes: true,
+            }
+        );
+
+        // Corrupted since its checksum was stored
+        std::fs::write(path, "G28\nG1 X11 E2\n").unwrap();
+        let (state, config) = ready_printer();
+        let validator = PrintJobValidator::inspect(&files, path).await;
+        let found = findings(&validator, &state, &config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Error);
+        assert!(found[0].1.contains("Checksum mismatch"));
+
+        let validator = PrintJobValidator::inspect(&files, &format!("{}/missing.gcode", dir.display())).await;
+        assert!(validator.file_problem.unwrap().contains("not found"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
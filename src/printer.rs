@@ -1,70 +1,412 @@
 // src/printer.rs - Use all fields properly
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{RwLock, broadcast};
-use crate::config::Config;
+use crate::config::{Config, ConfigManager};
+use crate::eeprom::EepromManager;
+use crate::file::FileManager;
 use crate::gcode::GCodeProcessor;
-use crate::motion::MotionController;
-use crate::hardware::HardwareManager;
+use crate::gcode::parser::GCodeError;
+use crate::gcode::pause::{LayerTracker, PauseConditions};
+use crate::gcode::wipe::NozzleWipe;
+use crate::motion::{ClogDetector, MotionConfig, MotionController, MotionRecorder, PositionDriftMonitor, ShaperPresetLibrary};
+use crate::motion::kinematics::{create_kinematics_from_config, DeltaKinematics, KinematicsType};
+use crate::hardware::{ExtruderSyncMonitor, HardwareManager, McuHealthMonitor, StepperStallDetector};
+use crate::mqtt::MqttTelemetryPublisher;
+use crate::post_print::PostPrintRoutine;
+use crate::print_job::{self, PrintJob};
+use crate::simulator::{EventLogConfig, EventLogger};
+use crate::temperature::{FanController, Heater, ThermalControlLoop, ToolHeater};
+use crate::web::{WebInterface, WebhookDispatcher};
 
 pub struct Printer {
     config: Config,
+    config_manager: Arc<RwLock<ConfigManager>>,
     state: Arc<RwLock<PrinterState>>,
     gcode_processor: GCodeProcessor,
     motion_controller: MotionController,
     hardware_manager: HardwareManager,
+    file_manager: FileManager,
+    web_interface: Option<WebInterface>,
+    webhook_task: Option<tokio::task::JoinHandle<()>>,
+    mqtt_task: Option<tokio::task::JoinHandle<()>>,
+    health_task: Option<tokio::task::JoinHandle<()>>,
+    /// Polls the filament encoder once started
+    extruder_sync: Option<ExtruderSyncMonitor>,
+    extruder_sync_task: Option<tokio::task::JoinHandle<()>>,
+    /// Polls the stepper encoders once started
+    position_drift: Option<PositionDriftMonitor>,
+    position_drift_task: Option<tokio::task::JoinHandle<()>>,
+    /// Reads the temperature sensors and drives the heaters once started
+    thermal_control: ThermalControlLoop,
+    thermal_control_task: Option<tokio::task::JoinHandle<()>>,
+    /// Audit log of printer events, if `advanced.event_log_file` is set
+    event_log: Option<Arc<PrinterEventLog>>,
+    event_log_task: Option<tokio::task::JoinHandle<()>>,
     shutdown_tx: broadcast::Sender<()>,
+    event_tx: broadcast::Sender<PrinterEvent>,
 }
 
 #[derive(Debug, Clone)]
 pub struct PrinterState {
     pub ready: bool,
+    pub homed: bool,
+    pub dry_run: bool,
     pub position: [f64; 3], // X, Y, Z
     pub temperature: f64,
+    /// Measured bed temperature (°C)
+    pub bed_temperature: f64,
+    /// Bed temperature last set with M140/M190 (°C)
+    pub bed_target: f64,
     pub print_progress: f64,
+    pub job: Option<PrintJob>,
+    pub gcode_commands: u64,
+    pub positioning_mode: PositioningMode,
+    pub extruder_mode: ExtruderMode,
+    pub fan: FanController,
+    pub chamber: Option<Heater>,
+    /// Filament left on the spool, as reported by the filament sensor (mm)
+    pub remaining_filament_mm: Option<f64>,
+    /// Bed heights from the last G29 [X, Y, Z]
+    pub bed_mesh: Option<Vec<[f64; 3]>>,
+    /// Hotend per tool; `temperature` follows the active one
+    pub tools: Vec<ToolHeater>,
+    pub active_tool: usize,
+    /// Pending pause-at-height/layer/line requests
+    pub pause_conditions: PauseConditions,
+    pub layer_tracker: LayerTracker,
+    /// Applied to every move's feedrate; below 1 while layers are slowed for cooling
+    pub feedrate_factor: f64,
+    /// Fan speed to go back to once layers are no longer slowed
+    pub fan_before_slowdown: Option<f64>,
+    /// Print time left as reported by the slicer with M73 (minutes)
+    pub slicer_remaining_mins: Option<f64>,
+    /// Layer the slicer's comments say is printing, counting the first as 1
+    pub current_layer: Option<u32>,
+    /// Layers in the print, from the slicer's comments
+    pub total_layers: Option<u32>,
+    /// Estimated time of the whole print, from the slicer's comments (seconds)
+    pub slicer_total_secs: Option<f64>,
+    /// Why the motion queue stopped, until the error is cleared
+    pub motion_error: Option<String>,
+}
+
+/// Printer events on disk, each stored as its `PrinterEvent::to_json`
+pub type PrinterEventLog = EventLogger<serde_json::Value>;
+
+/// Printer-wide events reported to interested listeners
+#[derive(Debug, Clone)]
+pub enum PrinterEvent {
+    /// A G-code line was rejected and skipped
+    GCodeError(GCodeError),
+
+    /// A file's contents no longer match its stored checksum
+    FileIntegrityError {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// A file print began
+    PrintStarted { path: String },
+
+    /// A file print ran to the end
+    PrintCompleted { path: String, lines: usize },
+
+    /// A file print stopped because of an error
+    PrintFailed { path: String, reason: String },
+
+    /// The MCU stopped answering pings and is being restarted
+    McuUnresponsive { missed_pings: u32 },
+
+    /// A stepper driver reported a stall on axis 0-2 (X, Y, Z)
+    StepperStalled { axis: usize },
+
+    /// The extruder fell this far behind the planned filament (mm)
+    ClogDetected { shortfall_mm: f64 },
+
+    /// The filament encoder saw fewer steps than the extruder was commanded
+    ExtruderDeSync { missing_steps: i64, missing_mm: f64 },
+
+    /// MCU firmware flashing reached `stage`, `percent` of the way through
+    FirmwareFlash { stage: String, percent: f64 },
+}
+
+impl PrinterEvent {
+    /// Snake-case event name, as used in notification filters
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            PrinterEvent::GCodeError(_) => "gcode_error",
+            PrinterEvent::FileIntegrityError { .. } => "file_integrity_error",
+            PrinterEvent::PrintStarted { .. } => "print_started",
+            PrinterEvent::PrintCompleted { .. } => "print_completed",
+            PrinterEvent::PrintFailed { .. } => "print_failed",
+            PrinterEvent::McuUnresponsive { .. } => "mcu_unresponsive",
+            PrinterEvent::StepperStalled { .. } => "stepper_stalled",
+            PrinterEvent::ClogDetected { .. } => "clog_detected",
+            PrinterEvent::ExtruderDeSync { .. } => "extruder_desync",
+            PrinterEvent::FirmwareFlash { .. } => "firmware_flash",
+        }
+    }
+
+    /// JSON description of the event for external notifications
+    pub fn to_json(&self) -> serde_json::Value {
+        let data = match self {
+            PrinterEvent::GCodeError(error) => serde_json::json!({ "message": error.to_string() }),
+            PrinterEvent::FileIntegrityError { path, expected, actual } => {
+                serde_json::json!({ "path": path, "expected": expected, "actual": actual })
+            }
+            PrinterEvent::PrintStarted { path } => serde_json::json!({ "path": path }),
+            PrinterEvent::PrintCompleted { path, lines } => serde_json::json!({ "path": path, "lines": lines }),
+            PrinterEvent::PrintFailed { path, reason } => serde_json::json!({ "path": path, "reason": reason }),
+            PrinterEvent::McuUnresponsive { missed_pings } => serde_json::json!({ "missed_pings": missed_pings }),
+            PrinterEvent::StepperStalled { axis } => serde_json::json!({ "axis": axis }),
+            PrinterEvent::ClogDetected { shortfall_mm } => serde_json::json!({ "shortfall_mm": shortfall_mm }),
+            PrinterEvent::ExtruderDeSync { missing_steps, missing_mm } => {
+                serde_json::json!({ "missing_steps": missing_steps, "missing_mm": missing_mm })
+            }
+            PrinterEvent::FirmwareFlash { stage, percent } => serde_json::json!({ "stage": stage, "percent": percent }),
+        };
+        serde_json::json!({ "event": self.event_type(), "data": data })
+    }
+}
+
+/// How X/Y/Z coordinates in moves are interpreted (G90/G91)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PositioningMode {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+/// How E coordinates in moves are interpreted (M82/M83)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExtruderMode {
+    #[default]
+    Absolute,
+    Relative,
 }
 
 impl PrinterState {
     pub fn new() -> Self {
         Self {
             ready: false,
+            homed: false,
+            dry_run: false,
             position: [0.0, 0.0, 0.0],
             temperature: 0.0,
+            bed_temperature: 0.0,
+            bed_target: 0.0,
             print_progress: 0.0,
+            job: None,
+            gcode_commands: 0,
+            positioning_mode: PositioningMode::Absolute,
+            extruder_mode: ExtruderMode::Absolute,
+            fan: FanController::default(),
+            chamber: None,
+            remaining_filament_mm: None,
+            bed_mesh: None,
+            tools: vec![ToolHeater::default()],
+            active_tool: 0,
+            pause_conditions: PauseConditions::default(),
+            layer_tracker: LayerTracker::default(),
+            feedrate_factor: 1.0,
+            fan_before_slowdown: None,
+            slicer_remaining_mins: None,
+            current_layer: None,
+            total_layers: None,
+            slicer_total_secs: None,
+            motion_error: None,
         }
     }
 }
 
+impl Default for PrinterState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Printer {
     pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
-        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let state = Arc::new(RwLock::new(PrinterState {
+            fan: FanController::new(&config.fan),
+            chamber: config.chamber.as_ref().map(Heater::from_chamber_config),
+            tools: config.tools().map(ToolHeater::from_config).collect(),
+            ..PrinterState::new()
+        }));
         let (shutdown_tx, _) = broadcast::channel(1);
+        let (event_tx, _) = broadcast::channel(16);
         
         let hardware_manager = HardwareManager::new(config.clone());
-        let motion_controller = MotionController::new(state.clone(), hardware_manager.clone());
-        let gcode_processor = GCodeProcessor::new(state.clone(), motion_controller.clone());
+        let motion_config = MotionConfig::new_from_printer_config(&config);
+        let kinematics = create_kinematics_from_config(&config, motion_config.axis_limits)?;
+        let kinematics_type = motion_config.kinematics_type;
+        let mut motion_controller = MotionController::new(state.clone(), hardware_manager.clone(), motion_config);
+        motion_controller.set_kinematics_handler(kinematics_type, kinematics);
+        if let Some(clog_detection) = &config.clog_detection {
+            motion_controller.set_clog_detector(ClogDetector::new(clog_detection, event_tx.clone()));
+        }
+        let extruder_sync = ExtruderSyncMonitor::new(hardware_manager.clone(), &config.extruder, event_tx.clone());
+        if let Some(monitor) = &extruder_sync {
+            motion_controller.set_extruder_sync_monitor(monitor.clone());
+        }
+        motion_controller.set_motion_recorder(MotionRecorder::new(
+            &config.advanced.trajectory_dir,
+            Duration::from_secs_f64(config.advanced.trajectory_sample_interval_ms / 1000.0),
+        ));
+        let position_drift = PositionDriftMonitor::new(
+            hardware_manager.clone(),
+            &config,
+            motion_controller.get_planner().step_generator(),
+        );
+        let event_log = config.advanced.event_log_file.as_ref().map(|path| {
+            Arc::new(PrinterEventLog::spawn(EventLogConfig {
+                max_size_bytes: config.advanced.event_log_max_bytes,
+                ..EventLogConfig::new(path)
+            }))
+        });
+        let thermal_control = ThermalControlLoop::new(hardware_manager.clone(), state.clone(), &config)?;
+        let mut gcode_processor = GCodeProcessor::new(state.clone(), motion_controller.clone())
+            .with_history_capacity(config.printer.gcode_history_size)
+            .with_nozzle_wipe(NozzleWipe::from_config(&config.printer))
+            .with_meta_patterns(config.gcode_meta_patterns.clone())
+            .with_event_sender(event_tx.clone());
+        if let Some(path) = &config.advanced.shaper_presets_file {
+            let library = match ShaperPresetLibrary::load_from_file(path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => ShaperPresetLibrary::default(),
+                loaded => loaded?,
+            };
+            gcode_processor = gcode_processor.with_shaper_presets(library, Some(Path::new(path)));
+        }
+        if let Some(post_print) = &config.post_print {
+            gcode_processor = gcode_processor.with_post_print(PostPrintRoutine::new(post_print.clone()));
+        }
+        if let Some(hangprinter) = &config.hangprinter {
+            gcode_processor = gcode_processor.with_hangprinter(hangprinter.clone());
+        }
+        if config.stall_detection.is_some() {
+            let detector = StepperStallDetector::new(hardware_manager.clone(), &config, event_tx.clone());
+            gcode_processor = gcode_processor.with_stall_detector(detector);
+        }
+        let mut delta = config.delta.clone();
+        if let Some(path) = &config.printer.settings_file {
+            let settings = EepromManager::new(path);
+            let saved = settings.load()?;
+            if let Some(delta) = &mut delta
+                && (saved.delta_endstop_corrections.is_some() || saved.delta_tower_angle_corrections.is_some())
+            {
+                delta.endstop_corrections = saved.delta_endstop_corrections.unwrap_or(delta.endstop_corrections);
+                delta.tower_angle_corrections = saved.delta_tower_angle_corrections.unwrap_or(delta.tower_angle_corrections);
+                if kinematics_type == KinematicsType::Delta {
+                    motion_controller.set_kinematics_handler(kinematics_type, Box::new(DeltaKinematics::from_config(delta)));
+                }
+            }
+            if let Some(steps_per_mm) = saved.steps_per_mm
+                && let Err(e) = motion_controller.set_steps_per_mm(steps_per_mm.map(Some))
+            {
+                tracing::warn!("Ignoring saved steps/mm: {}", e);
+            }
+            if let Some(skew) = saved.skew_correction
+                && let Err(e) = motion_controller.set_skew_correction(skew)
+            {
+                tracing::warn!("Ignoring saved skew correction: {}", e);
+            }
+            gcode_processor = gcode_processor.with_settings(settings);
+        }
+        if let Some(delta) = delta {
+            gcode_processor = gcode_processor.with_delta(delta);
+        }
         
         Ok(Self {
+            config_manager: Arc::new(RwLock::new(ConfigManager::new(config.clone()))),
             config,
             state,
             gcode_processor,
             motion_controller,
             hardware_manager,
+            file_manager: FileManager::new(),
+            web_interface: None,
+            webhook_task: None,
+            mqtt_task: None,
+            health_task: None,
+            extruder_sync,
+            extruder_sync_task: None,
+            position_drift,
+            position_drift_task: None,
+            thermal_control,
+            thermal_control_task: None,
+            event_log,
+            event_log_task: None,
             shutdown_tx,
+            event_tx,
         })
     }
     
+    /// Printer running the active profile of `config_manager`
+    pub async fn new_with_profiles(config_manager: ConfigManager) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut printer = Self::new(config_manager.active_config().clone()).await?;
+        printer.config_manager = Arc::new(RwLock::new(config_manager));
+        Ok(printer)
+    }
+
     pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Starting printer OS");
         
         // Initialize hardware
         self.hardware_manager.initialize().await?;
         
+        let startup_gcode = &self.config.printer.startup_gcode;
+        if !startup_gcode.is_empty() && !self.config.printer.skip_startup_gcode {
+            tracing::info!("Running {} startup G-code commands", startup_gcode.len());
+            self.gcode_processor.run_gcode_sequence(startup_gcode).await?;
+        }
+        
+        if self.config.web.enabled {
+            let mut web = WebInterface::new(
+                self.config.clone(),
+                self.state.clone(),
+                self.motion_controller.get_planner().subscribe_stats(),
+                self.motion_controller.get_planner().subscribe_position(),
+                self.hardware_manager.clone(),
+                self.gcode_processor.clone(),
+            )
+            .with_config_manager(self.config_manager.clone())
+            .with_event_sender(self.event_tx.clone());
+            if let Some(event_log) = &self.event_log {
+                web = web.with_event_log(event_log.clone());
+            }
+            web.start().await?;
+            self.web_interface = Some(web);
+        }
+        
+        if let Some(event_log) = &self.event_log {
+            self.event_log_task = Some(spawn_event_log(event_log.clone(), self.event_tx.subscribe()));
+        }
+        
+        if !self.config.web.webhooks.is_empty() {
+            let dispatcher = WebhookDispatcher::new(self.config.web.webhooks.clone());
+            self.webhook_task = Some(dispatcher.spawn(self.event_tx.subscribe()));
+        }
+        
+        if let Some(mqtt) = &self.config.mqtt {
+            let publisher = MqttTelemetryPublisher::new(mqtt.clone(), self.state.clone(), self.event_tx.subscribe());
+            self.mqtt_task = Some(publisher.spawn()?);
+        }
+        
         // Mark as ready
         {
             let mut state = self.state.write().await;
             state.ready = true;
         }
         
+        let monitor = McuHealthMonitor::new(self.hardware_manager.clone(), self.state.clone(), self.event_tx.clone());
+        self.health_task = Some(monitor.spawn());
+        self.extruder_sync_task = self.extruder_sync.clone().map(ExtruderSyncMonitor::spawn);
+        self.position_drift_task = self.position_drift.clone().map(PositionDriftMonitor::spawn);
+        self.thermal_control_task = Some(self.thermal_control.clone().spawn());
+        
         tracing::info!("Printer OS ready");
         Ok(())
     }
@@ -72,15 +414,71 @@ impl Printer {
     pub async fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Shutting down printer OS");
         let _ = self.shutdown_tx.send(());
+        if let Some(web) = self.web_interface.as_mut() {
+            web.shutdown().await?;
+        }
+        if let Some(task) = self.webhook_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.mqtt_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.health_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.extruder_sync_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.position_drift_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.thermal_control_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.event_log_task.take() {
+            task.abort();
+        }
+        let shutdown_gcode = &self.config.printer.shutdown_gcode;
+        if !shutdown_gcode.is_empty() {
+            // Errors are stringified so they aren't held across an await
+            let result = self.gcode_processor.run_gcode_sequence(shutdown_gcode).await.map_err(|e| e.to_string());
+            if let Err(e) = result {
+                tracing::warn!("Shutdown G-code failed: {}", e);
+            }
+        }
         self.hardware_manager.shutdown().await?;
         Ok(())
     }
     
+    /// Run a line sent by the host, checking its framing
     pub async fn process_gcode(&mut self, gcode: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.gcode_processor.process_command(gcode).await?;
-        Ok(())
+        self.gcode_processor.process_host_line(gcode).await
     }
     
+    /// Check a file against its stored checksum before printing it
+    ///
+    /// Files without a stored checksum are accepted. On a mismatch a
+    /// `FileIntegrityError` event is sent and the print must not start.
+    pub async fn verify_print_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        print_job::verify_file(&self.file_manager, &self.event_tx, path).await
+    }
+
+    /// Verify and print a G-code file, streaming it from disk
+    pub async fn print_file(&mut self, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        self.verify_print_file(path).await?;
+        let size = tokio::fs::metadata(path).await.map(|metadata| metadata.len()).unwrap_or(0);
+        let job = PrintJob::new(path, size);
+        Ok(print_job::run_with_events(job, &self.state, &mut self.gcode_processor, &self.event_tx).await?)
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PrinterEvent> {
+        self.event_tx.subscribe()
+    }
+
+    pub fn get_file_manager(&self) -> &FileManager {
+        &self.file_manager
+    }
+
     // Add methods to use the fields
     pub fn get_config(&self) -> &Config {
         &self.config
@@ -93,4 +491,19 @@ impl Printer {
     pub fn get_motion_controller(&self) -> &MotionController {
         &self.motion_controller
     }
-}
\ No newline at end of file
+}
+
+/// Write every event on the bus to `log` until the bus closes
+fn spawn_event_log(log: Arc<PrinterEventLog>, mut events: broadcast::Receiver<PrinterEvent>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => log.log(event.to_json()),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Event log missed {} events", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
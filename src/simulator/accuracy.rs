@@ -0,0 +1,141 @@
+// src/simulator/accuracy.rs - Simulated against known physical outcomes
+use std::error::Error;
+use std::time::Duration;
+use serde::Serialize;
+use super::{EventFilter, SimConfig, SimEvent, SimEventPayload, Simulator, StepCommand, ThermalEvent};
+
+/// Real time one scenario may take before it fails
+const SCENARIO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a scenario measures
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccuracyScenarioKind {
+    /// Seconds from ambient at full power until `target` (°C) is reached
+    HeatUpTime { target: f64, max_secs: f64 },
+    /// Temperature (°C) after heating from ambient at `power` for `secs`
+    TemperatureAfter { power: f64, secs: f64 },
+    /// Temperature (°C) after cooling from `start` with the heater off for `secs`
+    CoolingAfter { start: f64, secs: f64 },
+    /// Position (mm) of `axis` after `moves` moves of `steps` each, from 0
+    PositionAfterSteps { axis: usize, steps: i64, moves: usize },
+}
+
+/// A measurement with its reference value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccuracyScenario {
+    pub name: String,
+    pub kind: AccuracyScenarioKind,
+    /// Analytical or measured value the simulation should reproduce
+    pub expected: f64,
+    /// Largest deviation that still passes (%)
+    pub tolerance_pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccuracyResult {
+    pub name: String,
+    pub simulated: f64,
+    pub expected: f64,
+    /// |simulated - expected| relative to expected (%)
+    pub deviation_pct: f64,
+    pub passed: bool,
+}
+
+/// Runs scenarios on fresh simulators and scores them against references
+#[derive(Debug, Clone)]
+pub struct SimulationAccuracyBenchmark {
+    config: SimConfig,
+    scenarios: Vec<AccuracyScenario>,
+}
+
+impl SimulationAccuracyBenchmark {
+    pub fn new(config: SimConfig) -> Self {
+        Self { config, scenarios: Vec::new() }
+    }
+
+    pub fn add_scenario(&mut self, name: &str, kind: AccuracyScenarioKind, expected: f64, tolerance_pct: f64) {
+        self.scenarios.push(AccuracyScenario { name: name.to_string(), kind, expected, tolerance_pct });
+    }
+
+    pub fn scenarios(&self) -> &[AccuracyScenario] {
+        &self.scenarios
+    }
+
+    /// Run every scenario in turn
+    pub async fn run(&self) -> Result<Vec<AccuracyResult>, Box<dyn Error>> {
+        let mut results = Vec::with_capacity(self.scenarios.len());
+        for scenario in &self.scenarios {
+            let simulated = self.measure(scenario.kind).await?;
+            let deviation_pct = (simulated - scenario.expected).abs() / scenario.expected.abs().max(f64::EPSILON) * 100.0;
+            results.push(AccuracyResult {
+                name: scenario.name.clone(),
+                simulated,
+                expected: scenario.expected,
+                deviation_pct,
+                passed: deviation_pct <= scenario.tolerance_pct,
+            });
+        }
+        Ok(results)
+    }
+
+    async fn measure(&self, kind: AccuracyScenarioKind) -> Result<f64, Box<dyn Error>> {
+        let mut sim = Simulator::new_with_scale(self.config.clone(), f64::INFINITY);
+        match kind {
+            AccuracyScenarioKind::HeatUpTime { target, max_secs } => {
+                let (_, mut thermal_rx) = sim.subscribe(EventFilter::THERMAL);
+                sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterTarget { target }));
+                run_for(&mut sim, max_secs).await?;
+                std::iter::from_fn(|| thermal_rx.try_recv().ok())
+                    .find(|event| matches!(event.payload, Some(SimEventPayload::ThermalEvent(ThermalEvent::TargetReached { .. }))))
+                    .map(|event| event.timestamp)
+                    .ok_or_else(|| format!("{}°C not reached within {}s", target, max_secs).into())
+            }
+            AccuracyScenarioKind::TemperatureAfter { power, secs } => {
+                sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterUpdate { power }));
+                run_for(&mut sim, secs).await?;
+                Ok(sim.get_temperature())
+            }
+            AccuracyScenarioKind::CoolingAfter { start, secs } => {
+                sim.set_temperature(start);
+                run_for(&mut sim, secs).await?;
+                Ok(sim.get_temperature())
+            }
+            AccuracyScenarioKind::PositionAfterSteps { axis, steps, moves } => {
+                for i in 0..moves {
+                    sim.schedule(SimEvent::new(i as f64 * 0.001, SimEventPayload::Step(StepCommand { axis, steps })));
+                }
+                sim.run_event_loop();
+                sim.get_position()
+                    .get(axis)
+                    .copied()
+                    .ok_or_else(|| format!("No axis {}", axis).into())
+            }
+        }
+    }
+}
+
+async fn run_for(sim: &mut Simulator, secs: f64) -> Result<usize, Box<dyn Error>> {
+    sim.run_event_loop_with_timeout(SCENARIO_TIMEOUT, Duration::from_secs_f64(secs)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pass_fail_criteria() {
+        let mut benchmark = SimulationAccuracyBenchmark::new(SimConfig::default());
+        let steps = AccuracyScenarioKind::PositionAfterSteps { axis: 0, steps: 80, moves: 10 };
+        benchmark.add_scenario("x_10mm", steps, 10.0, 0.001);
+        benchmark.add_scenario("x_10mm_wrong", steps, 11.0, 5.0);
+        // Default hotend: 25 + 3 * 100 * (1 - e^-1) after one time constant
+        benchmark.add_scenario("one_tau", AccuracyScenarioKind::TemperatureAfter { power: 1.0, secs: 100.0 }, 214.636, 1.0);
+
+        let results = benchmark.run().await.unwrap();
+        assert!(results[0].passed && results[0].deviation_pct == 0.0);
+        assert!(!results[1].passed);
+        assert!((results[1].deviation_pct - 100.0 / 11.0).abs() < 1e-9);
+        assert!(results[2].passed, "{:?}", results[2]);
+    }
+}
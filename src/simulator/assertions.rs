@@ -0,0 +1,426 @@
+// src/simulator/assertions.rs - Self-checking G-code files for the simulator
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use serde::Serialize;
+use crate::gcode::parser::parse_words;
+use super::{SimEvent, SimEventPayload, SimulationReport, Simulator, StepCommand};
+
+/// Feedrate until a file sets one (mm/min)
+const DEFAULT_FEEDRATE: f64 = 3000.0;
+
+/// Longest M109 waits for its temperature (simulated seconds)
+const HEAT_WAIT_LIMIT: f64 = 600.0;
+
+/// Distance homing moves travel past where the endstop should be (mm)
+const HOMING_OVERTRAVEL: f64 = 10.0;
+
+/// `==` and `!=` compare within this unless the assertion gives `within`
+const DEFAULT_TOLERANCE: f64 = 1e-6;
+
+/// Simulator state an assertion can check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssertionVariable {
+    /// Position of an axis (mm), 0 = X .. 3 = E
+    Position(usize),
+    /// Filament pushed over the whole run (mm)
+    ExtrudedTotal,
+    HeaterTemp,
+    /// Heater target (°C), 0 when off
+    HeaterTarget,
+    /// 1 when the endstop of an axis is pressed, 0 otherwise
+    Endstop(usize),
+    /// Simulated time (s)
+    Time,
+}
+
+impl FromStr for AssertionVariable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "x" => Ok(AssertionVariable::Position(0)),
+            "y" => Ok(AssertionVariable::Position(1)),
+            "z" => Ok(AssertionVariable::Position(2)),
+            "e" => Ok(AssertionVariable::Position(3)),
+            "e_total" => Ok(AssertionVariable::ExtrudedTotal),
+            "heater_temp" => Ok(AssertionVariable::HeaterTemp),
+            "heater_target" => Ok(AssertionVariable::HeaterTarget),
+            "endstop_x" => Ok(AssertionVariable::Endstop(0)),
+            "endstop_y" => Ok(AssertionVariable::Endstop(1)),
+            "endstop_z" => Ok(AssertionVariable::Endstop(2)),
+            "time" => Ok(AssertionVariable::Time),
+            other => Err(format!("Unknown assertion variable: {}", other)),
+        }
+    }
+}
+
+impl AssertionVariable {
+    pub fn read(self, sim: &Simulator) -> f64 {
+        match self {
+            AssertionVariable::Position(axis) => sim.get_position()[axis],
+            AssertionVariable::ExtrudedTotal => sim.get_extruded_total(),
+            AssertionVariable::HeaterTemp => sim.get_temperature(),
+            AssertionVariable::HeaterTarget => sim.get_heater_target().unwrap_or(0.0),
+            AssertionVariable::Endstop(axis) => f64::from(u8::from(sim.is_endstop_triggered(axis))),
+            AssertionVariable::Time => sim.get_time(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            "==" => Ok(Comparison::Eq),
+            "!=" => Ok(Comparison::Ne),
+            other => Err(format!("Unknown comparison: {}", other)),
+        }
+    }
+}
+
+/// One `; ASSERT <variable> <op> <value> [within <tolerance>]` comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct GCodeAssertion {
+    /// Line of the file the comment is on, counting from 1
+    pub line: usize,
+    pub variable: AssertionVariable,
+    pub comparison: Comparison,
+    pub value: f64,
+    pub tolerance: f64,
+    /// The assertion as written
+    pub text: String,
+}
+
+impl GCodeAssertion {
+    /// Parse a line, returning `None` if it is not an assertion comment
+    pub fn parse(raw: &str, line: usize) -> Option<Result<Self, String>> {
+        let comment = raw.trim().strip_prefix(';')?.trim();
+        let text = comment.strip_prefix("ASSERT ")?.trim();
+        Some(Self::parse_expression(text, line).map_err(|e| format!("Line {}: {}", line, e)))
+    }
+
+    fn parse_expression(text: &str, line: usize) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        let (variable, comparison, value, tolerance) = match parts.as_slice() {
+            [variable, comparison, value] => (variable, comparison, value, None),
+            [variable, comparison, value, "within", tolerance] => (variable, comparison, value, Some(tolerance)),
+            _ => return Err(format!("Expected `<variable> <op> <value> [within <tolerance>]`, got `{}`", text)),
+        };
+        let number = |s: &str| s.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(|| format!("Invalid number: {}", s));
+        Ok(Self {
+            line,
+            variable: variable.parse()?,
+            comparison: comparison.parse()?,
+            value: number(value)?,
+            tolerance: tolerance.map(|t| number(t)).transpose()?.map_or(DEFAULT_TOLERANCE, f64::abs),
+            text: text.to_string(),
+        })
+    }
+
+    pub fn holds(&self, actual: f64) -> bool {
+        match self.comparison {
+            Comparison::Lt => actual < self.value,
+            Comparison::Le => actual <= self.value,
+            Comparison::Gt => actual > self.value,
+            Comparison::Ge => actual >= self.value,
+            Comparison::Eq => (actual - self.value).abs() <= self.tolerance,
+            Comparison::Ne => (actual - self.value).abs() > self.tolerance,
+        }
+    }
+}
+
+/// An assertion that did not hold when its line was reached
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssertionFailure {
+    pub line: usize,
+    pub assertion: String,
+    pub actual: f64,
+    /// Simulated time it was checked at (s)
+    pub time: f64,
+}
+
+impl fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ASSERT {} failed, actual {} at t={:.1}s", self.line, self.assertion, self.actual, self.time)
+    }
+}
+
+/// The assertions of a G-code file, checked in file order as the run
+/// reaches them
+#[derive(Debug, Clone, Default)]
+pub struct GCodeAssertionSuite {
+    assertions: Vec<GCodeAssertion>,
+    /// Assertions before this index have been checked
+    checked: usize,
+}
+
+impl GCodeAssertionSuite {
+    pub fn parse(gcode: &str) -> Result<Self, String> {
+        let assertions = gcode
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| GCodeAssertion::parse(line, index + 1))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { assertions, checked: 0 })
+    }
+
+    pub fn assertions(&self) -> &[GCodeAssertion] {
+        &self.assertions
+    }
+
+    /// Check the not yet checked assertions up to and including `line`
+    pub fn evaluate_pending(&mut self, line: usize, sim: &Simulator) -> Vec<AssertionFailure> {
+        let mut failures = Vec::new();
+        while let Some(assertion) = self.assertions.get(self.checked).filter(|assertion| assertion.line <= line) {
+            let actual = assertion.variable.read(sim);
+            if !assertion.holds(actual) {
+                failures.push(AssertionFailure {
+                    line: assertion.line,
+                    assertion: assertion.text.clone(),
+                    actual,
+                    time: sim.get_time(),
+                });
+            }
+            self.checked += 1;
+        }
+        failures
+    }
+}
+
+/// Modal state of the G-code being simulated
+#[derive(Debug, Clone)]
+struct GCodeState {
+    absolute: bool,
+    absolute_e: bool,
+    /// mm/min
+    feedrate: f64,
+    /// Machine position minus the position G-code sees, set by G92 (mm)
+    offset: [f64; 4],
+    /// Steps sent to each motor, relative to the machine origin
+    steps: [i64; 4],
+}
+
+impl Simulator {
+    /// Run a G-code file, see `run_gcode`
+    pub fn run_gcode_file(&mut self, path: &str) -> Result<SimulationReport, Box<dyn Error>> {
+        self.run_gcode(&std::fs::read_to_string(path)?)
+    }
+
+    /// Simulate G-code line by line, checking its `; ASSERT` comments
+    ///
+    /// Understands G0/G1, G4, G28, G90/G91, G92, M82/M83, M104 and M109;
+    /// other commands are skipped. Moves take distance / feedrate and their
+    /// steps land at the end of the move.
+    pub fn run_gcode(&mut self, gcode: &str) -> Result<SimulationReport, Box<dyn Error>> {
+        let mut suite = GCodeAssertionSuite::parse(gcode)?;
+        let spm = self.get_config().steps_per_mm;
+        let position = self.get_position();
+        let mut state = GCodeState {
+            absolute: true,
+            absolute_e: true,
+            feedrate: DEFAULT_FEEDRATE,
+            offset: [0.0; 4],
+            steps: std::array::from_fn(|axis| (position[axis] * spm[axis]).round() as i64),
+        };
+
+        let mut processed = 0;
+        let mut failures = Vec::new();
+        for (index, raw) in gcode.lines().enumerate() {
+            let command = raw.split(';').next().unwrap_or_default().trim();
+            if !command.is_empty() {
+                processed += self
+                    .execute_gcode(&mut state, command)
+                    .map_err(|e| format!("Line {}: {}", index + 1, e))?;
+            }
+            failures.extend(suite.evaluate_pending(index + 1, self));
+        }
+
+        let mut report = self.report(processed);
+        report.assertion_failures = failures;
+        Ok(report)
+    }
+
+    fn execute_gcode(&mut self, state: &mut GCodeState, command: &str) -> Result<usize, Box<dyn Error>> {
+        // Bare letters like the axes of `G28 X Y` are flags: give them a value
+        let command: Vec<String> = command
+            .split_whitespace()
+            .map(|word| if word.len() == 1 { format!("{}0", word) } else { word.to_string() })
+            .collect();
+        let words = parse_words(&command.join(" "))?;
+        let Some((code, params)) = words.split_first() else {
+            return Ok(0);
+        };
+        let param = |letter: char| params.iter().find(|word| word.letter == letter).map(|word| word.value);
+        let spm = self.get_config().steps_per_mm;
+        let now = self.get_time();
+
+        match (code.letter, code.value as u32) {
+            ('G', 0 | 1) => {
+                if let Some(feedrate) = param('F').filter(|f| *f > 0.0) {
+                    state.feedrate = feedrate;
+                }
+                let start = state.steps;
+                for (axis, letter) in ['X', 'Y', 'Z', 'E'].into_iter().enumerate() {
+                    let Some(value) = param(letter) else {
+                        continue;
+                    };
+                    let current = state.steps[axis] as f64 / spm[axis] - state.offset[axis];
+                    let absolute = if axis == 3 { state.absolute_e } else { state.absolute };
+                    let target = if absolute { value } else { current + value };
+                    state.steps[axis] = ((target + state.offset[axis]) * spm[axis]).round() as i64;
+                }
+                let mm = |steps: [i64; 4]| -> [f64; 4] { std::array::from_fn(|axis| steps[axis] as f64 / spm[axis]) };
+                let (from, to) = (mm(start), mm(state.steps));
+                let delta: [f64; 4] = std::array::from_fn(|axis| to[axis] - from[axis]);
+                let xyz = (delta[0].powi(2) + delta[1].powi(2) + delta[2].powi(2)).sqrt();
+                let distance = if xyz > 0.0 { xyz } else { delta[3].abs() };
+                let end = now + distance / (state.feedrate / 60.0);
+
+                for (axis, (target, start)) in state.steps.iter().zip(start).enumerate() {
+                    let steps = target - start;
+                    if steps != 0 {
+                        self.schedule(SimEvent::new(end, SimEventPayload::Step(StepCommand { axis, steps })));
+                    }
+                }
+                if let Some(renderer) = &mut self.svg_renderer
+                    && (delta[0] != 0.0 || delta[1] != 0.0)
+                {
+                    renderer.add_move([from[0], from[1]], [to[0], to[1]], to[2], delta[3] > 0.0);
+                }
+                Ok(self.run_until(end))
+            }
+            ('G', 4) => {
+                let dwell = param('S').or(param('P').map(|ms| ms / 1000.0)).unwrap_or(0.0);
+                Ok(self.run_until(now + dwell.max(0.0)))
+            }
+            ('G', 28) => {
+                let all = !params.iter().any(|word| matches!(word.letter, 'X' | 'Y' | 'Z'));
+                let mut homed = Vec::new();
+                for (axis, letter) in ['X', 'Y', 'Z'].into_iter().enumerate() {
+                    let Some(endstop) = self.get_config().endstop_positions[axis] else {
+                        continue;
+                    };
+                    if all || param(letter).is_some() {
+                        let travel = (self.get_position()[axis] - endstop).abs() + HOMING_OVERTRAVEL;
+                        let steps = -(travel * spm[axis]).round() as i64;
+                        self.schedule(SimEvent::new(now, SimEventPayload::Step(StepCommand { axis, steps })));
+                        homed.push(axis);
+                    }
+                }
+                let processed = self.run_until(now);
+                let position = self.get_position();
+                for axis in homed {
+                    state.steps[axis] = (position[axis] * spm[axis]).round() as i64;
+                    state.offset[axis] = 0.0;
+                }
+                Ok(processed)
+            }
+            ('G', 90) => {
+                state.absolute = true;
+                state.absolute_e = true;
+                Ok(0)
+            }
+            ('G', 91) => {
+                state.absolute = false;
+                state.absolute_e = false;
+                Ok(0)
+            }
+            ('G', 92) => {
+                for (axis, letter) in ['X', 'Y', 'Z', 'E'].into_iter().enumerate() {
+                    if let Some(value) = param(letter) {
+                        state.offset[axis] = state.steps[axis] as f64 / spm[axis] - value;
+                    }
+                }
+                Ok(0)
+            }
+            ('M', 82) => {
+                state.absolute_e = true;
+                Ok(0)
+            }
+            ('M', 83) => {
+                state.absolute_e = false;
+                Ok(0)
+            }
+            ('M', 104 | 109) => {
+                let target = param('S').unwrap_or(0.0);
+                self.schedule(SimEvent::new(now, SimEventPayload::HeaterTarget { target }));
+                let mut processed = self.run_until(now);
+                if code.value as u32 == 109 && target > 0.0 {
+                    while !self.is_target_reached() {
+                        if self.get_time() - now > HEAT_WAIT_LIMIT {
+                            return Err(format!("{}°C not reached after {}s", target, HEAT_WAIT_LIMIT).into());
+                        }
+                        processed += self.run_until(self.get_time() + 0.1);
+                    }
+                }
+                Ok(processed)
+            }
+            _ => {
+                tracing::debug!("Simulator skipping unsupported G-code: {}", command.join(" "));
+                Ok(0)
+            }
+        }
+    }
+}
+
+/// Simulate a G-code file on a default simulator, panicking with every
+/// failed `; ASSERT`
+#[track_caller]
+pub fn run_gcode_test(path: &str) -> SimulationReport {
+    let mut sim = Simulator::new(Default::default());
+    let report = sim
+        .run_gcode_file(path)
+        .unwrap_or_else(|e| panic!("Simulating {} failed: {}", path, e));
+    if !report.assertion_failures.is_empty() {
+        let failures: Vec<String> = report.assertion_failures.iter().map(ToString::to_string).collect();
+        panic!("{} assertion(s) failed in {}:\n{}", failures.len(), path, failures.join("\n"));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assertion_fixture() {
+        let report = run_gcode_test(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/assertions.gcode"));
+        assert_eq!(report.position, [100.0, 50.0, 10.0, 10.0]);
+    }
+
+    #[test]
+    fn test_failed_assertions_reported() {
+        let gcode = "\
+G28 X
+G1 X20 F600
+; ASSERT x > 100
+; ASSERT endstop_x == 0
+; ASSERT time == 2 within 0.01
+; ASSERT heater_temp >= 200
+";
+        let report = Simulator::default().run_gcode(gcode).unwrap();
+        let lines: Vec<usize> = report.assertion_failures.iter().map(|failure| failure.line).collect();
+        assert_eq!(lines, [3, 6]);
+        assert_eq!(report.assertion_failures[0].actual, 20.0);
+        assert_eq!(report.assertion_failures[0].assertion, "x > 100");
+
+        assert!(Simulator::default().run_gcode("; ASSERT x >\n").is_err());
+        assert!(Simulator::default().run_gcode("; ASSERT speed < 3\n").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+// src/simulator/breakpoint.rs - Pausing simulation runs to inspect their state
+use std::time::Duration;
+use super::{SimEvent, SimEventPayload, SimEventType, ThermalEvent};
+
+/// When `Simulator::run_event_loop` should stop and hand back the state
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakpointCondition {
+    /// Once the clock reaches this time, before later events; fires once
+    AtTime(Duration),
+
+    /// When a step moves the motor of `axis` onto or past `position` (steps)
+    AtStepperPosition { axis: usize, position: i64 },
+
+    /// After an event of this type has been applied
+    OnEvent(SimEventType),
+
+    /// After a thermal runaway has shut the heater down
+    OnThermalRunaway,
+}
+
+impl BreakpointCondition {
+    /// Whether `event`, which moved the motors from `steps_before` to
+    /// `steps_after`, hits this breakpoint
+    pub(super) fn matches(&self, event: &SimEvent, steps_before: &[i64; 4], steps_after: &[i64; 4]) -> bool {
+        match self {
+            BreakpointCondition::AtTime(_) => false,
+            BreakpointCondition::AtStepperPosition { axis, position } => {
+                let (Some(&before), Some(&after)) = (steps_before.get(*axis), steps_after.get(*axis)) else {
+                    return false;
+                };
+                before != after && (before.min(after)..=before.max(after)).contains(position) && before != *position
+            }
+            BreakpointCondition::OnEvent(event_type) => event.event_type == *event_type,
+            BreakpointCondition::OnThermalRunaway => {
+                matches!(event.payload, Some(SimEventPayload::ThermalEvent(ThermalEvent::Runaway { .. })))
+            }
+        }
+    }
+}
+
+/// The hotend as the simulator last modelled it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaterSnapshot {
+    /// °C
+    pub current_temp: f64,
+    /// Temperature regulated towards, if any (°C)
+    pub target: Option<f64>,
+    /// 0.0 - 1.0
+    pub power: f64,
+    pub target_reached: bool,
+}
+
+/// Everything the simulator knows at one moment
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimSnapshot {
+    /// Simulated time (seconds)
+    pub time: f64,
+    /// [X, Y, Z, E] (mm)
+    pub position: [f64; 4],
+    /// Motor positions [X, Y, Z, E] (steps)
+    pub stepper_positions: [i64; 4],
+    pub heater: HeaterSnapshot,
+    pub endstops_triggered: [bool; 4],
+    /// Filament pushed by forward extruder steps (mm)
+    pub extruded_total: f64,
+    pub thermal_events: Vec<ThermalEvent>,
+    pub user_events: Vec<String>,
+    /// Events still queued
+    pub pending_events: usize,
+}
+
+/// How a `run_event_loop` call ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+    /// The queue emptied after this many events
+    Completed(usize),
+
+    /// A breakpoint fired; the remaining events are still queued
+    BreakpointHit { condition: BreakpointCondition, state: SimSnapshot },
+}
+
+impl RunOutcome {
+    /// Events processed, `None` if a breakpoint stopped the run
+    pub fn completed(&self) -> Option<usize> {
+        match self {
+            RunOutcome::Completed(processed) => Some(*processed),
+            RunOutcome::BreakpointHit { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{SimConfig, Simulator, StepCommand};
+    use super::*;
+
+    fn step(timestamp: f64, axis: usize, steps: i64) -> SimEvent {
+        SimEvent::new(timestamp, SimEventPayload::Step(StepCommand { axis, steps }))
+    }
+
+    fn hit(outcome: RunOutcome) -> (BreakpointCondition, SimSnapshot) {
+        match outcome {
+            RunOutcome::BreakpointHit { condition, state } => (condition, state),
+            RunOutcome::Completed(processed) => panic!("no breakpoint hit after {} events", processed),
+        }
+    }
+
+    #[test]
+    fn test_break_at_time() {
+        let mut sim = Simulator::default();
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterUpdate { power: 1.0 }));
+        sim.schedule(SimEvent::new(10.0, SimEventPayload::UserEvent("late".to_string())));
+        sim.set_breakpoint(BreakpointCondition::AtTime(Duration::from_secs(5)));
+
+        let (condition, state) = hit(sim.run_event_loop());
+        assert_eq!(condition, BreakpointCondition::AtTime(Duration::from_secs(5)));
+        assert_eq!(state.time, 5.0);
+        assert!(state.heater.current_temp > 35.0);
+        assert!(state.user_events.is_empty());
+        assert_eq!(state.pending_events, 1);
+        assert_eq!(sim.get_snapshot(), state);
+
+        // Fires once, the rest of the run carries on
+        assert_eq!(sim.run_event_loop(), RunOutcome::Completed(1));
+        assert_eq!(sim.get_user_events(), ["late".to_string()]);
+    }
+
+    #[test]
+    fn test_break_at_stepper_position() {
+        let mut sim = Simulator::new(SimConfig { endstop_positions: [None; 4], ..SimConfig::default() });
+        for i in 0..10 {
+            sim.schedule(step(i as f64 * 0.1, 0, 80));
+        }
+        // Crossed part way through the fourth move
+        sim.set_breakpoint(BreakpointCondition::AtStepperPosition { axis: 0, position: 300 });
+
+        let (_, state) = hit(sim.run_event_loop());
+        assert_eq!(state.stepper_positions, [320, 0, 0, 0]);
+        assert_eq!(state.position[0], 4.0);
+        assert_eq!(state.pending_events, 6);
+
+        assert_eq!(sim.run_event_loop(), RunOutcome::Completed(6));
+        assert_eq!(sim.get_snapshot().stepper_positions[0], 800);
+    }
+
+    #[test]
+    fn test_break_on_event_and_single_step() {
+        let mut sim = Simulator::default();
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::PositionUpdate([10.0, 10.0, 10.0, 0.0])));
+        sim.schedule(step(1.0, 1, 80));
+        sim.schedule(SimEvent::new(2.0, SimEventPayload::UserEvent("layer 2".to_string())));
+        sim.schedule(step(3.0, 1, 80));
+        sim.set_breakpoint(BreakpointCondition::OnEvent(SimEventType::User));
+
+        let (condition, state) = hit(sim.run_event_loop());
+        assert_eq!(condition, BreakpointCondition::OnEvent(SimEventType::User));
+        assert_eq!(state.time, 2.0);
+        assert_eq!(state.position[1], 11.0);
+
+        // Single-stepping ignores breakpoints
+        let event = sim.step_one_event().unwrap();
+        assert_eq!(event.event_type, SimEventType::Step);
+        assert_eq!(sim.get_position()[1], 12.0);
+        assert_eq!(sim.step_one_event(), None);
+
+        sim.clear_breakpoints();
+        sim.schedule(SimEvent::new(4.0, SimEventPayload::UserEvent("done".to_string())));
+        assert_eq!(sim.run_event_loop(), RunOutcome::Completed(1));
+    }
+
+    #[test]
+    fn test_break_on_thermal_runaway() {
+        let mut sim = Simulator::default();
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterTarget { target: 200.0 }));
+        sim.schedule(SimEvent::new(20.0, SimEventPayload::ThermalEvent(ThermalEvent::Runaway {
+            reason: "heating too slowly".to_string(),
+        })));
+        sim.schedule(SimEvent::new(20.0, SimEventPayload::HeaterUpdate { power: 0.0 }));
+        sim.set_breakpoint(BreakpointCondition::OnThermalRunaway);
+
+        let (condition, state) = hit(sim.run_event_loop());
+        assert_eq!(condition, BreakpointCondition::OnThermalRunaway);
+        assert_eq!(state.time, 20.0);
+        assert!(state.heater.current_temp > 60.0 && state.heater.current_temp < 200.0);
+        assert_eq!(state.heater.target, Some(200.0));
+        assert_eq!(state.thermal_events.len(), 1);
+        assert_eq!(state.pending_events, 1);
+    }
+}
@@ -0,0 +1,117 @@
+// src/simulator/clock.rs - Simulated time with optional acceleration
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+/// Logical simulation time, optionally paced against the wall clock
+///
+/// With a `time_scale` of 1 the simulation runs in real time; larger values
+/// run it that many times faster.
+#[derive(Debug, Clone)]
+pub struct SimClock {
+    current_time: Duration,
+    time_scale: f64,
+    /// Real and logical time `advance` has paced from since the last
+    /// `skip_to`, so waits are measured from there and their overshoot
+    /// doesn't add up
+    paced_since: Option<(Instant, Duration)>,
+}
+
+impl SimClock {
+    pub fn new() -> Self {
+        Self::new_realtime()
+    }
+
+    /// Clock keeping pace with the wall clock, as `new_with_scale(1.0)`
+    pub fn new_realtime() -> Self {
+        Self::new_with_scale(1.0)
+    }
+
+    /// Clock running `scale` times faster than real time
+    ///
+    /// `f64::INFINITY` makes `advance` return without waiting at all.
+    pub fn new_with_scale(scale: f64) -> Self {
+        Self {
+            current_time: Duration::ZERO,
+            time_scale: if scale > 0.0 { scale } else { 1.0 },
+            paced_since: None,
+        }
+    }
+
+    /// Logical time elapsed since the simulation started
+    pub fn now(&self) -> Duration {
+        self.current_time
+    }
+
+    pub fn now_secs(&self) -> f64 {
+        self.current_time.as_secs_f64()
+    }
+
+    pub fn get_time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Advance logical time by `dt`, waiting until `time_scale` times the
+    /// real time since pacing started has caught up with it
+    pub async fn advance(&mut self, dt: Duration) {
+        let (started, start_time) = *self.paced_since.get_or_insert((Instant::now(), self.current_time));
+        self.current_time += dt;
+        let real = (self.current_time - start_time).as_secs_f64() / self.time_scale;
+        if real > 0.0 {
+            tokio::time::sleep_until(started + Duration::from_secs_f64(real)).await;
+        }
+    }
+
+    /// Jump logical time forward to `time` without waiting
+    pub fn skip_to(&mut self, time: Duration) {
+        self.current_time = self.current_time.max(time);
+        self.paced_since = None;
+    }
+
+    /// Wall-clock time the simulation reaches `sim_time` at the current
+    /// time scale, or reached it if it has passed
+    pub fn time_to_wall_clock(&self, sim_time: Duration) -> SystemTime {
+        let now = SystemTime::now();
+        if sim_time >= self.current_time {
+            now + (sim_time - self.current_time).div_f64(self.time_scale)
+        } else {
+            now - (self.current_time - sim_time).div_f64(self.time_scale)
+        }
+    }
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_realtime_pacing() {
+        let mut clock = SimClock::new_with_scale(10.0);
+        let started = Instant::now();
+        for _ in 0..10 {
+            clock.advance(Duration::from_millis(100)).await;
+        }
+        let elapsed = started.elapsed();
+        assert_eq!(clock.now(), Duration::from_secs(1));
+        assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_millis(101), "took {:?}", elapsed);
+
+        // Skipped time isn't waited for later
+        clock.skip_to(Duration::from_secs(60));
+        let started = Instant::now();
+        clock.advance(Duration::from_millis(100)).await;
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(10) && elapsed < Duration::from_millis(11), "took {:?}", elapsed);
+
+        let wall = clock.time_to_wall_clock(Duration::from_secs(70));
+        let ahead = wall.duration_since(SystemTime::now()).unwrap();
+        // The wall clock isn't paused, so allow for a slow machine
+        assert!(ahead > Duration::from_millis(500) && ahead <= Duration::from_millis(990), "{:?}", ahead);
+        assert!(clock.time_to_wall_clock(Duration::ZERO) < SystemTime::now());
+        assert_eq!(SimClock::new_realtime().get_time_scale(), SimClock::new_with_scale(1.0).get_time_scale());
+    }
+}
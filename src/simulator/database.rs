@@ -0,0 +1,155 @@
+// src/simulator/database.rs - SQLite output of a simulation run
+use std::path::Path;
+use rusqlite::{Connection, params};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS steps (
+    step INTEGER NOT NULL,
+    time REAL NOT NULL,
+    x REAL, y REAL, z REAL, e REAL,
+    vx REAL, vy REAL, vz REAL, ve REAL,
+    temperature REAL,
+    event_type TEXT,
+    gcode TEXT
+);
+CREATE TABLE IF NOT EXISTS hardware_events (
+    step INTEGER NOT NULL,
+    time REAL NOT NULL,
+    event_type TEXT NOT NULL,
+    value REAL
+);
+CREATE INDEX IF NOT EXISTS steps_step ON steps (step);
+CREATE INDEX IF NOT EXISTS hardware_events_step ON hardware_events (step);
+CREATE INDEX IF NOT EXISTS hardware_events_type ON hardware_events (event_type);
+";
+
+/// Simulator state after each processed event, as SQL tables
+///
+/// `steps` has a row per event with the position, the velocity since the
+/// previous row and the hotend temperature; `gcode` holds the text of user
+/// events, which scripts use to mark the command being simulated.
+/// `hardware_events` has a row per endstop hit or thermal event.
+#[derive(Debug)]
+pub struct SimulationDatabase {
+    connection: Connection,
+    last: Option<(f64, [f64; 4])>,
+}
+
+impl SimulationDatabase {
+    /// Open or create a database, adding the tables if missing
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Database kept in memory, for tests
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> rusqlite::Result<Self> {
+        // Rows arrive one at a time: don't sync the file for each of them
+        connection.pragma_update(None, "synchronous", "OFF")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection, last: None })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    pub fn record_step(
+        &mut self,
+        step: u64,
+        time: f64,
+        position: [f64; 4],
+        temperature: f64,
+        event_type: &str,
+        gcode: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        let velocity: [f64; 4] = match self.last {
+            Some((last_time, last_position)) if time > last_time => {
+                std::array::from_fn(|axis| (position[axis] - last_position[axis]) / (time - last_time))
+            }
+            _ => [0.0; 4],
+        };
+        self.last = Some((time, position));
+        self.connection
+            .prepare_cached(
+                "INSERT INTO steps (step, time, x, y, z, e, vx, vy, vz, ve, temperature, event_type, gcode)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )?
+            .execute(params![
+                step as i64,
+                time,
+                position[0],
+                position[1],
+                position[2],
+                position[3],
+                velocity[0],
+                velocity[1],
+                velocity[2],
+                velocity[3],
+                temperature,
+                event_type,
+                gcode,
+            ])?;
+        Ok(())
+    }
+
+    pub fn record_hardware_event(&mut self, step: u64, time: f64, event_type: &str, value: f64) -> rusqlite::Result<()> {
+        self.connection
+            .prepare_cached("INSERT INTO hardware_events (step, time, event_type, value) VALUES (?1, ?2, ?3, ?4)")?
+            .execute(params![step as i64, time, event_type, value])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{SimEvent, SimEventPayload, Simulator, StepCommand, ThermalEvent};
+
+    #[test]
+    fn test_rows_per_processed_event() {
+        let path = std::env::temp_dir().join(format!("krusty-sim-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut sim = Simulator::default();
+        sim.set_database(SimulationDatabase::open(&path).unwrap());
+
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::PositionUpdate([50.0, 0.0, 0.0, 0.0])));
+        sim.schedule(SimEvent::new(0.5, SimEventPayload::UserEvent("G1 X100".to_string())));
+        sim.schedule(SimEvent::new(1.0, SimEventPayload::Step(StepCommand { axis: 0, steps: 4000 })));
+        sim.schedule(SimEvent::new(1.5, SimEventPayload::UserEvent("G28 X".to_string())));
+        // Homing overshoots: the fourth move hits the endstop
+        for i in 0..5 {
+            sim.schedule(SimEvent::new(2.0 + i as f64, SimEventPayload::Step(StepCommand { axis: 0, steps: -2400 })));
+        }
+        sim.schedule(SimEvent::new(8.0, SimEventPayload::ThermalEvent(ThermalEvent::Runaway {
+            reason: "test".to_string(),
+        })));
+        let processed = sim.run_event_loop().completed().unwrap();
+        assert_eq!(processed, 11);
+        drop(sim.take_database());
+
+        let database = SimulationDatabase::open(&path).unwrap();
+        let count = |sql: &str| database.connection().query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count("SELECT count(*) FROM steps"), processed as i64);
+        assert_eq!(count("SELECT count(*) FROM hardware_events"), 2);
+        assert_eq!(count("SELECT step FROM hardware_events WHERE event_type = 'endstop_triggered'"), 8);
+        let (max_x, vx): (f64, f64) = database
+            .connection()
+            .query_row("SELECT max(x), vx FROM steps WHERE event_type = 'Step'", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((max_x, vx), (100.0, 100.0));
+        let gcode: Vec<String> = database
+            .connection()
+            .prepare("SELECT gcode FROM steps WHERE gcode LIKE '%G%' ORDER BY step")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(gcode, ["G1 X100", "G28 X"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
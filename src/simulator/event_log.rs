@@ -0,0 +1,341 @@
+// src/simulator/event_log.rs - Persistent JSONL audit log of simulation events
+use super::event_queue::{EventFilter, SimEvent};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::Stream;
+
+/// Where and how much to log
+#[derive(Debug, Clone)]
+pub struct EventLogConfig {
+    /// Active log file; rotated files are written next to it as `<path>.N.gz`
+    pub path: PathBuf,
+
+    /// Size at which the active file is compressed and a new one started
+    pub max_size_bytes: u64,
+
+    /// Number of compressed files kept before the oldest is deleted
+    pub max_rotated_files: usize,
+
+    /// Events buffered between producers and the writer
+    pub buffer_size: usize,
+}
+
+impl EventLogConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size_bytes: 10 * 1024 * 1024,
+            max_rotated_files: 5,
+            buffer_size: 4096,
+        }
+    }
+}
+
+/// One line of the log file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedEvent<E = SimEvent> {
+    /// Seconds since the logger started (monotonic)
+    pub monotonic_secs: f64,
+
+    /// Wall-clock time in milliseconds since the Unix epoch
+    pub unix_ms: u64,
+
+    pub event: E,
+}
+
+/// Appends events to a rolling JSONL file from a background task
+///
+/// Logging never blocks the caller: events go through a bounded channel
+/// and are dropped with a warning if the writer falls behind. Simulation
+/// events are logged by default; the printer logs its own events as JSON.
+#[derive(Debug)]
+pub struct EventLogger<E = SimEvent> {
+    config: EventLogConfig,
+    started: Instant,
+    tx: Option<mpsc::Sender<LoggedEvent<E>>>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl<E: Serialize + DeserializeOwned + Send + 'static> EventLogger<E> {
+    /// Start the writer task; must be called within a tokio runtime
+    pub fn spawn(config: EventLogConfig) -> Self {
+        let (tx, mut rx) = mpsc::channel::<LoggedEvent<E>>(config.buffer_size.max(1));
+        let writer_config = config.clone();
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut writer = LogWriter::open(writer_config)?;
+            while let Some(entry) = rx.blocking_recv() {
+                writer.append(&entry)?;
+            }
+            writer.flush()
+        });
+
+        Self {
+            config,
+            started: Instant::now(),
+            tx: Some(tx),
+            writer: Some(writer),
+        }
+    }
+
+    /// Queue an event for writing, stamped with the current time
+    pub fn log(&self, event: E) {
+        if let Some(tx) = &self.tx {
+            enqueue(tx, stamp(self.started, event));
+        }
+    }
+
+    /// Stop accepting events and wait for everything queued to reach disk
+    ///
+    /// Subscriptions passed to `attach` must be closed first or the writer
+    /// keeps running.
+    pub async fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.tx = None;
+        if let Some(writer) = self.writer.take() {
+            writer.await??;
+        }
+        Ok(())
+    }
+
+    /// Logged entries for which `keep` is true, oldest first
+    pub fn entries_where(&self, keep: impl Fn(&LoggedEvent<E>) -> bool) -> io::Result<Vec<LoggedEvent<E>>> {
+        let mut entries = Vec::new();
+        for path in self.log_files() {
+            let reader: Box<dyn Read> = if path == self.config.path {
+                Box::new(File::open(&path)?)
+            } else {
+                Box::new(GzDecoder::new(File::open(&path)?))
+            };
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry: LoggedEvent<E> = serde_json::from_str(&line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if keep(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Existing log files, oldest first
+    fn log_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = (1..=self.config.max_rotated_files)
+            .rev()
+            .map(|index| rotated_path(&self.config.path, index))
+            .filter(|path| path.exists())
+            .collect();
+        if self.config.path.exists() {
+            files.push(self.config.path.clone());
+        }
+        files
+    }
+}
+
+impl EventLogger<SimEvent> {
+    /// Log everything arriving on an event subscription until it closes
+    pub fn attach(&self, mut events: mpsc::Receiver<SimEvent>) -> JoinHandle<()> {
+        let tx = self.tx.clone();
+        let started = self.started;
+        tokio::spawn(async move {
+            let Some(tx) = tx else {
+                return;
+            };
+            while let Some(event) = events.recv().await {
+                enqueue(&tx, stamp(started, event));
+            }
+        })
+    }
+
+    /// Logged entries whose monotonic time lies in `from..=to` seconds and
+    /// whose type matches `filter`, oldest first
+    pub fn query(&self, from: Duration, to: Duration, filter: EventFilter) -> io::Result<Vec<LoggedEvent>> {
+        let (from, to) = (from.as_secs_f64(), to.as_secs_f64());
+        self.entries_where(|entry| {
+            entry.monotonic_secs >= from && entry.monotonic_secs <= to && filter.matches(entry.event.event_type)
+        })
+    }
+
+    /// Replay the events logged between two instants of this logger's run
+    pub fn replay_range(&self, start: Instant, end: Instant) -> impl Stream<Item = SimEvent> + use<> {
+        let from = start.saturating_duration_since(self.started);
+        let to = end.saturating_duration_since(self.started);
+        let events = match self.query(from, to, EventFilter::ALL) {
+            Ok(entries) => entries.into_iter().map(|entry| entry.event).collect(),
+            Err(e) => {
+                tracing::error!("Failed to read event log: {}", e);
+                Vec::new()
+            }
+        };
+        tokio_stream::iter(events)
+    }
+}
+
+fn stamp<E>(started: Instant, event: E) -> LoggedEvent<E> {
+    LoggedEvent {
+        monotonic_secs: started.elapsed().as_secs_f64(),
+        unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0),
+        event,
+    }
+}
+
+fn enqueue<E>(tx: &mpsc::Sender<LoggedEvent<E>>, entry: LoggedEvent<E>) {
+    if tx.try_send(entry).is_err() {
+        tracing::warn!("Event log writer lagging, dropped event");
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}.gz", index));
+    PathBuf::from(name)
+}
+
+/// Blocking side of the logger, owned by the writer task
+struct LogWriter {
+    config: EventLogConfig,
+    file: File,
+    size: u64,
+}
+
+impl LogWriter {
+    fn open(config: EventLogConfig) -> io::Result<Self> {
+        if let Some(parent) = config.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { config, file, size })
+    }
+
+    fn append<E: Serialize>(&mut self, entry: &LoggedEvent<E>) -> io::Result<()> {
+        let mut line = serde_json::to_string(entry).map_err(io::Error::other)?;
+        line.push('\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_size_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    /// Compress the active file to `.1.gz`, shifting older ones up
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let path = &self.config.path;
+        let keep = self.config.max_rotated_files;
+
+        if keep > 0 {
+            let oldest = rotated_path(path, keep);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+            for index in (1..keep).rev() {
+                let from = rotated_path(path, index);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(path, index + 1))?;
+                }
+            }
+
+            let mut encoder = GzEncoder::new(File::create(rotated_path(path, 1))?, Compression::default());
+            io::copy(&mut File::open(path)?, &mut encoder)?;
+            encoder.finish()?;
+        }
+
+        self.file = File::create(path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::event_queue::{SimEventPayload, SimEventType, StepCommand};
+    use tokio_stream::StreamExt;
+
+    fn temp_log_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("krusty-event-log-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("events.jsonl")
+    }
+
+    #[tokio::test]
+    async fn test_replay_time_range() {
+        let path = temp_log_path("replay");
+        let mut logger = EventLogger::spawn(EventLogConfig::new(&path));
+
+        for i in 0..500 {
+            logger.log(SimEvent::new(i as f64, SimEventPayload::Step(StepCommand { axis: 0, steps: i })));
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let middle = Instant::now();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        for i in 500..1000 {
+            let payload = if i % 2 == 0 {
+                SimEventPayload::Step(StepCommand { axis: 0, steps: i })
+            } else {
+                SimEventPayload::HeaterUpdate { power: 0.5 }
+            };
+            logger.log(SimEvent::new(i as f64, payload));
+        }
+        logger.finish().await.unwrap();
+
+        let replayed: Vec<SimEvent> = logger.replay_range(middle, Instant::now()).collect().await;
+        assert_eq!(replayed.len(), 500);
+        assert!(replayed.iter().enumerate().all(|(i, event)| event.timestamp == (500 + i) as f64));
+
+        let all = logger.query(Duration::ZERO, Duration::MAX, EventFilter::ALL).unwrap();
+        assert_eq!(all.len(), 1000);
+
+        let heater = logger.query(Duration::ZERO, Duration::MAX, EventFilter::HEATER_UPDATE).unwrap();
+        assert_eq!(heater.len(), 250);
+        assert!(heater.iter().all(|entry| entry.event.event_type == SimEventType::HeaterUpdate));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_keeps_events_readable() {
+        let path = temp_log_path("rotate");
+        let config = EventLogConfig {
+            max_size_bytes: 2048,
+            max_rotated_files: 100,
+            ..EventLogConfig::new(&path)
+        };
+        let mut logger = EventLogger::spawn(config);
+        for i in 0..200 {
+            logger.log(SimEvent::new(i as f64, SimEventPayload::UserEvent(format!("event {}", i))));
+        }
+        logger.finish().await.unwrap();
+
+        assert!(rotated_path(&path, 2).exists());
+        assert!(fs::metadata(&path).unwrap().len() <= 2048);
+
+        let entries = logger.query(Duration::ZERO, Duration::MAX, EventFilter::ALL).unwrap();
+        assert_eq!(entries.len(), 200);
+        assert!(entries.iter().enumerate().all(|(i, entry)| entry.event.timestamp == i as f64));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
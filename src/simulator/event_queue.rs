@@ -0,0 +1,412 @@
+// src/simulator/event_queue.rs - Time-ordered simulation events
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops::{BitOr, BitOrAssign};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Events buffered per subscriber before further ones are dropped
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Step pulses for one motor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepCommand {
+    /// Motor index (0 = X, 1 = Y, 2 = Z, 3 = E)
+    pub axis: usize,
+
+    /// Number of steps; negative values step backwards
+    pub steps: i64,
+}
+
+/// Notable changes reported by the thermal model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ThermalEvent {
+    /// The heater reached its target temperature
+    TargetReached { temperature: f64 },
+
+    /// The heater was shut down by a safety check
+    Runaway { reason: String },
+
+    /// The hotend thermistors disagree by more than allowed; control
+    /// switched to the lowest reading
+    SensorDivergence { readings: Vec<f64>, spread: f64 },
+}
+
+/// Kind of a simulation event, independent of its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SimEventType {
+    Step,
+    HeaterUpdate,
+    PositionUpdate,
+    Thermal,
+    User,
+    /// Check every axis against its endstop
+    EndstopCheck,
+    /// The endstop of an axis (index) was hit
+    EndstopTriggered(usize),
+}
+
+/// Set of event types a subscriber wants to receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventFilter(u8);
+
+impl EventFilter {
+    pub const NONE: Self = Self(0);
+    pub const STEP: Self = Self(1 << 0);
+    pub const HEATER_UPDATE: Self = Self(1 << 1);
+    pub const POSITION_UPDATE: Self = Self(1 << 2);
+    pub const THERMAL: Self = Self(1 << 3);
+    pub const USER: Self = Self(1 << 4);
+    pub const ENDSTOP: Self = Self(1 << 5);
+    pub const ALL: Self = Self(0b11_1111);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn matches(self, event_type: SimEventType) -> bool {
+        self.contains(event_type.into())
+    }
+}
+
+impl BitOr for EventFilter {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for EventFilter {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl From<SimEventType> for EventFilter {
+    fn from(event_type: SimEventType) -> Self {
+        match event_type {
+            SimEventType::Step => Self::STEP,
+            SimEventType::HeaterUpdate => Self::HEATER_UPDATE,
+            SimEventType::PositionUpdate => Self::POSITION_UPDATE,
+            SimEventType::Thermal => Self::THERMAL,
+            SimEventType::User => Self::USER,
+            SimEventType::EndstopCheck | SimEventType::EndstopTriggered(_) => Self::ENDSTOP,
+        }
+    }
+}
+
+/// Handle returned by `SimEventQueue::subscribe`, used to unsubscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriberToken(u64);
+
+/// Data carried by a simulation event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SimEventPayload {
+    /// Move a motor by a number of steps
+    Step(StepCommand),
+
+    /// Change the heater duty (0.0 - 1.0)
+    HeaterUpdate { power: f64 },
+
+    /// Regulate the heater towards a temperature (°C); 0 turns it off
+    HeaterTarget { target: f64 },
+
+    /// Toolhead position [X, Y, Z, E] in mm
+    PositionUpdate([f64; 4]),
+
+    /// Thermal model notification
+    ThermalEvent(ThermalEvent),
+
+    /// Free-form marker from test code or scripts
+    UserEvent(String),
+}
+
+impl SimEventPayload {
+    pub fn event_type(&self) -> SimEventType {
+        match self {
+            SimEventPayload::Step(_) => SimEventType::Step,
+            SimEventPayload::HeaterUpdate { .. } | SimEventPayload::HeaterTarget { .. } => SimEventType::HeaterUpdate,
+            SimEventPayload::PositionUpdate(_) => SimEventType::PositionUpdate,
+            SimEventPayload::ThermalEvent(_) => SimEventType::Thermal,
+            SimEventPayload::UserEvent(_) => SimEventType::User,
+        }
+    }
+}
+
+/// An event scheduled at a point in simulated time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimEvent {
+    /// Simulated time the event fires at (seconds)
+    pub timestamp: f64,
+
+    pub event_type: SimEventType,
+
+    pub payload: Option<SimEventPayload>,
+}
+
+impl SimEvent {
+    pub fn new(timestamp: f64, payload: SimEventPayload) -> Self {
+        Self {
+            timestamp,
+            event_type: payload.event_type(),
+            payload: Some(payload),
+        }
+    }
+
+    /// An event that carries no data
+    pub fn signal(timestamp: f64, event_type: SimEventType) -> Self {
+        Self {
+            timestamp,
+            event_type,
+            payload: None,
+        }
+    }
+}
+
+/// Heap entry ordering events by time, then by insertion order
+#[derive(Debug)]
+struct QueuedEvent {
+    event: SimEvent,
+    sequence: u64,
+}
+
+impl PartialEq for QueuedEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedEvent {}
+
+impl PartialOrd for QueuedEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap: reverse so the earliest event pops first
+        other.event.timestamp
+            .total_cmp(&self.event.timestamp)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    token: SubscriberToken,
+    filter: EventFilter,
+    tx: mpsc::Sender<SimEvent>,
+}
+
+/// Priority queue of simulation events, earliest first
+///
+/// Pushed events are also copied to every subscriber whose filter matches.
+#[derive(Debug, Default)]
+pub struct SimEventQueue {
+    heap: BinaryHeap<QueuedEvent>,
+    next_sequence: u64,
+    subscribers: Vec<Subscriber>,
+    next_token: u64,
+}
+
+impl SimEventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: SimEvent) {
+        self.notify_subscribers(&event);
+        self.heap.push(QueuedEvent {
+            event,
+            sequence: self.next_sequence,
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Remove and return the earliest event
+    pub fn pop(&mut self) -> Option<SimEvent> {
+        self.heap.pop().map(|queued| queued.event)
+    }
+
+    /// The earliest event without removing it
+    pub fn peek(&self) -> Option<&SimEvent> {
+        self.heap.peek().map(|queued| &queued.event)
+    }
+
+    /// When the earliest event fires, without removing it
+    ///
+    /// Events scheduled before time zero report zero.
+    pub fn peek_time(&self) -> Option<Duration> {
+        self.peek().map(|event| Duration::from_secs_f64(event.timestamp.max(0.0)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Receive a copy of every pushed event matching `filter`
+    ///
+    /// Returns the receiver along with a token for `unsubscribe`.
+    pub fn subscribe(&mut self, filter: EventFilter) -> (SubscriberToken, mpsc::Receiver<SimEvent>) {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+        let token = SubscriberToken(self.next_token);
+        self.next_token += 1;
+        self.subscribers.push(Subscriber { token, filter, tx });
+        (token, rx)
+    }
+
+    pub fn unsubscribe(&mut self, token: SubscriberToken) {
+        self.subscribers.retain(|subscriber| subscriber.token != token);
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    fn notify_subscribers(&mut self, event: &SimEvent) {
+        self.subscribers.retain(|subscriber| {
+            if !subscriber.filter.matches(event.event_type) {
+                return !subscriber.tx.is_closed();
+            }
+            match subscriber.tx.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!("Simulator subscriber lagging, dropped {:?} event", event.event_type);
+                    true
+                }
+                // Receiver dropped: forget the subscriber
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_variants_round_trip() {
+        let payloads = [
+            SimEventPayload::Step(StepCommand { axis: 0, steps: -80 }),
+            SimEventPayload::HeaterUpdate { power: 0.5 },
+            SimEventPayload::HeaterTarget { target: 210.0 },
+            SimEventPayload::PositionUpdate([1.0, 2.0, 3.0, 4.0]),
+            SimEventPayload::ThermalEvent(ThermalEvent::TargetReached { temperature: 200.0 }),
+            SimEventPayload::UserEvent("layer 2".to_string()),
+        ];
+
+        let mut queue = SimEventQueue::new();
+        for (i, payload) in payloads.iter().enumerate() {
+            queue.push(SimEvent::new(i as f64, payload.clone()));
+        }
+
+        let mut received = 0;
+        while let Some(event) = queue.pop() {
+            match event.payload {
+                Some(SimEventPayload::Step(command)) => {
+                    assert_eq!(event.event_type, SimEventType::Step);
+                    assert_eq!(command, StepCommand { axis: 0, steps: -80 });
+                }
+                Some(SimEventPayload::HeaterUpdate { power }) => {
+                    assert_eq!(event.event_type, SimEventType::HeaterUpdate);
+                    assert_eq!(power, 0.5);
+                }
+                Some(SimEventPayload::HeaterTarget { target }) => {
+                    assert_eq!(event.event_type, SimEventType::HeaterUpdate);
+                    assert_eq!(target, 210.0);
+                }
+                Some(SimEventPayload::PositionUpdate(position)) => {
+                    assert_eq!(event.event_type, SimEventType::PositionUpdate);
+                    assert_eq!(position, [1.0, 2.0, 3.0, 4.0]);
+                }
+                Some(SimEventPayload::ThermalEvent(thermal)) => {
+                    assert_eq!(event.event_type, SimEventType::Thermal);
+                    assert_eq!(thermal, ThermalEvent::TargetReached { temperature: 200.0 });
+                }
+                Some(SimEventPayload::UserEvent(text)) => {
+                    assert_eq!(event.event_type, SimEventType::User);
+                    assert_eq!(text, "layer 2");
+                }
+                None => panic!("every pushed event carries a payload"),
+            }
+            received += 1;
+        }
+        assert_eq!(received, payloads.len());
+    }
+
+    #[test]
+    fn test_events_pop_in_time_order() {
+        let mut queue = SimEventQueue::new();
+        queue.push(SimEvent::new(2.0, SimEventPayload::UserEvent("c".to_string())));
+        queue.push(SimEvent::new(0.5, SimEventPayload::UserEvent("a".to_string())));
+        queue.push(SimEvent::new(2.0, SimEventPayload::UserEvent("d".to_string())));
+        queue.push(SimEvent::new(1.0, SimEventPayload::UserEvent("b".to_string())));
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop())
+            .map(|event| match event.payload {
+                Some(SimEventPayload::UserEvent(text)) => text,
+                other => panic!("unexpected payload {:?}", other),
+            })
+            .collect();
+        assert_eq!(order, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_out_of_order_insertion() {
+        let mut queue = SimEventQueue::new();
+        assert_eq!(queue.peek_time(), None);
+        for i in 0..100 {
+            let timestamp = rand::random::<f64>() * 100.0;
+            queue.push(SimEvent::new(timestamp, SimEventPayload::UserEvent(i.to_string())));
+        }
+
+        let mut last = 0.0;
+        for _ in 0..100 {
+            let next = queue.peek_time().unwrap();
+            let event = queue.pop().unwrap();
+            assert_eq!(next, Duration::from_secs_f64(event.timestamp));
+            assert!(event.timestamp >= last, "{} popped after {}", event.timestamp, last);
+            last = event.timestamp;
+        }
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_subscribers_receive_filtered_events() {
+        let mut queue = SimEventQueue::new();
+        let (position_token, mut position_rx) = queue.subscribe(EventFilter::POSITION_UPDATE);
+        let (_, mut thermal_rx) = queue.subscribe(EventFilter::HEATER_UPDATE | EventFilter::THERMAL);
+        assert_eq!(queue.subscriber_count(), 2);
+
+        queue.push(SimEvent::new(0.0, SimEventPayload::PositionUpdate([1.0, 0.0, 0.0, 0.0])));
+        queue.push(SimEvent::new(0.1, SimEventPayload::HeaterUpdate { power: 0.4 }));
+        queue.push(SimEvent::new(0.2, SimEventPayload::Step(StepCommand { axis: 1, steps: 10 })));
+        queue.push(SimEvent::new(0.3, SimEventPayload::ThermalEvent(ThermalEvent::Runaway {
+            reason: "test".to_string(),
+        })));
+
+        let types = |rx: &mut mpsc::Receiver<SimEvent>| {
+            std::iter::from_fn(|| rx.try_recv().ok()).map(|event| event.event_type).collect::<Vec<_>>()
+        };
+        assert_eq!(types(&mut position_rx), vec![SimEventType::PositionUpdate]);
+        assert_eq!(types(&mut thermal_rx), vec![SimEventType::HeaterUpdate, SimEventType::Thermal]);
+
+        // Queue itself still holds every event
+        assert_eq!(queue.len(), 4);
+
+        queue.unsubscribe(position_token);
+        assert_eq!(queue.subscriber_count(), 1);
+        drop(thermal_rx);
+        queue.push(SimEvent::new(0.4, SimEventPayload::HeaterUpdate { power: 0.0 }));
+        assert_eq!(queue.subscriber_count(), 0);
+    }
+}
@@ -0,0 +1,735 @@
+// src/simulator/mod.rs - Discrete-event printer simulator
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use crate::temperature::{SensorFusionMode, ThermistorArray};
+
+pub mod accuracy;
+pub mod assertions;
+pub mod breakpoint;
+pub mod clock;
+pub mod database;
+pub mod event_log;
+pub mod event_queue;
+mod physics;
+pub mod scenario;
+pub mod svg;
+
+pub use accuracy::{AccuracyResult, AccuracyScenario, AccuracyScenarioKind, SimulationAccuracyBenchmark};
+pub use assertions::{AssertionFailure, GCodeAssertion, GCodeAssertionSuite, run_gcode_test};
+pub use breakpoint::{BreakpointCondition, HeaterSnapshot, RunOutcome, SimSnapshot};
+pub use clock::SimClock;
+pub use database::SimulationDatabase;
+pub use event_log::{EventLogConfig, EventLogger, LoggedEvent};
+pub use event_queue::{EventFilter, SimEvent, SimEventPayload, SimEventQueue, SimEventType, StepCommand, SubscriberToken, ThermalEvent};
+pub use scenario::{ScenarioEvent, SimulationReport, SimulationScenario};
+pub use svg::{LayerPreview, SvgRenderer, ToolpathMove};
+use physics::{HeaterJob, HeaterState, PhysicsWorkers, StepperJob, StepperState};
+
+/// Physical parameters of the simulated printer
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Steps per mm for [X, Y, Z, E]
+    pub steps_per_mm: [f64; 4],
+
+    /// Room temperature the hotend cools towards (°C)
+    pub ambient_temperature: f64,
+
+    /// Temperature rise per second at full power, ignoring losses (°C/s)
+    pub heating_rate: f64,
+
+    /// Time constant of cooling towards ambient (s)
+    pub cooling_time_constant: f64,
+
+    /// Position where each axis physically hits its endstop (mm), if it has one
+    pub endstop_positions: [Option<f64>; 4],
+
+    /// Threads to run on; with more than one, stepper moves and heater
+    /// physics each get a thread of their own beside the event loop
+    ///
+    /// Every event waits for both threads, so this only pays off when the
+    /// physics of an event costs more than the hand-off; see
+    /// `benches/sim_threads.rs`.
+    pub threads: usize,
+
+    /// Hotend thermistors, each reading the true temperature plus its
+    /// error (°C); the heater is controlled on their fused reading
+    pub thermistors: Option<(ThermistorArray, Vec<f64>)>,
+}
+
+impl SimConfig {
+    /// Derive the thermal model from a heater's physical properties
+    ///
+    /// `heat_capacity` is the thermal mass (J/K) and `loss_coefficient` the
+    /// heat lost to the surroundings per degree above ambient (W/K).
+    pub fn with_heater(mut self, power_watts: f64, heat_capacity: f64, loss_coefficient: f64) -> Self {
+        self.heating_rate = power_watts / heat_capacity;
+        self.cooling_time_constant = heat_capacity / loss_coefficient;
+        self
+    }
+
+    /// Read the hotend through thermistors off from the true temperature
+    /// by `errors` (°C), combined by `fusion_mode`
+    pub fn with_thermistors(mut self, errors: &[f64], fusion_mode: SensorFusionMode, divergence_threshold: f64) -> Self {
+        let pins: Vec<String> = (0..errors.len()).map(|i| format!("sensor{}", i)).collect();
+        let pins: Vec<&str> = pins.iter().map(String::as_str).collect();
+        let array = ThermistorArray::new(&pins, fusion_mode, divergence_threshold);
+        self.thermistors = Some((array, errors.to_vec()));
+        self
+    }
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            steps_per_mm: [80.0, 80.0, 400.0, 500.0],
+            ambient_temperature: 25.0,
+            heating_rate: 3.0,
+            cooling_time_constant: 100.0,
+            endstop_positions: [Some(0.0), Some(0.0), Some(0.0), None],
+            threads: 1,
+            thermistors: None,
+        }
+    }
+}
+
+/// Simulated printer driven by a queue of timed events
+#[derive(Debug)]
+pub struct Simulator {
+    config: SimConfig,
+    queue: SimEventQueue,
+    clock: SimClock,
+    /// Kept apart from `steppers` so the two threads never share a lock
+    heater: Arc<Mutex<HeaterState>>,
+    steppers: Arc<Mutex<StepperState>>,
+    /// Threads updating `heater` and `steppers`, if `threads` > 1
+    workers: Option<PhysicsWorkers>,
+    thermal_events: Vec<ThermalEvent>,
+    user_events: Vec<String>,
+    database: Option<SimulationDatabase>,
+    /// Rows written to `database` so far
+    steps_recorded: u64,
+    /// Collects the XY moves of `run_gcode` for drawing
+    svg_renderer: Option<SvgRenderer>,
+    /// Conditions that stop `run_event_loop`
+    breakpoints: Vec<BreakpointCondition>,
+}
+
+impl Simulator {
+    pub fn new(config: SimConfig) -> Self {
+        Self::new_with_scale(config, 1.0)
+    }
+
+    /// Simulator whose timed loop runs `scale` times faster than real time
+    pub fn new_with_scale(config: SimConfig, scale: f64) -> Self {
+        let heater = Arc::new(Mutex::new(HeaterState::new(&config)));
+        let steppers = Arc::new(Mutex::new(StepperState::new(&config)));
+        let workers = (config.threads > 1).then(|| PhysicsWorkers::spawn(heater.clone(), steppers.clone()));
+        Self {
+            config,
+            queue: SimEventQueue::new(),
+            clock: SimClock::new_with_scale(scale),
+            heater,
+            steppers,
+            workers,
+            thermal_events: Vec::new(),
+            user_events: Vec::new(),
+            database: None,
+            steps_recorded: 0,
+            svg_renderer: None,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    pub fn schedule(&mut self, event: SimEvent) {
+        self.queue.push(event);
+    }
+
+    /// Schedule the moves of a recorded trajectory (M926) to replay from
+    /// now, returning the number of samples loaded
+    ///
+    /// The toolhead jumps to the first sample, then steps between samples
+    /// at their recorded times.
+    pub fn load_trajectory(&mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<usize> {
+        let samples = crate::motion::recorder::read_trajectory(path)?;
+        let Some(first) = samples.first() else {
+            return Ok(0);
+        };
+        let now = self.get_time();
+        let spm = self.config.steps_per_mm;
+        let to_steps = |position: &[f64; 4]| -> [i64; 4] { std::array::from_fn(|axis| (position[axis] * spm[axis]).round() as i64) };
+        self.schedule(SimEvent::new(now, SimEventPayload::PositionUpdate(first.position)));
+        let mut steps = to_steps(&first.position);
+        for sample in &samples[1..] {
+            let target = to_steps(&sample.position);
+            let timestamp = now + (sample.timestamp - first.timestamp).max(0.0);
+            for axis in 0..4 {
+                let delta = target[axis] - steps[axis];
+                if delta != 0 {
+                    self.schedule(SimEvent::new(timestamp, SimEventPayload::Step(StepCommand { axis, steps: delta })));
+                }
+            }
+            steps = target;
+        }
+        Ok(samples.len())
+    }
+
+    /// Receive copies of scheduled events matching `filter`
+    pub fn subscribe(&mut self, filter: EventFilter) -> (SubscriberToken, tokio::sync::mpsc::Receiver<SimEvent>) {
+        self.queue.subscribe(filter)
+    }
+
+    pub fn unsubscribe(&mut self, token: SubscriberToken) {
+        self.queue.unsubscribe(token);
+    }
+
+    /// Current simulated time (seconds)
+    pub fn get_time(&self) -> f64 {
+        self.clock.now_secs()
+    }
+
+    pub fn get_clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    pub fn get_position(&self) -> [f64; 4] {
+        self.steppers().position
+    }
+
+    pub fn get_temperature(&self) -> f64 {
+        self.heater().temperature
+    }
+
+    pub fn get_config(&self) -> &SimConfig {
+        &self.config
+    }
+
+    /// Temperature the heater is regulated towards, if any (°C)
+    pub fn get_heater_target(&self) -> Option<f64> {
+        self.heater().target
+    }
+
+    /// Whether the heater has reached its target since it was set
+    pub fn is_target_reached(&self) -> bool {
+        self.heater().target_reached
+    }
+
+    /// Filament pushed by forward extruder steps over the whole run (mm)
+    pub fn get_extruded_total(&self) -> f64 {
+        self.steppers().extruded_total
+    }
+
+    /// Force the hotend to a temperature (°C), e.g. to start a cooling run
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.heater().temperature = temperature;
+    }
+
+    /// Configure where an axis physically hits its endstop (mm)
+    pub fn set_endstop_position(&mut self, axis: usize, position: f64) {
+        if let Some(endstop) = self.config.endstop_positions.get_mut(axis) {
+            *endstop = Some(position);
+            self.steppers().endstop_positions[axis] = Some(position);
+        }
+    }
+
+    /// Whether the endstop switch of an axis is currently pressed
+    pub fn is_endstop_triggered(&self, axis: usize) -> bool {
+        self.steppers().endstop_triggered.get(axis).copied().unwrap_or(false)
+    }
+
+    pub fn get_thermal_events(&self) -> &[ThermalEvent] {
+        &self.thermal_events
+    }
+
+    pub fn get_user_events(&self) -> &[String] {
+        &self.user_events
+    }
+
+    /// Record the state after every processed event to `database`
+    pub fn set_database(&mut self, database: SimulationDatabase) {
+        self.database = Some(database);
+    }
+
+    /// Stop recording, returning the database for querying
+    pub fn take_database(&mut self) -> Option<SimulationDatabase> {
+        self.database.take()
+    }
+
+    /// Collect the toolpath of G-code runs into `renderer`
+    pub fn set_svg_renderer(&mut self, renderer: SvgRenderer) {
+        self.svg_renderer = Some(renderer);
+    }
+
+    pub fn take_svg_renderer(&mut self) -> Option<SvgRenderer> {
+        self.svg_renderer.take()
+    }
+
+    /// Stop `run_event_loop` when `condition` is met
+    pub fn set_breakpoint(&mut self, condition: BreakpointCondition) {
+        self.breakpoints.push(condition);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Everything the simulator currently knows
+    pub fn get_snapshot(&self) -> SimSnapshot {
+        let heater = self.heater().clone();
+        let steppers = self.steppers().clone();
+        SimSnapshot {
+            time: self.get_time(),
+            position: steppers.position,
+            stepper_positions: steppers.stepper_positions(),
+            heater: HeaterSnapshot {
+                current_temp: heater.temperature,
+                target: heater.target,
+                power: heater.power,
+                target_reached: heater.target_reached,
+            },
+            endstops_triggered: steppers.endstop_triggered,
+            extruded_total: steppers.extruded_total,
+            thermal_events: self.thermal_events.clone(),
+            user_events: self.user_events.clone(),
+            pending_events: self.queue.len(),
+        }
+    }
+
+    fn heater(&self) -> MutexGuard<'_, HeaterState> {
+        self.heater.lock().unwrap()
+    }
+
+    fn steppers(&self) -> MutexGuard<'_, StepperState> {
+        self.steppers.lock().unwrap()
+    }
+
+    /// Motor positions [X, Y, Z, E] (steps)
+    fn stepper_positions(&self) -> [i64; 4] {
+        self.steppers().stepper_positions()
+    }
+
+    /// Process queued events in time order until the queue is empty or a
+    /// breakpoint fires
+    ///
+    /// Runs as fast as possible, jumping the clock from event to event.
+    /// After a breakpoint, calling it again carries on from there.
+    pub fn run_event_loop(&mut self) -> RunOutcome {
+        let mut processed = 0;
+        while let Some(next) = self.queue.peek().map(|event| event.timestamp) {
+            if let Some(outcome) = self.time_breakpoint(next) {
+                return outcome;
+            }
+            let steps_before = self.stepper_positions();
+            let Some(event) = self.step_one_event() else {
+                break;
+            };
+            processed += 1;
+            let steps_after = self.stepper_positions();
+            if let Some(condition) = self
+                .breakpoints
+                .iter()
+                .find(|condition| condition.matches(&event, &steps_before, &steps_after))
+            {
+                return RunOutcome::BreakpointHit {
+                    condition: condition.clone(),
+                    state: self.get_snapshot(),
+                };
+            }
+        }
+        RunOutcome::Completed(processed)
+    }
+
+    /// Handle the next queued event, ignoring breakpoints
+    pub fn step_one_event(&mut self) -> Option<SimEvent> {
+        let event = self.queue.pop()?;
+        let dt = event.timestamp - self.get_time();
+        if dt > 0.0 {
+            // With worker threads the heater catches up while the event is
+            // applied
+            self.integrate_thermal(dt);
+            self.clock.skip_to(Duration::from_secs_f64(event.timestamp));
+        }
+        self.handle_event(event.clone());
+        Some(event)
+    }
+
+    /// Run the clock up to the earliest `AtTime` breakpoint before `next`
+    /// (seconds), removing it
+    fn time_breakpoint(&mut self, next: f64) -> Option<RunOutcome> {
+        let (index, at) = self
+            .breakpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(index, condition)| match condition {
+                BreakpointCondition::AtTime(at) if at.as_secs_f64() < next => Some((index, *at)),
+                _ => None,
+            })
+            .min_by_key(|(_, at)| *at)?;
+        let condition = self.breakpoints.remove(index);
+        let dt = at.as_secs_f64() - self.get_time();
+        if dt > 0.0 {
+            self.integrate_thermal(dt);
+            self.sync_workers();
+            self.clock.skip_to(at);
+        }
+        Some(RunOutcome::BreakpointHit { condition, state: self.get_snapshot() })
+    }
+
+    /// Run the simulation in the same 0.1 s steps as
+    /// `run_event_loop_with_timeout` up to `end` seconds, without pacing
+    /// against the wall clock, returning how many events were handled
+    pub fn run_until(&mut self, end: f64) -> usize {
+        const DT_LOGICAL: Duration = Duration::from_millis(100);
+        let end = Duration::from_secs_f64(end.max(0.0));
+        let mut processed = 0;
+        loop {
+            let now = self.get_time();
+            while self.queue.peek().is_some_and(|event| event.timestamp <= now) {
+                if let Some(event) = self.queue.pop() {
+                    self.handle_event(event);
+                    processed += 1;
+                }
+            }
+            if self.clock.now() >= end {
+                return processed;
+            }
+            let dt = DT_LOGICAL.min(end - self.clock.now());
+            self.integrate_thermal(dt.as_secs_f64());
+            self.sync_workers();
+            self.clock.skip_to(self.clock.now() + dt);
+        }
+    }
+
+    /// Run the simulation in fixed 0.1 s logical steps, paced by the clock's
+    /// time scale, until `max_logical_time` has been simulated
+    ///
+    /// Events are processed as their timestamps come due. Fails if the run
+    /// takes longer than `timeout` of real time.
+    pub async fn run_event_loop_with_timeout(
+        &mut self,
+        timeout: Duration,
+        max_logical_time: Duration,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        const DT_LOGICAL: Duration = Duration::from_millis(100);
+        let started = Instant::now();
+        let end = self.clock.now() + max_logical_time;
+        let mut processed = 0;
+
+        while self.clock.now() < end {
+            if started.elapsed() > timeout {
+                return Err(format!(
+                    "Simulation timed out after {:.1}s at t={:.1}s",
+                    started.elapsed().as_secs_f64(),
+                    self.get_time()
+                ).into());
+            }
+
+            let now = self.get_time();
+            while self.queue.peek().is_some_and(|event| event.timestamp <= now) {
+                if let Some(event) = self.queue.pop() {
+                    self.handle_event(event);
+                    processed += 1;
+                }
+            }
+
+            let dt = DT_LOGICAL.min(end - self.clock.now());
+            self.integrate_thermal(dt.as_secs_f64());
+            self.sync_workers();
+            self.clock.advance(dt).await;
+        }
+        Ok(processed)
+    }
+
+    /// Step the hotend thermal model forward by `dt` seconds
+    ///
+    /// On a worker thread the result arrives with `sync_workers`.
+    fn integrate_thermal(&mut self, dt: f64) {
+        let now = self.get_time();
+        if let Some(workers) = &mut self.workers {
+            workers.send_heater(HeaterJob::Advance { dt, now });
+            return;
+        }
+        let events = self.heater().integrate(dt, now);
+        for event in events {
+            self.queue.push(event);
+        }
+    }
+
+    /// Wait for the worker threads to finish the current event, queueing
+    /// the events they raised
+    ///
+    /// Thermal events are queued before endstop ones, in the order a
+    /// single-threaded run raises them.
+    fn sync_workers(&mut self) {
+        let Some(workers) = &mut self.workers else {
+            return;
+        };
+        let (thermal, triggered) = workers.wait();
+        for event in thermal {
+            self.queue.push(event);
+        }
+        for axis in triggered {
+            self.trigger_endstop(axis);
+        }
+    }
+
+    fn handle_event(&mut self, event: SimEvent) {
+        if self.database.is_none() {
+            self.apply_event(event);
+            self.sync_workers();
+            return;
+        }
+        // The runaway temperature is read before the event is applied
+        self.sync_workers();
+        let event_type = event.event_type;
+        let gcode = match &event.payload {
+            Some(SimEventPayload::UserEvent(text)) => Some(text.clone()),
+            _ => None,
+        };
+        let hardware_event = match (&event.payload, event_type) {
+            (Some(SimEventPayload::ThermalEvent(ThermalEvent::Runaway { .. })), _) => {
+                Some(("thermal_runaway", self.get_temperature()))
+            }
+            (Some(SimEventPayload::ThermalEvent(ThermalEvent::TargetReached { temperature })), _) => {
+                Some(("target_reached", *temperature))
+            }
+            (Some(SimEventPayload::ThermalEvent(ThermalEvent::SensorDivergence { spread, .. })), _) => {
+                Some(("sensor_divergence", *spread))
+            }
+            (None, SimEventType::EndstopTriggered(axis)) => Some(("endstop_triggered", axis as f64)),
+            _ => None,
+        };
+        self.apply_event(event);
+        self.sync_workers();
+        self.record_step(event_type, gcode.as_deref(), hardware_event);
+    }
+
+    /// Write the state after an event to the database, dropping the
+    /// database if it fails
+    fn record_step(&mut self, event_type: SimEventType, gcode: Option<&str>, hardware_event: Option<(&str, f64)>) {
+        let (time, position, temperature) = (self.get_time(), self.get_position(), self.get_temperature());
+        let step = self.steps_recorded;
+        let Some(database) = &mut self.database else {
+            return;
+        };
+        let result = database
+            .record_step(step, time, position, temperature, &format!("{:?}", event_type), gcode)
+            .and_then(|()| match hardware_event {
+                Some((kind, value)) => database.record_hardware_event(step, time, kind, value),
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            tracing::error!("Failed to write simulation database, disabling it: {}", e);
+            self.database = None;
+        }
+        self.steps_recorded += 1;
+    }
+
+    fn apply_event(&mut self, event: SimEvent) {
+        let Some(payload) = event.payload else {
+            // EndstopTriggered needs no handling: the switch was latched
+            // when the step hit it
+            if event.event_type == SimEventType::EndstopCheck {
+                self.check_endstops();
+            }
+            return;
+        };
+        match payload {
+            SimEventPayload::Step(command) => self.apply_step(command),
+            SimEventPayload::HeaterUpdate { power } => match &mut self.workers {
+                Some(workers) => workers.send_heater(HeaterJob::SetPower(power)),
+                None => self.heater().set_power(power),
+            },
+            SimEventPayload::HeaterTarget { target } => match &mut self.workers {
+                Some(workers) => workers.send_heater(HeaterJob::SetTarget(target)),
+                None => self.heater().set_target(target),
+            },
+            SimEventPayload::PositionUpdate(position) => {
+                self.steppers().position = position;
+            }
+            SimEventPayload::ThermalEvent(thermal) => {
+                self.thermal_events.push(thermal);
+            }
+            SimEventPayload::UserEvent(text) => {
+                self.user_events.push(text);
+            }
+        }
+    }
+}
+
+impl Simulator {
+    /// Move a motor, stopping it at its endstop
+    fn apply_step(&mut self, command: StepCommand) {
+        if let Some(workers) = &mut self.workers {
+            workers.send_stepper(StepperJob::Step(command));
+            return;
+        }
+        let triggered = self.steppers().apply_step(&command);
+        if let Some(axis) = triggered {
+            self.trigger_endstop(axis);
+        }
+    }
+
+    /// Latch any switch whose axis sits on its endstop
+    fn check_endstops(&mut self) {
+        if let Some(workers) = &mut self.workers {
+            workers.send_stepper(StepperJob::CheckEndstops);
+            return;
+        }
+        let triggered = self.steppers().check_endstops();
+        for axis in triggered {
+            self.trigger_endstop(axis);
+        }
+    }
+
+    /// Queue the event for a switch that was just pressed
+    fn trigger_endstop(&mut self, axis: usize) {
+        self.queue.push(SimEvent::signal(self.get_time(), SimEventType::EndstopTriggered(axis)));
+    }
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new(SimConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_loop_applies_payloads() {
+        let mut sim = Simulator::default();
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::PositionUpdate([10.0, 0.0, 0.0, 0.0])));
+        sim.schedule(SimEvent::new(1.0, SimEventPayload::Step(StepCommand { axis: 0, steps: 800 })));
+        sim.schedule(SimEvent::new(0.5, SimEventPayload::HeaterUpdate { power: 1.0 }));
+        sim.schedule(SimEvent::new(10.5, SimEventPayload::HeaterUpdate { power: 0.0 }));
+        sim.schedule(SimEvent::new(11.0, SimEventPayload::UserEvent("done".to_string())));
+
+        assert_eq!(sim.run_event_loop(), RunOutcome::Completed(5));
+        assert_eq!(sim.get_position(), [20.0, 0.0, 0.0, 0.0]);
+        assert_eq!(sim.get_time(), 11.0);
+        assert!(sim.get_temperature() > 45.0);
+        assert_eq!(sim.get_user_events(), ["done".to_string()]);
+    }
+
+    #[test]
+    fn test_axis_stops_at_endstop() {
+        let mut sim = Simulator::default();
+        sim.set_endstop_position(0, 5.0);
+        let (_, mut endstop_rx) = sim.subscribe(EventFilter::ENDSTOP);
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::PositionUpdate([50.0, 20.0, 10.0, 0.0])));
+
+        // Home X: 100 moves of -1mm, far more than needed
+        for i in 0..100 {
+            let time = 0.1 + i as f64 * 0.01;
+            sim.schedule(SimEvent::new(time, SimEventPayload::Step(StepCommand { axis: 0, steps: -80 })));
+        }
+        sim.run_event_loop();
+
+        assert_eq!(sim.get_position()[0], 5.0);
+        assert!(sim.is_endstop_triggered(0));
+        assert!(!sim.is_endstop_triggered(1));
+        let event = endstop_rx.try_recv().unwrap();
+        assert_eq!(event.event_type, SimEventType::EndstopTriggered(0));
+        assert!(endstop_rx.try_recv().is_err());
+
+        // Backing off releases the switch, a check finds it open
+        sim.schedule(SimEvent::new(2.0, SimEventPayload::Step(StepCommand { axis: 0, steps: 400 })));
+        sim.schedule(SimEvent::signal(2.1, SimEventType::EndstopCheck));
+        sim.run_event_loop();
+        assert_eq!(sim.get_position()[0], 10.0);
+        assert!(!sim.is_endstop_triggered(0));
+    }
+
+    #[test]
+    fn test_threaded_matches_single_threaded() {
+        fn run(threads: usize) -> SimSnapshot {
+            let mut sim = Simulator::new(SimConfig { threads, ..SimConfig::default() });
+            sim.schedule(SimEvent::new(0.0, SimEventPayload::PositionUpdate([20.0, 20.0, 5.0, 0.0])));
+            sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterTarget { target: 150.0 }));
+            for i in 1..2000 {
+                let time = i as f64 * 0.05;
+                let event = match i % 8 {
+                    0 => SimEvent::signal(time, SimEventType::EndstopCheck),
+                    // Axes drift towards their endstops and bounce off
+                    n => SimEvent::new(time, SimEventPayload::Step(StepCommand {
+                        axis: n % 4,
+                        steps: if i % 5 == 0 { 120 } else { -37 },
+                    })),
+                };
+                sim.schedule(event);
+            }
+            sim.schedule(SimEvent::new(90.0, SimEventPayload::HeaterUpdate { power: 0.3 }));
+            // Finish by homing X
+            sim.schedule(SimEvent::new(101.0, SimEventPayload::Step(StepCommand { axis: 0, steps: -100_000 })));
+            assert!(sim.run_event_loop().completed().is_some());
+            sim.get_snapshot()
+        }
+
+        let single = run(1);
+        let threaded = run(3);
+        assert!(single.thermal_events.iter().any(|event| matches!(event, ThermalEvent::TargetReached { .. })));
+        assert!(single.endstops_triggered[0]);
+        assert_eq!(threaded.stepper_positions, single.stepper_positions);
+        assert!((threaded.heater.current_temp - single.heater.current_temp).abs() < 1e-9);
+        assert_eq!(threaded, single);
+    }
+
+    #[test]
+    fn test_thermistor_divergence() {
+        fn run(errors: &[f64]) -> Simulator {
+            let config = SimConfig::default().with_thermistors(errors, SensorFusionMode::Average, 5.0);
+            let mut sim = Simulator::new(config);
+            sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterTarget { target: 100.0 }));
+            sim.run_until(60.0);
+            sim
+        }
+
+        // Averaging a sensor reading 4°C high stops heating 2°C early
+        let agreeing = run(&[0.0, 4.0]);
+        match agreeing.get_thermal_events() {
+            [ThermalEvent::TargetReached { temperature }] => assert!((temperature - 100.0).abs() < 0.5),
+            events => panic!("unexpected thermal events {:?}", events),
+        }
+        assert!((agreeing.get_temperature() - 98.0).abs() < 1.0);
+
+        // 10°C apart: reported, then regulated on the lower, true, reading
+        let diverging = run(&[0.0, 10.0]);
+        let events = diverging.get_thermal_events();
+        match events {
+            [ThermalEvent::SensorDivergence { readings, spread }, ThermalEvent::TargetReached { .. }] => {
+                assert_eq!(readings.len(), 2);
+                assert!((spread - 10.0).abs() < 1e-9);
+            }
+            events => panic!("unexpected thermal events {:?}", events),
+        }
+        assert!((diverging.get_temperature() - 100.0).abs() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_accelerated_thermal_simulation() {
+        let mut sim = Simulator::new_with_scale(SimConfig::default(), 100.0);
+        sim.schedule(SimEvent::new(0.0, SimEventPayload::HeaterUpdate { power: 1.0 }));
+        sim.schedule(SimEvent::new(30.0, SimEventPayload::HeaterUpdate { power: 0.5 }));
+
+        let started = Instant::now();
+        let processed = sim
+            .run_event_loop_with_timeout(Duration::from_secs(10), Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(processed, 2);
+        assert!((sim.get_time() - 60.0).abs() < 1e-9);
+        assert!(sim.get_temperature() > 80.0);
+
+        // A real-time run of the same length gives up at the timeout
+        let mut sim = Simulator::default();
+        let result = sim
+            .run_event_loop_with_timeout(Duration::from_millis(50), Duration::from_secs(60))
+            .await;
+        assert!(result.is_err());
+        assert!(sim.get_time() < 1.0);
+    }
+}
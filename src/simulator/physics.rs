@@ -0,0 +1,308 @@
+// src/simulator/physics.rs - Heater and stepper models, optionally on worker threads
+use std::sync::{Arc, Mutex};
+use crossbeam_channel::{Receiver, Sender};
+use crate::temperature::ThermistorArray;
+use super::{SimConfig, SimEvent, SimEventPayload, StepCommand, ThermalEvent};
+
+/// Hotend thermal model
+#[derive(Debug, Clone)]
+pub(super) struct HeaterState {
+    ambient_temperature: f64,
+    heating_rate: f64,
+    cooling_time_constant: f64,
+    /// 0.0 - 1.0
+    pub power: f64,
+    /// Temperature the heater is regulated towards, if any (°C)
+    pub target: Option<f64>,
+    /// Whether `target` has been reached since it was set
+    pub target_reached: bool,
+    pub temperature: f64,
+    /// Thermistors read for control, with the error of each (°C); the
+    /// true temperature is used without them
+    sensors: Option<(ThermistorArray, Vec<f64>)>,
+}
+
+impl HeaterState {
+    pub fn new(config: &SimConfig) -> Self {
+        Self {
+            ambient_temperature: config.ambient_temperature,
+            heating_rate: config.heating_rate,
+            cooling_time_constant: config.cooling_time_constant,
+            power: 0.0,
+            target: None,
+            target_reached: false,
+            temperature: config.ambient_temperature,
+            sensors: config.thermistors.clone(),
+        }
+    }
+
+    /// Step the model forward by `dt` seconds from `now`, returning the
+    /// `TargetReached` event if the target was reached on the way and any
+    /// `SensorDivergence`
+    ///
+    /// With a target set the heater is switched fully on or off every
+    /// 0.1 s, like a bang-bang controller, on the fused thermistor reading.
+    pub fn integrate(&mut self, dt: f64, now: f64) -> Vec<SimEvent> {
+        const CONTROL_INTERVAL: f64 = 0.1;
+        let mut events = Vec::new();
+        let Some(target) = self.target else {
+            self.integrate_step(dt);
+            events.extend(self.read_sensors(now + dt));
+            return events;
+        };
+        let mut remaining = dt;
+        while remaining > 0.0 {
+            self.power = if self.measured_temperature() < target { 1.0 } else { 0.0 };
+            let step = remaining.min(CONTROL_INTERVAL);
+            self.integrate_step(step);
+            remaining -= step;
+            let time = now + (dt - remaining);
+            events.extend(self.read_sensors(time));
+            let measured = self.measured_temperature();
+            if !self.target_reached && measured >= target {
+                self.target_reached = true;
+                let thermal = ThermalEvent::TargetReached { temperature: measured };
+                events.push(SimEvent::new(time, SimEventPayload::ThermalEvent(thermal)));
+            }
+        }
+        events
+    }
+
+    /// Temperature the controller sees: the fused thermistor reading, or
+    /// the true temperature without thermistors (°C)
+    pub fn measured_temperature(&self) -> f64 {
+        self.sensors
+            .as_ref()
+            .and_then(|(array, _)| array.fused_temperature())
+            .unwrap_or(self.temperature)
+    }
+
+    /// Feed the thermistors the current temperature, returning a
+    /// `SensorDivergence` event at `time` if they drift apart
+    fn read_sensors(&mut self, time: f64) -> Option<SimEvent> {
+        let (array, offsets) = self.sensors.as_mut()?;
+        let readings: Vec<f64> = offsets.iter().map(|offset| self.temperature + offset).collect();
+        let divergence = array.update(&readings)?;
+        let thermal = ThermalEvent::SensorDivergence { readings: divergence.readings, spread: divergence.spread };
+        Some(SimEvent::new(time, SimEventPayload::ThermalEvent(thermal)))
+    }
+
+    fn integrate_step(&mut self, dt: f64) {
+        let losses = (self.temperature - self.ambient_temperature) / self.cooling_time_constant;
+        self.temperature += (self.power * self.heating_rate - losses) * dt;
+    }
+
+    pub fn set_power(&mut self, power: f64) {
+        self.target = None;
+        self.power = power.clamp(0.0, 1.0);
+    }
+
+    pub fn set_target(&mut self, target: f64) {
+        self.target = (target > 0.0).then_some(target);
+        self.target_reached = false;
+        self.power = 0.0;
+    }
+}
+
+/// Motor positions and the endstops they run into
+#[derive(Debug, Clone)]
+pub(super) struct StepperState {
+    steps_per_mm: [f64; 4],
+    /// Position where each axis physically hits its endstop (mm), if it has one
+    pub endstop_positions: [Option<f64>; 4],
+    /// [X, Y, Z, E] (mm)
+    pub position: [f64; 4],
+    pub endstop_triggered: [bool; 4],
+    /// Which side of its endstop each axis travels on (+1 above, -1 below)
+    endstop_side: [f64; 4],
+    /// Filament pushed by forward extruder steps (mm)
+    pub extruded_total: f64,
+}
+
+impl StepperState {
+    pub fn new(config: &SimConfig) -> Self {
+        Self {
+            steps_per_mm: config.steps_per_mm,
+            endstop_positions: config.endstop_positions,
+            position: [0.0; 4],
+            endstop_triggered: [false; 4],
+            endstop_side: [1.0; 4],
+            extruded_total: 0.0,
+        }
+    }
+
+    /// Motor positions [X, Y, Z, E] (steps)
+    pub fn stepper_positions(&self) -> [i64; 4] {
+        std::array::from_fn(|axis| (self.position[axis] * self.steps_per_mm[axis]).round() as i64)
+    }
+
+    /// Move a motor, stopping it at its endstop, returning the axis if this
+    /// move newly pressed its switch
+    ///
+    /// A motor that reaches its endstop halts there: the rest of the move
+    /// and any further steps into the switch are dropped until it moves
+    /// back off.
+    pub fn apply_step(&mut self, command: &StepCommand) -> Option<usize> {
+        let axis = command.axis;
+        let &steps_per_mm = self.steps_per_mm.get(axis)?;
+        let start = self.position[axis];
+        let mut target = start + command.steps as f64 / steps_per_mm;
+        if axis == 3 && command.steps > 0 {
+            self.extruded_total += command.steps as f64 / steps_per_mm;
+        }
+
+        let mut triggered = None;
+        if let Some(endstop) = self.endstop_positions[axis] {
+            if start != endstop {
+                self.endstop_side[axis] = (start - endstop).signum();
+            }
+            if (target - endstop) * self.endstop_side[axis] <= 0.0 {
+                // At or past the switch: the motor stops on it
+                target = endstop;
+                if !self.endstop_triggered[axis] {
+                    self.endstop_triggered[axis] = true;
+                    triggered = Some(axis);
+                }
+            } else {
+                self.endstop_triggered[axis] = false;
+            }
+        }
+
+        self.position[axis] = target;
+        triggered
+    }
+
+    /// Latch any switch whose axis sits on its endstop, returning the axes
+    /// newly pressed
+    pub fn check_endstops(&mut self) -> Vec<usize> {
+        let mut triggered = Vec::new();
+        for axis in 0..self.position.len() {
+            let Some(endstop) = self.endstop_positions[axis] else {
+                continue;
+            };
+            let half_step = 0.5 / self.steps_per_mm[axis];
+            if (self.position[axis] - endstop).abs() <= half_step {
+                if !self.endstop_triggered[axis] {
+                    self.endstop_triggered[axis] = true;
+                    triggered.push(axis);
+                }
+            } else {
+                self.endstop_triggered[axis] = false;
+            }
+        }
+        triggered
+    }
+}
+
+/// Work for the heater thread
+#[derive(Debug)]
+pub(super) enum HeaterJob {
+    Advance { dt: f64, now: f64 },
+    SetPower(f64),
+    SetTarget(f64),
+}
+
+/// Work for the stepper thread
+#[derive(Debug)]
+pub(super) enum StepperJob {
+    Step(StepCommand),
+    CheckEndstops,
+}
+
+/// Heater and stepper threads working on the simulator's shared state
+///
+/// The main thread hands out the work for an event and waits for both
+/// threads before the next one, so results match a single-threaded run
+/// exactly. Dropping this closes the channels, which ends the threads.
+#[derive(Debug)]
+pub(super) struct PhysicsWorkers {
+    heater_jobs: Sender<HeaterJob>,
+    heater_done: Receiver<Vec<SimEvent>>,
+    stepper_jobs: Sender<StepperJob>,
+    stepper_done: Receiver<Vec<usize>>,
+    /// Jobs sent whose results have not been collected
+    heater_pending: usize,
+    stepper_pending: usize,
+}
+
+impl PhysicsWorkers {
+    pub fn spawn(heater: Arc<Mutex<HeaterState>>, steppers: Arc<Mutex<StepperState>>) -> Self {
+        let (heater_jobs, heater_rx) = crossbeam_channel::unbounded::<HeaterJob>();
+        let (heater_tx, heater_done) = crossbeam_channel::unbounded();
+        std::thread::Builder::new()
+            .name("sim-heater".to_string())
+            .spawn(move || {
+                for job in heater_rx {
+                    let mut heater = heater.lock().unwrap();
+                    let events = match job {
+                        HeaterJob::Advance { dt, now } => heater.integrate(dt, now),
+                        HeaterJob::SetPower(power) => {
+                            heater.set_power(power);
+                            Vec::new()
+                        }
+                        HeaterJob::SetTarget(target) => {
+                            heater.set_target(target);
+                            Vec::new()
+                        }
+                    };
+                    drop(heater);
+                    if heater_tx.send(events).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn simulator heater thread");
+
+        let (stepper_jobs, stepper_rx) = crossbeam_channel::unbounded::<StepperJob>();
+        let (stepper_tx, stepper_done) = crossbeam_channel::unbounded();
+        std::thread::Builder::new()
+            .name("sim-stepper".to_string())
+            .spawn(move || {
+                for job in stepper_rx {
+                    let mut steppers = steppers.lock().unwrap();
+                    let triggered = match job {
+                        StepperJob::Step(command) => steppers.apply_step(&command).into_iter().collect(),
+                        StepperJob::CheckEndstops => steppers.check_endstops(),
+                    };
+                    drop(steppers);
+                    if stepper_tx.send(triggered).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn simulator stepper thread");
+
+        Self {
+            heater_jobs,
+            heater_done,
+            stepper_jobs,
+            stepper_done,
+            heater_pending: 0,
+            stepper_pending: 0,
+        }
+    }
+
+    pub fn send_heater(&mut self, job: HeaterJob) {
+        self.heater_jobs.send(job).expect("simulator heater thread stopped");
+        self.heater_pending += 1;
+    }
+
+    pub fn send_stepper(&mut self, job: StepperJob) {
+        self.stepper_jobs.send(job).expect("simulator stepper thread stopped");
+        self.stepper_pending += 1;
+    }
+
+    /// Wait for every job sent so far, returning the thermal events raised
+    /// and the axes whose endstops were newly pressed, in order
+    pub fn wait(&mut self) -> (Vec<SimEvent>, Vec<usize>) {
+        let mut thermal = Vec::new();
+        for _ in 0..std::mem::take(&mut self.heater_pending) {
+            thermal.extend(self.heater_done.recv().expect("simulator heater thread stopped"));
+        }
+        let mut triggered = Vec::new();
+        for _ in 0..std::mem::take(&mut self.stepper_pending) {
+            triggered.extend(self.stepper_done.recv().expect("simulator stepper thread stopped"));
+        }
+        (thermal, triggered)
+    }
+}
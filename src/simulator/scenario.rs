@@ -0,0 +1,222 @@
+// src/simulator/scenario.rs - Scripted simulation runs loaded from TOML or JSON
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use super::{AssertionFailure, SimulationDatabase, SimEvent, SimEventPayload, SimEventType, Simulator, StepCommand, ThermalEvent};
+
+/// One scripted action, tagged by `type`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScenarioEvent {
+    /// Step a motor; `direction = false` steps backwards
+    Step {
+        time: f64,
+        axis: usize,
+        steps: i64,
+        #[serde(default = "default_direction")]
+        direction: bool,
+    },
+    /// Regulate the hotend to a temperature (°C), 0 to turn it off
+    HeaterSet { time: f64, target_temp: f64 },
+    /// Drive the heater at a fixed duty (0.0 - 1.0)
+    HeaterPower { time: f64, power: f64 },
+    /// Teleport the toolhead to [X, Y, Z, E] (mm)
+    Position { time: f64, position: [f64; 4] },
+    EndstopCheck { time: f64 },
+    /// Free-form marker, reported back in `SimulationReport::user_events`
+    Marker { time: f64, text: String },
+}
+
+fn default_direction() -> bool { true }
+
+fn default_timeout_secs() -> f64 { 60.0 }
+
+impl ScenarioEvent {
+    pub fn time(&self) -> f64 {
+        match self {
+            ScenarioEvent::Step { time, .. }
+            | ScenarioEvent::HeaterSet { time, .. }
+            | ScenarioEvent::HeaterPower { time, .. }
+            | ScenarioEvent::Position { time, .. }
+            | ScenarioEvent::EndstopCheck { time }
+            | ScenarioEvent::Marker { time, .. } => *time,
+        }
+    }
+
+    pub fn to_sim_event(&self) -> SimEvent {
+        let time = self.time();
+        let payload = match self {
+            ScenarioEvent::Step { axis, steps, direction, .. } => SimEventPayload::Step(StepCommand {
+                axis: *axis,
+                steps: if *direction { *steps } else { -*steps },
+            }),
+            ScenarioEvent::HeaterSet { target_temp, .. } => SimEventPayload::HeaterTarget { target: *target_temp },
+            ScenarioEvent::HeaterPower { power, .. } => SimEventPayload::HeaterUpdate { power: *power },
+            ScenarioEvent::Position { position, .. } => SimEventPayload::PositionUpdate(*position),
+            ScenarioEvent::EndstopCheck { .. } => return SimEvent::signal(time, SimEventType::EndstopCheck),
+            ScenarioEvent::Marker { text, .. } => SimEventPayload::UserEvent(text.clone()),
+        };
+        SimEvent::new(time, payload)
+    }
+}
+
+/// A repeatable simulation run
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SimulationScenario {
+    /// Simulated time to run for (s); defaults to the time of the last event
+    #[serde(default)]
+    pub duration: Option<f64>,
+
+    /// Real time the run may take before it fails (s)
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: f64,
+
+    /// SQLite file to record every processed event to, see `SimulationDatabase`
+    #[serde(default)]
+    pub database: Option<PathBuf>,
+
+    #[serde(default)]
+    pub events: Vec<ScenarioEvent>,
+}
+
+impl SimulationScenario {
+    /// Load a script, as JSON if the file ends in `.json` and TOML otherwise
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let scenario: Self = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            serde_json::from_str(&contents)?
+        } else {
+            toml::from_str(&contents)?
+        };
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(event) = self.events.iter().find(|event| !event.time().is_finite() || event.time() < 0.0) {
+            return Err(format!("Scenario event has invalid time {}", event.time()));
+        }
+        if self.duration.is_some_and(|duration| !duration.is_finite() || duration < 0.0) {
+            return Err("Scenario duration must be a positive number of seconds".to_string());
+        }
+        if !self.timeout_secs.is_finite() || self.timeout_secs <= 0.0 {
+            return Err("Scenario timeout must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Simulated time the scenario covers (s)
+    pub fn duration(&self) -> f64 {
+        self.duration
+            .unwrap_or_else(|| self.events.iter().map(ScenarioEvent::time).fold(0.0, f64::max))
+    }
+}
+
+/// State of the simulator at the end of a scenario
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationReport {
+    /// Toolhead position [X, Y, Z, E] (mm)
+    pub position: [f64; 4],
+    /// Hotend temperature (°C)
+    pub temperature: f64,
+    pub endstops_triggered: [bool; 4],
+    pub thermal_events: Vec<ThermalEvent>,
+    pub user_events: Vec<String>,
+    pub events_processed: usize,
+    /// Simulated time at the end of the run (s)
+    pub simulated_time: f64,
+    /// `; ASSERT` checks of a G-code run that did not hold
+    pub assertion_failures: Vec<AssertionFailure>,
+}
+
+impl Simulator {
+    /// Load a scenario script and run it, see `run_scenario`
+    pub async fn run_scenario_from_file(&mut self, path: impl AsRef<Path>) -> Result<SimulationReport, Box<dyn Error>> {
+        let scenario = SimulationScenario::from_file(path)?;
+        self.run_scenario(&scenario).await
+    }
+
+    /// Queue the scenario's events and run it for its duration
+    ///
+    /// Runs at the simulator's time scale, so build it with
+    /// `new_with_scale` for anything longer than the timeout.
+    pub async fn run_scenario(&mut self, scenario: &SimulationScenario) -> Result<SimulationReport, Box<dyn Error>> {
+        scenario.validate()?;
+        if let Some(path) = &scenario.database {
+            self.set_database(SimulationDatabase::open(path)?);
+        }
+        let start = self.get_time();
+        for event in &scenario.events {
+            let mut event = event.to_sim_event();
+            event.timestamp += start;
+            self.schedule(event);
+        }
+        let events_processed = self
+            .run_event_loop_with_timeout(
+                Duration::from_secs_f64(scenario.timeout_secs),
+                Duration::from_secs_f64(scenario.duration()),
+            )
+            .await;
+        if scenario.database.is_some() {
+            self.take_database();
+        }
+        Ok(self.report(events_processed?))
+    }
+
+    /// Current state as a report
+    pub fn report(&self, events_processed: usize) -> SimulationReport {
+        SimulationReport {
+            position: self.get_position(),
+            temperature: self.get_temperature(),
+            endstops_triggered: std::array::from_fn(|axis| self.is_endstop_triggered(axis)),
+            thermal_events: self.get_thermal_events().to_vec(),
+            user_events: self.get_user_events().to_vec(),
+            events_processed,
+            simulated_time: self.get_time(),
+            assertion_failures: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::SimConfig;
+
+    #[tokio::test]
+    async fn test_sample_scenario() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/heat_and_home.toml");
+        let mut sim = Simulator::new_with_scale(SimConfig::default(), f64::INFINITY);
+        let report = sim.run_scenario_from_file(path).await.unwrap();
+
+        assert_eq!(report.position, [0.0, 30.0, 10.0, 1.0]);
+        assert_eq!(report.endstops_triggered, [true, false, false, false]);
+        assert!((report.temperature - 200.0).abs() < 2.0, "temperature {}", report.temperature);
+        assert!(matches!(
+            report.thermal_events.as_slice(),
+            [ThermalEvent::TargetReached { temperature }] if *temperature >= 200.0
+        ));
+        assert_eq!(report.user_events, ["homed".to_string()]);
+        assert!((report.simulated_time - 120.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_json_scenario() {
+        let scenario: SimulationScenario = serde_json::from_str(
+            r#"{"events": [
+                {"type": "step", "time": 0.5, "axis": 1, "steps": 80, "direction": false},
+                {"type": "heater_power", "time": 2.0, "power": 0.5}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.duration(), 2.0);
+        assert_eq!(scenario.timeout_secs, 60.0);
+        assert_eq!(
+            scenario.events[0].to_sim_event(),
+            SimEvent::new(0.5, SimEventPayload::Step(StepCommand { axis: 1, steps: -80 }))
+        );
+        assert!(SimulationScenario { duration: Some(-1.0), ..scenario }.validate().is_err());
+    }
+}
@@ -0,0 +1,234 @@
+// src/simulator/svg.rs - Toolpath drawings of simulated G-code
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use serde::Serialize;
+
+/// Drawn width of extruding moves (mm)
+const PRINT_LINE_WIDTH: f64 = 0.4;
+
+/// Drawn width of travel moves (mm)
+const TRAVEL_LINE_WIDTH: f64 = 0.2;
+
+/// Space kept around the print when fitting it to the canvas (mm)
+const MARGIN: f64 = 2.0;
+
+/// Z differences smaller than this are the same layer (mm)
+const LAYER_Z_TOLERANCE: f64 = 1e-4;
+
+/// One straight XY move of the toolhead
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ToolpathMove {
+    pub from: [f64; 2],
+    pub to: [f64; 2],
+    /// Height the move ends at (mm)
+    pub z: f64,
+    /// Whether the move extrudes, as opposed to travelling
+    pub is_print: bool,
+}
+
+/// Extent of one layer's printed moves, one line of `print_layer_preview.jsonl`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerPreview {
+    /// Counting the lowest as 1
+    pub layer: u32,
+    pub z: f64,
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+    pub moves: usize,
+}
+
+/// Collects toolhead moves and draws them as SVG
+///
+/// Print moves are coloured from blue at the lowest layer to red at the
+/// highest, travel moves are dashed gray. Coordinates stay in mm; the
+/// viewBox centres the print and scales it to fit `width` × `height`
+/// pixels.
+#[derive(Debug, Clone)]
+pub struct SvgRenderer {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels per mm, reduced if the print would not fit otherwise
+    pub scale: f64,
+    moves: Vec<ToolpathMove>,
+}
+
+impl SvgRenderer {
+    pub fn new(width: u32, height: u32, scale: f64) -> Self {
+        Self { width, height, scale, moves: Vec::new() }
+    }
+
+    pub fn add_move(&mut self, from: [f64; 2], to: [f64; 2], z: f64, is_print: bool) {
+        self.moves.push(ToolpathMove { from, to, z, is_print });
+    }
+
+    pub fn moves(&self) -> &[ToolpathMove] {
+        &self.moves
+    }
+
+    pub fn render(&self) -> String {
+        let (min, max) = bounds(self.moves.iter().flat_map(|m| [m.from, m.to])).unwrap_or(([0.0; 2], [0.0; 2]));
+        let (width, height) = (f64::from(self.width.max(1)), f64::from(self.height.max(1)));
+        let fit = (width / (max[0] - min[0] + 2.0 * MARGIN)).min(height / (max[1] - min[1] + 2.0 * MARGIN));
+        let scale = if self.scale > 0.0 { self.scale.min(fit) } else { fit };
+        let (view_width, view_height) = (width / scale, height / scale);
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+
+        let (min_z, max_z) = self
+            .moves
+            .iter()
+            .filter(|m| m.is_print)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), m| (lo.min(m.z), hi.max(m.z)));
+
+        let mut svg = String::new();
+        // Y is flipped so the print reads as seen from above
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            self.width,
+            self.height,
+            num(center[0] - view_width / 2.0),
+            num(-center[1] - view_height / 2.0),
+            num(view_width),
+            num(view_height),
+        );
+        svg.push_str(r#"<g transform="scale(1 -1)" fill="none" stroke-linecap="round">"#);
+        svg.push('\n');
+        for m in &self.moves {
+            let d = format!("M{} {} L{} {}", num(m.from[0]), num(m.from[1]), num(m.to[0]), num(m.to[1]));
+            if m.is_print {
+                let t = if max_z > min_z { (m.z - min_z) / (max_z - min_z) } else { 0.0 };
+                let _ = writeln!(
+                    svg,
+                    r#"<path class="print" d="{}" stroke="hsl({:.0},80%,45%)" stroke-width="{}"/>"#,
+                    d,
+                    240.0 * (1.0 - t),
+                    PRINT_LINE_WIDTH
+                );
+            } else {
+                let _ = writeln!(
+                    svg,
+                    r##"<path class="travel" d="{}" stroke="#999999" stroke-width="{}" stroke-dasharray="1 1"/>"##,
+                    d, TRAVEL_LINE_WIDTH
+                );
+            }
+        }
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+
+    /// Bounding box of the printed moves of every layer, lowest first
+    pub fn layer_previews(&self) -> Vec<LayerPreview> {
+        let mut prints: Vec<&ToolpathMove> = self.moves.iter().filter(|m| m.is_print).collect();
+        prints.sort_by(|a, b| a.z.total_cmp(&b.z));
+
+        let mut layers: Vec<(f64, Vec<&ToolpathMove>)> = Vec::new();
+        for m in prints {
+            match layers.last_mut() {
+                Some((z, moves)) if m.z - *z <= LAYER_Z_TOLERANCE => moves.push(m),
+                _ => layers.push((m.z, vec![m])),
+            }
+        }
+        layers
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, (z, moves))| {
+                let (min, max) = bounds(moves.iter().flat_map(|m| [m.from, m.to]))?;
+                Some(LayerPreview {
+                    layer: index as u32 + 1,
+                    z,
+                    min_x: min[0],
+                    min_y: min[1],
+                    max_x: max[0],
+                    max_y: max[1],
+                    moves: moves.len(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn write_svg(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.render())
+    }
+
+    pub fn write_layer_preview(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut jsonl = String::new();
+        for layer in self.layer_previews() {
+            let line = serde_json::to_string(&layer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            jsonl.push_str(&line);
+            jsonl.push('\n');
+        }
+        std::fs::write(path, jsonl)
+    }
+
+    /// Write `toolpath.svg` and `print_layer_preview.jsonl` into `dir`
+    pub fn write_to_dir(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        self.write_svg(dir.join("toolpath.svg"))?;
+        self.write_layer_preview(dir.join("print_layer_preview.jsonl"))
+    }
+}
+
+fn bounds(points: impl Iterator<Item = [f64; 2]>) -> Option<([f64; 2], [f64; 2])> {
+    points.fold(None, |acc, [x, y]| {
+        let (min, max) = acc.unwrap_or(([x, y], [x, y]));
+        Some(([min[0].min(x), min[1].min(y)], [max[0].max(x), max[1].max(y)]))
+    })
+}
+
+/// A coordinate rounded to µm, without trailing zeros or negative zero
+fn num(value: f64) -> String {
+    format!("{}", (value * 1000.0).round() / 1000.0 + 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+
+    #[test]
+    fn test_square_toolpath() {
+        let mut sim = Simulator::default();
+        sim.set_svg_renderer(SvgRenderer::new(400, 400, 20.0));
+        sim.run_gcode_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/square_10mm.gcode"))
+            .unwrap();
+        let renderer = sim.take_svg_renderer().unwrap();
+
+        let svg = renderer.render();
+        let paths: Vec<&str> = svg
+            .lines()
+            .filter(|line| line.starts_with("<path"))
+            .map(|line| line.split("d=\"").nth(1).unwrap().split('"').next().unwrap())
+            .collect();
+        assert_eq!(paths, ["M0 0 L10 0", "M10 0 L10 10", "M10 10 L0 10", "M0 10 L0 0"]);
+        // 400px at 20 px/mm show 20mm around the centre of the square
+        assert!(svg.contains(r#"viewBox="-5 -15 20 20""#), "{}", svg);
+        // 14mm with margins does not fit at 40 px/mm, so it is shrunk
+        let fitted = SvgRenderer { scale: 40.0, ..renderer.clone() }.render();
+        assert!(fitted.contains(r#"viewBox="-2 -12 14 14""#), "{}", fitted);
+
+        let dir = std::env::temp_dir().join(format!("krusty-svg-{}", std::process::id()));
+        renderer.write_to_dir(&dir).unwrap();
+        let preview = std::fs::read_to_string(dir.join("print_layer_preview.jsonl")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            preview,
+            "{\"layer\":1,\"z\":0.2,\"min_x\":0.0,\"min_y\":0.0,\"max_x\":10.0,\"max_y\":10.0,\"moves\":4}\n"
+        );
+    }
+
+    #[test]
+    fn test_travel_and_layer_colours() {
+        let mut renderer = SvgRenderer::new(100, 100, 5.0);
+        renderer.add_move([0.0, 0.0], [5.0, 5.0], 0.2, false);
+        renderer.add_move([5.0, 5.0], [6.0, 5.0], 0.2, true);
+        renderer.add_move([6.0, 5.0], [5.0, 5.0], 0.4, true);
+        let svg = renderer.render();
+        assert!(svg.contains(r#"class="travel" d="M0 0 L5 5""#));
+        assert!(svg.contains("hsl(240,80%,45%)") && svg.contains("hsl(0,80%,45%)"));
+        assert_eq!(renderer.layer_previews().len(), 2);
+    }
+}
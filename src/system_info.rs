@@ -0,0 +1,83 @@
+// src/system_info.rs - Host OS, CPU, memory and disk usage for the web API
+use std::path::Path;
+use serde::Serialize;
+use sysinfo::{Disks, System};
+
+const MB: f64 = 1024.0 * 1024.0;
+const GB: f64 = MB * 1024.0;
+
+/// Snapshot of the machine the host runs on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SystemInfo {
+    pub os_name: String,
+    pub os_version: String,
+    /// Load across all cores (%)
+    pub cpu_usage_pct: f64,
+    pub memory_used_mb: f64,
+    pub memory_total_mb: f64,
+    /// Disk holding the G-code storage path
+    pub disk_used_gb: f64,
+    pub disk_total_gb: f64,
+    /// Seconds since the machine booted
+    pub uptime_secs: u64,
+    pub krusty_version: String,
+}
+
+impl SystemInfo {
+    /// Read current usage, with disk figures for the disk holding `storage_path`
+    ///
+    /// Blocks for about 200ms, since CPU usage is measured between two samples.
+    pub fn collect(storage_path: &Path) -> SystemInfo {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        system.refresh_cpu_usage();
+        system.refresh_memory();
+
+        let (disk_used, disk_total) = disk_usage(storage_path);
+        SystemInfo {
+            os_name: System::name().unwrap_or_else(|| std::env::consts::OS.to_string()),
+            os_version: System::os_version().or_else(System::kernel_version).unwrap_or_default(),
+            cpu_usage_pct: system.global_cpu_usage() as f64,
+            memory_used_mb: system.used_memory() as f64 / MB,
+            memory_total_mb: system.total_memory() as f64 / MB,
+            disk_used_gb: disk_used as f64 / GB,
+            disk_total_gb: disk_total as f64 / GB,
+            uptime_secs: System::uptime(),
+            krusty_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// (used, total) bytes of the disk mounted deepest above `path`
+fn disk_usage(path: &Path) -> (u64, u64) {
+    // The storage directory may not have been created yet
+    let path = path
+        .ancestors()
+        .find_map(|ancestor| std::fs::canonicalize(ancestor).ok())
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    let disks = Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map_or((0, 0), |disk| (disk.total_space() - disk.available_space(), disk.total_space()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let info = SystemInfo::collect(&std::env::temp_dir().join("krusty-missing-dir"));
+        assert!(!info.os_name.is_empty() && !info.os_version.is_empty());
+        assert!((0.0..=100.0).contains(&info.cpu_usage_pct));
+        assert!(info.memory_used_mb > 0.0 && info.memory_total_mb >= info.memory_used_mb);
+        assert!(info.disk_used_gb > 0.0 && info.disk_total_gb >= info.disk_used_gb);
+        assert!(info.uptime_secs > 0);
+        assert_eq!(info.krusty_version, env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,259 @@
+// src/temperature/control.rs - Periodic heater control from sensor readings
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use crate::config::{ChamberConfig, Config};
+use crate::hardware::HardwareManager;
+use crate::printer::PrinterState;
+use super::thermistor::ThermistorModel;
+
+/// Time between sensor readings and heater updates
+const CONTROL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The thermistor divider is ratiometric, so any reference voltage will do
+const ADC_REFERENCE_VOLTAGE: f64 = 3.3;
+
+/// Pins and sensor of the chamber heater
+#[derive(Debug, Clone)]
+struct ChamberIo {
+    sensor_pin: String,
+    heater_pin: String,
+    pullup_ohms: f64,
+    thermistor: ThermistorModel,
+}
+
+impl ChamberIo {
+    fn from_config(config: &ChamberConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            sensor_pin: config.sensor_pin.clone(),
+            heater_pin: config.heater_pin.clone(),
+            pullup_ohms: config.pullup_resistor,
+            thermistor: ThermistorModel::from_sensor_type(&config.sensor_type)?,
+        })
+    }
+}
+
+/// Background task that feeds sensor readings to the heaters and the part
+/// cooling fan in the printer state, and drives the heater pins with the
+/// resulting duty
+///
+/// A heater whose sensor can't be read, or that has faulted, is switched
+/// off.
+#[derive(Debug, Clone)]
+pub struct ThermalControlLoop {
+    hardware: HardwareManager,
+    state: Arc<RwLock<PrinterState>>,
+    chamber: Option<ChamberIo>,
+}
+
+impl ThermalControlLoop {
+    pub fn new(
+        hardware: HardwareManager,
+        state: Arc<RwLock<PrinterState>>,
+        config: &Config,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let chamber = config.chamber.as_ref().map(ChamberIo::from_config).transpose()?;
+        Ok(Self { hardware, state, chamber })
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    async fn run(self) {
+        let mut interval = tokio::time::interval(CONTROL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.update(CONTROL_INTERVAL.as_secs_f64()).await;
+        }
+    }
+
+    /// Take one round of readings, `dt` seconds after the last, and update
+    /// the heaters and fan
+    pub async fn update(&self, dt: f64) {
+        if let Some(chamber) = &self.chamber {
+            self.update_chamber(chamber, dt).await;
+        }
+        self.update_fan().await;
+    }
+
+    /// Let the fan curve follow the active hotend, unless layers are slowed
+    /// for cooling and the fan is boosted
+    async fn update_fan(&self) {
+        let mut state = self.state.write().await;
+        if state.fan_before_slowdown.is_none() {
+            let temperature = state.temperature;
+            state.fan.update_temperature(temperature);
+        }
+    }
+
+    async fn update_chamber(&self, io: &ChamberIo, dt: f64) {
+        let duty = match self.hardware.read_adc(&io.sensor_pin).await {
+            Ok(adc) => {
+                let temperature = io.thermistor.adc_counts_to_celsius(adc, io.pullup_ohms, ADC_REFERENCE_VOLTAGE);
+                let mut state = self.state.write().await;
+                let Some(heater) = state.chamber.as_mut() else {
+                    return;
+                };
+                let faulted = heater.get_fault().is_some();
+                match heater.update(temperature, dt) {
+                    Ok(duty) => duty,
+                    Err(e) => {
+                        if !faulted {
+                            tracing::error!("{}", e);
+                        }
+                        0.0
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Could not read the chamber sensor, heater off: {}", e);
+                0.0
+            }
+        };
+        if let Err(e) = self.hardware.set_pwm(&io.heater_pin, duty).await {
+            tracing::warn!("Could not drive the chamber heater: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use crate::config::FanCurvePoint;
+    use crate::hardware::{McuPort, PortFuture};
+    use crate::temperature::{FanController, Heater};
+    use crate::temperature::thermistor::ADC_MAX;
+
+    /// MCU with a chamber thermistor on PA5 at a settable ADC reading;
+    /// 0 makes the read fail
+    #[derive(Debug, Default)]
+    struct ChamberPort {
+        adc: AtomicU32,
+        pwm: Mutex<Vec<String>>,
+    }
+
+    impl McuPort for ChamberPort {
+        fn transact<'a>(&'a self, command: &'a str) -> PortFuture<'a> {
+            let response = if command == "query_adc pin=PA5" {
+                match self.adc.load(Ordering::SeqCst) {
+                    0 => "error: no such pin".to_string(),
+                    adc => adc.to_string(),
+                }
+            } else {
+                if command.starts_with("set_pwm") {
+                    self.pwm.lock().unwrap().push(command.to_string());
+                }
+                "ok".to_string()
+            };
+            Box::pin(async move { Ok(response) })
+        }
+    }
+
+    impl ChamberPort {
+        /// Read `ohms` through a 4.7kΩ pullup
+        fn set_resistance(&self, ohms: f64) {
+            let adc = (ohms / (ohms + 4700.0) * ADC_MAX as f64).round() as u32;
+            self.adc.store(adc, Ordering::SeqCst);
+        }
+
+        fn last_pwm(&self) -> String {
+            self.pwm.lock().unwrap().last().cloned().unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chamber_heater_driven_from_sensor() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.chamber = Some(toml::from_str(r#"
+            heater_pin = "PB10"
+            sensor_type = "EPCOS 100K B57560G104F"
+            sensor_pin = "PA5"
+            max_power = 0.8
+        "#).unwrap());
+        let port = Arc::new(ChamberPort::default());
+        let mut hardware = HardwareManager::with_port(config.clone(), port.clone());
+        hardware.connect().await.unwrap();
+        let state = Arc::new(RwLock::new(PrinterState {
+            chamber: config.chamber.as_ref().map(Heater::from_chamber_config),
+            ..PrinterState::new()
+        }));
+        let control = ThermalControlLoop::new(hardware, state.clone(), &config).unwrap();
+
+        // Off until given a target
+        port.set_resistance(100000.0);
+        control.update(1.0).await;
+        assert_eq!(port.last_pwm(), "set_pwm pin=PB10 value=0.000");
+        let temperature = state.read().await.chamber.as_ref().unwrap().get_temperature();
+        assert!((temperature - 25.0).abs() < 0.5, "{}", temperature);
+
+        state.write().await.chamber.as_mut().unwrap().set_target(45.0).unwrap();
+        control.update(1.0).await;
+        assert_ne!(port.last_pwm(), "set_pwm pin=PB10 value=0.000");
+        assert!(state.read().await.chamber.as_ref().unwrap().get_output() <= 0.8);
+
+        // Lost sensor
+        port.adc.store(0, Ordering::SeqCst);
+        control.update(1.0).await;
+        assert_eq!(port.last_pwm(), "set_pwm pin=PB10 value=0.000");
+
+        // Shorted sensor reads as overheating and latches the heater off
+        port.adc.store(1, Ordering::SeqCst);
+        control.update(1.0).await;
+        port.set_resistance(100000.0);
+        control.update(1.0).await;
+        assert_eq!(port.last_pwm(), "set_pwm pin=PB10 value=0.000");
+        assert!(state.read().await.chamber.as_ref().unwrap().get_fault().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_fan_follows_hotend() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.fan.curve = vec![
+            FanCurvePoint { temperature: 50.0, speed_pct: 0.0 },
+            FanCurvePoint { temperature: 60.0, speed_pct: 100.0 },
+        ];
+        let state = Arc::new(RwLock::new(PrinterState {
+            fan: FanController::new(&config.fan),
+            temperature: 55.0,
+            ..PrinterState::new()
+        }));
+        let control = ThermalControlLoop::new(HardwareManager::new(config.clone()), state.clone(), &config).unwrap();
+        control.update(1.0).await;
+        assert_eq!(state.read().await.fan.get_speed(), 0.5);
+
+        // Boosted for a slowed layer
+        {
+            let mut state = state.write().await;
+            state.fan_before_slowdown = Some(0.5);
+            state.fan.set_speed(1.0);
+            state.temperature = 50.0;
+        }
+        control.update(1.0).await;
+        assert_eq!(state.read().await.fan.get_speed(), 1.0);
+        state.write().await.fan_before_slowdown = None;
+        control.update(1.0).await;
+        assert_eq!(state.read().await.fan.get_speed(), 0.0);
+
+        // M106 takes over from the curve
+        state.write().await.fan.override_speed(0.7);
+        control.update(1.0).await;
+        assert_eq!(state.read().await.fan.get_speed(), 0.7);
+    }
+
+    #[test]
+    fn test_unknown_chamber_sensor_rejected() {
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.chamber = Some(toml::from_str(r#"
+            heater_pin = "PB10"
+            sensor_type = "PT1000"
+            sensor_pin = "PA5"
+        "#).unwrap());
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        assert!(ThermalControlLoop::new(HardwareManager::new(config.clone()), state, &config).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+// src/temperature/controller.rs - PID heater control
+use std::time::Instant;
+use crate::config::ExtruderConfig;
+
+/// Tuning and limits for a PID heater loop
+#[derive(Debug, Clone)]
+pub struct PidParameters {
+    /// Proportional gain (duty per °C)
+    pub kp: f64,
+
+    /// Integral gain (duty per °C·s)
+    pub ki: f64,
+
+    /// Derivative gain (duty per °C/s)
+    pub kd: f64,
+
+    /// Lower bound for the accumulated integral (°C·s)
+    pub integral_min: f64,
+
+    /// Upper bound for the accumulated integral (°C·s)
+    pub integral_max: f64,
+
+    /// Cutoff frequency of the derivative low-pass filter (Hz)
+    pub derivative_filter_cutoff: f64,
+
+    /// Minimum heater duty
+    pub output_min: f64,
+
+    /// Maximum heater duty
+    pub output_max: f64,
+}
+
+impl PidParameters {
+    pub fn from_extruder_config(config: &ExtruderConfig) -> Self {
+        Self {
+            kp: config.pid_kp,
+            ki: config.pid_ki,
+            kd: config.pid_kd,
+            integral_min: config.integral_min,
+            integral_max: config.integral_max,
+            derivative_filter_cutoff: config.derivative_filter_cutoff,
+            output_min: config.output_min,
+            output_max: config.output_max,
+        }
+    }
+}
+
+/// PID controller driving a heater towards a target temperature
+#[derive(Debug, Clone)]
+pub struct TemperatureController {
+    params: PidParameters,
+    target: f64,
+    integral: f64,
+    derivative: f64,
+    last_temperature: Option<f64>,
+    /// When `update` was last called
+    last_update: Option<Instant>,
+}
+
+impl TemperatureController {
+    pub fn new(params: PidParameters) -> Self {
+        Self {
+            params,
+            target: 0.0,
+            integral: 0.0,
+            derivative: 0.0,
+            last_temperature: None,
+            last_update: None,
+        }
+    }
+
+    pub fn set_target(&mut self, target: f64) {
+        self.target = target;
+    }
+
+    pub fn get_target(&self) -> f64 {
+        self.target
+    }
+
+    /// Accumulated integral term (°C·s)
+    pub fn get_integral(&self) -> f64 {
+        self.integral
+    }
+
+    pub fn get_params(&self) -> &PidParameters {
+        &self.params
+    }
+
+    /// Clear accumulated state, e.g. when the heater is turned off
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.derivative = 0.0;
+        self.last_temperature = None;
+        self.last_update = None;
+    }
+
+    /// Compute the heater duty for a reading taken at `now`, timing it
+    /// against the previous call
+    ///
+    /// The first reading only starts the clock, so it adds nothing to the
+    /// integral or derivative.
+    pub fn update(&mut self, measured: f64, now: Instant) -> f64 {
+        let dt = self.last_update.map_or(0.0, |last| now.saturating_duration_since(last).as_secs_f64());
+        let output = self.calculate_output(measured, dt);
+        // Turning the heater off resets the clock along with the rest
+        self.last_update = (self.target > 0.0).then_some(now);
+        output
+    }
+
+    /// Compute the heater duty for a new temperature reading taken `dt` seconds after the last
+    pub fn calculate_output(&mut self, temperature: f64, dt: f64) -> f64 {
+        if self.target <= 0.0 {
+            self.reset();
+            return self.params.output_min;
+        }
+
+        let error = self.target - temperature;
+
+        if dt > 0.0 {
+            self.integral = (self.integral + error * dt)
+                .clamp(self.params.integral_min, self.params.integral_max);
+
+            // Differentiate the measurement rather than the error so target
+            // changes don't kick the output, then low-pass the noisy result
+            if let Some(last) = self.last_temperature {
+                let raw = -(temperature - last) / dt;
+                let rc = 1.0 / (2.0 * std::f64::consts::PI * self.params.derivative_filter_cutoff);
+                let alpha = rc / (rc + dt);
+                self.derivative = alpha * self.derivative + (1.0 - alpha) * raw;
+            }
+        }
+        self.last_temperature = Some(temperature);
+
+        let output = self.params.kp * error
+            + self.params.ki * self.integral
+            + self.params.kd * self.derivative;
+        output.clamp(self.params.output_min, self.params.output_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn extruder_params() -> PidParameters {
+        let config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        PidParameters::from_extruder_config(&config.extruder)
+    }
+
+    #[test]
+    fn test_heat_up_without_windup() {
+        let params = extruder_params();
+        let mut controller = TemperatureController::new(params.clone());
+        controller.set_target(200.0);
+
+        // First-order hotend: 5°C/s at full power, 80s cooling time constant,
+        // with the thermistor lagging the heater block by 2s
+        let dt = 0.1;
+        let ambient = 25.0;
+        let mut block = ambient;
+        let mut sensor = ambient;
+        let mut peak: f64 = ambient;
+
+        for _ in 0..6000 {
+            let duty = controller.calculate_output(sensor, dt);
+            assert!(duty >= params.output_min && duty <= params.output_max);
+            assert!(controller.get_integral() >= params.integral_min);
+            assert!(controller.get_integral() <= params.integral_max);
+
+            block += (duty * 5.0 - (block - ambient) / 80.0) * dt;
+            sensor += (block - sensor) / 2.0 * dt;
+            peak = peak.max(sensor);
+        }
+
+        assert!(peak - 200.0 < 10.0, "overshoot {:.2}°C", peak - 200.0);
+        assert!((sensor - 200.0).abs() < 1.0, "settled at {:.2}°C", sensor);
+    }
+
+    #[test]
+    fn test_update_integrates_over_elapsed_time() {
+        let mut params = extruder_params();
+        params.integral_max = 1e6;
+        let start = Instant::now();
+
+        // Ten seconds 10°C below target, read at 10Hz and at 2Hz
+        let integral_after = |interval_ms: u64| {
+            let mut controller = TemperatureController::new(params.clone());
+            controller.set_target(200.0);
+            for tick in 0..=10_000 / interval_ms {
+                controller.update(190.0, start + std::time::Duration::from_millis(tick * interval_ms));
+            }
+            controller.get_integral()
+        };
+        assert!((integral_after(100) - 100.0).abs() < 1e-9);
+        assert!((integral_after(500) - 100.0).abs() < 1e-9);
+
+        // The derivative is per second too: 1°C/s whatever the reading rate
+        for interval_ms in [100, 500] {
+            let mut controller = TemperatureController::new(PidParameters {
+                kp: 0.0,
+                ki: 0.0,
+                kd: 1.0,
+                derivative_filter_cutoff: 1e6,
+                output_min: -10.0,
+                ..params.clone()
+            });
+            controller.set_target(200.0);
+            let mut output = 0.0;
+            for tick in 0..=10 {
+                let temperature = 100.0 + (tick * interval_ms) as f64 / 1000.0;
+                output = controller.update(temperature, start + std::time::Duration::from_millis(tick * interval_ms));
+            }
+            assert!((output + 1.0).abs() < 1e-3, "{}", output);
+        }
+    }
+
+    #[test]
+    fn test_output_clamped_and_off_when_no_target() {
+        let mut params = extruder_params();
+        params.output_max = 0.6;
+        let mut controller = TemperatureController::new(params);
+
+        assert_eq!(controller.calculate_output(25.0, 0.1), 0.0);
+
+        controller.set_target(250.0);
+        assert_eq!(controller.calculate_output(25.0, 0.1), 0.6);
+        assert!(controller.get_integral() > 0.0);
+
+        controller.set_target(0.0);
+        assert_eq!(controller.calculate_output(100.0, 0.1), 0.0);
+        assert_eq!(controller.get_integral(), 0.0);
+    }
+}
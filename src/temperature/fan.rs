@@ -0,0 +1,313 @@
+// src/temperature/fan.rs - Part cooling fan control
+use crate::config::{FanConfig, FanControlMode, FanCurvePoint};
+
+/// Fan driven either directly or by a temperature curve
+#[derive(Debug, Clone)]
+pub struct FanController {
+    /// Curve points sorted by temperature
+    curve: Vec<FanCurvePoint>,
+
+    /// How far the temperature must fall before the curve follows it down (°C)
+    hysteresis_deg: f64,
+
+    /// Current duty (0.0 - 1.0)
+    speed: f64,
+
+    /// Temperature the curve was last evaluated at, after hysteresis
+    curve_temperature: Option<f64>,
+
+    /// Whether temperature readings set the speed; M106/M107 take over
+    /// until a curve is set again
+    follow_curve: bool,
+
+    /// Last tachometer reading, if the fan has one
+    rpm: Option<f64>,
+
+    mode: FanControlMode,
+
+    /// RPM the duty is adjusted to hold, if any
+    target_rpm: Option<f64>,
+
+    /// RPM error at the previous tachometer reading, as a fraction of `max_rpm`
+    last_rpm_error: Option<f64>,
+
+    rpm_tolerance: f64,
+    max_rpm: f64,
+    rpm_kp: f64,
+    rpm_ki: f64,
+}
+
+impl FanController {
+    pub fn new(config: &FanConfig) -> Self {
+        let mut fan = Self {
+            curve: Vec::new(),
+            hysteresis_deg: config.hysteresis_deg.max(0.0),
+            speed: 0.0,
+            curve_temperature: None,
+            follow_curve: false,
+            rpm: None,
+            mode: config.fan_control_mode,
+            target_rpm: None,
+            last_rpm_error: None,
+            rpm_tolerance: config.fan_rpm_tolerance.max(0.0) as f64,
+            max_rpm: config.max_rpm.max(1.0),
+            rpm_kp: config.rpm_kp,
+            rpm_ki: config.rpm_ki,
+        };
+        fan.set_curve(config.curve.clone());
+        fan
+    }
+
+    /// Set the fan duty (0.0 - 1.0), dropping any target RPM
+    pub fn set_speed(&mut self, speed: f64) {
+        self.target_rpm = None;
+        self.last_rpm_error = None;
+        self.speed = speed.clamp(0.0, 1.0);
+    }
+
+    /// Set the fan duty and stop following the temperature curve
+    pub fn override_speed(&mut self, speed: f64) {
+        self.follow_curve = false;
+        self.set_speed(speed);
+    }
+
+    pub fn get_speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn control_mode(&self) -> FanControlMode {
+        self.mode
+    }
+
+    /// Hold `rpm` by adjusting the duty on each tachometer reading; 0 stops
+    /// the fan
+    ///
+    /// The duty starts at the rated fraction of full speed and is corrected
+    /// from there.
+    pub fn set_target_rpm(&mut self, rpm: u32) {
+        self.override_speed(rpm as f64 / self.max_rpm);
+        if rpm > 0 {
+            self.target_rpm = Some(rpm as f64);
+        }
+    }
+
+    pub fn get_target_rpm(&self) -> Option<f64> {
+        self.target_rpm
+    }
+
+    /// Whether the last tachometer reading is within tolerance of the target
+    pub fn at_target_rpm(&self) -> bool {
+        match (self.target_rpm, self.rpm) {
+            (Some(target), Some(rpm)) => ((target - rpm) / target).abs() <= self.rpm_tolerance,
+            _ => false,
+        }
+    }
+
+    /// Record a tachometer reading, correcting the duty towards the target RPM
+    ///
+    /// The PI controller works in velocity form, so switching targets
+    /// doesn't have to unwind a stored integral. Readings within tolerance
+    /// leave the duty alone, so tach jitter doesn't make the fan hunt.
+    pub fn set_rpm(&mut self, rpm: f64) {
+        let rpm = rpm.max(0.0);
+        self.rpm = Some(rpm);
+        let Some(target) = self.target_rpm else { return };
+
+        let error = (target - rpm) / self.max_rpm;
+        let previous = self.last_rpm_error.replace(error).unwrap_or(0.0);
+        if !self.at_target_rpm() {
+            let correction = self.rpm_kp * (error - previous) + self.rpm_ki * error;
+            self.speed = (self.speed + correction).clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn get_rpm(&self) -> Option<f64> {
+        self.rpm
+    }
+
+    /// Replace the temperature curve and follow it, if it has any points
+    pub fn set_curve(&mut self, mut curve: Vec<FanCurvePoint>) {
+        curve.sort_by(|a, b| a.temperature.total_cmp(&b.temperature));
+        self.follow_curve = !curve.is_empty();
+        self.curve = curve;
+        self.curve_temperature = None;
+    }
+
+    /// Whether `update_temperature` sets the speed
+    pub fn follows_curve(&self) -> bool {
+        self.follow_curve && self.target_rpm.is_none()
+    }
+
+    pub fn get_curve(&self) -> &[FanCurvePoint] {
+        &self.curve
+    }
+
+    /// Fan speed (percent) for a temperature, interpolating linearly between
+    /// curve points and holding the end values outside them
+    pub fn evaluate_curve(&self, temperature: f64) -> f64 {
+        let (first, last) = match (self.curve.first(), self.curve.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 100.0,
+        };
+
+        if temperature <= first.temperature {
+            return first.speed_pct;
+        }
+        if temperature >= last.temperature {
+            return last.speed_pct;
+        }
+
+        for pair in self.curve.windows(2) {
+            let (low, high) = (&pair[0], &pair[1]);
+            if temperature <= high.temperature {
+                let span = high.temperature - low.temperature;
+                if span <= 0.0 {
+                    return high.speed_pct;
+                }
+                let fraction = (temperature - low.temperature) / span;
+                return low.speed_pct + (high.speed_pct - low.speed_pct) * fraction;
+            }
+        }
+
+        last.speed_pct
+    }
+
+    /// Update the fan from a new hotend temperature reading, unless it has
+    /// no curve, was overridden or is holding a target RPM
+    ///
+    /// Rising temperatures are followed immediately; falling ones only once
+    /// they drop more than `hysteresis_deg`, so noise around a curve knee
+    /// doesn't make the fan hunt.
+    pub fn update_temperature(&mut self, temperature: f64) {
+        if !self.follows_curve() {
+            return;
+        }
+        let effective = match self.curve_temperature {
+            Some(previous) if temperature < previous && temperature > previous - self.hysteresis_deg => previous,
+            Some(previous) if temperature < previous => temperature + self.hysteresis_deg,
+            _ => temperature,
+        };
+        self.curve_temperature = Some(effective);
+        self.set_speed(self.evaluate_curve(effective) / 100.0);
+    }
+}
+
+impl Default for FanController {
+    fn default() -> Self {
+        Self::new(&FanConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp_config(hysteresis_deg: f64) -> FanConfig {
+        FanConfig {
+            curve: vec![
+                FanCurvePoint { temperature: 60.0, speed_pct: 100.0 },
+                FanCurvePoint { temperature: 50.0, speed_pct: 0.0 },
+            ],
+            hysteresis_deg,
+            ..FanConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_curve_interpolation() {
+        let fan = FanController::new(&ramp_config(0.0));
+        assert_eq!(fan.evaluate_curve(55.0), 50.0);
+        assert_eq!(fan.evaluate_curve(20.0), 0.0);
+        assert_eq!(fan.evaluate_curve(200.0), 100.0);
+
+        // Without a curve the fan keeps the speed it was given
+        let mut fan = FanController::default();
+        fan.set_speed(0.3);
+        fan.update_temperature(25.0);
+        assert_eq!(fan.get_speed(), 0.3);
+    }
+
+    #[test]
+    fn test_override_until_curve_set() {
+        let mut fan = FanController::new(&ramp_config(0.0));
+        fan.update_temperature(55.0);
+        assert_eq!(fan.get_speed(), 0.5);
+
+        fan.override_speed(1.0);
+        fan.update_temperature(50.0);
+        assert_eq!(fan.get_speed(), 1.0);
+
+        fan.set_curve(ramp_config(0.0).curve);
+        fan.update_temperature(50.0);
+        assert_eq!(fan.get_speed(), 0.0);
+    }
+
+    #[test]
+    fn test_hysteresis_prevents_toggling() {
+        let readings = [50.6, 49.9, 50.5, 49.8, 50.6, 49.9, 50.4];
+        let count_changes = |fan: &mut FanController| {
+            let mut changes = 0;
+            let mut last = fan.get_speed();
+            for &temp in &readings {
+                fan.update_temperature(temp);
+                if fan.get_speed() != last {
+                    changes += 1;
+                    last = fan.get_speed();
+                }
+            }
+            changes
+        };
+
+        // Without hysteresis the fan switches on and off with every reading
+        assert_eq!(count_changes(&mut FanController::new(&ramp_config(0.0))), readings.len());
+
+        // With hysteresis it spins up once and holds
+        let mut fan = FanController::new(&ramp_config(1.0));
+        assert_eq!(count_changes(&mut fan), 1);
+        assert!((fan.get_speed() - 0.06).abs() < 1e-9);
+
+        // A real drop still slows the fan
+        fan.update_temperature(45.0);
+        assert_eq!(fan.get_speed(), 0.0);
+    }
+
+    #[test]
+    fn test_rpm_control_converges() {
+        let config = FanConfig {
+            fan_control_mode: FanControlMode::Rpm,
+            max_rpm: 5000.0,
+            ..FanConfig::default()
+        };
+
+        // Clogged, underpowered and overvolted fans
+        for (actual_max_rpm, target) in [(3500.0, 600), (3500.0, 3000), (5000.0, 2500), (7000.0, 1500), (7000.0, 4500)] {
+            let mut fan = FanController::new(&config);
+            fan.set_target_rpm(target);
+
+            // Tachometer reporting RPM proportional to duty
+            let mut cycles = 0;
+            while cycles < 20 {
+                fan.set_rpm(fan.get_speed() * actual_max_rpm);
+                cycles += 1;
+                if fan.at_target_rpm() {
+                    break;
+                }
+            }
+            assert!(fan.at_target_rpm(), "{} RPM not reached in {} cycles", target, cycles);
+
+            // Once there, it holds
+            for _ in 0..20 {
+                fan.set_rpm(fan.get_speed() * actual_max_rpm);
+                assert!(fan.at_target_rpm());
+            }
+        }
+
+        // Setting a duty directly drops the target
+        let mut fan = FanController::new(&config);
+        fan.set_target_rpm(3000);
+        fan.set_speed(0.25);
+        fan.set_rpm(1800.0);
+        assert_eq!(fan.get_speed(), 0.25);
+        assert_eq!(fan.get_target_rpm(), None);
+    }
+}
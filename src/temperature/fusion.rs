@@ -0,0 +1,181 @@
+// src/temperature/fusion.rs - Several thermistors read as one heater zone
+use serde::{Deserialize, Serialize};
+use crate::config::ExtruderConfig;
+
+/// How the readings of a zone's thermistors combine into one temperature
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorFusionMode {
+    #[default]
+    Average,
+    /// The coolest reading, so a failing sensor can't hide overheating
+    MinValue,
+    MaxValue,
+    Median,
+}
+
+/// Last reading of one thermistor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermistorState {
+    /// Pin the thermistor is read on
+    pub sensor_pin: String,
+    /// `None` until the first reading (°C)
+    pub temperature: Option<f64>,
+}
+
+impl ThermistorState {
+    pub fn new(sensor_pin: &str) -> Self {
+        Self { sensor_pin: sensor_pin.to_string(), temperature: None }
+    }
+}
+
+/// Readings of a zone's thermistors that spread wider than allowed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SensorDivergence {
+    /// (°C), in sensor order
+    pub readings: Vec<f64>,
+    /// Hottest minus coolest reading (°C)
+    pub spread: f64,
+}
+
+/// Thermistors measuring the same heater zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermistorArray {
+    pub sensors: Vec<ThermistorState>,
+    pub fusion_mode: SensorFusionMode,
+    /// Spread between readings that counts as a sensor fault (°C)
+    divergence_threshold: f64,
+    /// Whether the readings are currently spread past the threshold
+    diverged: bool,
+}
+
+impl ThermistorArray {
+    pub fn new(sensor_pins: &[&str], fusion_mode: SensorFusionMode, divergence_threshold: f64) -> Self {
+        Self {
+            sensors: sensor_pins.iter().map(|pin| ThermistorState::new(pin)).collect(),
+            fusion_mode,
+            divergence_threshold,
+            diverged: false,
+        }
+    }
+
+    /// The extruder's own thermistor followed by its `extra_sensors`, `None`
+    /// if it has no extra sensors
+    pub fn from_extruder_config(config: &ExtruderConfig) -> Option<Self> {
+        if config.extra_sensors.is_empty() {
+            return None;
+        }
+        let pins: Vec<&str> = std::iter::once("extruder")
+            .chain(config.extra_sensors.iter().map(|sensor| sensor.sensor_pin.as_str()))
+            .collect();
+        Some(Self::new(&pins, config.sensor_fusion, config.sensor_divergence_threshold_deg))
+    }
+
+    /// Record one reading per sensor, in sensor order
+    ///
+    /// Returns the readings the first time they spread wider than the
+    /// divergence threshold, after switching to `MinValue` so a failing
+    /// sensor can't make the heater overshoot.
+    pub fn update(&mut self, readings: &[f64]) -> Option<SensorDivergence> {
+        for (sensor, &reading) in self.sensors.iter_mut().zip(readings) {
+            sensor.temperature = Some(reading);
+        }
+        let readings = self.readings();
+        let (min, max) = readings
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &t| (min.min(t), max.max(t)));
+        let spread = max - min;
+        if readings.len() < 2 || spread <= self.divergence_threshold {
+            self.diverged = false;
+            return None;
+        }
+        if self.diverged {
+            return None;
+        }
+        self.diverged = true;
+        if self.fusion_mode != SensorFusionMode::MinValue {
+            tracing::warn!(
+                "Thermistors {:?} diverged by {:.1}°C, using the lowest reading",
+                self.sensors.iter().map(|sensor| &sensor.sensor_pin).collect::<Vec<_>>(),
+                spread
+            );
+            self.fusion_mode = SensorFusionMode::MinValue;
+        }
+        Some(SensorDivergence { readings, spread })
+    }
+
+    /// Whether the readings are spread past the divergence threshold
+    pub fn is_diverged(&self) -> bool {
+        self.diverged
+    }
+
+    /// Temperature of the zone by `fusion_mode`, `None` before any reading (°C)
+    pub fn fused_temperature(&self) -> Option<f64> {
+        let mut readings = self.readings();
+        if readings.is_empty() {
+            return None;
+        }
+        let fused = match self.fusion_mode {
+            SensorFusionMode::Average => readings.iter().sum::<f64>() / readings.len() as f64,
+            SensorFusionMode::MinValue => readings.iter().copied().fold(f64::INFINITY, f64::min),
+            SensorFusionMode::MaxValue => readings.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            SensorFusionMode::Median => {
+                readings.sort_by(f64::total_cmp);
+                let middle = readings.len() / 2;
+                if readings.len().is_multiple_of(2) {
+                    (readings[middle - 1] + readings[middle]) / 2.0
+                } else {
+                    readings[middle]
+                }
+            }
+        };
+        Some(fused)
+    }
+
+    fn readings(&self) -> Vec<f64> {
+        self.sensors.iter().filter_map(|sensor| sensor.temperature).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fused(mode: SensorFusionMode, readings: &[f64]) -> f64 {
+        let mut array = ThermistorArray::new(&["PA1", "PA2", "PA3", "PA4"][..readings.len()], mode, 100.0);
+        assert_eq!(array.update(readings), None);
+        array.fused_temperature().unwrap()
+    }
+
+    #[test]
+    fn test_fusion_modes() {
+        let readings = [200.0, 204.0, 203.0, 201.0];
+        assert_eq!(fused(SensorFusionMode::Average, &readings), 202.0);
+        assert_eq!(fused(SensorFusionMode::MinValue, &readings), 200.0);
+        assert_eq!(fused(SensorFusionMode::MaxValue, &readings), 204.0);
+        assert_eq!(fused(SensorFusionMode::Median, &readings), 202.0);
+        assert_eq!(fused(SensorFusionMode::Median, &readings[..3]), 203.0);
+
+        let array = ThermistorArray::new(&["PA1", "PA2"], SensorFusionMode::Average, 5.0);
+        assert_eq!(array.fused_temperature(), None);
+    }
+
+    #[test]
+    fn test_divergence_switches_to_min_value() {
+        let mut array = ThermistorArray::new(&["PA1", "PA2"], SensorFusionMode::Average, 5.0);
+        assert_eq!(array.update(&[200.0, 204.0]), None);
+        assert_eq!(array.fused_temperature(), Some(202.0));
+
+        let divergence = array.update(&[200.0, 210.0]).unwrap();
+        assert_eq!(divergence, SensorDivergence { readings: vec![200.0, 210.0], spread: 10.0 });
+        assert_eq!(array.fusion_mode, SensorFusionMode::MinValue);
+        assert_eq!(array.fused_temperature(), Some(200.0));
+
+        // Reported once per divergence, and MinValue stays after the sensors agree again
+        assert_eq!(array.update(&[201.0, 212.0]), None);
+        assert_eq!(array.update(&[201.0, 202.0]), None);
+        assert!(!array.is_diverged());
+        assert_eq!(array.fusion_mode, SensorFusionMode::MinValue);
+        assert!(array.update(&[190.0, 202.0]).is_some());
+    }
+}
@@ -0,0 +1,335 @@
+// src/temperature/heater.rs - Heater zones with thermal runaway protection
+use std::time::Duration;
+use super::controller::{PidParameters, TemperatureController};
+use crate::config::ChamberConfig;
+
+/// How far below its target a heater may be and still count as heated (°C)
+const HEATED_TOLERANCE: f64 = 2.0;
+
+/// How long waiting for a heater lasts unless configured otherwise
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Thermal runaway detection limits
+#[derive(Debug, Clone)]
+pub struct ThermalProtection {
+    /// Band below target within which the heater counts as holding (°C)
+    pub hysteresis: f64,
+
+    /// Accumulated shortfall below the band that triggers a fault (°C·s)
+    pub max_error: f64,
+
+    /// Minimum rise expected within `check_gain_time` while heating (°C)
+    pub heating_gain: f64,
+
+    /// Time allowed to rise by `heating_gain` while heating (s)
+    pub check_gain_time: f64,
+}
+
+impl Default for ThermalProtection {
+    fn default() -> Self {
+        Self {
+            hysteresis: 5.0,
+            max_error: 120.0,
+            heating_gain: 2.0,
+            check_gain_time: 20.0,
+        }
+    }
+}
+
+/// Watches a heater for readings that stop following its target
+#[derive(Debug, Clone)]
+struct RunawayMonitor {
+    protection: ThermalProtection,
+    error: f64,
+    approaching_target: bool,
+    goal_temperature: f64,
+    goal_time_remaining: f64,
+    last_target: f64,
+}
+
+impl RunawayMonitor {
+    fn new(protection: ThermalProtection) -> Self {
+        Self {
+            protection,
+            error: 0.0,
+            approaching_target: false,
+            goal_temperature: 0.0,
+            goal_time_remaining: 0.0,
+            last_target: 0.0,
+        }
+    }
+
+    /// Returns an error description once the heater is considered runaway
+    fn check(&mut self, temperature: f64, target: f64, dt: f64) -> Result<(), String> {
+        let p = &self.protection;
+
+        if target <= 0.0 || temperature >= target - p.hysteresis {
+            // Near target (or off): nothing to check
+            self.approaching_target = false;
+            if temperature <= self.last_target {
+                self.error = 0.0;
+            }
+            self.last_target = target;
+            return Ok(());
+        }
+
+        self.error += ((target - p.hysteresis) - temperature) * dt;
+
+        if !self.approaching_target {
+            if target != self.last_target {
+                // New target: expect steady progress towards it
+                self.approaching_target = true;
+                self.goal_temperature = temperature + p.heating_gain;
+                self.goal_time_remaining = p.check_gain_time;
+            } else if self.error >= p.max_error {
+                return Err(format!(
+                    "temperature {:.1}°C fell away from target {:.1}°C",
+                    temperature, target
+                ));
+            }
+        } else if temperature >= self.goal_temperature {
+            // Still heating at the expected rate
+            self.error = 0.0;
+            self.goal_temperature = temperature + p.heating_gain;
+            self.goal_time_remaining = p.check_gain_time;
+        } else {
+            self.goal_time_remaining -= dt;
+            if self.goal_time_remaining <= 0.0 {
+                return Err(format!(
+                    "not heating: gained less than {:.1}°C in {:.0}s",
+                    p.heating_gain, p.check_gain_time
+                ));
+            }
+        }
+
+        self.last_target = target;
+        Ok(())
+    }
+}
+
+/// A heated zone: sensor reading, PID loop, power limit and safety checks
+#[derive(Debug, Clone)]
+pub struct Heater {
+    name: String,
+    controller: TemperatureController,
+    monitor: RunawayMonitor,
+    min_temp: f64,
+    max_temp: f64,
+    max_power: f64,
+    temperature: f64,
+    output: f64,
+    fault: Option<String>,
+    runaway_count: u64,
+    wait_timeout: Duration,
+}
+
+impl Heater {
+    pub fn new(
+        name: &str,
+        params: PidParameters,
+        protection: ThermalProtection,
+        min_temp: f64,
+        max_temp: f64,
+        max_power: f64,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            controller: TemperatureController::new(params),
+            monitor: RunawayMonitor::new(protection),
+            min_temp,
+            max_temp,
+            max_power: max_power.clamp(0.0, 1.0),
+            temperature: 0.0,
+            output: 0.0,
+            fault: None,
+            runaway_count: 0,
+            wait_timeout: DEFAULT_WAIT_TIMEOUT,
+        }
+    }
+
+    pub fn from_chamber_config(config: &ChamberConfig) -> Self {
+        let params = PidParameters {
+            kp: config.pid_kp,
+            ki: config.pid_ki,
+            kd: config.pid_kd,
+            integral_min: 0.0,
+            integral_max: config.max_power / config.pid_ki.max(f64::EPSILON),
+            derivative_filter_cutoff: 0.1,
+            output_min: 0.0,
+            output_max: config.max_power,
+        };
+        // Chambers heat slowly; allow more time per degree gained
+        let protection = ThermalProtection {
+            check_gain_time: 120.0,
+            ..ThermalProtection::default()
+        };
+        Self {
+            wait_timeout: Duration::from_secs_f64(config.wait_timeout_secs.max(0.0)),
+            ..Self::new("chamber", params, protection, config.min_temp, config.max_temp, config.max_power)
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the target temperature (0 turns the heater off)
+    pub fn set_target(&mut self, target: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if target != 0.0 && (target < self.min_temp || target > self.max_temp) {
+            return Err(format!(
+                "{} target {:.1}°C outside of range {:.1}-{:.1}°C",
+                self.name, target, self.min_temp, self.max_temp
+            ).into());
+        }
+        self.controller.set_target(target);
+        Ok(())
+    }
+
+    pub fn get_target(&self) -> f64 {
+        self.controller.get_target()
+    }
+
+    /// Last measured temperature (°C)
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Current heater duty (0.0 - max_power)
+    pub fn get_output(&self) -> f64 {
+        self.output
+    }
+
+    pub fn get_fault(&self) -> Option<&str> {
+        self.fault.as_deref()
+    }
+
+    /// Whether the last reading is at the target, or the heater is off
+    pub fn is_heated(&self) -> bool {
+        self.temperature >= self.get_target() - HEATED_TOLERANCE
+    }
+
+    /// How long to wait for the heater to reach its target before giving up
+    pub fn get_wait_timeout(&self) -> Duration {
+        self.wait_timeout
+    }
+
+    /// Number of faults raised since the heater was created
+    pub fn get_runaway_count(&self) -> u64 {
+        self.runaway_count
+    }
+
+    /// Feed a new temperature reading taken `dt` seconds after the last and
+    /// compute the heater duty
+    ///
+    /// Any fault latches the heater off until `clear_fault` is called.
+    pub fn update(&mut self, temperature: f64, dt: f64) -> Result<f64, Box<dyn std::error::Error>> {
+        self.temperature = temperature;
+
+        if self.fault.is_none() {
+            if temperature > self.max_temp || temperature < self.min_temp {
+                self.fault = Some(format!(
+                    "temperature {:.1}°C outside of range {:.1}-{:.1}°C",
+                    temperature, self.min_temp, self.max_temp
+                ));
+            } else if let Err(reason) = self.monitor.check(temperature, self.get_target(), dt) {
+                self.fault = Some(reason);
+            }
+            if self.fault.is_some() {
+                self.runaway_count += 1;
+            }
+        }
+
+        if let Some(fault) = &self.fault {
+            self.output = 0.0;
+            return Err(format!("Thermal runaway on {}: {}", self.name, fault).into());
+        }
+
+        self.output = self.controller.calculate_output(temperature, dt).min(self.max_power);
+        Ok(self.output)
+    }
+
+    /// Re-arm the heater after a fault, with the target cleared
+    pub fn clear_fault(&mut self) {
+        self.fault = None;
+        self.controller.set_target(0.0);
+        self.controller.reset();
+        self.monitor = RunawayMonitor::new(self.monitor.protection.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chamber(max_power: f64) -> Heater {
+        Heater::from_chamber_config(&ChamberConfig {
+            heater_pin: "PB10".to_string(),
+            sensor_pin: "PA5".to_string(),
+            sensor_type: "EPCOS 100K B57560G104F".to_string(),
+            min_temp: 0.0,
+            max_temp: 80.0,
+            max_power,
+            pid_kp: 0.3,
+            pid_ki: 0.002,
+            pid_kd: 0.0,
+            pullup_resistor: 4700.0,
+            wait_timeout_secs: 1800.0,
+        })
+    }
+
+    #[test]
+    fn test_heater_on_below_target_and_limited() {
+        let mut heater = chamber(0.6);
+        assert_eq!(heater.update(25.0, 1.0).unwrap(), 0.0);
+
+        heater.set_target(50.0).unwrap();
+        let output = heater.update(25.0, 1.0).unwrap();
+        assert!(output > 0.0);
+        assert!(output <= 0.6);
+
+        assert!(heater.set_target(120.0).is_err());
+    }
+
+    #[test]
+    fn test_runaway_on_unexpected_drop() {
+        let mut heater = chamber(1.0);
+        heater.set_target(50.0).unwrap();
+
+        // Warm up steadily and hold at target
+        let mut temp = 25.0;
+        while temp < 50.0 {
+            heater.update(temp, 1.0).unwrap();
+            temp += 0.5;
+        }
+        for _ in 0..60 {
+            heater.update(50.0, 1.0).unwrap();
+        }
+
+        // Door opens: temperature collapses and stays low
+        let mut result = Ok(0.0);
+        for _ in 0..60 {
+            result = heater.update(30.0, 1.0);
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(result.is_err());
+        assert!(heater.get_fault().is_some());
+        assert_eq!(heater.get_output(), 0.0);
+
+        // Stays latched off
+        assert!(heater.update(50.0, 1.0).is_err());
+        heater.clear_fault();
+        assert_eq!(heater.update(30.0, 1.0).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_runaway_when_not_heating() {
+        let mut heater = chamber(1.0);
+        heater.set_target(60.0).unwrap();
+
+        // Heater element disconnected: temperature never rises
+        let failed = (0..200).any(|_| heater.update(25.0, 1.0).is_err());
+        assert!(failed);
+    }
+}
@@ -0,0 +1,164 @@
+// src/temperature/history.rs - Recent temperatures kept for graphing
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use crate::printer::PrinterState;
+
+/// Columns of `TemperatureHistoryBuffer::to_csv`
+const CSV_HEADER: &str = "timestamp,hotend_temp,hotend_target,bed_temp,bed_target";
+
+/// Temperatures and targets at one moment (°C)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TemperatureSample {
+    /// Unix milliseconds
+    pub timestamp: u64,
+    pub hotend_temp: f32,
+    pub hotend_target: f32,
+    pub bed_temp: f32,
+    pub bed_target: f32,
+}
+
+impl TemperatureSample {
+    /// The active hotend and the bed as `state` last saw them
+    pub fn from_state(state: &PrinterState, timestamp: u64) -> Self {
+        let hotend_target = state.tools.get(state.active_tool).map_or(0.0, |tool| tool.target);
+        Self {
+            timestamp,
+            hotend_temp: state.temperature as f32,
+            hotend_target: hotend_target as f32,
+            bed_temp: state.bed_temperature as f32,
+            bed_target: state.bed_target as f32,
+        }
+    }
+}
+
+/// Samples from the last `retention`, oldest first
+///
+/// Older samples are dropped as new ones are recorded.
+#[derive(Debug, Clone)]
+pub struct TemperatureHistoryBuffer {
+    samples: VecDeque<TemperatureSample>,
+    retention: Duration,
+}
+
+impl TemperatureHistoryBuffer {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            retention,
+        }
+    }
+
+    pub fn record(&mut self, sample: TemperatureSample) {
+        let oldest = sample.timestamp.saturating_sub(self.retention.as_millis() as u64);
+        while self.samples.front().is_some_and(|front| front.timestamp <= oldest) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples taken in the `duration` before `now` (Unix milliseconds)
+    pub fn recent(&self, duration: Duration, now: u64) -> Vec<TemperatureSample> {
+        let since = now.saturating_sub(duration.as_millis() as u64);
+        let start = self.samples.partition_point(|sample| sample.timestamp <= since);
+        self.samples.range(start..).copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    /// `samples` as CSV with a header row
+    pub fn to_csv(samples: &[TemperatureSample]) -> String {
+        let mut csv = format!("{}\n", CSV_HEADER);
+        for sample in samples {
+            let _ = writeln!(
+                csv,
+                "{},{:.2},{:.2},{:.2},{:.2}",
+                sample.timestamp, sample.hotend_temp, sample.hotend_target, sample.bed_temp, sample.bed_target
+            );
+        }
+        csv
+    }
+}
+
+/// Current time in Unix milliseconds
+pub fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Samples the printer's temperatures into a history buffer at a fixed rate
+pub struct TemperatureHistoryRecorder {
+    history: Arc<Mutex<TemperatureHistoryBuffer>>,
+    state: Arc<RwLock<PrinterState>>,
+    interval: Duration,
+}
+
+impl TemperatureHistoryRecorder {
+    pub fn new(
+        history: Arc<Mutex<TemperatureHistoryBuffer>>,
+        state: Arc<RwLock<PrinterState>>,
+        interval: Duration,
+    ) -> Self {
+        Self { history, state, interval }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let sample = TemperatureSample::from_state(&*self.state.read().await, unix_millis());
+                self.history.lock().unwrap().record(sample);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: u64) -> TemperatureSample {
+        TemperatureSample {
+            timestamp,
+            hotend_temp: 200.0,
+            hotend_target: 210.0,
+            bed_temp: 60.0,
+            bed_target: 60.0,
+        }
+    }
+
+    #[test]
+    fn test_retention_and_recent() {
+        let mut history = TemperatureHistoryBuffer::new(Duration::from_secs(45));
+        for second in 1..=60 {
+            history.record(sample(second * 1000));
+        }
+        // Seconds 16-60
+        assert_eq!(history.len(), 45);
+
+        let recent = history.recent(Duration::from_secs(30), 60_000);
+        assert_eq!(recent.len(), 30);
+        assert_eq!(recent[0].timestamp, 31_000);
+        assert_eq!(recent[29].timestamp, 60_000);
+        assert_eq!(history.recent(Duration::from_secs(3600), 60_000).len(), 45);
+        assert!(history.recent(Duration::from_secs(30), 120_000).is_empty());
+
+        let csv = TemperatureHistoryBuffer::to_csv(&recent[..1]);
+        assert_eq!(csv, "timestamp,hotend_temp,hotend_target,bed_temp,bed_target\n31000,200.00,210.00,60.00,60.00\n");
+    }
+}
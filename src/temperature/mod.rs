@@ -0,0 +1,18 @@
+// src/temperature/mod.rs - Temperature sensing and heater control
+pub mod control;
+pub mod controller;
+pub mod fan;
+pub mod fusion;
+pub mod heater;
+pub mod history;
+pub mod thermistor;
+pub mod tool;
+
+pub use control::ThermalControlLoop;
+pub use controller::{PidParameters, TemperatureController};
+pub use fan::FanController;
+pub use fusion::{SensorDivergence, SensorFusionMode, ThermistorArray, ThermistorState};
+pub use heater::{Heater, ThermalProtection};
+pub use history::{TemperatureHistoryBuffer, TemperatureHistoryRecorder, TemperatureSample};
+pub use thermistor::{SteinhartHartCoefficients, ThermistorModel};
+pub use tool::ToolHeater;
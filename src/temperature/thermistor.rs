@@ -0,0 +1,142 @@
+// src/temperature/thermistor.rs - NTC thermistor resistance to temperature conversion
+
+/// Full-scale reading of the MCU's 12-bit ADC
+pub const ADC_MAX: u16 = 4095;
+
+const KELVIN_OFFSET: f64 = 273.15;
+
+/// Steinhart-Hart equation coefficients: 1/T = A + B·ln(R) + C·ln(R)³
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteinhartHartCoefficients {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl SteinhartHartCoefficients {
+    /// Solve the coefficients from three (temperature °C, resistance Ω) points
+    pub fn from_points(points: [(f64, f64); 3]) -> Self {
+        let [(t1, r1), (t2, r2), (t3, r3)] = points;
+        let (l1, l2, l3) = (r1.ln(), r2.ln(), r3.ln());
+        let (y1, y2, y3) = (
+            1.0 / (t1 + KELVIN_OFFSET),
+            1.0 / (t2 + KELVIN_OFFSET),
+            1.0 / (t3 + KELVIN_OFFSET),
+        );
+
+        let g2 = (y2 - y1) / (l2 - l1);
+        let g3 = (y3 - y1) / (l3 - l1);
+        let c = (g3 - g2) / (l3 - l2) / (l1 + l2 + l3);
+        let b = g2 - c * (l1 * l1 + l1 * l2 + l2 * l2);
+        let a = y1 - (b + l1 * l1 * c) * l1;
+
+        Self { a, b, c }
+    }
+}
+
+/// Known thermistors, defined by three points on their resistance curve
+const SENSOR_TABLE: &[(&str, [(f64, f64); 3])] = &[
+    ("EPCOS 100K B57560G104F", [(25.0, 100000.0), (150.0, 1641.9), (250.0, 226.15)]),
+    ("NTC 100K B57560G104F", [(25.0, 100000.0), (150.0, 1641.9), (250.0, 226.15)]),
+    ("ATC Semitec 104GT-2", [(20.0, 126800.0), (150.0, 1360.0), (300.0, 80.65)]),
+];
+
+/// Resistance to temperature model for an NTC thermistor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermistorModel {
+    coefficients: SteinhartHartCoefficients,
+}
+
+impl ThermistorModel {
+    pub fn from_steinhart_hart(coefficients: SteinhartHartCoefficients) -> Self {
+        Self { coefficients }
+    }
+
+    /// Look up a thermistor by its config `sensor_type` name (case-insensitive)
+    pub fn from_sensor_type(sensor_type: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        SENSOR_TABLE
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(sensor_type.trim()))
+            .map(|(_, points)| Self::from_steinhart_hart(SteinhartHartCoefficients::from_points(*points)))
+            .ok_or_else(|| format!("Unknown thermistor sensor type: {}", sensor_type).into())
+    }
+
+    pub fn coefficients(&self) -> SteinhartHartCoefficients {
+        self.coefficients
+    }
+
+    /// Convert thermistor resistance (Ω) to temperature (°C)
+    pub fn resistance_to_celsius(&self, ohms: f64) -> f64 {
+        let SteinhartHartCoefficients { a, b, c } = self.coefficients;
+        let ln_r = ohms.ln();
+        1.0 / (a + b * ln_r + c * ln_r * ln_r * ln_r) - KELVIN_OFFSET
+    }
+
+    /// Convert a raw ADC reading to temperature (°C)
+    ///
+    /// The thermistor sits between the ADC pin and ground with a pullup to
+    /// `ref_voltage`. A shorted sensor reads as infinitely hot so that
+    /// over-temperature protection trips.
+    pub fn adc_counts_to_celsius(&self, adc: u16, pullup_ohms: f64, ref_voltage: f64) -> f64 {
+        if adc == 0 {
+            return f64::INFINITY;
+        }
+        if adc >= ADC_MAX {
+            return -KELVIN_OFFSET;
+        }
+
+        let voltage = adc as f64 / ADC_MAX as f64 * ref_voltage;
+        let ohms = pullup_ohms * voltage / (ref_voltage - voltage);
+        self.resistance_to_celsius(ohms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ADC reading for a thermistor resistance with a 4.7kΩ pullup
+    fn adc_for_resistance(ohms: f64) -> u16 {
+        (ohms / (ohms + 4700.0) * ADC_MAX as f64).round() as u16
+    }
+
+    #[test]
+    fn test_epcos_adc_to_celsius() {
+        let model = ThermistorModel::from_sensor_type("EPCOS 100K B57560G104F").unwrap();
+
+        // Datasheet resistances (R/T curve 8016), read back through a quantized ADC
+        for (expected, ohms) in [(25.0, 100000.0), (150.0, 1641.9), (250.0, 226.15)] {
+            let temp = model.adc_counts_to_celsius(adc_for_resistance(ohms), 4700.0, 3.3);
+            assert!((temp - expected).abs() < 0.5, "{}Ω: expected {}°C, got {:.2}°C", ohms, expected, temp);
+        }
+
+        // A datasheet point the coefficients were not fitted to; the
+        // three-point fit drifts a little between its anchors
+        let temp = model.adc_counts_to_celsius(adc_for_resistance(6700.0), 4700.0, 3.3);
+        assert!((temp - 100.0).abs() < 2.0, "6700Ω: expected 100°C, got {:.2}°C", temp);
+
+        // Hotter always reads lower resistance across the printable range
+        let temps: Vec<f64> = (1..ADC_MAX).step_by(64).map(|adc| model.adc_counts_to_celsius(adc, 4700.0, 3.3)).collect();
+        assert!(temps.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn test_sensor_table_lookup() {
+        let semitec = ThermistorModel::from_sensor_type("atc semitec 104gt-2").unwrap();
+        assert!((semitec.resistance_to_celsius(126800.0) - 20.0).abs() < 1e-6);
+        assert!((semitec.resistance_to_celsius(80.65) - 300.0).abs() < 1e-6);
+
+        assert_eq!(
+            ThermistorModel::from_sensor_type("NTC 100K B57560G104F").unwrap(),
+            ThermistorModel::from_sensor_type("EPCOS 100K B57560G104F").unwrap(),
+        );
+        assert!(ThermistorModel::from_sensor_type("PT1000").is_err());
+    }
+
+    #[test]
+    fn test_adc_fault_readings() {
+        let model = ThermistorModel::from_sensor_type("EPCOS 100K B57560G104F").unwrap();
+        assert_eq!(model.adc_counts_to_celsius(0, 4700.0, 3.3), f64::INFINITY);
+        assert!(model.adc_counts_to_celsius(ADC_MAX, 4700.0, 3.3) < -200.0);
+    }
+}
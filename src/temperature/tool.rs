@@ -0,0 +1,128 @@
+// src/temperature/tool.rs - Hotend targets per tool on multi-extruder printers
+use std::time::Duration;
+use crate::config::ExtruderConfig;
+use super::fusion::{SensorDivergence, ThermistorArray};
+
+/// How far below its target a hotend may be and still count as heated (°C)
+const HEATED_TOLERANCE: f64 = 2.0;
+
+/// Hotend of one tool (T0, T1, ...)
+///
+/// No hotend sensor is read yet, so like `PrinterState::temperature` the
+/// reading follows the target as soon as it is set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolHeater {
+    /// Temperature the tool prints at (°C)
+    pub active_temp: f64,
+    /// What the heater is driving towards; the standby temperature while idle (°C)
+    pub target: f64,
+    /// Last reading (°C)
+    pub temperature: f64,
+    pub standby_temp: Option<f64>,
+    pub standby_delay: Duration,
+    /// The hotend's thermistors when it has `extra_sensors`
+    pub sensors: Option<ThermistorArray>,
+}
+
+impl ToolHeater {
+    pub fn from_config(config: &ExtruderConfig) -> Self {
+        Self {
+            standby_temp: config.standby_temp,
+            standby_delay: Duration::from_secs_f64(config.standby_delay_secs.max(0.0)),
+            sensors: ThermistorArray::from_extruder_config(config),
+            ..Self::default()
+        }
+    }
+
+    /// Set the printing temperature and heat to it
+    pub fn set_active_temp(&mut self, temp: f64) {
+        self.active_temp = temp;
+        self.set_target(temp);
+    }
+
+    pub fn set_target(&mut self, target: f64) {
+        self.target = target;
+        self.temperature = target;
+    }
+
+    /// Drop to the standby temperature, if that is cooler than the target
+    pub fn enter_standby(&mut self) -> bool {
+        match self.standby_temp {
+            Some(standby) if standby < self.target => {
+                self.set_target(standby);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the tool is below its printing temperature
+    pub fn is_on_standby(&self) -> bool {
+        self.target < self.active_temp
+    }
+
+    /// Go back to the printing temperature
+    pub fn restore(&mut self) {
+        self.set_target(self.active_temp);
+    }
+
+    /// Take one reading from each thermistor, the hotend's own first, and
+    /// use their fused temperature
+    pub fn report_readings(&mut self, readings: &[f64]) -> Option<SensorDivergence> {
+        let Some(sensors) = &mut self.sensors else {
+            if let Some(&reading) = readings.first() {
+                self.temperature = reading;
+            }
+            return None;
+        };
+        let divergence = sensors.update(readings);
+        if let Some(temperature) = sensors.fused_temperature() {
+            self.temperature = temperature;
+        }
+        divergence
+    }
+
+    pub fn is_heated(&self) -> bool {
+        self.temperature >= self.target - HEATED_TOLERANCE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standby_never_heats() {
+        let mut heater = ToolHeater {
+            standby_temp: Some(150.0),
+            ..ToolHeater::default()
+        };
+        assert!(!heater.enter_standby());
+        assert_eq!(heater.target, 0.0);
+
+        heater.set_active_temp(210.0);
+        assert!(heater.enter_standby());
+        assert_eq!((heater.target, heater.active_temp), (150.0, 210.0));
+        assert!(heater.is_on_standby());
+
+        heater.restore();
+        assert_eq!(heater.target, 210.0);
+        assert!(!heater.is_on_standby() && heater.is_heated());
+    }
+
+    #[test]
+    fn test_extra_sensors_from_config() {
+        let toml = include_str!("../printer.toml").to_string()
+            + "\n[[extruder.extra_sensors]]\nsensor_pin = \"PA6\"\n";
+        let config: crate::config::Config = toml::from_str(&toml).unwrap();
+        assert!(config.validate().is_ok());
+        let mut heater = ToolHeater::from_config(&config.extruder);
+        let sensors = heater.sensors.as_ref().unwrap();
+        assert_eq!(sensors.sensors[1].sensor_pin, "PA6");
+
+        assert_eq!(heater.report_readings(&[210.0, 212.0]), None);
+        assert_eq!(heater.temperature, 211.0);
+        assert!(heater.report_readings(&[200.0, 210.0]).is_some());
+        assert_eq!(heater.temperature, 200.0);
+    }
+}
@@ -0,0 +1,2735 @@
+// src/web/api.rs - HTTP routes
+use std::net::SocketAddr;
+use std::convert::Infallible;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::{Mutex, RwLock, broadcast, watch};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::IntervalStream;
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+use crate::config::{Config, ConfigError, ConfigErrorSeverity, ConfigManager};
+use crate::file::{thumbnail_content_type, FileManager};
+use crate::gcode::GCodeProcessor;
+use crate::hardware::{FlashMethod, HardwareManager};
+use crate::motion::{MotionMode, MotionPlannerStats, ShaperPreset};
+use crate::motion::adaptive_planner::ErrorPredictor;
+use crate::motion::kinematics::KinematicsType;
+use crate::print_job::{self, PrintJob, PrintJobValidator, Severity};
+use crate::printer::{PrinterEvent, PrinterEventLog, PrinterState};
+use crate::system_info::SystemInfo;
+use crate::temperature::TemperatureHistoryBuffer;
+use crate::temperature::history::unix_millis;
+use super::auth::{AuthPermission, AuthRejection, Claims, JwtAuth, TokenPair, require_permission};
+use super::metrics::PrinterMetrics;
+use super::octoprint;
+use super::rate_limiter::{RateLimited, RateLimits};
+
+/// Fastest rate `GET /api/position/stream` sends at
+pub const MAX_POSITION_STREAM_HZ: u32 = 200;
+
+/// Slowest temperature history sampling allowed (one sample per hour)
+const MIN_TEMPERATURE_HISTORY_HZ: f64 = 1.0 / 3600.0;
+
+/// Shared handles the API routes read from
+#[derive(Clone)]
+pub struct ApiContext {
+    pub state: Arc<RwLock<PrinterState>>,
+    pub planner_stats: watch::Receiver<MotionPlannerStats>,
+    /// Interpolated toolhead position [X, Y, Z, E]
+    pub position: watch::Receiver<[f64; 4]>,
+    pub hardware: HardwareManager,
+    /// Runs G-code sent by clients
+    pub gcode: GCodeProcessor,
+    /// Configuration to use on the next start; `PUT /api/config` replaces it
+    pub config: Arc<RwLock<Config>>,
+    /// Named profiles that can replace `config`
+    pub profiles: Arc<RwLock<ConfigManager>>,
+    pub metrics: Option<Arc<PrinterMetrics>>,
+    /// `None` when no users are configured
+    pub auth: Option<Arc<JwtAuth>>,
+    pub rate_limits: Arc<RateLimits>,
+    pub api_key: Option<String>,
+    pub upload_dir: PathBuf,
+    /// Reads uploaded files, caching their thumbnails
+    pub files: Arc<FileManager>,
+    /// Recent temperatures, filled by `TemperatureHistoryRecorder`
+    pub temperature_history: Arc<std::sync::Mutex<TemperatureHistoryBuffer>>,
+    /// Seconds between temperature history samples
+    pub temperature_history_interval: Duration,
+    /// Printer event bus, e.g. for firmware flash progress
+    pub events: broadcast::Sender<PrinterEvent>,
+    /// Where `GET /api/events/log` reads from; `None` when events aren't logged
+    pub event_log: Option<Arc<PrinterEventLog>>,
+    /// Position updates per second on the SSE stream
+    pub position_stream_hz: u32,
+    /// Motion error predictor, restored from `advanced.predictor_model_path`
+    pub error_predictor: Arc<std::sync::Mutex<ErrorPredictor>>,
+}
+
+impl ApiContext {
+    pub fn new(
+        config: &Config,
+        state: Arc<RwLock<PrinterState>>,
+        planner_stats: watch::Receiver<MotionPlannerStats>,
+        position: watch::Receiver<[f64; 4]>,
+        hardware: HardwareManager,
+        gcode: GCodeProcessor,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let web = &config.web;
+        let metrics = if web.prometheus_enabled {
+            Some(Arc::new(PrinterMetrics::new()?))
+        } else {
+            None
+        };
+        Ok(Self {
+            state,
+            planner_stats,
+            position,
+            hardware,
+            gcode,
+            config: Arc::new(RwLock::new(config.clone())),
+            profiles: Arc::new(RwLock::new(ConfigManager::new(config.clone()))),
+            metrics,
+            auth: JwtAuth::from_config(web),
+            rate_limits: Arc::new(RateLimits::from_config(web)),
+            api_key: web.api_key.clone(),
+            upload_dir: PathBuf::from(&web.upload_dir),
+            files: Arc::new(FileManager::new()),
+            temperature_history: Arc::new(std::sync::Mutex::new(TemperatureHistoryBuffer::new(
+                Duration::from_secs(web.history_retention_secs),
+            ))),
+            temperature_history_interval: Duration::from_secs_f64(1.0 / web.temperature_history_hz.max(MIN_TEMPERATURE_HISTORY_HZ)),
+            events: broadcast::channel(16).0,
+            event_log: None,
+            position_stream_hz: web.position_stream_hz.clamp(1, MAX_POSITION_STREAM_HZ),
+            error_predictor: Arc::new(std::sync::Mutex::new(ErrorPredictor::load_or_new(
+                config.advanced.predictor_model_path.as_deref(),
+            ))),
+        })
+    }
+
+    /// Share the printer's configuration profiles
+    pub fn with_config_manager(mut self, profiles: Arc<RwLock<ConfigManager>>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Report events on the printer's event bus
+    pub fn with_event_sender(mut self, events: broadcast::Sender<PrinterEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Serve queries against the printer's event log
+    pub fn with_event_log(mut self, event_log: Arc<PrinterEventLog>) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Filter passing on the caller's claims if their role is at least `required`
+    fn require(&self, required: AuthPermission) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone + use<> {
+        require_permission(self.auth.clone(), required)
+    }
+
+    /// Charge each request to its user's bucket, or its IP's when it has
+    /// no valid token
+    fn rate_limited(&self) -> impl Filter<Extract = (), Error = Rejection> + Clone + use<> {
+        let auth = self.auth.clone();
+        let limits = self.rate_limits.clone();
+        warp::addr::remote()
+            .and(warp::header::optional::<String>("x-forwarded-for"))
+            .and(warp::header::optional::<String>("authorization"))
+            .and_then(move |remote: Option<SocketAddr>, forwarded: Option<String>, authorization: Option<String>| {
+                let auth = auth.clone();
+                let limits = limits.clone();
+                async move {
+                    let user = auth.zip(authorization).and_then(|(auth, header)| {
+                        let token = header.strip_prefix("Bearer ")?;
+                        auth.verify(token.trim()).ok().map(|claims| claims.sub)
+                    });
+                    let (limiter, key) = match user {
+                        Some(user) => (&limits.per_user, user),
+                        None => {
+                            let forwarded = forwarded
+                                .as_deref()
+                                .and_then(|header| limits.forwarded_client(header))
+                                .map(str::to_string);
+                            let ip = forwarded.or_else(|| remote.map(|addr| addr.ip().to_string()));
+                            (&limits.per_ip, ip.unwrap_or_else(|| "unknown".to_string()))
+                        }
+                    };
+                    match limiter {
+                        Some(limiter) => limiter.check(&key).map_err(|retry_after| {
+                            warp::reject::custom(RateLimited { retry_after })
+                        }),
+                        None => Ok(()),
+                    }
+                }
+            })
+            .untuple_one()
+    }
+}
+
+/// All API routes
+pub fn routes(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    let endpoints = metrics_route(ctx.clone())
+        .or(health_route())
+        .unify()
+        .or(system_info_route(ctx.clone()))
+        .unify()
+        .or(login_route(ctx.clone()))
+        .unify()
+        .or(refresh_route(ctx.clone()))
+        .unify()
+        .or(logout_route(ctx.clone()))
+        .unify()
+        .or(temperature_route(ctx.clone()))
+        .unify()
+        .or(temperature_history_route(ctx.clone()))
+        .unify()
+        .or(temperature_history_csv_route(ctx.clone()))
+        .unify()
+        .or(gcode_route(ctx.clone()))
+        .unify()
+        .or(gcode_batch_route(ctx.clone()))
+        .unify()
+        .or(gcode_batch_status_route(ctx.clone()))
+        .unify()
+        .or(gcode_history_route(ctx.clone()))
+        .unify()
+        .or(gcode_history_export_route(ctx.clone()))
+        .unify()
+        .or(hardware_stats_route(ctx.clone()))
+        .unify()
+        .or(position_stream_route(ctx.clone()))
+        .unify()
+        .or(hardware_reset_route(ctx.clone()))
+        .unify()
+        .or(hardware_flash_route(ctx.clone()))
+        .unify()
+        .or(job_start_route(ctx.clone()))
+        .unify()
+        .or(job_dry_run_route(ctx.clone()))
+        .unify()
+        .or(event_log_route(ctx.clone()))
+        .unify()
+        // Boxed part way so the filter type stays within the recursion limit
+        .boxed()
+        .or(file_thumbnail_route(ctx.clone()))
+        .unify()
+        .or(file_info_route(ctx.clone()))
+        .unify()
+        .or(file_checksum_route(ctx.clone()))
+        .unify()
+        .or(steps_per_mm_route(ctx.clone()))
+        .unify()
+        .or(set_steps_per_mm_route(ctx.clone()))
+        .unify()
+        .or(config_route(ctx.clone()))
+        .unify()
+        .or(set_motion_mode_route(ctx.clone()))
+        .unify()
+        .or(config_validate_route(ctx.clone()))
+        .unify()
+        .or(config_profiles_route(ctx.clone()))
+        .unify()
+        .or(config_profile_route(ctx.clone()))
+        .unify()
+        .or(activate_config_profile_route(ctx.clone()))
+        .unify()
+        .or(pause_conditions_route(ctx.clone()))
+        .unify()
+        .or(motion_segments_route(ctx.clone()))
+        .unify()
+        .or(position_drift_route(ctx.clone()))
+        .unify()
+        .or(set_kinematics_route(ctx.clone()))
+        .unify()
+        .or(save_predictor_route(ctx.clone()))
+        .unify()
+        .or(reset_predictor_route(ctx.clone()))
+        .unify()
+        .or(motion_recordings_route(ctx.clone()))
+        .unify()
+        .or(motion_recording_route(ctx.clone()))
+        .unify()
+        .or(delete_pause_condition_route(ctx.clone()))
+        .unify()
+        .or(shaper_presets_route(ctx.clone()))
+        .unify()
+        .or(add_shaper_preset_route(ctx.clone()))
+        .unify()
+        .or(octoprint::routes(ctx.clone()))
+        .unify();
+
+    ctx.rate_limited()
+        .and(endpoints)
+        .recover(|rejection: Rejection| async move {
+            if let Some(RateLimited { retry_after }) = rejection.find() {
+                let retry_after = retry_after.as_secs_f64().ceil().min(u32::MAX as f64) as u32;
+                return Ok(warp::reply::with_header(
+                    error(StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded"),
+                    "retry-after",
+                    retry_after.to_string(),
+                )
+                .into_response());
+            }
+            match rejection.find::<AuthRejection>() {
+                Some(AuthRejection::Unauthorized) => Ok(warp::reply::with_header(
+                    error(StatusCode::UNAUTHORIZED, "Missing or invalid access token"),
+                    "www-authenticate",
+                    "Bearer",
+                )
+                .into_response()),
+                Some(AuthRejection::InsufficientPermission { required, .. }) => Ok(error(
+                    StatusCode::FORBIDDEN,
+                    &format!("Requires {:?} permission", required),
+                )),
+                None => Err(rejection),
+            }
+        })
+        .unify()
+        .boxed()
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+/// Cookie holding the refresh token, sent only to `/api/auth`
+const REFRESH_COOKIE: &str = "krusty_refresh";
+
+/// `POST /api/auth/login`: exchange credentials for an access token
+///
+/// The refresh token is set as an HTTP-only cookie.
+fn login_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "auth" / "login")
+        .and(warp::post())
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .map(|ctx: ApiContext, request: LoginRequest| {
+            let Some(auth) = &ctx.auth else {
+                return error(StatusCode::NOT_FOUND, "Authentication is disabled");
+            };
+            match auth.login(&request.username, &request.password) {
+                Some(tokens) => token_response(auth, tokens),
+                None => {
+                    tracing::warn!("Failed login for {}", request.username);
+                    error(StatusCode::UNAUTHORIZED, "Invalid username or password")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `POST /api/auth/refresh`: rotate the refresh cookie and issue a new
+/// access token
+fn refresh_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "auth" / "refresh")
+        .and(warp::post())
+        .and(with_context(ctx))
+        .and(warp::cookie::optional::<String>(REFRESH_COOKIE))
+        .map(|ctx: ApiContext, refresh_token: Option<String>| {
+            let Some(auth) = &ctx.auth else {
+                return error(StatusCode::NOT_FOUND, "Authentication is disabled");
+            };
+            let Some(refresh_token) = refresh_token else {
+                return error(StatusCode::UNAUTHORIZED, "Missing refresh token");
+            };
+            match auth.refresh(&refresh_token) {
+                Ok(tokens) => token_response(auth, tokens),
+                Err(e) => warp::reply::with_header(
+                    error(StatusCode::UNAUTHORIZED, &e.to_string()),
+                    "set-cookie",
+                    clear_refresh_cookie(),
+                )
+                .into_response(),
+            }
+        })
+        .boxed()
+}
+
+/// `POST /api/auth/logout`: revoke the refresh cookie
+fn logout_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "auth" / "logout")
+        .and(warp::post())
+        .and(with_context(ctx))
+        .and(warp::cookie::optional::<String>(REFRESH_COOKIE))
+        .map(|ctx: ApiContext, refresh_token: Option<String>| {
+            if let (Some(auth), Some(refresh_token)) = (&ctx.auth, refresh_token) {
+                auth.logout(&refresh_token);
+            }
+            warp::reply::with_header(StatusCode::NO_CONTENT, "set-cookie", clear_refresh_cookie()).into_response()
+        })
+        .boxed()
+}
+
+fn token_response(auth: &JwtAuth, tokens: TokenPair) -> Response {
+    let refresh = &tokens.refresh_token;
+    let cookie = format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/api/auth; Max-Age={}",
+        REFRESH_COOKIE,
+        refresh.token,
+        refresh.expires_at.saturating_sub(refresh.issued_at)
+    );
+    let body = warp::reply::json(&json!({
+        "access_token": tokens.access_token,
+        "token_type": "Bearer",
+        "expires_in": auth.token_lifetime_secs(),
+    }));
+    warp::reply::with_header(body, "set-cookie", cookie).into_response()
+}
+
+fn clear_refresh_cookie() -> String {
+    format!("{}=; HttpOnly; SameSite=Strict; Path=/api/auth; Max-Age=0", REFRESH_COOKIE)
+}
+
+/// `GET /api/temperature`: current heater temperatures; `chamber` is null
+/// without a chamber heater
+fn temperature_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "temperature")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|_: Claims, ctx: ApiContext| async move {
+            let state = ctx.state.read().await;
+            let chamber = state.chamber.as_ref().map(|chamber| {
+                json!({
+                    "temperature": chamber.get_temperature(),
+                    "target": chamber.get_target(),
+                    "power": chamber.get_output(),
+                    "fault": chamber.get_fault(),
+                })
+            });
+            warp::reply::json(&json!({
+                "hotend": state.temperature,
+                "bed": state.bed_temperature,
+                "chamber": chamber,
+            }))
+            .into_response()
+        })
+        .boxed()
+}
+
+#[derive(Debug, Deserialize)]
+struct TemperatureHistoryQuery {
+    /// How far back to go; everything kept if missing
+    seconds: Option<u64>,
+}
+
+impl TemperatureHistoryQuery {
+    fn samples(&self, ctx: &ApiContext) -> Vec<crate::temperature::TemperatureSample> {
+        let history = ctx.temperature_history.lock().unwrap();
+        let duration = self.seconds.map_or(history.retention(), Duration::from_secs);
+        history.recent(duration, unix_millis())
+    }
+}
+
+/// `GET /api/temperature/history?seconds=<n>`: temperatures and targets,
+/// oldest first
+fn temperature_history_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "temperature" / "history")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .and(warp::query::<TemperatureHistoryQuery>())
+        .map(|_: Claims, ctx: ApiContext, query: TemperatureHistoryQuery| {
+            warp::reply::json(&query.samples(&ctx)).into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/temperature/history.csv?seconds=<n>`: the same as CSV
+fn temperature_history_csv_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "temperature" / "history.csv")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .and(warp::query::<TemperatureHistoryQuery>())
+        .map(|_: Claims, ctx: ApiContext, query: TemperatureHistoryQuery| {
+            let csv = TemperatureHistoryBuffer::to_csv(&query.samples(&ctx));
+            let reply = warp::reply::with_header(csv, "content-type", "text/csv");
+            warp::reply::with_header(reply, "content-disposition", "attachment; filename=\"temperature-history.csv\"")
+                .into_response()
+        })
+        .boxed()
+}
+
+#[derive(Debug, Deserialize)]
+struct GCodeRequest {
+    command: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GCodeBatchRequest {
+    commands: Vec<String>,
+}
+
+/// Operators only, charging the user's G-code rate limit
+fn require_gcode(ctx: &ApiContext) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone + use<> {
+    let limits = ctx.rate_limits.clone();
+    ctx.require(AuthPermission::Operator).and_then(move |claims: Claims| {
+        let limits = limits.clone();
+        async move {
+            if let Some(limiter) = &limits.gcode_per_user {
+                limiter
+                    .check(&claims.sub)
+                    .map_err(|retry_after| warp::reject::custom(RateLimited { retry_after }))?;
+            }
+            Ok::<_, Rejection>(claims)
+        }
+    })
+}
+
+/// `POST /api/gcode`: run one G-code command
+fn gcode_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "gcode")
+        .and(warp::post())
+        .and(require_gcode(&ctx))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|claims: Claims, ctx: ApiContext, request: GCodeRequest| async move {
+            tracing::info!("{} ran G-code: {}", claims.sub, request.command);
+            let mut gcode = ctx.gcode.clone();
+            if gcode.run_cancel_wait(std::slice::from_ref(&request.command)) {
+                return warp::reply::json(&json!({ "ok": true })).into_response();
+            }
+            let _commands = gcode.lock_commands().await;
+            // Errors are stringified so they aren't held across an await
+            match gcode.process_command(&request.command).await.map_err(|e| e.to_string()) {
+                Ok(()) => warp::reply::json(&json!({ "ok": true })).into_response(),
+                Err(e) => error(StatusCode::BAD_REQUEST, &e),
+            }
+        })
+        .boxed()
+}
+
+/// `POST /api/gcode/batch`: queue commands to run back to back, returning
+/// the batch ID to poll
+fn gcode_batch_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "gcode" / "batch")
+        .and(warp::post())
+        .and(require_gcode(&ctx))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .map(|claims: Claims, ctx: ApiContext, request: GCodeBatchRequest| {
+            let total = request.commands.len();
+            match ctx.gcode.submit_batch(request.commands) {
+                Ok(id) => {
+                    tracing::info!("{} queued G-code batch {} of {} commands", claims.sub, id, total);
+                    warp::reply::with_status(warp::reply::json(&json!({ "id": id })), StatusCode::ACCEPTED).into_response()
+                }
+                Err(e) => error(StatusCode::BAD_REQUEST, &e),
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/gcode/batch/<id>/status`: how far a batch has got
+fn gcode_batch_status_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "gcode" / "batch" / u64 / "status")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|id: u64, _claims: Claims, ctx: ApiContext| match ctx.gcode.batch_status(id) {
+            Some(status) => warp::reply::json(&status).into_response(),
+            None => error(StatusCode::NOT_FOUND, &format!("No G-code batch {}", id)),
+        })
+        .boxed()
+}
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    #[serde(default = "default_history_limit")]
+    limit: usize,
+    #[serde(default)]
+    offset: usize,
+    status: Option<String>,
+}
+
+fn default_history_limit() -> usize { 100 }
+
+/// `GET /api/gcode/history`: executed commands, newest first
+fn gcode_history_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "gcode" / "history")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .and(warp::query::<HistoryQuery>())
+        .map(|_: Claims, ctx: ApiContext, query: HistoryQuery| {
+            if let Some(status) = query.status.as_deref()
+                && !["ok", "warn", "err"].contains(&status)
+            {
+                return error(StatusCode::BAD_REQUEST, "status must be one of ok, warn, err");
+            }
+            warp::reply::json(&ctx.gcode.query_history(query.status.as_deref(), query.offset, query.limit)).into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/gcode/history/export`: the whole history as JSON lines,
+/// oldest first
+fn gcode_history_export_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "gcode" / "history" / "export")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_: Claims, ctx: ApiContext| {
+            let body: String = ctx
+                .gcode
+                .history_entries()
+                .iter()
+                .filter_map(|entry| serde_json::to_string(entry).ok())
+                .map(|line| line + "\n")
+                .collect();
+            let reply = warp::reply::with_header(body, "content-type", "application/x-ndjson");
+            warp::reply::with_header(reply, "content-disposition", "attachment; filename=\"gcode-history.jsonl\"").into_response()
+        })
+        .boxed()
+}
+
+
+/// `GET /metrics` in Prometheus text format; 404 unless enabled
+fn metrics_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(with_context(ctx))
+        .then(|ctx: ApiContext| async move {
+            let Some(metrics) = &ctx.metrics else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            let stats = *ctx.planner_stats.borrow();
+            metrics.update(&*ctx.state.read().await, &stats);
+            match metrics.encode() {
+                Ok(body) => warp::reply::with_header(body, "content-type", "text/plain; version=0.0.4").into_response(),
+                Err(e) => {
+                    tracing::error!("Failed to encode metrics: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/health`: liveness probe that touches nothing but the web server
+fn health_route() -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "health")
+        .and(warp::get())
+        .map(|| warp::reply::json(&json!({ "status": "ok" })).into_response())
+        .boxed()
+}
+
+/// `GET /api/system/info`: OS, CPU, memory and upload disk usage, read afresh
+fn system_info_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "system" / "info")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|_: Claims, ctx: ApiContext| async move {
+            match tokio::task::spawn_blocking(move || SystemInfo::collect(&ctx.upload_dir)).await {
+                Ok(info) => warp::reply::json(&info).into_response(),
+                Err(e) => {
+                    tracing::error!("Failed to collect system info: {}", e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to collect system info")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/hardware/stats`: MCU command counts and latency percentiles
+fn hardware_stats_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "hardware" / "stats")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_: Claims, ctx: ApiContext| {
+            let stats = ctx.hardware.get_command_stats();
+            warp::reply::json(&json!({
+                "total_commands": stats.total_commands,
+                "failed_commands": stats.failed_commands,
+                "timeout_commands": stats.timeout_commands,
+                "mcu_reset_count": stats.mcu_reset_count,
+                "stepper_stall_count": stats.stepper_stall_count,
+                "latency_histogram": stats.latency_histogram,
+                "p50_latency_ms": stats.p50_latency_ms(),
+                "p95_latency_ms": stats.p95_latency_ms(),
+                "p99_latency_ms": stats.p99_latency_ms(),
+            }))
+            .into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/position/stream`: the toolhead position as Server-Sent Events
+fn position_stream_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "position" / "stream")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_: Claims, ctx: ApiContext| {
+            let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / ctx.position_stream_hz as f64));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let position = ctx.position;
+            let events = IntervalStream::new(interval).map(move |_| {
+                let [x, y, z, e] = *position.borrow();
+                Ok::<_, Infallible>(warp::sse::Event::default().data(json!({ "x": x, "y": y, "z": z, "e": e }).to_string()))
+            });
+            warp::sse::reply(events).into_response()
+        })
+        .boxed()
+}
+
+/// `POST /api/hardware/reset`: restart and re-initialize the MCU
+fn hardware_reset_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "hardware" / "reset")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .then(|claims: Claims, ctx: ApiContext| async move {
+            tracing::warn!("{} requested an MCU reset", claims.sub);
+            let mut hardware = ctx.hardware.clone();
+            let restarted = hardware.restart_mcu().await.map_err(|e| e.to_string());
+            let result = match restarted {
+                Ok(()) => hardware.initialize().await.map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => warp::reply::json(&json!({ "ok": true })).into_response(),
+                Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+            }
+        })
+        .boxed()
+}
+
+/// Largest accepted firmware image
+const MAX_FIRMWARE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Held while firmware is being written, so flashes never overlap
+static FLASH_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Deserialize)]
+struct FlashQuery {
+    method: FlashMethod,
+}
+
+/// `POST /api/hardware/flash?method=<method>`: write the uploaded
+/// `firmware` part to the MCU, then initialize it again
+///
+/// Refused while a print is running. Progress is published as
+/// `firmware_flash` events.
+fn hardware_flash_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "hardware" / "flash")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .and(warp::query::<FlashQuery>())
+        .and(warp::multipart::form().max_length(MAX_FIRMWARE_BYTES))
+        .then(|claims: Claims, ctx: ApiContext, query: FlashQuery, form: warp::multipart::FormData| async move {
+            let Ok(_flashing) = FLASH_LOCK.try_lock() else {
+                return error(StatusCode::CONFLICT, "Firmware is already being flashed");
+            };
+            if ctx.state.read().await.job.as_ref().is_some_and(|job| job.state().is_active()) {
+                return error(StatusCode::CONFLICT, "Cannot flash firmware while printing");
+            }
+
+            // The flashing tool gets the image by path, so nobody else may
+            // be able to swap it
+            let dir = match create_private_temp_dir("krusty-firmware") {
+                Ok(dir) => dir,
+                Err(e) => {
+                    tracing::error!("Failed to create firmware directory: {}", e);
+                    return error(StatusCode::INTERNAL_SERVER_ERROR, "Could not store firmware");
+                }
+            };
+            let response = flash_upload(&ctx, &claims, query.method, &dir.join("firmware.bin"), form).await;
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            response
+        })
+        .boxed()
+}
+
+/// Store the `firmware` part of `form` at `path` and flash it
+async fn flash_upload(
+    ctx: &ApiContext,
+    claims: &Claims,
+    method: FlashMethod,
+    path: &Path,
+    mut form: warp::multipart::FormData,
+) -> Response {
+    let mut received = false;
+    while let Some(part) = form.next().await {
+        let part = match part {
+            Ok(part) => part,
+            Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        if part.name() == "firmware" {
+            let stored = match tokio::fs::OpenOptions::new().write(true).create_new(true).open(path).await {
+                Ok(file) => octoprint::write_part(file, part).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = stored {
+                tracing::error!("Failed to store firmware upload: {}", e);
+                return error(StatusCode::INTERNAL_SERVER_ERROR, "Could not store firmware");
+            }
+            received = true;
+            break;
+        }
+    }
+    if !received {
+        return error(StatusCode::BAD_REQUEST, "No firmware included");
+    }
+
+    tracing::warn!("{} is flashing MCU firmware", claims.sub);
+    let path = path.to_string_lossy();
+    if let Err(e) = ctx.hardware.flash_firmware(&path, method, &ctx.events).await {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string());
+    }
+    let mut hardware = ctx.hardware.clone();
+    match hardware.initialize().await.map_err(|e| e.to_string()) {
+        Ok(()) => warp::reply::json(&json!({ "ok": true })).into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &format!("Firmware written but MCU did not come back: {}", e)),
+    }
+}
+
+/// Create a new, randomly named directory under the system temp directory
+/// that only this user can open
+fn create_private_temp_dir(prefix: &str) -> std::io::Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    loop {
+        let dir = std::env::temp_dir().join(format!("{}-{:016x}", prefix, rand::random::<u64>()));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EventLogQuery {
+    /// ISO 8601 time of the earliest event; the start of the log if missing
+    from: Option<String>,
+    /// ISO 8601 time of the latest event; now if missing
+    to: Option<String>,
+    /// Event types to keep, comma-separated, e.g. `print_failed`
+    #[serde(rename = "type")]
+    event_type: Option<String>,
+}
+
+/// `GET /api/events/log?from=<iso8601>&to=<iso8601>&type=<types>`: logged
+/// printer events, oldest first
+///
+/// Answers 404 unless `advanced.event_log_file` is set.
+fn event_log_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "events" / "log")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .and(warp::query::<EventLogQuery>())
+        .then(|_: Claims, ctx: ApiContext, query: EventLogQuery| async move {
+            let Some(log) = ctx.event_log.clone() else {
+                return error(StatusCode::NOT_FOUND, "Event logging is not enabled");
+            };
+            let parse = |time: Option<&String>, default: u64| match time {
+                Some(time) => parse_iso8601_ms(time).ok_or_else(|| format!("Invalid ISO 8601 time: {}", time)),
+                None => Ok(default),
+            };
+            let (from, to) = match (parse(query.from.as_ref(), 0), parse(query.to.as_ref(), u64::MAX)) {
+                (Ok(from), Ok(to)) => (from, to),
+                (Err(e), _) | (_, Err(e)) => return error(StatusCode::BAD_REQUEST, &e),
+            };
+            let types: Option<Vec<String>> = query
+                .event_type
+                .map(|types| types.split(',').map(|event_type| event_type.trim().to_string()).collect());
+
+            let entries = tokio::task::spawn_blocking(move || {
+                log.entries_where(|entry| {
+                    (from..=to).contains(&entry.unix_ms)
+                        && types.as_ref().is_none_or(|types| {
+                            types.iter().any(|event_type| entry.event["event"] == event_type.as_str())
+                        })
+                })
+            })
+            .await;
+            match entries {
+                Ok(Ok(entries)) => warp::reply::json(&entries).into_response(),
+                Ok(Err(e)) => {
+                    tracing::error!("Failed to read event log: {}", e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read event log")
+                }
+                Err(e) => {
+                    tracing::error!("Event log reader panicked: {}", e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read event log")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Milliseconds since the Unix epoch for an ISO 8601 time such as
+/// `2024-05-01T12:30:00Z` or `2024-05-01T14:30:00.250+02:00`
+///
+/// A space stands for `+` in the offset, since that is what an unescaped
+/// `+` in a query string decodes to.
+fn parse_iso8601_ms(text: &str) -> Option<u64> {
+    let (date, time) = text.trim_end().split_once(['T', 't'])?;
+    let mut date = date.splitn(3, '-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: i64 = date.next()?.parse().ok()?;
+    let day: i64 = date.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (clock, offset_secs) = match time.strip_suffix(['Z', 'z']) {
+        Some(clock) => (clock, 0),
+        None => {
+            let (clock, offset) = time.split_at(time.rfind(['+', '-', ' '])?);
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            (clock, sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60))
+        }
+    };
+    let mut clock = clock.splitn(3, ':');
+    let hour: i64 = clock.next()?.parse().ok()?;
+    let minute: i64 = clock.next()?.parse().ok()?;
+    let second: f64 = clock.next()?.parse().ok()?;
+    if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0.0..61.0).contains(&second) {
+        return None;
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 - offset_secs;
+    let millis = secs as f64 * 1000.0 + (second * 1000.0).round();
+    (millis >= 0.0).then_some(millis as u64)
+}
+
+#[derive(Debug, Deserialize)]
+struct JobStartRequest {
+    /// File in the upload directory
+    path: String,
+}
+
+/// `POST /api/jobs/start`: check a file's preconditions and print it
+///
+/// Any error-level finding refuses the print (422); warnings are returned
+/// alongside the started job.
+fn job_start_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "jobs" / "start")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Operator))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|claims: Claims, ctx: ApiContext, request: JobStartRequest| async move {
+            let relative = Path::new(&request.path);
+            if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+                return error(StatusCode::BAD_REQUEST, "Path must be inside the upload directory");
+            }
+            let path = ctx.upload_dir.join(relative).to_string_lossy().into_owned();
+            if let Err(e) = print_job::verify_file(&ctx.files, &ctx.events, &path).await {
+                return error(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string());
+            }
+
+            let validator = PrintJobValidator::inspect(&FileManager::new(), &path).await;
+            let size = tokio::fs::metadata(&path).await.map(|metadata| metadata.len()).unwrap_or(0);
+            let job = PrintJob::new(&path, size);
+            let config = ctx.config.read().await.clone();
+            let mut state = ctx.state.write().await;
+            if state.job.as_ref().is_some_and(|job| job.state().is_active()) {
+                return error(StatusCode::CONFLICT, "A print is already running");
+            }
+            let findings = validator.validate(&job, &state, &config);
+            if findings.iter().any(|finding| finding.severity == Severity::Error) {
+                let body = json!({ "started": false, "findings": findings });
+                return warp::reply::with_status(warp::reply::json(&body), StatusCode::UNPROCESSABLE_ENTITY)
+                    .into_response();
+            }
+            // Claim the printer before the job starts so a second request conflicts
+            state.job = Some(job.clone());
+            drop(state);
+
+            tracing::info!("{} started printing {}", claims.sub, path);
+            let mut gcode = ctx.gcode.clone();
+            tokio::spawn(async move {
+                if let Err(e) = print_job::run_with_events(job, &ctx.state, &mut gcode, &ctx.events).await {
+                    tracing::error!("Print failed: {}", e);
+                }
+            });
+            let body = json!({ "started": true, "findings": findings });
+            warp::reply::with_status(warp::reply::json(&body), StatusCode::ACCEPTED).into_response()
+        })
+        .boxed()
+}
+
+/// `POST /api/jobs/dry-run`: simulate a file without moving the printer
+///
+/// Reports the commands run, XYZ travel (mm), estimated time (s) and the
+/// final [X, Y, Z, E] position; a command that fails answers 422.
+fn job_dry_run_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "jobs" / "dry-run")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Operator))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|_: Claims, ctx: ApiContext, request: JobStartRequest| async move {
+            let relative = Path::new(&request.path);
+            if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+                return error(StatusCode::BAD_REQUEST, "Path must be inside the upload directory");
+            }
+            let path = ctx.upload_dir.join(relative).to_string_lossy().into_owned();
+            if ctx.state.read().await.job.as_ref().is_some_and(|job| job.state().is_active()) {
+                return error(StatusCode::CONFLICT, "Cannot dry-run while printing");
+            }
+            if tokio::fs::metadata(&path).await.is_err() {
+                return error(StatusCode::NOT_FOUND, "File not found");
+            }
+
+            let mut gcode = ctx.gcode.clone();
+            match gcode.dry_run_file(&path).await.map_err(|e| e.to_string()) {
+                Ok(report) => warp::reply::json(&json!({
+                    "commands": report.commands,
+                    "total_distance": report.total_distance,
+                    "estimated_time": report.estimated_time,
+                    "final_position": report.final_position,
+                }))
+                .into_response(),
+                Err(e) => error(StatusCode::UNPROCESSABLE_ENTITY, &e),
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/files/<name>/info`: size of an uploaded file and the print
+/// time, filament, layers and slicer its header reports
+fn file_info_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "files" / String / "info")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|name: String, _: Claims, ctx: ApiContext| async move {
+            if !Path::new(&name).components().all(|component| matches!(component, Component::Normal(_))) {
+                return error(StatusCode::BAD_REQUEST, "Path must be inside the upload directory");
+            }
+            let path = ctx.upload_dir.join(&name).to_string_lossy().into_owned();
+            let size = match tokio::fs::metadata(&path).await {
+                Ok(metadata) if metadata.is_file() => metadata.len(),
+                _ => return error(StatusCode::NOT_FOUND, "File not found"),
+            };
+            match ctx.files.parse_metadata(&path).await.map_err(|e| e.to_string()) {
+                Ok(metadata) => warp::reply::json(&json!({
+                    "name": name,
+                    "size": size,
+                    "metadata": metadata,
+                }))
+                .into_response(),
+                Err(e) => {
+                    tracing::error!("Failed to read metadata from {}: {}", path, e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/files/<name>/checksum`: SHA-256 digest stored when a file was
+/// uploaded, which prints are checked against; 404 if it has none
+fn file_checksum_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "files" / String / "checksum")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|name: String, _: Claims, ctx: ApiContext| async move {
+            if !Path::new(&name).components().all(|component| matches!(component, Component::Normal(_))) {
+                return error(StatusCode::BAD_REQUEST, "Path must be inside the upload directory");
+            }
+            let path = ctx.upload_dir.join(&name).to_string_lossy().into_owned();
+            if tokio::fs::metadata(&path).await.is_err() {
+                return error(StatusCode::NOT_FOUND, "File not found");
+            }
+            match ctx.files.get_stored_checksum(&path).await {
+                Ok(Some(checksum)) => warp::reply::json(&json!({ "name": name, "sha256": checksum })).into_response(),
+                Ok(None) => error(StatusCode::NOT_FOUND, "No checksum stored for file"),
+                Err(e) => {
+                    tracing::error!("Failed to read checksum of {}: {}", path, e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read checksum")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/files/<name>/thumbnail`: the image the slicer embedded in an
+/// uploaded file, 404 if it has none
+fn file_thumbnail_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "files" / String / "thumbnail")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|name: String, _: Claims, ctx: ApiContext| async move {
+            if !Path::new(&name).components().all(|component| matches!(component, Component::Normal(_))) {
+                return error(StatusCode::BAD_REQUEST, "Path must be inside the upload directory");
+            }
+            let path = ctx.upload_dir.join(&name).to_string_lossy().into_owned();
+            match ctx.files.extract_thumbnail(&path).await {
+                Ok(Some(thumbnail)) => {
+                    let content_type = thumbnail_content_type(&thumbnail);
+                    warp::reply::with_header(thumbnail, "content-type", content_type).into_response()
+                }
+                Ok(None) => error(StatusCode::NOT_FOUND, "File has no thumbnail"),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => error(StatusCode::NOT_FOUND, "File not found"),
+                Err(e) => {
+                    tracing::error!("Failed to read thumbnail from {}: {}", path, e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Steps/mm per axis; in requests, axes left out are unchanged
+#[derive(Debug, Deserialize)]
+struct StepsPerMm {
+    x: Option<f64>,
+    y: Option<f64>,
+    z: Option<f64>,
+    e: Option<f64>,
+}
+
+fn steps_per_mm_json(gcode: &GCodeProcessor) -> serde_json::Value {
+    let [x, y, z, e] = gcode.steps_per_mm();
+    json!({ "x": x, "y": y, "z": z, "e": e })
+}
+
+/// `GET /api/calibration/steps`: current steps/mm
+fn steps_per_mm_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "calibration" / "steps")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_claims: Claims, ctx: ApiContext| warp::reply::json(&steps_per_mm_json(&ctx.gcode)).into_response())
+        .boxed()
+}
+
+/// `POST /api/calibration/steps`: change steps/mm, as M92 would
+fn set_steps_per_mm_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "calibration" / "steps")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Operator))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|claims: Claims, ctx: ApiContext, request: StepsPerMm| async move {
+            let axes = [('X', request.x), ('Y', request.y), ('Z', request.z), ('E', request.e)];
+            let words: Vec<String> = axes
+                .iter()
+                .filter_map(|(axis, value)| value.map(|value| format!("{}{}", axis, value)))
+                .collect();
+            if words.is_empty() {
+                return error(StatusCode::BAD_REQUEST, "No axes given");
+            }
+            let command = format!("M92 {}", words.join(" "));
+            tracing::info!("{} ran {}", claims.sub, command);
+            let mut gcode = ctx.gcode.clone();
+            match gcode.process_command(&command).await.map_err(|e| e.to_string()) {
+                Ok(()) => warp::reply::json(&steps_per_mm_json(&gcode)).into_response(),
+                Err(e) => error(StatusCode::BAD_REQUEST, &e),
+            }
+        })
+        .boxed()
+}
+
+/// `PUT /api/config`: replace the configuration with a TOML document
+///
+/// The configuration is validated and written to the configuration file.
+/// It takes effect on the next restart; the reply says whether anything
+/// that needs one changed.
+fn config_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "config")
+        .and(warp::put())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .and(warp::body::bytes())
+        .then(|claims: Claims, ctx: ApiContext, body: warp::hyper::body::Bytes| async move {
+            let config = match std::str::from_utf8(&body).map_err(|e| e.to_string()).and_then(|text| {
+                toml::from_str::<Config>(text).map_err(|e| e.to_string())
+            }) {
+                Ok(config) => config,
+                Err(e) => return error(StatusCode::BAD_REQUEST, &e),
+            };
+            let errors = config.validate_all();
+            if errors.iter().any(|e| e.severity == ConfigErrorSeverity::Error) {
+                return warp::reply::with_status(
+                    warp::reply::json(&json!({ "error": "Invalid configuration", "errors": errors })),
+                    StatusCode::BAD_REQUEST,
+                )
+                .into_response();
+            }
+            if let Err(e) = ctx.profiles.write().await.save_config(&config) {
+                return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string());
+            }
+            let (diff, unchanged) = {
+                let mut current = ctx.config.write().await;
+                let diff = Config::diff(&current, &config);
+                // Sections outside the diff (web, MQTT, ...) still need a restart
+                let unchanged = diff.is_empty() && serde_json::to_value(&*current).ok() == serde_json::to_value(&config).ok();
+                *current = config;
+                (diff, unchanged)
+            };
+            tracing::info!("{} updated the configuration: {:?}", claims.sub, diff);
+            warp::reply::json(&json!({
+                "restart_required": !unchanged,
+                "hardware_restart_required": diff.requires_hardware_restart(),
+                "motion_replan_required": diff.requires_motion_replan(),
+            }))
+            .into_response()
+        })
+        .boxed()
+}
+
+#[derive(Debug, Deserialize)]
+struct MotionModeRequest {
+    mode: String,
+}
+
+/// `PUT /api/motion/mode`: shape moves as `basic`, `adaptive` or
+/// `snap_crackle` once queued moves finish; refused while printing
+fn set_motion_mode_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "mode")
+        .and(warp::put())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|claims: Claims, ctx: ApiContext, request: MotionModeRequest| async move {
+            let mode: MotionMode = match request.mode.parse() {
+                Ok(mode) => mode,
+                Err(e) => return error(StatusCode::BAD_REQUEST, &e),
+            };
+            if ctx.state.read().await.job.as_ref().is_some_and(|job| job.state().is_active()) {
+                return error(StatusCode::CONFLICT, "Cannot change motion mode while printing");
+            }
+            tracing::info!("{} switched motion mode to {}", claims.sub, mode.name());
+            let mut gcode = ctx.gcode.clone();
+            match gcode.set_motion_mode(mode).await {
+                Ok(()) => warp::reply::json(&json!({ "mode": gcode.motion_mode().await.name() })).into_response(),
+                Err(e) => error(StatusCode::CONFLICT, &e.to_string()),
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/motion/debug/segments` or `GET /api/motion/queue/segments`: the
+/// planned motion queue, labeled with the commands that planned it
+fn motion_segments_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "debug" / "segments")
+        .or(warp::path!("api" / "motion" / "queue" / "segments"))
+        .unify()
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_claims: Claims, ctx: ApiContext| {
+            let segments: Vec<_> = ctx
+                .gcode
+                .queued_segments()
+                .iter()
+                .map(|segment| {
+                    json!({
+                        "target": segment.target,
+                        "motion_type": segment.motion_type.as_str(),
+                        "feedrate": segment.feedrate,
+                        "acceleration": segment.acceleration,
+                        "distance": segment.distance,
+                        "entry_speed": segment.entry_speed,
+                        "exit_speed": segment.exit_speed,
+                        "is_curve": segment.is_curve,
+                        "bypass_shaper": segment.bypass_shaper,
+                        "label": segment.label,
+                        "sequence_number": segment.sequence_number,
+                    })
+                })
+                .collect();
+            warp::reply::json(&json!({ "segments": segments })).into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/motion/position/drift`: each encoder-tracked motor's position
+/// against the steps commanded
+fn position_drift_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "position" / "drift")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_claims: Claims, ctx: ApiContext| {
+            warp::reply::json(&json!({ "axes": ctx.gcode.position_drift() })).into_response()
+        })
+        .boxed()
+}
+
+#[derive(Debug, Deserialize)]
+struct KinematicsRequest {
+    kinematics: String,
+}
+
+/// `PUT /api/motion/kinematics`: switch kinematics, e.g.
+/// `{"kinematics": "corexy"}`, once queued moves finish
+///
+/// Refused while printing. SCARA and delta geometry comes from the
+/// configuration.
+fn set_kinematics_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "kinematics")
+        .and(warp::put())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|claims: Claims, ctx: ApiContext, request: KinematicsRequest| async move {
+            let kinematics_type: KinematicsType = match request.kinematics.parse() {
+                Ok(kinematics_type) => kinematics_type,
+                Err(e) => return error(StatusCode::BAD_REQUEST, &e),
+            };
+            if ctx.state.read().await.job.as_ref().is_some_and(|job| job.state().is_active()) {
+                return error(StatusCode::CONFLICT, "Cannot change kinematics while printing");
+            }
+            tracing::info!("{} switched kinematics to {:?}", claims.sub, kinematics_type);
+            let mut gcode = ctx.gcode.clone();
+            match gcode.set_kinematics(kinematics_type).await.map_err(|e| e.to_string()) {
+                Ok(()) => warp::reply::json(&json!({
+                    "kinematics": format!("{:?}", gcode.kinematics_type()).to_lowercase(),
+                }))
+                .into_response(),
+                Err(e) => error(StatusCode::BAD_REQUEST, &e),
+            }
+        })
+        .boxed()
+}
+
+/// `POST /api/motion/predictor/save`: write the error predictor's learned
+/// model to `advanced.predictor_model_path`; 404 if that isn't set
+fn save_predictor_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "predictor" / "save")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .then(|_claims: Claims, ctx: ApiContext| async move {
+            let Some(path) = ctx.config.read().await.advanced.predictor_model_path.clone() else {
+                return error(StatusCode::NOT_FOUND, "No predictor model path configured");
+            };
+            let predictor = ctx.error_predictor.lock().unwrap();
+            match predictor.save_model(&path) {
+                Ok(()) => warp::reply::json(&json!({ "path": path, "model_version": predictor.model_version })).into_response(),
+                Err(e) => {
+                    tracing::error!("Failed to save predictor model to {}: {}", path, e);
+                    error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to save predictor model")
+                }
+            }
+        })
+        .boxed()
+}
+
+/// `DELETE /api/motion/predictor/model`: forget what the error predictor
+/// learned, starting again from random weights, and delete the saved model
+fn reset_predictor_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "predictor" / "model")
+        .and(warp::delete())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .then(|claims: Claims, ctx: ApiContext| async move {
+            if let Some(path) = ctx.config.read().await.advanced.predictor_model_path.clone()
+                && let Err(e) = std::fs::remove_file(&path)
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                tracing::error!("Failed to delete predictor model {}: {}", path, e);
+                return error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete predictor model");
+            }
+            ctx.error_predictor.lock().unwrap().reset_model();
+            tracing::info!("{} reset the motion error predictor", claims.sub);
+            StatusCode::NO_CONTENT.into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/motion/recordings`: saved trajectory recordings
+fn motion_recordings_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "recordings")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|_claims: Claims, ctx: ApiContext| {
+            let recorder = ctx.gcode.motion_recorder();
+            match recorder.list_recordings() {
+                Ok(recordings) => warp::reply::json(&json!({
+                    "recordings": recordings,
+                    "recording": recorder.is_recording(),
+                }))
+                .into_response(),
+                Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/motion/recordings/<id>`: samples of a saved recording
+fn motion_recording_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "motion" / "recordings" / String)
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .map(|id: String, _claims: Claims, ctx: ApiContext| {
+            match ctx.gcode.motion_recorder().read_recording(&id) {
+                Ok(samples) => warp::reply::json(&json!({ "id": id, "samples": samples })).into_response(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    error(StatusCode::NOT_FOUND, &format!("No recording {}", id))
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => error(StatusCode::BAD_REQUEST, &e.to_string()),
+                Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            }
+        })
+        .boxed()
+}
+
+/// `GET /api/pause-conditions`: pauses that have not triggered yet
+fn pause_conditions_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "pause-conditions")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|_claims: Claims, ctx: ApiContext| async move {
+            let state = ctx.state.read().await;
+            warp::reply::json(&json!({ "conditions": state.pause_conditions.list() })).into_response()
+        })
+        .boxed()
+}
+
+/// `DELETE /api/pause-conditions/<id>`: cancel a pending pause
+fn delete_pause_condition_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "pause-conditions" / u64)
+        .and(warp::delete())
+        .and(ctx.require(AuthPermission::Operator))
+        .and(with_context(ctx))
+        .then(|id: u64, claims: Claims, ctx: ApiContext| async move {
+            if !ctx.state.write().await.pause_conditions.remove(id) {
+                return error(StatusCode::NOT_FOUND, &format!("No pause condition {}", id));
+            }
+            tracing::info!("{} removed pause condition {}", claims.sub, id);
+            warp::reply::with_status(warp::reply(), StatusCode::NO_CONTENT).into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/config/validate`: every problem with the running configuration,
+/// by field
+fn config_validate_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "config" / "validate")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|_claims: Claims, ctx: ApiContext| async move {
+            let errors = ctx.config.read().await.validate_all();
+            let valid = errors.iter().all(|e| e.severity != ConfigErrorSeverity::Error);
+            warp::reply::json(&json!({ "valid": valid, "errors": errors })).into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/config/profiles`: every configuration profile and which is active
+fn config_profiles_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "config" / "profiles")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|_claims: Claims, ctx: ApiContext| async move {
+            let profiles = ctx.profiles.read().await;
+            warp::reply::json(&json!({
+                "active": profiles.active_profile(),
+                "profiles": profiles.profile_names(),
+            }))
+            .into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/config/profiles/<name>`: the full configuration of a profile
+///
+/// Admin only, as the configuration holds credentials.
+fn config_profile_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "config" / "profiles" / String)
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .then(|name: String, _claims: Claims, ctx: ApiContext| async move {
+            match ctx.profiles.read().await.profile(&name) {
+                Some(config) => warp::reply::json(config).into_response(),
+                None => error(StatusCode::NOT_FOUND, &ConfigError::UnknownProfile(name).to_string()),
+            }
+        })
+        .boxed()
+}
+
+/// `POST /api/config/profiles/<name>/activate`: switch to a profile
+///
+/// Like `PUT /api/config`, the profile takes effect on the next restart.
+fn activate_config_profile_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "config" / "profiles" / String / "activate")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Admin))
+        .and(with_context(ctx))
+        .then(|name: String, claims: Claims, ctx: ApiContext| async move {
+            let (diff, config) = {
+                let mut profiles = ctx.profiles.write().await;
+                match profiles.activate_profile(&name) {
+                    Ok(diff) => (diff, profiles.active_config().clone()),
+                    Err(e) => return error(StatusCode::NOT_FOUND, &e.to_string()),
+                }
+            };
+            let unchanged = {
+                let mut current = ctx.config.write().await;
+                let unchanged = serde_json::to_value(&*current).ok() == serde_json::to_value(&config).ok();
+                *current = config;
+                unchanged
+            };
+            tracing::info!("{} activated configuration profile {}: {:?}", claims.sub, name, diff);
+            warp::reply::json(&json!({
+                "active": name,
+                "restart_required": !unchanged,
+                "hardware_restart_required": diff.requires_hardware_restart(),
+                "motion_replan_required": diff.requires_motion_replan(),
+            }))
+            .into_response()
+        })
+        .boxed()
+}
+
+/// `GET /api/shapers/presets`: the input shaper preset library
+fn shaper_presets_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "shapers" / "presets")
+        .and(warp::get())
+        .and(ctx.require(AuthPermission::ReadOnly))
+        .and(with_context(ctx))
+        .then(|_claims: Claims, ctx: ApiContext| async move {
+            warp::reply::json(&json!({ "presets": ctx.gcode.shaper_presets() })).into_response()
+        })
+        .boxed()
+}
+
+/// `POST /api/shapers/presets`: add a preset, replacing any with the same name
+fn add_shaper_preset_route(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    warp::path!("api" / "shapers" / "presets")
+        .and(warp::post())
+        .and(ctx.require(AuthPermission::Operator))
+        .and(with_context(ctx))
+        .and(warp::body::json())
+        .then(|claims: Claims, ctx: ApiContext, preset: ShaperPreset| async move {
+            let name = preset.name.clone();
+            if let Err(e) = ctx.gcode.add_shaper_preset(preset) {
+                return error(StatusCode::BAD_REQUEST, &e.to_string());
+            }
+            tracing::info!("{} saved shaper preset {}", claims.sub, name);
+            warp::reply::with_status(warp::reply(), StatusCode::CREATED).into_response()
+        })
+        .boxed()
+}
+
+pub(crate) fn with_context(ctx: ApiContext) -> impl Filter<Extract = (ApiContext,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || ctx.clone())
+}
+
+/// JSON `{"error": message}` response
+pub(crate) fn error(status: StatusCode, message: &str) -> Response {
+    warp::reply::with_status(warp::reply::json(&json!({ "error": message })), status).into_response()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::{UserConfig, WebConfig};
+    use crate::motion::{MotionConfig, MotionController};
+
+    pub(crate) fn test_context(prometheus_enabled: bool) -> (ApiContext, watch::Sender<MotionPlannerStats>) {
+        test_context_with(WebConfig {
+            prometheus_enabled,
+            ..WebConfig::default()
+        })
+    }
+
+    pub(crate) fn test_context_with(web: WebConfig) -> (ApiContext, watch::Sender<MotionPlannerStats>) {
+        let (stats_tx, stats_rx) = watch::channel(MotionPlannerStats::default());
+        let state = Arc::new(RwLock::new(PrinterState::new()));
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.web = web;
+        let hardware = HardwareManager::new(config.clone());
+        let motion = MotionController::new(state.clone(), hardware.clone(), MotionConfig::new_from_printer_config(&config));
+        let position = motion.get_planner().subscribe_position();
+        let gcode = GCodeProcessor::new(state.clone(), motion);
+        (ApiContext::new(&config, state, stats_rx, position, hardware, gcode).unwrap(), stats_tx)
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let (ctx, stats_tx) = test_context(true);
+        {
+            let mut state = ctx.state.write().await;
+            state.temperature = 210.5;
+            state.bed_temperature = 60.0;
+            state.print_progress = 0.25;
+            state.gcode_commands = 42;
+            state.fan.set_rpm(4800.0);
+        }
+        stats_tx.send_replace(MotionPlannerStats { queue_depth: 7, underruns: 2 });
+
+        let response = warp::test::request().path("/metrics").reply(&routes(ctx)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+
+        // name (with labels) -> value, skipping comments
+        let mut samples = std::collections::HashMap::new();
+        for line in body.lines().filter(|line| !line.starts_with('#') && !line.is_empty()) {
+            let (name, value) = line.rsplit_once(' ').unwrap();
+            let value: f64 = value.parse().unwrap_or_else(|_| panic!("invalid value in {:?}", line));
+            samples.insert(name.to_string(), value);
+        }
+
+        assert_eq!(samples["krusty_hotend_temperature_celsius"], 210.5);
+        assert_eq!(samples["krusty_bed_temperature_celsius"], 60.0);
+        assert_eq!(samples["krusty_print_progress_ratio"], 0.25);
+        assert_eq!(samples["krusty_motion_queue_depth"], 7.0);
+        assert_eq!(samples["krusty_gcode_commands_total"], 42.0);
+        assert_eq!(samples["krusty_thermal_runaway_events_total"], 0.0);
+        assert_eq!(samples["krusty_motion_underruns_total"], 2.0);
+        assert_eq!(samples["krusty_fan_rpm{fan=\"part\"}"], 4800.0);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_disabled() {
+        let (ctx, _stats_tx) = test_context(false);
+        let response = warp::test::request().path("/metrics").reply(&routes(ctx)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_hardware_stats_endpoint() {
+        let (ctx, _stats_tx) = test_context(false);
+        let mut hardware = ctx.hardware.clone();
+        hardware.connect().await.unwrap();
+        hardware.send_command("reset").await.unwrap();
+
+        let response = warp::test::request().path("/api/hardware/stats").reply(&routes(ctx)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let stats: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(stats["total_commands"], 1);
+        assert_eq!(stats["latency_histogram"].as_array().unwrap().len(), 16);
+        assert!(stats["p99_latency_ms"].as_f64().unwrap() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_health_and_system_info() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx);
+
+        let response = warp::test::request().path("/api/health").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(response.body()).unwrap(), json!({ "status": "ok" }));
+
+        let response = warp::test::request().path("/api/system/info").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let info: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        for field in ["os_name", "os_version", "krusty_version"] {
+            assert!(!info[field].as_str().unwrap().is_empty(), "{} is empty", field);
+        }
+        for field in ["memory_used_mb", "memory_total_mb", "disk_used_gb", "disk_total_gb", "uptime_secs"] {
+            assert!(info[field].as_f64().unwrap() > 0.0, "{} is zero", field);
+        }
+        assert!(info["cpu_usage_pct"].as_f64().is_some());
+    }
+
+    /// Auth enabled with one user per role, each with password "pw"
+    async fn rbac_routes() -> (BoxedFilter<(Response,)>, ApiContext) {
+        rbac_routes_with(WebConfig::default()).await
+    }
+
+    async fn rbac_routes_with(web: WebConfig) -> (BoxedFilter<(Response,)>, ApiContext) {
+        let users = ["viewer", "operator", "admin"]
+            .into_iter()
+            .zip([AuthPermission::ReadOnly, AuthPermission::Operator, AuthPermission::Admin])
+            .map(|(username, role)| UserConfig {
+                username: username.to_string(),
+                password_hash: crate::web::auth::test_password_hash("pw"),
+                role,
+            })
+            .collect();
+        let (ctx, _stats_tx) = test_context_with(WebConfig { users, ..web });
+        ctx.hardware.clone().connect().await.unwrap();
+        ctx.state.write().await.ready = true;
+        (routes(ctx.clone()), ctx)
+    }
+
+    async fn login(routes: &BoxedFilter<(Response,)>, username: &str) -> String {
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/auth/login")
+            .json(&json!({ "username": username, "password": "pw" }))
+            .reply(routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        format!("Bearer {}", body["access_token"].as_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_login() {
+        let (routes, _ctx) = rbac_routes().await;
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/auth/login")
+            .json(&json!({ "username": "admin", "password": "wrong" }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = warp::test::request().path("/api/temperature").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = warp::test::request()
+            .path("/api/temperature")
+            .header("authorization", "Bearer not-a-token")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_permissions() {
+        let (routes, ctx) = rbac_routes().await;
+        let tokens = [
+            login(&routes, "viewer").await,
+            login(&routes, "operator").await,
+            login(&routes, "admin").await,
+        ];
+
+        // (method, path, body, minimum role index)
+        let endpoints = [
+            ("GET", "/api/temperature", String::new(), 0),
+            ("GET", "/api/hardware/stats", String::new(), 0),
+            ("POST", "/api/gcode", json!({ "command": "M104 S200" }).to_string(), 1),
+            ("POST", "/api/hardware/reset", String::new(), 2),
+            ("PUT", "/api/config", include_str!("../printer.toml").to_string(), 2),
+        ];
+        for (method, path, body, minimum) in endpoints {
+            for (role, token) in tokens.iter().enumerate() {
+                let response = warp::test::request()
+                    .method(method)
+                    .path(path)
+                    .header("authorization", token)
+                    .header("content-type", "application/json")
+                    .body(body.clone())
+                    .reply(&routes)
+                    .await;
+                let expected = if role >= minimum { StatusCode::OK } else { StatusCode::FORBIDDEN };
+                assert_eq!(response.status(), expected, "{} {} as role {}", method, path, role);
+            }
+        }
+
+        // Only the operator's and admin's G-code ran
+        assert_eq!(ctx.state.read().await.temperature, 200.0);
+        assert_eq!(ctx.hardware.get_command_stats().mcu_reset_count, 1);
+    }
+
+    /// Value of the refresh cookie set by a response
+    fn refresh_cookie(response: &warp::http::Response<warp::hyper::body::Bytes>) -> String {
+        let cookie = response.headers()["set-cookie"].to_str().unwrap();
+        assert!(cookie.contains("HttpOnly"));
+        cookie.split(';').next().unwrap().to_string()
+    }
+
+    async fn post_with_cookie(
+        routes: &BoxedFilter<(Response,)>,
+        path: &str,
+        cookie: &str,
+    ) -> warp::http::Response<warp::hyper::body::Bytes> {
+        warp::test::request().method("POST").path(path).header("cookie", cookie).reply(routes).await
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotation() {
+        let (routes, _ctx) = rbac_routes().await;
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/auth/login")
+            .json(&json!({ "username": "operator", "password": "pw" }))
+            .reply(&routes)
+            .await;
+        let first = refresh_cookie(&response);
+        assert!(response.headers()["set-cookie"].to_str().unwrap().contains("Max-Age=2592000"));
+
+        // Normal rotation: new access token and a different refresh token
+        let response = post_with_cookie(&routes, "/api/auth/refresh", &first).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["expires_in"], 900);
+        let second = refresh_cookie(&response);
+        assert_ne!(second, first);
+        let access = format!("Bearer {}", body["access_token"].as_str().unwrap());
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/gcode")
+            .header("authorization", &access)
+            .json(&json!({ "command": "G90" }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Replaying the rotated token fails and also kills the current one
+        let response = post_with_cookie(&routes, "/api/auth/refresh", &first).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = post_with_cookie(&routes, "/api/auth/refresh", &second).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_logout_revokes_refresh_token() {
+        let (routes, _ctx) = rbac_routes().await;
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/auth/login")
+            .json(&json!({ "username": "viewer", "password": "pw" }))
+            .reply(&routes)
+            .await;
+        let cookie = refresh_cookie(&response);
+
+        let response = post_with_cookie(&routes, "/api/auth/logout", &cookie).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response.headers()["set-cookie"].to_str().unwrap().contains("Max-Age=0"));
+        let response = post_with_cookie(&routes, "/api/auth/refresh", &cookie).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_per_ip() {
+        const LIMIT: usize = 30;
+        let (ctx, _stats_tx) = test_context_with(WebConfig {
+            rate_limit_per_ip_per_min: LIMIT as u32,
+            ..WebConfig::default()
+        });
+        let routes = routes(ctx);
+
+        let client: SocketAddr = "192.0.2.7:50000".parse().unwrap();
+        let mut statuses = Vec::new();
+        for _ in 0..100 {
+            let response = warp::test::request().path("/api/temperature").remote_addr(client).reply(&routes).await;
+            statuses.push(response.status());
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert_eq!(response.headers()["retry-after"], "2");
+            }
+        }
+        assert!(statuses[..LIMIT].iter().all(|status| *status == StatusCode::OK));
+        assert!(statuses[LIMIT..].iter().all(|status| *status == StatusCode::TOO_MANY_REQUESTS));
+
+        // Other clients have their own bucket
+        let other: SocketAddr = "192.0.2.8:50000".parse().unwrap();
+        let response = warp::test::request().path("/api/temperature").remote_addr(other).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // X-Forwarded-For is ignored unless trusted
+        let response = warp::test::request()
+            .path("/api/temperature")
+            .remote_addr(client)
+            .header("x-forwarded-for", "198.51.100.1")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Behind a proxy, prepending a fake address doesn't get a new bucket
+        let (ctx, _stats_tx) = test_context_with(WebConfig {
+            rate_limit_per_ip_per_min: LIMIT as u32,
+            trust_forwarded_for: true,
+            ..WebConfig::default()
+        });
+        let proxied = super::routes(ctx);
+        let proxy: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let mut statuses = Vec::new();
+        for i in 0..LIMIT + 1 {
+            let response = warp::test::request()
+                .path("/api/temperature")
+                .remote_addr(proxy)
+                .header("x-forwarded-for", format!("10.0.0.{}, 192.0.2.7", i))
+                .reply(&proxied)
+                .await;
+            statuses.push(response.status());
+        }
+        assert_eq!(statuses[LIMIT], StatusCode::TOO_MANY_REQUESTS);
+        let response = warp::test::request()
+            .path("/api/temperature")
+            .remote_addr(proxy)
+            .header("x-forwarded-for", "192.0.2.8")
+            .reply(&proxied)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_per_user() {
+        let (routes, _ctx) = rbac_routes_with(WebConfig {
+            rate_limit_per_ip_per_min: 1,
+            rate_limit_gcode_per_user_per_min: 2,
+            ..WebConfig::default()
+        })
+        .await;
+        let token = login(&routes, "operator").await;
+
+        // Authenticated requests don't use up the IP bucket
+        for _ in 0..5 {
+            let response = warp::test::request()
+                .path("/api/temperature")
+                .header("authorization", &token)
+                .reply(&routes)
+                .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // G-code has its own, tighter limit
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            let response = warp::test::request()
+                .method("POST")
+                .path("/api/gcode")
+                .header("authorization", &token)
+                .json(&json!({ "command": "G90" }))
+                .reply(&routes)
+                .await;
+            statuses.push(response.status());
+        }
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::OK, StatusCode::TOO_MANY_REQUESTS]);
+    }
+
+    #[tokio::test]
+    async fn test_set_motion_mode() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let set = |mode: &str| {
+            warp::test::request()
+                .method("PUT")
+                .path("/api/motion/mode")
+                .json(&json!({ "mode": mode }))
+                .reply(&routes)
+        };
+
+        assert_eq!(set("fastest").await.status(), StatusCode::BAD_REQUEST);
+        let response = set("adaptive").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "mode": "adaptive" }));
+        assert_eq!(ctx.gcode.motion_mode().await, MotionMode::Adaptive);
+
+        ctx.state.write().await.job = Some(PrintJob::new("part.gcode", 0));
+        assert_eq!(set("basic").await.status(), StatusCode::CONFLICT);
+        assert_eq!(ctx.gcode.motion_mode().await, MotionMode::Adaptive);
+    }
+
+    #[tokio::test]
+    async fn test_gcode_cancel_wait() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let send = |command: &'static str| {
+            let routes = routes.clone();
+            async move {
+                warp::test::request()
+                    .method("POST")
+                    .path("/api/gcode")
+                    .json(&json!({ "command": command }))
+                    .reply(&routes)
+                    .await
+            }
+        };
+
+        // M600 holds the command lock until M108 ends its wait
+        let change = tokio::spawn(send("M600"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!change.is_finished());
+        let cancel = tokio::time::timeout(Duration::from_secs(5), send("M108")).await.unwrap();
+        assert_eq!(cancel.status(), StatusCode::OK);
+        let change = tokio::time::timeout(Duration::from_secs(5), change).await.unwrap().unwrap();
+        assert_eq!(change.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_gcode_batch() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let commands: Vec<String> = (1..=10).map(|i| format!("G92 X{}", i)).collect();
+        let status = |id: u64| {
+            let routes = routes.clone();
+            async move {
+                let response = warp::test::request().path(&format!("/api/gcode/batch/{}/status", id)).reply(&routes).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()
+            }
+        };
+
+        // Held by another source, the batch waits its turn
+        let other = ctx.gcode.lock_commands().await;
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/gcode/batch")
+            .json(&json!({ "commands": commands }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let id = serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()["id"].as_u64().unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let queued = status(id).await;
+        assert_eq!((queued["state"].as_str(), queued["executed"].as_u64()), (Some("queued"), Some(0)));
+        drop(other);
+
+        let mut done = status(id).await;
+        for _ in 0..100 {
+            if done["state"] == "completed" {
+                break;
+            }
+            assert!(done["executed"].as_u64().unwrap() < 10);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            done = status(id).await;
+        }
+        assert_eq!(done, json!({ "id": id, "state": "completed", "total": 10, "executed": 10, "error": null }));
+        let executed: Vec<String> = ctx.gcode.history_entries().into_iter().map(|entry| entry.command).collect();
+        assert_eq!(executed, commands);
+
+        let too_many = vec!["G4 P0"; 501];
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/gcode/batch")
+            .json(&json!({ "commands": too_many }))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = warp::test::request().path("/api/gcode/batch/99/status").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_gcode_history() {
+        let (routes, _ctx) = rbac_routes().await;
+        let token = login(&routes, "operator").await;
+        for i in 0..50 {
+            let command = match i % 10 {
+                3 => "M852 Ibogus".to_string(),
+                7 => "M145".to_string(),
+                9 => "M9999".to_string(),
+                _ => format!("G92 X{}", i),
+            };
+            warp::test::request()
+                .method("POST")
+                .path("/api/gcode")
+                .header("authorization", &token)
+                .json(&json!({ "command": command }))
+                .reply(&routes)
+                .await;
+        }
+
+        let history = |query: &'static str| {
+            let routes = routes.clone();
+            let token = token.clone();
+            async move {
+                let response = warp::test::request()
+                    .path(&format!("/api/gcode/history?{}", query))
+                    .header("authorization", &token)
+                    .reply(&routes)
+                    .await;
+                assert_eq!(response.status(), StatusCode::OK);
+                serde_json::from_slice::<Vec<serde_json::Value>>(response.body()).unwrap()
+            }
+        };
+
+        let errors = history("status=err&limit=1000").await;
+        assert_eq!(errors.len(), 10);
+        assert!(errors.iter().all(|entry| entry["result"]["status"] == "err"));
+        assert!(errors.iter().all(|entry| ["M852 Ibogus", "M145"].contains(&entry["command"].as_str().unwrap())));
+        assert_eq!(history("status=warn").await.len(), 5);
+
+        let page = history("limit=2&offset=1").await;
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0]["command"], "G92 X48");
+        assert_eq!(page[1]["command"], "M145");
+        assert!(page[0]["timestamp"].as_u64().unwrap() > 0);
+        assert!(page[0]["duration_us"].is_u64());
+
+        let response = warp::test::request()
+            .path("/api/gcode/history?status=bogus")
+            .header("authorization", &token)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = warp::test::request()
+            .path("/api/gcode/history/export")
+            .header("authorization", &token)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        let lines: Vec<&str> = std::str::from_utf8(response.body()).unwrap().lines().collect();
+        assert_eq!(lines.len(), 50);
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["command"], "G92 X0");
+    }
+
+    #[tokio::test]
+    async fn test_temperature_reports_chamber() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let response = warp::test::request().path("/api/temperature").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["chamber"], serde_json::Value::Null);
+
+        let chamber: crate::config::ChamberConfig = toml::from_str(r#"
+            heater_pin = "PB10"
+            sensor_type = "EPCOS 100K B57560G104F"
+            sensor_pin = "PA5"
+        "#).unwrap();
+        let mut heater = crate::temperature::Heater::from_chamber_config(&chamber);
+        heater.set_target(45.0).unwrap();
+        heater.update(30.0, 1.0).unwrap();
+        ctx.state.write().await.chamber = Some(heater);
+
+        let response = warp::test::request().path("/api/temperature").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["chamber"]["temperature"], 30.0);
+        assert_eq!(body["chamber"]["target"], 45.0);
+        assert!(body["chamber"]["power"].as_f64().unwrap() > 0.0);
+        assert_eq!(body["chamber"]["fault"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_temperature_history() {
+        let (ctx, _stats_tx) = test_context(false);
+        let now = unix_millis();
+        {
+            let mut history = ctx.temperature_history.lock().unwrap();
+            for second in 0..60u64 {
+                history.record(crate::temperature::TemperatureSample {
+                    timestamp: now - (59 - second) * 1000,
+                    hotend_temp: 20.0 + second as f32 * 3.0,
+                    hotend_target: 200.0,
+                    bed_temp: 60.0,
+                    bed_target: 60.0,
+                });
+            }
+        }
+        let routes = routes(ctx);
+
+        let response = warp::test::request().path("/api/temperature/history?seconds=30").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let samples = body.as_array().unwrap();
+        assert_eq!(samples.len(), 30);
+        assert_eq!(samples[0]["hotend_temp"], 110.0);
+        assert_eq!(samples[29]["timestamp"], now);
+        assert_eq!(samples[29]["hotend_target"], 200.0);
+
+        let response = warp::test::request().path("/api/temperature/history").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 60);
+
+        let response = warp::test::request().path("/api/temperature/history.csv?seconds=10").reply(&routes).await;
+        assert_eq!(response.headers()["content-type"], "text/csv");
+        let csv = std::str::from_utf8(response.body()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "timestamp,hotend_temp,hotend_target,bed_temp,bed_target");
+        assert_eq!(lines[10], format!("{},197.00,200.00,60.00,60.00", now));
+    }
+
+    #[tokio::test]
+    async fn test_file_thumbnail() {
+        let dir = std::env::temp_dir().join(format!("krusty-thumbnails-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/thumbnail.gcode"), dir.join("benchy.gcode")).unwrap();
+        std::fs::write(dir.join("plain.gcode"), "G28\n").unwrap();
+        let (ctx, _stats_tx) = test_context_with(WebConfig {
+            upload_dir: dir.to_string_lossy().into_owned(),
+            ..WebConfig::default()
+        });
+        let routes = routes(ctx);
+        let thumbnail = |name: &'static str| warp::test::request().path(&format!("/api/files/{}/thumbnail", name)).reply(&routes);
+
+        let response = thumbnail("benchy.gcode").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        let png = response.body();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // IHDR: 32x32
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 0, 32, 0, 0, 0, 32]);
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+
+        assert_eq!(thumbnail("plain.gcode").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(thumbnail("missing.gcode").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(thumbnail("..").await.status(), StatusCode::BAD_REQUEST);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hardware_flash() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("krusty-flash-api-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("dfu-util");
+        // Keep a copy of the image it was given (the last argument) and
+        // note where it was
+        std::fs::write(&script, format!(
+            "#!/bin/sh\nfor arg; do image=$arg; done\ncp \"$image\" {0}/flashed\ndirname \"$image\" > {0}/image_dir\n",
+            dir.display()
+        )).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (mut ctx, _stats_tx) = test_context(false);
+        let mut config: Config = toml::from_str(include_str!("../printer.toml")).unwrap();
+        config.mcu.dfu_util_path = Some(script.to_string_lossy().into_owned());
+        ctx.hardware = HardwareManager::new(config);
+        let mut events = ctx.events.subscribe();
+        let routes = routes(ctx);
+        let flash = |part: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/api/hardware/flash?method=dfu_util")
+                .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+                .body(format!(
+                    "--BOUNDARY\r\n\
+                     Content-Disposition: form-data; name=\"{}\"; filename=\"klipper.bin\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n\
+                     FIRMWARE\r\n\
+                     --BOUNDARY--\r\n",
+                    part
+                ))
+                .reply(&routes)
+        };
+
+        assert_eq!(flash("file").await.status(), StatusCode::BAD_REQUEST);
+        let response = flash("firmware").await;
+        assert_eq!(response.status(), StatusCode::OK, "{:?}", response.body());
+        assert_eq!(std::fs::read_to_string(dir.join("flashed")).unwrap(), "FIRMWARE");
+        // Uploaded to a private directory, removed once flashed
+        let image_dir = std::fs::read_to_string(dir.join("image_dir")).unwrap();
+        let image_dir = Path::new(image_dir.trim());
+        assert!(image_dir.file_name().unwrap().to_string_lossy().starts_with("krusty-firmware-"));
+        assert!(!image_dir.exists());
+        let private = create_private_temp_dir("krusty-flash-test").unwrap();
+        assert_eq!(std::fs::metadata(&private).unwrap().permissions().mode() & 0o777, 0o700);
+        std::fs::remove_dir(&private).unwrap();
+        let mut stages = Vec::new();
+        while let Ok(PrinterEvent::FirmwareFlash { stage, .. }) = events.try_recv() {
+            stages.push(stage);
+        }
+        assert_eq!(stages, ["entering_bootloader", "done"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_job_start_validation() {
+        let dir = std::env::temp_dir().join(format!("krusty-jobs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("part.gcode"), "M106 S128\n").unwrap();
+        let (ctx, _stats_tx) = test_context_with(WebConfig {
+            upload_dir: dir.to_string_lossy().into_owned(),
+            ..WebConfig::default()
+        });
+        let routes = routes(ctx.clone());
+        let start = |path: &'static str| {
+            warp::test::request()
+                .method("POST")
+                .path("/api/jobs/start")
+                .json(&json!({ "path": path }))
+                .reply(&routes)
+        };
+
+        assert_eq!(start("../part.gcode").await.status(), StatusCode::BAD_REQUEST);
+
+        // Cold and unhomed, and the file does neither
+        let response = start("part.gcode").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["started"], false);
+        let findings = body["findings"].as_array().unwrap();
+        assert_eq!(findings.len(), 2);
+        assert!(findings.iter().all(|finding| finding["severity"] == "error"));
+        assert!(ctx.state.read().await.job.is_none());
+
+        let response = start("missing.gcode").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        {
+            let mut state = ctx.state.write().await;
+            state.homed = true;
+            state.temperature = 200.0;
+        }
+        let mut events = ctx.events.subscribe();
+        let response = start("part.gcode").await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "started": true, "findings": [] }));
+
+        let finished = async {
+            loop {
+                if let Some(job) = ctx.state.read().await.job.as_ref().filter(|job| !job.state().is_active()) {
+                    return job.state().clone();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        let finished = tokio::time::timeout(Duration::from_secs(5), finished).await.unwrap();
+        assert_eq!(finished, crate::print_job::PrintJobState::Completed);
+        assert_eq!(events.try_recv().unwrap().event_type(), "print_started");
+        // Sent once the job has been marked completed
+        let completed = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+        assert_eq!(completed.event_type(), "print_completed");
+
+        // Only one print at a time
+        ctx.state.write().await.job = Some(PrintJob::new("other.gcode", 0));
+        assert_eq!(start("part.gcode").await.status(), StatusCode::CONFLICT);
+
+        // Changed since upload
+        ctx.state.write().await.job = None;
+        let path = dir.join("part.gcode").to_string_lossy().into_owned();
+        ctx.files.store_checksum(&path).await.unwrap();
+        std::fs::write(&path, "M106 S255\n").unwrap();
+        let response = start("part.gcode").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(events.try_recv().unwrap().event_type(), "file_integrity_error");
+        assert!(events.try_recv().is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_job_dry_run() {
+        let dir = std::env::temp_dir().join(format!("krusty-dry-run-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dry_run.gcode"), dir.join("part.gcode")).unwrap();
+        std::fs::write(dir.join("bad.gcode"), "G28\nM141 S40\n").unwrap();
+        let (ctx, _stats_tx) = test_context_with(WebConfig {
+            upload_dir: dir.to_string_lossy().into_owned(),
+            ..WebConfig::default()
+        });
+        let routes = routes(ctx.clone());
+        let dry_run = |path: &'static str| {
+            warp::test::request()
+                .method("POST")
+                .path("/api/jobs/dry-run")
+                .json(&json!({ "path": path }))
+                .reply(&routes)
+        };
+
+        let response = dry_run("part.gcode").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["commands"], 16);
+        assert_eq!(body["final_position"], json!([0.0, 0.0, 10.0, 3.0]));
+        assert!((body["total_distance"].as_f64().unwrap() - 140.0).abs() < 1e-9);
+        // Nothing moved
+        assert_eq!(ctx.state.read().await.position, [0.0; 3]);
+        assert!(!ctx.state.read().await.dry_run);
+
+        // No chamber to heat
+        assert_eq!(dry_run("bad.gcode").await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(dry_run("missing.gcode").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(dry_run("../part.gcode").await.status(), StatusCode::BAD_REQUEST);
+
+        ctx.state.write().await.job = Some(PrintJob::new("other.gcode", 0));
+        assert_eq!(dry_run("part.gcode").await.status(), StatusCode::CONFLICT);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_iso8601() {
+        assert_eq!(parse_iso8601_ms("1970-01-01T00:00:01.5Z"), Some(1500));
+        assert_eq!(parse_iso8601_ms("2024-02-29T12:00:00+02:00"), Some(1_709_200_800_000));
+        assert_eq!(parse_iso8601_ms("2024-02-29T12:00:00 02:00"), Some(1_709_200_800_000));
+        assert_eq!(parse_iso8601_ms("1969-12-31T23:00:00-01:00"), Some(0));
+        assert_eq!(parse_iso8601_ms("1969-12-31T23:59:59Z"), None);
+        assert_eq!(parse_iso8601_ms("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_iso8601_ms("2024-01-01T00:00:00"), None);
+        assert_eq!(parse_iso8601_ms("2024-01-01"), None);
+    }
+
+    #[tokio::test]
+    async fn test_event_log() {
+        let (ctx, _stats_tx) = test_context(false);
+        let response = warp::test::request().path("/api/events/log").reply(&routes(ctx.clone())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let dir = std::env::temp_dir().join(format!("krusty-api-event-log-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut log = PrinterEventLog::spawn(crate::simulator::EventLogConfig::new(dir.join("events.jsonl")));
+        for i in 0..1000 {
+            let event = if i % 4 == 0 {
+                PrinterEvent::PrintFailed { path: format!("part{}.gcode", i), reason: "clog".to_string() }
+            } else {
+                PrinterEvent::StepperStalled { axis: i % 3 }
+            };
+            log.log(event.to_json());
+        }
+        log.finish().await.unwrap();
+        let routes = routes(ctx.with_event_log(Arc::new(log)));
+        let query = |query: &str| {
+            let request = warp::test::request().path(&format!("/api/events/log{}", query));
+            async { request.reply(&routes).await }
+        };
+
+        let response = query("").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(entries.len(), 1000);
+        assert_eq!(entries[0]["event"]["event"], "print_failed");
+        assert_eq!(entries[0]["event"]["data"]["path"], "part0.gcode");
+
+        let response = query("?type=print_failed&from=2000-01-01T00:00:00Z").await;
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(entries.len(), 250);
+        assert!(entries.iter().all(|entry| entry["event"]["event"] == "print_failed"));
+
+        let response = query("?type=print_failed,stepper_stalled&to=2000-01-01T00:00:00Z").await;
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(response.body()).unwrap();
+        assert!(entries.is_empty());
+
+        assert_eq!(query("?from=yesterday").await.status(), StatusCode::BAD_REQUEST);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_file_info() {
+        let dir = std::env::temp_dir().join(format!("krusty-file-info-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let gcode = "\
+; generated by PrusaSlicer 2.6.1+linux-x64-GTK3 on 2023-10-01 at 12:00:00 UTC
+G28
+; filament used [mm] = 2340.5
+; estimated printing time (normal mode) = 1h 23m
+; nozzle_diameter = 0.4
+";
+        std::fs::write(dir.join("part.gcode"), gcode).unwrap();
+        let (ctx, _stats_tx) = test_context_with(WebConfig {
+            upload_dir: dir.to_string_lossy().into_owned(),
+            ..WebConfig::default()
+        });
+        let routes = routes(ctx);
+        let info = |name: &'static str| warp::test::request().path(&format!("/api/files/{}/info", name)).reply(&routes);
+
+        let response = info("part.gcode").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["name"], "part.gcode");
+        assert_eq!(body["size"], gcode.len());
+        assert_eq!(body["metadata"]["slicer"], "PrusaSlicer");
+        assert_eq!(body["metadata"]["estimated_time_secs"], 3600 + 23 * 60);
+        assert_eq!(body["metadata"]["filament_used_mm"], 2340.5);
+        assert_eq!(body["metadata"]["nozzle_diameter"], 0.4);
+        assert_eq!(body["metadata"]["layer_count"], serde_json::Value::Null);
+
+        assert_eq!(info("missing.gcode").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(info("..").await.status(), StatusCode::BAD_REQUEST);
+
+        // Not uploaded through the API, so nothing to compare prints against
+        let response = warp::test::request().path("/api/files/part.gcode/checksum").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_calibration_steps() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let response = warp::test::request().path("/api/calibration/steps").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let current: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!((current["x"].as_f64(), current["z"].as_f64()), (Some(80.0), Some(400.0)));
+
+        let set = |body: serde_json::Value| {
+            warp::test::request()
+                .method("POST")
+                .path("/api/calibration/steps")
+                .json(&body)
+                .reply(&routes)
+        };
+        let response = set(json!({ "z": 410.0 })).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(updated["z"], 410.0);
+        assert_eq!(updated["x"], current["x"]);
+        assert_eq!(ctx.gcode.steps_per_mm()[2], 410.0);
+
+        assert_eq!(set(json!({ "z": 4000.0 })).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(set(json!({})).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(ctx.gcode.steps_per_mm()[2], 410.0);
+    }
+
+    #[tokio::test]
+    async fn test_pause_conditions() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        ctx.gcode.clone().process_command("M226 L5 Z2.4").await.unwrap();
+
+        let response = warp::test::request().path("/api/pause-conditions").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["conditions"][0], json!({ "id": 1, "condition": { "type": "at_layer", "value": 5 } }));
+        assert_eq!(body["conditions"][1]["condition"]["type"], "at_height");
+
+        let delete = |id: u64| {
+            warp::test::request()
+                .method("DELETE")
+                .path(&format!("/api/pause-conditions/{}", id))
+                .reply(&routes)
+        };
+        assert_eq!(delete(1).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(delete(1).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(ctx.state.read().await.pause_conditions.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shaper_presets() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let post = |body: serde_json::Value| {
+            warp::test::request().method("POST").path("/api/shapers/presets").json(&body).reply(&routes)
+        };
+        let preset = json!({ "name": "bench", "shaper_type": "mzv", "frequency": 48.5, "damping": 0.1 });
+        assert_eq!(post(preset.clone()).await.status(), StatusCode::CREATED);
+        assert_eq!(post(json!({ "name": "bad", "shaper_type": "zv", "frequency": -1.0, "damping": 0.1 })).await.status(), StatusCode::BAD_REQUEST);
+
+        let response = warp::test::request().path("/api/shapers/presets").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["presets"].as_array().unwrap().len(), 1);
+        assert_eq!(body["presets"][0]["shaper_type"], "mzv");
+        assert_eq!(body["presets"][0]["target_printer_model"], serde_json::Value::Null);
+
+        // Presets are shared with the processor running M593
+        ctx.gcode.clone().process_command("M593 PRESET=bench").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_config_profiles() {
+        let (ctx, _stats_tx) = test_context(false);
+        {
+            let mut profiles = ctx.profiles.write().await;
+            let mut fast = ctx.config.read().await.clone();
+            fast.printer.max_velocity = 500.0;
+            profiles.add_profile("fast", fast);
+            profiles.add_profile("same", ctx.config.read().await.clone());
+        }
+        let routes = routes(ctx.clone());
+
+        let response = warp::test::request().path("/api/config/profiles").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "active": null, "profiles": ["fast", "same"] }));
+
+        let response = warp::test::request().path("/api/config/profiles/fast").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["printer"]["max_velocity"], 500.0);
+
+        let activate = |name: &str| {
+            warp::test::request().method("POST").path(&format!("/api/config/profiles/{}/activate", name)).reply(&routes)
+        };
+        let response = activate("fast").await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["restart_required"], true);
+        assert_eq!(body["motion_replan_required"], true);
+        assert_eq!(ctx.config.read().await.printer.max_velocity, 500.0);
+        assert_eq!(ctx.profiles.read().await.active_profile(), Some("fast"));
+
+        assert_eq!(activate("missing").await.status(), StatusCode::NOT_FOUND);
+        let response = warp::test::request().path("/api/config/profiles/missing").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_config_update() {
+        let dir = std::env::temp_dir().join(format!("krusty-config-update-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("printer.toml");
+        std::fs::write(&path, include_str!("../printer.toml")).unwrap();
+        let (ctx, _stats_tx) = test_context(false);
+        let ctx = ctx.with_config_manager(Arc::new(RwLock::new(ConfigManager::load(&path).unwrap())));
+        let routes = routes(ctx.clone());
+        let put = |config: &Config| {
+            warp::test::request().method("PUT").path("/api/config").body(toml::to_string(config).unwrap()).reply(&routes)
+        };
+
+        let mut config = ConfigManager::load(&path).unwrap().active_config().clone();
+        config.printer.max_velocity = 450.0;
+        let response = put(&config).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["restart_required"], true);
+        assert_eq!(body["motion_replan_required"], true);
+
+        // The change is in the file the next start reads
+        let reloaded = ConfigManager::load(&path).unwrap();
+        assert_eq!(reloaded.active_config().printer.max_velocity, 450.0);
+
+        // Invalid configurations are refused with every field at fault
+        config.steppers.get_mut("stepper_x").unwrap().rotation_distance = -40.0;
+        let response = put(&config).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["errors"][0]["field_path"], "steppers.stepper_x.rotation_distance");
+        let reloaded = ConfigManager::load(&path).unwrap();
+        assert!(reloaded.active_config().steppers["stepper_x"].rotation_distance > 0.0);
+        assert!(ctx.config.read().await.steppers["stepper_x"].rotation_distance > 0.0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_config_validate() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let response = warp::test::request().path("/api/config/validate").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "valid": true, "errors": [] }));
+
+        {
+            let mut config = ctx.config.write().await;
+            config.steppers.get_mut("stepper_x").unwrap().rotation_distance = -40.0;
+            config.steppers.get_mut("stepper_y").unwrap().microsteps = 12;
+        }
+        let response = warp::test::request().path("/api/config/validate").reply(&routes).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["errors"][0]["field_path"], "steppers.stepper_x.rotation_distance");
+        assert_eq!(body["errors"][0]["severity"], "error");
+        assert_eq!(body["errors"][1]["field_path"], "steppers.stepper_y.microsteps");
+        assert_eq!(body["errors"][1]["severity"], "warning");
+    }
+
+    #[tokio::test]
+    async fn test_motion_debug_segments() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        ctx.state.write().await.homed = true;
+        let mut gcode = ctx.gcode.clone();
+        gcode.process_command("G1 X10 F100").await.unwrap();
+
+        let response = warp::test::request().path("/api/motion/debug/segments").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["segments"][0]["target"], json!([10.0, 0.0, 0.0, 0.0]));
+        assert_eq!(body["segments"][0]["motion_type"], "travel");
+        assert_eq!(body["segments"][0]["is_curve"], false);
+    }
+
+    #[tokio::test]
+    async fn test_position_drift() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx);
+
+        // No stepper in the test config has an encoder
+        let response = warp::test::request().path("/api/motion/position/drift").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "axes": [] }));
+    }
+
+    #[tokio::test]
+    async fn test_predictor_model() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let save = || warp::test::request().method("POST").path("/api/motion/predictor/save").reply(&routes);
+        assert_eq!(save().await.status(), StatusCode::NOT_FOUND);
+
+        let path = std::env::temp_dir().join(format!("krusty-api-predictor-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        ctx.config.write().await.advanced.predictor_model_path = Some(path.clone());
+        ctx.error_predictor.lock().unwrap().model_version = 5;
+        let response = save().await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "path": path, "model_version": 5 }));
+        assert_eq!(ErrorPredictor::load_or_new(Some(&path)).model_version, 5);
+
+        let response = warp::test::request().method("DELETE").path("/api/motion/predictor/model").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(ctx.error_predictor.lock().unwrap().model_version, 0);
+        assert!(!Path::new(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_set_kinematics() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        let set = |kinematics: &str| {
+            warp::test::request()
+                .method("PUT")
+                .path("/api/motion/kinematics")
+                .json(&json!({ "kinematics": kinematics }))
+                .reply(&routes)
+        };
+
+        assert_eq!(set("corexy").await.status(), StatusCode::BAD_REQUEST);
+        ctx.state.write().await.homed = true;
+        assert_eq!(set("polar").await.status(), StatusCode::BAD_REQUEST);
+        // The test config has no [delta] section
+        assert_eq!(set("delta").await.status(), StatusCode::BAD_REQUEST);
+
+        let response = set("corexy").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body, json!({ "kinematics": "corexy" }));
+        assert_eq!(ctx.gcode.kinematics_type(), KinematicsType::CoreXY);
+
+        ctx.state.write().await.job = Some(PrintJob::new("part.gcode", 0));
+        assert_eq!(set("cartesian").await.status(), StatusCode::CONFLICT);
+        assert_eq!(ctx.gcode.kinematics_type(), KinematicsType::CoreXY);
+    }
+
+    #[tokio::test]
+    async fn test_motion_recordings() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx);
+
+        let response = warp::test::request().path("/api/motion/recordings").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["recording"], false);
+
+        let response = warp::test::request().path("/api/motion/recordings/trajectory_1").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = warp::test::request().path("/api/motion/recordings/printer").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_motion_queue_segment_labels() {
+        let (ctx, _stats_tx) = test_context(false);
+        let routes = routes(ctx.clone());
+        ctx.state.write().await.homed = true;
+        let mut gcode = ctx.gcode.clone();
+        gcode.process_command("G1 X100 Y50 F3000").await.unwrap();
+        gcode.process_command("G1 X120 Y50 F3000").await.unwrap();
+
+        let response = warp::test::request().path("/api/motion/queue/segments").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let segments = body["segments"].as_array().unwrap();
+        assert_eq!(segments[0]["label"], "G1 X100 Y50 F3000");
+        assert_eq!(segments[1]["label"], "G1 X120 Y50 F3000");
+        assert!(segments[1]["sequence_number"].as_u64() > segments[0]["sequence_number"].as_u64());
+    }
+}
@@ -0,0 +1,298 @@
+// src/web/auth.rs - User authentication and role-based access control
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use warp::{Filter, Rejection};
+use crate::config::{UserConfig, WebConfig};
+use super::token_blacklist::{RefreshToken, RefreshTokenStore};
+
+/// What a user may do, each level including the ones below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthPermission {
+    /// View status
+    #[default]
+    ReadOnly,
+    /// Run G-code
+    Operator,
+    /// Change configuration and reset hardware
+    Admin,
+}
+
+/// Checks user credentials
+pub trait AuthBackend: Send + Sync {
+    /// The user's role, or `None` if the credentials are wrong
+    fn validate_with_role(&self, username: &str, password: &str) -> Option<AuthPermission>;
+
+    fn validate(&self, username: &str, password: &str) -> bool {
+        self.validate_with_role(username, password).is_some()
+    }
+}
+
+/// Accounts listed under `[[web.users]]`
+pub struct ConfigAuthBackend {
+    users: Vec<UserConfig>,
+}
+
+impl ConfigAuthBackend {
+    pub fn new(users: Vec<UserConfig>) -> Self {
+        Self { users }
+    }
+}
+
+impl AuthBackend for ConfigAuthBackend {
+    fn validate_with_role(&self, username: &str, password: &str) -> Option<AuthPermission> {
+        let user = self.users.iter().find(|user| user.username == username)?;
+        verify_password(password, &user.password_hash).then_some(user.role)
+    }
+}
+
+/// Argon2id hash of `password` with a random salt, as a PHC string for
+/// `password_hash` under `[[web.users]]`
+pub fn hash_password(password: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let salt = random_salt()?;
+    Ok(Argon2::default().hash_password(password.as_bytes(), &salt).map_err(|e| e.to_string())?.to_string())
+}
+
+fn random_salt() -> Result<SaltString, Box<dyn std::error::Error>> {
+    Ok(SaltString::encode_b64(&rand::random::<[u8; 16]>()).map_err(|e| e.to_string())?)
+}
+
+/// Whether `password` matches a PHC string from `hash_password`
+///
+/// The parameters are read from the hash and the digests are compared in
+/// constant time.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// Token payload
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Claims {
+    /// Username
+    pub sub: String,
+    pub role: AuthPermission,
+    /// Issued at (Unix seconds)
+    pub iat: u64,
+    /// Expires at (Unix seconds)
+    pub exp: u64,
+}
+
+impl Claims {
+    /// Stand-in identity used when authentication is disabled
+    pub fn anonymous() -> Self {
+        Self {
+            sub: "anonymous".to_string(),
+            role: AuthPermission::Admin,
+            iat: 0,
+            exp: u64::MAX,
+        }
+    }
+}
+
+/// Authentication failures, turned into responses by `api::routes`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthRejection {
+    /// No valid bearer token (401)
+    Unauthorized,
+    /// Valid token whose role is below the one required (403)
+    InsufficientPermission { required: AuthPermission, role: AuthPermission },
+}
+
+impl warp::reject::Reject for AuthRejection {}
+
+/// Short-lived access token plus the refresh token that renews it
+#[derive(Debug, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: RefreshToken,
+}
+
+/// Issues and verifies HS256 access tokens and their refresh tokens
+pub struct JwtAuth {
+    backend: Arc<dyn AuthBackend>,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    token_lifetime_secs: u64,
+    refresh_tokens: RefreshTokenStore,
+}
+
+impl JwtAuth {
+    pub fn new(
+        backend: Arc<dyn AuthBackend>,
+        secret: &[u8],
+        token_lifetime_secs: u64,
+        refresh_token_lifetime_secs: u64,
+    ) -> Self {
+        Self {
+            backend,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            token_lifetime_secs,
+            refresh_tokens: RefreshTokenStore::new(refresh_token_lifetime_secs),
+        }
+    }
+
+    /// Authentication for the configured users, or `None` if there are none
+    pub fn from_config(config: &WebConfig) -> Option<Arc<Self>> {
+        if config.users.is_empty() {
+            return None;
+        }
+        let secret = match &config.jwt_secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => rand::random::<[u8; 32]>().to_vec(),
+        };
+        let backend = Arc::new(ConfigAuthBackend::new(config.users.clone()));
+        Some(Arc::new(Self::new(
+            backend,
+            &secret,
+            config.token_lifetime_secs,
+            config.refresh_token_lifetime_secs,
+        )))
+    }
+
+    pub fn token_lifetime_secs(&self) -> u64 {
+        self.token_lifetime_secs
+    }
+
+    /// Check credentials and start a session for them
+    pub fn login(&self, username: &str, password: &str) -> Option<TokenPair> {
+        let role = self.backend.validate_with_role(username, password)?;
+        let access_token = self.issue(username, role).ok()?;
+        Some(TokenPair {
+            access_token,
+            refresh_token: self.refresh_tokens.issue(username, role, unix_now()),
+        })
+    }
+
+    /// Exchange a refresh token for a new pair; the old one stops working
+    pub fn refresh(&self, refresh_token: &str) -> Result<TokenPair, Box<dyn std::error::Error>> {
+        let refresh_token = self.refresh_tokens.rotate(refresh_token, unix_now())?;
+        Ok(TokenPair {
+            access_token: self.issue(&refresh_token.user_id, refresh_token.role)?,
+            refresh_token,
+        })
+    }
+
+    /// End the session a refresh token belongs to
+    pub fn logout(&self, refresh_token: &str) {
+        self.refresh_tokens.revoke(refresh_token);
+    }
+
+    pub fn issue(&self, username: &str, role: AuthPermission) -> Result<String, Box<dyn std::error::Error>> {
+        let now = unix_now();
+        let claims = Claims {
+            sub: username.to_string(),
+            role,
+            iat: now,
+            exp: now + self.token_lifetime_secs,
+        };
+        Ok(jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)?)
+    }
+
+    /// Claims of a correctly signed, unexpired token
+    pub fn verify(&self, token: &str) -> Result<Claims, Box<dyn std::error::Error>> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        Ok(jsonwebtoken::decode::<Claims>(token, &self.decoding_key, &validation)?.claims)
+    }
+}
+
+/// Require a bearer token whose role is at least `required`
+///
+/// Passes the token's claims on; with authentication disabled every
+/// request is let through as `Claims::anonymous`.
+pub fn require_permission(
+    auth: Option<Arc<JwtAuth>>,
+    required: AuthPermission,
+) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization").and_then(move |header: Option<String>| {
+        let auth = auth.clone();
+        async move {
+            let Some(auth) = auth else {
+                return Ok(Claims::anonymous());
+            };
+            let claims = header
+                .as_deref()
+                .and_then(|header| header.strip_prefix("Bearer "))
+                .and_then(|token| auth.verify(token.trim()).ok())
+                .ok_or_else(|| warp::reject::custom(AuthRejection::Unauthorized))?;
+            if claims.role < required {
+                return Err(warp::reject::custom(AuthRejection::InsufficientPermission {
+                    required,
+                    role: claims.role,
+                }));
+            }
+            Ok(claims)
+        }
+    })
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0)
+}
+
+/// Fast Argon2id hash for test accounts; the default cost takes seconds in
+/// debug builds
+#[cfg(test)]
+pub(crate) fn test_password_hash(password: &str) -> String {
+    let params = argon2::Params::new(64, 1, 1, None).unwrap();
+    let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    argon2.hash_password(password.as_bytes(), &random_salt().unwrap()).unwrap().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend() -> ConfigAuthBackend {
+        ConfigAuthBackend::new(vec![UserConfig {
+            username: "alice".to_string(),
+            password_hash: test_password_hash("hunter2"),
+            role: AuthPermission::Operator,
+        }])
+    }
+
+    #[test]
+    fn test_hash_and_verify_password() {
+        let hash = hash_password("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        // Salted
+        assert_ne!(hash, hash_password("hunter2").unwrap());
+        // Unsalted SHA-256 digests are no longer accepted
+        assert!(!verify_password("hunter2", "f52fbd32b2b3b86ff88ef6c490628285f482af15ddcb29541f94bcf526a3f6c7"));
+    }
+
+    #[test]
+    fn test_validate_with_role() {
+        let backend = backend();
+        assert_eq!(backend.validate_with_role("alice", "hunter2"), Some(AuthPermission::Operator));
+        assert_eq!(backend.validate_with_role("alice", "hunter3"), None);
+        assert!(!backend.validate("bob", "hunter2"));
+        assert!(AuthPermission::ReadOnly < AuthPermission::Operator && AuthPermission::Operator < AuthPermission::Admin);
+    }
+
+    #[test]
+    fn test_token_round_trip() {
+        let auth = JwtAuth::new(Arc::new(backend()), b"secret", 60, 600);
+        assert!(auth.login("alice", "wrong").is_none());
+        let claims = auth.verify(&auth.login("alice", "hunter2").unwrap().access_token).unwrap();
+        assert_eq!((claims.sub.as_str(), claims.role), ("alice", AuthPermission::Operator));
+        assert_eq!(claims.exp - claims.iat, 60);
+
+        // Signed with another key
+        let other = JwtAuth::new(Arc::new(backend()), b"other", 60, 600);
+        assert!(auth.verify(&other.issue("alice", AuthPermission::Admin).unwrap()).is_err());
+
+        // Expired
+        let expired = JwtAuth::new(Arc::new(backend()), b"secret", 0, 600);
+        let token = expired.issue("alice", AuthPermission::Admin).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert!(auth.verify(&token).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+// src/web/metrics.rs - Prometheus metrics for the web API
+use prometheus::{Encoder, Gauge, GaugeVec, IntCounter, IntGauge, Opts, Registry, TextEncoder};
+use crate::motion::MotionPlannerStats;
+use crate::printer::PrinterState;
+
+/// Registry of printer metrics, refreshed from the printer state on each scrape
+pub struct PrinterMetrics {
+    registry: Registry,
+    hotend_temperature: Gauge,
+    bed_temperature: Gauge,
+    print_progress: Gauge,
+    motion_queue_depth: IntGauge,
+    gcode_commands: IntCounter,
+    thermal_runaway_events: IntCounter,
+    motion_underruns: IntCounter,
+    fan_rpm: GaugeVec,
+}
+
+impl PrinterMetrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let metrics = Self {
+            hotend_temperature: Gauge::new("krusty_hotend_temperature_celsius", "Hotend temperature")?,
+            bed_temperature: Gauge::new("krusty_bed_temperature_celsius", "Heated bed temperature")?,
+            print_progress: Gauge::new("krusty_print_progress_ratio", "Print progress from 0 to 1")?,
+            motion_queue_depth: IntGauge::new("krusty_motion_queue_depth", "Segments waiting in the motion queue")?,
+            gcode_commands: IntCounter::new("krusty_gcode_commands_total", "G-code commands processed")?,
+            thermal_runaway_events: IntCounter::new(
+                "krusty_thermal_runaway_events_total",
+                "Heater faults raised by thermal runaway protection",
+            )?,
+            motion_underruns: IntCounter::new(
+                "krusty_motion_underruns_total",
+                "Times the motion queue ran dry while printing",
+            )?,
+            fan_rpm: GaugeVec::new(Opts::new("krusty_fan_rpm", "Measured fan speed"), &["fan"])?,
+            registry,
+        };
+
+        metrics.registry.register(Box::new(metrics.hotend_temperature.clone()))?;
+        metrics.registry.register(Box::new(metrics.bed_temperature.clone()))?;
+        metrics.registry.register(Box::new(metrics.print_progress.clone()))?;
+        metrics.registry.register(Box::new(metrics.motion_queue_depth.clone()))?;
+        metrics.registry.register(Box::new(metrics.gcode_commands.clone()))?;
+        metrics.registry.register(Box::new(metrics.thermal_runaway_events.clone()))?;
+        metrics.registry.register(Box::new(metrics.motion_underruns.clone()))?;
+        metrics.registry.register(Box::new(metrics.fan_rpm.clone()))?;
+        Ok(metrics)
+    }
+
+    /// Copy the current printer values into the registry
+    pub fn update(&self, state: &PrinterState, stats: &MotionPlannerStats) {
+        self.hotend_temperature.set(state.temperature);
+        self.bed_temperature.set(state.bed_temperature);
+        self.print_progress.set(state.print_progress.clamp(0.0, 1.0));
+        self.motion_queue_depth.set(stats.queue_depth as i64);
+        self.fan_rpm
+            .with_label_values(&["part"])
+            .set(state.fan.get_rpm().unwrap_or(0.0));
+
+        // The sources keep running totals; counters only move forward
+        let runaways = state.chamber.as_ref().map_or(0, |heater| heater.get_runaway_count());
+        advance_counter(&self.gcode_commands, state.gcode_commands);
+        advance_counter(&self.thermal_runaway_events, runaways);
+        advance_counter(&self.motion_underruns, stats.underruns as u64);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+}
+
+fn advance_counter(counter: &IntCounter, total: u64) {
+    let current = counter.get();
+    if total > current {
+        counter.inc_by(total - current);
+    }
+}
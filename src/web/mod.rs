@@ -1,90 +1,127 @@
 // src/web/mod.rs - Web interface for printer control
+pub mod api;
+pub mod auth;
+pub mod metrics;
+pub mod octoprint;
+pub mod rate_limiter;
+pub mod token_blacklist;
+pub mod webhooks;
+
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use crate::host_os::SystemState;
+use tokio::sync::{RwLock, broadcast, watch};
+use crate::config::{Config, ConfigManager};
+use crate::gcode::GCodeProcessor;
+use crate::hardware::HardwareManager;
+use crate::motion::MotionPlannerStats;
+use crate::printer::{PrinterEvent, PrinterEventLog, PrinterState};
+use crate::temperature::TemperatureHistoryRecorder;
+
+pub use api::ApiContext;
+pub use metrics::PrinterMetrics;
+pub use webhooks::WebhookDispatcher;
 
 /// Web interface for remote printer control
 pub struct WebInterface {
-    state: Arc<RwLock<SystemState>>,
+    config: Config,
+    state: Arc<RwLock<PrinterState>>,
+    planner_stats: watch::Receiver<MotionPlannerStats>,
+    position: watch::Receiver<[f64; 4]>,
+    hardware: HardwareManager,
+    gcode: GCodeProcessor,
+    config_manager: Option<Arc<RwLock<ConfigManager>>>,
+    events: Option<broadcast::Sender<PrinterEvent>>,
+    event_log: Option<Arc<PrinterEventLog>>,
     server_handle: Option<tokio::task::JoinHandle<()>>,
+    history_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl WebInterface {
-    pub fn new(state: Arc<RwLock<SystemState>>) -> Self {
+    pub fn new(
+        config: Config,
+        state: Arc<RwLock<PrinterState>>,
+        planner_stats: watch::Receiver<MotionPlannerStats>,
+        position: watch::Receiver<[f64; 4]>,
+        hardware: HardwareManager,
+        gcode: GCodeProcessor,
+    ) -> Self {
         Self {
+            config,
             state,
+            planner_stats,
+            position,
+            hardware,
+            gcode,
+            config_manager: None,
+            events: None,
+            event_log: None,
             server_handle: None,
+            history_handle: None,
         }
     }
 
+    /// Serve the printer's configuration profiles
+    pub fn with_config_manager(mut self, config_manager: Arc<RwLock<ConfigManager>>) -> Self {
+        self.config_manager = Some(config_manager);
+        self
+    }
+
+    /// Publish events from the API on the printer's event bus
+    pub fn with_event_sender(mut self, events: broadcast::Sender<PrinterEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Serve queries against the printer's event log
+    pub fn with_event_log(mut self, event_log: Arc<PrinterEventLog>) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
     /// Start the web server
     pub async fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // In a real implementation, this would start an HTTP server
-        // For now, we'll just simulate it
-        tracing::info!("Web interface started on http://localhost:8080");
-        
-        let state = self.state.clone();
-        self.server_handle = Some(tokio::spawn(async move {
-            // Simulate web server running
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                // In real implementation, handle HTTP requests here
-            }
-        }));
-        
+        let address: SocketAddr = self.config.web.bind_address.parse()?;
+        let mut ctx = ApiContext::new(
+            &self.config,
+            self.state.clone(),
+            self.planner_stats.clone(),
+            self.position.clone(),
+            self.hardware.clone(),
+            self.gcode.clone(),
+        )?;
+        if let Some(config_manager) = &self.config_manager {
+            ctx = ctx.with_config_manager(config_manager.clone());
+        }
+        if let Some(events) = &self.events {
+            ctx = ctx.with_event_sender(events.clone());
+        }
+        if let Some(event_log) = &self.event_log {
+            ctx = ctx.with_event_log(event_log.clone());
+        }
+        let recorder = TemperatureHistoryRecorder::new(
+            ctx.temperature_history.clone(),
+            self.state.clone(),
+            ctx.temperature_history_interval,
+        );
+        let (bound, server) = warp::serve(api::routes(ctx)).try_bind_ephemeral(address)?;
+        tracing::info!("Web interface started on http://{}", bound);
+
+        self.server_handle = Some(tokio::spawn(server));
+        self.history_handle = Some(recorder.spawn());
         Ok(())
     }
 
-    /// Get current system status for web API
-    pub async fn get_status(&self) -> SystemState {
+    /// Get current printer status for web API
+    pub async fn get_status(&self) -> PrinterState {
         self.state.read().await.clone()
     }
 
-    /// Handle a web command
-    pub async fn handle_command(&self, command: WebCommand) -> Result<String, Box<dyn std::error::Error>> {
-        match command {
-            WebCommand::GetStatus => {
-                let status = self.get_status().await;
-                Ok(serde_json::to_string(&status)?)
-            }
-            WebCommand::StartPrint => {
-                // Would trigger print start
-                Ok("Print started".to_string())
-            }
-            WebCommand::PausePrint => {
-                // Would trigger print pause
-                Ok("Print paused".to_string())
-            }
-            WebCommand::StopPrint => {
-                // Would trigger print stop
-                Ok("Print stopped".to_string())
-            }
-            WebCommand::Home => {
-                // Would trigger homing
-                Ok("Homing started".to_string())
-            }
-            WebCommand::MoveTo { x, y, z, f } => {
-                // Would trigger movement
-                Ok(format!("Moving to X:{:?} Y:{:?} Z:{:?} F:{:?}", x, y, z, f))
-            }
-        }
-    }
-
     /// Shutdown the web interface
-    pub async fn shutdown(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Shutting down web interface");
-        // In real implementation, would gracefully shutdown HTTP server
+        for handle in [self.server_handle.take(), self.history_handle.take()].into_iter().flatten() {
+            handle.abort();
+        }
         Ok(())
     }
 }
-
-/// Web commands that can be received
-#[derive(Debug, Clone)]
-pub enum WebCommand {
-    GetStatus,
-    StartPrint,
-    PausePrint,
-    StopPrint,
-    Home,
-    MoveTo { x: Option<f64>, y: Option<f64>, z: Option<f64>, f: Option<f64> },
-}
\ No newline at end of file
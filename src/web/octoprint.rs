@@ -0,0 +1,577 @@
+// src/web/octoprint.rs - OctoPrint REST API compatibility layer
+//
+// Implements the subset of https://docs.octoprint.org/en/master/api/ that
+// slicers use to upload files and drive the printer.
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::io::AsyncWriteExt;
+use tokio_stream::StreamExt;
+use warp::filters::BoxedFilter;
+use warp::hyper::body::Buf;
+use warp::http::StatusCode;
+use warp::multipart::FormData;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+use crate::file::FileManager;
+use crate::print_job::PrintJobState;
+use crate::printer::PrinterState;
+use super::api::{ApiContext, error, with_context};
+use super::auth::AuthPermission;
+use super::rate_limiter::RateLimited;
+
+/// OctoPrint release whose API we mirror
+pub const OCTOPRINT_VERSION: &str = "1.9.3";
+
+/// OctoPrint API version reported by `/api/version`
+const OCTOPRINT_API_VERSION: &str = "0.1";
+
+/// Largest accepted upload
+const MAX_UPLOAD_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Extensions OctoPrint treats as printable machine code
+const GCODE_EXTENSIONS: &[&str] = &["gcode", "gco", "g"];
+
+/// Missing or wrong `X-Api-Key`, and no bearer token with enough
+/// permission
+#[derive(Debug)]
+struct Forbidden;
+
+impl warp::reject::Reject for Forbidden {}
+
+/// All routes under `/octoprint/api`
+pub fn routes(ctx: ApiContext) -> BoxedFilter<(Response,)> {
+    let version = warp::path!("version")
+        .and(warp::get())
+        .and(authorized(ctx.clone(), AuthPermission::ReadOnly))
+        .map(|_: ApiContext| version());
+    let printer = warp::path!("printer")
+        .and(warp::get())
+        .and(authorized(ctx.clone(), AuthPermission::ReadOnly))
+        .then(printer);
+    let job = warp::path!("job").and(warp::get()).and(authorized(ctx.clone(), AuthPermission::ReadOnly)).then(job);
+    let list = warp::path!("files")
+        .and(warp::get())
+        .and(authorized(ctx.clone(), AuthPermission::ReadOnly))
+        .then(list_files);
+    let upload = warp::path!("files" / "local")
+        .and(warp::post())
+        .and(authorized(ctx.clone(), AuthPermission::Operator))
+        .and(warp::multipart::form().max_length(MAX_UPLOAD_BYTES))
+        .then(upload_file);
+    let command = warp::path!("printer" / "command")
+        .and(warp::post())
+        .and(authorized_caller(ctx, AuthPermission::Operator))
+        .and_then(|ctx: ApiContext, caller: String| async move {
+            if let Some(limiter) = &ctx.rate_limits.gcode_per_user {
+                limiter
+                    .check(&caller)
+                    .map_err(|retry_after| warp::reject::custom(RateLimited { retry_after }))?;
+            }
+            Ok::<_, Rejection>(ctx)
+        })
+        .and(warp::body::json())
+        .then(command);
+
+    let endpoints = version
+        .or(printer)
+        .unify()
+        .or(job)
+        .unify()
+        .or(list)
+        .unify()
+        .or(upload)
+        .unify()
+        .or(command)
+        .unify();
+
+    warp::path!("octoprint" / "api" / ..)
+        .and(endpoints)
+        .recover(|rejection: Rejection| async move {
+            if rejection.find::<Forbidden>().is_some() {
+                Ok(error(StatusCode::FORBIDDEN, "Invalid API key or token"))
+            } else {
+                Err(rejection)
+            }
+        })
+        .unify()
+        .boxed()
+}
+
+/// Pass the context on if the request carries the configured API key, or,
+/// with users configured, a bearer token whose role is at least `required`
+///
+/// With neither a key nor users configured the API is open.
+fn authorized(
+    ctx: ApiContext,
+    required: AuthPermission,
+) -> impl Filter<Extract = (ApiContext,), Error = Rejection> + Clone {
+    authorized_caller(ctx, required).map(|ctx: ApiContext, _caller: String| ctx)
+}
+
+/// `authorized`, also naming the caller for rate limiting: the user a
+/// token was issued to, `api_key`, or `anonymous` on an open API
+fn authorized_caller(
+    ctx: ApiContext,
+    required: AuthPermission,
+) -> impl Filter<Extract = (ApiContext, String), Error = Rejection> + Clone {
+    with_context(ctx)
+        .and(warp::header::optional::<String>("x-api-key"))
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |ctx: ApiContext, key: Option<String>, authorization: Option<String>| async move {
+            if ctx.api_key.as_deref().zip(key.as_deref()).is_some_and(|(expected, key)| api_key_matches(expected, key)) {
+                return Ok((ctx, "api_key".to_string()));
+            }
+            if let Some(auth) = &ctx.auth {
+                let claims = authorization
+                    .as_deref()
+                    .and_then(|header| header.strip_prefix("Bearer "))
+                    .and_then(|token| auth.verify(token.trim()).ok());
+                return match claims {
+                    Some(claims) if claims.role >= required => Ok((ctx, claims.sub)),
+                    _ => Err(warp::reject::custom(Forbidden)),
+                };
+            }
+            match ctx.api_key {
+                Some(_) => Err(warp::reject::custom(Forbidden)),
+                None => Ok((ctx, "anonymous".to_string())),
+            }
+        })
+        .untuple_one()
+}
+
+/// Compare keys in constant time; hashing first hides the expected length
+fn api_key_matches(expected: &str, key: &str) -> bool {
+    Sha256::digest(expected.as_bytes()).ct_eq(&Sha256::digest(key.as_bytes())).into()
+}
+
+fn version() -> Response {
+    warp::reply::json(&json!({
+        "api": OCTOPRINT_API_VERSION,
+        "server": OCTOPRINT_VERSION,
+        "text": format!("OctoPrint {}", OCTOPRINT_VERSION),
+    }))
+    .into_response()
+}
+
+async fn printer(ctx: ApiContext) -> Response {
+    let state = ctx.state.read().await;
+    if !state.ready {
+        return error(StatusCode::CONFLICT, "Printer is not operational");
+    }
+    // Heaters are simulated and sit at their target
+    let temperature = |celsius: f64| json!({ "actual": celsius, "target": celsius, "offset": 0 });
+    let printing = is_printing(&state);
+    warp::reply::json(&json!({
+        "temperature": {
+            "tool0": temperature(state.temperature),
+            "bed": temperature(state.bed_temperature),
+        },
+        "sd": { "ready": false },
+        "state": {
+            "text": state_text(&state),
+            "flags": {
+                "operational": true,
+                "printing": printing,
+                "paused": job_state(&state) == Some(&PrintJobState::Paused),
+                "pausing": false,
+                "cancelling": job_state(&state) == Some(&PrintJobState::Cancelling),
+                "sdReady": false,
+                "error": false,
+                "ready": !printing,
+                "closedOrError": false,
+            },
+        },
+    }))
+    .into_response()
+}
+
+async fn job(ctx: ApiContext) -> Response {
+    let state = ctx.state.read().await;
+    let (file, print_time) = match &state.job {
+        Some(job) => {
+            let print_time = job.state().is_active().then(|| job.elapsed_secs() as u64);
+            let name = Path::new(&job.path).file_name().and_then(|name| name.to_str()).unwrap_or(&job.path);
+            let file = json!({
+                "name": name,
+                "path": job.path,
+                "display": name,
+                "origin": "local",
+                "size": job.size,
+                "date": unix_time(job.created_at),
+            });
+            (file, print_time)
+        }
+        None => (json!({ "name": null, "path": null, "display": null, "origin": null, "size": null, "date": null }), None),
+    };
+    warp::reply::json(&json!({
+        "job": {
+            "file": file,
+            "estimatedPrintTime": null,
+            "lastPrintTime": null,
+            "filament": null,
+            "user": null,
+        },
+        "progress": {
+            "completion": state.job.as_ref().map(|_| state.print_progress * 100.0),
+            "filepos": null,
+            "printTime": print_time,
+            "printTimeLeft": state.slicer_remaining_mins.map(|mins| mins * 60.0),
+            "printTimeLeftOrigin": state.slicer_remaining_mins.map(|_| "estimate"),
+        },
+        "state": state_text(&state),
+    }))
+    .into_response()
+}
+
+async fn list_files(ctx: ApiContext) -> Response {
+    let upload_dir = ctx.upload_dir.to_string_lossy();
+    // No uploads yet
+    let files = FileManager::new().list_files(&upload_dir).await.unwrap_or_default();
+    let files: Vec<Value> = files
+        .iter()
+        .filter(|file| !file.is_directory && is_gcode(&file.name))
+        .map(|file| {
+            json!({
+                "name": file.name,
+                "display": file.name,
+                "path": file.name,
+                "type": "machinecode",
+                "typePath": ["machinecode", "gcode"],
+                "origin": "local",
+                "size": file.size,
+                "date": unix_time(file.modified),
+                "refs": refs(&file.name),
+            })
+        })
+        .collect();
+    warp::reply::json(&json!({ "files": files })).into_response()
+}
+
+/// Store the multipart `file` field in the upload directory
+///
+/// The `select` and `print` fields are ignored; prints are started locally.
+async fn upload_file(ctx: ApiContext, mut form: FormData) -> Response {
+    while let Some(part) = form.next().await {
+        let part = match part {
+            Ok(part) => part,
+            Err(e) => return error(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        if part.name() != "file" {
+            continue;
+        }
+        let Some(name) = part
+            .filename()
+            .and_then(|name| Path::new(name).file_name())
+            .and_then(|name| name.to_str())
+            .map(str::to_string)
+        else {
+            return error(StatusCode::BAD_REQUEST, "No file name given");
+        };
+        if !is_gcode(&name) {
+            return error(StatusCode::UNSUPPORTED_MEDIA_TYPE, "Only G-code files can be uploaded");
+        }
+        let path = ctx.upload_dir.join(&name);
+        let printing = ctx.state.read().await.job.as_ref()
+            .is_some_and(|job| job.state().is_active() && Path::new(&job.path) == path);
+        if printing {
+            return error(StatusCode::CONFLICT, "File is being printed");
+        }
+        if let Err(e) = save_part(&path, part).await {
+            tracing::error!("Failed to store upload {}: {}", name, e);
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "Could not store file");
+        }
+        // Prints are checked against this before they start
+        if let Err(e) = ctx.files.store_checksum(&path.to_string_lossy()).await {
+            tracing::error!("Failed to store checksum of {}: {}", name, e);
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "Could not store file");
+        }
+        tracing::info!("Received upload {}", name);
+
+        let body = json!({
+            "done": true,
+            "files": {
+                "local": { "name": name, "display": name, "path": name, "origin": "local", "refs": refs(&name) },
+            },
+        });
+        let reply = warp::reply::with_status(warp::reply::json(&body), StatusCode::CREATED);
+        return warp::reply::with_header(reply, "location", refs(&name)["resource"].as_str().unwrap_or_default())
+            .into_response();
+    }
+    error(StatusCode::BAD_REQUEST, "No file included")
+}
+
+pub(super) async fn save_part(path: &Path, part: warp::multipart::Part) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    write_part(tokio::fs::File::create(path).await?, part).await
+}
+
+/// Stream an upload into an already opened `file`
+pub(super) async fn write_part(mut file: tokio::fs::File, part: warp::multipart::Part) -> Result<(), Box<dyn std::error::Error>> {
+    let mut chunks = part.stream();
+    while let Some(chunk) = chunks.next().await {
+        let mut chunk = chunk?;
+        while chunk.has_remaining() {
+            let written = file.write(chunk.chunk()).await?;
+            chunk.advance(written);
+        }
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+/// Body of `POST /api/printer/command`
+#[derive(Debug, Deserialize)]
+struct CommandRequest {
+    command: Option<String>,
+    #[serde(default)]
+    commands: Vec<String>,
+}
+
+async fn command(ctx: ApiContext, request: CommandRequest) -> Response {
+    if !ctx.state.read().await.ready {
+        return error(StatusCode::CONFLICT, "Printer is not operational");
+    }
+    let mut gcode = ctx.gcode.clone();
+    let lines: Vec<&String> = request.command.iter().chain(&request.commands).collect();
+    if gcode.run_cancel_wait(&lines) {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    // Run the request's commands together, not interleaved with other clients'
+    let _commands = gcode.lock_commands().await;
+    for line in lines {
+        // Errors are stringified so they aren't held across an await
+        if let Err(e) = gcode.process_command(line).await.map_err(|e| e.to_string()) {
+            return error(StatusCode::BAD_REQUEST, &format!("{}: {}", line, e));
+        }
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+fn job_state(state: &PrinterState) -> Option<&PrintJobState> {
+    state.job.as_ref().map(|job| job.state())
+}
+
+fn is_printing(state: &PrinterState) -> bool {
+    job_state(state).is_some_and(PrintJobState::is_active)
+}
+
+fn state_text(state: &PrinterState) -> &'static str {
+    if !state.ready {
+        "Offline"
+    } else if let Some(PrintJobState::Paused) = job_state(state) {
+        "Paused"
+    } else if let Some(PrintJobState::Cancelling) = job_state(state) {
+        "Cancelling"
+    } else if is_printing(state) {
+        "Printing"
+    } else {
+        "Operational"
+    }
+}
+
+fn is_gcode(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| GCODE_EXTENSIONS.iter().any(|known| extension.eq_ignore_ascii_case(known)))
+}
+
+fn refs(name: &str) -> Value {
+    json!({
+        "resource": format!("/octoprint/api/files/local/{}", name),
+        "download": format!("/octoprint/downloads/files/local/{}", name),
+    })
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{UserConfig, WebConfig};
+    use crate::web::api::{routes, tests::{test_context, test_context_with}};
+    use crate::web::auth::test_password_hash;
+    use crate::file::FileManager;
+    use crate::print_job::PrintJob;
+
+    #[tokio::test]
+    async fn test_api_key_required() {
+        let (mut ctx, _stats_tx) = test_context(false);
+        ctx.api_key = Some("secret".to_string());
+        let routes = routes(ctx);
+
+        let response = warp::test::request().path("/octoprint/api/version").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = warp::test::request()
+            .path("/octoprint/api/version")
+            .header("X-Api-Key", "wrong")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = warp::test::request()
+            .path("/octoprint/api/version")
+            .header("X-Api-Key", "secret")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Other routes are unaffected
+        let response = warp::test::request().path("/api/hardware/stats").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_user_auth_required() {
+        let users = [("viewer", AuthPermission::ReadOnly), ("operator", AuthPermission::Operator)]
+            .into_iter()
+            .map(|(username, role)| UserConfig {
+                username: username.to_string(),
+                password_hash: test_password_hash("pw"),
+                role,
+            })
+            .collect();
+        let (ctx, _stats_tx) = test_context_with(WebConfig { users, api_key: Some("secret".to_string()), ..WebConfig::default() });
+        ctx.state.write().await.ready = true;
+        let routes = routes(ctx.clone());
+        let auth = ctx.auth.clone().unwrap();
+        let bearer = |username: &str, role| format!("Bearer {}", auth.issue(username, role).unwrap());
+        let command = || {
+            warp::test::request()
+                .method("POST")
+                .path("/octoprint/api/printer/command")
+                .json(&json!({ "command": "M400" }))
+        };
+
+        // No credentials
+        let response = warp::test::request().path("/octoprint/api/version").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(command().reply(&routes).await.status(), StatusCode::FORBIDDEN);
+        let response = command().header("authorization", "Bearer not-a-token").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Viewers can read but not run G-code or upload
+        let viewer = bearer("viewer", AuthPermission::ReadOnly);
+        let response = warp::test::request()
+            .path("/octoprint/api/version")
+            .header("authorization", &viewer)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(command().header("authorization", &viewer).reply(&routes).await.status(), StatusCode::FORBIDDEN);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/octoprint/api/files/local")
+            .header("authorization", &viewer)
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body("--BOUNDARY--\r\n")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let operator = bearer("operator", AuthPermission::Operator);
+        assert_eq!(command().header("authorization", &operator).reply(&routes).await.status(), StatusCode::NO_CONTENT);
+
+        // The API key still works on its own
+        assert_eq!(command().header("X-Api-Key", "secret").reply(&routes).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(command().header("X-Api-Key", "wrong").reply(&routes).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_command_rate_limited() {
+        let (ctx, _stats_tx) = test_context_with(WebConfig { rate_limit_gcode_per_user_per_min: 2, ..WebConfig::default() });
+        ctx.state.write().await.ready = true;
+        let routes = routes(ctx);
+        let command = || {
+            warp::test::request()
+                .method("POST")
+                .path("/octoprint/api/printer/command")
+                .json(&json!({ "commands": ["M400", "M400"] }))
+                .reply(&routes)
+        };
+
+        // Charged per request, like POST /api/gcode
+        assert_eq!(command().await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(command().await.status(), StatusCode::NO_CONTENT);
+        let response = command().await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("retry-after"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_list() {
+        let dir = std::env::temp_dir().join(format!("krusty-octoprint-{}", std::process::id()));
+        let (mut ctx, _stats_tx) = test_context(false);
+        ctx.upload_dir = dir.clone();
+        let routes = routes(ctx.clone());
+
+        let response = warp::test::request().path("/octoprint/api/files").reply(&routes).await;
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["files"], json!([]));
+
+        let upload = |name: &str| {
+            let body = format!(
+                "--BOUNDARY\r\n\
+                 Content-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n\
+                 G28\nG1 X10 Y10\n\r\n\
+                 --BOUNDARY\r\n\
+                 Content-Disposition: form-data; name=\"print\"\r\n\r\n\
+                 false\r\n\
+                 --BOUNDARY--\r\n",
+                name
+            );
+            warp::test::request()
+                .method("POST")
+                .path("/octoprint/api/files/local")
+                .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+                .body(body)
+        };
+
+        let response = upload("../escape/benchy.gcode").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["location"], "/octoprint/api/files/local/benchy.gcode");
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["done"], true);
+        assert_eq!(body["files"]["local"]["name"], "benchy.gcode");
+        assert_eq!(std::fs::read_to_string(dir.join("benchy.gcode")).unwrap(), "G28\nG1 X10 Y10\n");
+
+        let response = warp::test::request().path("/api/files/benchy.gcode/checksum").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        let path = dir.join("benchy.gcode").to_string_lossy().into_owned();
+        let checksum = FileManager::new().compute_checksum(&path).await.unwrap();
+        assert_eq!(body["sha256"], checksum);
+
+        // Re-uploading replaces a stale checksum
+        std::fs::write(format!("{}.sha256", path), "0".repeat(64)).unwrap();
+        let response = upload("benchy.gcode").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(FileManager::new().get_stored_checksum(&path).await.unwrap(), Some(checksum));
+
+        // The file being printed can't be replaced
+        ctx.state.write().await.job = Some(PrintJob::new(&path, 15));
+        let response = upload("benchy.gcode").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        ctx.state.write().await.job = None;
+
+        let response = upload("model.stl").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = warp::test::request().path("/octoprint/api/files").reply(&routes).await;
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["files"].as_array().unwrap().len(), 1);
+        assert_eq!(body["files"][0]["name"], "benchy.gcode");
+        assert_eq!(body["files"][0]["size"], 15);
+        assert_eq!(body["files"][0]["type"], "machinecode");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
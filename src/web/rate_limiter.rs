@@ -0,0 +1,152 @@
+// src/web/rate_limiter.rs - Token bucket request limits per client
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Buckets kept before idle, full ones are dropped
+const PRUNE_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Allows `per_minute` requests per key, refilled continuously
+///
+/// A key that has been idle may burst up to the full minute's allowance.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one request from `key`'s bucket, or say how long until one is
+    /// available
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let capacity = self.per_minute as f64;
+        let refill_per_sec = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * refill_per_sec < capacity);
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(TokenBucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if refill_per_sec > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
+
+/// The limiters applied to API requests; a limit of 0 disables it
+#[derive(Debug)]
+pub struct RateLimits {
+    /// Unauthenticated requests, keyed by client IP
+    pub per_ip: Option<RateLimiter>,
+    /// Authenticated requests, keyed by username
+    pub per_user: Option<RateLimiter>,
+    /// G-code execution, keyed by username
+    pub gcode_per_user: Option<RateLimiter>,
+    /// Take the client IP from `X-Forwarded-For`
+    pub trust_forwarded_for: bool,
+    /// Proxies appending to `X-Forwarded-For`
+    pub trusted_proxy_hops: usize,
+}
+
+impl RateLimits {
+    pub fn from_config(config: &crate::config::WebConfig) -> Self {
+        let limiter = |per_minute: u32| (per_minute > 0).then(|| RateLimiter::new(per_minute));
+        Self {
+            per_ip: limiter(config.rate_limit_per_ip_per_min),
+            per_user: limiter(config.rate_limit_per_user_per_min),
+            gcode_per_user: limiter(config.rate_limit_gcode_per_user_per_min),
+            trust_forwarded_for: config.trust_forwarded_for,
+            trusted_proxy_hops: config.trusted_proxy_hops as usize,
+        }
+    }
+
+    /// Client address from an `X-Forwarded-For` header, if it's trusted
+    ///
+    /// Each trusted proxy appends the address it received from, so the
+    /// client is `trusted_proxy_hops` entries from the right; the entries
+    /// before it come from the client and can't be believed. `None` if the
+    /// header is too short to hold that entry.
+    pub fn forwarded_client<'a>(&self, header: &'a str) -> Option<&'a str> {
+        if !self.trust_forwarded_for {
+            return None;
+        }
+        let entries: Vec<&str> = header.split(',').map(str::trim).filter(|entry| !entry.is_empty()).collect();
+        entries.len().checked_sub(self.trusted_proxy_hops).and_then(|i| entries.get(i)).copied()
+    }
+}
+
+/// Request refused until `retry_after` has passed (429)
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl warp::reject::Reject for RateLimited {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket() {
+        let limiter = RateLimiter::new(60);
+        for _ in 0..60 {
+            limiter.check("a").unwrap();
+        }
+        assert_eq!(limiter.check("a"), Err(Duration::from_secs(1)));
+        limiter.check("b").unwrap();
+
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        limiter.check("a").unwrap();
+        assert_eq!(limiter.check("a"), Err(Duration::from_millis(500)));
+
+        // Refills no further than a minute's worth
+        tokio::time::advance(Duration::from_secs(600)).await;
+        assert_eq!((0..100).filter(|_| limiter.check("a").is_ok()).count(), 60);
+    }
+
+    #[test]
+    fn test_forwarded_client() {
+        let mut config = crate::config::WebConfig::default();
+        let limits = RateLimits::from_config(&config);
+        assert_eq!(limits.forwarded_client("198.51.100.1"), None);
+
+        config.trust_forwarded_for = true;
+        let limits = RateLimits::from_config(&config);
+        // The client's own claim is ignored
+        assert_eq!(limits.forwarded_client("10.0.0.1, 198.51.100.1"), Some("198.51.100.1"));
+        assert_eq!(limits.forwarded_client("198.51.100.1"), Some("198.51.100.1"));
+        assert_eq!(limits.forwarded_client(""), None);
+
+        config.trusted_proxy_hops = 2;
+        let limits = RateLimits::from_config(&config);
+        assert_eq!(limits.forwarded_client("10.0.0.1, 198.51.100.1, 192.0.2.1"), Some("198.51.100.1"));
+        assert_eq!(limits.forwarded_client("198.51.100.1"), None);
+    }
+}
@@ -0,0 +1,209 @@
+// src/web/token_blacklist.rs - Refresh token storage and revocation
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use super::auth::AuthPermission;
+
+/// A long-lived token that can be exchanged once for a new access token
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshToken {
+    pub token: String,
+    pub user_id: String,
+    /// Role granted to access tokens issued from this one
+    pub role: AuthPermission,
+    /// Unix seconds
+    pub issued_at: u64,
+    /// Unix seconds
+    pub expires_at: u64,
+}
+
+/// Why a refresh token was refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefreshError {
+    /// Never issued
+    Invalid,
+    Expired,
+    /// Already exchanged or logged out; every token of that user has been
+    /// revoked
+    Replayed,
+}
+
+impl fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefreshError::Invalid => write!(f, "Invalid refresh token"),
+            RefreshError::Expired => write!(f, "Refresh token expired"),
+            RefreshError::Replayed => write!(f, "Refresh token already used"),
+        }
+    }
+}
+
+impl std::error::Error for RefreshError {}
+
+/// Tokens that must no longer be accepted, kept until they would have
+/// expired anyway
+#[derive(Debug, Default)]
+pub struct TokenBlacklist {
+    /// Token -> (owner, expiry)
+    revoked: Mutex<HashMap<String, (String, u64)>>,
+}
+
+impl TokenBlacklist {
+    pub fn revoke(&self, token: &RefreshToken) {
+        self.revoked
+            .lock()
+            .unwrap()
+            .insert(token.token.clone(), (token.user_id.clone(), token.expires_at));
+    }
+
+    /// Owner of a revoked token
+    pub fn revoked_owner(&self, token: &str) -> Option<String> {
+        self.revoked.lock().unwrap().get(token).map(|(user_id, _)| user_id.clone())
+    }
+
+    pub fn is_revoked(&self, token: &str) -> bool {
+        self.revoked.lock().unwrap().contains_key(token)
+    }
+
+    /// Forget tokens that have expired by `now`
+    pub fn prune(&self, now: u64) {
+        self.revoked.lock().unwrap().retain(|_, (_, expires_at)| *expires_at > now);
+    }
+
+    pub fn len(&self) -> usize {
+        self.revoked.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Outstanding refresh tokens; each can be rotated exactly once
+#[derive(Debug)]
+pub struct RefreshTokenStore {
+    active: Mutex<HashMap<String, RefreshToken>>,
+    blacklist: TokenBlacklist,
+    lifetime_secs: u64,
+}
+
+impl RefreshTokenStore {
+    pub fn new(lifetime_secs: u64) -> Self {
+        Self {
+            active: Mutex::new(HashMap::new()),
+            blacklist: TokenBlacklist::default(),
+            lifetime_secs,
+        }
+    }
+
+    pub fn issue(&self, user_id: &str, role: AuthPermission, now: u64) -> RefreshToken {
+        let token = RefreshToken {
+            token: URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>()),
+            user_id: user_id.to_string(),
+            role,
+            issued_at: now,
+            expires_at: now.saturating_add(self.lifetime_secs),
+        };
+        self.active.lock().unwrap().insert(token.token.clone(), token.clone());
+        token
+    }
+
+    /// Exchange `token` for a new refresh token, invalidating it
+    ///
+    /// Presenting a token that was already exchanged means it leaked, so
+    /// all of that user's tokens are revoked.
+    pub fn rotate(&self, token: &str, now: u64) -> Result<RefreshToken, RefreshError> {
+        self.blacklist.prune(now);
+        let mut active = self.active.lock().unwrap();
+        if let Some(user_id) = self.blacklist.revoked_owner(token) {
+            active.retain(|_, issued| {
+                if issued.user_id == user_id {
+                    self.blacklist.revoke(issued);
+                }
+                issued.user_id != user_id
+            });
+            tracing::warn!("Refresh token of {} replayed, revoking all of their sessions", user_id);
+            return Err(RefreshError::Replayed);
+        }
+
+        let current = active.remove(token).ok_or(RefreshError::Invalid)?;
+        drop(active);
+        self.blacklist.revoke(&current);
+        if current.expires_at <= now {
+            return Err(RefreshError::Expired);
+        }
+        Ok(self.issue(&current.user_id, current.role, now))
+    }
+
+    /// Invalidate `token`, e.g. on logout
+    pub fn revoke(&self, token: &str) {
+        if let Some(current) = self.active.lock().unwrap().remove(token) {
+            self.blacklist.revoke(&current);
+        }
+    }
+
+    pub fn blacklist(&self) -> &TokenBlacklist {
+        &self.blacklist
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn test_rotation() {
+        let store = RefreshTokenStore::new(30 * DAY);
+        let first = store.issue("alice", AuthPermission::Operator, 1000);
+        assert_eq!(first.expires_at, 1000 + 30 * DAY);
+
+        let second = store.rotate(&first.token, 2000).unwrap();
+        assert_ne!(second.token, first.token);
+        assert_eq!((second.user_id.as_str(), second.role), ("alice", AuthPermission::Operator));
+        assert_eq!((second.issued_at, second.expires_at), (2000, 2000 + 30 * DAY));
+        assert!(store.blacklist().is_revoked(&first.token));
+
+        assert!(store.rotate(&second.token, 3000).is_ok());
+        assert_eq!(store.rotate("unknown", 3000), Err(RefreshError::Invalid));
+    }
+
+    #[test]
+    fn test_replay_revokes_user_sessions() {
+        let store = RefreshTokenStore::new(30 * DAY);
+        let stolen = store.issue("alice", AuthPermission::Admin, 0);
+        let other_device = store.issue("alice", AuthPermission::Admin, 0);
+        let bob = store.issue("bob", AuthPermission::ReadOnly, 0);
+
+        let rotated = store.rotate(&stolen.token, 10).unwrap();
+        assert_eq!(store.rotate(&stolen.token, 20), Err(RefreshError::Replayed));
+
+        // Every token alice holds is now dead; bob is unaffected
+        assert_eq!(store.rotate(&rotated.token, 30), Err(RefreshError::Replayed));
+        assert_eq!(store.rotate(&other_device.token, 30), Err(RefreshError::Replayed));
+        assert!(store.rotate(&bob.token, 30).is_ok());
+    }
+
+    #[test]
+    fn test_expired_token_rejected() {
+        let store = RefreshTokenStore::new(30 * DAY);
+        let token = store.issue("alice", AuthPermission::Operator, 0);
+        assert_eq!(store.rotate(&token.token, 30 * DAY), Err(RefreshError::Expired));
+
+        // Expired blacklist entries are eventually forgotten
+        store.rotate("unknown", 30 * DAY + 1).unwrap_err();
+        assert!(store.blacklist().is_empty());
+    }
+
+    #[test]
+    fn test_logout() {
+        let store = RefreshTokenStore::new(30 * DAY);
+        let token = store.issue("alice", AuthPermission::Operator, 0);
+        store.revoke(&token.token);
+        assert!(store.blacklist().is_revoked(&token.token));
+        assert_eq!(store.rotate(&token.token, 1), Err(RefreshError::Replayed));
+    }
+}
@@ -0,0 +1,181 @@
+// src/web/webhooks.rs - HTTP notifications for printer events
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use crate::config::WebhookConfig;
+use crate::printer::PrinterEvent;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when a secret is set
+pub const SIGNATURE_HEADER: &str = "X-Krusty-Signature";
+
+/// Delay before the first retry; doubled for each further attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Posts printer events to the configured webhook URLs
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+    webhooks: Vec<WebhookConfig>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(webhooks: Vec<WebhookConfig>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self { client, webhooks }
+    }
+
+    /// Deliver every matching event from `events` until the channel closes
+    pub fn spawn(self, mut events: broadcast::Receiver<PrinterEvent>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => self.dispatch(&event),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Webhook dispatcher lagging, skipped {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Start a delivery to each webhook subscribed to the event
+    fn dispatch(&self, event: &PrinterEvent) {
+        let event_type = event.event_type();
+        let body = event.to_json().to_string();
+        for webhook in &self.webhooks {
+            if !webhook.events.is_empty() && !webhook.events.iter().any(|name| name == event_type) {
+                continue;
+            }
+            let client = self.client.clone();
+            let webhook = webhook.clone();
+            let body = body.clone();
+            tokio::spawn(async move {
+                if let Err(e) = deliver(&client, &webhook, body).await {
+                    tracing::error!("Webhook {} failed for {}: {}", webhook.url, event_type, e);
+                }
+            });
+        }
+    }
+}
+
+/// POST the body, retrying with exponential backoff
+async fn deliver(client: &reqwest::Client, webhook: &WebhookConfig, body: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let signature = webhook.secret.as_deref().map(|secret| sign(secret, body.as_bytes()));
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        let mut request = client
+            .post(&webhook.url)
+            .header("content-type", "application/json")
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt >= webhook.retry_count {
+            return Err(format!("gave up after {} attempts: {}", attempt + 1, error).into());
+        }
+
+        tracing::warn!("Webhook {} attempt {} failed ({}), retrying in {:?}", webhook.url, attempt + 1, error, delay);
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// `sha256=<hex>` HMAC-SHA256 signature of a payload
+pub fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload);
+    let digest: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+    use warp::Filter;
+    use warp::http::StatusCode;
+
+    /// Receiver that answers 500 to the first `failures` requests and
+    /// forwards the signature and body of each successful one
+    fn spawn_receiver(failures: usize) -> (String, mpsc::UnboundedReceiver<(Option<String>, String)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let route = warp::post()
+            .and(warp::path("hook"))
+            .and(warp::header::optional::<String>(SIGNATURE_HEADER))
+            .and(warp::body::bytes())
+            .map(move |signature: Option<String>, body: warp::hyper::body::Bytes| {
+                if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+                let _ = tx.send((signature, String::from_utf8_lossy(&body).into_owned()));
+                StatusCode::OK
+            });
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}/hook", address), rx)
+    }
+
+    fn webhook(url: String, events: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            events: events.iter().map(|name| name.to_string()).collect(),
+            secret: Some("s3cret".to_string()),
+            retry_count: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retry() {
+        let (url, mut received) = spawn_receiver(1);
+        let (event_tx, event_rx) = broadcast::channel(16);
+        let dispatcher = WebhookDispatcher::new(vec![webhook(url, &["print_completed"])]);
+        let task = dispatcher.spawn(event_rx);
+
+        // Filtered out
+        event_tx.send(PrinterEvent::PrintStarted { path: "part.gcode".to_string() }).unwrap();
+        event_tx
+            .send(PrinterEvent::PrintCompleted { path: "part.gcode".to_string(), lines: 1200 })
+            .unwrap();
+
+        let (signature, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(signature, Some(sign("s3cret", body.as_bytes())));
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["event"], "print_completed");
+        assert_eq!(json["data"]["lines"], 1200);
+
+        drop(event_tx);
+        task.await.unwrap();
+        assert!(received.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_sign_known_vector() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+; Simulator self-test: homing, moves, extrusion and heating
+G28
+; ASSERT x == 0
+; ASSERT endstop_x == 1
+; ASSERT endstop_z == 1
+M104 S200
+; ASSERT heater_target == 200
+G90
+G1 X120 Y50 F6000
+; ASSERT x > 100
+; ASSERT endstop_x == 0
+G1 Z10 F600
+M83
+G1 X150 E5 F1200
+G1 X120 E5
+; ASSERT e_total == 10
+; ASSERT e_total < 500
+M109 S200
+; ASSERT heater_temp >= 200
+G92 E0
+G91
+G1 X-20 F3000
+; ASSERT x == 100
+; ASSERT y == 50
+; ASSERT z == 10
+G4 S30
+; ASSERT heater_temp == 200 within 2
+M104 S0
+G4 S60
+; ASSERT heater_target == 0
+; ASSERT heater_temp < 150
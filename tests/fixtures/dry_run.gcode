@@ -0,0 +1,17 @@
+; Fixture for GCodeProcessor::dry_run_file
+G28
+G90
+M82
+M104 S200
+M116 ; not waited for in a dry run
+G1 Z5 F10
+G4 P1500 ; dwell 1.5s
+G1 X30 Y40 F50
+G1 X30 Y0 E4 F20 ; print 40mm
+G91
+G1 X-30 F30
+G1 E3 F10 ; retract 1mm
+M107
+G90
+G1 Z10 F10
+M600 ; parks, without waiting for M108
@@ -0,0 +1,8 @@
+; Single 10x10mm perimeter on the first layer
+G28
+G1 Z0.2 F600
+M83
+G1 X10 Y0 E0.5 F1200
+G1 X10 Y10 E0.5
+G1 X0 Y10 E0.5
+G1 X0 Y0 E0.5
@@ -0,0 +1,32 @@
+; generated by PrusaSlicer 2.6.1+linux-x64-GTK3 on 2023-10-01 at 12:00:00 UTC
+;
+;
+; thumbnail begin 32x32 1596
+; iVBORw0KGgoAAAANSUhEUgAAACAAAAAgCAYAAABzenr0AAAEcklEQVR42hXOEaClAAAAsIcfwzAMwz
+; AMwzAMwzAMwzAMwzAMwzAMw+xuwXy/36/790dASERMQkpGTkFJRU1DS0fPwMjEzMLKxs7BycXNw8vv
+; 9ydAQEhETEJKRk5BSUVNQ0tHz8DIxMzCysbOwcnFzcP79wUCAQJCImISUjJyCkoqahpaOnoGRiZmFl
+; Y2dg5OLm4e3uALhAIEhETEJKRk5BSUVNQ0tHT0DIxMzCysbOwcnFzcPLzhF4gECAiJiElIycgpKKmo
+; aWjp6BkYmZhZWNnYOTi5uHl4oy8QCxAQEhGTkJKRU1BSUdPQ0tEzMDIxs7CysXNwcnHz8MZfIBEgIC
+; QiJiElI6egpKKmoaWjZ2BkYmZhZWPn4OTi5uFNvkAqQEBIRExCSkZOQUlFTUNLR8/AyMTMwsrGzsHJ
+; xc3Dm36BTICAkIiYhJSMnIKSipqGlo6egZGJmYWVjZ2Dk4ubhzf7ArkAASERMQkpGTkFJRU1DS0dPQ
+; MjEzMLKxs7BycXNw9v/gUKAQJCImISUjJyCkoqahpaOnoGRiZmFlY2dg5OLm4e3uILlAIEhETEJKRk
+; 5BSUVNQ0tHT0DIxMzCysbOwcnFzcPLzlF6gECAiJiElIycgpKKmoaWjp6BkYmZhZWNnYOTi5uHl4qy
+; 9QCxAQEhGTkJKRU1BSUdPQ0tEzMDIxs7CysXNwcnHz8NZfoBEgICQiJiElI6egpKKmoaWjZ2BkYmZh
+; ZWPn4OTi5uFtvkArQEBIRExCSkZOQUlFTUNLR8/AyMTMwsrGzsHJxc3D236BToCAkIiYhJSMnIKSip
+; qGlo6egZGJmYWVjZ2Dk4ubh7f7Ar0AASERMQkpGTkFJRU1DS0dPQMjEzMLKxs7BycXNw9v/wUGAQJC
+; ImISUjJyCkoqahpaOnoGRiZmFlY2dg5OLm4e3uELjAIEhETEJKRk5BSUVNQ0tHT0DIxMzCysbOwcnF
+; zcPLzjF5gECAiJiElIycgpKKmoaWjp6BkYmZhZWNnYOTi5uHl4py8wCxAQEhGTkJKRU1BSUdPQ0tEz
+; MDIxs7CysXNwcnHz8M5fYBEgICQiJiElI6egpKKmoaWjZ2BkYmZhZWPn4OTi5uFdvsAqQEBIRExCSk
+; ZOQUlFTUNLR8/AyMTMwsrGzsHJxc3Du36BTYCAkIiYhJSMnIKSipqGlo6egZGJmYWVjZ2Dk4ubh3f7
+; ArsAASERMQkpGTkFJRU1DS0dPQMjEzMLKxs7BycXNw/v/gUOAQJCImISUjJyCkoqahpaOnoGRiZmFl
+; Y2dg5OLm4e3uMLnAIEhETEJKRk5BSUVNQ0tHT0DIxMzCysbOwcnFzcPLznF7gECAiJiElIycgpKKmo
+; aWjp6BkYmZhZWNnYOTi5uHl4ry9wCxAQEhGTkJKRU1BSUdPQ0tEzMDIxs7CysXNwcnHz8N5f4BEgIC
+; QiJiElI6egpKKmoaWjZ2BkYmZhZWPn4OTi5uF9vsArQEBIRExCSkZOQUlFTUNLR8/AyMTMwsrGzsHJ
+; xc3Dy3/De9yIYX+pOAAAAABJRU5ErkJggg==
+; thumbnail end
+;
+; estimated printing time (normal mode) = 1m 2s
+
+G28
+G1 Z0.3 F600
+G1 X10 Y10 E0.5 F1500
@@ -0,0 +1,96 @@
+// tests/gcode_fuzz.rs - Property-based tests for the G-code parser
+use krusty_rs::gcode::parser::{
+    AsyncGCodeParser, ErrorRecovery, GCodeParser, GCodeParserConfig, GCodeWord, parse_words,
+};
+use proptest::prelude::*;
+
+const CASES: u32 = 10_000;
+
+/// Numbers as hosts send them, plus malformed and out-of-range ones
+fn number() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<f64>().prop_map(|value| value.to_string()),
+        (-100_000i64..100_000).prop_map(|value| value.to_string()),
+        Just("1e999".to_string()),
+        Just("-1e999".to_string()),
+        Just("1.2.3".to_string()),
+        Just("NaN".to_string()),
+        Just(".".to_string()),
+        Just(String::new()),
+        "[0-9.eE+-]{1,12}",
+    ]
+}
+
+fn word() -> impl Strategy<Value = String> {
+    (prop::sample::select(vec!['G', 'M', 'T', 'X', 'Y', 'Z', 'E', 'F', 'S', 'P', 'N', '*', 'g', 'x']), number())
+        .prop_map(|(letter, value)| format!("{}{}", letter, value))
+}
+
+/// A line built from words, comments, framing and raw garbage
+fn line() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        4 => word(),
+        1 => "[ \t]{0,3}".prop_map(String::from),
+        1 => "; ?[ -~]{0,20}".prop_map(String::from),
+        1 => "\\*[0-9]{0,4}".prop_map(String::from),
+        1 => prop::collection::vec(any::<u8>(), 0..16)
+            .prop_map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+    ];
+    prop::collection::vec(piece, 0..12).prop_map(|pieces| pieces.join(" "))
+}
+
+fn parser_config() -> impl Strategy<Value = GCodeParserConfig> {
+    (
+        any::<bool>(),
+        prop::sample::select(vec![ErrorRecovery::Abort, ErrorRecovery::SkipLine, ErrorRecovery::SkipToNextN]),
+        0usize..4,
+    )
+        .prop_map(|(enable_checksums, error_recovery, max_consecutive_errors)| GCodeParserConfig {
+            enable_checksums,
+            error_recovery,
+            max_consecutive_errors,
+            keep_comments: false,
+            strict_line_numbers: false,
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn parse_line_never_panics(lines in prop::collection::vec(line(), 1..4), config in parser_config()) {
+        let mut parser = GCodeParser::new(config);
+        for line in &lines {
+            if let Ok(Some(parsed)) = parser.parse_line(line) {
+                let _ = parse_words(&parsed.command);
+            }
+        }
+    }
+
+    #[test]
+    fn async_parser_never_panics(lines in prop::collection::vec(line(), 1..8), config in parser_config()) {
+        let input = lines.join("\n");
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let mut parser = AsyncGCodeParser::new(input.as_bytes(), config);
+            let mut results = 0;
+            while let Some(result) = parser.next_command().await {
+                results += 1;
+                if result.is_err() || results > lines.len() {
+                    break;
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn word_round_trip(
+        letter in prop::sample::select(vec!['G', 'M', 'T', 'X', 'Y', 'Z', 'E', 'F', 'S', 'P']),
+        value in any::<f64>().prop_filter("finite", |value| value.is_finite()),
+    ) {
+        let word = GCodeWord { letter, value };
+        let parsed = GCodeWord::parse(&word.to_string()).unwrap();
+        prop_assert_eq!(parsed.letter, letter);
+        prop_assert!((parsed.value - value).abs() <= value.abs() * f64::EPSILON);
+    }
+}
@@ -0,0 +1,136 @@
+// tests/octoprint_api.rs - OctoPrint clients against the compatibility layer
+use std::sync::Arc;
+use krusty_rs::config::{Config, WebConfig};
+use krusty_rs::gcode::GCodeProcessor;
+use krusty_rs::hardware::HardwareManager;
+use krusty_rs::motion::{MotionConfig, MotionController, MotionPlannerStats};
+use krusty_rs::printer::PrinterState;
+use krusty_rs::web::api::{ApiContext, routes};
+use serde_json::Value;
+use tokio::sync::{RwLock, watch};
+use warp::http::StatusCode;
+
+const API_KEY: &str = "0123456789ABCDEF";
+
+fn context() -> (ApiContext, Arc<RwLock<PrinterState>>) {
+    let mut config: Config = toml::from_str(include_str!("../src/printer.toml")).unwrap();
+    config.web = WebConfig {
+        api_key: Some(API_KEY.to_string()),
+        ..WebConfig::default()
+    };
+    let state = Arc::new(RwLock::new(PrinterState { ready: true, ..PrinterState::new() }));
+    let (_, planner_stats) = watch::channel(MotionPlannerStats::default());
+    let hardware = HardwareManager::new(config.clone());
+    let motion = MotionController::new(state.clone(), hardware.clone(), MotionConfig::new_from_printer_config(&config));
+    let position = motion.get_planner().subscribe_position();
+    let gcode = GCodeProcessor::new(state.clone(), motion);
+    (ApiContext::new(&config, state.clone(), planner_stats, position, hardware, gcode).unwrap(), state)
+}
+
+/// Assert `value[key]` exists and has the JSON type `kind`
+fn expect(value: &Value, key: &str, kind: &str) {
+    let field = value.get(key).unwrap_or_else(|| panic!("missing {:?} in {}", key, value));
+    let matches = match kind {
+        "string" => field.is_string(),
+        "number" => field.is_number(),
+        "boolean" => field.is_boolean(),
+        "object" => field.is_object(),
+        "number|null" => field.is_number() || field.is_null(),
+        "string|null" => field.is_string() || field.is_null(),
+        "object|null" => field.is_object() || field.is_null(),
+        _ => unreachable!(),
+    };
+    assert!(matches, "{:?} should be {} in {}", key, kind, value);
+}
+
+fn expect_temperature(value: &Value) {
+    for key in ["actual", "target", "offset"] {
+        expect(value, key, "number");
+    }
+}
+
+#[tokio::test]
+async fn test_prusaslicer_connection_sequence() {
+    let (ctx, state) = context();
+    let routes = routes(ctx);
+    let get = |path: &'static str| warp::test::request().path(path).header("X-Api-Key", API_KEY);
+
+    // Connection test: version check
+    let response = get("/octoprint/api/version").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let version: Value = serde_json::from_slice(response.body()).unwrap();
+    expect(&version, "api", "string");
+    expect(&version, "server", "string");
+    expect(&version, "text", "string");
+    assert!(version["text"].as_str().unwrap().starts_with("OctoPrint"));
+
+    // Printer state and temperatures
+    state.write().await.temperature = 215.0;
+    let response = get("/octoprint/api/printer").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let printer: Value = serde_json::from_slice(response.body()).unwrap();
+    expect(&printer, "temperature", "object");
+    expect_temperature(&printer["temperature"]["tool0"]);
+    expect_temperature(&printer["temperature"]["bed"]);
+    assert_eq!(printer["temperature"]["tool0"]["actual"], 215.0);
+    expect(&printer, "state", "object");
+    expect(&printer["state"], "text", "string");
+    for flag in ["operational", "paused", "printing", "pausing", "cancelling", "sdReady", "error", "ready", "closedOrError"] {
+        expect(&printer["state"]["flags"], flag, "boolean");
+    }
+    assert_eq!(printer["state"]["text"], "Operational");
+
+    // Current job (none yet)
+    let response = get("/octoprint/api/job").reply(&routes).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let job: Value = serde_json::from_slice(response.body()).unwrap();
+    expect(&job, "job", "object");
+    expect(&job["job"], "file", "object");
+    for key in ["name", "origin"] {
+        expect(&job["job"]["file"], key, "string|null");
+    }
+    expect(&job["job"], "estimatedPrintTime", "number|null");
+    expect(&job["job"], "filament", "object|null");
+    expect(&job, "progress", "object");
+    for key in ["completion", "filepos", "printTime", "printTimeLeft"] {
+        expect(&job["progress"], key, "number|null");
+    }
+    expect(&job, "state", "string");
+    assert_eq!(job["state"], "Operational");
+
+    // G-code execution
+    let response = warp::test::request()
+        .method("POST")
+        .path("/octoprint/api/printer/command")
+        .header("X-Api-Key", API_KEY)
+        .json(&serde_json::json!({ "commands": ["M104 S200", "M140 S60"] }))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(response.body().is_empty());
+    let state = state.read().await;
+    assert_eq!((state.temperature, state.bed_target), (200.0, 60.0));
+}
+
+#[tokio::test]
+async fn test_not_operational() {
+    let (ctx, state) = context();
+    state.write().await.ready = false;
+    let routes = routes(ctx);
+
+    let response = warp::test::request()
+        .path("/octoprint/api/printer")
+        .header("X-Api-Key", API_KEY)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/octoprint/api/printer/command")
+        .header("X-Api-Key", API_KEY)
+        .json(&serde_json::json!({ "command": "G28" }))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
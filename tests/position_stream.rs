@@ -0,0 +1,59 @@
+// tests/position_stream.rs - Live toolhead position over Server-Sent Events
+use std::sync::Arc;
+use std::time::Duration;
+use krusty_rs::config::{Config, WebConfig};
+use krusty_rs::gcode::GCodeProcessor;
+use krusty_rs::hardware::HardwareManager;
+use krusty_rs::motion::{MotionConfig, MotionController, MotionPlannerStats};
+use krusty_rs::printer::PrinterState;
+use krusty_rs::web::api::{ApiContext, routes};
+use serde_json::Value;
+use tokio::sync::{RwLock, watch};
+
+#[tokio::test]
+async fn test_position_stream_follows_move() {
+    let mut config: Config = toml::from_str(include_str!("../src/printer.toml")).unwrap();
+    config.web = WebConfig::default();
+    let state = Arc::new(RwLock::new(PrinterState::new()));
+    let (_, planner_stats) = watch::channel(MotionPlannerStats::default());
+    let mut hardware = HardwareManager::new(config.clone());
+    hardware.connect().await.unwrap();
+    let mut motion = MotionController::new(state.clone(), hardware.clone(), MotionConfig::new_from_printer_config(&config));
+    let position = motion.get_planner().subscribe_position();
+    let gcode = GCodeProcessor::new(state.clone(), motion.clone());
+    let ctx = ApiContext::new(&config, state, planner_stats, position, hardware, gcode).unwrap();
+    let (address, server) = warp::serve(routes(ctx)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    // A 2 s move along +X, executed as the host loop would
+    motion.queue_home().await.unwrap();
+    motion.queue_linear_move([100.0, 0.0, 0.0], Some(50.0), None).await.unwrap();
+    let executor = tokio::spawn(async move {
+        loop {
+            motion.update().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    });
+
+    let mut response = reqwest::get(format!("http://{}/api/position/stream", address)).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+    let mut buffer = String::new();
+    let mut xs = Vec::new();
+    while xs.len() < 10 {
+        let chunk = tokio::time::timeout(Duration::from_secs(1), response.chunk()).await.unwrap().unwrap().unwrap();
+        buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = buffer.find("\n\n") {
+            let event: String = buffer.drain(..end + 2).collect();
+            let data = event.lines().find_map(|line| line.strip_prefix("data:")).unwrap();
+            let position: Value = serde_json::from_str(data.trim()).unwrap();
+            assert_eq!((position["y"].as_f64(), position["z"].as_f64()), (Some(0.0), Some(0.0)));
+            xs.push(position["x"].as_f64().unwrap());
+        }
+    }
+    executor.abort();
+
+    assert!(xs.windows(2).all(|pair| pair[0] <= pair[1]), "X went backwards: {:?}", xs);
+    assert!(xs[9] > xs[0], "X never moved: {:?}", xs);
+    assert!(xs[9] < 100.0, "stream should report positions inside the move: {:?}", xs);
+}